@@ -92,8 +92,11 @@ fn main() -> anyhow::Result<()> {
     let s = digin.state()?;
     println!("Digital: {}", s);
 
-    digin.set_on_state_change_handler(|_, s: i32| {
-        println!("State: {}", s);
+    // The handler can keep its own state, like a count of the changes.
+    let mut n = 0;
+    let _handler = digin.set_on_state_change_handler(move |_, s: i32| {
+        n += 1;
+        println!("State: {} [{}]", s, n);
     })?;
 
     // ^C handler wakes up the main thread
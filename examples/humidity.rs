@@ -74,7 +74,7 @@ fn main() -> anyhow::Result<()> {
     let t = sensor.humidity()?;
     println!("Humidity: {}", t);
 
-    sensor.set_on_humidity_change_handler(|_, t: f64| {
+    let _handler = sensor.set_on_humidity_change_handler(|_, t: f64| {
         println!("Humidity: {}", t);
     })?;
 
@@ -83,7 +83,7 @@ fn main() -> anyhow::Result<()> {
     let position = stepper.position()?;
     println!("Stepper position: {}", position);
 
-    stepper.set_on_position_change_handler(|_, position: f64| {
+    let _handler = stepper.set_on_position_change_handler(|_, position: f64| {
         println!("Stepper position: {}", position);
     })?;
 
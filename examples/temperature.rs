@@ -74,15 +74,16 @@ fn main() -> anyhow::Result<()> {
     let t = sensor.temperature()?;
     println!("Temperature: {}", t);
 
-    sensor.set_on_temperature_change_handler(|_, t: f64| {
+    let _handler = sensor.set_on_temperature_change_handler(|_, t: f64| {
         println!("Temperature: {}", t);
     })?;
 
-    // ^C handler wakes up the main thread
+    // ^C handler closes the channels and wakes up the main thread
     ctrlc::set_handler({
         let thr = thread::current();
         move || {
             println!("\nExiting...");
+            let _ = phidget::shutdown();
             thr.unpark();
         }
     })
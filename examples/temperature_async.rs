@@ -0,0 +1,54 @@
+// phidget-rs/examples/temperature_async.rs
+//
+// This file is an example application for the 'phidget-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! Rust Phidget example application to read temperature from an async
+//! application. This uses Tokio, but the async API works with any executor.
+//!
+//! This requires the "async" feature:
+//!
+//! ```text
+//! $ cargo run --example temperature_async --features async
+//! ```
+
+use futures::StreamExt;
+use phidget::{devices::TemperatureSensor, Phidget};
+use std::time::Duration;
+
+// The open/connect timeout
+const TIMEOUT: Duration = phidget::TIMEOUT_DEFAULT;
+
+// --------------------------------------------------------------------------
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    println!("Opening Phidget temperature sensor...");
+    let mut sensor = TemperatureSensor::new();
+
+    // Other tasks keep running while we wait for the sensor to attach.
+    sensor.open_wait_async(TIMEOUT).await?;
+
+    let port = sensor.hub_port()?;
+    println!("Opened on hub port: {}", port);
+
+    let t = sensor.temperature()?;
+    println!("Temperature: {}", t);
+
+    // Read the change events until a ^C
+    let mut temps = sensor.temperature_stream()?;
+    loop {
+        tokio::select! {
+            Some(t) = temps.next() => println!("Temperature: {}", t),
+            _ = tokio::signal::ctrl_c() => break,
+        }
+    }
+
+    println!("\nExiting...");
+    Ok(())
+}
@@ -126,7 +126,7 @@ fn main() -> anyhow::Result<()> {
     let val = (v - offset) * gain;
     println!("{:.4}", val);
 
-    vin.set_on_voltage_change_handler(move |_, v: f64| {
+    let _handler = vin.set_on_voltage_change_handler(move |_, v: f64| {
         let val = (v - offset) * gain;
         println!("{:.4}", val);
     })?;
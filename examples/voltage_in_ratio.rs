@@ -102,7 +102,7 @@ fn main() -> anyhow::Result<()> {
     let val = (v - offset) * gain;
     println!("{:.4}", val);
 
-    vin.set_on_voltage_ratio_change_handler(move |_, v| {
+    let _handler = vin.set_on_voltage_ratio_change_handler(move |_, v| {
         let val = (v - offset) * gain;
         println!("{:.4}", val);
     })?;
@@ -3975,6 +3975,15 @@ extern "C" {
         deviceLabel: *const ::std::os::raw::c_char,
     ) -> PhidgetReturnCode;
 }
+extern "C" {
+    pub fn Phidget_reboot(phid: PhidgetHandle) -> PhidgetReturnCode;
+}
+extern "C" {
+    pub fn Phidget_rebootFirmwareUpgrade(
+        phid: PhidgetHandle,
+        upgradeTimeout: u32,
+    ) -> PhidgetReturnCode;
+}
 extern "C" {
     pub fn Phidget_retain(phid: PhidgetHandle) -> PhidgetReturnCode;
 }
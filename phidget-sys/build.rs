@@ -10,7 +10,7 @@
 
 use std::env;
 
-#[cfg(target_os = "macos")]
+#[cfg(all(target_os = "macos", not(feature = "build-from-source")))]
 fn config_macos() {
     println!("cargo:rustc-link-lib=framework=phidget22");
 
@@ -22,18 +22,216 @@ fn config_macos() {
     }
 }
 
+// Builds the phidget22 library from source, and links to it statically.
+//
+// No source is bundled with the crate. It's the unpacked libphidget22
+// release tarball, found at PHIDGET22_SRC, or in the 'libphidget22'
+// directory of this crate. It is configured and built with its own
+// autotools scripts, so this only works on Unix-like hosts.
+#[cfg(feature = "build-from-source")]
+fn build_from_source() {
+    use std::path::PathBuf;
+
+    println!("cargo:rerun-if-env-changed=PHIDGET22_SRC");
+
+    if env::var("CARGO_CFG_TARGET_OS").as_deref() == Ok("windows") {
+        panic!(
+            "The 'build-from-source' feature uses autotools, which isn't supported on Windows. \
+             Install the phidget22 library instead, and set PHIDGET_ROOT to its location."
+        );
+    }
+
+    let src = env::var("PHIDGET22_SRC")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| {
+            PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap()).join("libphidget22")
+        });
+
+    if !src.join("configure").exists() {
+        panic!(
+            "The 'build-from-source' feature needs the libphidget22 source, which isn't \
+             bundled with the crate, and was not found at '{}'. Download the source tarball \
+             from the Phidgets website, and set PHIDGET22_SRC to where it was unpacked.",
+            src.display()
+        );
+    }
+
+    let dst = autotools::Config::new(&src)
+        .enable_static()
+        .disable_shared()
+        .build();
+
+    println!("cargo:rustc-link-search=native={}/lib", dst.display());
+    link_static();
+}
+
+// Links to the static phidget22 library, along with the system libraries
+// that it depends on.
+fn link_static() {
+    println!("cargo:rustc-link-lib=static=phidget22");
+
+    if env::var("CARGO_CFG_TARGET_OS").as_deref() == Ok("macos") {
+        println!("cargo:rustc-link-lib=framework=CoreFoundation");
+        println!("cargo:rustc-link-lib=framework=IOKit");
+    }
+    else {
+        println!("cargo:rustc-link-lib=usb-1.0");
+        println!("cargo:rustc-link-lib=pthread");
+        println!("cargo:rustc-link-lib=m");
+    }
+}
+
+// Determines whether to link to the system library statically.
+// The PHIDGET_STATIC environment variable, if set, overrides the `static`
+// feature: "1" or "true" to link statically, "0" or "false" to not.
+#[cfg(not(feature = "build-from-source"))]
+fn is_static() -> bool {
+    println!("cargo:rerun-if-env-changed=PHIDGET_STATIC");
+
+    match env::var("PHIDGET_STATIC") {
+        Ok(val) => !matches!(
+            val.trim().to_lowercase().as_str(),
+            "" | "0" | "false" | "no"
+        ),
+        Err(_) => env::var("CARGO_FEATURE_STATIC").is_ok(),
+    }
+}
+
+// Generates the shims that forward each library function to the
+// phidget22 library, loaded at runtime, for the `dlopen` feature.
+//
+// The functions are taken from the bindings for the target. Each shim has
+// the name and signature of the library function, so that the bindings
+// resolve to it, instead of to the library, when the crate is linked.
+fn gen_shims() {
+    use std::{fmt::Write, fs, path::PathBuf};
+
+    let width = env::var("CARGO_CFG_TARGET_POINTER_WIDTH").unwrap();
+    let bindings = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap())
+        .join(format!("bindings/phidget22-{}.rs", width));
+    println!("cargo:rerun-if-changed={}", bindings.display());
+
+    let text = fs::read_to_string(&bindings).expect("failed to read the bindings");
+    let mut shims = String::new();
+
+    for decl in text.split("pub fn ").skip(1) {
+        let open = match decl.find('(') {
+            Some(n) => n,
+            None => continue,
+        };
+        let name = decl[..open].trim();
+
+        // The parameters end at the matching parenthesis, and the return
+        // type at the semicolon after it.
+        let mut depth = 0;
+        let close = decl[open..]
+            .char_indices()
+            .find(|&(_, c)| {
+                match c {
+                    '(' => depth += 1,
+                    ')' => depth -= 1,
+                    _ => {}
+                }
+                depth == 0
+            })
+            .map(|(i, _)| open + i)
+            .expect("unbalanced parameters in the bindings");
+        let params = decl[open + 1..close].trim().trim_end_matches(',');
+        let ret = decl[close + 1..].split(';').next().unwrap().trim();
+
+        // Only the phidget22 functions, and not variadic ones, which
+        // can't be defined in Rust.
+        if !name.starts_with("Phidget") || params.contains("...") {
+            continue;
+        }
+
+        // The argument names, from the parameters split at the top-level
+        // commas.
+        let mut args = Vec::new();
+        let mut depth = 0;
+        let mut start = 0;
+        let flat = params.replace("->", "  ");
+        for (i, c) in flat.char_indices() {
+            match c {
+                '(' | '[' | '<' => depth += 1,
+                ')' | ']' | '>' => depth -= 1,
+                ',' if depth == 0 => {
+                    args.push(&flat[start..i]);
+                    start = i + 1;
+                }
+                _ => {}
+            }
+        }
+        args.push(&flat[start..]);
+        let args = args
+            .iter()
+            .filter_map(|p| p.split(':').next())
+            .map(str::trim)
+            .filter(|a| !a.is_empty())
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        writeln!(
+            shims,
+            r#"
+#[no_mangle]
+pub unsafe extern "C" fn {name}({params}) {ret} {{
+    static SYM: Symbol = Symbol::new(b"{name}\0");
+    match SYM.get() {{
+        Some(addr) => {{
+            let func: unsafe extern "C" fn({params}) {ret} = mem::transmute(addr);
+            func({args})
+        }}
+        None => NotFound::not_found(),
+    }}
+}}"#
+        )
+        .unwrap();
+    }
+
+    let out = PathBuf::from(env::var("OUT_DIR").unwrap()).join("shims.rs");
+    fs::write(out, shims).expect("failed to write the shims");
+}
+
 fn main() {
     // TODO: We should eventually find or regenerate the
     //      bindings file for the specific target.
     let tgt = env::var("TARGET").unwrap();
     println!("debug: Building for target: '{}'", tgt);
 
+    // The mock backend defines the library functions itself
+    if env::var("CARGO_FEATURE_MOCK").is_ok() {
+        return;
+    }
+
+    // With `dlopen`, the library is loaded at runtime, not linked
+    if env::var("CARGO_FEATURE_DLOPEN").is_ok() {
+        gen_shims();
+        return;
+    }
+
+    #[cfg(feature = "build-from-source")]
+    build_from_source();
+
+    #[cfg(not(feature = "build-from-source"))]
+    link_system();
+}
+
+// Links to the phidget22 library installed on the system.
+#[cfg(not(feature = "build-from-source"))]
+fn link_system() {
     // PHIDGET_ROOT should be set to point to the installation directory of phidgets
     // (e.g. C:\Program Files\Phidgets\Phidget22)
+    println!("cargo:rerun-if-env-changed=PHIDGET_ROOT");
     if let Ok(phidget_libs) = env::var("PHIDGET_ROOT") {
         println!("cargo:rustc-link-search={}", phidget_libs);
     }
 
+    if is_static() {
+        link_static();
+        return;
+    }
+
     #[cfg(target_os = "macos")]
     config_macos();
 
@@ -0,0 +1,189 @@
+// phidget-sys/src/dlopen.rs
+//
+// This file is part of the 'phidget-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+//! Loading the phidget22 library at runtime.
+//!
+//! With the `dlopen` feature, the crate doesn't link to the phidget22
+//! library. Instead, each library function is defined as a shim that
+//! loads the library the first time that any of them is called, and then
+//! forwards the call to it. So an application builds and starts whether or
+//! not the library is installed.
+//!
+//! If the library can't be loaded, the functions that return a
+//! `PhidgetReturnCode` return `EPHIDGET_LIBRARYNOTFOUND`, and the reason
+//! is available from `load_error()`. If it's loaded, but is too old to
+//! have a function, that function returns `EPHIDGET_UNSUPPORTED`.
+//!
+//! The library is searched for by its usual name for the platform, unless
+//! the `PHIDGET22_LIBRARY` environment variable is set to its path.
+//!
+
+use super::*;
+use libloading::Library;
+use std::{
+    env,
+    os::raw::{c_char, c_int, c_void},
+    ptr,
+    sync::{
+        atomic::{AtomicPtr, Ordering},
+        Mutex,
+    },
+};
+
+/// The code returned by the library functions when the library couldn't
+/// be loaded. This is outside the range of the phidget22 return codes.
+pub const PhidgetReturnCode_EPHIDGET_LIBRARYNOTFOUND: PhidgetReturnCode = 0x8000_0001;
+
+// The names to search for the library, if the path isn't in the
+// environment.
+#[cfg(target_os = "macos")]
+const LIBRARY_NAMES: &[&str] = &[
+    "/Library/Frameworks/Phidget22.framework/Phidget22",
+    "libphidget22.dylib",
+];
+
+#[cfg(windows)]
+const LIBRARY_NAMES: &[&str] = &["phidget22.dll"];
+
+#[cfg(not(any(target_os = "macos", windows)))]
+const LIBRARY_NAMES: &[&str] = &["libphidget22.so.0", "libphidget22.so"];
+
+// The library, once loaded, or the reason it couldn't be.
+// It's never unloaded, since the shims keep pointers into it.
+static LIBRARY: Mutex<Option<Result<Library, String>>> = Mutex::new(None);
+
+// Attempts to load the library from the environment path, or each of the
+// usual names in turn.
+fn open_library() -> Result<Library, String> {
+    if let Ok(path) = env::var("PHIDGET22_LIBRARY") {
+        return unsafe { Library::new(&path) }.map_err(|err| err.to_string());
+    }
+
+    let mut errs = Vec::new();
+    for name in LIBRARY_NAMES {
+        match unsafe { Library::new(name) } {
+            Ok(lib) => return Ok(lib),
+            Err(err) => errs.push(err.to_string()),
+        }
+    }
+    Err(errs.join("; "))
+}
+
+// Runs the function on the library, loading it on the first call.
+fn with_library<F, T>(f: F) -> Result<T, String>
+where
+    F: FnOnce(&Library) -> T,
+{
+    let mut lib = LIBRARY.lock().unwrap_or_else(|err| err.into_inner());
+    match lib.get_or_insert_with(open_library) {
+        Ok(lib) => Ok(f(lib)),
+        Err(err) => Err(err.clone()),
+    }
+}
+
+/// Loads the phidget22 library, if it isn't already loaded.
+///
+/// This is done automatically by the first call to any library function,
+/// but can be called at startup to determine whether the library is
+/// available. The result is remembered; a library that couldn't be
+/// loaded isn't searched for again.
+pub fn load() -> Result<(), String> {
+    with_library(|_| ())
+}
+
+/// Gets the reason the library couldn't be loaded, if it was attempted
+/// and failed.
+pub fn load_error() -> Option<String> {
+    let lib = LIBRARY.lock().unwrap_or_else(|err| err.into_inner());
+    match &*lib {
+        Some(Err(err)) => Some(err.clone()),
+        _ => None,
+    }
+}
+
+// A library function, resolved the first time it's called.
+struct Symbol {
+    // The nul-terminated name of the function
+    name: &'static [u8],
+    // The address of the function, once resolved
+    addr: AtomicPtr<c_void>,
+}
+
+impl Symbol {
+    // Creates an unresolved symbol for the function.
+    const fn new(name: &'static [u8]) -> Self {
+        Self {
+            name,
+            addr: AtomicPtr::new(ptr::null_mut()),
+        }
+    }
+
+    // Gets the address of the function, resolving it if necessary.
+    // Returns None if the library or the function couldn't be found.
+    fn get(&self) -> Option<*mut c_void> {
+        let addr = self.addr.load(Ordering::Acquire);
+        if !addr.is_null() {
+            return Some(addr);
+        }
+
+        let addr = with_library(|lib| unsafe {
+            lib.get::<*mut c_void>(self.name)
+                .map(|sym| *sym)
+                .unwrap_or(ptr::null_mut())
+        })
+        .ok()
+        .filter(|addr| !addr.is_null())?;
+
+        self.addr.store(addr, Ordering::Release);
+        Some(addr)
+    }
+}
+
+// The value returned by a shim when the function couldn't be found, for
+// each of the return types of the library functions.
+trait NotFound {
+    fn not_found() -> Self;
+}
+
+impl NotFound for PhidgetReturnCode {
+    fn not_found() -> Self {
+        if load_error().is_some() {
+            PhidgetReturnCode_EPHIDGET_LIBRARYNOTFOUND
+        }
+        else {
+            PhidgetReturnCode_EPHIDGET_UNSUPPORTED
+        }
+    }
+}
+
+impl NotFound for c_int {
+    fn not_found() -> Self {
+        0
+    }
+}
+
+impl NotFound for *const c_char {
+    fn not_found() -> Self {
+        ptr::null()
+    }
+}
+
+impl NotFound for () {
+    fn not_found() -> Self {}
+}
+
+// The shims for the library functions, generated from the bindings.
+#[cfg(not(feature = "mock"))]
+#[allow(clippy::missing_safety_doc, clippy::too_many_arguments)]
+mod shims {
+    use super::*;
+    use std::mem;
+
+    include!(concat!(env!("OUT_DIR"), "/shims.rs"));
+}
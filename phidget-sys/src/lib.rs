@@ -23,3 +23,15 @@ include!(concat!(
     env!("CARGO_MANIFEST_DIR"),
     "/bindings/phidget22-32.rs"
 ));
+
+/// The version of the phidget22 library that the bindings were generated
+/// from, as (major, minor).
+pub const PHIDGET22_BINDINGS_VERSION: (u32, u32) = (1, 14);
+
+// An in-memory simulation of the library
+#[cfg(feature = "mock")]
+pub mod mock;
+
+// Loading the library at runtime, instead of linking to it
+#[cfg(feature = "dlopen")]
+pub mod dlopen;
@@ -0,0 +1,3183 @@
+// phidget-sys/src/mock.rs
+//
+// This file is part of the 'phidget-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+//! An in-memory simulation of the phidget22 library.
+//!
+//! With the `mock` feature, the crate doesn't link to the phidget22
+//! library. Instead, this module defines the library functions itself,
+//! simulating channels in memory. Channels attach as soon as they are
+//! opened, and the functions in this module can be used to detach and
+//! re-attach them, feed them values, and raise errors. The events are
+//! delivered to the handlers that were registered through the normal
+//! API, on the calling thread.
+//!
+//! Only the functions used by the Rust wrapper are defined. Properties
+//! are stored by name, and reading a property that was never set on an
+//! attached channel returns `EPHIDGET_UNKNOWNVAL`.
+//!
+
+#![allow(clippy::missing_safety_doc)]
+
+use super::*;
+use std::{
+    collections::BTreeMap,
+    ffi::{CStr, CString},
+    mem,
+    os::raw::{c_char, c_int, c_void},
+    ptr,
+    sync::{Mutex, MutexGuard},
+};
+
+/// The serial number given to a simulated channel that attaches without
+/// one being set.
+pub const MOCK_SERIAL_NUMBER: i32 = 100000;
+
+// The properties that can be read and written while a channel is not
+// attached, along with their defaults.
+const ADDRESS_PROPS: &[(&str, f64)] = &[
+    ("DeviceSerialNumber", -1.0),
+    ("HubPort", -1.0),
+    ("Channel", -1.0),
+    ("IsHubPortDevice", 0.0),
+    ("IsLocal", 0.0),
+    ("IsRemote", 0.0),
+];
+
+// The defaults for properties common to attached channels.
+const DEVICE_PROPS: &[(&str, f64)] = &[
+    ("DataInterval", 250.0),
+    ("MinDataInterval", 20.0),
+    ("MaxDataInterval", 60000.0),
+    ("DataRate", 4.0),
+    ("MinDataRate", 1.0 / 60.0),
+    ("MaxDataRate", 50.0),
+    ("DeviceID", Phidget_DeviceID_PHIDID_UNKNOWN as f64),
+    ("DeviceVersion", 100.0),
+    ("HubPortSpeed", 100_000.0),
+    ("MaxHubPortSpeed", 1_000_000.0),
+    ("HubPortSupportsSetSpeed", 1.0),
+    ("HubPortSupportsAutoSetSpeed", 0.0),
+    ("VINTDeviceSupportsSetSpeed", 1.0),
+    ("VINTDeviceSupportsAutoSetSpeed", 0.0),
+    ("MaxVINTDeviceSpeed", 400_000.0),
+];
+
+// The property holding the value of each class of channel, and the event
+// that reports changes to it.
+const VALUE_PROPS: &[(Phidget_ChannelClass, &str, &str)] = &[
+    (
+        Phidget_ChannelClass_PHIDCHCLASS_CAPACITIVETOUCH,
+        "TouchValue",
+        "Touch",
+    ),
+    (
+        Phidget_ChannelClass_PHIDCHCLASS_CURRENTINPUT,
+        "Current",
+        "CurrentChange",
+    ),
+    (
+        Phidget_ChannelClass_PHIDCHCLASS_DIGITALINPUT,
+        "State",
+        "StateChange",
+    ),
+    (
+        Phidget_ChannelClass_PHIDCHCLASS_FREQUENCYCOUNTER,
+        "Frequency",
+        "FrequencyChange",
+    ),
+    (
+        Phidget_ChannelClass_PHIDCHCLASS_HUMIDITYSENSOR,
+        "Humidity",
+        "HumidityChange",
+    ),
+    (
+        Phidget_ChannelClass_PHIDCHCLASS_LIGHTSENSOR,
+        "Illuminance",
+        "IlluminanceChange",
+    ),
+    (Phidget_ChannelClass_PHIDCHCLASS_PHSENSOR, "PH", "PHChange"),
+    (
+        Phidget_ChannelClass_PHIDCHCLASS_PRESSURESENSOR,
+        "Pressure",
+        "PressureChange",
+    ),
+    (
+        Phidget_ChannelClass_PHIDCHCLASS_RCSERVO,
+        "Position",
+        "PositionChange",
+    ),
+    (
+        Phidget_ChannelClass_PHIDCHCLASS_STEPPER,
+        "Position",
+        "PositionChange",
+    ),
+    (
+        Phidget_ChannelClass_PHIDCHCLASS_TEMPERATURESENSOR,
+        "Temperature",
+        "TemperatureChange",
+    ),
+    (
+        Phidget_ChannelClass_PHIDCHCLASS_VOLTAGEINPUT,
+        "Voltage",
+        "VoltageChange",
+    ),
+    (
+        Phidget_ChannelClass_PHIDCHCLASS_VOLTAGERATIOINPUT,
+        "VoltageRatio",
+        "VoltageRatioChange",
+    ),
+];
+
+// Calls a low-level handler with a value.
+type FireFn = unsafe fn(usize, usize, usize, f64);
+
+// A handler registered on a simulated channel
+#[derive(Clone, Copy)]
+struct Handler {
+    event: &'static str,
+    fptr: usize,
+    ctx: usize,
+    fire: FireFn,
+}
+
+// A simulated channel
+struct Channel {
+    key: usize,
+    class: Phidget_ChannelClass,
+    refs: u32,
+    // The key of the parent device, once it's been asked for, which holds
+    // a reference to it
+    parent: usize,
+    open: bool,
+    attached: bool,
+    props: Vec<(&'static str, f64)>,
+    label: CString,
+    server: CString,
+    handlers: Vec<Handler>,
+    // The entries, for a dictionary
+    dict: BTreeMap<String, String>,
+}
+
+impl Channel {
+    fn prop(&self, name: &str) -> Option<f64> {
+        self.props.iter().find(|(k, _)| *k == name).map(|(_, v)| *v)
+    }
+
+    fn set_prop(&mut self, name: &'static str, val: f64) {
+        match self.props.iter_mut().find(|(k, _)| *k == name) {
+            Some((_, v)) => *v = val,
+            None => self.props.push((name, val)),
+        }
+    }
+
+    fn handler(&self, event: &str) -> Option<Handler> {
+        self.handlers.iter().find(|h| h.event == event).copied()
+    }
+}
+
+// The simulated channels
+static CHANNELS: Mutex<Vec<Channel>> = Mutex::new(Vec::new());
+
+fn channels() -> MutexGuard<'static, Vec<Channel>> {
+    CHANNELS.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+// Runs the function on the channel with the key, if it exists.
+fn with_channel<F, T>(key: usize, f: F) -> Result<T, PhidgetReturnCode>
+where
+    F: FnOnce(&mut Channel) -> Result<T, PhidgetReturnCode>,
+{
+    let mut chans = channels();
+    match chans.iter_mut().find(|ch| ch.key == key) {
+        Some(ch) => f(ch),
+        None => Err(PhidgetReturnCode_EPHIDGET_INVALIDARG),
+    }
+}
+
+// Converts a result to a return code.
+fn rc(res: Result<(), PhidgetReturnCode>) -> PhidgetReturnCode {
+    res.err().unwrap_or(PhidgetReturnCode_EPHIDGET_OK)
+}
+
+// Creates a new channel, returning its key.
+fn create(class: Phidget_ChannelClass) -> usize {
+    // The allocation gives each channel a unique address for its handle
+    let key = Box::into_raw(Box::new(0u64)) as usize;
+    channels().push(Channel {
+        key,
+        class,
+        refs: 1,
+        parent: 0,
+        open: false,
+        attached: false,
+        props: Vec::new(),
+        label: CString::default(),
+        server: CString::default(),
+        handlers: Vec::new(),
+        dict: BTreeMap::new(),
+    });
+    key
+}
+
+// Drops a reference to a channel, deleting it when there are none left.
+fn release(key: usize) -> PhidgetReturnCode {
+    let mut chans = channels();
+    let Some(i) = chans.iter().position(|ch| ch.key == key)
+    else {
+        return PhidgetReturnCode_EPHIDGET_INVALIDARG;
+    };
+    chans[i].refs -= 1;
+    if chans[i].refs == 0 {
+        let ch = chans.swap_remove(i);
+        drop(chans);
+        drop(unsafe { Box::from_raw(key as *mut u64) });
+        if ch.parent != 0 {
+            release(ch.parent);
+        }
+    }
+    PhidgetReturnCode_EPHIDGET_OK
+}
+
+// Reads a property from a channel.
+fn get_prop(key: usize, name: &str) -> Result<f64, PhidgetReturnCode> {
+    with_channel(key, |ch| {
+        if let Some((_, def)) = ADDRESS_PROPS.iter().find(|(k, _)| *k == name) {
+            return Ok(ch.prop(name).unwrap_or(*def));
+        }
+        if !ch.attached {
+            return Err(PhidgetReturnCode_EPHIDGET_NOTATTACHED);
+        }
+        ch.prop(name)
+            .or_else(|| {
+                DEVICE_PROPS
+                    .iter()
+                    .find(|(k, _)| *k == name)
+                    .map(|(_, v)| *v)
+            })
+            .ok_or(PhidgetReturnCode_EPHIDGET_UNKNOWNVAL)
+    })
+}
+
+// Writes a property to a channel.
+fn set_prop(key: usize, name: &'static str, val: f64) -> PhidgetReturnCode {
+    rc(with_channel(key, |ch| {
+        if !ch.attached && !ADDRESS_PROPS.iter().any(|(k, _)| *k == name) {
+            return Err(PhidgetReturnCode_EPHIDGET_NOTATTACHED);
+        }
+        ch.set_prop(name, val);
+        Ok(())
+    }))
+}
+
+// Sets or clears the handler for an event on a channel.
+fn set_handler(
+    key: usize,
+    event: &'static str,
+    fptr: usize,
+    ctx: usize,
+    fire: FireFn,
+) -> PhidgetReturnCode {
+    rc(with_channel(key, |ch| {
+        ch.handlers.retain(|h| h.event != event);
+        if fptr != 0 {
+            ch.handlers.push(Handler {
+                event,
+                fptr,
+                ctx,
+                fire,
+            });
+        }
+        Ok(())
+    }))
+}
+
+// Calls the handler for an event on a channel, if there is one.
+// This must be called without holding the lock on the channels.
+fn fire(key: usize, event: &str, val: f64) {
+    if let Ok(Some(h)) = with_channel(key, |ch| Ok(ch.handler(event))) {
+        unsafe { (h.fire)(key, h.fptr, h.ctx, val) };
+    }
+}
+
+// A simulated manager
+struct Manager {
+    key: usize,
+    open: bool,
+    // The attach and detach handlers, as (fptr, ctx)
+    attach: (usize, usize),
+    detach: (usize, usize),
+}
+
+// The simulated managers
+static MANAGERS: Mutex<Vec<Manager>> = Mutex::new(Vec::new());
+
+fn managers() -> MutexGuard<'static, Vec<Manager>> {
+    MANAGERS.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+// Reports a channel attaching or detaching to the open managers.
+// This must be called without holding the lock on the channels.
+fn notify_managers(key: usize, attached: bool) {
+    let handlers: Vec<_> = managers()
+        .iter()
+        .filter(|m| m.open)
+        .map(|m| (m.key, if attached { m.attach } else { m.detach }))
+        .collect();
+
+    for (mgr, (fptr, ctx)) in handlers {
+        let f = unsafe { mem::transmute::<usize, PhidgetManager_OnAttachCallback>(fptr) };
+        if let Some(f) = f {
+            unsafe {
+                f(
+                    mgr as PhidgetManagerHandle,
+                    ctx as *mut c_void,
+                    key as PhidgetHandle,
+                )
+            };
+        }
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+// Simulation control
+
+/// Attaches an open channel to a simulated device, and fires its attach
+/// event.
+///
+/// Any addressing properties that were left as "any" are given concrete
+/// values. This has no effect if the channel is not open, or already
+/// attached.
+pub fn attach(phid: PhidgetHandle) {
+    let key = phid as usize;
+    let res = with_channel(key, |ch| {
+        if !ch.open || ch.attached {
+            return Ok(false);
+        }
+        ch.attached = true;
+        if ch.prop("DeviceSerialNumber").unwrap_or(-1.0) < 0.0 {
+            ch.set_prop("DeviceSerialNumber", MOCK_SERIAL_NUMBER as f64);
+        }
+        if ch.prop("HubPort").unwrap_or(-1.0) < 0.0 {
+            ch.set_prop("HubPort", 0.0);
+        }
+        if ch.prop("Channel").unwrap_or(-1.0) < 0.0 {
+            ch.set_prop("Channel", 0.0);
+        }
+        // Simulated devices are local, unless the channel asked for remote
+        if ch.prop("IsRemote").unwrap_or(0.0) == 0.0 {
+            ch.set_prop("IsLocal", 1.0);
+        }
+        Ok(true)
+    });
+    if let Ok(true) = res {
+        fire(key, "Attach", 0.0);
+        notify_managers(key, true);
+    }
+}
+
+/// Detaches a channel from its simulated device, and fires its detach
+/// event.
+///
+/// The channel stays open, and can be attached again with `attach()`.
+pub fn detach(phid: PhidgetHandle) {
+    let key = phid as usize;
+    let res = with_channel(key, |ch| {
+        let was_attached = ch.attached;
+        ch.attached = false;
+        Ok(was_attached)
+    });
+    if let Ok(true) = res {
+        notify_managers(key, false);
+        fire(key, "Detach", 0.0);
+    }
+}
+
+/// Sets the value of an attached channel, and fires its change event.
+///
+/// The value is the main reading of the channel, such as the temperature
+/// of a temperature sensor. Boolean states are set as 0.0 or 1.0. This
+/// returns `EPHIDGET_UNSUPPORTED` if the class of channel doesn't have a
+/// value that can be simulated.
+///
+/// As with a device, a value that differs from the last one by less than
+/// the change trigger of the channel is dropped, without an event.
+pub fn set_value(phid: PhidgetHandle, val: f64) -> PhidgetReturnCode {
+    let key = phid as usize;
+    let res = with_channel(key, |ch| {
+        let Some((_, prop, event)) = VALUE_PROPS.iter().find(|(cls, ..)| *cls == ch.class)
+        else {
+            return Err(PhidgetReturnCode_EPHIDGET_UNSUPPORTED);
+        };
+        if !ch.attached {
+            return Err(PhidgetReturnCode_EPHIDGET_NOTATTACHED);
+        }
+        let trigger = ch.prop(&format!("{prop}ChangeTrigger")).unwrap_or(0.0);
+        if matches!(ch.prop(prop), Some(last) if (val - last).abs() < trigger) {
+            return Ok(None);
+        }
+        ch.set_prop(prop, val);
+        Ok(Some(*event))
+    });
+    match res {
+        Ok(Some(event)) => {
+            fire(key, event, val);
+            PhidgetReturnCode_EPHIDGET_OK
+        }
+        Ok(None) => PhidgetReturnCode_EPHIDGET_OK,
+        Err(rc) => rc,
+    }
+}
+
+/// Sets any property of a channel by name, without firing an event.
+///
+/// The name is the one used in the library functions, like "MinVoltage"
+/// for `PhidgetVoltageInput_getMinVoltage()`.
+pub fn set_property(phid: PhidgetHandle, name: &'static str, val: f64) -> PhidgetReturnCode {
+    rc(with_channel(phid as usize, |ch| {
+        ch.set_prop(name, val);
+        Ok(())
+    }))
+}
+
+/// Sets the acceleration of an attached accelerometer channel, and fires
+/// its change event.
+pub fn acceleration(phid: PhidgetHandle, accel: [f64; 3], timestamp: f64) -> PhidgetReturnCode {
+    let res = with_channel(phid as usize, |ch| {
+        if ch.class != Phidget_ChannelClass_PHIDCHCLASS_ACCELEROMETER {
+            return Err(PhidgetReturnCode_EPHIDGET_UNSUPPORTED);
+        }
+        if !ch.attached {
+            return Err(PhidgetReturnCode_EPHIDGET_NOTATTACHED);
+        }
+        ch.set_prop("AccelerationX", accel[0]);
+        ch.set_prop("AccelerationY", accel[1]);
+        ch.set_prop("AccelerationZ", accel[2]);
+        Ok(ch.handler("AccelerationChange"))
+    });
+    match res {
+        Ok(Some(h)) => {
+            let f = unsafe {
+                mem::transmute::<usize, PhidgetAccelerometer_OnAccelerationChangeCallback>(h.fptr)
+            };
+            if let Some(f) = f {
+                unsafe {
+                    f(
+                        phid as PhidgetAccelerometerHandle,
+                        h.ctx as *mut c_void,
+                        accel.as_ptr(),
+                        timestamp,
+                    )
+                };
+            }
+            PhidgetReturnCode_EPHIDGET_OK
+        }
+        Ok(None) => PhidgetReturnCode_EPHIDGET_OK,
+        Err(rc) => rc,
+    }
+}
+
+/// Moves an attached encoder channel, and fires its position change event.
+///
+/// The change is added to the position of the channel.
+pub fn position_change(
+    phid: PhidgetHandle,
+    change: i32,
+    time_change: f64,
+    index_triggered: bool,
+) -> PhidgetReturnCode {
+    let res = with_channel(phid as usize, |ch| {
+        if ch.class != Phidget_ChannelClass_PHIDCHCLASS_ENCODER {
+            return Err(PhidgetReturnCode_EPHIDGET_UNSUPPORTED);
+        }
+        if !ch.attached {
+            return Err(PhidgetReturnCode_EPHIDGET_NOTATTACHED);
+        }
+        let pos = ch.prop("Position").unwrap_or(0.0) + change as f64;
+        ch.set_prop("Position", pos);
+        if index_triggered {
+            ch.set_prop("IndexPosition", pos);
+        }
+        Ok(ch.handler("PositionChange"))
+    });
+    match res {
+        Ok(Some(h)) => {
+            let f =
+                unsafe { mem::transmute::<usize, PhidgetEncoder_OnPositionChangeCallback>(h.fptr) };
+            if let Some(f) = f {
+                unsafe {
+                    f(
+                        phid as PhidgetEncoderHandle,
+                        h.ctx as *mut c_void,
+                        change as c_int,
+                        time_change,
+                        index_triggered as c_int,
+                    )
+                };
+            }
+            PhidgetReturnCode_EPHIDGET_OK
+        }
+        Ok(None) => PhidgetReturnCode_EPHIDGET_OK,
+        Err(rc) => rc,
+    }
+}
+
+/// Sets the heading and velocity of an attached GPS channel, and fires its
+/// heading change event.
+pub fn heading(phid: PhidgetHandle, heading: f64, velocity: f64) -> PhidgetReturnCode {
+    let res = with_channel(phid as usize, |ch| {
+        if ch.class != Phidget_ChannelClass_PHIDCHCLASS_GPS {
+            return Err(PhidgetReturnCode_EPHIDGET_UNSUPPORTED);
+        }
+        if !ch.attached {
+            return Err(PhidgetReturnCode_EPHIDGET_NOTATTACHED);
+        }
+        ch.set_prop("Heading", heading);
+        ch.set_prop("Velocity", velocity);
+        Ok(ch.handler("HeadingChange"))
+    });
+    match res {
+        Ok(Some(h)) => {
+            let f = unsafe { mem::transmute::<usize, PhidgetGPS_OnHeadingChangeCallback>(h.fptr) };
+            if let Some(f) = f {
+                unsafe {
+                    f(
+                        phid as PhidgetGPSHandle,
+                        h.ctx as *mut c_void,
+                        heading,
+                        velocity,
+                    )
+                };
+            }
+            PhidgetReturnCode_EPHIDGET_OK
+        }
+        Ok(None) => PhidgetReturnCode_EPHIDGET_OK,
+        Err(rc) => rc,
+    }
+}
+
+/// Fires a data event on an attached spatial channel, with the
+/// acceleration, angular rate, and magnetic field of a sample.
+pub fn spatial_data(
+    phid: PhidgetHandle,
+    acceleration: [f64; 3],
+    angular_rate: [f64; 3],
+    magnetic_field: [f64; 3],
+    timestamp: f64,
+) -> PhidgetReturnCode {
+    let res = with_channel(phid as usize, |ch| {
+        if ch.class != Phidget_ChannelClass_PHIDCHCLASS_SPATIAL {
+            return Err(PhidgetReturnCode_EPHIDGET_UNSUPPORTED);
+        }
+        if !ch.attached {
+            return Err(PhidgetReturnCode_EPHIDGET_NOTATTACHED);
+        }
+        Ok(ch.handler("SpatialData"))
+    });
+    match res {
+        Ok(Some(h)) => {
+            let f =
+                unsafe { mem::transmute::<usize, PhidgetSpatial_OnSpatialDataCallback>(h.fptr) };
+            if let Some(f) = f {
+                unsafe {
+                    f(
+                        phid as PhidgetSpatialHandle,
+                        h.ctx as *mut c_void,
+                        acceleration.as_ptr(),
+                        angular_rate.as_ptr(),
+                        magnetic_field.as_ptr(),
+                        timestamp,
+                    )
+                };
+            }
+            PhidgetReturnCode_EPHIDGET_OK
+        }
+        Ok(None) => PhidgetReturnCode_EPHIDGET_OK,
+        Err(rc) => rc,
+    }
+}
+
+/// Fires an error event on a channel.
+pub fn raise_error(phid: PhidgetHandle, code: Phidget_ErrorEventCode, descr: &CStr) {
+    if let Ok(Some(h)) = with_channel(phid as usize, |ch| Ok(ch.handler("Error"))) {
+        let f = unsafe { mem::transmute::<usize, Phidget_OnErrorCallback>(h.fptr) };
+        if let Some(f) = f {
+            unsafe { f(phid, h.ctx as *mut c_void, code, descr.as_ptr()) };
+        }
+    }
+}
+
+/// Gets the number of simulated channels that have not been deleted.
+pub fn channel_count() -> usize {
+    channels().len()
+}
+
+/// Gets the number of references to a simulated channel, or zero if it
+/// has been deleted.
+pub fn ref_count(phid: PhidgetHandle) -> u32 {
+    with_channel(phid as usize, |ch| Ok(ch.refs)).unwrap_or(0)
+}
+
+/////////////////////////////////////////////////////////////////////////////
+// Function definitions
+
+macro_rules! mock_create {
+    ($name:ident, $h:ty, $cls:ident) => {
+        #[no_mangle]
+        pub unsafe extern "C" fn $name(ch: *mut $h) -> PhidgetReturnCode {
+            if ch.is_null() {
+                return PhidgetReturnCode_EPHIDGET_INVALIDARG;
+            }
+            *ch = create($cls) as $h;
+            PhidgetReturnCode_EPHIDGET_OK
+        }
+    };
+}
+
+macro_rules! mock_delete {
+    ($name:ident, $h:ty) => {
+        #[no_mangle]
+        pub unsafe extern "C" fn $name(ch: *mut $h) -> PhidgetReturnCode {
+            if ch.is_null() {
+                return PhidgetReturnCode_EPHIDGET_INVALIDARG;
+            }
+            let rc = release(*ch as usize);
+            *ch = ptr::null_mut();
+            rc
+        }
+    };
+}
+
+macro_rules! mock_get {
+    ($name:ident, $h:ty, $t:ty, $prop:literal) => {
+        #[no_mangle]
+        pub unsafe extern "C" fn $name(ch: $h, val: *mut $t) -> PhidgetReturnCode {
+            if val.is_null() {
+                return PhidgetReturnCode_EPHIDGET_INVALIDARG;
+            }
+            match get_prop(ch as usize, $prop) {
+                Ok(v) => {
+                    *val = v as $t;
+                    PhidgetReturnCode_EPHIDGET_OK
+                }
+                Err(rc) => rc,
+            }
+        }
+    };
+}
+
+macro_rules! mock_get_axes {
+    ($name:ident, $h:ty, $prop:literal) => {
+        #[no_mangle]
+        pub unsafe extern "C" fn $name(ch: $h, val: *mut [f64; 3]) -> PhidgetReturnCode {
+            if val.is_null() {
+                return PhidgetReturnCode_EPHIDGET_INVALIDARG;
+            }
+            let axes = [
+                concat!($prop, "X"),
+                concat!($prop, "Y"),
+                concat!($prop, "Z"),
+            ];
+            for (i, prop) in axes.into_iter().enumerate() {
+                match get_prop(ch as usize, prop) {
+                    Ok(v) => (*val)[i] = v,
+                    Err(rc) => return rc,
+                }
+            }
+            PhidgetReturnCode_EPHIDGET_OK
+        }
+    };
+}
+
+macro_rules! mock_set {
+    ($name:ident, $h:ty, $t:ty, $prop:literal) => {
+        #[no_mangle]
+        pub unsafe extern "C" fn $name(ch: $h, val: $t) -> PhidgetReturnCode {
+            set_prop(ch as usize, $prop, val as f64)
+        }
+    };
+}
+
+macro_rules! mock_handler {
+    ($name:ident, $h:ty, $cb:ty, $ev:literal) => {
+        #[no_mangle]
+        pub unsafe extern "C" fn $name(ch: $h, fptr: $cb, ctx: *mut c_void) -> PhidgetReturnCode {
+            unsafe fn fire(ch: usize, fptr: usize, ctx: usize, _: f64) {
+                if let Some(f) = mem::transmute::<usize, $cb>(fptr) {
+                    f(ch as $h, ctx as *mut c_void);
+                }
+            }
+            set_handler(
+                ch as usize,
+                $ev,
+                fptr.map_or(0, |f| f as usize),
+                ctx as usize,
+                fire,
+            )
+        }
+    };
+    ($name:ident, $h:ty, $cb:ty, $ev:literal, $t:ty) => {
+        #[no_mangle]
+        pub unsafe extern "C" fn $name(ch: $h, fptr: $cb, ctx: *mut c_void) -> PhidgetReturnCode {
+            unsafe fn fire(ch: usize, fptr: usize, ctx: usize, val: f64) {
+                if let Some(f) = mem::transmute::<usize, $cb>(fptr) {
+                    f(ch as $h, ctx as *mut c_void, val as $t);
+                }
+            }
+            set_handler(
+                ch as usize,
+                $ev,
+                fptr.map_or(0, |f| f as usize),
+                ctx as usize,
+                fire,
+            )
+        }
+    };
+}
+
+macro_rules! mock_ok {
+    ($name:ident($($arg:ident: $t:ty),*)) => {
+        #[no_mangle]
+        #[allow(unused_variables)]
+        pub unsafe extern "C" fn $name($($arg: $t),*) -> PhidgetReturnCode {
+            PhidgetReturnCode_EPHIDGET_OK
+        }
+    };
+}
+
+macro_rules! mock_unsupported {
+    ($name:ident($($arg:ident: $t:ty),*)) => {
+        #[no_mangle]
+        #[allow(unused_variables)]
+        pub unsafe extern "C" fn $name($($arg: $t),*) -> PhidgetReturnCode {
+            PhidgetReturnCode_EPHIDGET_UNSUPPORTED
+        }
+    };
+}
+
+// Gets the name of a channel class as a C string.
+fn class_name(class: Phidget_ChannelClass) -> &'static [u8] {
+    match class {
+        Phidget_ChannelClass_PHIDCHCLASS_ACCELEROMETER => b"PhidgetAccelerometer\0",
+        Phidget_ChannelClass_PHIDCHCLASS_CAPACITIVETOUCH => b"PhidgetCapacitiveTouch\0",
+        Phidget_ChannelClass_PHIDCHCLASS_CURRENTINPUT => b"PhidgetCurrentInput\0",
+        Phidget_ChannelClass_PHIDCHCLASS_DICTIONARY => b"PhidgetDictionary\0",
+        Phidget_ChannelClass_PHIDCHCLASS_DIGITALINPUT => b"PhidgetDigitalInput\0",
+        Phidget_ChannelClass_PHIDCHCLASS_DIGITALOUTPUT => b"PhidgetDigitalOutput\0",
+        Phidget_ChannelClass_PHIDCHCLASS_ENCODER => b"PhidgetEncoder\0",
+        Phidget_ChannelClass_PHIDCHCLASS_FREQUENCYCOUNTER => b"PhidgetFrequencyCounter\0",
+        Phidget_ChannelClass_PHIDCHCLASS_GPS => b"PhidgetGPS\0",
+        Phidget_ChannelClass_PHIDCHCLASS_HUB => b"PhidgetHub\0",
+        Phidget_ChannelClass_PHIDCHCLASS_HUMIDITYSENSOR => b"PhidgetHumiditySensor\0",
+        Phidget_ChannelClass_PHIDCHCLASS_LCD => b"PhidgetLCD\0",
+        Phidget_ChannelClass_PHIDCHCLASS_LIGHTSENSOR => b"PhidgetLightSensor\0",
+        Phidget_ChannelClass_PHIDCHCLASS_MAGNETOMETER => b"PhidgetMagnetometer\0",
+        Phidget_ChannelClass_PHIDCHCLASS_PHSENSOR => b"PhidgetPHSensor\0",
+        Phidget_ChannelClass_PHIDCHCLASS_PRESSURESENSOR => b"PhidgetPressureSensor\0",
+        Phidget_ChannelClass_PHIDCHCLASS_RCSERVO => b"PhidgetRCServo\0",
+        Phidget_ChannelClass_PHIDCHCLASS_SPATIAL => b"PhidgetSpatial\0",
+        Phidget_ChannelClass_PHIDCHCLASS_STEPPER => b"PhidgetStepper\0",
+        Phidget_ChannelClass_PHIDCHCLASS_TEMPERATURESENSOR => b"PhidgetTemperatureSensor\0",
+        Phidget_ChannelClass_PHIDCHCLASS_VOLTAGEINPUT => b"PhidgetVoltageInput\0",
+        Phidget_ChannelClass_PHIDCHCLASS_VOLTAGEOUTPUT => b"PhidgetVoltageOutput\0",
+        Phidget_ChannelClass_PHIDCHCLASS_VOLTAGERATIOINPUT => b"PhidgetVoltageRatioInput\0",
+        _ => b"Phidget\0",
+    }
+}
+
+// Writes a static C string to an output parameter.
+unsafe fn put_str(out: *mut *const c_char, s: &'static [u8]) -> PhidgetReturnCode {
+    if out.is_null() {
+        return PhidgetReturnCode_EPHIDGET_INVALIDARG;
+    }
+    *out = s.as_ptr() as *const c_char;
+    PhidgetReturnCode_EPHIDGET_OK
+}
+
+// ----- Library -----
+
+#[no_mangle]
+pub unsafe extern "C" fn Phidget_getLibraryVersion(
+    libraryVersion: *mut *const c_char,
+) -> PhidgetReturnCode {
+    put_str(libraryVersion, b"Phidget22 - Mock\0")
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Phidget_getLibraryVersionNumber(
+    libraryVersion: *mut *const c_char,
+) -> PhidgetReturnCode {
+    put_str(libraryVersion, b"1.14-mock\0")
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Phidget_getErrorDescription(
+    errorCode: PhidgetReturnCode,
+    errorString: *mut *const c_char,
+) -> PhidgetReturnCode {
+    let s: &'static [u8] = match errorCode {
+        PhidgetReturnCode_EPHIDGET_OK => b"Success\0",
+        PhidgetReturnCode_EPHIDGET_TIMEOUT => b"Timed Out\0",
+        PhidgetReturnCode_EPHIDGET_UNSUPPORTED => b"Not Supported\0",
+        PhidgetReturnCode_EPHIDGET_INVALIDARG => b"Invalid Argument\0",
+        PhidgetReturnCode_EPHIDGET_UNKNOWNVAL => b"Unknown or Invalid Value\0",
+        PhidgetReturnCode_EPHIDGET_NOTATTACHED => b"Device not Attached\0",
+        _ => b"Error\0",
+    };
+    put_str(errorString, s)
+}
+
+// ----- Library -----
+
+// The simulation isn't reset, since it's shared by all the tests
+mock_ok!(Phidget_resetLibrary());
+mock_ok!(Phidget_finalize(flags: c_int));
+
+// ----- Channels -----
+
+#[no_mangle]
+pub unsafe extern "C" fn Phidget_retain(phid: PhidgetHandle) -> PhidgetReturnCode {
+    rc(with_channel(phid as usize, |ch| {
+        ch.refs += 1;
+        Ok(())
+    }))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Phidget_release(phid: *mut PhidgetHandle) -> PhidgetReturnCode {
+    if phid.is_null() {
+        return PhidgetReturnCode_EPHIDGET_INVALIDARG;
+    }
+    let rc = release(*phid as usize);
+    *phid = ptr::null_mut();
+    rc
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Phidget_open(phid: PhidgetHandle) -> PhidgetReturnCode {
+    let res = with_channel(phid as usize, |ch| {
+        ch.open = true;
+        Ok(())
+    });
+    if res.is_ok() {
+        attach(phid);
+    }
+    rc(res)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Phidget_openWaitForAttachment(
+    phid: PhidgetHandle,
+    _timeoutMs: u32,
+) -> PhidgetReturnCode {
+    Phidget_open(phid)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Phidget_close(phid: PhidgetHandle) -> PhidgetReturnCode {
+    detach(phid);
+    rc(with_channel(phid as usize, |ch| {
+        ch.open = false;
+        Ok(())
+    }))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Phidget_reboot(phid: PhidgetHandle) -> PhidgetReturnCode {
+    // The device goes away, and comes back
+    if with_channel(phid as usize, |ch| Ok(ch.attached)) != Ok(true) {
+        return PhidgetReturnCode_EPHIDGET_NOTATTACHED;
+    }
+    detach(phid);
+    attach(phid);
+    PhidgetReturnCode_EPHIDGET_OK
+}
+
+mock_unsupported!(Phidget_rebootFirmwareUpgrade(phid: PhidgetHandle, upgradeTimeout: u32));
+
+// Like the library, this returns a new reference to the parent, which the
+// caller must release.
+#[no_mangle]
+pub unsafe extern "C" fn Phidget_getParent(
+    phid: PhidgetHandle,
+    parent: *mut PhidgetHandle,
+) -> PhidgetReturnCode {
+    if parent.is_null() {
+        return PhidgetReturnCode_EPHIDGET_INVALIDARG;
+    }
+    let res = with_channel(phid as usize, |ch| match ch.attached {
+        true => Ok(ch.parent),
+        false => Err(PhidgetReturnCode_EPHIDGET_NOTATTACHED),
+    });
+    let key = match res {
+        Ok(0) => {
+            // The simulated parent device is always attached
+            let key = create(Phidget_ChannelClass_PHIDCHCLASS_HUB);
+            let _ = with_channel(key, |ch| {
+                ch.attached = true;
+                Ok(())
+            });
+            let _ = with_channel(phid as usize, |ch| {
+                ch.parent = key;
+                Ok(())
+            });
+            key
+        }
+        Ok(key) => key,
+        Err(rc) => return rc,
+    };
+    *parent = key as PhidgetHandle;
+    Phidget_retain(*parent)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Phidget_getAttached(
+    phid: PhidgetHandle,
+    attached: *mut c_int,
+) -> PhidgetReturnCode {
+    if attached.is_null() {
+        return PhidgetReturnCode_EPHIDGET_INVALIDARG;
+    }
+    rc(with_channel(phid as usize, |ch| {
+        *attached = ch.attached as c_int;
+        Ok(())
+    }))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Phidget_getIsOpen(
+    phid: PhidgetHandle,
+    status: *mut c_int,
+) -> PhidgetReturnCode {
+    if status.is_null() {
+        return PhidgetReturnCode_EPHIDGET_INVALIDARG;
+    }
+    rc(with_channel(phid as usize, |ch| {
+        *status = ch.open as c_int;
+        Ok(())
+    }))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Phidget_getChannelClass(
+    phid: PhidgetHandle,
+    channelClass: *mut Phidget_ChannelClass,
+) -> PhidgetReturnCode {
+    if channelClass.is_null() {
+        return PhidgetReturnCode_EPHIDGET_INVALIDARG;
+    }
+    rc(with_channel(phid as usize, |ch| {
+        *channelClass = ch.class;
+        Ok(())
+    }))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Phidget_getChannelClassName(
+    phid: PhidgetHandle,
+    channelClassName: *mut *const c_char,
+) -> PhidgetReturnCode {
+    match with_channel(phid as usize, |ch| Ok(ch.class)) {
+        Ok(cls) => put_str(channelClassName, class_name(cls)),
+        Err(rc) => rc,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Phidget_getChannelName(
+    phid: PhidgetHandle,
+    channelName: *mut *const c_char,
+) -> PhidgetReturnCode {
+    Phidget_getChannelClassName(phid, channelName)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Phidget_getChannelSubclass(
+    phid: PhidgetHandle,
+    channelSubclass: *mut Phidget_ChannelSubclass,
+) -> PhidgetReturnCode {
+    if channelSubclass.is_null() {
+        return PhidgetReturnCode_EPHIDGET_INVALIDARG;
+    }
+    rc(with_channel(phid as usize, |_| {
+        *channelSubclass = Phidget_ChannelSubclass_PHIDCHSUBCLASS_NONE;
+        Ok(())
+    }))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Phidget_getDeviceClass(
+    phid: PhidgetHandle,
+    deviceClass: *mut Phidget_DeviceClass,
+) -> PhidgetReturnCode {
+    if deviceClass.is_null() {
+        return PhidgetReturnCode_EPHIDGET_INVALIDARG;
+    }
+    rc(with_channel(phid as usize, |_| {
+        *deviceClass = Phidget_DeviceClass_PHIDCLASS_VINT;
+        Ok(())
+    }))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Phidget_getDeviceClassName(
+    phid: PhidgetHandle,
+    deviceClassName: *mut *const c_char,
+) -> PhidgetReturnCode {
+    match with_channel(phid as usize, |_| Ok(())) {
+        Ok(_) => put_str(deviceClassName, b"PhidgetVINT\0"),
+        Err(rc) => rc,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Phidget_getDeviceLabel(
+    phid: PhidgetHandle,
+    deviceLabel: *mut *const c_char,
+) -> PhidgetReturnCode {
+    if deviceLabel.is_null() {
+        return PhidgetReturnCode_EPHIDGET_INVALIDARG;
+    }
+    // The string stays valid until the label is changed
+    rc(with_channel(phid as usize, |ch| {
+        *deviceLabel = ch.label.as_ptr();
+        Ok(())
+    }))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Phidget_setDeviceLabel(
+    phid: PhidgetHandle,
+    deviceLabel: *const c_char,
+) -> PhidgetReturnCode {
+    if deviceLabel.is_null() {
+        return PhidgetReturnCode_EPHIDGET_INVALIDARG;
+    }
+    let label = CStr::from_ptr(deviceLabel).to_owned();
+    rc(with_channel(phid as usize, |ch| {
+        ch.label = label;
+        Ok(())
+    }))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Phidget_getServerName(
+    phid: PhidgetHandle,
+    serverName: *mut *const c_char,
+) -> PhidgetReturnCode {
+    if serverName.is_null() {
+        return PhidgetReturnCode_EPHIDGET_INVALIDARG;
+    }
+    // The string stays valid until the server name is changed
+    rc(with_channel(phid as usize, |ch| {
+        *serverName = ch.server.as_ptr();
+        Ok(())
+    }))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Phidget_setServerName(
+    phid: PhidgetHandle,
+    serverName: *const c_char,
+) -> PhidgetReturnCode {
+    let server = match serverName.is_null() {
+        true => CString::default(),
+        false => CStr::from_ptr(serverName).to_owned(),
+    };
+    rc(with_channel(phid as usize, |ch| {
+        ch.server = server;
+        Ok(())
+    }))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Phidget_writeDeviceLabel(
+    phid: PhidgetHandle,
+    deviceLabel: *const c_char,
+) -> PhidgetReturnCode {
+    if deviceLabel.is_null() {
+        return PhidgetReturnCode_EPHIDGET_INVALIDARG;
+    }
+    let label = CStr::from_ptr(deviceLabel).to_owned();
+    rc(with_channel(phid as usize, |ch| {
+        if !ch.attached {
+            return Err(PhidgetReturnCode_EPHIDGET_NOTATTACHED);
+        }
+        ch.label = label;
+        Ok(())
+    }))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Phidget_getDeviceName(
+    phid: PhidgetHandle,
+    deviceName: *mut *const c_char,
+) -> PhidgetReturnCode {
+    match with_channel(phid as usize, |ch| Ok(ch.attached)) {
+        Ok(true) => put_str(deviceName, b"Simulated Phidget\0"),
+        Ok(false) => PhidgetReturnCode_EPHIDGET_NOTATTACHED,
+        Err(rc) => rc,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Phidget_getDeviceSKU(
+    phid: PhidgetHandle,
+    deviceSKU: *mut *const c_char,
+) -> PhidgetReturnCode {
+    match with_channel(phid as usize, |ch| Ok(ch.attached)) {
+        Ok(true) => put_str(deviceSKU, b"MOCK0000_0\0"),
+        Ok(false) => PhidgetReturnCode_EPHIDGET_NOTATTACHED,
+        Err(rc) => rc,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Phidget_getDeviceChannelCount(
+    phid: PhidgetHandle,
+    cls: Phidget_ChannelClass,
+    count: *mut u32,
+) -> PhidgetReturnCode {
+    if count.is_null() {
+        return PhidgetReturnCode_EPHIDGET_INVALIDARG;
+    }
+    rc(with_channel(phid as usize, |ch| {
+        *count = (cls == ch.class) as u32;
+        Ok(())
+    }))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Phidget_setOnErrorHandler(
+    phid: PhidgetHandle,
+    fptr: Phidget_OnErrorCallback,
+    ctx: *mut c_void,
+) -> PhidgetReturnCode {
+    // Errors are fired by `raise_error()`, which has the full signature
+    unsafe fn fire(_: usize, _: usize, _: usize, _: f64) {}
+    set_handler(
+        phid as usize,
+        "Error",
+        fptr.map_or(0, |f| f as usize),
+        ctx as usize,
+        fire,
+    )
+}
+
+mock_create!(
+    PhidgetAccelerometer_create,
+    PhidgetAccelerometerHandle,
+    Phidget_ChannelClass_PHIDCHCLASS_ACCELEROMETER
+);
+mock_delete!(PhidgetAccelerometer_delete, PhidgetAccelerometerHandle);
+mock_get_axes!(
+    PhidgetAccelerometer_getAcceleration,
+    PhidgetAccelerometerHandle,
+    "Acceleration"
+);
+mock_get_axes!(
+    PhidgetAccelerometer_getMinAcceleration,
+    PhidgetAccelerometerHandle,
+    "MinAcceleration"
+);
+mock_get_axes!(
+    PhidgetAccelerometer_getMaxAcceleration,
+    PhidgetAccelerometerHandle,
+    "MaxAcceleration"
+);
+mock_get!(
+    PhidgetAccelerometer_getAxisCount,
+    PhidgetAccelerometerHandle,
+    c_int,
+    "AxisCount"
+);
+mock_get!(
+    PhidgetAccelerometer_getAccelerationChangeTrigger,
+    PhidgetAccelerometerHandle,
+    f64,
+    "AccelerationChangeTrigger"
+);
+mock_get!(
+    PhidgetAccelerometer_getMinAccelerationChangeTrigger,
+    PhidgetAccelerometerHandle,
+    f64,
+    "MinAccelerationChangeTrigger"
+);
+mock_get!(
+    PhidgetAccelerometer_getMaxAccelerationChangeTrigger,
+    PhidgetAccelerometerHandle,
+    f64,
+    "MaxAccelerationChangeTrigger"
+);
+mock_get!(
+    PhidgetAccelerometer_getHeatingEnabled,
+    PhidgetAccelerometerHandle,
+    c_int,
+    "HeatingEnabled"
+);
+mock_set!(
+    PhidgetAccelerometer_setAccelerationChangeTrigger,
+    PhidgetAccelerometerHandle,
+    f64,
+    "AccelerationChangeTrigger"
+);
+mock_set!(
+    PhidgetAccelerometer_setHeatingEnabled,
+    PhidgetAccelerometerHandle,
+    c_int,
+    "HeatingEnabled"
+);
+
+// The acceleration is fired by `acceleration()`, since it isn't a single value
+#[no_mangle]
+pub unsafe extern "C" fn PhidgetAccelerometer_setOnAccelerationChangeHandler(
+    ch: PhidgetAccelerometerHandle,
+    fptr: PhidgetAccelerometer_OnAccelerationChangeCallback,
+    ctx: *mut c_void,
+) -> PhidgetReturnCode {
+    unsafe fn fire(_: usize, _: usize, _: usize, _: f64) {}
+    set_handler(
+        ch as usize,
+        "AccelerationChange",
+        fptr.map_or(0, |f| f as usize),
+        ctx as usize,
+        fire,
+    )
+}
+mock_create!(
+    PhidgetCapacitiveTouch_create,
+    PhidgetCapacitiveTouchHandle,
+    Phidget_ChannelClass_PHIDCHCLASS_CAPACITIVETOUCH
+);
+mock_delete!(PhidgetCapacitiveTouch_delete, PhidgetCapacitiveTouchHandle);
+mock_get!(
+    PhidgetCapacitiveTouch_getTouchValue,
+    PhidgetCapacitiveTouchHandle,
+    f64,
+    "TouchValue"
+);
+mock_get!(
+    PhidgetCapacitiveTouch_getIsTouched,
+    PhidgetCapacitiveTouchHandle,
+    c_int,
+    "IsTouched"
+);
+mock_get!(
+    PhidgetCapacitiveTouch_getMinTouchValue,
+    PhidgetCapacitiveTouchHandle,
+    f64,
+    "MinTouchValue"
+);
+mock_get!(
+    PhidgetCapacitiveTouch_getMaxTouchValue,
+    PhidgetCapacitiveTouchHandle,
+    f64,
+    "MaxTouchValue"
+);
+mock_get!(
+    PhidgetCapacitiveTouch_getTouchValueChangeTrigger,
+    PhidgetCapacitiveTouchHandle,
+    f64,
+    "TouchValueChangeTrigger"
+);
+mock_get!(
+    PhidgetCapacitiveTouch_getMinTouchValueChangeTrigger,
+    PhidgetCapacitiveTouchHandle,
+    f64,
+    "MinTouchValueChangeTrigger"
+);
+mock_get!(
+    PhidgetCapacitiveTouch_getMaxTouchValueChangeTrigger,
+    PhidgetCapacitiveTouchHandle,
+    f64,
+    "MaxTouchValueChangeTrigger"
+);
+mock_get!(
+    PhidgetCapacitiveTouch_getSensitivity,
+    PhidgetCapacitiveTouchHandle,
+    f64,
+    "Sensitivity"
+);
+mock_get!(
+    PhidgetCapacitiveTouch_getMinSensitivity,
+    PhidgetCapacitiveTouchHandle,
+    f64,
+    "MinSensitivity"
+);
+mock_get!(
+    PhidgetCapacitiveTouch_getMaxSensitivity,
+    PhidgetCapacitiveTouchHandle,
+    f64,
+    "MaxSensitivity"
+);
+mock_set!(
+    PhidgetCapacitiveTouch_setTouchValueChangeTrigger,
+    PhidgetCapacitiveTouchHandle,
+    f64,
+    "TouchValueChangeTrigger"
+);
+mock_set!(
+    PhidgetCapacitiveTouch_setSensitivity,
+    PhidgetCapacitiveTouchHandle,
+    f64,
+    "Sensitivity"
+);
+mock_handler!(
+    PhidgetCapacitiveTouch_setOnTouchHandler,
+    PhidgetCapacitiveTouchHandle,
+    PhidgetCapacitiveTouch_OnTouchCallback,
+    "Touch",
+    f64
+);
+mock_handler!(
+    PhidgetCapacitiveTouch_setOnTouchEndHandler,
+    PhidgetCapacitiveTouchHandle,
+    PhidgetCapacitiveTouch_OnTouchEndCallback,
+    "TouchEnd"
+);
+mock_create!(
+    PhidgetCurrentInput_create,
+    PhidgetCurrentInputHandle,
+    Phidget_ChannelClass_PHIDCHCLASS_CURRENTINPUT
+);
+mock_delete!(PhidgetCurrentInput_delete, PhidgetCurrentInputHandle);
+mock_get!(
+    PhidgetCurrentInput_getCurrent,
+    PhidgetCurrentInputHandle,
+    f64,
+    "Current"
+);
+mock_get!(
+    PhidgetCurrentInput_getMinCurrent,
+    PhidgetCurrentInputHandle,
+    f64,
+    "MinCurrent"
+);
+mock_get!(
+    PhidgetCurrentInput_getMaxCurrent,
+    PhidgetCurrentInputHandle,
+    f64,
+    "MaxCurrent"
+);
+mock_get!(
+    PhidgetCurrentInput_getCurrentChangeTrigger,
+    PhidgetCurrentInputHandle,
+    f64,
+    "CurrentChangeTrigger"
+);
+mock_get!(
+    PhidgetCurrentInput_getPowerSupply,
+    PhidgetCurrentInputHandle,
+    Phidget_PowerSupply,
+    "PowerSupply"
+);
+mock_set!(
+    PhidgetCurrentInput_setCurrentChangeTrigger,
+    PhidgetCurrentInputHandle,
+    f64,
+    "CurrentChangeTrigger"
+);
+mock_set!(
+    PhidgetCurrentInput_setPowerSupply,
+    PhidgetCurrentInputHandle,
+    Phidget_PowerSupply,
+    "PowerSupply"
+);
+mock_handler!(
+    PhidgetCurrentInput_setOnCurrentChangeHandler,
+    PhidgetCurrentInputHandle,
+    PhidgetCurrentInput_OnCurrentChangeCallback,
+    "CurrentChange",
+    f64
+);
+mock_create!(
+    PhidgetDictionary_create,
+    PhidgetDictionaryHandle,
+    Phidget_ChannelClass_PHIDCHCLASS_DICTIONARY
+);
+mock_delete!(PhidgetDictionary_delete, PhidgetDictionaryHandle);
+
+// Runs the function on the entries of an attached dictionary.
+unsafe fn with_dict<F>(ch: PhidgetDictionaryHandle, f: F) -> PhidgetReturnCode
+where
+    F: FnOnce(&mut BTreeMap<String, String>) -> Result<(), PhidgetReturnCode>,
+{
+    rc(with_channel(ch as usize, |ch| {
+        if !ch.attached {
+            return Err(PhidgetReturnCode_EPHIDGET_NOTATTACHED);
+        }
+        f(&mut ch.dict)
+    }))
+}
+
+// Copies a string to an output buffer, if it fits.
+unsafe fn put_buf(out: *mut c_char, len: usize, s: &str) -> Result<(), PhidgetReturnCode> {
+    if out.is_null() || s.len() >= len {
+        return Err(PhidgetReturnCode_EPHIDGET_NOSPC);
+    }
+    ptr::copy_nonoverlapping(s.as_ptr() as *const c_char, out, s.len());
+    *out.add(s.len()) = 0;
+    Ok(())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn PhidgetDictionary_get(
+    ch: PhidgetDictionaryHandle,
+    key: *const c_char,
+    value: *mut c_char,
+    len: usize,
+) -> PhidgetReturnCode {
+    let key = CStr::from_ptr(key).to_string_lossy();
+    with_dict(ch, |dict| {
+        let val = dict.get(&*key).ok_or(PhidgetReturnCode_EPHIDGET_NOENT)?;
+        put_buf(value, len, val)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn PhidgetDictionary_set(
+    ch: PhidgetDictionaryHandle,
+    key: *const c_char,
+    value: *const c_char,
+) -> PhidgetReturnCode {
+    let key = CStr::from_ptr(key).to_string_lossy().into_owned();
+    let value = CStr::from_ptr(value).to_string_lossy().into_owned();
+    with_dict(ch, |dict| {
+        dict.insert(key, value);
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn PhidgetDictionary_add(
+    ch: PhidgetDictionaryHandle,
+    key: *const c_char,
+    value: *const c_char,
+) -> PhidgetReturnCode {
+    let key = CStr::from_ptr(key).to_string_lossy().into_owned();
+    let value = CStr::from_ptr(value).to_string_lossy().into_owned();
+    with_dict(ch, |dict| {
+        if dict.contains_key(&key) {
+            return Err(PhidgetReturnCode_EPHIDGET_EXIST);
+        }
+        dict.insert(key, value);
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn PhidgetDictionary_update(
+    ch: PhidgetDictionaryHandle,
+    key: *const c_char,
+    value: *const c_char,
+) -> PhidgetReturnCode {
+    let key = CStr::from_ptr(key).to_string_lossy();
+    let value = CStr::from_ptr(value).to_string_lossy().into_owned();
+    with_dict(ch, |dict| {
+        let val = dict
+            .get_mut(&*key)
+            .ok_or(PhidgetReturnCode_EPHIDGET_NOENT)?;
+        *val = value;
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn PhidgetDictionary_remove(
+    ch: PhidgetDictionaryHandle,
+    key: *const c_char,
+) -> PhidgetReturnCode {
+    let key = CStr::from_ptr(key).to_string_lossy();
+    with_dict(ch, |dict| {
+        dict.remove(&*key)
+            .map(|_| ())
+            .ok_or(PhidgetReturnCode_EPHIDGET_NOENT)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn PhidgetDictionary_removeAll(
+    ch: PhidgetDictionaryHandle,
+) -> PhidgetReturnCode {
+    with_dict(ch, |dict| {
+        dict.clear();
+        Ok(())
+    })
+}
+
+// Lists the keys from the start key on, one per line, as many as fit in
+// the buffer, like the library does
+#[no_mangle]
+pub unsafe extern "C" fn PhidgetDictionary_scan(
+    ch: PhidgetDictionaryHandle,
+    start: *const c_char,
+    list: *mut c_char,
+    len: usize,
+) -> PhidgetReturnCode {
+    let start = if start.is_null() {
+        String::new()
+    }
+    else {
+        CStr::from_ptr(start).to_string_lossy().into_owned()
+    };
+    with_dict(ch, |dict| {
+        let mut keys = String::new();
+        for key in dict.range(start..).map(|(k, _)| k) {
+            if keys.len() + key.len() + 1 >= len {
+                break;
+            }
+            keys.push_str(key);
+            keys.push('\n');
+        }
+        put_buf(list, len, &keys)
+    })
+}
+mock_create!(
+    PhidgetDigitalInput_create,
+    PhidgetDigitalInputHandle,
+    Phidget_ChannelClass_PHIDCHCLASS_DIGITALINPUT
+);
+mock_delete!(PhidgetDigitalInput_delete, PhidgetDigitalInputHandle);
+mock_get!(
+    PhidgetDigitalInput_getInputMode,
+    PhidgetDigitalInputHandle,
+    Phidget_InputMode,
+    "InputMode"
+);
+mock_get!(
+    PhidgetDigitalInput_getPowerSupply,
+    PhidgetDigitalInputHandle,
+    Phidget_PowerSupply,
+    "PowerSupply"
+);
+mock_get!(
+    PhidgetDigitalInput_getState,
+    PhidgetDigitalInputHandle,
+    c_int,
+    "State"
+);
+mock_set!(
+    PhidgetDigitalInput_setInputMode,
+    PhidgetDigitalInputHandle,
+    Phidget_InputMode,
+    "InputMode"
+);
+mock_handler!(
+    PhidgetDigitalInput_setOnStateChangeHandler,
+    PhidgetDigitalInputHandle,
+    PhidgetDigitalInput_OnStateChangeCallback,
+    "StateChange",
+    c_int
+);
+mock_set!(
+    PhidgetDigitalInput_setPowerSupply,
+    PhidgetDigitalInputHandle,
+    Phidget_PowerSupply,
+    "PowerSupply"
+);
+mock_create!(
+    PhidgetDigitalOutput_create,
+    PhidgetDigitalOutputHandle,
+    Phidget_ChannelClass_PHIDCHCLASS_DIGITALOUTPUT
+);
+mock_delete!(PhidgetDigitalOutput_delete, PhidgetDigitalOutputHandle);
+mock_ok!(PhidgetDigitalOutput_enableFailsafe(ch: PhidgetDigitalOutputHandle, failsafeTime: u32));
+mock_get!(
+    PhidgetDigitalOutput_getDutyCycle,
+    PhidgetDigitalOutputHandle,
+    f64,
+    "DutyCycle"
+);
+mock_get!(
+    PhidgetDigitalOutput_getFrequency,
+    PhidgetDigitalOutputHandle,
+    f64,
+    "Frequency"
+);
+mock_get!(
+    PhidgetDigitalOutput_getLEDCurrentLimit,
+    PhidgetDigitalOutputHandle,
+    f64,
+    "LEDCurrentLimit"
+);
+mock_get!(
+    PhidgetDigitalOutput_getLEDForwardVoltage,
+    PhidgetDigitalOutputHandle,
+    PhidgetDigitalOutput_LEDForwardVoltage,
+    "LEDForwardVoltage"
+);
+mock_get!(
+    PhidgetDigitalOutput_getMaxDutyCycle,
+    PhidgetDigitalOutputHandle,
+    f64,
+    "MaxDutyCycle"
+);
+mock_get!(
+    PhidgetDigitalOutput_getMaxFailsafeTime,
+    PhidgetDigitalOutputHandle,
+    u32,
+    "MaxFailsafeTime"
+);
+mock_get!(
+    PhidgetDigitalOutput_getMaxFrequency,
+    PhidgetDigitalOutputHandle,
+    f64,
+    "MaxFrequency"
+);
+mock_get!(
+    PhidgetDigitalOutput_getMaxLEDCurrentLimit,
+    PhidgetDigitalOutputHandle,
+    f64,
+    "MaxLEDCurrentLimit"
+);
+mock_get!(
+    PhidgetDigitalOutput_getMinDutyCycle,
+    PhidgetDigitalOutputHandle,
+    f64,
+    "MinDutyCycle"
+);
+mock_get!(
+    PhidgetDigitalOutput_getMinFailsafeTime,
+    PhidgetDigitalOutputHandle,
+    u32,
+    "MinFailsafeTime"
+);
+mock_get!(
+    PhidgetDigitalOutput_getMinFrequency,
+    PhidgetDigitalOutputHandle,
+    f64,
+    "MinFrequency"
+);
+mock_get!(
+    PhidgetDigitalOutput_getMinLEDCurrentLimit,
+    PhidgetDigitalOutputHandle,
+    f64,
+    "MinLEDCurrentLimit"
+);
+mock_get!(
+    PhidgetDigitalOutput_getState,
+    PhidgetDigitalOutputHandle,
+    c_int,
+    "State"
+);
+mock_ok!(PhidgetDigitalOutput_resetFailsafe(ch: PhidgetDigitalOutputHandle));
+mock_set!(
+    PhidgetDigitalOutput_setDutyCycle,
+    PhidgetDigitalOutputHandle,
+    f64,
+    "DutyCycle"
+);
+mock_set!(
+    PhidgetDigitalOutput_setFrequency,
+    PhidgetDigitalOutputHandle,
+    f64,
+    "Frequency"
+);
+mock_set!(
+    PhidgetDigitalOutput_setLEDCurrentLimit,
+    PhidgetDigitalOutputHandle,
+    f64,
+    "LEDCurrentLimit"
+);
+mock_set!(
+    PhidgetDigitalOutput_setLEDForwardVoltage,
+    PhidgetDigitalOutputHandle,
+    PhidgetDigitalOutput_LEDForwardVoltage,
+    "LEDForwardVoltage"
+);
+mock_set!(
+    PhidgetDigitalOutput_setState,
+    PhidgetDigitalOutputHandle,
+    c_int,
+    "State"
+);
+mock_create!(
+    PhidgetEncoder_create,
+    PhidgetEncoderHandle,
+    Phidget_ChannelClass_PHIDCHCLASS_ENCODER
+);
+mock_delete!(PhidgetEncoder_delete, PhidgetEncoderHandle);
+mock_get!(
+    PhidgetEncoder_getPosition,
+    PhidgetEncoderHandle,
+    i64,
+    "Position"
+);
+mock_get!(
+    PhidgetEncoder_getIndexPosition,
+    PhidgetEncoderHandle,
+    i64,
+    "IndexPosition"
+);
+mock_get!(
+    PhidgetEncoder_getEnabled,
+    PhidgetEncoderHandle,
+    c_int,
+    "Enabled"
+);
+mock_get!(
+    PhidgetEncoder_getPositionChangeTrigger,
+    PhidgetEncoderHandle,
+    u32,
+    "PositionChangeTrigger"
+);
+mock_get!(
+    PhidgetEncoder_getMinPositionChangeTrigger,
+    PhidgetEncoderHandle,
+    u32,
+    "MinPositionChangeTrigger"
+);
+mock_get!(
+    PhidgetEncoder_getMaxPositionChangeTrigger,
+    PhidgetEncoderHandle,
+    u32,
+    "MaxPositionChangeTrigger"
+);
+mock_get!(
+    PhidgetEncoder_getIOMode,
+    PhidgetEncoderHandle,
+    Phidget_EncoderIOMode,
+    "IOMode"
+);
+mock_set!(
+    PhidgetEncoder_setPosition,
+    PhidgetEncoderHandle,
+    i64,
+    "Position"
+);
+mock_set!(
+    PhidgetEncoder_setEnabled,
+    PhidgetEncoderHandle,
+    c_int,
+    "Enabled"
+);
+mock_set!(
+    PhidgetEncoder_setPositionChangeTrigger,
+    PhidgetEncoderHandle,
+    u32,
+    "PositionChangeTrigger"
+);
+mock_set!(
+    PhidgetEncoder_setIOMode,
+    PhidgetEncoderHandle,
+    Phidget_EncoderIOMode,
+    "IOMode"
+);
+
+// The position change is fired by `position_change()`, since it has three
+// values
+#[no_mangle]
+pub unsafe extern "C" fn PhidgetEncoder_setOnPositionChangeHandler(
+    ch: PhidgetEncoderHandle,
+    fptr: PhidgetEncoder_OnPositionChangeCallback,
+    ctx: *mut c_void,
+) -> PhidgetReturnCode {
+    unsafe fn fire(_: usize, _: usize, _: usize, _: f64) {}
+    set_handler(
+        ch as usize,
+        "PositionChange",
+        fptr.map_or(0, |f| f as usize),
+        ctx as usize,
+        fire,
+    )
+}
+mock_create!(
+    PhidgetFrequencyCounter_create,
+    PhidgetFrequencyCounterHandle,
+    Phidget_ChannelClass_PHIDCHCLASS_FREQUENCYCOUNTER
+);
+mock_delete!(
+    PhidgetFrequencyCounter_delete,
+    PhidgetFrequencyCounterHandle
+);
+mock_get!(
+    PhidgetFrequencyCounter_getFrequency,
+    PhidgetFrequencyCounterHandle,
+    f64,
+    "Frequency"
+);
+mock_get!(
+    PhidgetFrequencyCounter_getMaxFrequency,
+    PhidgetFrequencyCounterHandle,
+    f64,
+    "MaxFrequency"
+);
+mock_get!(
+    PhidgetFrequencyCounter_getCount,
+    PhidgetFrequencyCounterHandle,
+    u64,
+    "Count"
+);
+mock_get!(
+    PhidgetFrequencyCounter_getTimeElapsed,
+    PhidgetFrequencyCounterHandle,
+    f64,
+    "TimeElapsed"
+);
+mock_get!(
+    PhidgetFrequencyCounter_getEnabled,
+    PhidgetFrequencyCounterHandle,
+    c_int,
+    "Enabled"
+);
+mock_get!(
+    PhidgetFrequencyCounter_getFrequencyCutoff,
+    PhidgetFrequencyCounterHandle,
+    f64,
+    "FrequencyCutoff"
+);
+mock_get!(
+    PhidgetFrequencyCounter_getMinFrequencyCutoff,
+    PhidgetFrequencyCounterHandle,
+    f64,
+    "MinFrequencyCutoff"
+);
+mock_get!(
+    PhidgetFrequencyCounter_getMaxFrequencyCutoff,
+    PhidgetFrequencyCounterHandle,
+    f64,
+    "MaxFrequencyCutoff"
+);
+mock_get!(
+    PhidgetFrequencyCounter_getPowerSupply,
+    PhidgetFrequencyCounterHandle,
+    Phidget_PowerSupply,
+    "PowerSupply"
+);
+mock_set!(
+    PhidgetFrequencyCounter_setEnabled,
+    PhidgetFrequencyCounterHandle,
+    c_int,
+    "Enabled"
+);
+mock_set!(
+    PhidgetFrequencyCounter_setFrequencyCutoff,
+    PhidgetFrequencyCounterHandle,
+    f64,
+    "FrequencyCutoff"
+);
+mock_set!(
+    PhidgetFrequencyCounter_setPowerSupply,
+    PhidgetFrequencyCounterHandle,
+    Phidget_PowerSupply,
+    "PowerSupply"
+);
+
+#[no_mangle]
+pub unsafe extern "C" fn PhidgetFrequencyCounter_reset(
+    ch: PhidgetFrequencyCounterHandle,
+) -> PhidgetReturnCode {
+    rc(with_channel(ch as usize, |ch| {
+        if !ch.attached {
+            return Err(PhidgetReturnCode_EPHIDGET_NOTATTACHED);
+        }
+        ch.set_prop("Count", 0.0);
+        ch.set_prop("TimeElapsed", 0.0);
+        Ok(())
+    }))
+}
+mock_handler!(
+    PhidgetFrequencyCounter_setOnFrequencyChangeHandler,
+    PhidgetFrequencyCounterHandle,
+    PhidgetFrequencyCounter_OnFrequencyChangeCallback,
+    "FrequencyChange",
+    f64
+);
+mock_create!(
+    PhidgetGPS_create,
+    PhidgetGPSHandle,
+    Phidget_ChannelClass_PHIDCHCLASS_GPS
+);
+mock_delete!(PhidgetGPS_delete, PhidgetGPSHandle);
+mock_get!(PhidgetGPS_getLatitude, PhidgetGPSHandle, f64, "Latitude");
+mock_get!(PhidgetGPS_getLongitude, PhidgetGPSHandle, f64, "Longitude");
+mock_get!(PhidgetGPS_getAltitude, PhidgetGPSHandle, f64, "Altitude");
+mock_get!(PhidgetGPS_getHeading, PhidgetGPSHandle, f64, "Heading");
+mock_get!(PhidgetGPS_getVelocity, PhidgetGPSHandle, f64, "Velocity");
+mock_get!(
+    PhidgetGPS_getPositionFixState,
+    PhidgetGPSHandle,
+    c_int,
+    "PositionFixState"
+);
+
+// The heading change is fired by `heading()`, since it has two values
+#[no_mangle]
+pub unsafe extern "C" fn PhidgetGPS_setOnHeadingChangeHandler(
+    ch: PhidgetGPSHandle,
+    fptr: PhidgetGPS_OnHeadingChangeCallback,
+    ctx: *mut c_void,
+) -> PhidgetReturnCode {
+    unsafe fn fire(_: usize, _: usize, _: usize, _: f64) {}
+    set_handler(
+        ch as usize,
+        "HeadingChange",
+        fptr.map_or(0, |f| f as usize),
+        ctx as usize,
+        fire,
+    )
+}
+mock_create!(
+    PhidgetHub_create,
+    PhidgetHubHandle,
+    Phidget_ChannelClass_PHIDCHCLASS_HUB
+);
+mock_delete!(PhidgetHub_delete, PhidgetHubHandle);
+mock_unsupported!(PhidgetHub_getPortMode(ch: PhidgetHubHandle, port: c_int, mode: *mut PhidgetHub_PortMode));
+mock_unsupported!(PhidgetHub_setPortMode(ch: PhidgetHubHandle, port: c_int, mode: PhidgetHub_PortMode));
+mock_create!(
+    PhidgetHumiditySensor_create,
+    PhidgetHumiditySensorHandle,
+    Phidget_ChannelClass_PHIDCHCLASS_HUMIDITYSENSOR
+);
+mock_delete!(PhidgetHumiditySensor_delete, PhidgetHumiditySensorHandle);
+mock_get!(
+    PhidgetHumiditySensor_getHumidity,
+    PhidgetHumiditySensorHandle,
+    f64,
+    "Humidity"
+);
+mock_get!(
+    PhidgetHumiditySensor_getHumidityChangeTrigger,
+    PhidgetHumiditySensorHandle,
+    f64,
+    "HumidityChangeTrigger"
+);
+mock_set!(
+    PhidgetHumiditySensor_setHumidityChangeTrigger,
+    PhidgetHumiditySensorHandle,
+    f64,
+    "HumidityChangeTrigger"
+);
+mock_handler!(
+    PhidgetHumiditySensor_setOnHumidityChangeHandler,
+    PhidgetHumiditySensorHandle,
+    PhidgetHumiditySensor_OnHumidityChangeCallback,
+    "HumidityChange",
+    f64
+);
+// The names of the registered servers
+static SERVERS: Mutex<Vec<CString>> = Mutex::new(Vec::new());
+
+fn servers() -> MutexGuard<'static, Vec<CString>> {
+    SERVERS.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn PhidgetNet_addServer(
+    serverName: *const c_char,
+    address: *const c_char,
+    _port: c_int,
+    _password: *const c_char,
+    _flags: c_int,
+) -> PhidgetReturnCode {
+    if serverName.is_null() || address.is_null() {
+        return PhidgetReturnCode_EPHIDGET_INVALIDARG;
+    }
+    let name = CStr::from_ptr(serverName).to_owned();
+    let mut servers = servers();
+    if servers.contains(&name) {
+        return PhidgetReturnCode_EPHIDGET_DUPLICATE;
+    }
+    servers.push(name);
+    PhidgetReturnCode_EPHIDGET_OK
+}
+
+mock_unsupported!(PhidgetNet_disableServer(serverName: *const c_char, flags: c_int));
+mock_unsupported!(PhidgetNet_disableServerDiscovery(serverType: PhidgetServerType));
+mock_unsupported!(PhidgetNet_enableServer(serverName: *const c_char));
+mock_unsupported!(PhidgetNet_enableServerDiscovery(serverType: PhidgetServerType));
+
+#[no_mangle]
+pub unsafe extern "C" fn PhidgetNet_removeAllServers() -> PhidgetReturnCode {
+    servers().clear();
+    PhidgetReturnCode_EPHIDGET_OK
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn PhidgetNet_removeServer(serverName: *const c_char) -> PhidgetReturnCode {
+    if serverName.is_null() {
+        return PhidgetReturnCode_EPHIDGET_INVALIDARG;
+    }
+    let name = CStr::from_ptr(serverName);
+    let mut servers = servers();
+    match servers.iter().position(|s| s.as_c_str() == name) {
+        Some(i) => {
+            servers.remove(i);
+            PhidgetReturnCode_EPHIDGET_OK
+        }
+        None => PhidgetReturnCode_EPHIDGET_NOENT,
+    }
+}
+
+mock_unsupported!(PhidgetNet_setServerPassword(serverName: *const c_char, password: *const c_char));
+mock_create!(
+    PhidgetLCD_create,
+    PhidgetLCDHandle,
+    Phidget_ChannelClass_PHIDCHCLASS_LCD
+);
+mock_delete!(PhidgetLCD_delete, PhidgetLCDHandle);
+mock_get!(PhidgetLCD_getWidth, PhidgetLCDHandle, c_int, "Width");
+mock_get!(PhidgetLCD_getHeight, PhidgetLCDHandle, c_int, "Height");
+mock_get!(PhidgetLCD_getBacklight, PhidgetLCDHandle, f64, "Backlight");
+mock_get!(PhidgetLCD_getContrast, PhidgetLCDHandle, f64, "Contrast");
+mock_set!(PhidgetLCD_setBacklight, PhidgetLCDHandle, f64, "Backlight");
+mock_set!(PhidgetLCD_setContrast, PhidgetLCDHandle, f64, "Contrast");
+mock_ok!(PhidgetLCD_clear(ch: PhidgetLCDHandle));
+mock_ok!(PhidgetLCD_drawPixel(
+    ch: PhidgetLCDHandle,
+    x: c_int,
+    y: c_int,
+    state: PhidgetLCD_PixelState
+));
+mock_ok!(PhidgetLCD_drawLine(
+    ch: PhidgetLCDHandle,
+    x1: c_int,
+    y1: c_int,
+    x2: c_int,
+    y2: c_int
+));
+mock_ok!(PhidgetLCD_drawRect(
+    ch: PhidgetLCDHandle,
+    x1: c_int,
+    y1: c_int,
+    x2: c_int,
+    y2: c_int,
+    filled: c_int,
+    inverted: c_int
+));
+mock_ok!(PhidgetLCD_writeText(
+    ch: PhidgetLCDHandle,
+    font: PhidgetLCD_Font,
+    x: c_int,
+    y: c_int,
+    text: *const c_char
+));
+mock_ok!(PhidgetLCD_writeBitmap(
+    ch: PhidgetLCDHandle,
+    x: c_int,
+    y: c_int,
+    w: c_int,
+    h: c_int,
+    bitmap: *const u8
+));
+
+// The built-in fonts have their nominal sizes, and the user fonts have none
+#[no_mangle]
+pub unsafe extern "C" fn PhidgetLCD_getFontSize(
+    ch: PhidgetLCDHandle,
+    font: PhidgetLCD_Font,
+    width: *mut c_int,
+    height: *mut c_int,
+) -> PhidgetReturnCode {
+    if width.is_null() || height.is_null() {
+        return PhidgetReturnCode_EPHIDGET_INVALIDARG;
+    }
+    let size = match font {
+        PhidgetLCD_Font_FONT_5x8 => (5, 8),
+        PhidgetLCD_Font_FONT_6x10 => (6, 10),
+        PhidgetLCD_Font_FONT_6x12 => (6, 12),
+        _ => return PhidgetReturnCode_EPHIDGET_UNKNOWNVAL,
+    };
+    let res = with_channel(ch as usize, |ch| {
+        if !ch.attached {
+            return Err(PhidgetReturnCode_EPHIDGET_NOTATTACHED);
+        }
+        Ok(())
+    });
+    if res.is_ok() {
+        (*width, *height) = size;
+    }
+    rc(res)
+}
+
+// A flush only needs the channel to be attached
+#[no_mangle]
+pub unsafe extern "C" fn PhidgetLCD_flush(ch: PhidgetLCDHandle) -> PhidgetReturnCode {
+    rc(with_channel(ch as usize, |ch| {
+        if !ch.attached {
+            return Err(PhidgetReturnCode_EPHIDGET_NOTATTACHED);
+        }
+        Ok(())
+    }))
+}
+mock_create!(
+    PhidgetLightSensor_create,
+    PhidgetLightSensorHandle,
+    Phidget_ChannelClass_PHIDCHCLASS_LIGHTSENSOR
+);
+mock_delete!(PhidgetLightSensor_delete, PhidgetLightSensorHandle);
+mock_get!(
+    PhidgetLightSensor_getIlluminance,
+    PhidgetLightSensorHandle,
+    f64,
+    "Illuminance"
+);
+mock_get!(
+    PhidgetLightSensor_getMinIlluminance,
+    PhidgetLightSensorHandle,
+    f64,
+    "MinIlluminance"
+);
+mock_get!(
+    PhidgetLightSensor_getMaxIlluminance,
+    PhidgetLightSensorHandle,
+    f64,
+    "MaxIlluminance"
+);
+mock_get!(
+    PhidgetLightSensor_getIlluminanceChangeTrigger,
+    PhidgetLightSensorHandle,
+    f64,
+    "IlluminanceChangeTrigger"
+);
+mock_get!(
+    PhidgetLightSensor_getMinIlluminanceChangeTrigger,
+    PhidgetLightSensorHandle,
+    f64,
+    "MinIlluminanceChangeTrigger"
+);
+mock_get!(
+    PhidgetLightSensor_getMaxIlluminanceChangeTrigger,
+    PhidgetLightSensorHandle,
+    f64,
+    "MaxIlluminanceChangeTrigger"
+);
+mock_set!(
+    PhidgetLightSensor_setIlluminanceChangeTrigger,
+    PhidgetLightSensorHandle,
+    f64,
+    "IlluminanceChangeTrigger"
+);
+mock_handler!(
+    PhidgetLightSensor_setOnIlluminanceChangeHandler,
+    PhidgetLightSensorHandle,
+    PhidgetLightSensor_OnIlluminanceChangeCallback,
+    "IlluminanceChange",
+    f64
+);
+mock_create!(
+    PhidgetMagnetometer_create,
+    PhidgetMagnetometerHandle,
+    Phidget_ChannelClass_PHIDCHCLASS_MAGNETOMETER
+);
+mock_delete!(PhidgetMagnetometer_delete, PhidgetMagnetometerHandle);
+mock_get_axes!(
+    PhidgetMagnetometer_getMagneticField,
+    PhidgetMagnetometerHandle,
+    "MagneticField"
+);
+mock_get_axes!(
+    PhidgetMagnetometer_getMinMagneticField,
+    PhidgetMagnetometerHandle,
+    "MinMagneticField"
+);
+mock_get_axes!(
+    PhidgetMagnetometer_getMaxMagneticField,
+    PhidgetMagnetometerHandle,
+    "MaxMagneticField"
+);
+mock_get!(
+    PhidgetMagnetometer_getAxisCount,
+    PhidgetMagnetometerHandle,
+    c_int,
+    "AxisCount"
+);
+mock_get!(
+    PhidgetMagnetometer_getMagneticFieldChangeTrigger,
+    PhidgetMagnetometerHandle,
+    f64,
+    "MagneticFieldChangeTrigger"
+);
+mock_get!(
+    PhidgetMagnetometer_getMinMagneticFieldChangeTrigger,
+    PhidgetMagnetometerHandle,
+    f64,
+    "MinMagneticFieldChangeTrigger"
+);
+mock_get!(
+    PhidgetMagnetometer_getMaxMagneticFieldChangeTrigger,
+    PhidgetMagnetometerHandle,
+    f64,
+    "MaxMagneticFieldChangeTrigger"
+);
+mock_get!(
+    PhidgetMagnetometer_getHeatingEnabled,
+    PhidgetMagnetometerHandle,
+    c_int,
+    "HeatingEnabled"
+);
+mock_set!(
+    PhidgetMagnetometer_setMagneticFieldChangeTrigger,
+    PhidgetMagnetometerHandle,
+    f64,
+    "MagneticFieldChangeTrigger"
+);
+mock_set!(
+    PhidgetMagnetometer_setHeatingEnabled,
+    PhidgetMagnetometerHandle,
+    c_int,
+    "HeatingEnabled"
+);
+mock_ok!(PhidgetMagnetometer_setCorrectionParameters(ch: PhidgetMagnetometerHandle, magneticField: f64, offset0: f64, offset1: f64, offset2: f64, gain0: f64, gain1: f64, gain2: f64, T0: f64, T1: f64, T2: f64, T3: f64, T4: f64, T5: f64));
+mock_ok!(PhidgetMagnetometer_saveCorrectionParameters(ch: PhidgetMagnetometerHandle));
+mock_ok!(PhidgetMagnetometer_resetCorrectionParameters(ch: PhidgetMagnetometerHandle));
+
+// The magnetic field events aren't simulated, since it isn't a single value
+#[no_mangle]
+pub unsafe extern "C" fn PhidgetMagnetometer_setOnMagneticFieldChangeHandler(
+    ch: PhidgetMagnetometerHandle,
+    fptr: PhidgetMagnetometer_OnMagneticFieldChangeCallback,
+    ctx: *mut c_void,
+) -> PhidgetReturnCode {
+    unsafe fn fire(_: usize, _: usize, _: usize, _: f64) {}
+    set_handler(
+        ch as usize,
+        "MagneticFieldChange",
+        fptr.map_or(0, |f| f as usize),
+        ctx as usize,
+        fire,
+    )
+}
+mock_create!(
+    PhidgetPHSensor_create,
+    PhidgetPHSensorHandle,
+    Phidget_ChannelClass_PHIDCHCLASS_PHSENSOR
+);
+mock_delete!(PhidgetPHSensor_delete, PhidgetPHSensorHandle);
+mock_get!(PhidgetPHSensor_getPH, PhidgetPHSensorHandle, f64, "PH");
+mock_get!(
+    PhidgetPHSensor_getMinPH,
+    PhidgetPHSensorHandle,
+    f64,
+    "MinPH"
+);
+mock_get!(
+    PhidgetPHSensor_getMaxPH,
+    PhidgetPHSensorHandle,
+    f64,
+    "MaxPH"
+);
+mock_get!(
+    PhidgetPHSensor_getPHChangeTrigger,
+    PhidgetPHSensorHandle,
+    f64,
+    "PHChangeTrigger"
+);
+mock_get!(
+    PhidgetPHSensor_getMinPHChangeTrigger,
+    PhidgetPHSensorHandle,
+    f64,
+    "MinPHChangeTrigger"
+);
+mock_get!(
+    PhidgetPHSensor_getMaxPHChangeTrigger,
+    PhidgetPHSensorHandle,
+    f64,
+    "MaxPHChangeTrigger"
+);
+mock_set!(
+    PhidgetPHSensor_setPHChangeTrigger,
+    PhidgetPHSensorHandle,
+    f64,
+    "PHChangeTrigger"
+);
+mock_handler!(
+    PhidgetPHSensor_setOnPHChangeHandler,
+    PhidgetPHSensorHandle,
+    PhidgetPHSensor_OnPHChangeCallback,
+    "PHChange",
+    f64
+);
+mock_create!(
+    PhidgetPressureSensor_create,
+    PhidgetPressureSensorHandle,
+    Phidget_ChannelClass_PHIDCHCLASS_PRESSURESENSOR
+);
+mock_delete!(PhidgetPressureSensor_delete, PhidgetPressureSensorHandle);
+mock_get!(
+    PhidgetPressureSensor_getPressure,
+    PhidgetPressureSensorHandle,
+    f64,
+    "Pressure"
+);
+mock_get!(
+    PhidgetPressureSensor_getMinPressure,
+    PhidgetPressureSensorHandle,
+    f64,
+    "MinPressure"
+);
+mock_get!(
+    PhidgetPressureSensor_getMaxPressure,
+    PhidgetPressureSensorHandle,
+    f64,
+    "MaxPressure"
+);
+mock_get!(
+    PhidgetPressureSensor_getPressureChangeTrigger,
+    PhidgetPressureSensorHandle,
+    f64,
+    "PressureChangeTrigger"
+);
+mock_get!(
+    PhidgetPressureSensor_getMinPressureChangeTrigger,
+    PhidgetPressureSensorHandle,
+    f64,
+    "MinPressureChangeTrigger"
+);
+mock_get!(
+    PhidgetPressureSensor_getMaxPressureChangeTrigger,
+    PhidgetPressureSensorHandle,
+    f64,
+    "MaxPressureChangeTrigger"
+);
+mock_set!(
+    PhidgetPressureSensor_setPressureChangeTrigger,
+    PhidgetPressureSensorHandle,
+    f64,
+    "PressureChangeTrigger"
+);
+mock_handler!(
+    PhidgetPressureSensor_setOnPressureChangeHandler,
+    PhidgetPressureSensorHandle,
+    PhidgetPressureSensor_OnPressureChangeCallback,
+    "PressureChange",
+    f64
+);
+mock_create!(
+    PhidgetRCServo_create,
+    PhidgetRCServoHandle,
+    Phidget_ChannelClass_PHIDCHCLASS_RCSERVO
+);
+mock_delete!(PhidgetRCServo_delete, PhidgetRCServoHandle);
+mock_get!(
+    PhidgetRCServo_getPosition,
+    PhidgetRCServoHandle,
+    f64,
+    "Position"
+);
+mock_get!(
+    PhidgetRCServo_getMinPosition,
+    PhidgetRCServoHandle,
+    f64,
+    "MinPosition"
+);
+mock_get!(
+    PhidgetRCServo_getMaxPosition,
+    PhidgetRCServoHandle,
+    f64,
+    "MaxPosition"
+);
+mock_get!(
+    PhidgetRCServo_getTargetPosition,
+    PhidgetRCServoHandle,
+    f64,
+    "TargetPosition"
+);
+mock_get!(
+    PhidgetRCServo_getEngaged,
+    PhidgetRCServoHandle,
+    c_int,
+    "Engaged"
+);
+mock_get!(
+    PhidgetRCServo_getIsMoving,
+    PhidgetRCServoHandle,
+    c_int,
+    "IsMoving"
+);
+mock_get!(
+    PhidgetRCServo_getVelocityLimit,
+    PhidgetRCServoHandle,
+    f64,
+    "VelocityLimit"
+);
+mock_get!(
+    PhidgetRCServo_getAcceleration,
+    PhidgetRCServoHandle,
+    f64,
+    "Acceleration"
+);
+mock_set!(
+    PhidgetRCServo_setMinPosition,
+    PhidgetRCServoHandle,
+    f64,
+    "MinPosition"
+);
+mock_set!(
+    PhidgetRCServo_setMaxPosition,
+    PhidgetRCServoHandle,
+    f64,
+    "MaxPosition"
+);
+mock_set!(
+    PhidgetRCServo_setEngaged,
+    PhidgetRCServoHandle,
+    c_int,
+    "Engaged"
+);
+mock_set!(
+    PhidgetRCServo_setVelocityLimit,
+    PhidgetRCServoHandle,
+    f64,
+    "VelocityLimit"
+);
+mock_set!(
+    PhidgetRCServo_setAcceleration,
+    PhidgetRCServoHandle,
+    f64,
+    "Acceleration"
+);
+mock_handler!(
+    PhidgetRCServo_setOnPositionChangeHandler,
+    PhidgetRCServoHandle,
+    PhidgetRCServo_OnPositionChangeCallback,
+    "PositionChange",
+    f64
+);
+mock_handler!(
+    PhidgetRCServo_setOnTargetPositionReachedHandler,
+    PhidgetRCServoHandle,
+    PhidgetRCServo_OnTargetPositionReachedCallback,
+    "TargetPositionReached",
+    f64
+);
+
+// An engaged servo moves to its target at once, and reports reaching it
+#[no_mangle]
+pub unsafe extern "C" fn PhidgetRCServo_setTargetPosition(
+    ch: PhidgetRCServoHandle,
+    pos: f64,
+) -> PhidgetReturnCode {
+    let key = ch as usize;
+    let res = with_channel(key, |ch| {
+        if !ch.attached {
+            return Err(PhidgetReturnCode_EPHIDGET_NOTATTACHED);
+        }
+        ch.set_prop("TargetPosition", pos);
+        let engaged = ch.prop("Engaged").is_some_and(|v| v != 0.0);
+        if engaged {
+            ch.set_prop("Position", pos);
+        }
+        Ok(engaged)
+    });
+    match res {
+        Ok(true) => {
+            fire(key, "PositionChange", pos);
+            fire(key, "TargetPositionReached", pos);
+            PhidgetReturnCode_EPHIDGET_OK
+        }
+        Ok(false) => PhidgetReturnCode_EPHIDGET_OK,
+        Err(rc) => rc,
+    }
+}
+mock_create!(
+    PhidgetSpatial_create,
+    PhidgetSpatialHandle,
+    Phidget_ChannelClass_PHIDCHCLASS_SPATIAL
+);
+mock_delete!(PhidgetSpatial_delete, PhidgetSpatialHandle);
+
+// The spatial data is fired by `spatial_data()`, since it isn't a single value
+#[no_mangle]
+pub unsafe extern "C" fn PhidgetSpatial_setOnSpatialDataHandler(
+    ch: PhidgetSpatialHandle,
+    fptr: PhidgetSpatial_OnSpatialDataCallback,
+    ctx: *mut c_void,
+) -> PhidgetReturnCode {
+    unsafe fn fire(_: usize, _: usize, _: usize, _: f64) {}
+    set_handler(
+        ch as usize,
+        "SpatialData",
+        fptr.map_or(0, |f| f as usize),
+        ctx as usize,
+        fire,
+    )
+}
+mock_ok!(PhidgetSpatial_zeroGyro(ch: PhidgetSpatialHandle));
+#[no_mangle]
+pub unsafe extern "C" fn PhidgetStepper_addPositionOffset(
+    ch: PhidgetStepperHandle,
+    positionOffset: f64,
+) -> PhidgetReturnCode {
+    rc(with_channel(ch as usize, |ch| {
+        if !ch.attached {
+            return Err(PhidgetReturnCode_EPHIDGET_NOTATTACHED);
+        }
+        let pos = ch.prop("Position").unwrap_or(0.0) + positionOffset;
+        ch.set_prop("Position", pos);
+        Ok(())
+    }))
+}
+mock_create!(
+    PhidgetStepper_create,
+    PhidgetStepperHandle,
+    Phidget_ChannelClass_PHIDCHCLASS_STEPPER
+);
+mock_delete!(PhidgetStepper_delete, PhidgetStepperHandle);
+mock_ok!(PhidgetStepper_enableFailsafe(ch: PhidgetStepperHandle, failsafeTime: u32));
+mock_get!(
+    PhidgetStepper_getAcceleration,
+    PhidgetStepperHandle,
+    f64,
+    "Acceleration"
+);
+mock_get!(
+    PhidgetStepper_getControlMode,
+    PhidgetStepperHandle,
+    PhidgetStepper_ControlMode,
+    "ControlMode"
+);
+mock_get!(
+    PhidgetStepper_getCurrentLimit,
+    PhidgetStepperHandle,
+    f64,
+    "CurrentLimit"
+);
+mock_get!(
+    PhidgetStepper_getDataInterval,
+    PhidgetStepperHandle,
+    u32,
+    "DataInterval"
+);
+mock_get!(
+    PhidgetStepper_getDataRate,
+    PhidgetStepperHandle,
+    f64,
+    "DataRate"
+);
+mock_get!(
+    PhidgetStepper_getEngaged,
+    PhidgetStepperHandle,
+    c_int,
+    "Engaged"
+);
+mock_get!(
+    PhidgetStepper_getHoldingCurrentLimit,
+    PhidgetStepperHandle,
+    f64,
+    "HoldingCurrentLimit"
+);
+mock_get!(
+    PhidgetStepper_getIsMoving,
+    PhidgetStepperHandle,
+    c_int,
+    "IsMoving"
+);
+mock_get!(
+    PhidgetStepper_getMaxAcceleration,
+    PhidgetStepperHandle,
+    f64,
+    "MaxAcceleration"
+);
+mock_get!(
+    PhidgetStepper_getMaxCurrentLimit,
+    PhidgetStepperHandle,
+    f64,
+    "MaxCurrentLimit"
+);
+mock_get!(
+    PhidgetStepper_getMaxDataInterval,
+    PhidgetStepperHandle,
+    u32,
+    "MaxDataInterval"
+);
+mock_get!(
+    PhidgetStepper_getMaxDataRate,
+    PhidgetStepperHandle,
+    f64,
+    "MaxDataRate"
+);
+mock_get!(
+    PhidgetStepper_getMaxPosition,
+    PhidgetStepperHandle,
+    f64,
+    "MaxPosition"
+);
+mock_get!(
+    PhidgetStepper_getMaxVelocityLimit,
+    PhidgetStepperHandle,
+    f64,
+    "MaxVelocityLimit"
+);
+mock_get!(
+    PhidgetStepper_getMinAcceleration,
+    PhidgetStepperHandle,
+    f64,
+    "MinAcceleration"
+);
+mock_get!(
+    PhidgetStepper_getMinCurrentLimit,
+    PhidgetStepperHandle,
+    f64,
+    "MinCurrentLimit"
+);
+mock_get!(
+    PhidgetStepper_getMinDataInterval,
+    PhidgetStepperHandle,
+    u32,
+    "MinDataInterval"
+);
+mock_get!(
+    PhidgetStepper_getMinDataRate,
+    PhidgetStepperHandle,
+    f64,
+    "MinDataRate"
+);
+mock_get!(
+    PhidgetStepper_getMinPosition,
+    PhidgetStepperHandle,
+    f64,
+    "MinPosition"
+);
+mock_get!(
+    PhidgetStepper_getMinVelocityLimit,
+    PhidgetStepperHandle,
+    f64,
+    "MinVelocityLimit"
+);
+mock_get!(
+    PhidgetStepper_getPosition,
+    PhidgetStepperHandle,
+    f64,
+    "Position"
+);
+mock_get!(
+    PhidgetStepper_getRescaleFactor,
+    PhidgetStepperHandle,
+    f64,
+    "RescaleFactor"
+);
+mock_get!(
+    PhidgetStepper_getTargetPosition,
+    PhidgetStepperHandle,
+    f64,
+    "TargetPosition"
+);
+mock_get!(
+    PhidgetStepper_getVelocityLimit,
+    PhidgetStepperHandle,
+    f64,
+    "VelocityLimit"
+);
+mock_ok!(PhidgetStepper_resetFailsafe(ch: PhidgetStepperHandle));
+mock_set!(
+    PhidgetStepper_setAcceleration,
+    PhidgetStepperHandle,
+    f64,
+    "Acceleration"
+);
+mock_set!(
+    PhidgetStepper_setControlMode,
+    PhidgetStepperHandle,
+    PhidgetStepper_ControlMode,
+    "ControlMode"
+);
+mock_set!(
+    PhidgetStepper_setCurrentLimit,
+    PhidgetStepperHandle,
+    f64,
+    "CurrentLimit"
+);
+mock_set!(
+    PhidgetStepper_setDataInterval,
+    PhidgetStepperHandle,
+    u32,
+    "DataInterval"
+);
+mock_set!(
+    PhidgetStepper_setDataRate,
+    PhidgetStepperHandle,
+    f64,
+    "DataRate"
+);
+mock_set!(
+    PhidgetStepper_setEngaged,
+    PhidgetStepperHandle,
+    c_int,
+    "Engaged"
+);
+mock_set!(
+    PhidgetStepper_setHoldingCurrentLimit,
+    PhidgetStepperHandle,
+    f64,
+    "HoldingCurrentLimit"
+);
+mock_handler!(
+    PhidgetStepper_setOnPositionChangeHandler,
+    PhidgetStepperHandle,
+    PhidgetStepper_OnPositionChangeCallback,
+    "PositionChange",
+    f64
+);
+mock_handler!(
+    PhidgetStepper_setOnStoppedHandler,
+    PhidgetStepperHandle,
+    PhidgetStepper_OnStoppedCallback,
+    "Stopped"
+);
+mock_handler!(
+    PhidgetStepper_setOnVelocityChangeHandler,
+    PhidgetStepperHandle,
+    PhidgetStepper_OnVelocityChangeCallback,
+    "VelocityChange",
+    f64
+);
+mock_set!(
+    PhidgetStepper_setRescaleFactor,
+    PhidgetStepperHandle,
+    f64,
+    "RescaleFactor"
+);
+mock_set!(
+    PhidgetStepper_setTargetPosition,
+    PhidgetStepperHandle,
+    f64,
+    "TargetPosition"
+);
+mock_set!(
+    PhidgetStepper_setVelocityLimit,
+    PhidgetStepperHandle,
+    f64,
+    "VelocityLimit"
+);
+mock_create!(
+    PhidgetTemperatureSensor_create,
+    PhidgetTemperatureSensorHandle,
+    Phidget_ChannelClass_PHIDCHCLASS_TEMPERATURESENSOR
+);
+mock_delete!(
+    PhidgetTemperatureSensor_delete,
+    PhidgetTemperatureSensorHandle
+);
+mock_get!(
+    PhidgetTemperatureSensor_getTemperature,
+    PhidgetTemperatureSensorHandle,
+    f64,
+    "Temperature"
+);
+mock_get!(
+    PhidgetTemperatureSensor_getTemperatureChangeTrigger,
+    PhidgetTemperatureSensorHandle,
+    f64,
+    "TemperatureChangeTrigger"
+);
+mock_get!(
+    PhidgetTemperatureSensor_getThermocoupleType,
+    PhidgetTemperatureSensorHandle,
+    PhidgetTemperatureSensor_ThermocoupleType,
+    "ThermocoupleType"
+);
+mock_get!(
+    PhidgetTemperatureSensor_getRTDType,
+    PhidgetTemperatureSensorHandle,
+    PhidgetTemperatureSensor_RTDType,
+    "RTDType"
+);
+mock_get!(
+    PhidgetTemperatureSensor_getRTDWireSetup,
+    PhidgetTemperatureSensorHandle,
+    Phidget_RTDWireSetup,
+    "RTDWireSetup"
+);
+mock_set!(
+    PhidgetTemperatureSensor_setTemperatureChangeTrigger,
+    PhidgetTemperatureSensorHandle,
+    f64,
+    "TemperatureChangeTrigger"
+);
+mock_set!(
+    PhidgetTemperatureSensor_setThermocoupleType,
+    PhidgetTemperatureSensorHandle,
+    PhidgetTemperatureSensor_ThermocoupleType,
+    "ThermocoupleType"
+);
+mock_set!(
+    PhidgetTemperatureSensor_setRTDType,
+    PhidgetTemperatureSensorHandle,
+    PhidgetTemperatureSensor_RTDType,
+    "RTDType"
+);
+mock_set!(
+    PhidgetTemperatureSensor_setRTDWireSetup,
+    PhidgetTemperatureSensorHandle,
+    Phidget_RTDWireSetup,
+    "RTDWireSetup"
+);
+mock_handler!(
+    PhidgetTemperatureSensor_setOnTemperatureChangeHandler,
+    PhidgetTemperatureSensorHandle,
+    PhidgetTemperatureSensor_OnTemperatureChangeCallback,
+    "TemperatureChange",
+    f64
+);
+mock_create!(
+    PhidgetVoltageInput_create,
+    PhidgetVoltageInputHandle,
+    Phidget_ChannelClass_PHIDCHCLASS_VOLTAGEINPUT
+);
+mock_delete!(PhidgetVoltageInput_delete, PhidgetVoltageInputHandle);
+mock_get!(
+    PhidgetVoltageInput_getVoltage,
+    PhidgetVoltageInputHandle,
+    f64,
+    "Voltage"
+);
+mock_get!(
+    PhidgetVoltageInput_getVoltageChangeTrigger,
+    PhidgetVoltageInputHandle,
+    f64,
+    "VoltageChangeTrigger"
+);
+mock_get!(
+    PhidgetVoltageInput_getPowerSupply,
+    PhidgetVoltageInputHandle,
+    Phidget_PowerSupply,
+    "PowerSupply"
+);
+mock_get!(
+    PhidgetVoltageInput_getVoltageRange,
+    PhidgetVoltageInputHandle,
+    PhidgetVoltageInput_VoltageRange,
+    "VoltageRange"
+);
+mock_set!(
+    PhidgetVoltageInput_setVoltageChangeTrigger,
+    PhidgetVoltageInputHandle,
+    f64,
+    "VoltageChangeTrigger"
+);
+mock_set!(
+    PhidgetVoltageInput_setPowerSupply,
+    PhidgetVoltageInputHandle,
+    Phidget_PowerSupply,
+    "PowerSupply"
+);
+mock_set!(
+    PhidgetVoltageInput_setVoltageRange,
+    PhidgetVoltageInputHandle,
+    PhidgetVoltageInput_VoltageRange,
+    "VoltageRange"
+);
+mock_handler!(
+    PhidgetVoltageInput_setOnVoltageChangeHandler,
+    PhidgetVoltageInputHandle,
+    PhidgetVoltageInput_OnVoltageChangeCallback,
+    "VoltageChange",
+    f64
+);
+mock_create!(
+    PhidgetVoltageOutput_create,
+    PhidgetVoltageOutputHandle,
+    Phidget_ChannelClass_PHIDCHCLASS_VOLTAGEOUTPUT
+);
+mock_delete!(PhidgetVoltageOutput_delete, PhidgetVoltageOutputHandle);
+mock_get!(
+    PhidgetVoltageOutput_getVoltage,
+    PhidgetVoltageOutputHandle,
+    f64,
+    "Voltage"
+);
+mock_set!(
+    PhidgetVoltageOutput_setEnabled,
+    PhidgetVoltageOutputHandle,
+    c_int,
+    "Enabled"
+);
+mock_set!(
+    PhidgetVoltageOutput_setVoltage,
+    PhidgetVoltageOutputHandle,
+    f64,
+    "Voltage"
+);
+mock_create!(
+    PhidgetVoltageRatioInput_create,
+    PhidgetVoltageRatioInputHandle,
+    Phidget_ChannelClass_PHIDCHCLASS_VOLTAGERATIOINPUT
+);
+mock_delete!(
+    PhidgetVoltageRatioInput_delete,
+    PhidgetVoltageRatioInputHandle
+);
+mock_get!(
+    PhidgetVoltageRatioInput_getBridgeEnabled,
+    PhidgetVoltageRatioInputHandle,
+    c_int,
+    "BridgeEnabled"
+);
+mock_get!(
+    PhidgetVoltageRatioInput_getBridgeGain,
+    PhidgetVoltageRatioInputHandle,
+    PhidgetVoltageRatioInput_BridgeGain,
+    "BridgeGain"
+);
+mock_get!(
+    PhidgetVoltageRatioInput_getVoltageRatio,
+    PhidgetVoltageRatioInputHandle,
+    f64,
+    "VoltageRatio"
+);
+mock_get!(
+    PhidgetVoltageRatioInput_getMinVoltageRatioChangeTrigger,
+    PhidgetVoltageRatioInputHandle,
+    f64,
+    "MinVoltageRatioChangeTrigger"
+);
+mock_get!(
+    PhidgetVoltageRatioInput_getMaxVoltageRatioChangeTrigger,
+    PhidgetVoltageRatioInputHandle,
+    f64,
+    "MaxVoltageRatioChangeTrigger"
+);
+mock_get!(
+    PhidgetVoltageRatioInput_getVoltageRatioChangeTrigger,
+    PhidgetVoltageRatioInputHandle,
+    f64,
+    "VoltageRatioChangeTrigger"
+);
+mock_set!(
+    PhidgetVoltageRatioInput_setBridgeEnabled,
+    PhidgetVoltageRatioInputHandle,
+    c_int,
+    "BridgeEnabled"
+);
+mock_set!(
+    PhidgetVoltageRatioInput_setBridgeGain,
+    PhidgetVoltageRatioInputHandle,
+    PhidgetVoltageRatioInput_BridgeGain,
+    "BridgeGain"
+);
+mock_set!(
+    PhidgetVoltageRatioInput_setVoltageRatioChangeTrigger,
+    PhidgetVoltageRatioInputHandle,
+    f64,
+    "VoltageRatioChangeTrigger"
+);
+mock_handler!(
+    PhidgetVoltageRatioInput_setOnVoltageRatioChangeHandler,
+    PhidgetVoltageRatioInputHandle,
+    PhidgetVoltageRatioInput_OnVoltageRatioChangeCallback,
+    "VoltageRatioChange",
+    f64
+);
+mock_get!(Phidget_getChannel, PhidgetHandle, c_int, "Channel");
+mock_get!(Phidget_getDataInterval, PhidgetHandle, u32, "DataInterval");
+mock_get!(Phidget_getDataRate, PhidgetHandle, f64, "DataRate");
+mock_get!(
+    Phidget_getDeviceID,
+    PhidgetHandle,
+    Phidget_DeviceID,
+    "DeviceID"
+);
+mock_get!(
+    Phidget_getDeviceVersion,
+    PhidgetHandle,
+    c_int,
+    "DeviceVersion"
+);
+mock_get!(
+    Phidget_getDeviceSerialNumber,
+    PhidgetHandle,
+    i32,
+    "DeviceSerialNumber"
+);
+mock_get!(Phidget_getHubPort, PhidgetHandle, c_int, "HubPort");
+mock_get!(Phidget_getHubPortSpeed, PhidgetHandle, u32, "HubPortSpeed");
+mock_get!(
+    Phidget_getHubPortSupportsAutoSetSpeed,
+    PhidgetHandle,
+    c_int,
+    "HubPortSupportsAutoSetSpeed"
+);
+mock_get!(
+    Phidget_getHubPortSupportsSetSpeed,
+    PhidgetHandle,
+    c_int,
+    "HubPortSupportsSetSpeed"
+);
+mock_get!(
+    Phidget_getIsHubPortDevice,
+    PhidgetHandle,
+    c_int,
+    "IsHubPortDevice"
+);
+mock_get!(Phidget_getIsLocal, PhidgetHandle, c_int, "IsLocal");
+mock_get!(Phidget_getIsRemote, PhidgetHandle, c_int, "IsRemote");
+mock_get!(
+    Phidget_getMaxDataInterval,
+    PhidgetHandle,
+    u32,
+    "MaxDataInterval"
+);
+mock_get!(Phidget_getMaxDataRate, PhidgetHandle, f64, "MaxDataRate");
+mock_get!(
+    Phidget_getMaxHubPortSpeed,
+    PhidgetHandle,
+    u32,
+    "MaxHubPortSpeed"
+);
+mock_get!(
+    Phidget_getMaxVINTDeviceSpeed,
+    PhidgetHandle,
+    u32,
+    "MaxVINTDeviceSpeed"
+);
+mock_get!(
+    Phidget_getMinDataInterval,
+    PhidgetHandle,
+    u32,
+    "MinDataInterval"
+);
+mock_get!(Phidget_getMinDataRate, PhidgetHandle, f64, "MinDataRate");
+mock_get!(
+    Phidget_getVINTDeviceSupportsAutoSetSpeed,
+    PhidgetHandle,
+    c_int,
+    "VINTDeviceSupportsAutoSetSpeed"
+);
+mock_get!(
+    Phidget_getVINTDeviceSupportsSetSpeed,
+    PhidgetHandle,
+    c_int,
+    "VINTDeviceSupportsSetSpeed"
+);
+mock_set!(Phidget_setChannel, PhidgetHandle, c_int, "Channel");
+mock_set!(Phidget_setDataInterval, PhidgetHandle, u32, "DataInterval");
+mock_set!(Phidget_setDataRate, PhidgetHandle, f64, "DataRate");
+mock_set!(
+    Phidget_setDeviceSerialNumber,
+    PhidgetHandle,
+    i32,
+    "DeviceSerialNumber"
+);
+mock_set!(Phidget_setHubPort, PhidgetHandle, c_int, "HubPort");
+mock_set!(Phidget_setHubPortSpeed, PhidgetHandle, u32, "HubPortSpeed");
+mock_set!(
+    Phidget_setIsHubPortDevice,
+    PhidgetHandle,
+    c_int,
+    "IsHubPortDevice"
+);
+mock_set!(Phidget_setIsLocal, PhidgetHandle, c_int, "IsLocal");
+mock_set!(Phidget_setIsRemote, PhidgetHandle, c_int, "IsRemote");
+mock_handler!(
+    Phidget_setOnAttachHandler,
+    PhidgetHandle,
+    Phidget_OnAttachCallback,
+    "Attach"
+);
+mock_handler!(
+    Phidget_setOnDetachHandler,
+    PhidgetHandle,
+    Phidget_OnDetachCallback,
+    "Detach"
+);
+
+// ----- Manager -----
+
+#[no_mangle]
+pub unsafe extern "C" fn PhidgetManager_create(
+    phidm: *mut PhidgetManagerHandle,
+) -> PhidgetReturnCode {
+    if phidm.is_null() {
+        return PhidgetReturnCode_EPHIDGET_INVALIDARG;
+    }
+    let key = Box::into_raw(Box::new(0u64)) as usize;
+    managers().push(Manager {
+        key,
+        open: false,
+        attach: (0, 0),
+        detach: (0, 0),
+    });
+    *phidm = key as PhidgetManagerHandle;
+    PhidgetReturnCode_EPHIDGET_OK
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn PhidgetManager_delete(
+    phidm: *mut PhidgetManagerHandle,
+) -> PhidgetReturnCode {
+    if phidm.is_null() {
+        return PhidgetReturnCode_EPHIDGET_INVALIDARG;
+    }
+    let key = *phidm as usize;
+    let mut mgrs = managers();
+    let Some(i) = mgrs.iter().position(|m| m.key == key)
+    else {
+        return PhidgetReturnCode_EPHIDGET_INVALIDARG;
+    };
+    mgrs.swap_remove(i);
+    drop(Box::from_raw(key as *mut u64));
+    *phidm = ptr::null_mut();
+    PhidgetReturnCode_EPHIDGET_OK
+}
+
+// Runs the function on the manager with the key, if it exists.
+fn with_manager<F>(key: usize, f: F) -> PhidgetReturnCode
+where
+    F: FnOnce(&mut Manager),
+{
+    match managers().iter_mut().find(|m| m.key == key) {
+        Some(m) => {
+            f(m);
+            PhidgetReturnCode_EPHIDGET_OK
+        }
+        None => PhidgetReturnCode_EPHIDGET_INVALIDARG,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn PhidgetManager_open(phidm: PhidgetManagerHandle) -> PhidgetReturnCode {
+    let mut attach = (0, 0);
+    let rc = with_manager(phidm as usize, |m| {
+        m.open = true;
+        attach = m.attach;
+    });
+    if rc != PhidgetReturnCode_EPHIDGET_OK {
+        return rc;
+    }
+
+    // Report the channels that are already attached
+    let keys: Vec<_> = channels()
+        .iter()
+        .filter(|ch| ch.attached)
+        .map(|ch| ch.key)
+        .collect();
+
+    if let Some(f) = mem::transmute::<usize, PhidgetManager_OnAttachCallback>(attach.0) {
+        for key in keys {
+            f(phidm, attach.1 as *mut c_void, key as PhidgetHandle);
+        }
+    }
+    rc
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn PhidgetManager_close(phidm: PhidgetManagerHandle) -> PhidgetReturnCode {
+    with_manager(phidm as usize, |m| m.open = false)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn PhidgetManager_setOnAttachHandler(
+    phidm: PhidgetManagerHandle,
+    fptr: PhidgetManager_OnAttachCallback,
+    ctx: *mut c_void,
+) -> PhidgetReturnCode {
+    let fptr = fptr.map_or(0, |f| f as usize);
+    with_manager(phidm as usize, |m| m.attach = (fptr, ctx as usize))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn PhidgetManager_setOnDetachHandler(
+    phidm: PhidgetManagerHandle,
+    fptr: PhidgetManager_OnDetachCallback,
+    ctx: *mut c_void,
+) -> PhidgetReturnCode {
+    let fptr = fptr.map_or(0, |f| f as usize);
+    with_manager(phidm as usize, |m| m.detach = (fptr, ctx as usize))
+}
@@ -0,0 +1,390 @@
+// phidget-rs/src/alarms.rs
+//
+// This file is part of the 'phidget-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+//! Threshold alarms on sensor values.
+//!
+//! An alarm watches the change events of a sensor for a condition, like a
+//! temperature above a limit, and reports when the alarm is raised and
+//! when it's cleared. The condition can be required to hold for some time
+//! before the alarm is raised, so that a brief spike doesn't trip it, and
+//! the alarm can drive a digital output, like a relay for a siren or a
+//! shutoff valve:
+//!
+//! ```text
+//! let mut alarms = Alarms::new();
+//! alarms.add(
+//!     "boiler",
+//!     &boiler_temp,
+//!     Alarm::above(85.0)
+//!         .sustained(Duration::from_secs(30))
+//!         .output(siren),
+//! )?;
+//!
+//! for ev in alarms.iter() {
+//!     println!("{}: {:?} at {}", ev.name, ev.state, ev.value);
+//! }
+//! ```
+//!
+//! The conditions are checked as the change events arrive, so the time
+//! that a condition has held is only as accurate as the data interval of
+//! the sensor. A sensor with a change trigger that stops reporting while
+//! its value holds steady won't raise a sustained alarm until it reports
+//! again.
+//!
+
+use crate::{devices::DigitalOutput, HandlerGuard, Result, Sensor};
+use std::{
+    ops::Deref,
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant, SystemTime},
+};
+
+/// The condition on a value that raises an alarm.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Condition {
+    /// The value is above the limit
+    Above(f64),
+    /// The value is below the limit
+    Below(f64),
+    /// The value is outside the band from the low to the high limit
+    Outside(f64, f64),
+}
+
+impl Condition {
+    /// Determines if the value meets the condition.
+    pub fn is_met(&self, val: f64) -> bool {
+        use Condition::*;
+        match *self {
+            Above(lim) => val > lim,
+            Below(lim) => val < lim,
+            Outside(lo, hi) => val < lo || val > hi,
+        }
+    }
+}
+
+/// Whether an alarm was raised or cleared.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AlarmState {
+    /// The condition held for the sustain time
+    Raised,
+    /// The condition no longer holds
+    Cleared,
+}
+
+/// A change in the state of an alarm.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AlarmEvent {
+    /// The name of the alarm
+    pub name: String,
+    /// Whether the alarm was raised or cleared
+    pub state: AlarmState,
+    /// The value of the sensor that changed the state
+    pub value: f64,
+    /// The time of the change
+    pub time: SystemTime,
+}
+
+/// The definition of an alarm, to add to a set of `Alarms`.
+#[derive(Debug)]
+pub struct Alarm {
+    // The condition that raises the alarm
+    condition: Condition,
+    // The time the condition needs to hold before it's raised
+    sustain: Duration,
+    // The output that's turned on while the alarm is raised
+    output: Option<DigitalOutput>,
+}
+
+impl Alarm {
+    /// Creates an alarm for the condition, that's raised as soon as the
+    /// condition is met.
+    pub fn new(condition: Condition) -> Self {
+        Self {
+            condition,
+            sustain: Duration::ZERO,
+            output: None,
+        }
+    }
+
+    /// Creates an alarm for a value above the limit.
+    pub fn above(limit: f64) -> Self {
+        Self::new(Condition::Above(limit))
+    }
+
+    /// Creates an alarm for a value below the limit.
+    pub fn below(limit: f64) -> Self {
+        Self::new(Condition::Below(limit))
+    }
+
+    /// Creates an alarm for a value outside the band from the low to the
+    /// high limit.
+    pub fn outside(low: f64, high: f64) -> Self {
+        Self::new(Condition::Outside(low, high))
+    }
+
+    /// Sets the time that the condition needs to hold before the alarm is
+    /// raised.
+    pub fn sustained(mut self, sustain: Duration) -> Self {
+        self.sustain = sustain;
+        self
+    }
+
+    /// Sets a digital output that's turned on while the alarm is raised,
+    /// and off when it's cleared.
+    ///
+    /// The output should be open and attached. The alarm owns it from
+    /// here on.
+    pub fn output(mut self, output: DigitalOutput) -> Self {
+        self.output = Some(output);
+        self
+    }
+}
+
+// Tracks the state of an alarm from the values of its sensor.
+struct Tracker {
+    condition: Condition,
+    sustain: Duration,
+    // When the condition started to hold, if it does
+    since: Option<Instant>,
+    raised: bool,
+}
+
+impl Tracker {
+    fn new(condition: Condition, sustain: Duration) -> Self {
+        Self {
+            condition,
+            sustain,
+            since: None,
+            raised: false,
+        }
+    }
+
+    // Updates the state with a value, returning the new state if it
+    // changed.
+    fn update(&mut self, t: Instant, val: f64) -> Option<AlarmState> {
+        if !self.condition.is_met(val) {
+            self.since = None;
+            return if self.raised {
+                self.raised = false;
+                Some(AlarmState::Cleared)
+            }
+            else {
+                None
+            };
+        }
+
+        let since = *self.since.get_or_insert(t);
+        if !self.raised && t.saturating_duration_since(since) >= self.sustain {
+            self.raised = true;
+            return Some(AlarmState::Raised);
+        }
+        None
+    }
+}
+
+/// A set of alarms on sensors.
+///
+/// This dereferences to a `std::sync::mpsc::Receiver` for reading the
+/// alarm events. The sensors' change handlers are removed when the set is
+/// dropped.
+pub struct Alarms {
+    // The names of the alarms, in order
+    names: Vec<String>,
+    // Whether each alarm is raised
+    raised: Arc<Mutex<Vec<bool>>>,
+    // Sends the alarm events
+    tx: Sender<AlarmEvent>,
+    // Receives the alarm events
+    rx: Receiver<AlarmEvent>,
+    // The change handlers feeding the alarms
+    guards: Vec<HandlerGuard>,
+}
+
+impl Alarms {
+    /// Creates an empty set of alarms.
+    pub fn new() -> Self {
+        let (tx, rx) = mpsc::channel();
+        Self {
+            names: Vec::new(),
+            raised: Arc::default(),
+            tx,
+            rx,
+            guards: Vec::new(),
+        }
+    }
+
+    /// Adds an alarm on a sensor.
+    ///
+    /// The sensor should be open and attached. This sets its change
+    /// handler, replacing any handler that was previously set. Boolean
+    /// values are checked as 0.0 or 1.0.
+    pub fn add<S>(&mut self, name: &str, sensor: &S, alarm: Alarm) -> Result<()>
+    where
+        S: Sensor,
+        S::Value: Into<f64>,
+    {
+        let Alarm {
+            condition,
+            sustain,
+            mut output,
+        } = alarm;
+
+        let idx = self.names.len();
+        let mut tracker = Tracker::new(condition, sustain);
+        let raised = Arc::clone(&self.raised);
+        let tx = self.tx.clone();
+        let alarm_name = name.to_string();
+
+        let guard = sensor.on_change(move |_, val| {
+            let value = val.into();
+            let Some(state) = tracker.update(Instant::now(), value)
+            else {
+                return;
+            };
+
+            let is_raised = state == AlarmState::Raised;
+            // A failed output is still reported by its own error events
+            if let Some(out) = output.as_mut() {
+                let _ = out.set_state(is_raised);
+            }
+            if let Some(r) = raised
+                .lock()
+                .unwrap_or_else(|err| err.into_inner())
+                .get_mut(idx)
+            {
+                *r = is_raised;
+            }
+            let _ = tx.send(AlarmEvent {
+                name: alarm_name.clone(),
+                state,
+                value,
+                time: SystemTime::now(),
+            });
+        })?;
+
+        self.raised
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .push(false);
+        self.names.push(name.into());
+        self.guards.push(guard);
+        Ok(())
+    }
+
+    /// Gets the names of the alarms, in the order they were added.
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    /// Determines if the alarm with the name is currently raised.
+    pub fn is_raised(&self, name: &str) -> bool {
+        let raised = self.raised.lock().unwrap_or_else(|err| err.into_inner());
+        self.names
+            .iter()
+            .zip(raised.iter())
+            .any(|(n, &r)| n == name && r)
+    }
+
+    /// Gets the names of the alarms that are currently raised.
+    pub fn raised(&self) -> Vec<String> {
+        let raised = self.raised.lock().unwrap_or_else(|err| err.into_inner());
+        self.names
+            .iter()
+            .zip(raised.iter())
+            .filter(|(_, &r)| r)
+            .map(|(n, _)| n.clone())
+            .collect()
+    }
+}
+
+impl Default for Alarms {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Deref for Alarms {
+    type Target = Receiver<AlarmEvent>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.rx
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tracker() {
+        use AlarmState::*;
+
+        let mut tr = Tracker::new(Condition::Outside(10.0, 20.0), Duration::from_millis(100));
+        let t0 = Instant::now();
+        let ms = Duration::from_millis;
+
+        // A spike shorter than the sustain time doesn't raise the alarm
+        assert_eq!(tr.update(t0, 25.0), None);
+        assert_eq!(tr.update(t0 + ms(50), 15.0), None);
+
+        // The condition has to hold for the whole time
+        assert_eq!(tr.update(t0 + ms(100), 5.0), None);
+        assert_eq!(tr.update(t0 + ms(150), 4.0), None);
+        assert_eq!(tr.update(t0 + ms(200), 3.0), Some(Raised));
+        assert_eq!(tr.update(t0 + ms(250), 2.0), None);
+        assert_eq!(tr.update(t0 + ms(300), 12.0), Some(Cleared));
+        assert_eq!(tr.update(t0 + ms(350), 13.0), None);
+    }
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod mock_tests {
+    use super::*;
+    use crate::{mock::set_value, Phidget, TemperatureSensor};
+
+    #[test]
+    fn test_mock_alarm_output() {
+        let mut sensor = TemperatureSensor::new();
+        let mut siren = DigitalOutput::new();
+        sensor.open().unwrap();
+        siren.open().unwrap();
+        siren.set_state(false).unwrap();
+
+        // A view of the output, to check it after the alarm takes it
+        let handle = siren.as_handle() as phidget_sys::PhidgetDigitalOutputHandle;
+        let view = std::mem::ManuallyDrop::new(DigitalOutput::from(handle));
+
+        let mut alarms = Alarms::new();
+        alarms
+            .add("hot", &sensor, Alarm::above(80.0).output(siren))
+            .unwrap();
+
+        set_value(&mut sensor, 75.0).unwrap();
+        assert!(alarms.try_recv().is_err());
+
+        set_value(&mut sensor, 85.0).unwrap();
+        let ev = alarms.try_recv().unwrap();
+        assert_eq!((ev.name.as_str(), ev.state), ("hot", AlarmState::Raised));
+        assert_eq!(alarms.raised(), vec!["hot".to_string()]);
+        assert_eq!(view.state(), Ok(true));
+
+        set_value(&mut sensor, 70.0).unwrap();
+        assert_eq!(alarms.try_recv().unwrap().state, AlarmState::Cleared);
+        assert!(!alarms.is_raised("hot"));
+        assert_eq!(view.state(), Ok(false));
+    }
+}
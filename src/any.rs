@@ -0,0 +1,266 @@
+// phidget-rs/src/any.rs
+//
+// This file is part of the 'phidget-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+//! Opening a channel by its address, without knowing its type.
+//!
+//! The phidget22 library needs to know the class of a channel before it
+//! can be opened. For applications that take the channels from a
+//! configuration file, `open_any()` uses the manager to find the channel
+//! at an address, and then opens it as the matching type:
+//!
+//! ```text
+//! let addr = ChannelAddress {
+//!     serial: Some(561234),
+//!     hub_port: Some(2),
+//!     ..ChannelAddress::default()
+//! };
+//!
+//! match phidget::open_any(&addr, TIMEOUT)? {
+//!     AnyChannel::TemperatureSensor(sensor) => { ... }
+//!     AnyChannel::HumiditySensor(sensor) => { ... }
+//!     mut ch => println!("Not a sensor: {:?}", ch.channel_class()?),
+//! }
+//! ```
+//!
+
+use crate::{
+    devices::*, ChannelClass, ChannelInfo, Error, GenericPhidget, Manager, Phidget, Result,
+};
+use phidget_sys::PhidgetHandle;
+use std::{
+    sync::mpsc,
+    time::{Duration, Instant},
+};
+
+/// The address of a channel to open.
+///
+/// Any part of the address that is not set matches any device.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChannelAddress {
+    /// The serial number of the device, or the VINT Hub
+    pub serial: Option<i32>,
+    /// The port on the VINT Hub
+    pub hub_port: Option<i32>,
+    /// The index of the channel on the device
+    pub channel: Option<i32>,
+    /// Whether the channel is a VINT Hub port used directly as a device
+    pub is_hub_port_device: Option<bool>,
+    /// The label of the device
+    pub label: Option<String>,
+    /// Whether the channel must be opened remotely, or locally
+    pub remote: Option<bool>,
+}
+
+impl ChannelAddress {
+    // Determines whether the channel reported by the manager is at this
+    // address.
+    fn matches(&self, ph: &mut GenericPhidget) -> bool {
+        self.serial.map_or(true, |sn| ph.serial_number() == Ok(sn))
+            && self.hub_port.map_or(true, |port| ph.hub_port() == Ok(port))
+            && self.channel.map_or(true, |ch| ph.channel() == Ok(ch))
+            && self
+                .is_hub_port_device
+                .map_or(true, |on| ph.is_hub_port_device() == Ok(on))
+            && self
+                .label
+                .as_ref()
+                .map_or(true, |label| ph.device_label().as_ref() == Ok(label))
+            && self.remote.map_or(true, |rem| ph.is_remote() == Ok(rem))
+    }
+
+    // Opens a channel of a specific type at the exact address of a channel
+    // reported by the manager.
+    fn open<T>(&self, info: &ChannelInfo, to: Duration) -> Result<T>
+    where
+        T: Phidget + Default,
+    {
+        let mut bld = crate::builder::<T>()
+            .serial(info.serial_number)
+            .hub_port_device(info.is_hub_port_device)
+            .hub_port(info.hub_port)
+            .channel(info.channel);
+        if let Some(label) = &self.label {
+            bld = bld.label(label);
+        }
+        if let Some(rem) = self.remote {
+            bld = bld.remote(rem);
+        }
+        bld.open_wait(to)
+    }
+}
+
+/// A channel of any of the types supported by the crate.
+#[derive(Debug)]
+pub enum AnyChannel {
+    /// An accelerometer channel
+    Accelerometer(Accelerometer),
+    /// A capacitive touch channel
+    CapacitiveTouch(CapacitiveTouch),
+    /// A current input channel
+    CurrentInput(CurrentInput),
+    /// A network dictionary channel
+    Dictionary(Dictionary),
+    /// A digital input channel
+    DigitalInput(DigitalInput),
+    /// A digital output channel
+    DigitalOutput(DigitalOutput),
+    /// An encoder channel
+    Encoder(Encoder),
+    /// A frequency counter channel
+    FrequencyCounter(FrequencyCounter),
+    /// A GPS channel
+    Gps(Gps),
+    /// A VINT Hub channel
+    Hub(Hub),
+    /// A humidity sensor channel
+    HumiditySensor(HumiditySensor),
+    /// A graphic LCD channel
+    Lcd(Lcd),
+    /// A light sensor channel
+    LightSensor(LightSensor),
+    /// A magnetometer channel
+    Magnetometer(Magnetometer),
+    /// A pH sensor channel
+    PhSensor(PhSensor),
+    /// A pressure sensor channel
+    PressureSensor(PressureSensor),
+    /// An RC servo controller channel
+    RcServo(RcServo),
+    /// A spatial (IMU) channel
+    Spatial(Spatial),
+    /// A stepper motor channel
+    Stepper(Stepper),
+    /// A temperature sensor channel
+    TemperatureSensor(TemperatureSensor),
+    /// A voltage input channel
+    VoltageInput(VoltageInput),
+    /// A voltage output channel
+    VoltageOutput(VoltageOutput),
+    /// A voltage ratio input channel
+    VoltageRatioInput(VoltageRatioInput),
+}
+
+impl Phidget for AnyChannel {
+    fn as_handle(&mut self) -> PhidgetHandle {
+        use AnyChannel::*;
+        match self {
+            Accelerometer(ch) => ch.as_handle(),
+            CapacitiveTouch(ch) => ch.as_handle(),
+            CurrentInput(ch) => ch.as_handle(),
+            Dictionary(ch) => ch.as_handle(),
+            DigitalInput(ch) => ch.as_handle(),
+            DigitalOutput(ch) => ch.as_handle(),
+            Encoder(ch) => ch.as_handle(),
+            FrequencyCounter(ch) => ch.as_handle(),
+            Gps(ch) => ch.as_handle(),
+            Hub(ch) => ch.as_handle(),
+            HumiditySensor(ch) => ch.as_handle(),
+            Lcd(ch) => ch.as_handle(),
+            LightSensor(ch) => ch.as_handle(),
+            Magnetometer(ch) => ch.as_handle(),
+            PhSensor(ch) => ch.as_handle(),
+            PressureSensor(ch) => ch.as_handle(),
+            RcServo(ch) => ch.as_handle(),
+            Spatial(ch) => ch.as_handle(),
+            Stepper(ch) => ch.as_handle(),
+            TemperatureSensor(ch) => ch.as_handle(),
+            VoltageInput(ch) => ch.as_handle(),
+            VoltageOutput(ch) => ch.as_handle(),
+            VoltageRatioInput(ch) => ch.as_handle(),
+        }
+    }
+}
+
+/// Opens the channel at the address, as the type that matches its class,
+/// waiting a limited time for it to be found and attach.
+///
+/// If several channels match the address, the first one that the manager
+/// reports is opened. Returns a `Timeout` error if no channel at the
+/// address is found in time, or `Unsupported` if the crate doesn't have a
+/// type for the class of the channel.
+pub fn open_any(addr: &ChannelAddress, to: Duration) -> Result<AnyChannel> {
+    let deadline = Instant::now() + to;
+    let (tx, rx) = mpsc::channel();
+
+    let mut mgr = Manager::try_new()?;
+    let filter = addr.clone();
+    mgr.set_on_attach_handler(move |ph| {
+        if filter.matches(ph) {
+            if let Ok(info) = ChannelInfo::from_phidget(ph) {
+                let _ = tx.send(info);
+            }
+        }
+    })?;
+    mgr.open()?;
+    let info = rx.recv_timeout(to).map_err(|_| Error::Timeout);
+    drop(mgr);
+    let info = info?;
+
+    let to = deadline.saturating_duration_since(Instant::now());
+    let ch = match info.channel_class {
+        ChannelClass::Accelerometer => AnyChannel::Accelerometer(addr.open(&info, to)?),
+        ChannelClass::CaptiveTouch => AnyChannel::CapacitiveTouch(addr.open(&info, to)?),
+        ChannelClass::CurrentInput => AnyChannel::CurrentInput(addr.open(&info, to)?),
+        ChannelClass::Dictionary => AnyChannel::Dictionary(addr.open(&info, to)?),
+        ChannelClass::DigitalInput => AnyChannel::DigitalInput(addr.open(&info, to)?),
+        ChannelClass::DigitalOutput => AnyChannel::DigitalOutput(addr.open(&info, to)?),
+        ChannelClass::Encoder => AnyChannel::Encoder(addr.open(&info, to)?),
+        ChannelClass::FrequencyCounter => AnyChannel::FrequencyCounter(addr.open(&info, to)?),
+        ChannelClass::Gps => AnyChannel::Gps(addr.open(&info, to)?),
+        ChannelClass::Hub => AnyChannel::Hub(addr.open(&info, to)?),
+        ChannelClass::HumiditySensor => AnyChannel::HumiditySensor(addr.open(&info, to)?),
+        ChannelClass::Lcd => AnyChannel::Lcd(addr.open(&info, to)?),
+        ChannelClass::LightSensor => AnyChannel::LightSensor(addr.open(&info, to)?),
+        ChannelClass::Magnetometer => AnyChannel::Magnetometer(addr.open(&info, to)?),
+        ChannelClass::PhSensor => AnyChannel::PhSensor(addr.open(&info, to)?),
+        ChannelClass::PressureSensor => AnyChannel::PressureSensor(addr.open(&info, to)?),
+        ChannelClass::RcServo => AnyChannel::RcServo(addr.open(&info, to)?),
+        ChannelClass::Spatial => AnyChannel::Spatial(addr.open(&info, to)?),
+        ChannelClass::Stepper => AnyChannel::Stepper(addr.open(&info, to)?),
+        ChannelClass::TemperatureSensor => AnyChannel::TemperatureSensor(addr.open(&info, to)?),
+        ChannelClass::VoltageInput => AnyChannel::VoltageInput(addr.open(&info, to)?),
+        ChannelClass::VoltageOutput => AnyChannel::VoltageOutput(addr.open(&info, to)?),
+        ChannelClass::VoltageRatioInput => AnyChannel::VoltageRatioInput(addr.open(&info, to)?),
+        _ => return Err(Error::Unsupported),
+    };
+    Ok(ch)
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mock_open_any() {
+        let mut sensor = TemperatureSensor::new();
+        sensor.set_serial_number(626_001).unwrap();
+        sensor.open().unwrap();
+
+        let mut addr = ChannelAddress {
+            serial: Some(626_001),
+            ..ChannelAddress::default()
+        };
+        match open_any(&addr, Duration::from_millis(100)) {
+            Ok(AnyChannel::TemperatureSensor(mut temp)) => {
+                assert_eq!(temp.serial_number(), Ok(626_001));
+                assert!(temp.is_attached().unwrap());
+            }
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        addr.serial = Some(626_002);
+        assert!(matches!(
+            open_any(&addr, Duration::from_millis(10)),
+            Err(Error::Timeout)
+        ));
+    }
+}
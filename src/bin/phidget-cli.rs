@@ -0,0 +1,183 @@
+// phidget-rs/src/bin/phidget-cli.rs
+//
+// This file is part of the 'phidget-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! Command-line tool to verify Phidget hardware in the field.
+//!
+//! List the channels that are available to the system:
+//!
+//! ```text
+//! $ phidget-cli list
+//! ```
+//!
+//! Read a channel by its address, once or continuously:
+//!
+//! ```text
+//! $ phidget-cli read temperature -s 561234 -p 2
+//! $ phidget-cli read voltage -h -p 5 --follow
+//! $ phidget-cli read humidity -l greenhouse-north
+//! ```
+
+use clap::{arg, value_parser, ArgAction, ArgMatches, Command};
+use phidget::{
+    devices::{DigitalInput, HumiditySensor, TemperatureSensor, VoltageInput, VoltageRatioInput},
+    Sensor,
+};
+use std::{fmt::Display, thread, time::Duration};
+
+// The open/connect timeout
+const TIMEOUT: Duration = phidget::TIMEOUT_DEFAULT;
+
+// The package version is used as the app version
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+// The classes of channel that can be read
+const CLASSES: [&str; 5] = [
+    "temperature",
+    "humidity",
+    "voltage",
+    "voltage-ratio",
+    "digital-input",
+];
+
+// --------------------------------------------------------------------------
+
+// Lists the channels that are available.
+fn list(opts: &ArgMatches) -> anyhow::Result<()> {
+    let wait = Duration::from_secs_f64(*opts.get_one::<f64>("wait").unwrap());
+    let chans = phidget::manager::list(wait)?;
+
+    if chans.is_empty() {
+        println!("No channels found");
+        return Ok(());
+    }
+
+    println!("    Serial Hub Port Channel  Class                    Name");
+    for ch in chans {
+        let hub = if ch.is_hub_port_device { "*" } else { "" };
+        println!(
+            "{:>10} {:>7}{:1} {:>7}  {:<24} {}",
+            ch.serial_number,
+            ch.hub_port,
+            hub,
+            ch.channel,
+            ch.channel_class.to_string(),
+            ch.channel_name
+        );
+    }
+    Ok(())
+}
+
+// Opens the sensor at the address in the options, and prints its value.
+fn read<S>(mut dev: S, opts: &ArgMatches) -> anyhow::Result<()>
+where
+    S: Sensor,
+    S::Value: Display,
+{
+    dev.set_is_hub_port_device(opts.get_flag("hub"))?;
+    if let Some(&num) = opts.get_one::<i32>("serial") {
+        dev.set_serial_number(num)?;
+    }
+    if let Some(label) = opts.get_one::<String>("label") {
+        dev.set_device_label(label)?;
+    }
+    if let Some(&port) = opts.get_one::<i32>("port") {
+        dev.set_hub_port(port)?;
+    }
+    if let Some(&chan) = opts.get_one::<i32>("channel") {
+        dev.set_channel(chan)?;
+    }
+
+    dev.open_wait(TIMEOUT)?;
+    println!("{}", dev.value()?);
+
+    if !opts.get_flag("follow") {
+        return Ok(());
+    }
+
+    let _handler = dev.on_change(|_, val| println!("{}", val))?;
+
+    // ^C handler wakes up the main thread
+    ctrlc::set_handler({
+        let thr = thread::current();
+        move || {
+            println!("\nExiting...");
+            thr.unpark();
+        }
+    })
+    .expect("Error setting Ctrl-C handler");
+
+    // Block until a ^C wakes us up to exit.
+    thread::park();
+    Ok(())
+}
+
+// --------------------------------------------------------------------------
+
+fn main() -> anyhow::Result<()> {
+    let opts = Command::new("phidget-cli")
+        .version(VERSION)
+        .author(env!("CARGO_PKG_AUTHORS"))
+        .about("Phidget command-line tool")
+        .subcommand_required(true)
+        .subcommand(
+            Command::new("list")
+                .about("List the channels that are available")
+                .arg(
+                    arg!(-w --wait [secs] "The time to wait for the channels to be found")
+                        .default_value("1.0")
+                        .value_parser(value_parser!(f64)),
+                ),
+        )
+        .subcommand(
+            Command::new("read")
+                .about("Read the value of a channel")
+                .disable_help_flag(true)
+                .arg(
+                    arg!(--help "Print help information")
+                        .short('?')
+                        .action(ArgAction::Help),
+                )
+                .arg(
+                    arg!(<class> "The class of the channel")
+                        .value_parser(CLASSES),
+                )
+                .arg(
+                    arg!(-s --serial [serial_num] "Specify the serial number of the device to open")
+                        .value_parser(value_parser!(i32)),
+                )
+                .arg(arg!(-l --label [label] "Specify the label of the device to open"))
+                .arg(
+                    arg!(-p --port [port] "Specify the port on the VINT hub")
+                        .value_parser(value_parser!(i32)),
+                )
+                .arg(
+                    arg!(-c --channel [chan] "Specify the channel number of the device to open")
+                        .value_parser(value_parser!(i32)),
+                )
+                .arg(arg!(-h --hub "Use a hub VINT input port directly").action(ArgAction::SetTrue))
+                .arg(arg!(-f --follow "Keep printing the value as it changes").action(ArgAction::SetTrue)),
+        )
+        .get_matches();
+
+    phidget::version_check()?;
+
+    match opts.subcommand() {
+        Some(("list", opts)) => list(opts),
+        Some(("read", opts)) => match opts.get_one::<String>("class").map(String::as_str) {
+            Some("temperature") => read(TemperatureSensor::new(), opts),
+            Some("humidity") => read(HumiditySensor::new(), opts),
+            Some("voltage") => read(VoltageInput::new(), opts),
+            Some("voltage-ratio") => read(VoltageRatioInput::new(), opts),
+            Some("digital-input") => read(DigitalInput::new(), opts),
+            _ => unreachable!(),
+        },
+        _ => unreachable!(),
+    }
+}
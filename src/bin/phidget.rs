@@ -12,10 +12,10 @@ fn main() -> anyhow::Result<()> {
     println!("{}", phidget::library_version_number()?);
 
     let mut hum_sensor = HumiditySensor::new();
-    phidget::phidget::set_on_attach_handler(&mut hum_sensor, |_| {
+    let _hum_attach = phidget::phidget::set_on_attach_handler(&mut hum_sensor, |_| {
         println!("Humidity sensor attached");
     })?;
-    phidget::phidget::set_on_detach_handler(&mut hum_sensor, |_| {
+    let _hum_detach = phidget::phidget::set_on_detach_handler(&mut hum_sensor, |_| {
         println!("Humidity sensor detached");
     })?;
     hum_sensor.open_wait(TIMEOUT)?;
@@ -35,10 +35,10 @@ fn main() -> anyhow::Result<()> {
     println!("Humidity: {}", humidity);
 
     let mut temp_sensor = TemperatureSensor::new();
-    phidget::phidget::set_on_attach_handler(&mut temp_sensor, |_| {
+    let _temp_attach = phidget::phidget::set_on_attach_handler(&mut temp_sensor, |_| {
         println!("Temperature sensor attached");
     })?;
-    phidget::phidget::set_on_detach_handler(&mut temp_sensor, |_| {
+    let _temp_detach = phidget::phidget::set_on_detach_handler(&mut temp_sensor, |_| {
         println!("Temperature sensor detached");
     })?;
     temp_sensor.open_wait(TIMEOUT)?;
@@ -57,13 +57,16 @@ fn main() -> anyhow::Result<()> {
     let temperature = temp_sensor.temperature()?;
     println!("Temperature: {}\n", temperature);
 
-    hum_sensor.set_on_humidity_change_handler(|_s: &HumiditySensor, humidity: f64| {
-        println!("Humidity: {}", humidity);
-    })?;
+    let _hum_handler =
+        hum_sensor.set_on_humidity_change_handler(|_s: &HumiditySensor, humidity: f64| {
+            println!("Humidity: {}", humidity);
+        })?;
 
-    temp_sensor.set_on_temperature_change_handler(|_s: &TemperatureSensor, temperature: f64| {
-        println!("Temerature: {}", temperature);
-    })?;
+    let _temp_handler = temp_sensor.set_on_temperature_change_handler(
+        |_s: &TemperatureSensor, temperature: f64| {
+            println!("Temerature: {}", temperature);
+        },
+    )?;
 
     // ^C handler wakes up the main thread
     ctrlc::set_handler({
@@ -0,0 +1,190 @@
+// phidget-rs/src/builder.rs
+//
+// This file is part of the 'phidget-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+//! A builder to select and open a Phidget channel.
+//!
+//! The device selection filters, like the serial number, hub port, and
+//! channel, must all be set on a channel before it is opened. The builder
+//! collects them, applies them to a new channel in the proper order, and
+//! then opens it:
+//!
+//! ```text
+//! let sensor = phidget::builder::<TemperatureSensor>()
+//!     .serial(12345)
+//!     .hub_port(2)
+//!     .channel(0)
+//!     .open_wait(TIMEOUT)?;
+//! ```
+//!
+
+use crate::{Phidget, Result};
+use std::{marker::PhantomData, time::Duration};
+
+/// Creates a builder to open a channel of the specified type.
+pub fn builder<T>() -> ChannelBuilder<T>
+where
+    T: Phidget + Default,
+{
+    ChannelBuilder::new()
+}
+
+/// A builder to select and open a Phidget channel.
+///
+/// Any filter that is not set is left at the library default, which
+/// generally matches any device.
+#[derive(Debug)]
+pub struct ChannelBuilder<T> {
+    serial_number: Option<i32>,
+    is_hub_port_device: Option<bool>,
+    hub_port: Option<i32>,
+    channel: Option<i32>,
+    label: Option<String>,
+    local: Option<bool>,
+    remote: Option<bool>,
+    server: Option<(String, i32)>,
+    phantom: PhantomData<fn() -> T>,
+}
+
+impl<T> ChannelBuilder<T>
+where
+    T: Phidget + Default,
+{
+    /// Creates a new builder with no filters set.
+    pub fn new() -> Self {
+        Self {
+            serial_number: None,
+            is_hub_port_device: None,
+            hub_port: None,
+            channel: None,
+            label: None,
+            local: None,
+            remote: None,
+            server: None,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Sets the serial number of the device to open.
+    /// If the channel is part of a VINT device, this is the serial number
+    /// of the VINT Hub to which the device is attached.
+    pub fn serial(mut self, sn: i32) -> Self {
+        self.serial_number = Some(sn);
+        self
+    }
+
+    /// Sets whether the channel is a VINT Hub port channel, rather than
+    /// part of a VINT device attached to a hub port.
+    pub fn hub_port_device(mut self, on: bool) -> Self {
+        self.is_hub_port_device = Some(on);
+        self
+    }
+
+    /// Sets the port on the VINT Hub to which the device is attached.
+    pub fn hub_port(mut self, port: i32) -> Self {
+        self.hub_port = Some(port);
+        self
+    }
+
+    /// Sets the index of the channel to open on the device.
+    pub fn channel(mut self, chan: i32) -> Self {
+        self.channel = Some(chan);
+        self
+    }
+
+    /// Sets the label of the device to open.
+    /// This can be used instead of the serial number to select a device
+    /// that was given a label with `write_device_label()`.
+    pub fn label(mut self, label: &str) -> Self {
+        self.label = Some(label.to_string());
+        self
+    }
+
+    /// Sets whether the channel must be opened locally (not over a
+    /// network).
+    pub fn local(mut self, local: bool) -> Self {
+        self.local = Some(local);
+        self
+    }
+
+    /// Sets whether the channel must be opened remotely (over a network).
+    pub fn remote(mut self, rem: bool) -> Self {
+        self.remote = Some(rem);
+        self
+    }
+
+    /// Sets the host and port of the Phidget server through which the
+    /// channel must be opened.
+    /// The server is registered so that it doesn't need to be found by
+    /// discovery.
+    pub fn server(mut self, host: &str, port: i32) -> Self {
+        self.server = Some((host.to_string(), port));
+        self
+    }
+
+    /// Creates the channel with the filters applied, without opening it.
+    pub fn build(self) -> Result<T> {
+        let mut ph = T::default();
+        if let Some(sn) = self.serial_number {
+            ph.set_serial_number(sn)?;
+        }
+        if let Some(on) = self.is_hub_port_device {
+            ph.set_is_hub_port_device(on)?;
+        }
+        if let Some(port) = self.hub_port {
+            ph.set_hub_port(port)?;
+        }
+        if let Some(chan) = self.channel {
+            ph.set_channel(chan)?;
+        }
+        if let Some(label) = &self.label {
+            ph.set_device_label(label)?;
+        }
+        if let Some(local) = self.local {
+            ph.set_is_local(local)?;
+        }
+        if let Some(rem) = self.remote {
+            ph.set_is_remote(rem)?;
+        }
+        if let Some((host, port)) = &self.server {
+            ph.set_server_hostname(host, *port)?;
+        }
+        Ok(ph)
+    }
+
+    /// Creates the channel and starts to open it, without waiting for it
+    /// to attach.
+    pub fn open(self) -> Result<T> {
+        let mut ph = self.build()?;
+        ph.open()?;
+        Ok(ph)
+    }
+
+    /// Creates the channel and opens it, waiting a limited time for it to
+    /// attach.
+    pub fn open_wait(self, to: Duration) -> Result<T> {
+        let mut ph = self.build()?;
+        ph.open_wait(to)?;
+        Ok(ph)
+    }
+
+    /// Creates the channel and opens it, waiting the default time for it
+    /// to attach.
+    pub fn open_wait_default(self) -> Result<T> {
+        self.open_wait(crate::TIMEOUT_DEFAULT)
+    }
+}
+
+impl<T> Default for ChannelBuilder<T>
+where
+    T: Phidget + Default,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,254 @@
+// phidget-rs/src/cache.rs
+//
+// This file is part of the 'phidget-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+//! Internal cache of channel properties.
+//!
+//! Some properties of a channel, like the serial number of the device and
+//! the hub port that it's plugged into, can't change while the channel is
+//! attached. These are read from the library the first time that they're
+//! needed, and kept until the channel detaches.
+//!
+
+use crate::Result;
+use phidget_sys::{self as ffi, PhidgetHandle};
+use std::{any::Any, os::raw::c_int, sync::Mutex};
+
+/// The address of a channel: the physical location of the device and
+/// the index of the channel on it.
+///
+/// For a channel that is not attached, these are the values that were set
+/// to match a device, which might be one of the `PHIDGET_..._ANY` values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Address {
+    /// The serial number of the device, or the VINT Hub
+    pub serial_number: i32,
+    /// The port on the VINT Hub
+    pub hub_port: i32,
+    /// The index of the channel on the device
+    pub channel: i32,
+}
+
+// The addresses of the attached channels, keyed by channel handle.
+static ADDRESSES: Mutex<Vec<(usize, Address)>> = Mutex::new(Vec::new());
+
+// The other cached properties of the attached channels, keyed by
+// channel handle and property name.
+type Property = (usize, &'static str, Box<dyn Any + Send>);
+static PROPERTIES: Mutex<Vec<Property>> = Mutex::new(Vec::new());
+
+// The generation of the cache for each channel, and the last one that was
+// handed out. A channel's entry is removed whenever its cache is cleared,
+// such as when it detaches, and the next read gets a new generation. A
+// value is only cached if its channel is still on the generation from
+// before it was read, since otherwise it might be from the device that
+// the channel was attached to before.
+static GENERATIONS: Mutex<(u64, Vec<(usize, u64)>)> = Mutex::new((0, Vec::new()));
+
+// Gets the current generation of the cache for the channel.
+fn generation(key: usize) -> Option<u64> {
+    let mut gens = GENERATIONS.lock().ok()?;
+    if let Some((_, gen)) = gens.1.iter().find(|(k, _)| *k == key) {
+        return Some(*gen);
+    }
+    gens.0 += 1;
+    let gen = gens.0;
+    gens.1.push((key, gen));
+    Some(gen)
+}
+
+// Calls `insert` if the channel is still on the generation of the cache.
+// The generations are locked meanwhile, so that the entry can't be cleared
+// before it's been inserted.
+fn insert_if_current<F: FnOnce()>(key: usize, gen: Option<u64>, insert: F) {
+    if let (Some(gen), Ok(gens)) = (gen, GENERATIONS.lock()) {
+        if gens.1.contains(&(key, gen)) {
+            insert();
+        }
+    }
+}
+
+/// Gets the address of the channel.
+///
+/// This is read from the library the first time it's requested after the
+/// channel attaches, and cached until it detaches. For a channel that is
+/// not attached, it is always read from the library. Returns `None` if
+/// the values can't be read.
+pub(crate) fn address(phid: PhidgetHandle) -> Option<Address> {
+    let key = phid as usize;
+
+    if let Ok(addrs) = ADDRESSES.lock() {
+        if let Some((_, addr)) = addrs.iter().find(|(k, _)| *k == key) {
+            return Some(*addr);
+        }
+    }
+
+    let gen = generation(key);
+    let mut serial_number: c_int = 0;
+    let mut hub_port: c_int = 0;
+    let mut channel: c_int = 0;
+    let mut attached: c_int = 0;
+
+    unsafe {
+        if ffi::Phidget_getDeviceSerialNumber(phid, &mut serial_number) != 0
+            || ffi::Phidget_getHubPort(phid, &mut hub_port) != 0
+            || ffi::Phidget_getChannel(phid, &mut channel) != 0
+        {
+            return None;
+        }
+        if ffi::Phidget_getAttached(phid, &mut attached) != 0 {
+            attached = 0;
+        }
+    }
+
+    let addr = Address {
+        serial_number: serial_number as i32,
+        hub_port: hub_port as i32,
+        channel: channel as i32,
+    };
+
+    if attached != 0 {
+        insert_if_current(key, gen, || {
+            if let Ok(mut addrs) = ADDRESSES.lock() {
+                if !addrs.iter().any(|(k, _)| *k == key) {
+                    addrs.push((key, addr));
+                }
+            }
+        });
+    }
+    Some(addr)
+}
+
+/// Gets a property of the channel that can't change while it's attached.
+///
+/// The property is identified by name, and is read with the `read`
+/// function the first time it's requested after the channel attaches.
+/// It's then cached until the channel detaches. For a channel that is not
+/// attached, it is always read, and errors are never cached.
+pub(crate) fn property<T, F>(phid: PhidgetHandle, name: &'static str, read: F) -> Result<T>
+where
+    T: Clone + Send + 'static,
+    F: FnOnce() -> Result<T>,
+{
+    let key = phid as usize;
+
+    if let Ok(props) = PROPERTIES.lock() {
+        let val = props
+            .iter()
+            .find(|(k, nm, _)| *k == key && *nm == name)
+            .and_then(|(_, _, val)| val.downcast_ref::<T>());
+        if let Some(val) = val {
+            return Ok(val.clone());
+        }
+    }
+
+    let gen = generation(key);
+    let val = read()?;
+
+    let mut attached: c_int = 0;
+    if unsafe { ffi::Phidget_getAttached(phid, &mut attached) } == 0 && attached != 0 {
+        insert_if_current(key, gen, || {
+            if let Ok(mut props) = PROPERTIES.lock() {
+                if !props.iter().any(|(k, nm, _)| *k == key && *nm == name) {
+                    props.push((key, name, Box::new(val.clone())));
+                }
+            }
+        });
+    }
+    Ok(val)
+}
+
+/// Clears any cached properties for the channel, and moves it on to a
+/// new generation, so that values which are being read aren't cached.
+/// This is called when the channel is created, when it detaches, when
+/// it's deleted, and when a generic phidget for it is dropped.
+pub(crate) fn clear(phid: PhidgetHandle) {
+    let key = phid as usize;
+    if let Ok(mut gens) = GENERATIONS.lock() {
+        gens.1.retain(|(k, _)| *k != key);
+    }
+    if let Ok(mut addrs) = ADDRESSES.lock() {
+        addrs.retain(|(k, _)| *k != key);
+    }
+    if let Ok(mut props) = PROPERTIES.lock() {
+        props.retain(|(k, _, _)| *k != key);
+    }
+}
+
+/// Determines if anything is cached for the channel.
+#[cfg(all(test, feature = "mock"))]
+pub(crate) fn is_cached(phid: PhidgetHandle) -> bool {
+    let key = phid as usize;
+    ADDRESSES.lock().unwrap().iter().any(|(k, _)| *k == key)
+        || PROPERTIES.lock().unwrap().iter().any(|(k, _, _)| *k == key)
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::*;
+    use crate::{
+        mock::{attach, detach, set_property},
+        Error, Phidget, TemperatureSensor,
+    };
+    use std::time::Duration;
+
+    #[test]
+    fn test_mock_cached_properties() {
+        let mut sensor = TemperatureSensor::new();
+        sensor.open().unwrap();
+        assert_eq!(sensor.min_data_interval(), Ok(Duration::from_millis(20)));
+        assert_eq!(
+            sensor.data_interval_range(),
+            Ok(Duration::from_millis(20)..=Duration::from_secs(60))
+        );
+
+        // Cached while attached
+        set_property(&mut sensor, "MinDataInterval", 50.0).unwrap();
+        assert_eq!(sensor.min_data_interval(), Ok(Duration::from_millis(20)));
+
+        // Read again after the channel re-attaches
+        detach(&mut sensor);
+        attach(&mut sensor);
+        assert_eq!(sensor.min_data_interval(), Ok(Duration::from_millis(50)));
+
+        // Intervals outside the range are refused
+        assert_eq!(
+            sensor.set_data_interval(Duration::from_millis(10)),
+            Err(Error::IntervalOutOfRange {
+                interval: Duration::from_millis(10),
+                min: Duration::from_millis(50),
+                max: Duration::from_secs(60),
+            })
+        );
+        sensor.set_data_interval(Duration::from_millis(50)).unwrap();
+        assert_eq!(sensor.data_interval(), Ok(Duration::from_millis(50)));
+    }
+
+    #[test]
+    fn test_mock_property_not_cached_across_detach() {
+        let mut sensor = TemperatureSensor::new();
+        sensor.open().unwrap();
+        attach(&mut sensor);
+
+        // The channel detaches and attaches again while the value is read
+        let phid = sensor.as_handle();
+        let val = property(phid, "MinDataInterval", || {
+            phidget_sys::mock::detach(phid);
+            phidget_sys::mock::attach(phid);
+            Ok(20u32)
+        });
+        assert_eq!(val, Ok(20));
+        assert!(!is_cached(phid));
+
+        // But is cached once it's read while attached
+        assert!(sensor.min_data_interval().is_ok());
+        assert!(is_cached(phid));
+    }
+}
@@ -0,0 +1,347 @@
+// phidget-rs/src/calibration.rs
+//
+// This file is part of the 'phidget-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+//! Per-channel calibration of sensor values.
+//!
+//! A `Calibration` corrects the raw readings of a sensor, either with a
+//! linear offset and gain, or with a polynomial. A `Calibrated` sensor
+//! wraps any `Sensor` and applies the calibration to the values returned
+//! from its getter and delivered to its change handler:
+//!
+//! ```text
+//! let table = CalibrationTable::load("calibration.csv")?;
+//! let sensor = Calibrated::new(TemperatureSensor::new(), table.get_or_default("greenhouse"));
+//!
+//! let _handler = sensor.on_change(|_, t| println!("Temperature: {}", t))?;
+//! ```
+//!
+//! A `CalibrationTable` holds the calibrations for a set of channels by
+//! name, and can be saved to, and loaded from, a CSV file with one
+//! calibration per line:
+//!
+//! ```text
+//! # name,offset,gain[,c0,c1,c2...]
+//! greenhouse,-0.25,1.02
+//! tank,0,1,0.5,1.1,-0.002
+//! ```
+//!
+//! If any polynomial coefficients are given, they are used instead of the
+//! offset and gain.
+//!
+
+use crate::{HandlerGuard, Phidget, Result, Sensor};
+use phidget_sys::PhidgetHandle;
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{self, Write},
+    path::Path,
+    sync::{Arc, RwLock},
+};
+
+/// The calibration for a single channel.
+///
+/// The corrected value is `offset + gain * x`, unless polynomial
+/// coefficients are given, in which case it is
+/// `c[0] + c[1] * x + c[2] * x^2 + ...`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Calibration {
+    /// The offset added to the scaled value
+    pub offset: f64,
+    /// The scale factor applied to the raw value
+    pub gain: f64,
+    /// Polynomial coefficients, in order of increasing power.
+    /// If this is not empty, the offset and gain are ignored.
+    pub polynomial: Vec<f64>,
+}
+
+impl Calibration {
+    /// Creates a linear calibration from an offset and gain.
+    pub fn linear(offset: f64, gain: f64) -> Self {
+        Self {
+            offset,
+            gain,
+            polynomial: Vec::new(),
+        }
+    }
+
+    /// Creates a polynomial calibration from the coefficients, in order
+    /// of increasing power.
+    pub fn polynomial(coeffs: &[f64]) -> Self {
+        Self {
+            polynomial: coeffs.to_vec(),
+            ..Self::default()
+        }
+    }
+
+    /// Determines if the calibration leaves values unchanged.
+    pub fn is_identity(&self) -> bool {
+        match self.polynomial.as_slice() {
+            [] => self.offset == 0.0 && self.gain == 1.0,
+            [c0, c1] => *c0 == 0.0 && *c1 == 1.0,
+            _ => false,
+        }
+    }
+
+    /// Applies the calibration to a raw value.
+    pub fn apply(&self, x: f64) -> f64 {
+        if self.polynomial.is_empty() {
+            self.offset + self.gain * x
+        }
+        else {
+            // Horner's method
+            self.polynomial.iter().rev().fold(0.0, |acc, c| acc * x + c)
+        }
+    }
+}
+
+impl Default for Calibration {
+    /// The identity calibration, with no offset and unity gain.
+    fn default() -> Self {
+        Self::linear(0.0, 1.0)
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+/// A sensor with a calibration applied to its values.
+///
+/// The calibration is shared with any change handler that was set
+/// through the wrapper, so changing it takes effect immediately.
+pub struct Calibrated<S> {
+    // The sensor
+    sensor: S,
+    // The calibration, shared with the change handler
+    cal: Arc<RwLock<Calibration>>,
+}
+
+impl<S> Calibrated<S>
+where
+    S: Sensor,
+    S::Value: Into<f64>,
+{
+    /// Wraps a sensor with a calibration.
+    pub fn new(sensor: S, cal: Calibration) -> Self {
+        Self {
+            sensor,
+            cal: Arc::new(RwLock::new(cal)),
+        }
+    }
+
+    /// Gets a reference to the underlying sensor.
+    pub fn sensor(&self) -> &S {
+        &self.sensor
+    }
+
+    /// Gets a mutable reference to the underlying sensor.
+    pub fn sensor_mut(&mut self) -> &mut S {
+        &mut self.sensor
+    }
+
+    /// Unwraps the sensor, discarding the calibration.
+    pub fn into_inner(self) -> S {
+        self.sensor
+    }
+
+    /// Gets a copy of the current calibration.
+    pub fn calibration(&self) -> Calibration {
+        self.cal
+            .read()
+            .unwrap_or_else(|err| err.into_inner())
+            .clone()
+    }
+
+    /// Replaces the calibration.
+    pub fn set_calibration(&self, cal: Calibration) {
+        *self.cal.write().unwrap_or_else(|err| err.into_inner()) = cal;
+    }
+
+    /// Reads the current value of the sensor, with the calibration
+    /// applied.
+    pub fn value(&self) -> Result<f64> {
+        let x = self.sensor.value()?.into();
+        Ok(self.calibration().apply(x))
+    }
+
+    /// Reads the current value of the sensor, without the calibration.
+    pub fn raw_value(&self) -> Result<f64> {
+        self.sensor.value().map(Into::into)
+    }
+
+    /// Sets a handler to receive the sensor's calibrated value change
+    /// events.
+    ///
+    /// This replaces any change handler that was previously set on the
+    /// sensor. The handler is removed when the returned guard is dropped.
+    pub fn on_change<F>(&self, mut cb: F) -> Result<HandlerGuard>
+    where
+        F: FnMut(&S, f64) + Send + 'static,
+    {
+        let cal = Arc::clone(&self.cal);
+        self.sensor.on_change(move |s, x| {
+            let val = cal
+                .read()
+                .unwrap_or_else(|err| err.into_inner())
+                .apply(x.into());
+            cb(s, val);
+        })
+    }
+}
+
+impl<S: Phidget> Phidget for Calibrated<S> {
+    fn as_handle(&mut self) -> PhidgetHandle {
+        self.sensor.as_handle()
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+/// A set of calibrations for named channels.
+#[derive(Debug, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CalibrationTable {
+    entries: BTreeMap<String, Calibration>,
+}
+
+impl CalibrationTable {
+    /// Creates a new, empty table.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Gets the calibration for the named channel, if there is one.
+    pub fn get(&self, name: &str) -> Option<&Calibration> {
+        self.entries.get(name)
+    }
+
+    /// Gets a copy of the calibration for the named channel, or the
+    /// identity calibration if there isn't one.
+    pub fn get_or_default(&self, name: &str) -> Calibration {
+        self.get(name).cloned().unwrap_or_default()
+    }
+
+    /// Sets the calibration for the named channel, returning the one it
+    /// replaced, if any.
+    pub fn insert(&mut self, name: &str, cal: Calibration) -> Option<Calibration> {
+        self.entries.insert(name.to_string(), cal)
+    }
+
+    /// Removes the calibration for the named channel.
+    pub fn remove(&mut self, name: &str) -> Option<Calibration> {
+        self.entries.remove(name)
+    }
+
+    /// Gets an iterator over the names and calibrations in the table.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Calibration)> {
+        self.entries.iter().map(|(k, v)| (k.as_str(), v))
+    }
+
+    /// Parses a table from the CSV text format.
+    pub fn parse(s: &str) -> io::Result<Self> {
+        let mut tbl = Self::new();
+        for (i, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let err = || {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid calibration on line {}", i + 1),
+                )
+            };
+
+            let mut fields = line.split(',').map(str::trim);
+            let name = fields.next().filter(|s| !s.is_empty()).ok_or_else(err)?;
+            let vals = fields
+                .map(|s| s.parse::<f64>())
+                .collect::<std::result::Result<Vec<_>, _>>()
+                .map_err(|_| err())?;
+
+            let cal = match vals.as_slice() {
+                [offset, gain] => Calibration::linear(*offset, *gain),
+                [offset, gain, coeffs @ ..] => Calibration {
+                    offset: *offset,
+                    gain: *gain,
+                    polynomial: coeffs.to_vec(),
+                },
+                _ => return Err(err()),
+            };
+            tbl.insert(name, cal);
+        }
+        Ok(tbl)
+    }
+
+    /// Loads a table from a CSV file.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    /// Saves the table to a CSV file, replacing any existing file.
+    ///
+    /// This fails if any of the names contain a comma or a newline, since
+    /// they couldn't be read back.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut out = String::from("# name,offset,gain[,c0,c1,c2...]\n");
+        for (name, cal) in &self.entries {
+            if name.contains([',', '\n', '\r']) || name.starts_with('#') {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("invalid calibration name: {:?}", name),
+                ));
+            }
+            out.push_str(&format!("{},{},{}", name, cal.offset, cal.gain));
+            for c in &cal.polynomial {
+                out.push_str(&format!(",{}", c));
+            }
+            out.push('\n');
+        }
+        fs::File::create(path)?.write_all(out.as_bytes())
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply() {
+        let cal = Calibration::default();
+        assert!(cal.is_identity());
+        assert_eq!(cal.apply(21.5), 21.5);
+
+        let cal = Calibration::linear(-0.5, 2.0);
+        assert_eq!(cal.apply(10.0), 19.5);
+
+        // 1 + 2x + 3x^2
+        let cal = Calibration::polynomial(&[1.0, 2.0, 3.0]);
+        assert_eq!(cal.apply(2.0), 17.0);
+    }
+
+    #[test]
+    fn test_table() {
+        let mut tbl = CalibrationTable::new();
+        tbl.insert("greenhouse", Calibration::linear(-0.25, 1.02));
+        tbl.insert("tank", Calibration::polynomial(&[0.5, 1.1, -0.002]));
+
+        let path =
+            std::env::temp_dir().join(format!("phidget-calibration-{}.csv", std::process::id()));
+        tbl.save(&path).unwrap();
+        let tbl2 = CalibrationTable::load(&path).unwrap();
+        let _ = fs::remove_file(&path);
+        assert_eq!(tbl, tbl2);
+
+        assert!(CalibrationTable::parse("temp,1.0\n").is_err());
+        assert!(CalibrationTable::parse("temp,1.0,x\n").is_err());
+        assert_eq!(tbl.get_or_default("missing"), Calibration::default());
+    }
+}
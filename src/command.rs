@@ -0,0 +1,48 @@
+// phidget-rs/src/command.rs
+//
+// This file is part of the 'phidget-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+//! Text commands for output channels.
+//!
+//! The bridges to other systems, like MQTT and HTTP, drive output
+//! channels from the text of the messages they receive. Each output
+//! parses the text itself. A digital output takes "on", "off", "true",
+//! "false", or a duty cycle from 0.0 to 1.0. A voltage output takes a
+//! voltage.
+//!
+
+use crate::{DigitalOutput, Error, Result, VoltageOutput};
+
+/// An output channel that can be driven by command messages.
+pub trait Commandable: Send {
+    /// Parses the message payload and applies it to the channel.
+    fn command(&mut self, payload: &str) -> Result<()>;
+}
+
+impl Commandable for DigitalOutput {
+    fn command(&mut self, payload: &str) -> Result<()> {
+        match payload.trim().to_ascii_lowercase().as_str() {
+            "on" | "true" => self.set_state(true),
+            "off" | "false" => self.set_state(false),
+            s => {
+                let duty_cycle = s.parse::<f64>().map_err(|_| Error::InvalidArg)?;
+                self.set_duty_cycle(duty_cycle)
+            }
+        }
+    }
+}
+
+impl Commandable for VoltageOutput {
+    fn command(&mut self, payload: &str) -> Result<()> {
+        let v = payload
+            .trim()
+            .parse::<f64>()
+            .map_err(|_| Error::InvalidArg)?;
+        self.set_voltage(v)
+    }
+}
@@ -0,0 +1,463 @@
+// phidget-rs/src/config.rs
+//
+// This file is part of the 'phidget-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+//! Saving and applying the settings of channels.
+//!
+//! A `ChannelConfig` is a snapshot of the settings of a channel, like its
+//! data interval, change trigger, or thermocouple type. It can be read
+//! from a channel that was set up by hand, and applied to other channels
+//! of the same class, so that a number of identical rigs are configured
+//! the same way.
+//!
+//! The settings are lost when a device is unplugged or reset, so they
+//! should be applied every time the channel attaches. The easiest way is
+//! with a `Reconnector`:
+//!
+//! ```text
+//! let cfg = profile.get("greenhouse").copied().unwrap_or_default();
+//! let sensor = Reconnector::new(TemperatureSensor::new(), move |s| cfg.apply(s))?;
+//! ```
+//!
+//! A `ConfigProfile` holds the settings for a set of channels by name.
+//! With the `toml` feature it can be loaded from, and saved to, a TOML
+//! file, with a table per channel. Any setting that is left out is not
+//! changed on the channel:
+//!
+//! ```text
+//! [greenhouse]
+//! data_interval_ms = 500
+//! change_trigger = 0.1
+//! thermocouple_type = "K"
+//!
+//! [load-cell]
+//! bridge_enabled = true
+//! bridge_gain = "Gain128"
+//! ```
+//!
+
+use crate::{
+    devices::{
+        digital_input::{InputMode, PowerSupply},
+        BridgeGain, CapacitiveTouch, CurrentInput, DigitalInput, Encoder, HumiditySensor,
+        LightSensor, PhSensor, PressureSensor, TemperatureSensor, ThermocoupleType, VoltageInput,
+        VoltageRatioInput,
+    },
+    Error, Phidget, Result,
+};
+use std::{collections::BTreeMap, time::Duration};
+
+#[cfg(feature = "toml")]
+use std::{fs, io, path::Path};
+
+// Reads a setting that the channel might not have. A setting that isn't
+// supported, or isn't known, is left unset.
+fn optional<T>(res: Result<T>) -> Result<Option<T>> {
+    match res {
+        Ok(val) => Ok(Some(val)),
+        Err(Error::Unsupported | Error::UnknownValue(_)) => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+/// The settings of a single channel.
+///
+/// Each setting is optional. One that is not set is not changed when the
+/// configuration is applied. Settings that don't apply to the class of
+/// the channel are ignored.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct ChannelConfig {
+    /// The data interval, in milliseconds
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub data_interval_ms: Option<u32>,
+    /// The minimum change in the value that triggers a change event
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub change_trigger: Option<f64>,
+    /// The type of thermocouple, for a temperature sensor
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub thermocouple_type: Option<ThermocoupleType>,
+    /// Whether the bridge is enabled, for a voltage ratio input
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub bridge_enabled: Option<bool>,
+    /// The bridge gain, for a voltage ratio input
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub bridge_gain: Option<BridgeGain>,
+    /// The input mode, for a digital input
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub input_mode: Option<InputMode>,
+    /// The power supply, for a digital or current input
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub power_supply: Option<PowerSupply>,
+    /// The sensitivity, for a capacitive touch sensor
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub sensitivity: Option<f64>,
+}
+
+impl ChannelConfig {
+    /// Reads the current settings of an attached channel.
+    ///
+    /// Any setting that the channel doesn't support is left unset.
+    pub fn read<C: Configurable>(ch: &mut C) -> Result<Self> {
+        let mut cfg = Self {
+            data_interval_ms: optional(ch.data_interval())?.map(|d| d.as_millis() as u32),
+            ..Self::default()
+        };
+        ch.read_settings(&mut cfg)?;
+        Ok(cfg)
+    }
+
+    /// Applies the settings to an attached channel.
+    ///
+    /// This stops at the first setting that fails, like one that the
+    /// channel doesn't support.
+    pub fn apply<C: Configurable>(&self, ch: &mut C) -> Result<()> {
+        ch.write_settings(self)?;
+        if let Some(ms) = self.data_interval_ms {
+            ch.set_data_interval(Duration::from_millis(ms as u64))?;
+        }
+        Ok(())
+    }
+}
+
+/// A channel with settings that can be saved and applied through a
+/// `ChannelConfig`.
+///
+/// The settings common to all channels, like the data interval, are
+/// handled by the configuration itself. This reads and writes the ones
+/// specific to the class of the channel.
+pub trait Configurable: Phidget {
+    /// Reads the class-specific settings of the channel into the
+    /// configuration.
+    fn read_settings(&mut self, cfg: &mut ChannelConfig) -> Result<()>;
+
+    /// Writes the class-specific settings that are set in the
+    /// configuration to the channel.
+    fn write_settings(&mut self, cfg: &ChannelConfig) -> Result<()>;
+}
+
+impl Configurable for TemperatureSensor {
+    fn read_settings(&mut self, cfg: &mut ChannelConfig) -> Result<()> {
+        cfg.change_trigger = optional(self.temperature_change_trigger())?;
+        cfg.thermocouple_type = optional(self.thermocouple_type())?;
+        Ok(())
+    }
+
+    fn write_settings(&mut self, cfg: &ChannelConfig) -> Result<()> {
+        // Changing the thermocouple type can reset the trigger
+        if let Some(tc) = cfg.thermocouple_type {
+            self.set_thermocouple_type(tc)?;
+        }
+        if let Some(trigger) = cfg.change_trigger {
+            self.set_temperature_change_trigger(trigger)?;
+        }
+        Ok(())
+    }
+}
+
+impl Configurable for HumiditySensor {
+    fn read_settings(&mut self, cfg: &mut ChannelConfig) -> Result<()> {
+        cfg.change_trigger = optional(self.humidity_change_trigger())?;
+        Ok(())
+    }
+
+    fn write_settings(&mut self, cfg: &ChannelConfig) -> Result<()> {
+        if let Some(trigger) = cfg.change_trigger {
+            self.set_humidity_change_trigger(trigger)?;
+        }
+        Ok(())
+    }
+}
+
+impl Configurable for VoltageInput {
+    fn read_settings(&mut self, cfg: &mut ChannelConfig) -> Result<()> {
+        cfg.change_trigger = optional(self.voltage_change_trigger())?;
+        Ok(())
+    }
+
+    fn write_settings(&mut self, cfg: &ChannelConfig) -> Result<()> {
+        if let Some(trigger) = cfg.change_trigger {
+            self.set_voltage_change_trigger(trigger)?;
+        }
+        Ok(())
+    }
+}
+
+impl Configurable for VoltageRatioInput {
+    fn read_settings(&mut self, cfg: &mut ChannelConfig) -> Result<()> {
+        cfg.change_trigger = optional(self.voltage_ratio_change_trigger())?;
+        cfg.bridge_enabled = optional(self.bridge_enabled())?;
+        cfg.bridge_gain = optional(self.bridge_gain())?;
+        Ok(())
+    }
+
+    fn write_settings(&mut self, cfg: &ChannelConfig) -> Result<()> {
+        if let Some(gain) = cfg.bridge_gain {
+            self.set_bridge_gain(gain)?;
+        }
+        if let Some(enabled) = cfg.bridge_enabled {
+            self.set_bridge_enabled(enabled)?;
+        }
+        if let Some(trigger) = cfg.change_trigger {
+            self.set_voltage_ratio_change_trigger(trigger)?;
+        }
+        Ok(())
+    }
+}
+
+impl Configurable for CapacitiveTouch {
+    fn read_settings(&mut self, cfg: &mut ChannelConfig) -> Result<()> {
+        cfg.change_trigger = optional(self.touch_value_change_trigger())?;
+        cfg.sensitivity = optional(self.sensitivity())?;
+        Ok(())
+    }
+
+    fn write_settings(&mut self, cfg: &ChannelConfig) -> Result<()> {
+        if let Some(sensitivity) = cfg.sensitivity {
+            self.set_sensitivity(sensitivity)?;
+        }
+        if let Some(trigger) = cfg.change_trigger {
+            self.set_touch_value_change_trigger(trigger)?;
+        }
+        Ok(())
+    }
+}
+
+impl Configurable for CurrentInput {
+    fn read_settings(&mut self, cfg: &mut ChannelConfig) -> Result<()> {
+        cfg.change_trigger = optional(self.current_change_trigger())?;
+        cfg.power_supply = optional(self.power_supply())?;
+        Ok(())
+    }
+
+    fn write_settings(&mut self, cfg: &ChannelConfig) -> Result<()> {
+        if let Some(supply) = cfg.power_supply {
+            self.set_power_supply(supply)?;
+        }
+        if let Some(trigger) = cfg.change_trigger {
+            self.set_current_change_trigger(trigger)?;
+        }
+        Ok(())
+    }
+}
+
+impl Configurable for DigitalInput {
+    fn read_settings(&mut self, cfg: &mut ChannelConfig) -> Result<()> {
+        cfg.input_mode = optional(self.input_mode())?;
+        cfg.power_supply = optional(self.power_supply())?;
+        Ok(())
+    }
+
+    fn write_settings(&mut self, cfg: &ChannelConfig) -> Result<()> {
+        if let Some(mode) = cfg.input_mode {
+            self.set_input_mode(mode)?;
+        }
+        if let Some(supply) = cfg.power_supply {
+            self.set_power_supply(supply)?;
+        }
+        Ok(())
+    }
+}
+
+impl Configurable for Encoder {
+    fn read_settings(&mut self, cfg: &mut ChannelConfig) -> Result<()> {
+        cfg.change_trigger = optional(self.position_change_trigger())?.map(f64::from);
+        Ok(())
+    }
+
+    fn write_settings(&mut self, cfg: &ChannelConfig) -> Result<()> {
+        if let Some(trigger) = cfg.change_trigger {
+            self.set_position_change_trigger(trigger as u32)?;
+        }
+        Ok(())
+    }
+}
+
+impl Configurable for LightSensor {
+    fn read_settings(&mut self, cfg: &mut ChannelConfig) -> Result<()> {
+        cfg.change_trigger = optional(self.illuminance_change_trigger())?;
+        Ok(())
+    }
+
+    fn write_settings(&mut self, cfg: &ChannelConfig) -> Result<()> {
+        if let Some(trigger) = cfg.change_trigger {
+            self.set_illuminance_change_trigger(trigger)?;
+        }
+        Ok(())
+    }
+}
+
+impl Configurable for PressureSensor {
+    fn read_settings(&mut self, cfg: &mut ChannelConfig) -> Result<()> {
+        cfg.change_trigger = optional(self.pressure_change_trigger())?;
+        Ok(())
+    }
+
+    fn write_settings(&mut self, cfg: &ChannelConfig) -> Result<()> {
+        if let Some(trigger) = cfg.change_trigger {
+            self.set_pressure_change_trigger(trigger)?;
+        }
+        Ok(())
+    }
+}
+
+impl Configurable for PhSensor {
+    fn read_settings(&mut self, cfg: &mut ChannelConfig) -> Result<()> {
+        cfg.change_trigger = optional(self.ph_change_trigger())?;
+        Ok(())
+    }
+
+    fn write_settings(&mut self, cfg: &ChannelConfig) -> Result<()> {
+        if let Some(trigger) = cfg.change_trigger {
+            self.set_ph_change_trigger(trigger)?;
+        }
+        Ok(())
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+/// A set of channel configurations, by name.
+#[derive(Debug, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct ConfigProfile {
+    entries: BTreeMap<String, ChannelConfig>,
+}
+
+impl ConfigProfile {
+    /// Creates a new, empty profile.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Gets the configuration for the named channel, if there is one.
+    pub fn get(&self, name: &str) -> Option<&ChannelConfig> {
+        self.entries.get(name)
+    }
+
+    /// Sets the configuration for the named channel, returning the one it
+    /// replaced, if any.
+    pub fn insert(&mut self, name: &str, cfg: ChannelConfig) -> Option<ChannelConfig> {
+        self.entries.insert(name.to_string(), cfg)
+    }
+
+    /// Removes the configuration for the named channel.
+    pub fn remove(&mut self, name: &str) -> Option<ChannelConfig> {
+        self.entries.remove(name)
+    }
+
+    /// Gets an iterator over the names and configurations in the profile.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &ChannelConfig)> {
+        self.entries.iter().map(|(k, v)| (k.as_str(), v))
+    }
+
+    /// Applies the configuration for the named channel, if there is one.
+    ///
+    /// Returns whether the profile had a configuration for the channel.
+    pub fn apply<C: Configurable>(&self, name: &str, ch: &mut C) -> Result<bool> {
+        match self.get(name) {
+            Some(cfg) => cfg.apply(ch).map(|_| true),
+            None => Ok(false),
+        }
+    }
+
+    /// Parses a profile from TOML text.
+    #[cfg(feature = "toml")]
+    pub fn parse(s: &str) -> io::Result<Self> {
+        toml::from_str(s).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    /// Loads a profile from a TOML file.
+    #[cfg(feature = "toml")]
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    /// Saves the profile to a TOML file, replacing any existing file.
+    #[cfg(feature = "toml")]
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let s = toml::to_string(self)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+        fs::write(path, s)
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(all(test, feature = "toml"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profile() {
+        let prof = ConfigProfile::parse(
+            "[greenhouse]\n\
+             data_interval_ms = 500\n\
+             thermocouple_type = \"K\"\n\
+             \n\
+             [load-cell]\n\
+             bridge_gain = \"Gain128\"\n",
+        )
+        .unwrap();
+
+        let cfg = prof.get("greenhouse").unwrap();
+        assert_eq!(cfg.data_interval_ms, Some(500));
+        assert_eq!(cfg.thermocouple_type, Some(ThermocoupleType::K));
+        assert_eq!(cfg.change_trigger, None);
+        assert_eq!(
+            prof.get("load-cell").unwrap().bridge_gain,
+            Some(BridgeGain::Gain128)
+        );
+
+        let path = std::env::temp_dir().join(format!("phidget-config-{}.toml", std::process::id()));
+        prof.save(&path).unwrap();
+        let prof2 = ConfigProfile::load(&path).unwrap();
+        let _ = fs::remove_file(&path);
+        assert_eq!(prof, prof2);
+
+        assert!(ConfigProfile::parse("[greenhouse]\nbridge_gain = 3\n").is_err());
+    }
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod mock_tests {
+    use super::*;
+    use crate::mock::set_property;
+
+    #[test]
+    fn test_mock_config() {
+        let mut sensor = TemperatureSensor::new();
+        sensor.set_serial_number(638_001).unwrap();
+        sensor.open().unwrap();
+
+        let cfg = ChannelConfig {
+            data_interval_ms: Some(500),
+            change_trigger: Some(0.25),
+            thermocouple_type: Some(ThermocoupleType::K),
+            ..ChannelConfig::default()
+        };
+        cfg.apply(&mut sensor).unwrap();
+        assert_eq!(sensor.data_interval(), Ok(Duration::from_millis(500)));
+        assert_eq!(ChannelConfig::read(&mut sensor), Ok(cfg));
+
+        // Settings that aren't known are left unset
+        let mut sensor = TemperatureSensor::new();
+        sensor.set_serial_number(638_002).unwrap();
+        sensor.open().unwrap();
+        let cfg = ChannelConfig::read(&mut sensor).unwrap();
+        assert_eq!(cfg.thermocouple_type, None);
+        assert_eq!(cfg.data_interval_ms, Some(250));
+
+        // But one that the crate doesn't recognize is an error
+        set_property(&mut sensor, "ThermocoupleType", 99.0).unwrap();
+        assert_eq!(sensor.thermocouple_type(), Err(Error::InvalidArg));
+        assert_eq!(ChannelConfig::read(&mut sensor), Err(Error::InvalidArg));
+    }
+}
@@ -0,0 +1,371 @@
+// phidget-rs/src/datalog.rs
+//
+// This file is part of the 'phidget-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+//! A data logger that writes sensor readings to files.
+//!
+//! The `DataLogger` subscribes to the change events of any number of
+//! sensors, and writes each reading as a timestamped row to a CSV or a
+//! newline-delimited JSON file. Readings from a `Sampler` can be logged
+//! as well. When a file reaches the configured size, it is closed and a
+//! new one is started.
+//!
+//! ```text
+//! let cfg = LogConfig {
+//!     dir: "/var/log/greenhouse".into(),
+//!     format: LogFormat::Csv,
+//!     ..LogConfig::default()
+//! };
+//! let mut logger = DataLogger::new(cfg)?;
+//! logger.add("temperature", &temp_sensor)?;
+//! logger.add("humidity", &hum_sensor)?;
+//! ```
+//!
+//! Each CSV file starts with a `time,name,value,error` header. The time
+//! is in seconds since the Unix epoch. A JSON row looks like:
+//!
+//! ```text
+//! {"time":1700000000.250,"name":"temperature","value":21.5}
+//! ```
+//!
+
+use crate::{HandlerGuard, Result, Sample, Sensor};
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver, Sender},
+    thread::{self, JoinHandle},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// The format of the log files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LogFormat {
+    /// Comma-separated values, with a header row
+    Csv,
+    /// Newline-delimited JSON, with one object per row
+    Json,
+}
+
+impl LogFormat {
+    /// Gets the file extension for the format.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            LogFormat::Csv => "csv",
+            LogFormat::Json => "json",
+        }
+    }
+}
+
+/// The configuration for a data logger.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LogConfig {
+    /// The directory for the log files
+    pub dir: PathBuf,
+    /// The start of the log file names.
+    /// The files are named like "<prefix>-0001.csv"
+    pub prefix: String,
+    /// The format of the files
+    pub format: LogFormat,
+    /// The size, in bytes, at which a file is closed and a new one started.
+    /// If this is zero, the files are not rotated.
+    pub max_file_size: u64,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            dir: PathBuf::from("."),
+            prefix: "phidget".into(),
+            format: LogFormat::Csv,
+            max_file_size: 10 * 1024 * 1024,
+        }
+    }
+}
+
+/// A logger that writes sensor readings to a set of rotating files.
+///
+/// The files are written by a background thread. The sensor handlers are
+/// removed and the last file is flushed when the logger is dropped.
+pub struct DataLogger {
+    // Sends the rows to the writer thread
+    tx: Option<Sender<Sample>>,
+    // The writer thread
+    thr: Option<JoinHandle<io::Result<()>>>,
+    // The handlers feeding the logger
+    guards: Vec<HandlerGuard>,
+}
+
+impl DataLogger {
+    /// Creates a new logger and opens the first file.
+    pub fn new(cfg: LogConfig) -> io::Result<Self> {
+        let mut out = RotatingFile::new(cfg)?;
+        let (tx, rx) = mpsc::channel();
+        let thr = thread::spawn(move || out.run(rx));
+
+        Ok(Self {
+            tx: Some(tx),
+            thr: Some(thr),
+            guards: Vec::new(),
+        })
+    }
+
+    /// Logs all the value changes from the sensor under the specified
+    /// name.
+    ///
+    /// This sets the sensor's change handler, replacing any handler that
+    /// was previously set. Boolean values are logged as 0 or 1.
+    pub fn add<S>(&mut self, name: &str, sensor: &S) -> Result<()>
+    where
+        S: Sensor,
+        S::Value: Into<f64>,
+    {
+        let Some(tx) = self.tx.clone()
+        else {
+            return Ok(());
+        };
+        let name = name.to_string();
+        let guard = sensor.on_change(move |_, val| {
+            let _ = tx.send(Sample {
+                name: name.clone(),
+                time: SystemTime::now(),
+                value: Ok(val.into()),
+            });
+        })?;
+        self.guards.push(guard);
+        Ok(())
+    }
+
+    /// Logs a single sample, such as one from a `Sampler`.
+    pub fn log(&self, sample: Sample) {
+        if let Some(tx) = &self.tx {
+            let _ = tx.send(sample);
+        }
+    }
+}
+
+impl Drop for DataLogger {
+    fn drop(&mut self) {
+        self.guards.clear();
+        self.tx = None;
+        if let Some(thr) = self.thr.take() {
+            let _ = thr.join();
+        }
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+// A log file that is replaced with a new one when it gets too large.
+struct RotatingFile {
+    cfg: LogConfig,
+    // The sequence number of the current file
+    seq: u32,
+    // The current file
+    file: BufWriter<File>,
+    // The number of bytes written to the current file
+    size: u64,
+}
+
+impl RotatingFile {
+    // Creates the directory, if needed, and opens the first file.
+    fn new(cfg: LogConfig) -> io::Result<Self> {
+        fs::create_dir_all(&cfg.dir)?;
+        let seq = next_seq(&cfg);
+        let (file, size) = open_file(&cfg, seq)?;
+        Ok(Self {
+            cfg,
+            seq,
+            file,
+            size,
+        })
+    }
+
+    // Writes the samples until the sender is dropped.
+    fn run(&mut self, rx: Receiver<Sample>) -> io::Result<()> {
+        while let Ok(sample) = rx.recv() {
+            self.write(&sample)?;
+            for sample in rx.try_iter() {
+                self.write(&sample)?;
+            }
+            self.file.flush()?;
+        }
+        Ok(())
+    }
+
+    // Writes a row, rotating the file first if it is full.
+    fn write(&mut self, sample: &Sample) -> io::Result<()> {
+        if self.cfg.max_file_size > 0 && self.size >= self.cfg.max_file_size {
+            self.file.flush()?;
+            self.seq += 1;
+            let (file, size) = open_file(&self.cfg, self.seq)?;
+            self.file = file;
+            self.size = size;
+        }
+        let row = format_row(self.cfg.format, sample);
+        self.file.write_all(row.as_bytes())?;
+        self.size += row.len() as u64;
+        Ok(())
+    }
+}
+
+// Gets the path for the file with the sequence number.
+fn file_path(cfg: &LogConfig, seq: u32) -> PathBuf {
+    let name = format!("{}-{:04}.{}", cfg.prefix, seq, cfg.format.extension());
+    Path::new(&cfg.dir).join(name)
+}
+
+// Finds the sequence number after the last existing file, so that
+// restarting the logger never overwrites old data.
+fn next_seq(cfg: &LogConfig) -> u32 {
+    let mut seq = 1;
+    while file_path(cfg, seq).exists() {
+        seq += 1;
+    }
+    seq
+}
+
+// Creates a new file, writing the header if the format has one.
+fn open_file(cfg: &LogConfig, seq: u32) -> io::Result<(BufWriter<File>, u64)> {
+    let mut file = BufWriter::new(File::create(file_path(cfg, seq))?);
+    let mut size = 0;
+    if cfg.format == LogFormat::Csv {
+        let hdr = "time,name,value,error\n";
+        file.write_all(hdr.as_bytes())?;
+        size = hdr.len() as u64;
+    }
+    Ok((file, size))
+}
+
+// Formats a sample as a row in the log file, including the newline.
+fn format_row(fmt: LogFormat, sample: &Sample) -> String {
+    let time = sample
+        .time
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs_f64())
+        .unwrap_or(0.0);
+
+    match (fmt, &sample.value) {
+        (LogFormat::Csv, Ok(val)) => {
+            format!("{:.3},{},{},\n", time, csv_field(&sample.name), val)
+        }
+        (LogFormat::Csv, Err(err)) => format!(
+            "{:.3},{},,{}\n",
+            time,
+            csv_field(&sample.name),
+            csv_field(&err.to_string())
+        ),
+        (LogFormat::Json, Ok(val)) if val.is_finite() => format!(
+            "{{\"time\":{:.3},\"name\":{},\"value\":{}}}\n",
+            time,
+            json_str(&sample.name),
+            val
+        ),
+        (LogFormat::Json, Ok(_)) => format!(
+            "{{\"time\":{:.3},\"name\":{},\"value\":null}}\n",
+            time,
+            json_str(&sample.name)
+        ),
+        (LogFormat::Json, Err(err)) => format!(
+            "{{\"time\":{:.3},\"name\":{},\"error\":{}}}\n",
+            time,
+            json_str(&sample.name),
+            json_str(&err.to_string())
+        ),
+    }
+}
+
+// Quotes a CSV field if it contains any special characters.
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    }
+    else {
+        s.to_string()
+    }
+}
+
+// Formats a string as a quoted and escaped JSON string.
+fn json_str(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Error;
+    use std::time::Duration;
+
+    fn sample(name: &str, value: Result<f64>) -> Sample {
+        Sample {
+            name: name.into(),
+            time: UNIX_EPOCH + Duration::from_millis(1_700_000_000_250),
+            value,
+        }
+    }
+
+    #[test]
+    fn test_format_row() {
+        let s = sample("temp", Ok(21.5));
+        assert_eq!(
+            format_row(LogFormat::Csv, &s),
+            "1700000000.250,temp,21.5,\n"
+        );
+        assert_eq!(
+            format_row(LogFormat::Json, &s),
+            "{\"time\":1700000000.250,\"name\":\"temp\",\"value\":21.5}\n"
+        );
+
+        let s = sample("a,\"b\"", Err(Error::Timeout));
+        assert!(format_row(LogFormat::Csv, &s).starts_with("1700000000.250,\"a,\"\"b\"\"\",,"));
+        assert!(format_row(LogFormat::Json, &s)
+            .starts_with("{\"time\":1700000000.250,\"name\":\"a,\\\"b\\\"\",\"error\":"));
+    }
+
+    #[test]
+    fn test_rotation() {
+        let dir = std::env::temp_dir().join(format!("phidget-datalog-{}", std::process::id()));
+        let cfg = LogConfig {
+            dir: dir.clone(),
+            prefix: "test".into(),
+            format: LogFormat::Csv,
+            max_file_size: 64,
+        };
+
+        let mut out = RotatingFile::new(cfg.clone()).unwrap();
+        for _ in 0..4 {
+            out.write(&sample("temp", Ok(21.5))).unwrap();
+        }
+        out.file.flush().unwrap();
+        assert!(file_path(&cfg, 1).exists());
+        assert!(file_path(&cfg, 2).exists());
+
+        // A new logger doesn't overwrite the existing files
+        assert_eq!(next_seq(&cfg), 3);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,478 @@
+// phidget-rs/src/decimate.rs
+//
+// This file is part of the 'phidget-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+//! Decimation and resampling of timestamped readings.
+//!
+//! A sensor with a short data interval can deliver far more readings than
+//! are worth storing. The decimators here each reduce a sequence of
+//! timestamped readings:
+//!
+//! - `EveryNth` - keeps one reading out of every N
+//! - `Downsample` - the mean, minimum, or maximum of each group of N
+//!   readings, or of each window of time
+//! - `Resample` - interpolates the readings onto a fixed time grid
+//!
+//! A `Decimated` iterator applies one to any iterator of timestamped
+//! readings, like the one from a sensor's timestamped channel:
+//!
+//! ```text
+//! let rx = voltage_input.timestamped_channel()?;
+//! let per_sec = Decimated::new(rx.iter(), Downsample::window(Duration::from_secs(1), Aggregate::Mean));
+//! for reading in per_sec {
+//!     println!("{:?}: {}", reading.time, reading.value);
+//! }
+//! ```
+//!
+//! With the `async` feature, one can also be applied to an event stream
+//! of timestamped readings with `DecimatedStream`.
+//!
+
+use crate::Timestamped;
+use std::{collections::VecDeque, time::Duration};
+
+#[cfg(feature = "async")]
+use futures_core::Stream;
+#[cfg(feature = "async")]
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+/// A reducer of a sequence of timestamped readings.
+pub trait Decimator: Send + 'static {
+    /// Adds a reading, and pushes any readings that it completes onto the
+    /// output queue.
+    fn update(&mut self, x: Timestamped<f64>, out: &mut VecDeque<Timestamped<f64>>);
+
+    /// Clears the decimator, so that it starts over with the next reading.
+    fn reset(&mut self);
+}
+
+/// Keeps one reading out of every N, starting with the first.
+#[derive(Debug, Clone, Copy)]
+pub struct EveryNth {
+    n: usize,
+    count: usize,
+}
+
+impl EveryNth {
+    /// Creates a decimator that keeps one reading out of every `n`.
+    ///
+    /// Zero is treated as one, which passes the readings through.
+    pub fn new(n: usize) -> Self {
+        Self {
+            n: n.max(1),
+            count: 0,
+        }
+    }
+}
+
+impl Decimator for EveryNth {
+    fn update(&mut self, x: Timestamped<f64>, out: &mut VecDeque<Timestamped<f64>>) {
+        if self.count == 0 {
+            out.push_back(x);
+        }
+        self.count = (self.count + 1) % self.n;
+    }
+
+    fn reset(&mut self) {
+        self.count = 0;
+    }
+}
+
+/// How the readings in a group are combined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Aggregate {
+    /// The mean of the readings
+    Mean,
+    /// The lowest of the readings
+    Min,
+    /// The highest of the readings
+    Max,
+}
+
+// The span of each group of readings
+#[derive(Debug, Clone, Copy)]
+enum Span {
+    Count(usize),
+    Window(Duration),
+}
+
+/// Combines the readings in each group of N, or in each window of time,
+/// into one.
+///
+/// A group of N is stamped with the time of its first reading. The
+/// windows of time are laid out from the first reading, and each is
+/// stamped with the time at which it starts. A window with no readings
+/// gives nothing, and the last group isn't given until it's complete.
+#[derive(Debug, Clone, Copy)]
+pub struct Downsample {
+    span: Span,
+    agg: Aggregate,
+    // The time at which the current group started, and its readings so far
+    start: Option<Timestamped<f64>>,
+    acc: f64,
+    count: usize,
+}
+
+impl Downsample {
+    /// Creates a decimator that combines each group of `n` readings.
+    ///
+    /// Zero is treated as one.
+    pub fn count(n: usize, agg: Aggregate) -> Self {
+        Self::new(Span::Count(n.max(1)), agg)
+    }
+
+    /// Creates a decimator that combines the readings in each window of
+    /// time.
+    pub fn window(window: Duration, agg: Aggregate) -> Self {
+        Self::new(Span::Window(window), agg)
+    }
+
+    fn new(span: Span, agg: Aggregate) -> Self {
+        Self {
+            span,
+            agg,
+            start: None,
+            acc: 0.0,
+            count: 0,
+        }
+    }
+
+    // Adds a reading to the current group.
+    fn add(&mut self, x: f64) {
+        self.acc = match (self.count, self.agg) {
+            (0, _) => x,
+            (_, Aggregate::Mean) => self.acc + x,
+            (_, Aggregate::Min) => self.acc.min(x),
+            (_, Aggregate::Max) => self.acc.max(x),
+        };
+        self.count += 1;
+    }
+
+    // Gets the combined value of the current group.
+    fn value(&self) -> f64 {
+        match self.agg {
+            Aggregate::Mean => self.acc / self.count as f64,
+            _ => self.acc,
+        }
+    }
+}
+
+impl Decimator for Downsample {
+    fn update(&mut self, x: Timestamped<f64>, out: &mut VecDeque<Timestamped<f64>>) {
+        match self.span {
+            Span::Count(n) => {
+                if self.start.is_none() {
+                    self.start = Some(x);
+                    self.count = 0;
+                }
+                self.add(x.value);
+                if self.count == n {
+                    if let Some(start) = self.start.take() {
+                        out.push_back(start.map(|_| self.value()));
+                    }
+                }
+            }
+            Span::Window(window) => {
+                let Some(start) = self.start
+                else {
+                    self.start = Some(x);
+                    self.add(x.value);
+                    return;
+                };
+
+                let elapsed = x.instant.saturating_duration_since(start.instant);
+                if elapsed < window {
+                    self.add(x.value);
+                    return;
+                }
+                out.push_back(start.map(|_| self.value()));
+
+                // Start the window that the reading falls in, which may be
+                // a few windows on if there was a gap
+                let skip = if window.is_zero() {
+                    elapsed
+                }
+                else {
+                    let n = elapsed.as_nanos() / window.as_nanos();
+                    Duration::from_nanos((n * window.as_nanos()) as u64)
+                };
+                self.start = Some(Timestamped {
+                    value: 0.0,
+                    instant: start.instant + skip,
+                    time: start.time + skip,
+                });
+                self.count = 0;
+                self.add(x.value);
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        self.start = None;
+        self.count = 0;
+    }
+}
+
+/// Interpolates the readings onto a fixed time grid.
+///
+/// The grid starts at the first reading, and each point on it is given a
+/// value that's linearly interpolated between the readings on either
+/// side, once the reading after it arrives. A gap in the readings is
+/// filled in by interpolation across it.
+#[derive(Debug, Clone, Copy)]
+pub struct Resample {
+    period: Duration,
+    // The last reading
+    prev: Option<Timestamped<f64>>,
+    // The offset of the next grid point from the last reading
+    next: Duration,
+}
+
+impl Resample {
+    /// Creates a resampler onto a grid with the period.
+    ///
+    /// A period of zero is treated as one microsecond.
+    pub fn new(period: Duration) -> Self {
+        Self {
+            period: period.max(Duration::from_micros(1)),
+            prev: None,
+            next: Duration::ZERO,
+        }
+    }
+}
+
+impl Decimator for Resample {
+    fn update(&mut self, x: Timestamped<f64>, out: &mut VecDeque<Timestamped<f64>>) {
+        let Some(prev) = self.prev
+        else {
+            out.push_back(x);
+            self.prev = Some(x);
+            self.next = self.period;
+            return;
+        };
+
+        let span = x.instant.saturating_duration_since(prev.instant);
+        if span.is_zero() {
+            return;
+        }
+        while self.next <= span {
+            let frac = self.next.as_secs_f64() / span.as_secs_f64();
+            out.push_back(Timestamped {
+                value: prev.value + frac * (x.value - prev.value),
+                instant: prev.instant + self.next,
+                time: prev.time + self.next,
+            });
+            self.next += self.period;
+        }
+        self.next -= span;
+        self.prev = Some(x);
+    }
+
+    fn reset(&mut self) {
+        self.prev = None;
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+/// An iterator of timestamped readings with a decimator applied.
+///
+/// This wraps any iterator of timestamped readings, like the one from the
+/// receiver of a sensor's `timestamped_channel()`, and yields the reduced
+/// readings.
+#[derive(Debug)]
+pub struct Decimated<I, D> {
+    iter: I,
+    dec: D,
+    out: VecDeque<Timestamped<f64>>,
+}
+
+impl<I, D, T> Decimated<I, D>
+where
+    I: Iterator<Item = Timestamped<T>>,
+    T: Into<f64>,
+    D: Decimator,
+{
+    /// Wraps an iterator of readings with a decimator.
+    pub fn new(iter: I, dec: D) -> Self {
+        Self {
+            iter,
+            dec,
+            out: VecDeque::new(),
+        }
+    }
+
+    /// Unwraps the iterator, discarding the decimator.
+    pub fn into_inner(self) -> I {
+        self.iter
+    }
+}
+
+impl<I, D, T> Iterator for Decimated<I, D>
+where
+    I: Iterator<Item = Timestamped<T>>,
+    T: Into<f64>,
+    D: Decimator,
+{
+    type Item = Timestamped<f64>;
+
+    fn next(&mut self) -> Option<Timestamped<f64>> {
+        loop {
+            if let Some(x) = self.out.pop_front() {
+                return Some(x);
+            }
+            let x = self.iter.next()?;
+            self.dec.update(x.map(Into::into), &mut self.out);
+        }
+    }
+}
+
+/// A stream of timestamped readings with a decimator applied.
+///
+/// This wraps any stream of timestamped readings, like the one from a
+/// sensor's `timestamped_stream()`, and yields the reduced readings.
+#[cfg(feature = "async")]
+pub struct DecimatedStream<St, D> {
+    stream: St,
+    dec: D,
+    out: VecDeque<Timestamped<f64>>,
+}
+
+#[cfg(feature = "async")]
+impl<St, D, T> DecimatedStream<St, D>
+where
+    St: Stream<Item = Timestamped<T>> + Unpin,
+    T: Into<f64>,
+    D: Decimator + Unpin,
+{
+    /// Wraps a stream of readings with a decimator.
+    pub fn new(stream: St, dec: D) -> Self {
+        Self {
+            stream,
+            dec,
+            out: VecDeque::new(),
+        }
+    }
+
+    /// Unwraps the stream, discarding the decimator.
+    pub fn into_inner(self) -> St {
+        self.stream
+    }
+}
+
+#[cfg(feature = "async")]
+impl<St, D, T> Stream for DecimatedStream<St, D>
+where
+    St: Stream<Item = Timestamped<T>> + Unpin,
+    T: Into<f64>,
+    D: Decimator + Unpin,
+{
+    type Item = Timestamped<f64>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Timestamped<f64>>> {
+        let this = &mut *self;
+        loop {
+            if let Some(x) = this.out.pop_front() {
+                return Poll::Ready(Some(x));
+            }
+            match Pin::new(&mut this.stream).poll_next(cx) {
+                Poll::Ready(Some(x)) => this.dec.update(x.map(Into::into), &mut this.out),
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    // Readings at 100 ms intervals, from a fixed start time
+    fn readings(xs: &[f64]) -> Vec<Timestamped<f64>> {
+        let t0 = Timestamped::now(0.0);
+        xs.iter()
+            .enumerate()
+            .map(|(i, &x)| {
+                let dt = Duration::from_millis(100 * i as u64);
+                Timestamped {
+                    value: x,
+                    instant: t0.instant + dt,
+                    time: t0.time + dt,
+                }
+            })
+            .collect()
+    }
+
+    fn run<D: Decimator>(dec: D, xs: &[f64]) -> Vec<f64> {
+        Decimated::new(readings(xs).into_iter(), dec)
+            .map(|x| x.value)
+            .collect()
+    }
+
+    #[test]
+    fn test_every_nth() {
+        let out = run(EveryNth::new(3), &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0]);
+        assert_eq!(out, vec![1.0, 4.0, 7.0]);
+    }
+
+    #[test]
+    fn test_downsample() {
+        let xs = [1.0, 5.0, 3.0, 2.0, 8.0, 4.0, 9.0];
+        assert_eq!(
+            run(Downsample::count(3, Aggregate::Mean), &xs),
+            vec![3.0, 14.0 / 3.0]
+        );
+        assert_eq!(
+            run(Downsample::count(2, Aggregate::Min), &xs),
+            vec![1.0, 2.0, 4.0]
+        );
+
+        // 250 ms windows hold 3, 2, and 3 readings, the last one incomplete
+        let win = Downsample::window(Duration::from_millis(250), Aggregate::Max);
+        assert_eq!(run(win, &xs), vec![5.0, 8.0]);
+
+        // A gap skips the empty windows, and stays on the grid
+        let mut dec = Downsample::window(Duration::from_millis(250), Aggregate::Mean);
+        let rs = readings(&[1.0, 3.0, 0.0, 0.0, 0.0, 0.0, 0.0, 7.0, 0.0, 0.0, 9.0]);
+        let mut out = VecDeque::new();
+        for i in [0, 1, 7, 10] {
+            dec.update(rs[i], &mut out);
+        }
+        let ts: Vec<_> = out
+            .iter()
+            .map(|x| (x.value, x.instant.duration_since(rs[0].instant)))
+            .collect();
+        assert_eq!(
+            ts,
+            vec![(2.0, Duration::ZERO), (7.0, Duration::from_millis(500))]
+        );
+    }
+
+    #[test]
+    fn test_resample() {
+        let t0 = Instant::now();
+        let out: Vec<_> = Decimated::new(
+            readings(&[0.0, 1.0, 2.0, 4.0]).into_iter(),
+            Resample::new(Duration::from_millis(150)),
+        )
+        .map(|x| (x.value, x.instant))
+        .collect();
+
+        let vals: Vec<_> = out.iter().map(|x| x.0).collect();
+        assert_eq!(vals, vec![0.0, 1.5, 4.0]);
+        assert_eq!(out[2].1 - out[0].1, Duration::from_millis(300));
+        assert!(out[0].1 >= t0);
+    }
+}
@@ -0,0 +1,395 @@
+// phidget-rs/src/devices/accelerometer.rs
+//
+// This file is part of the 'phidget-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+use crate::{
+    handler::Context, ChannelClass, Error, EventReceiver, GenericPhidget, HandlerGuard,
+    HandlerResult, HandlerScope, Phidget, Result, ReturnCode,
+};
+use phidget_sys::{self as ffi, PhidgetAccelerometerHandle, PhidgetHandle};
+use std::{
+    fmt, mem,
+    os::raw::{c_int, c_void},
+    ptr, slice,
+    sync::mpsc,
+};
+
+#[cfg(feature = "async")]
+use crate::stream::EventStream;
+
+/// The function signature for the safe Rust acceleration change callback.
+///
+/// This receives the acceleration on each axis, in g, and the timestamp of
+/// the sample, in milliseconds.
+pub type AccelerationChangeCallback = dyn FnMut(&Accelerometer, [f64; 3], f64) + Send + 'static;
+
+/////////////////////////////////////////////////////////////////////////////
+
+/// Phidget accelerometer
+pub struct Accelerometer {
+    // Handle to the accelerometer in the phidget22 library
+    chan: PhidgetAccelerometerHandle,
+}
+
+impl Accelerometer {
+    /// Create a new accelerometer.
+    ///
+    /// This panics if the library can't create the channel. Use
+    /// `try_new()` to get the error instead.
+    pub fn new() -> Self {
+        Self::try_new().expect("failed to create the channel")
+    }
+
+    /// Create a new accelerometer, returning an error if the library can't
+    /// create the channel.
+    pub fn try_new() -> Result<Self> {
+        let mut chan: PhidgetAccelerometerHandle = ptr::null_mut();
+        ReturnCode::result(unsafe { ffi::PhidgetAccelerometer_create(&mut chan) })?;
+        crate::phidget::install_handlers(chan as PhidgetHandle);
+        Ok(Self::from(chan))
+    }
+
+    // Low-level, unsafe, callback for the acceleration change event.
+    // The context holds a view of the channel and the safe Rust callback.
+    unsafe extern "C" fn on_acceleration_change<F, R>(
+        chan: PhidgetAccelerometerHandle,
+        ctx: *mut c_void,
+        acceleration: *const f64,
+        timestamp: f64,
+    ) where
+        F: FnMut(&Accelerometer, [f64; 3], f64) -> R + Send + 'static,
+        R: HandlerResult,
+    {
+        let mut accel = [0.0; 3];
+        if !acceleration.is_null() {
+            accel.copy_from_slice(slice::from_raw_parts(acceleration, 3));
+        }
+        crate::handler::deliver(chan as PhidgetHandle, ctx, move |phid, ctx| {
+            let ctx: &mut Context<Self, F> = &mut *(ctx as *mut _);
+            crate::handler::report(phid, (ctx.cb)(&ctx.chan, accel, timestamp));
+        });
+    }
+
+    /// Get a reference to the underlying accelerometer handle
+    pub fn as_channel(&self) -> &PhidgetAccelerometerHandle {
+        &self.chan
+    }
+
+    /// Gets the number of axes that the accelerometer measures.
+    pub fn axis_count(&self) -> Result<i32> {
+        crate::cache::property(self.chan as PhidgetHandle, "AxisCount", || {
+            let mut n: c_int = 0;
+            ReturnCode::result(unsafe {
+                traced!(ffi::PhidgetAccelerometer_getAxisCount(self.chan, &mut n))
+            })?;
+            Ok(n as i32)
+        })
+    }
+
+    /// Get the acceleration on each axis, in g
+    pub fn acceleration(&self) -> Result<[f64; 3]> {
+        let mut accel = [0.0; 3];
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetAccelerometer_getAcceleration(
+                self.chan, &mut accel
+            ))
+        })?;
+        Ok(accel)
+    }
+
+    /// Gets the minimum acceleration that can be measured on each axis,
+    /// in g.
+    ///
+    /// Together with `max_acceleration()`, this is the range of the
+    /// accelerometer.
+    pub fn min_acceleration(&self) -> Result<[f64; 3]> {
+        crate::cache::property(self.chan as PhidgetHandle, "MinAcceleration", || {
+            let mut accel = [0.0; 3];
+            ReturnCode::result(unsafe {
+                traced!(ffi::PhidgetAccelerometer_getMinAcceleration(
+                    self.chan, &mut accel
+                ))
+            })?;
+            Ok(accel)
+        })
+    }
+
+    /// Gets the maximum acceleration that can be measured on each axis,
+    /// in g.
+    pub fn max_acceleration(&self) -> Result<[f64; 3]> {
+        crate::cache::property(self.chan as PhidgetHandle, "MaxAcceleration", || {
+            let mut accel = [0.0; 3];
+            ReturnCode::result(unsafe {
+                traced!(ffi::PhidgetAccelerometer_getMaxAcceleration(
+                    self.chan, &mut accel
+                ))
+            })?;
+            Ok(accel)
+        })
+    }
+
+    /// Gets the minimum change in acceleration, in g, that triggers a
+    /// change event.
+    pub fn acceleration_change_trigger(&self) -> Result<f64> {
+        let mut trigger = 0.0;
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetAccelerometer_getAccelerationChangeTrigger(
+                self.chan,
+                &mut trigger
+            ))
+        })?;
+        Ok(trigger)
+    }
+
+    /// Sets the minimum change in acceleration, in g, that triggers a
+    /// change event. Zero reports every sample.
+    pub fn set_acceleration_change_trigger(&mut self, trigger: f64) -> Result<()> {
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetAccelerometer_setAccelerationChangeTrigger(
+                self.chan, trigger
+            ))
+        })
+    }
+
+    /// Gets the smallest change trigger that can be set.
+    pub fn min_acceleration_change_trigger(&self) -> Result<f64> {
+        crate::cache::property(
+            self.chan as PhidgetHandle,
+            "MinAccelerationChangeTrigger",
+            || {
+                let mut value = 0.0;
+                ReturnCode::result(unsafe {
+                    traced!(ffi::PhidgetAccelerometer_getMinAccelerationChangeTrigger(
+                        self.chan, &mut value
+                    ))
+                })?;
+                Ok(value)
+            },
+        )
+    }
+
+    /// Gets the largest change trigger that can be set.
+    pub fn max_acceleration_change_trigger(&self) -> Result<f64> {
+        crate::cache::property(
+            self.chan as PhidgetHandle,
+            "MaxAccelerationChangeTrigger",
+            || {
+                let mut value = 0.0;
+                ReturnCode::result(unsafe {
+                    traced!(ffi::PhidgetAccelerometer_getMaxAccelerationChangeTrigger(
+                        self.chan, &mut value
+                    ))
+                })?;
+                Ok(value)
+            },
+        )
+    }
+
+    /// Determines if the heating element of the accelerometer is enabled.
+    pub fn heating_enabled(&self) -> Result<bool> {
+        let mut enabled: c_int = 0;
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetAccelerometer_getHeatingEnabled(
+                self.chan,
+                &mut enabled
+            ))
+        })?;
+        Ok(enabled != 0)
+    }
+
+    /// Enables or disables the heating element of the accelerometer.
+    ///
+    /// On boards that have one, like the MOT0100, the heater keeps the
+    /// sensor within its operating temperature in a cold enclosure.
+    pub fn set_heating_enabled(&mut self, enabled: bool) -> Result<()> {
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetAccelerometer_setHeatingEnabled(
+                self.chan,
+                enabled as c_int
+            ))
+        })
+    }
+
+    /// Sets a handler to receive acceleration change callbacks, with the
+    /// acceleration on each axis and the timestamp of the sample.
+    /// The handler is removed when the returned guard is dropped.
+    pub fn set_on_acceleration_change_handler<F, R>(&self, cb: F) -> Result<HandlerGuard>
+    where
+        F: FnMut(&Accelerometer, [f64; 3], f64) -> R + Send + 'static,
+        R: HandlerResult,
+    {
+        let ctx = Context::into_raw(Self::from(self.chan), cb);
+
+        crate::handler::register::<Context<Self, F>, _>(
+            self.chan as PhidgetHandle,
+            "acceleration_change",
+            ctx,
+            || unsafe {
+                traced!(ffi::PhidgetAccelerometer_setOnAccelerationChangeHandler(
+                    self.chan,
+                    Some(Self::on_acceleration_change::<F, R>),
+                    ctx,
+                ))
+            },
+            |phid| unsafe {
+                traced!(ffi::PhidgetAccelerometer_setOnAccelerationChangeHandler(
+                    phid as _,
+                    None,
+                    ptr::null_mut()
+                ))
+            },
+        )
+    }
+
+    /// Set a handler to receive acceleration change callbacks, which can
+    /// borrow from the environment of the scope.
+    /// The handler is removed at the end of the scope.
+    pub fn set_on_acceleration_change_handler_scoped<'scope, F, R>(
+        &self,
+        scope: &'scope HandlerScope<'scope, '_>,
+        cb: F,
+    ) -> Result<()>
+    where
+        F: FnMut(&Accelerometer, [f64; 3], f64) -> R + Send + 'scope,
+        R: HandlerResult + 'static,
+    {
+        // SAFETY: The guard is held by the scope, which can't be forgotten,
+        // and which waits for any call to the handler. See `HandlerScope`.
+        let cb = unsafe {
+            mem::transmute::<
+                Box<dyn FnMut(&Accelerometer, [f64; 3], f64) -> R + Send + 'scope>,
+                Box<dyn FnMut(&Accelerometer, [f64; 3], f64) -> R + Send>,
+            >(Box::new(cb))
+        };
+        scope.hold(self.set_on_acceleration_change_handler(cb)?);
+        Ok(())
+    }
+
+    /// Removes the acceleration change handler, if one is set, and
+    /// releases its closure.
+    pub fn remove_on_acceleration_change_handler(&self) -> Result<()> {
+        crate::handler::unregister(self.chan as PhidgetHandle, "acceleration_change")
+    }
+
+    /// Gets a channel that receives the acceleration readings, fed by the
+    /// acceleration change events.
+    ///
+    /// This registers the channel's acceleration change handler, replacing
+    /// any handler that was previously set.
+    /// The handler is removed when the receiver is dropped.
+    pub fn acceleration_channel(&self) -> Result<EventReceiver<[f64; 3]>> {
+        let (tx, rx) = mpsc::channel();
+        let guard = self.set_on_acceleration_change_handler(move |_, a, _| {
+            let _ = tx.send(a);
+        })?;
+        Ok(EventReceiver::new(rx, guard))
+    }
+
+    /// Gets a stream of the acceleration readings, fed by the acceleration
+    /// change events.
+    ///
+    /// This registers the channel's acceleration change handler, replacing
+    /// any handler that was previously set.
+    /// The handler is removed when the stream is dropped.
+    #[cfg(feature = "async")]
+    pub fn acceleration_stream(&self) -> Result<EventStream<[f64; 3]>> {
+        let (mut tx, rx) = crate::stream::event_channel();
+        let guard = self.set_on_acceleration_change_handler(move |_, a, _| tx.send(a))?;
+        Ok(EventStream::new(rx, guard))
+    }
+}
+
+impl Phidget for Accelerometer {
+    fn as_handle(&mut self) -> PhidgetHandle {
+        self.chan as PhidgetHandle
+    }
+}
+
+impl fmt::Debug for Accelerometer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        crate::phidget::fmt_debug(f, "Accelerometer", self.chan as PhidgetHandle)
+    }
+}
+
+impl fmt::Display for Accelerometer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        crate::phidget::fmt_display(f, "Accelerometer", self.chan as PhidgetHandle)
+    }
+}
+
+unsafe impl Send for Accelerometer {}
+unsafe impl Sync for Accelerometer {}
+
+impl Default for Accelerometer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl From<PhidgetAccelerometerHandle> for Accelerometer {
+    fn from(chan: PhidgetAccelerometerHandle) -> Self {
+        Self { chan }
+    }
+}
+
+impl TryFrom<GenericPhidget> for Accelerometer {
+    type Error = Error;
+
+    /// Converts a generic phidget into a typed channel, if it's of the
+    /// right class. If the channel is open, it's closed when dropped.
+    fn try_from(ph: GenericPhidget) -> Result<Self> {
+        let chan = ph.into_channel(ChannelClass::Accelerometer)?;
+        Ok(Self::from(chan as PhidgetAccelerometerHandle))
+    }
+}
+
+impl Drop for Accelerometer {
+    fn drop(&mut self) {
+        if let Ok(true) = self.is_open() {
+            let _ = self.close();
+        }
+        let phid = self.chan as PhidgetHandle;
+        unsafe {
+            ffi::PhidgetAccelerometer_delete(&mut self.chan);
+        }
+        crate::notify::reset(phid);
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::*;
+    use crate::mock::{set_acceleration, set_property};
+
+    #[test]
+    fn test_mock_accelerometer() {
+        let mut accel = Accelerometer::new();
+        accel.open().unwrap();
+        accel.set_heating_enabled(true).unwrap();
+        assert_eq!(accel.heating_enabled(), Ok(true));
+        for (prop, val) in [
+            ("MaxAccelerationX", 8.0),
+            ("MaxAccelerationY", 8.0),
+            ("MaxAccelerationZ", 8.0),
+        ] {
+            set_property(&mut accel, prop, val).unwrap();
+        }
+        assert_eq!(accel.max_acceleration(), Ok([8.0; 3]));
+
+        let (tx, rx) = mpsc::channel();
+        let _handler = accel
+            .set_on_acceleration_change_handler(move |_, a, t| tx.send((a, t)).unwrap())
+            .unwrap();
+        set_acceleration(&mut accel, [0.0, 0.5, -1.0], 12.0).unwrap();
+        assert_eq!(rx.try_recv(), Ok(([0.0, 0.5, -1.0], 12.0)));
+        assert_eq!(accel.acceleration(), Ok([0.0, 0.5, -1.0]));
+    }
+}
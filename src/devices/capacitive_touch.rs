@@ -0,0 +1,463 @@
+// phidget-rs/src/devices/capacitive_touch.rs
+//
+// This file is part of the 'phidget-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+use crate::{
+    handler::Context, ChannelClass, Error, EventReceiver, GenericPhidget, HandlerGuard,
+    HandlerResult, HandlerScope, Phidget, Result, ReturnCode, Sensor,
+};
+use phidget_sys::{self as ffi, PhidgetCapacitiveTouchHandle, PhidgetHandle};
+use std::{
+    fmt, mem,
+    os::raw::{c_int, c_void},
+    ptr,
+    sync::mpsc,
+};
+
+#[cfg(feature = "async")]
+use crate::stream::EventStream;
+
+/// The function signature for the safe Rust touch callback.
+pub type TouchCallback = dyn FnMut(&CapacitiveTouch, f64) + Send + 'static;
+
+/////////////////////////////////////////////////////////////////////////////
+
+/// Phidget capacitive touch sensor
+pub struct CapacitiveTouch {
+    // Handle to the capacitive touch sensor in the phidget22 library
+    chan: PhidgetCapacitiveTouchHandle,
+}
+
+impl CapacitiveTouch {
+    /// Create a new capacitive touch sensor.
+    ///
+    /// This panics if the library can't create the channel. Use
+    /// `try_new()` to get the error instead.
+    pub fn new() -> Self {
+        Self::try_new().expect("failed to create the channel")
+    }
+
+    /// Create a new capacitive touch sensor, returning an error if the library can't
+    /// create the channel.
+    pub fn try_new() -> Result<Self> {
+        let mut chan: PhidgetCapacitiveTouchHandle = ptr::null_mut();
+        ReturnCode::result(unsafe { ffi::PhidgetCapacitiveTouch_create(&mut chan) })?;
+        crate::phidget::install_handlers(chan as PhidgetHandle);
+        Ok(Self::from(chan))
+    }
+
+    // Low-level, unsafe, callback for the touch event.
+    // The context holds a view of the channel and the safe Rust callback.
+    unsafe extern "C" fn on_touch<F, R>(
+        chan: PhidgetCapacitiveTouchHandle,
+        ctx: *mut c_void,
+        touch_value: f64,
+    ) where
+        F: FnMut(&CapacitiveTouch, f64) -> R + Send + 'static,
+        R: HandlerResult,
+    {
+        crate::handler::deliver(chan as PhidgetHandle, ctx, move |phid, ctx| {
+            let ctx: &mut Context<Self, F> = &mut *(ctx as *mut _);
+            crate::handler::report(phid, (ctx.cb)(&ctx.chan, touch_value));
+        });
+    }
+
+    // Low-level, unsafe, callback for the touch end event.
+    // The context holds a view of the channel and the safe Rust callback.
+    unsafe extern "C" fn on_touch_end<F, R>(chan: PhidgetCapacitiveTouchHandle, ctx: *mut c_void)
+    where
+        F: FnMut(&CapacitiveTouch) -> R + Send + 'static,
+        R: HandlerResult,
+    {
+        crate::handler::deliver(chan as PhidgetHandle, ctx, move |phid, ctx| {
+            let ctx: &mut Context<Self, F> = &mut *(ctx as *mut _);
+            crate::handler::report(phid, (ctx.cb)(&ctx.chan));
+        });
+    }
+
+    /// Get a reference to the underlying sensor handle
+    pub fn as_channel(&self) -> &PhidgetCapacitiveTouchHandle {
+        &self.chan
+    }
+
+    /// Gets the touch value, which is a measure of how firmly the sensor
+    /// is touched, or, for a slider or wheel, the position of the touch.
+    pub fn touch_value(&self) -> Result<f64> {
+        let mut v: f64 = 0.0;
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetCapacitiveTouch_getTouchValue(self.chan, &mut v))
+        })?;
+        Ok(v)
+    }
+
+    /// Gets the minimum change in the touch value that triggers a touch
+    /// event.
+    pub fn touch_value_change_trigger(&self) -> Result<f64> {
+        let mut trigger = 0.0;
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetCapacitiveTouch_getTouchValueChangeTrigger(
+                self.chan,
+                &mut trigger
+            ))
+        })?;
+        Ok(trigger)
+    }
+
+    /// Sets the minimum change in the touch value that triggers a touch
+    /// event.
+    pub fn set_touch_value_change_trigger(&mut self, trigger: f64) -> Result<()> {
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetCapacitiveTouch_setTouchValueChangeTrigger(
+                self.chan, trigger
+            ))
+        })
+    }
+
+    /// Determines if the sensor is currently being touched.
+    pub fn is_touched(&self) -> Result<bool> {
+        let mut touched: c_int = 0;
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetCapacitiveTouch_getIsTouched(
+                self.chan,
+                &mut touched
+            ))
+        })?;
+        Ok(touched != 0)
+    }
+
+    /// Gets the minimum touch value that the sensor reports.
+    pub fn min_touch_value(&self) -> Result<f64> {
+        crate::cache::property(self.chan as PhidgetHandle, "MinTouchValue", || {
+            let mut value = 0.0;
+            ReturnCode::result(unsafe {
+                traced!(ffi::PhidgetCapacitiveTouch_getMinTouchValue(
+                    self.chan, &mut value
+                ))
+            })?;
+            Ok(value)
+        })
+    }
+
+    /// Gets the maximum touch value that the sensor reports.
+    pub fn max_touch_value(&self) -> Result<f64> {
+        crate::cache::property(self.chan as PhidgetHandle, "MaxTouchValue", || {
+            let mut value = 0.0;
+            ReturnCode::result(unsafe {
+                traced!(ffi::PhidgetCapacitiveTouch_getMaxTouchValue(
+                    self.chan, &mut value
+                ))
+            })?;
+            Ok(value)
+        })
+    }
+
+    /// Gets the smallest change trigger that can be set.
+    pub fn min_touch_value_change_trigger(&self) -> Result<f64> {
+        crate::cache::property(
+            self.chan as PhidgetHandle,
+            "MinTouchValueChangeTrigger",
+            || {
+                let mut value = 0.0;
+                ReturnCode::result(unsafe {
+                    traced!(ffi::PhidgetCapacitiveTouch_getMinTouchValueChangeTrigger(
+                        self.chan, &mut value
+                    ))
+                })?;
+                Ok(value)
+            },
+        )
+    }
+
+    /// Gets the largest change trigger that can be set.
+    pub fn max_touch_value_change_trigger(&self) -> Result<f64> {
+        crate::cache::property(
+            self.chan as PhidgetHandle,
+            "MaxTouchValueChangeTrigger",
+            || {
+                let mut value = 0.0;
+                ReturnCode::result(unsafe {
+                    traced!(ffi::PhidgetCapacitiveTouch_getMaxTouchValueChangeTrigger(
+                        self.chan, &mut value
+                    ))
+                })?;
+                Ok(value)
+            },
+        )
+    }
+
+    /// Gets the sensitivity of the sensor.
+    pub fn sensitivity(&self) -> Result<f64> {
+        let mut value = 0.0;
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetCapacitiveTouch_getSensitivity(
+                self.chan, &mut value
+            ))
+        })?;
+        Ok(value)
+    }
+
+    /// Sets the sensitivity of the sensor.
+    ///
+    /// A higher sensitivity detects a touch through a thicker overlay, or
+    /// through gloves, but is more prone to false touches.
+    pub fn set_sensitivity(&mut self, sensitivity: f64) -> Result<()> {
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetCapacitiveTouch_setSensitivity(
+                self.chan,
+                sensitivity
+            ))
+        })
+    }
+
+    /// Gets the minimum sensitivity that can be set.
+    pub fn min_sensitivity(&self) -> Result<f64> {
+        crate::cache::property(self.chan as PhidgetHandle, "MinSensitivity", || {
+            let mut value = 0.0;
+            ReturnCode::result(unsafe {
+                traced!(ffi::PhidgetCapacitiveTouch_getMinSensitivity(
+                    self.chan, &mut value
+                ))
+            })?;
+            Ok(value)
+        })
+    }
+
+    /// Gets the maximum sensitivity that can be set.
+    pub fn max_sensitivity(&self) -> Result<f64> {
+        crate::cache::property(self.chan as PhidgetHandle, "MaxSensitivity", || {
+            let mut value = 0.0;
+            ReturnCode::result(unsafe {
+                traced!(ffi::PhidgetCapacitiveTouch_getMaxSensitivity(
+                    self.chan, &mut value
+                ))
+            })?;
+            Ok(value)
+        })
+    }
+
+    /// Sets a handler to receive touch callbacks, with the touch value.
+    /// The handler is removed when the returned guard is dropped.
+    pub fn set_on_touch_handler<F, R>(&self, cb: F) -> Result<HandlerGuard>
+    where
+        F: FnMut(&CapacitiveTouch, f64) -> R + Send + 'static,
+        R: HandlerResult,
+    {
+        let ctx = Context::into_raw(Self::from(self.chan), cb);
+
+        crate::handler::register::<Context<Self, F>, _>(
+            self.chan as PhidgetHandle,
+            "touch",
+            ctx,
+            || unsafe {
+                traced!(ffi::PhidgetCapacitiveTouch_setOnTouchHandler(
+                    self.chan,
+                    Some(Self::on_touch::<F, R>),
+                    ctx,
+                ))
+            },
+            |phid| unsafe {
+                traced!(ffi::PhidgetCapacitiveTouch_setOnTouchHandler(
+                    phid as _,
+                    None,
+                    ptr::null_mut()
+                ))
+            },
+        )
+    }
+
+    /// Set a handler to receive touch callbacks, with the touch value, which can borrow
+    /// from the environment of the scope.
+    /// The handler is removed at the end of the scope.
+    pub fn set_on_touch_handler_scoped<'scope, F, R>(
+        &self,
+        scope: &'scope HandlerScope<'scope, '_>,
+        cb: F,
+    ) -> Result<()>
+    where
+        F: FnMut(&CapacitiveTouch, f64) -> R + Send + 'scope,
+        R: HandlerResult + 'static,
+    {
+        // SAFETY: The guard is held by the scope, which can't be forgotten,
+        // and which waits for any call to the handler. See `HandlerScope`.
+        let cb = unsafe {
+            mem::transmute::<
+                Box<dyn FnMut(&CapacitiveTouch, f64) -> R + Send + 'scope>,
+                Box<dyn FnMut(&CapacitiveTouch, f64) -> R + Send>,
+            >(Box::new(cb))
+        };
+        scope.hold(self.set_on_touch_handler(cb)?);
+        Ok(())
+    }
+
+    /// Removes the touch handler, if one is set, and releases
+    /// its closure.
+    pub fn remove_on_touch_handler(&self) -> Result<()> {
+        crate::handler::unregister(self.chan as PhidgetHandle, "touch")
+    }
+
+    /// Sets a handler to receive touch end callbacks, when the sensor is
+    /// no longer touched.
+    /// The handler is removed when the returned guard is dropped.
+    pub fn set_on_touch_end_handler<F, R>(&self, cb: F) -> Result<HandlerGuard>
+    where
+        F: FnMut(&CapacitiveTouch) -> R + Send + 'static,
+        R: HandlerResult,
+    {
+        let ctx = Context::into_raw(Self::from(self.chan), cb);
+
+        crate::handler::register::<Context<Self, F>, _>(
+            self.chan as PhidgetHandle,
+            "touch_end",
+            ctx,
+            || unsafe {
+                traced!(ffi::PhidgetCapacitiveTouch_setOnTouchEndHandler(
+                    self.chan,
+                    Some(Self::on_touch_end::<F, R>),
+                    ctx,
+                ))
+            },
+            |phid| unsafe {
+                traced!(ffi::PhidgetCapacitiveTouch_setOnTouchEndHandler(
+                    phid as _,
+                    None,
+                    ptr::null_mut()
+                ))
+            },
+        )
+    }
+
+    /// Removes the touch end handler, if one is set, and releases its
+    /// closure.
+    pub fn remove_on_touch_end_handler(&self) -> Result<()> {
+        crate::handler::unregister(self.chan as PhidgetHandle, "touch_end")
+    }
+
+    /// Gets a channel that receives the touch values, fed by the touch events.
+    ///
+    /// This registers the channel's touch handler, replacing any handler
+    /// that was previously set.
+    /// The handler is removed when the receiver is dropped.
+    pub fn touch_channel(&self) -> Result<EventReceiver<f64>> {
+        let (tx, rx) = mpsc::channel();
+        let guard = self.set_on_touch_handler(move |_, v| {
+            let _ = tx.send(v);
+        })?;
+        Ok(EventReceiver::new(rx, guard))
+    }
+
+    /// Gets a stream of the touch values, fed by the touch events.
+    ///
+    /// This registers the channel's touch handler, replacing any handler
+    /// that was previously set.
+    /// The handler is removed when the stream is dropped.
+    #[cfg(feature = "async")]
+    pub fn touch_stream(&self) -> Result<EventStream<f64>> {
+        let (mut tx, rx) = crate::stream::event_channel();
+        let guard = self.set_on_touch_handler(move |_, v| tx.send(v))?;
+        Ok(EventStream::new(rx, guard))
+    }
+}
+
+impl Phidget for CapacitiveTouch {
+    fn as_handle(&mut self) -> PhidgetHandle {
+        self.chan as PhidgetHandle
+    }
+}
+
+impl Sensor for CapacitiveTouch {
+    type Value = f64;
+
+    fn value(&self) -> Result<f64> {
+        self.touch_value()
+    }
+
+    fn on_change<F>(&self, cb: F) -> Result<HandlerGuard>
+    where
+        F: FnMut(&Self, f64) + Send + 'static,
+    {
+        self.set_on_touch_handler(cb)
+    }
+}
+
+impl fmt::Debug for CapacitiveTouch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        crate::phidget::fmt_debug(f, "CapacitiveTouch", self.chan as PhidgetHandle)
+    }
+}
+
+impl fmt::Display for CapacitiveTouch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        crate::phidget::fmt_display(f, "CapacitiveTouch", self.chan as PhidgetHandle)
+    }
+}
+
+unsafe impl Send for CapacitiveTouch {}
+unsafe impl Sync for CapacitiveTouch {}
+
+impl Default for CapacitiveTouch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl From<PhidgetCapacitiveTouchHandle> for CapacitiveTouch {
+    fn from(chan: PhidgetCapacitiveTouchHandle) -> Self {
+        Self { chan }
+    }
+}
+
+impl TryFrom<GenericPhidget> for CapacitiveTouch {
+    type Error = Error;
+
+    /// Converts a generic phidget into a typed channel, if it's of the
+    /// right class. If the channel is open, it's closed when dropped.
+    fn try_from(ph: GenericPhidget) -> Result<Self> {
+        let chan = ph.into_channel(ChannelClass::CaptiveTouch)?;
+        Ok(Self::from(chan as PhidgetCapacitiveTouchHandle))
+    }
+}
+
+impl Drop for CapacitiveTouch {
+    fn drop(&mut self) {
+        if let Ok(true) = self.is_open() {
+            let _ = self.close();
+        }
+        let phid = self.chan as PhidgetHandle;
+        unsafe {
+            ffi::PhidgetCapacitiveTouch_delete(&mut self.chan);
+        }
+        crate::notify::reset(phid);
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::*;
+    use crate::mock::{set_property, set_value};
+
+    #[test]
+    fn test_mock_capacitive_touch() {
+        let mut touch = CapacitiveTouch::new();
+        touch.open().unwrap();
+        set_property(&mut touch, "MinSensitivity", 0.0).unwrap();
+        set_property(&mut touch, "MaxSensitivity", 1.0).unwrap();
+        touch.set_sensitivity(0.8).unwrap();
+        touch.set_touch_value_change_trigger(0.05).unwrap();
+        assert_eq!(touch.sensitivity(), Ok(0.8));
+        assert_eq!(touch.max_sensitivity(), Ok(1.0));
+        assert_eq!(touch.touch_value_change_trigger(), Ok(0.05));
+
+        let rx = touch.touch_channel().unwrap();
+        set_value(&mut touch, 0.25).unwrap();
+        assert_eq!(rx.try_recv(), Ok(0.25));
+        assert_eq!(touch.touch_value(), Ok(0.25));
+    }
+}
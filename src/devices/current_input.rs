@@ -0,0 +1,369 @@
+// phidget-rs/src/devices/current_input.rs
+//
+// This file is part of the 'phidget-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+use crate::{
+    devices::digital_input::PowerSupply, handler::Context, ChannelClass, Error, EventReceiver,
+    GenericPhidget, HandlerGuard, HandlerResult, HandlerScope, Phidget, Result, ReturnCode, Sensor,
+};
+use phidget_sys::{self as ffi, PhidgetCurrentInputHandle, PhidgetHandle};
+use std::{
+    fmt, mem,
+    os::raw::{c_uint, c_void},
+    ptr,
+    sync::mpsc,
+};
+
+#[cfg(feature = "async")]
+use crate::stream::EventStream;
+
+#[cfg(feature = "units")]
+use uom::si::{electric_current::ampere, f64::ElectricCurrent};
+
+/// The function signature for the safe Rust current change callback.
+pub type CurrentChangeCallback = dyn FnMut(&CurrentInput, f64) + Send + 'static;
+
+/////////////////////////////////////////////////////////////////////////////
+
+/// Phidget current input
+pub struct CurrentInput {
+    // Handle to the current input in the phidget22 library
+    chan: PhidgetCurrentInputHandle,
+}
+
+impl CurrentInput {
+    /// Create a new current input.
+    ///
+    /// This panics if the library can't create the channel. Use
+    /// `try_new()` to get the error instead.
+    pub fn new() -> Self {
+        Self::try_new().expect("failed to create the channel")
+    }
+
+    /// Create a new current input, returning an error if the library can't
+    /// create the channel.
+    pub fn try_new() -> Result<Self> {
+        let mut chan: PhidgetCurrentInputHandle = ptr::null_mut();
+        ReturnCode::result(unsafe { ffi::PhidgetCurrentInput_create(&mut chan) })?;
+        crate::phidget::install_handlers(chan as PhidgetHandle);
+        Ok(Self::from(chan))
+    }
+
+    // Low-level, unsafe, callback for the current change event.
+    // The context holds a view of the channel and the safe Rust callback.
+    unsafe extern "C" fn on_current_change<F, R>(
+        chan: PhidgetCurrentInputHandle,
+        ctx: *mut c_void,
+        current: f64,
+    ) where
+        F: FnMut(&CurrentInput, f64) -> R + Send + 'static,
+        R: HandlerResult,
+    {
+        crate::handler::deliver(chan as PhidgetHandle, ctx, move |phid, ctx| {
+            let ctx: &mut Context<Self, F> = &mut *(ctx as *mut _);
+            crate::handler::report(phid, (ctx.cb)(&ctx.chan, current));
+        });
+    }
+
+    /// Get a reference to the underlying sensor handle
+    pub fn as_channel(&self) -> &PhidgetCurrentInputHandle {
+        &self.chan
+    }
+
+    /// Get the current on the input channel, in amps
+    pub fn current(&self) -> Result<f64> {
+        let mut v: f64 = 0.0;
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetCurrentInput_getCurrent(self.chan, &mut v))
+        })?;
+        Ok(v)
+    }
+
+    /// Read the current as a typed quantity.
+    #[cfg(feature = "units")]
+    pub fn current_quantity(&self) -> Result<ElectricCurrent> {
+        Ok(ElectricCurrent::new::<ampere>(self.current()?))
+    }
+
+    /// Gets the minimum change in current, in amps, that triggers a change
+    /// event.
+    pub fn current_change_trigger(&self) -> Result<f64> {
+        let mut trigger = 0.0;
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetCurrentInput_getCurrentChangeTrigger(
+                self.chan,
+                &mut trigger
+            ))
+        })?;
+        Ok(trigger)
+    }
+
+    /// Sets the minimum change in current, in amps, that triggers a change
+    /// event. Zero reports every reading.
+    pub fn set_current_change_trigger(&mut self, trigger: f64) -> Result<()> {
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetCurrentInput_setCurrentChangeTrigger(
+                self.chan, trigger
+            ))
+        })
+    }
+
+    /// Gets the minimum current that the channel can measure, in amps.
+    pub fn min_current(&self) -> Result<f64> {
+        crate::cache::property(self.chan as PhidgetHandle, "MinCurrent", || {
+            let mut value = 0.0;
+            ReturnCode::result(unsafe {
+                traced!(ffi::PhidgetCurrentInput_getMinCurrent(
+                    self.chan, &mut value
+                ))
+            })?;
+            Ok(value)
+        })
+    }
+
+    /// Gets the maximum current that the channel can measure, in amps.
+    pub fn max_current(&self) -> Result<f64> {
+        crate::cache::property(self.chan as PhidgetHandle, "MaxCurrent", || {
+            let mut value = 0.0;
+            ReturnCode::result(unsafe {
+                traced!(ffi::PhidgetCurrentInput_getMaxCurrent(
+                    self.chan, &mut value
+                ))
+            })?;
+            Ok(value)
+        })
+    }
+
+    /// Gets the power supply voltage for the sensor on the channel.
+    pub fn power_supply(&self) -> Result<PowerSupply> {
+        let mut ps: ffi::Phidget_PowerSupply = 0;
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetCurrentInput_getPowerSupply(self.chan, &mut ps))
+        })?;
+        PowerSupply::try_from(ps)
+    }
+
+    /// Sets the power supply voltage for the sensor on the channel, like
+    /// the loop supply for a 4-20mA transmitter.
+    pub fn set_power_supply(&mut self, power_supply: PowerSupply) -> Result<()> {
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetCurrentInput_setPowerSupply(
+                self.chan,
+                power_supply as c_uint
+            ))
+        })
+    }
+
+    /// Set a handler to receive current change callbacks with the value as a
+    /// typed quantity.
+    /// This replaces any current change handler that was previously set.
+    /// The handler is removed when the returned guard is dropped.
+    #[cfg(feature = "units")]
+    pub fn set_on_current_change_quantity_handler<F, R>(&self, mut cb: F) -> Result<HandlerGuard>
+    where
+        F: FnMut(&CurrentInput, ElectricCurrent) -> R + Send + 'static,
+        R: HandlerResult,
+    {
+        self.set_on_current_change_handler(move |ch, val| {
+            cb(ch, ElectricCurrent::new::<ampere>(val))
+        })
+    }
+
+    /// Sets a handler to receive current change callbacks.
+    /// The handler is removed when the returned guard is dropped.
+    pub fn set_on_current_change_handler<F, R>(&self, cb: F) -> Result<HandlerGuard>
+    where
+        F: FnMut(&CurrentInput, f64) -> R + Send + 'static,
+        R: HandlerResult,
+    {
+        let ctx = Context::into_raw(Self::from(self.chan), cb);
+
+        crate::handler::register::<Context<Self, F>, _>(
+            self.chan as PhidgetHandle,
+            "current_change",
+            ctx,
+            || unsafe {
+                traced!(ffi::PhidgetCurrentInput_setOnCurrentChangeHandler(
+                    self.chan,
+                    Some(Self::on_current_change::<F, R>),
+                    ctx,
+                ))
+            },
+            |phid| unsafe {
+                traced!(ffi::PhidgetCurrentInput_setOnCurrentChangeHandler(
+                    phid as _,
+                    None,
+                    ptr::null_mut()
+                ))
+            },
+        )
+    }
+
+    /// Set a handler to receive current change callbacks, which can borrow
+    /// from the environment of the scope.
+    /// The handler is removed at the end of the scope.
+    pub fn set_on_current_change_handler_scoped<'scope, F, R>(
+        &self,
+        scope: &'scope HandlerScope<'scope, '_>,
+        cb: F,
+    ) -> Result<()>
+    where
+        F: FnMut(&CurrentInput, f64) -> R + Send + 'scope,
+        R: HandlerResult + 'static,
+    {
+        // SAFETY: The guard is held by the scope, which can't be forgotten,
+        // and which waits for any call to the handler. See `HandlerScope`.
+        let cb = unsafe {
+            mem::transmute::<
+                Box<dyn FnMut(&CurrentInput, f64) -> R + Send + 'scope>,
+                Box<dyn FnMut(&CurrentInput, f64) -> R + Send>,
+            >(Box::new(cb))
+        };
+        scope.hold(self.set_on_current_change_handler(cb)?);
+        Ok(())
+    }
+
+    /// Removes the current change handler, if one is set, and releases
+    /// its closure.
+    pub fn remove_on_current_change_handler(&self) -> Result<()> {
+        crate::handler::unregister(self.chan as PhidgetHandle, "current_change")
+    }
+
+    /// Gets a channel that receives the current readings, fed by the current change events.
+    ///
+    /// This registers the channel's current change handler, replacing any handler
+    /// that was previously set.
+    /// The handler is removed when the receiver is dropped.
+    pub fn current_channel(&self) -> Result<EventReceiver<f64>> {
+        let (tx, rx) = mpsc::channel();
+        let guard = self.set_on_current_change_handler(move |_, v| {
+            let _ = tx.send(v);
+        })?;
+        Ok(EventReceiver::new(rx, guard))
+    }
+
+    /// Gets a stream of the current readings, fed by the current change events.
+    ///
+    /// This registers the channel's current change handler, replacing any handler
+    /// that was previously set.
+    /// The handler is removed when the stream is dropped.
+    #[cfg(feature = "async")]
+    pub fn current_stream(&self) -> Result<EventStream<f64>> {
+        let (mut tx, rx) = crate::stream::event_channel();
+        let guard = self.set_on_current_change_handler(move |_, v| tx.send(v))?;
+        Ok(EventStream::new(rx, guard))
+    }
+}
+
+impl Phidget for CurrentInput {
+    fn as_handle(&mut self) -> PhidgetHandle {
+        self.chan as PhidgetHandle
+    }
+}
+
+impl Sensor for CurrentInput {
+    type Value = f64;
+
+    fn value(&self) -> Result<f64> {
+        self.current()
+    }
+
+    fn on_change<F>(&self, cb: F) -> Result<HandlerGuard>
+    where
+        F: FnMut(&Self, f64) + Send + 'static,
+    {
+        self.set_on_current_change_handler(cb)
+    }
+}
+
+impl fmt::Debug for CurrentInput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        crate::phidget::fmt_debug(f, "CurrentInput", self.chan as PhidgetHandle)
+    }
+}
+
+impl fmt::Display for CurrentInput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        crate::phidget::fmt_display(f, "CurrentInput", self.chan as PhidgetHandle)
+    }
+}
+
+unsafe impl Send for CurrentInput {}
+unsafe impl Sync for CurrentInput {}
+
+impl Default for CurrentInput {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl From<PhidgetCurrentInputHandle> for CurrentInput {
+    fn from(chan: PhidgetCurrentInputHandle) -> Self {
+        Self { chan }
+    }
+}
+
+impl TryFrom<GenericPhidget> for CurrentInput {
+    type Error = Error;
+
+    /// Converts a generic phidget into a typed channel, if it's of the
+    /// right class. If the channel is open, it's closed when dropped.
+    fn try_from(ph: GenericPhidget) -> Result<Self> {
+        let chan = ph.into_channel(ChannelClass::CurrentInput)?;
+        Ok(Self::from(chan as PhidgetCurrentInputHandle))
+    }
+}
+
+impl Drop for CurrentInput {
+    fn drop(&mut self) {
+        if let Ok(true) = self.is_open() {
+            let _ = self.close();
+        }
+        let phid = self.chan as PhidgetHandle;
+        unsafe {
+            ffi::PhidgetCurrentInput_delete(&mut self.chan);
+        }
+        crate::notify::reset(phid);
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::*;
+    use crate::mock::{set_property, set_value};
+
+    #[test]
+    fn test_mock_current_power_supply() {
+        let mut cin = CurrentInput::new();
+        cin.open().unwrap();
+        cin.set_power_supply(PowerSupply::V24).unwrap();
+        assert_eq!(cin.power_supply(), Ok(PowerSupply::V24));
+
+        // The values are passed through as the library's constants
+        set_property(&mut cin, "PowerSupply", 1.0).unwrap();
+        assert_eq!(cin.power_supply(), Ok(PowerSupply::OFF));
+    }
+
+    #[test]
+    fn test_mock_current_change_trigger() {
+        let mut cin = CurrentInput::new();
+        cin.open().unwrap();
+        cin.set_current_change_trigger(0.0005).unwrap();
+        assert_eq!(cin.current_change_trigger(), Ok(0.0005));
+
+        // Only changes of at least the trigger are reported
+        let rx = cin.current_channel().unwrap();
+        for amps in [0.0120, 0.0122, 0.0118, 0.0130] {
+            set_value(&mut cin, amps).unwrap();
+        }
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), [0.0120, 0.0130]);
+    }
+}
@@ -0,0 +1,299 @@
+// phidget-rs/src/devices/dictionary.rs
+//
+// This file is part of the 'phidget-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+use crate::{ChannelClass, Error, GenericPhidget, Phidget, Result, ReturnCode};
+use phidget_sys::{self as ffi, PhidgetDictionaryHandle, PhidgetHandle};
+use std::{
+    ffi::{CStr, CString},
+    fmt,
+    os::raw::c_char,
+    ptr,
+};
+
+// The size of the buffer for reading a value, which is larger than the
+// longest value the server accepts
+const VALUE_BUF_LEN: usize = 64 * 1024;
+
+// The size of the buffer for each scan of the keys
+const SCAN_BUF_LEN: usize = 16 * 1024;
+
+// Converts a key or value to a C string.
+fn cstring(s: &str) -> Result<CString> {
+    CString::new(s).map_err(|_| Error::InvalidArg)
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+/// Phidget dictionary
+///
+/// A dictionary is a set of string key/value pairs kept by a Phidget
+/// network server, and shared by all the clients that open it. It's
+/// opened by the serial number given to it in the server's configuration.
+pub struct Dictionary {
+    // Handle to the dictionary in the phidget22 library
+    chan: PhidgetDictionaryHandle,
+}
+
+impl Dictionary {
+    /// Create a new dictionary channel.
+    ///
+    /// This panics if the library can't create the channel. Use
+    /// `try_new()` to get the error instead.
+    pub fn new() -> Self {
+        Self::try_new().expect("failed to create the channel")
+    }
+
+    /// Create a new dictionary channel, returning an error if the library
+    /// can't create the channel.
+    pub fn try_new() -> Result<Self> {
+        let mut chan: PhidgetDictionaryHandle = ptr::null_mut();
+        ReturnCode::result(unsafe { ffi::PhidgetDictionary_create(&mut chan) })?;
+        crate::phidget::install_handlers(chan as PhidgetHandle);
+        Ok(Self::from(chan))
+    }
+
+    /// Get a reference to the underlying dictionary handle
+    pub fn as_channel(&self) -> &PhidgetDictionaryHandle {
+        &self.chan
+    }
+
+    /// Gets the value for a key, or `None` if the key isn't in the
+    /// dictionary.
+    pub fn get(&self, key: &str) -> Result<Option<String>> {
+        let key = cstring(key)?;
+        let mut buf = vec![0 as c_char; VALUE_BUF_LEN];
+        let rc = unsafe {
+            traced!(ffi::PhidgetDictionary_get(
+                self.chan,
+                key.as_ptr(),
+                buf.as_mut_ptr(),
+                buf.len()
+            ))
+        };
+        if rc == ffi::PhidgetReturnCode_EPHIDGET_NOENT {
+            return Ok(None);
+        }
+        ReturnCode::result(rc)?;
+        let val = unsafe { CStr::from_ptr(buf.as_ptr()) };
+        Ok(Some(val.to_string_lossy().into()))
+    }
+
+    /// Sets the value for a key, adding the key if it isn't in the
+    /// dictionary.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<()> {
+        let (key, value) = (cstring(key)?, cstring(value)?);
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetDictionary_set(
+                self.chan,
+                key.as_ptr(),
+                value.as_ptr()
+            ))
+        })
+    }
+
+    /// Adds a key and its value, failing if the key is already in the
+    /// dictionary.
+    pub fn add(&mut self, key: &str, value: &str) -> Result<()> {
+        let (key, value) = (cstring(key)?, cstring(value)?);
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetDictionary_add(
+                self.chan,
+                key.as_ptr(),
+                value.as_ptr()
+            ))
+        })
+    }
+
+    /// Updates the value of a key, failing if the key isn't in the
+    /// dictionary.
+    pub fn update(&mut self, key: &str, value: &str) -> Result<()> {
+        let (key, value) = (cstring(key)?, cstring(value)?);
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetDictionary_update(
+                self.chan,
+                key.as_ptr(),
+                value.as_ptr()
+            ))
+        })
+    }
+
+    /// Removes a key from the dictionary.
+    pub fn remove(&mut self, key: &str) -> Result<()> {
+        let key = cstring(key)?;
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetDictionary_remove(self.chan, key.as_ptr()))
+        })
+    }
+
+    /// Removes all the keys from the dictionary.
+    pub fn remove_all(&mut self) -> Result<()> {
+        ReturnCode::result(unsafe { traced!(ffi::PhidgetDictionary_removeAll(self.chan)) })
+    }
+
+    // Gets the keys, in order, starting at the one specified.
+    // The list is limited by the size of the buffer, so may not be all of
+    // the remaining keys.
+    fn scan_from(&self, start: &str) -> Result<Vec<String>> {
+        let start = cstring(start)?;
+        let mut buf = vec![0 as c_char; SCAN_BUF_LEN];
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetDictionary_scan(
+                self.chan,
+                start.as_ptr(),
+                buf.as_mut_ptr(),
+                buf.len()
+            ))
+        })?;
+        let list = unsafe { CStr::from_ptr(buf.as_ptr()) };
+        Ok(list
+            .to_string_lossy()
+            .lines()
+            .filter(|k| !k.is_empty())
+            .map(String::from)
+            .collect())
+    }
+
+    /// Gets the keys that start with the prefix, in order.
+    ///
+    /// An empty prefix gets all of the keys in the dictionary.
+    pub fn scan(&self, prefix: &str) -> Result<Vec<String>> {
+        let mut keys: Vec<String> = Vec::new();
+        let mut start = prefix.to_string();
+
+        // The keys come back in order, from the start key, as many as fit
+        // in the buffer, so the scan continues from the last one until
+        // it's past the prefix or there are no new keys
+        loop {
+            let batch = self.scan_from(&start)?;
+            let n = keys.len();
+            for key in batch {
+                if !key.starts_with(prefix) {
+                    return Ok(keys);
+                }
+                if keys.last().map_or(true, |last| key > *last) {
+                    keys.push(key);
+                }
+            }
+            match keys.last() {
+                Some(last) if keys.len() > n => start.clone_from(last),
+                _ => return Ok(keys),
+            }
+        }
+    }
+
+    /// Gets the key/value pairs in the dictionary, in the order of the
+    /// keys.
+    ///
+    /// This is a snapshot of the dictionary when it's called. A key that's
+    /// removed while the values are being read is left out.
+    pub fn iter(&self) -> Result<impl Iterator<Item = (String, String)>> {
+        let mut pairs = Vec::new();
+        for key in self.scan("")? {
+            if let Some(val) = self.get(&key)? {
+                pairs.push((key, val));
+            }
+        }
+        Ok(pairs.into_iter())
+    }
+}
+
+impl Phidget for Dictionary {
+    fn as_handle(&mut self) -> PhidgetHandle {
+        self.chan as PhidgetHandle
+    }
+}
+
+impl fmt::Debug for Dictionary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        crate::phidget::fmt_debug(f, "Dictionary", self.chan as PhidgetHandle)
+    }
+}
+
+impl fmt::Display for Dictionary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        crate::phidget::fmt_display(f, "Dictionary", self.chan as PhidgetHandle)
+    }
+}
+
+unsafe impl Send for Dictionary {}
+unsafe impl Sync for Dictionary {}
+
+impl Default for Dictionary {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl From<PhidgetDictionaryHandle> for Dictionary {
+    fn from(chan: PhidgetDictionaryHandle) -> Self {
+        Self { chan }
+    }
+}
+
+impl TryFrom<GenericPhidget> for Dictionary {
+    type Error = Error;
+
+    /// Converts a generic phidget into a typed channel, if it's of the
+    /// right class. If the channel is open, it's closed when dropped.
+    fn try_from(ph: GenericPhidget) -> Result<Self> {
+        let chan = ph.into_channel(ChannelClass::Dictionary)?;
+        Ok(Self::from(chan as PhidgetDictionaryHandle))
+    }
+}
+
+impl Drop for Dictionary {
+    fn drop(&mut self) {
+        if let Ok(true) = self.is_open() {
+            let _ = self.close();
+        }
+        let phid = self.chan as PhidgetHandle;
+        unsafe {
+            ffi::PhidgetDictionary_delete(&mut self.chan);
+        }
+        crate::notify::reset(phid);
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mock_dictionary_scan() {
+        let mut dict = Dictionary::new();
+        dict.open().unwrap();
+        dict.set("motor/speed", "120").unwrap();
+        dict.set("motor/dir", "fwd").unwrap();
+        dict.set("mode", "auto").unwrap();
+        dict.set("name", "cell 1").unwrap();
+        assert_eq!(dict.get("mode"), Ok(Some("auto".into())));
+        assert_eq!(dict.get("missing"), Ok(None));
+
+        assert_eq!(
+            dict.scan("motor/"),
+            Ok(vec!["motor/dir".into(), "motor/speed".into()])
+        );
+        assert_eq!(dict.scan("z"), Ok(vec![]));
+
+        let pairs: Vec<_> = dict.iter().unwrap().collect();
+        assert_eq!(pairs[0], ("mode".into(), "auto".into()));
+        assert_eq!(pairs.len(), 4);
+
+        // More keys than fit in one scan of the library
+        for i in 0..2000 {
+            dict.set(&format!("log/{i:05}"), "x").unwrap();
+        }
+        let keys = dict.scan("log/").unwrap();
+        assert_eq!(keys.len(), 2000);
+        assert_eq!(keys[1999], "log/01999");
+    }
+}
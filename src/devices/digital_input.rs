@@ -10,16 +10,23 @@
 // to those terms.
 //
 
-use crate::{AttachCallback, DetachCallback, Error, GenericPhidget, Phidget, Result, ReturnCode};
+use crate::{
+    handler::Context, ChannelClass, Error, EventReceiver, GenericPhidget, HandlerGuard,
+    HandlerResult, HandlerScope, Phidget, Result, ReturnCode, Sensor,
+};
 use phidget_sys::{self as ffi, PhidgetDigitalInputHandle, PhidgetHandle};
 use std::{
-    mem,
+    fmt, mem,
     os::raw::{c_int, c_uint, c_void},
     ptr,
+    sync::mpsc,
 };
 
+#[cfg(feature = "async")]
+use crate::stream::EventStream;
+
 /// The function signature for the safe Rust digital input state change callback.
-pub type DigitalInputCallback = dyn Fn(&DigitalInput, i32) + Send + 'static;
+pub type DigitalInputCallback = dyn FnMut(&DigitalInput, i32) + Send + 'static;
 
 /////////////////////////////////////////////////////////////////////////////
 
@@ -27,23 +34,18 @@ pub type DigitalInputCallback = dyn Fn(&DigitalInput, i32) + Send + 'static;
 pub struct DigitalInput {
     // Handle to the digital input in the phidget22 library
     chan: PhidgetDigitalInputHandle,
-    // Double-boxed DigitalInputCallback, if registered
-    cb: Option<*mut c_void>,
-    // Double-boxed attach callback, if registered
-    attach_cb: Option<*mut c_void>,
-    // Double-boxed detach callback, if registered
-    detach_cb: Option<*mut c_void>,
 }
 
 /// InputMode for digital input
 /// <http://perk-software.cs.queensu.ca/plus/doc/nightly/dev/phidget22_8h.html#a5ad0740978daad6539d3a8249607bd46>
 #[derive(Copy, Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u32)]
 pub enum InputMode {
     /// For using sensors with PNP transistor outputs.
-    PNP = 0,
+    PNP = ffi::Phidget_InputMode_INPUT_MODE_PNP,
     /// For using sensors with NPN transistor outputs.
-    NPN,
+    NPN = ffi::Phidget_InputMode_INPUT_MODE_NPN,
 }
 
 impl TryFrom<u32> for InputMode {
@@ -52,9 +54,9 @@ impl TryFrom<u32> for InputMode {
     fn try_from(value: u32) -> Result<Self> {
         use InputMode::*;
         match value {
-            0 => Ok(PNP),
-            1 => Ok(NPN),
-            _ => Err(ReturnCode::UnknownVal),
+            ffi::Phidget_InputMode_INPUT_MODE_PNP => Ok(PNP),
+            ffi::Phidget_InputMode_INPUT_MODE_NPN => Ok(NPN),
+            _ => Err(Error::InvalidArg),
         }
     }
 }
@@ -62,14 +64,15 @@ impl TryFrom<u32> for InputMode {
 /// PowerSupply for digital input
 /// <http://perk-software.cs.queensu.ca/plus/doc/nightly/dev/phidget22_8h.html#a0293d3a21e8de247c4b562ceda897876>
 #[derive(Copy, Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u32)]
 pub enum PowerSupply {
-    /// OFF: cannot find docs
-    OFF = 1,
-    /// V12: cannot find docs
-    V12,
-    /// v24: cannot find docs
-    V24,
+    /// OFF: The power supply to the sensor is off
+    OFF = ffi::Phidget_PowerSupply_POWER_SUPPLY_OFF,
+    /// V12: A 12V power supply to the sensor
+    V12 = ffi::Phidget_PowerSupply_POWER_SUPPLY_12V,
+    /// V24: A 24V power supply to the sensor
+    V24 = ffi::Phidget_PowerSupply_POWER_SUPPLY_24V,
 }
 
 impl TryFrom<u32> for PowerSupply {
@@ -78,28 +81,52 @@ impl TryFrom<u32> for PowerSupply {
     fn try_from(value: u32) -> Result<Self> {
         use PowerSupply::*;
         match value {
-            1 => Ok(OFF),
-            2 => Ok(V12),
-            3 => Ok(V24),
-            _ => Err(ReturnCode::UnknownVal),
+            ffi::Phidget_PowerSupply_POWER_SUPPLY_OFF => Ok(OFF),
+            ffi::Phidget_PowerSupply_POWER_SUPPLY_12V => Ok(V12),
+            ffi::Phidget_PowerSupply_POWER_SUPPLY_24V => Ok(V24),
+            _ => Err(Error::InvalidArg),
         }
     }
 }
 
 impl DigitalInput {
     /// Create a new digital input.
+    ///
+    /// This panics if the library can't create the channel. Use
+    /// `try_new()` to get the error instead.
     pub fn new() -> Self {
+        Self::try_new().expect("failed to create the channel")
+    }
+
+    /// Create a new digital input, returning an error if the library can't
+    /// create the channel.
+    pub fn try_new() -> Result<Self> {
         let mut chan: PhidgetDigitalInputHandle = ptr::null_mut();
-        unsafe {
-            ffi::PhidgetDigitalInput_create(&mut chan);
-        }
-        Self::from(chan)
+        ReturnCode::result(unsafe { ffi::PhidgetDigitalInput_create(&mut chan) })?;
+        crate::phidget::install_handlers(chan as PhidgetHandle);
+        Ok(Self::from(chan))
+    }
+
+    /// Create a new digital input that uses a port of a VINT hub directly
+    /// as its input, in hub port mode.
+    ///
+    /// This selects the hub by its serial number and the port by its
+    /// number. The channel still needs to be opened.
+    pub fn on_hub_port(serial: i32, port: i32) -> Result<Self> {
+        let mut ch = Self::try_new()?;
+        ch.set_is_hub_port_device(true)?;
+        ch.set_hub_port(port)?;
+        ch.set_serial_number(serial)?;
+        Ok(ch)
     }
 
     /// Set input mode
     pub fn set_input_mode(&mut self, input_mode: InputMode) -> Result<()> {
         ReturnCode::result(unsafe {
-            ffi::PhidgetDigitalInput_setInputMode(self.chan, input_mode as c_uint)
+            traced!(ffi::PhidgetDigitalInput_setInputMode(
+                self.chan,
+                input_mode as c_uint
+            ))
         })?;
         Ok(())
     }
@@ -107,14 +134,19 @@ impl DigitalInput {
     /// Get input mode
     pub fn input_mode(&self) -> Result<InputMode> {
         let mut im: ffi::Phidget_InputMode = 0;
-        ReturnCode::result(unsafe { ffi::PhidgetDigitalInput_getInputMode(self.chan, &mut im) })?;
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetDigitalInput_getInputMode(self.chan, &mut im))
+        })?;
         InputMode::try_from(im)
     }
 
     /// Set power supply
     pub fn set_power_supply(&mut self, power_supply: PowerSupply) -> Result<()> {
         ReturnCode::result(unsafe {
-            ffi::PhidgetDigitalInput_setPowerSupply(self.chan, power_supply as c_uint)
+            traced!(ffi::PhidgetDigitalInput_setPowerSupply(
+                self.chan,
+                power_supply as c_uint
+            ))
         })?;
         Ok(())
     }
@@ -122,32 +154,37 @@ impl DigitalInput {
     /// Get power supply
     pub fn power_supply(&self) -> Result<PowerSupply> {
         let mut ps: ffi::Phidget_PowerSupply = 0;
-        ReturnCode::result(unsafe { ffi::PhidgetDigitalInput_getPowerSupply(self.chan, &mut ps) })?;
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetDigitalInput_getPowerSupply(self.chan, &mut ps))
+        })?;
         PowerSupply::try_from(ps)
     }
 
     /// Get the state of the digital input channel
     pub fn state(&self) -> Result<bool> {
         let mut value = 0;
-        ReturnCode::result(unsafe { ffi::PhidgetDigitalInput_getState(self.chan, &mut value) })?;
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetDigitalInput_getState(self.chan, &mut value))
+        })?;
         Ok(value != 0)
     }
 
     // ---------------------------------------------------
 
     // Low-level, unsafe, callback for the digital input state change event.
-    // The context is a double-boxed pointer to the safe Rust callback.
-    unsafe extern "C" fn on_state_change(
+    // The context holds a view of the channel and the safe Rust callback.
+    unsafe extern "C" fn on_state_change<F, R>(
         chan: PhidgetDigitalInputHandle,
         ctx: *mut c_void,
         state: c_int,
-    ) {
-        if !ctx.is_null() {
-            let cb: &mut Box<DigitalInputCallback> = &mut *(ctx as *mut _);
-            let sensor = Self::from(chan);
-            cb(&sensor, state as i32);
-            mem::forget(sensor);
-        }
+    ) where
+        F: FnMut(&DigitalInput, i32) -> R + Send + 'static,
+        R: HandlerResult,
+    {
+        crate::handler::deliver(chan as PhidgetHandle, ctx, move |phid, ctx| {
+            let ctx: &mut Context<Self, F> = &mut *(ctx as *mut _);
+            crate::handler::report(phid, (ctx.cb)(&ctx.chan, state as i32));
+        });
     }
 
     /// Get a reference to the underlying sensor handle
@@ -156,42 +193,88 @@ impl DigitalInput {
     }
 
     /// Sets a handler to receive digital input state change callbacks.
-    pub fn set_on_state_change_handler<F>(&mut self, cb: F) -> Result<()>
+    /// The handler is removed when the returned guard is dropped.
+    pub fn set_on_state_change_handler<F, R>(&self, cb: F) -> Result<HandlerGuard>
     where
-        F: Fn(&DigitalInput, i32) + Send + 'static,
+        F: FnMut(&DigitalInput, i32) -> R + Send + 'static,
+        R: HandlerResult,
     {
-        // 1st box is fat ptr, 2nd is regular pointer.
-        let cb: Box<Box<DigitalInputCallback>> = Box::new(Box::new(cb));
-        let ctx = Box::into_raw(cb) as *mut c_void;
-        self.cb = Some(ctx);
+        let ctx = Context::into_raw(Self::from(self.chan), cb);
 
-        ReturnCode::result(unsafe {
-            ffi::PhidgetDigitalInput_setOnStateChangeHandler(
-                self.chan,
-                Some(Self::on_state_change),
-                ctx,
-            )
-        })
+        crate::handler::register::<Context<Self, F>, _>(
+            self.chan as PhidgetHandle,
+            "state_change",
+            ctx,
+            || unsafe {
+                traced!(ffi::PhidgetDigitalInput_setOnStateChangeHandler(
+                    self.chan,
+                    Some(Self::on_state_change::<F, R>),
+                    ctx,
+                ))
+            },
+            |phid| unsafe {
+                traced!(ffi::PhidgetDigitalInput_setOnStateChangeHandler(
+                    phid as _,
+                    None,
+                    ptr::null_mut()
+                ))
+            },
+        )
     }
 
-    /// Sets a handler to receive attach callbacks
-    pub fn set_on_attach_handler<F>(&mut self, cb: F) -> Result<()>
+    /// Set a handler to receive state change callbacks, which can borrow
+    /// from the environment of the scope.
+    /// The handler is removed at the end of the scope.
+    pub fn set_on_state_change_handler_scoped<'scope, F, R>(
+        &self,
+        scope: &'scope HandlerScope<'scope, '_>,
+        cb: F,
+    ) -> Result<()>
     where
-        F: Fn(&GenericPhidget) + Send + 'static,
+        F: FnMut(&DigitalInput, i32) -> R + Send + 'scope,
+        R: HandlerResult + 'static,
     {
-        let ctx = crate::phidget::set_on_attach_handler(self, cb)?;
-        self.attach_cb = Some(ctx);
+        // SAFETY: The guard is held by the scope, which can't be forgotten,
+        // and which waits for any call to the handler. See `HandlerScope`.
+        let cb = unsafe {
+            mem::transmute::<
+                Box<dyn FnMut(&DigitalInput, i32) -> R + Send + 'scope>,
+                Box<dyn FnMut(&DigitalInput, i32) -> R + Send>,
+            >(Box::new(cb))
+        };
+        scope.hold(self.set_on_state_change_handler(cb)?);
         Ok(())
     }
 
-    /// Sets a handler to receive detach callbacks
-    pub fn set_on_detach_handler<F>(&mut self, cb: F) -> Result<()>
-    where
-        F: Fn(&GenericPhidget) + Send + 'static,
-    {
-        let ctx = crate::phidget::set_on_detach_handler(self, cb)?;
-        self.detach_cb = Some(ctx);
-        Ok(())
+    /// Removes the state change handler, if one is set, and releases
+    /// its closure.
+    pub fn remove_on_state_change_handler(&self) -> Result<()> {
+        crate::handler::unregister(self.chan as PhidgetHandle, "state_change")
+    }
+
+    /// Gets a channel that receives the input states, fed by the state change events.
+    ///
+    /// This registers the channel's state change handler, replacing any handler
+    /// that was previously set.
+    /// The handler is removed when the receiver is dropped.
+    pub fn state_channel(&self) -> Result<EventReceiver<bool>> {
+        let (tx, rx) = mpsc::channel();
+        let guard = self.set_on_state_change_handler(move |_, s| {
+            let _ = tx.send(s != 0);
+        })?;
+        Ok(EventReceiver::new(rx, guard))
+    }
+
+    /// Gets a stream of the input states, fed by the state change events.
+    ///
+    /// This registers the channel's state change handler, replacing any handler
+    /// that was previously set.
+    /// The handler is removed when the stream is dropped.
+    #[cfg(feature = "async")]
+    pub fn state_stream(&self) -> Result<EventStream<bool>> {
+        let (mut tx, rx) = crate::stream::event_channel();
+        let guard = self.set_on_state_change_handler(move |_, s| tx.send(s != 0))?;
+        Ok(EventStream::new(rx, guard))
     }
 }
 
@@ -201,7 +284,35 @@ impl Phidget for DigitalInput {
     }
 }
 
+impl Sensor for DigitalInput {
+    type Value = bool;
+
+    fn value(&self) -> Result<bool> {
+        self.state()
+    }
+
+    fn on_change<F>(&self, mut cb: F) -> Result<HandlerGuard>
+    where
+        F: FnMut(&Self, bool) + Send + 'static,
+    {
+        self.set_on_state_change_handler(move |di, state| cb(di, state != 0))
+    }
+}
+
+impl fmt::Debug for DigitalInput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        crate::phidget::fmt_debug(f, "DigitalInput", self.chan as PhidgetHandle)
+    }
+}
+
+impl fmt::Display for DigitalInput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        crate::phidget::fmt_display(f, "DigitalInput", self.chan as PhidgetHandle)
+    }
+}
+
 unsafe impl Send for DigitalInput {}
+unsafe impl Sync for DigitalInput {}
 
 impl Default for DigitalInput {
     fn default() -> Self {
@@ -211,12 +322,34 @@ impl Default for DigitalInput {
 
 impl From<PhidgetDigitalInputHandle> for DigitalInput {
     fn from(chan: PhidgetDigitalInputHandle) -> Self {
-        Self {
-            chan,
-            cb: None,
-            attach_cb: None,
-            detach_cb: None,
-        }
+        Self { chan }
+    }
+}
+
+impl TryFrom<GenericPhidget> for DigitalInput {
+    type Error = Error;
+
+    /// Converts a generic phidget into a typed channel, if it's of the
+    /// right class. If the channel is open, it's closed when dropped.
+    fn try_from(ph: GenericPhidget) -> Result<Self> {
+        let chan = ph.into_channel(ChannelClass::DigitalInput)?;
+        Ok(Self::from(chan as PhidgetDigitalInputHandle))
+    }
+}
+
+#[cfg(feature = "embedded-hal")]
+impl embedded_hal::digital::ErrorType for DigitalInput {
+    type Error = Error;
+}
+
+#[cfg(feature = "embedded-hal")]
+impl embedded_hal::digital::InputPin for DigitalInput {
+    fn is_high(&mut self) -> Result<bool> {
+        self.state()
+    }
+
+    fn is_low(&mut self) -> Result<bool> {
+        Ok(!self.state()?)
     }
 }
 
@@ -225,11 +358,33 @@ impl Drop for DigitalInput {
         if let Ok(true) = self.is_open() {
             let _ = self.close();
         }
+        let phid = self.chan as PhidgetHandle;
         unsafe {
             ffi::PhidgetDigitalInput_delete(&mut self.chan);
-            crate::drop_cb::<DigitalInputCallback>(self.cb.take());
-            crate::drop_cb::<AttachCallback>(self.attach_cb.take());
-            crate::drop_cb::<DetachCallback>(self.detach_cb.take());
         }
+        crate::notify::reset(phid);
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::*;
+    use crate::mock::set_property;
+
+    #[test]
+    fn test_mock_input_mode() {
+        let mut di = DigitalInput::new();
+        di.set_serial_number(683_002).unwrap();
+        di.open().unwrap();
+        di.set_input_mode(InputMode::NPN).unwrap();
+        assert_eq!(di.input_mode(), Ok(InputMode::NPN));
+
+        // The values are passed through as the library's constants
+        set_property(&mut di, "InputMode", 2.0).unwrap();
+        assert_eq!(di.input_mode(), Ok(InputMode::PNP));
+        set_property(&mut di, "InputMode", 0.0).unwrap();
+        assert_eq!(di.input_mode(), Err(Error::InvalidArg));
     }
 }
@@ -10,40 +10,102 @@
 // to those terms.
 //
 
-use crate::{AttachCallback, DetachCallback, GenericPhidget, Phidget, Result, ReturnCode};
+use crate::{ChannelClass, Error, GenericPhidget, Phidget, Result, ReturnCode};
 use phidget_sys::{self as ffi, PhidgetDigitalOutputHandle, PhidgetHandle};
-use std::{os::raw::c_void, ptr};
+use std::{fmt, os::raw::c_uint, ptr};
+
+/// The forward voltage of an LED driven by a digital output.
+#[derive(Copy, Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(u32)]
+pub enum LedForwardVoltage {
+    /// 1.7V
+    V1_7 = ffi::PhidgetDigitalOutput_LEDForwardVoltage_LED_FORWARD_VOLTAGE_1_7V,
+    /// 2.75V
+    V2_75 = ffi::PhidgetDigitalOutput_LEDForwardVoltage_LED_FORWARD_VOLTAGE_2_75V,
+    /// 3.2V
+    V3_2 = ffi::PhidgetDigitalOutput_LEDForwardVoltage_LED_FORWARD_VOLTAGE_3_2V,
+    /// 3.9V
+    V3_9 = ffi::PhidgetDigitalOutput_LEDForwardVoltage_LED_FORWARD_VOLTAGE_3_9V,
+    /// 4.0V
+    V4_0 = ffi::PhidgetDigitalOutput_LEDForwardVoltage_LED_FORWARD_VOLTAGE_4_0V,
+    /// 4.8V
+    V4_8 = ffi::PhidgetDigitalOutput_LEDForwardVoltage_LED_FORWARD_VOLTAGE_4_8V,
+    /// 5.0V
+    V5_0 = ffi::PhidgetDigitalOutput_LEDForwardVoltage_LED_FORWARD_VOLTAGE_5_0V,
+    /// 5.6V
+    V5_6 = ffi::PhidgetDigitalOutput_LEDForwardVoltage_LED_FORWARD_VOLTAGE_5_6V,
+}
+
+impl TryFrom<u32> for LedForwardVoltage {
+    type Error = Error;
+
+    fn try_from(value: u32) -> Result<Self> {
+        use LedForwardVoltage::*;
+        match value {
+            ffi::PhidgetDigitalOutput_LEDForwardVoltage_LED_FORWARD_VOLTAGE_1_7V => Ok(V1_7),
+            ffi::PhidgetDigitalOutput_LEDForwardVoltage_LED_FORWARD_VOLTAGE_2_75V => Ok(V2_75),
+            ffi::PhidgetDigitalOutput_LEDForwardVoltage_LED_FORWARD_VOLTAGE_3_2V => Ok(V3_2),
+            ffi::PhidgetDigitalOutput_LEDForwardVoltage_LED_FORWARD_VOLTAGE_3_9V => Ok(V3_9),
+            ffi::PhidgetDigitalOutput_LEDForwardVoltage_LED_FORWARD_VOLTAGE_4_0V => Ok(V4_0),
+            ffi::PhidgetDigitalOutput_LEDForwardVoltage_LED_FORWARD_VOLTAGE_4_8V => Ok(V4_8),
+            ffi::PhidgetDigitalOutput_LEDForwardVoltage_LED_FORWARD_VOLTAGE_5_0V => Ok(V5_0),
+            ffi::PhidgetDigitalOutput_LEDForwardVoltage_LED_FORWARD_VOLTAGE_5_6V => Ok(V5_6),
+            _ => Err(Error::InvalidArg),
+        }
+    }
+}
 
 /// Phidget digital output
 pub struct DigitalOutput {
     // Handle to the digital output in the phidget22 library
     chan: PhidgetDigitalOutputHandle,
-    // Double-boxed attach callback, if registered
-    attach_cb: Option<*mut c_void>,
-    // Double-boxed detach callback, if registered
-    detach_cb: Option<*mut c_void>,
 }
 
 impl DigitalOutput {
-    /// Create a new digital input.
+    /// Create a new digital output.
+    ///
+    /// This panics if the library can't create the channel. Use
+    /// `try_new()` to get the error instead.
     pub fn new() -> Self {
+        Self::try_new().expect("failed to create the channel")
+    }
+
+    /// Create a new digital output, returning an error if the library can't
+    /// create the channel.
+    pub fn try_new() -> Result<Self> {
         let mut chan: PhidgetDigitalOutputHandle = ptr::null_mut();
-        unsafe {
-            ffi::PhidgetDigitalOutput_create(&mut chan);
-        }
-        Self::from(chan)
+        ReturnCode::result(unsafe { ffi::PhidgetDigitalOutput_create(&mut chan) })?;
+        crate::phidget::install_handlers(chan as PhidgetHandle);
+        Ok(Self::from(chan))
+    }
+
+    /// Create a new digital output that uses a port of a VINT hub directly
+    /// as its output, in hub port mode.
+    ///
+    /// This selects the hub by its serial number and the port by its
+    /// number. The channel still needs to be opened.
+    pub fn on_hub_port(serial: i32, port: i32) -> Result<Self> {
+        let mut ch = Self::try_new()?;
+        ch.set_is_hub_port_device(true)?;
+        ch.set_hub_port(port)?;
+        ch.set_serial_number(serial)?;
+        Ok(ch)
     }
 
     /// Set enable failsafe
     pub fn set_enable_failsafe(&mut self, failsafe_time: u32) -> Result<()> {
         ReturnCode::result(unsafe {
-            ffi::PhidgetDigitalOutput_enableFailsafe(self.chan, failsafe_time)
+            traced!(ffi::PhidgetDigitalOutput_enableFailsafe(
+                self.chan,
+                failsafe_time
+            ))
         })?;
         Ok(())
     }
     /// Set reset failsafe
     pub fn set_reset_failsafe(&mut self) -> Result<()> {
-        ReturnCode::result(unsafe { ffi::PhidgetDigitalOutput_resetFailsafe(self.chan) })?;
+        ReturnCode::result(unsafe { traced!(ffi::PhidgetDigitalOutput_resetFailsafe(self.chan)) })?;
         Ok(())
     }
 
@@ -52,11 +114,13 @@ impl DigitalOutput {
     /// means constantly high; 0.0 means constantly low
     pub fn set_duty_cycle(&mut self, duty_cycle: f64) -> Result<()> {
         ReturnCode::result(unsafe {
-            ffi::PhidgetDigitalOutput_setDutyCycle(self.chan, duty_cycle)
+            traced!(ffi::PhidgetDigitalOutput_setDutyCycle(
+                self.chan, duty_cycle
+            ))
         })?;
         Ok(())
     }
-    
+
     // /// Set  duty cycle async
     // pub async fn set_duty_cycle_async(&self, duty_cycle: f64) -> Result<()> {
     //     _ = duty_cycle;
@@ -67,51 +131,69 @@ impl DigitalOutput {
     pub fn duty_cycle(&self) -> Result<f64> {
         let mut value = 0.0;
         ReturnCode::result(unsafe {
-            ffi::PhidgetDigitalOutput_getDutyCycle(self.chan, &mut value)
+            traced!(ffi::PhidgetDigitalOutput_getDutyCycle(
+                self.chan, &mut value
+            ))
         })?;
         Ok(value)
     }
 
     /// Get minimum duty cycle
     pub fn min_duty_cycle(&self) -> Result<f64> {
-        let mut value = 0.0;
-        ReturnCode::result(unsafe {
-            ffi::PhidgetDigitalOutput_getMinDutyCycle(self.chan, &mut value)
-        })?;
-        Ok(value)
+        crate::cache::property(self.chan as PhidgetHandle, "MinDutyCycle", || {
+            let mut value = 0.0;
+            ReturnCode::result(unsafe {
+                traced!(ffi::PhidgetDigitalOutput_getMinDutyCycle(
+                    self.chan, &mut value
+                ))
+            })?;
+            Ok(value)
+        })
     }
 
     /// Get maximum duty cycle
     pub fn max_duty_cycle(&self) -> Result<f64> {
-        let mut value = 0.0;
-        ReturnCode::result(unsafe {
-            ffi::PhidgetDigitalOutput_getMaxDutyCycle(self.chan, &mut value)
-        })?;
-        Ok(value)
+        crate::cache::property(self.chan as PhidgetHandle, "MaxDutyCycle", || {
+            let mut value = 0.0;
+            ReturnCode::result(unsafe {
+                traced!(ffi::PhidgetDigitalOutput_getMaxDutyCycle(
+                    self.chan, &mut value
+                ))
+            })?;
+            Ok(value)
+        })
     }
 
     /// Get minimum failsafe time
     pub fn min_failsafe_time(&self) -> Result<u32> {
-        let mut value = 0;
-        ReturnCode::result(unsafe {
-            ffi::PhidgetDigitalOutput_getMinFailsafeTime(self.chan, &mut value)
-        })?;
-        Ok(value)
+        crate::cache::property(self.chan as PhidgetHandle, "MinFailsafeTime", || {
+            let mut value = 0;
+            ReturnCode::result(unsafe {
+                traced!(ffi::PhidgetDigitalOutput_getMinFailsafeTime(
+                    self.chan, &mut value
+                ))
+            })?;
+            Ok(value)
+        })
     }
 
     /// Get maximum failsafe time
     pub fn max_failsafe_time(&self) -> Result<u32> {
-        let mut value = 0;
-        ReturnCode::result(unsafe {
-            ffi::PhidgetDigitalOutput_getMaxFailsafeTime(self.chan, &mut value)
-        })?;
-        Ok(value)
+        crate::cache::property(self.chan as PhidgetHandle, "MaxFailsafeTime", || {
+            let mut value = 0;
+            ReturnCode::result(unsafe {
+                traced!(ffi::PhidgetDigitalOutput_getMaxFailsafeTime(
+                    self.chan, &mut value
+                ))
+            })?;
+            Ok(value)
+        })
     }
 
     /// Set frequency
     pub fn set_frequency(&mut self, frequency: f64) -> Result<()> {
         ReturnCode::result(unsafe {
-            ffi::PhidgetDigitalOutput_setFrequency(self.chan, frequency)
+            traced!(ffi::PhidgetDigitalOutput_setFrequency(self.chan, frequency))
         })?;
         Ok(())
     }
@@ -120,33 +202,46 @@ impl DigitalOutput {
     pub fn frequency(&self) -> Result<f64> {
         let mut value = 0.0;
         ReturnCode::result(unsafe {
-            ffi::PhidgetDigitalOutput_getFrequency(self.chan, &mut value)
+            traced!(ffi::PhidgetDigitalOutput_getFrequency(
+                self.chan, &mut value
+            ))
         })?;
         Ok(value)
     }
 
     /// Get minimum frequency
     pub fn min_frequency(&self) -> Result<f64> {
-        let mut value = 0.0;
-        ReturnCode::result(unsafe {
-            ffi::PhidgetDigitalOutput_getMinFrequency(self.chan, &mut value)
-        })?;
-        Ok(value)
+        crate::cache::property(self.chan as PhidgetHandle, "MinFrequency", || {
+            let mut value = 0.0;
+            ReturnCode::result(unsafe {
+                traced!(ffi::PhidgetDigitalOutput_getMinFrequency(
+                    self.chan, &mut value
+                ))
+            })?;
+            Ok(value)
+        })
     }
 
     /// Get maximum frequency
     pub fn max_frequency(&self) -> Result<f64> {
-        let mut value = 0.0;
-        ReturnCode::result(unsafe {
-            ffi::PhidgetDigitalOutput_getMaxFrequency(self.chan, &mut value)
-        })?;
-        Ok(value)
+        crate::cache::property(self.chan as PhidgetHandle, "MaxFrequency", || {
+            let mut value = 0.0;
+            ReturnCode::result(unsafe {
+                traced!(ffi::PhidgetDigitalOutput_getMaxFrequency(
+                    self.chan, &mut value
+                ))
+            })?;
+            Ok(value)
+        })
     }
 
     /// Set led current limit
     pub fn set_led_current_limit(&mut self, led_current_limit: f64) -> Result<()> {
         ReturnCode::result(unsafe {
-            ffi::PhidgetDigitalOutput_setLEDCurrentLimit(self.chan, led_current_limit)
+            traced!(ffi::PhidgetDigitalOutput_setLEDCurrentLimit(
+                self.chan,
+                led_current_limit
+            ))
         })?;
         Ok(())
     }
@@ -161,42 +256,67 @@ impl DigitalOutput {
     pub fn led_current_limit(&self) -> Result<f64> {
         let mut value = 0.0;
         ReturnCode::result(unsafe {
-            ffi::PhidgetDigitalOutput_getLEDCurrentLimit(self.chan, &mut value)
+            traced!(ffi::PhidgetDigitalOutput_getLEDCurrentLimit(
+                self.chan, &mut value
+            ))
         })?;
         Ok(value)
     }
 
     /// Get minimum led current limit
     pub fn min_led_current_limit(&self) -> Result<f64> {
-        let mut value = 0.0;
-        ReturnCode::result(unsafe {
-            ffi::PhidgetDigitalOutput_getMinLEDCurrentLimit(self.chan, &mut value)
-        })?;
-        Ok(value)
+        crate::cache::property(self.chan as PhidgetHandle, "MinLEDCurrentLimit", || {
+            let mut value = 0.0;
+            ReturnCode::result(unsafe {
+                traced!(ffi::PhidgetDigitalOutput_getMinLEDCurrentLimit(
+                    self.chan, &mut value
+                ))
+            })?;
+            Ok(value)
+        })
     }
 
     /// Get maximum led current limit
     pub fn max_led_current_limit(&self) -> Result<f64> {
-        let mut value = 0.0;
+        crate::cache::property(self.chan as PhidgetHandle, "MaxLEDCurrentLimit", || {
+            let mut value = 0.0;
+            ReturnCode::result(unsafe {
+                traced!(ffi::PhidgetDigitalOutput_getMaxLEDCurrentLimit(
+                    self.chan, &mut value
+                ))
+            })?;
+            Ok(value)
+        })
+    }
+
+    /// Gets the forward voltage of the LED connected to the output.
+    pub fn led_forward_voltage(&self) -> Result<LedForwardVoltage> {
+        let mut voltage: ffi::PhidgetDigitalOutput_LEDForwardVoltage = 0;
         ReturnCode::result(unsafe {
-            ffi::PhidgetDigitalOutput_getMaxLEDCurrentLimit(self.chan, &mut value)
+            traced!(ffi::PhidgetDigitalOutput_getLEDForwardVoltage(
+                self.chan,
+                &mut voltage
+            ))
         })?;
-        Ok(value)
+        LedForwardVoltage::try_from(voltage)
     }
 
-    /// Get led forward voltage
-    pub fn led_forward_voltage(&self) -> Result<u32> {
-        let mut value: ffi::PhidgetDigitalOutput_LEDForwardVoltage = 0;
+    /// Sets the forward voltage of the LED connected to the output.
+    pub fn set_led_forward_voltage(&mut self, voltage: LedForwardVoltage) -> Result<()> {
         ReturnCode::result(unsafe {
-            ffi::PhidgetDigitalOutput_getLEDForwardVoltage(self.chan, &mut value)
-        })?;
-        Ok(value)
+            traced!(ffi::PhidgetDigitalOutput_setLEDForwardVoltage(
+                self.chan,
+                voltage as c_uint
+            ))
+        })
     }
 
     /// Set the state of the digital output
     /// This overrides any duty cycle that was previously set.
     pub fn set_state(&mut self, state: bool) -> Result<()> {
-        ReturnCode::result(unsafe { ffi::PhidgetDigitalOutput_setState(self.chan, state as i32) })
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetDigitalOutput_setState(self.chan, state as i32))
+        })
     }
 
     // /// Set state async
@@ -208,38 +328,33 @@ impl DigitalOutput {
     /// Get the state of the digital output channel
     pub fn state(&self) -> Result<bool> {
         let mut value = 0;
-        ReturnCode::result(unsafe { ffi::PhidgetDigitalOutput_getState(self.chan, &mut value) })?;
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetDigitalOutput_getState(self.chan, &mut value))
+        })?;
         Ok(value != 0)
     }
+}
 
-    /// Sets a handler to receive attach callbacks
-    pub fn set_on_attach_handler<F>(&mut self, cb: F) -> Result<()>
-    where
-        F: Fn(&GenericPhidget) + Send + 'static,
-    {
-        let ctx = crate::phidget::set_on_attach_handler(self, cb)?;
-        self.attach_cb = Some(ctx);
-        Ok(())
+impl Phidget for DigitalOutput {
+    fn as_handle(&mut self) -> PhidgetHandle {
+        self.chan as PhidgetHandle
     }
+}
 
-    /// Sets a handler to receive detach callbacks
-    pub fn set_on_detach_handler<F>(&mut self, cb: F) -> Result<()>
-    where
-        F: Fn(&GenericPhidget) + Send + 'static,
-    {
-        let ctx = crate::phidget::set_on_detach_handler(self, cb)?;
-        self.detach_cb = Some(ctx);
-        Ok(())
+impl fmt::Debug for DigitalOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        crate::phidget::fmt_debug(f, "DigitalOutput", self.chan as PhidgetHandle)
     }
 }
 
-impl Phidget for DigitalOutput {
-    fn as_handle(&mut self) -> PhidgetHandle {
-        self.chan as PhidgetHandle
+impl fmt::Display for DigitalOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        crate::phidget::fmt_display(f, "DigitalOutput", self.chan as PhidgetHandle)
     }
 }
 
 unsafe impl Send for DigitalOutput {}
+unsafe impl Sync for DigitalOutput {}
 
 impl Default for DigitalOutput {
     fn default() -> Self {
@@ -249,11 +364,63 @@ impl Default for DigitalOutput {
 
 impl From<PhidgetDigitalOutputHandle> for DigitalOutput {
     fn from(chan: PhidgetDigitalOutputHandle) -> Self {
-        Self {
-            chan,
-            attach_cb: None,
-            detach_cb: None,
-        }
+        Self { chan }
+    }
+}
+
+impl TryFrom<GenericPhidget> for DigitalOutput {
+    type Error = Error;
+
+    /// Converts a generic phidget into a typed channel, if it's of the
+    /// right class. If the channel is open, it's closed when dropped.
+    fn try_from(ph: GenericPhidget) -> Result<Self> {
+        let chan = ph.into_channel(ChannelClass::DigitalOutput)?;
+        Ok(Self::from(chan as PhidgetDigitalOutputHandle))
+    }
+}
+
+#[cfg(feature = "embedded-hal")]
+impl embedded_hal::digital::ErrorType for DigitalOutput {
+    type Error = Error;
+}
+
+#[cfg(feature = "embedded-hal")]
+impl embedded_hal::digital::OutputPin for DigitalOutput {
+    fn set_low(&mut self) -> Result<()> {
+        self.set_state(false)
+    }
+
+    fn set_high(&mut self) -> Result<()> {
+        self.set_state(true)
+    }
+}
+
+#[cfg(feature = "embedded-hal")]
+impl embedded_hal::digital::StatefulOutputPin for DigitalOutput {
+    fn is_set_high(&mut self) -> Result<bool> {
+        self.state()
+    }
+
+    fn is_set_low(&mut self) -> Result<bool> {
+        Ok(!self.state()?)
+    }
+}
+
+#[cfg(feature = "embedded-hal")]
+impl embedded_hal::pwm::ErrorType for DigitalOutput {
+    type Error = Error;
+}
+
+#[cfg(feature = "embedded-hal")]
+impl embedded_hal::pwm::SetDutyCycle for DigitalOutput {
+    /// The full scale of the duty cycle, which maps to the channel's duty
+    /// cycle of 1.0.
+    fn max_duty_cycle(&self) -> u16 {
+        u16::MAX
+    }
+
+    fn set_duty_cycle(&mut self, duty: u16) -> Result<()> {
+        DigitalOutput::set_duty_cycle(self, f64::from(duty) / f64::from(u16::MAX))
     }
 }
 
@@ -262,10 +429,10 @@ impl Drop for DigitalOutput {
         if let Ok(true) = self.is_open() {
             let _ = self.close();
         }
+        let phid = self.chan as PhidgetHandle;
         unsafe {
             ffi::PhidgetDigitalOutput_delete(&mut self.chan);
-            crate::drop_cb::<AttachCallback>(self.attach_cb.take());
-            crate::drop_cb::<DetachCallback>(self.detach_cb.take());
         }
+        crate::notify::reset(phid);
     }
 }
@@ -0,0 +1,423 @@
+// phidget-rs/src/devices/encoder.rs
+//
+// This file is part of the 'phidget-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+use crate::{
+    handler::Context, ChannelClass, Error, EventReceiver, GenericPhidget, HandlerGuard,
+    HandlerResult, HandlerScope, Phidget, Result, ReturnCode,
+};
+use phidget_sys::{self as ffi, PhidgetEncoderHandle, PhidgetHandle};
+use std::{
+    fmt, mem,
+    os::raw::{c_int, c_uint, c_void},
+    ptr,
+    sync::mpsc,
+};
+
+#[cfg(feature = "async")]
+use crate::stream::EventStream;
+
+/// The function signature for the safe Rust position change callback.
+pub type PositionChangeCallback = dyn FnMut(&Encoder, PositionChange) + Send + 'static;
+
+/// A change in the position of an encoder, reported by its position
+/// change event.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PositionChange {
+    /// The change in position since the last event, in quadrature counts
+    pub position_change: i32,
+    /// The time since the last event, in milliseconds
+    pub time_change: f64,
+    /// Whether the index pulse was seen since the last event
+    pub index_triggered: bool,
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+/// The electrical interface of the encoder connected to an input
+#[derive(Copy, Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(u32)]
+pub enum EncoderIoMode {
+    /// An encoder with push-pull outputs
+    PushPull = ffi::Phidget_EncoderIOMode_ENCODER_IO_MODE_PUSH_PULL,
+    /// An encoder with line driver outputs, with 2.2kΩ pull-down resistors
+    LineDriver2K2 = ffi::Phidget_EncoderIOMode_ENCODER_IO_MODE_LINE_DRIVER_2K2,
+    /// An encoder with line driver outputs, with 10kΩ pull-down resistors
+    LineDriver10K = ffi::Phidget_EncoderIOMode_ENCODER_IO_MODE_LINE_DRIVER_10K,
+    /// An encoder with open collector outputs, with 2.2kΩ pull-up resistors
+    OpenCollector2K2 = ffi::Phidget_EncoderIOMode_ENCODER_IO_MODE_OPEN_COLLECTOR_2K2,
+    /// An encoder with open collector outputs, with 10kΩ pull-up resistors
+    OpenCollector10K = ffi::Phidget_EncoderIOMode_ENCODER_IO_MODE_OPEN_COLLECTOR_10K,
+}
+
+impl TryFrom<u32> for EncoderIoMode {
+    type Error = Error;
+
+    fn try_from(value: u32) -> Result<Self> {
+        use EncoderIoMode::*;
+        match value {
+            ffi::Phidget_EncoderIOMode_ENCODER_IO_MODE_PUSH_PULL => Ok(PushPull),
+            ffi::Phidget_EncoderIOMode_ENCODER_IO_MODE_LINE_DRIVER_2K2 => Ok(LineDriver2K2),
+            ffi::Phidget_EncoderIOMode_ENCODER_IO_MODE_LINE_DRIVER_10K => Ok(LineDriver10K),
+            ffi::Phidget_EncoderIOMode_ENCODER_IO_MODE_OPEN_COLLECTOR_2K2 => Ok(OpenCollector2K2),
+            ffi::Phidget_EncoderIOMode_ENCODER_IO_MODE_OPEN_COLLECTOR_10K => Ok(OpenCollector10K),
+            _ => Err(Error::InvalidArg),
+        }
+    }
+}
+
+/// Phidget encoder
+///
+/// The rate of the position change events is set by the data interval of
+/// the channel, from the `Phidget` trait, and by the position change
+/// trigger. A high-resolution encoder can otherwise report thousands of
+/// small changes each second.
+pub struct Encoder {
+    // Handle to the encoder in the phidget22 library
+    chan: PhidgetEncoderHandle,
+}
+
+impl Encoder {
+    /// Create a new encoder.
+    ///
+    /// This panics if the library can't create the channel. Use
+    /// `try_new()` to get the error instead.
+    pub fn new() -> Self {
+        Self::try_new().expect("failed to create the channel")
+    }
+
+    /// Create a new encoder, returning an error if the library can't
+    /// create the channel.
+    pub fn try_new() -> Result<Self> {
+        let mut chan: PhidgetEncoderHandle = ptr::null_mut();
+        ReturnCode::result(unsafe { ffi::PhidgetEncoder_create(&mut chan) })?;
+        crate::phidget::install_handlers(chan as PhidgetHandle);
+        Ok(Self::from(chan))
+    }
+
+    // Low-level, unsafe, callback for the position change event.
+    // The context holds a view of the channel and the safe Rust callback.
+    unsafe extern "C" fn on_position_change<F, R>(
+        chan: PhidgetEncoderHandle,
+        ctx: *mut c_void,
+        position_change: c_int,
+        time_change: f64,
+        index_triggered: c_int,
+    ) where
+        F: FnMut(&Encoder, PositionChange) -> R + Send + 'static,
+        R: HandlerResult,
+    {
+        let change = PositionChange {
+            position_change: position_change as i32,
+            time_change,
+            index_triggered: index_triggered != 0,
+        };
+        crate::handler::deliver(chan as PhidgetHandle, ctx, move |phid, ctx| {
+            let ctx: &mut Context<Self, F> = &mut *(ctx as *mut _);
+            crate::handler::report(phid, (ctx.cb)(&ctx.chan, change));
+        });
+    }
+
+    /// Get a reference to the underlying encoder handle
+    pub fn as_channel(&self) -> &PhidgetEncoderHandle {
+        &self.chan
+    }
+
+    /// Gets the position of the encoder, in quadrature counts.
+    pub fn position(&self) -> Result<i64> {
+        let mut pos: i64 = 0;
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetEncoder_getPosition(self.chan, &mut pos))
+        })?;
+        Ok(pos)
+    }
+
+    /// Sets the position of the encoder, in quadrature counts.
+    ///
+    /// This sets the reference point that the position is counted from,
+    /// and doesn't move anything.
+    pub fn set_position(&mut self, pos: i64) -> Result<()> {
+        ReturnCode::result(unsafe { traced!(ffi::PhidgetEncoder_setPosition(self.chan, pos)) })
+    }
+
+    /// Gets the position at which the index pulse was last seen.
+    pub fn index_position(&self) -> Result<i64> {
+        let mut pos: i64 = 0;
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetEncoder_getIndexPosition(self.chan, &mut pos))
+        })?;
+        Ok(pos)
+    }
+
+    /// Determines if the encoder input is enabled.
+    pub fn enabled(&self) -> Result<bool> {
+        let mut enabled: c_int = 0;
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetEncoder_getEnabled(self.chan, &mut enabled))
+        })?;
+        Ok(enabled != 0)
+    }
+
+    /// Enables or disables the encoder input.
+    pub fn set_enabled(&mut self, enabled: bool) -> Result<()> {
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetEncoder_setEnabled(self.chan, enabled as c_int))
+        })
+    }
+
+    /// Gets the minimum change in position, in quadrature counts, that
+    /// triggers a change event.
+    pub fn position_change_trigger(&self) -> Result<u32> {
+        let mut trigger = 0;
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetEncoder_getPositionChangeTrigger(
+                self.chan,
+                &mut trigger
+            ))
+        })?;
+        Ok(trigger)
+    }
+
+    /// Sets the minimum change in position, in quadrature counts, that
+    /// triggers a change event. Zero reports every change, at the data
+    /// interval.
+    pub fn set_position_change_trigger(&mut self, trigger: u32) -> Result<()> {
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetEncoder_setPositionChangeTrigger(
+                self.chan, trigger
+            ))
+        })
+    }
+
+    /// Gets the smallest change trigger that can be set.
+    pub fn min_position_change_trigger(&self) -> Result<u32> {
+        crate::cache::property(
+            self.chan as PhidgetHandle,
+            "MinPositionChangeTrigger",
+            || {
+                let mut value = 0;
+                ReturnCode::result(unsafe {
+                    traced!(ffi::PhidgetEncoder_getMinPositionChangeTrigger(
+                        self.chan, &mut value
+                    ))
+                })?;
+                Ok(value)
+            },
+        )
+    }
+
+    /// Gets the largest change trigger that can be set.
+    pub fn max_position_change_trigger(&self) -> Result<u32> {
+        crate::cache::property(
+            self.chan as PhidgetHandle,
+            "MaxPositionChangeTrigger",
+            || {
+                let mut value = 0;
+                ReturnCode::result(unsafe {
+                    traced!(ffi::PhidgetEncoder_getMaxPositionChangeTrigger(
+                        self.chan, &mut value
+                    ))
+                })?;
+                Ok(value)
+            },
+        )
+    }
+
+    /// Gets the electrical interface of the encoder connected to the input.
+    pub fn io_mode(&self) -> Result<EncoderIoMode> {
+        let mut mode: ffi::Phidget_EncoderIOMode = 0;
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetEncoder_getIOMode(self.chan, &mut mode))
+        })?;
+        EncoderIoMode::try_from(mode)
+    }
+
+    /// Sets the electrical interface of the encoder connected to the input.
+    pub fn set_io_mode(&mut self, mode: EncoderIoMode) -> Result<()> {
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetEncoder_setIOMode(self.chan, mode as c_uint))
+        })
+    }
+
+    /// Sets a handler to receive position change callbacks.
+    /// The handler is removed when the returned guard is dropped.
+    pub fn set_on_position_change_handler<F, R>(&self, cb: F) -> Result<HandlerGuard>
+    where
+        F: FnMut(&Encoder, PositionChange) -> R + Send + 'static,
+        R: HandlerResult,
+    {
+        let ctx = Context::into_raw(Self::from(self.chan), cb);
+
+        crate::handler::register::<Context<Self, F>, _>(
+            self.chan as PhidgetHandle,
+            "position_change",
+            ctx,
+            || unsafe {
+                traced!(ffi::PhidgetEncoder_setOnPositionChangeHandler(
+                    self.chan,
+                    Some(Self::on_position_change::<F, R>),
+                    ctx,
+                ))
+            },
+            |phid| unsafe {
+                traced!(ffi::PhidgetEncoder_setOnPositionChangeHandler(
+                    phid as _,
+                    None,
+                    ptr::null_mut()
+                ))
+            },
+        )
+    }
+
+    /// Set a handler to receive position change callbacks, which can borrow
+    /// from the environment of the scope.
+    /// The handler is removed at the end of the scope.
+    pub fn set_on_position_change_handler_scoped<'scope, F, R>(
+        &self,
+        scope: &'scope HandlerScope<'scope, '_>,
+        cb: F,
+    ) -> Result<()>
+    where
+        F: FnMut(&Encoder, PositionChange) -> R + Send + 'scope,
+        R: HandlerResult + 'static,
+    {
+        // SAFETY: The guard is held by the scope, which can't be forgotten,
+        // and which waits for any call to the handler. See `HandlerScope`.
+        let cb = unsafe {
+            mem::transmute::<
+                Box<dyn FnMut(&Encoder, PositionChange) -> R + Send + 'scope>,
+                Box<dyn FnMut(&Encoder, PositionChange) -> R + Send>,
+            >(Box::new(cb))
+        };
+        scope.hold(self.set_on_position_change_handler(cb)?);
+        Ok(())
+    }
+
+    /// Removes the position change handler, if one is set, and releases its
+    /// closure.
+    pub fn remove_on_position_change_handler(&self) -> Result<()> {
+        crate::handler::unregister(self.chan as PhidgetHandle, "position_change")
+    }
+
+    /// Gets a channel that receives the position changes, fed by the
+    /// position change events.
+    ///
+    /// This registers the channel's position change handler, replacing any
+    /// handler that was previously set.
+    /// The handler is removed when the receiver is dropped.
+    pub fn position_change_channel(&self) -> Result<EventReceiver<PositionChange>> {
+        let (tx, rx) = mpsc::channel();
+        let guard = self.set_on_position_change_handler(move |_, change| {
+            let _ = tx.send(change);
+        })?;
+        Ok(EventReceiver::new(rx, guard))
+    }
+
+    /// Gets a stream of the position changes, fed by the position change
+    /// events.
+    ///
+    /// This registers the channel's position change handler, replacing any
+    /// handler that was previously set.
+    /// The handler is removed when the stream is dropped.
+    #[cfg(feature = "async")]
+    pub fn position_change_stream(&self) -> Result<EventStream<PositionChange>> {
+        let (mut tx, rx) = crate::stream::event_channel();
+        let guard = self.set_on_position_change_handler(move |_, change| tx.send(change))?;
+        Ok(EventStream::new(rx, guard))
+    }
+}
+
+impl Phidget for Encoder {
+    fn as_handle(&mut self) -> PhidgetHandle {
+        self.chan as PhidgetHandle
+    }
+}
+
+impl fmt::Debug for Encoder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        crate::phidget::fmt_debug(f, "Encoder", self.chan as PhidgetHandle)
+    }
+}
+
+impl fmt::Display for Encoder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        crate::phidget::fmt_display(f, "Encoder", self.chan as PhidgetHandle)
+    }
+}
+
+unsafe impl Send for Encoder {}
+unsafe impl Sync for Encoder {}
+
+impl Default for Encoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl From<PhidgetEncoderHandle> for Encoder {
+    fn from(chan: PhidgetEncoderHandle) -> Self {
+        Self { chan }
+    }
+}
+
+impl TryFrom<GenericPhidget> for Encoder {
+    type Error = Error;
+
+    /// Converts a generic phidget into a typed channel, if it's of the
+    /// right class. If the channel is open, it's closed when dropped.
+    fn try_from(ph: GenericPhidget) -> Result<Self> {
+        let chan = ph.into_channel(ChannelClass::Encoder)?;
+        Ok(Self::from(chan as PhidgetEncoderHandle))
+    }
+}
+
+impl Drop for Encoder {
+    fn drop(&mut self) {
+        if let Ok(true) = self.is_open() {
+            let _ = self.close();
+        }
+        let phid = self.chan as PhidgetHandle;
+        unsafe {
+            ffi::PhidgetEncoder_delete(&mut self.chan);
+        }
+        crate::notify::reset(phid);
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::*;
+    use crate::mock::move_encoder;
+    use std::time::Duration;
+
+    #[test]
+    fn test_mock_encoder() {
+        let mut enc = Encoder::new();
+        enc.open().unwrap();
+        enc.set_data_interval(Duration::from_millis(100)).unwrap();
+        enc.set_position_change_trigger(50).unwrap();
+        assert_eq!(enc.position_change_trigger(), Ok(50));
+        enc.set_position(1000).unwrap();
+
+        let rx = enc.position_change_channel().unwrap();
+        move_encoder(&mut enc, -120, 100.0, true).unwrap();
+        let change = PositionChange {
+            position_change: -120,
+            time_change: 100.0,
+            index_triggered: true,
+        };
+        assert_eq!(rx.try_recv(), Ok(change));
+        assert_eq!(enc.position(), Ok(880));
+        assert_eq!(enc.index_position(), Ok(880));
+    }
+}
@@ -0,0 +1,449 @@
+// phidget-rs/src/devices/frequency_counter.rs
+//
+// This file is part of the 'phidget-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+use crate::{
+    devices::digital_input::PowerSupply, handler::Context, ChannelClass, Error, EventReceiver,
+    GenericPhidget, HandlerGuard, HandlerResult, HandlerScope, Phidget, Result, ReturnCode, Sensor,
+};
+use phidget_sys::{self as ffi, PhidgetFrequencyCounterHandle, PhidgetHandle};
+use std::{
+    fmt, mem,
+    os::raw::{c_int, c_uint, c_void},
+    ptr,
+    sync::mpsc,
+    time::Duration,
+};
+
+#[cfg(feature = "async")]
+use crate::stream::EventStream;
+
+#[cfg(feature = "units")]
+use uom::si::{f64::Frequency, frequency::hertz};
+
+/// The function signature for the safe Rust frequency change callback.
+pub type FrequencyChangeCallback = dyn FnMut(&FrequencyCounter, f64) + Send + 'static;
+
+/////////////////////////////////////////////////////////////////////////////
+
+/// Phidget frequency counter
+pub struct FrequencyCounter {
+    // Handle to the frequency counter in the phidget22 library
+    chan: PhidgetFrequencyCounterHandle,
+}
+
+impl FrequencyCounter {
+    /// Create a new frequency counter.
+    ///
+    /// This panics if the library can't create the channel. Use
+    /// `try_new()` to get the error instead.
+    pub fn new() -> Self {
+        Self::try_new().expect("failed to create the channel")
+    }
+
+    /// Create a new frequency counter, returning an error if the library can't
+    /// create the channel.
+    pub fn try_new() -> Result<Self> {
+        let mut chan: PhidgetFrequencyCounterHandle = ptr::null_mut();
+        ReturnCode::result(unsafe { ffi::PhidgetFrequencyCounter_create(&mut chan) })?;
+        crate::phidget::install_handlers(chan as PhidgetHandle);
+        Ok(Self::from(chan))
+    }
+
+    // Low-level, unsafe, callback for the frequency change event.
+    // The context holds a view of the channel and the safe Rust callback.
+    unsafe extern "C" fn on_frequency_change<F, R>(
+        chan: PhidgetFrequencyCounterHandle,
+        ctx: *mut c_void,
+        frequency: f64,
+    ) where
+        F: FnMut(&FrequencyCounter, f64) -> R + Send + 'static,
+        R: HandlerResult,
+    {
+        crate::handler::deliver(chan as PhidgetHandle, ctx, move |phid, ctx| {
+            let ctx: &mut Context<Self, F> = &mut *(ctx as *mut _);
+            crate::handler::report(phid, (ctx.cb)(&ctx.chan, frequency));
+        });
+    }
+
+    /// Get a reference to the underlying sensor handle
+    pub fn as_channel(&self) -> &PhidgetFrequencyCounterHandle {
+        &self.chan
+    }
+
+    /// Get the frequency on the input channel, in Hz
+    pub fn frequency(&self) -> Result<f64> {
+        let mut v: f64 = 0.0;
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetFrequencyCounter_getFrequency(self.chan, &mut v))
+        })?;
+        Ok(v)
+    }
+
+    /// Read the frequency as a typed quantity.
+    #[cfg(feature = "units")]
+    pub fn frequency_quantity(&self) -> Result<Frequency> {
+        Ok(Frequency::new::<hertz>(self.frequency()?))
+    }
+
+    /// Gets the number of pulses counted since the channel was opened, or
+    /// since it was last reset.
+    pub fn count(&self) -> Result<u64> {
+        let mut count: u64 = 0;
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetFrequencyCounter_getCount(self.chan, &mut count))
+        })?;
+        Ok(count)
+    }
+
+    /// Gets the time over which the pulses were counted, since the
+    /// channel was opened, or since it was last reset.
+    pub fn time_elapsed(&self) -> Result<Duration> {
+        let mut ms: f64 = 0.0;
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetFrequencyCounter_getTimeElapsed(
+                self.chan, &mut ms
+            ))
+        })?;
+        Ok(Duration::from_secs_f64(ms.max(0.0) / 1000.0))
+    }
+
+    /// Resets the count and the elapsed time, to start a new counting
+    /// window.
+    pub fn reset(&mut self) -> Result<()> {
+        ReturnCode::result(unsafe { traced!(ffi::PhidgetFrequencyCounter_reset(self.chan)) })
+    }
+
+    /// Determines if the channel is counting pulses.
+    pub fn enabled(&self) -> Result<bool> {
+        let mut enabled: c_int = 0;
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetFrequencyCounter_getEnabled(
+                self.chan,
+                &mut enabled
+            ))
+        })?;
+        Ok(enabled != 0)
+    }
+
+    /// Enables or disables the counting of pulses.
+    ///
+    /// While the channel is disabled, the count and the elapsed time are
+    /// held.
+    pub fn set_enabled(&mut self, enabled: bool) -> Result<()> {
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetFrequencyCounter_setEnabled(
+                self.chan,
+                enabled as c_int
+            ))
+        })
+    }
+
+    /// Gets the frequency cutoff, in Hz.
+    ///
+    /// Below this frequency, the channel reports a frequency of zero.
+    pub fn frequency_cutoff(&self) -> Result<f64> {
+        let mut cutoff = 0.0;
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetFrequencyCounter_getFrequencyCutoff(
+                self.chan,
+                &mut cutoff
+            ))
+        })?;
+        Ok(cutoff)
+    }
+
+    /// Sets the frequency cutoff, in Hz.
+    ///
+    /// A lower cutoff detects slower signals, but takes longer to report
+    /// that a signal has stopped.
+    pub fn set_frequency_cutoff(&mut self, cutoff: f64) -> Result<()> {
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetFrequencyCounter_setFrequencyCutoff(
+                self.chan, cutoff
+            ))
+        })
+    }
+
+    /// Gets the smallest frequency cutoff that can be set, in Hz.
+    pub fn min_frequency_cutoff(&self) -> Result<f64> {
+        crate::cache::property(self.chan as PhidgetHandle, "MinFrequencyCutoff", || {
+            let mut value = 0.0;
+            ReturnCode::result(unsafe {
+                traced!(ffi::PhidgetFrequencyCounter_getMinFrequencyCutoff(
+                    self.chan, &mut value
+                ))
+            })?;
+            Ok(value)
+        })
+    }
+
+    /// Gets the largest frequency cutoff that can be set, in Hz.
+    pub fn max_frequency_cutoff(&self) -> Result<f64> {
+        crate::cache::property(self.chan as PhidgetHandle, "MaxFrequencyCutoff", || {
+            let mut value = 0.0;
+            ReturnCode::result(unsafe {
+                traced!(ffi::PhidgetFrequencyCounter_getMaxFrequencyCutoff(
+                    self.chan, &mut value
+                ))
+            })?;
+            Ok(value)
+        })
+    }
+
+    /// Gets the maximum frequency that the channel can measure, in Hz.
+    pub fn max_frequency(&self) -> Result<f64> {
+        crate::cache::property(self.chan as PhidgetHandle, "MaxFrequency", || {
+            let mut value = 0.0;
+            ReturnCode::result(unsafe {
+                traced!(ffi::PhidgetFrequencyCounter_getMaxFrequency(
+                    self.chan, &mut value
+                ))
+            })?;
+            Ok(value)
+        })
+    }
+
+    /// Gets the power supply voltage for the sensor on the channel.
+    pub fn power_supply(&self) -> Result<PowerSupply> {
+        let mut power_supply: ffi::Phidget_PowerSupply = 0;
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetFrequencyCounter_getPowerSupply(
+                self.chan,
+                &mut power_supply
+            ))
+        })?;
+        PowerSupply::try_from(power_supply)
+    }
+
+    /// Sets the power supply voltage for the sensor on the channel.
+    pub fn set_power_supply(&mut self, power_supply: PowerSupply) -> Result<()> {
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetFrequencyCounter_setPowerSupply(
+                self.chan,
+                power_supply as c_uint
+            ))
+        })
+    }
+
+    /// Set a handler to receive frequency change callbacks with the value as a
+    /// typed quantity.
+    /// This replaces any frequency change handler that was previously set.
+    /// The handler is removed when the returned guard is dropped.
+    #[cfg(feature = "units")]
+    pub fn set_on_frequency_change_quantity_handler<F, R>(&self, mut cb: F) -> Result<HandlerGuard>
+    where
+        F: FnMut(&FrequencyCounter, Frequency) -> R + Send + 'static,
+        R: HandlerResult,
+    {
+        self.set_on_frequency_change_handler(move |ch, val| cb(ch, Frequency::new::<hertz>(val)))
+    }
+
+    /// Sets a handler to receive frequency change callbacks.
+    /// The handler is removed when the returned guard is dropped.
+    pub fn set_on_frequency_change_handler<F, R>(&self, cb: F) -> Result<HandlerGuard>
+    where
+        F: FnMut(&FrequencyCounter, f64) -> R + Send + 'static,
+        R: HandlerResult,
+    {
+        let ctx = Context::into_raw(Self::from(self.chan), cb);
+
+        crate::handler::register::<Context<Self, F>, _>(
+            self.chan as PhidgetHandle,
+            "frequency_change",
+            ctx,
+            || unsafe {
+                traced!(ffi::PhidgetFrequencyCounter_setOnFrequencyChangeHandler(
+                    self.chan,
+                    Some(Self::on_frequency_change::<F, R>),
+                    ctx,
+                ))
+            },
+            |phid| unsafe {
+                traced!(ffi::PhidgetFrequencyCounter_setOnFrequencyChangeHandler(
+                    phid as _,
+                    None,
+                    ptr::null_mut()
+                ))
+            },
+        )
+    }
+
+    /// Set a handler to receive frequency change callbacks, which can borrow
+    /// from the environment of the scope.
+    /// The handler is removed at the end of the scope.
+    pub fn set_on_frequency_change_handler_scoped<'scope, F, R>(
+        &self,
+        scope: &'scope HandlerScope<'scope, '_>,
+        cb: F,
+    ) -> Result<()>
+    where
+        F: FnMut(&FrequencyCounter, f64) -> R + Send + 'scope,
+        R: HandlerResult + 'static,
+    {
+        // SAFETY: The guard is held by the scope, which can't be forgotten,
+        // and which waits for any call to the handler. See `HandlerScope`.
+        let cb = unsafe {
+            mem::transmute::<
+                Box<dyn FnMut(&FrequencyCounter, f64) -> R + Send + 'scope>,
+                Box<dyn FnMut(&FrequencyCounter, f64) -> R + Send>,
+            >(Box::new(cb))
+        };
+        scope.hold(self.set_on_frequency_change_handler(cb)?);
+        Ok(())
+    }
+
+    /// Removes the frequency change handler, if one is set, and releases
+    /// its closure.
+    pub fn remove_on_frequency_change_handler(&self) -> Result<()> {
+        crate::handler::unregister(self.chan as PhidgetHandle, "frequency_change")
+    }
+
+    /// Gets a channel that receives the frequency readings, fed by the frequency change events.
+    ///
+    /// This registers the channel's frequency change handler, replacing any handler
+    /// that was previously set.
+    /// The handler is removed when the receiver is dropped.
+    pub fn frequency_channel(&self) -> Result<EventReceiver<f64>> {
+        let (tx, rx) = mpsc::channel();
+        let guard = self.set_on_frequency_change_handler(move |_, v| {
+            let _ = tx.send(v);
+        })?;
+        Ok(EventReceiver::new(rx, guard))
+    }
+
+    /// Gets a stream of the frequency readings, fed by the frequency change events.
+    ///
+    /// This registers the channel's frequency change handler, replacing any handler
+    /// that was previously set.
+    /// The handler is removed when the stream is dropped.
+    #[cfg(feature = "async")]
+    pub fn frequency_stream(&self) -> Result<EventStream<f64>> {
+        let (mut tx, rx) = crate::stream::event_channel();
+        let guard = self.set_on_frequency_change_handler(move |_, v| tx.send(v))?;
+        Ok(EventStream::new(rx, guard))
+    }
+}
+
+impl Phidget for FrequencyCounter {
+    fn as_handle(&mut self) -> PhidgetHandle {
+        self.chan as PhidgetHandle
+    }
+}
+
+impl Sensor for FrequencyCounter {
+    type Value = f64;
+
+    fn value(&self) -> Result<f64> {
+        self.frequency()
+    }
+
+    fn on_change<F>(&self, cb: F) -> Result<HandlerGuard>
+    where
+        F: FnMut(&Self, f64) + Send + 'static,
+    {
+        self.set_on_frequency_change_handler(cb)
+    }
+}
+
+impl fmt::Debug for FrequencyCounter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        crate::phidget::fmt_debug(f, "FrequencyCounter", self.chan as PhidgetHandle)
+    }
+}
+
+impl fmt::Display for FrequencyCounter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        crate::phidget::fmt_display(f, "FrequencyCounter", self.chan as PhidgetHandle)
+    }
+}
+
+unsafe impl Send for FrequencyCounter {}
+unsafe impl Sync for FrequencyCounter {}
+
+impl Default for FrequencyCounter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl From<PhidgetFrequencyCounterHandle> for FrequencyCounter {
+    fn from(chan: PhidgetFrequencyCounterHandle) -> Self {
+        Self { chan }
+    }
+}
+
+impl TryFrom<GenericPhidget> for FrequencyCounter {
+    type Error = Error;
+
+    /// Converts a generic phidget into a typed channel, if it's of the
+    /// right class. If the channel is open, it's closed when dropped.
+    fn try_from(ph: GenericPhidget) -> Result<Self> {
+        let chan = ph.into_channel(ChannelClass::FrequencyCounter)?;
+        Ok(Self::from(chan as PhidgetFrequencyCounterHandle))
+    }
+}
+
+impl Drop for FrequencyCounter {
+    fn drop(&mut self) {
+        if let Ok(true) = self.is_open() {
+            let _ = self.close();
+        }
+        let phid = self.chan as PhidgetHandle;
+        unsafe {
+            ffi::PhidgetFrequencyCounter_delete(&mut self.chan);
+        }
+        crate::notify::reset(phid);
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::*;
+    use crate::mock::{detach, set_property};
+
+    #[test]
+    fn test_mock_counting_window() {
+        let mut fc = FrequencyCounter::new();
+        fc.open().unwrap();
+        fc.set_enabled(true).unwrap();
+        assert_eq!(fc.enabled(), Ok(true));
+
+        set_property(&mut fc, "Count", 42.0).unwrap();
+        set_property(&mut fc, "TimeElapsed", 1500.0).unwrap();
+        assert_eq!(fc.count(), Ok(42));
+        assert_eq!(fc.time_elapsed(), Ok(Duration::from_millis(1500)));
+
+        // A reset starts a new window
+        fc.reset().unwrap();
+        assert_eq!(fc.count(), Ok(0));
+        assert_eq!(fc.time_elapsed(), Ok(Duration::ZERO));
+
+        fc.set_enabled(false).unwrap();
+        assert_eq!(fc.enabled(), Ok(false));
+
+        detach(&mut fc);
+        assert_eq!(fc.reset(), Err(Error::NotAttached));
+    }
+
+    #[test]
+    fn test_mock_frequency_cutoff() {
+        let mut fc = FrequencyCounter::new();
+        fc.open().unwrap();
+        set_property(&mut fc, "MinFrequencyCutoff", 0.01).unwrap();
+        set_property(&mut fc, "MaxFrequencyCutoff", 10.0).unwrap();
+        assert_eq!(fc.min_frequency_cutoff(), Ok(0.01));
+        assert_eq!(fc.max_frequency_cutoff(), Ok(10.0));
+
+        fc.set_frequency_cutoff(0.5).unwrap();
+        assert_eq!(fc.frequency_cutoff(), Ok(0.5));
+    }
+}
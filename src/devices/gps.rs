@@ -0,0 +1,289 @@
+// phidget-rs/src/devices/gps.rs
+//
+// This file is part of the 'phidget-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+use crate::{
+    handler::Context, ChannelClass, Error, EventReceiver, GenericPhidget, HandlerGuard,
+    HandlerResult, HandlerScope, Phidget, Result, ReturnCode,
+};
+use phidget_sys::{self as ffi, PhidgetGPSHandle, PhidgetHandle};
+use std::{
+    fmt, mem,
+    os::raw::{c_int, c_void},
+    ptr,
+    sync::mpsc,
+};
+
+#[cfg(feature = "async")]
+use crate::stream::EventStream;
+
+/// The function signature for the safe Rust heading change callback.
+///
+/// This receives the heading, in degrees, and the velocity, in km/h.
+pub type HeadingChangeCallback = dyn FnMut(&Gps, f64, f64) + Send + 'static;
+
+/////////////////////////////////////////////////////////////////////////////
+
+/// Phidget GPS
+pub struct Gps {
+    // Handle to the GPS in the phidget22 library
+    chan: PhidgetGPSHandle,
+}
+
+impl Gps {
+    /// Create a new GPS channel.
+    ///
+    /// This panics if the library can't create the channel. Use
+    /// `try_new()` to get the error instead.
+    pub fn new() -> Self {
+        Self::try_new().expect("failed to create the channel")
+    }
+
+    /// Create a new GPS channel, returning an error if the library can't
+    /// create the channel.
+    pub fn try_new() -> Result<Self> {
+        let mut chan: PhidgetGPSHandle = ptr::null_mut();
+        ReturnCode::result(unsafe { ffi::PhidgetGPS_create(&mut chan) })?;
+        crate::phidget::install_handlers(chan as PhidgetHandle);
+        Ok(Self::from(chan))
+    }
+
+    // Low-level, unsafe, callback for the heading change event.
+    // The context holds a view of the channel and the safe Rust callback.
+    unsafe extern "C" fn on_heading_change<F, R>(
+        chan: PhidgetGPSHandle,
+        ctx: *mut c_void,
+        heading: f64,
+        velocity: f64,
+    ) where
+        F: FnMut(&Gps, f64, f64) -> R + Send + 'static,
+        R: HandlerResult,
+    {
+        crate::handler::deliver(chan as PhidgetHandle, ctx, move |phid, ctx| {
+            let ctx: &mut Context<Self, F> = &mut *(ctx as *mut _);
+            crate::handler::report(phid, (ctx.cb)(&ctx.chan, heading, velocity));
+        });
+    }
+
+    /// Get a reference to the underlying GPS handle
+    pub fn as_channel(&self) -> &PhidgetGPSHandle {
+        &self.chan
+    }
+
+    /// Determines if the GPS has a fix on its position.
+    pub fn position_fix_state(&self) -> Result<bool> {
+        let mut fix: c_int = 0;
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetGPS_getPositionFixState(self.chan, &mut fix))
+        })?;
+        Ok(fix != 0)
+    }
+
+    /// Get the latitude of the position, in degrees
+    pub fn latitude(&self) -> Result<f64> {
+        let mut v: f64 = 0.0;
+        ReturnCode::result(unsafe { traced!(ffi::PhidgetGPS_getLatitude(self.chan, &mut v)) })?;
+        Ok(v)
+    }
+
+    /// Get the longitude of the position, in degrees
+    pub fn longitude(&self) -> Result<f64> {
+        let mut v: f64 = 0.0;
+        ReturnCode::result(unsafe { traced!(ffi::PhidgetGPS_getLongitude(self.chan, &mut v)) })?;
+        Ok(v)
+    }
+
+    /// Get the altitude of the position, in meters
+    pub fn altitude(&self) -> Result<f64> {
+        let mut v: f64 = 0.0;
+        ReturnCode::result(unsafe { traced!(ffi::PhidgetGPS_getAltitude(self.chan, &mut v)) })?;
+        Ok(v)
+    }
+
+    /// Get the heading, in degrees
+    pub fn heading(&self) -> Result<f64> {
+        let mut v: f64 = 0.0;
+        ReturnCode::result(unsafe { traced!(ffi::PhidgetGPS_getHeading(self.chan, &mut v)) })?;
+        Ok(v)
+    }
+
+    /// Get the velocity, in km/h
+    pub fn velocity(&self) -> Result<f64> {
+        let mut v: f64 = 0.0;
+        ReturnCode::result(unsafe { traced!(ffi::PhidgetGPS_getVelocity(self.chan, &mut v)) })?;
+        Ok(v)
+    }
+
+    /// Sets a handler to receive heading change callbacks, with the
+    /// heading, in degrees, and the velocity, in km/h.
+    /// The handler is removed when the returned guard is dropped.
+    pub fn set_on_heading_change_handler<F, R>(&self, cb: F) -> Result<HandlerGuard>
+    where
+        F: FnMut(&Gps, f64, f64) -> R + Send + 'static,
+        R: HandlerResult,
+    {
+        let ctx = Context::into_raw(Self::from(self.chan), cb);
+
+        crate::handler::register::<Context<Self, F>, _>(
+            self.chan as PhidgetHandle,
+            "heading_change",
+            ctx,
+            || unsafe {
+                traced!(ffi::PhidgetGPS_setOnHeadingChangeHandler(
+                    self.chan,
+                    Some(Self::on_heading_change::<F, R>),
+                    ctx,
+                ))
+            },
+            |phid| unsafe {
+                traced!(ffi::PhidgetGPS_setOnHeadingChangeHandler(
+                    phid as _,
+                    None,
+                    ptr::null_mut()
+                ))
+            },
+        )
+    }
+
+    /// Set a handler to receive heading change callbacks, which can borrow
+    /// from the environment of the scope.
+    /// The handler is removed at the end of the scope.
+    pub fn set_on_heading_change_handler_scoped<'scope, F, R>(
+        &self,
+        scope: &'scope HandlerScope<'scope, '_>,
+        cb: F,
+    ) -> Result<()>
+    where
+        F: FnMut(&Gps, f64, f64) -> R + Send + 'scope,
+        R: HandlerResult + 'static,
+    {
+        // SAFETY: The guard is held by the scope, which can't be forgotten,
+        // and which waits for any call to the handler. See `HandlerScope`.
+        let cb = unsafe {
+            mem::transmute::<
+                Box<dyn FnMut(&Gps, f64, f64) -> R + Send + 'scope>,
+                Box<dyn FnMut(&Gps, f64, f64) -> R + Send>,
+            >(Box::new(cb))
+        };
+        scope.hold(self.set_on_heading_change_handler(cb)?);
+        Ok(())
+    }
+
+    /// Removes the heading change handler, if one is set, and releases its
+    /// closure.
+    pub fn remove_on_heading_change_handler(&self) -> Result<()> {
+        crate::handler::unregister(self.chan as PhidgetHandle, "heading_change")
+    }
+
+    /// Gets a channel that receives the heading and velocity, as a pair,
+    /// fed by the heading change events.
+    ///
+    /// This registers the channel's heading change handler, replacing any
+    /// handler that was previously set.
+    /// The handler is removed when the receiver is dropped.
+    pub fn heading_channel(&self) -> Result<EventReceiver<(f64, f64)>> {
+        let (tx, rx) = mpsc::channel();
+        let guard = self.set_on_heading_change_handler(move |_, heading, velocity| {
+            let _ = tx.send((heading, velocity));
+        })?;
+        Ok(EventReceiver::new(rx, guard))
+    }
+
+    /// Gets a stream of the heading and velocity, as a pair, fed by the
+    /// heading change events.
+    ///
+    /// This registers the channel's heading change handler, replacing any
+    /// handler that was previously set.
+    /// The handler is removed when the stream is dropped.
+    #[cfg(feature = "async")]
+    pub fn heading_stream(&self) -> Result<EventStream<(f64, f64)>> {
+        let (mut tx, rx) = crate::stream::event_channel();
+        let guard = self.set_on_heading_change_handler(move |_, heading, velocity| {
+            tx.send((heading, velocity))
+        })?;
+        Ok(EventStream::new(rx, guard))
+    }
+}
+
+impl Phidget for Gps {
+    fn as_handle(&mut self) -> PhidgetHandle {
+        self.chan as PhidgetHandle
+    }
+}
+
+impl fmt::Debug for Gps {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        crate::phidget::fmt_debug(f, "Gps", self.chan as PhidgetHandle)
+    }
+}
+
+impl fmt::Display for Gps {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        crate::phidget::fmt_display(f, "Gps", self.chan as PhidgetHandle)
+    }
+}
+
+unsafe impl Send for Gps {}
+unsafe impl Sync for Gps {}
+
+impl Default for Gps {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl From<PhidgetGPSHandle> for Gps {
+    fn from(chan: PhidgetGPSHandle) -> Self {
+        Self { chan }
+    }
+}
+
+impl TryFrom<GenericPhidget> for Gps {
+    type Error = Error;
+
+    /// Converts a generic phidget into a typed channel, if it's of the
+    /// right class. If the channel is open, it's closed when dropped.
+    fn try_from(ph: GenericPhidget) -> Result<Self> {
+        let chan = ph.into_channel(ChannelClass::Gps)?;
+        Ok(Self::from(chan as PhidgetGPSHandle))
+    }
+}
+
+impl Drop for Gps {
+    fn drop(&mut self) {
+        if let Ok(true) = self.is_open() {
+            let _ = self.close();
+        }
+        let phid = self.chan as PhidgetHandle;
+        unsafe {
+            ffi::PhidgetGPS_delete(&mut self.chan);
+        }
+        crate::notify::reset(phid);
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::*;
+    use crate::mock::set_heading;
+
+    #[test]
+    fn test_mock_gps() {
+        let mut gps = Gps::new();
+        gps.open().unwrap();
+
+        let rx = gps.heading_channel().unwrap();
+        set_heading(&mut gps, 270.0, 42.5).unwrap();
+        assert_eq!(rx.try_recv(), Ok((270.0, 42.5)));
+        assert_eq!(gps.heading(), Ok(270.0));
+        assert_eq!(gps.velocity(), Ok(42.5));
+    }
+}
@@ -10,10 +10,11 @@
 // to those terms.
 //
 
-use crate::{AttachCallback, DetachCallback, Error, GenericPhidget, Phidget, Result, ReturnCode};
+use crate::{ChannelClass, Error, GenericPhidget, Phidget, Result, ReturnCode};
 use phidget_sys::{self as ffi, PhidgetHandle, PhidgetHubHandle as HubHandle};
 use std::{
-    os::raw::{c_int, c_uint, c_void},
+    fmt,
+    os::raw::{c_int, c_uint},
     ptr,
 };
 
@@ -21,6 +22,7 @@ use std::{
 
 /// Possible operational modes for a hub port
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u32)]
 pub enum HubPortMode {
     /// Communicate with a smart VINT device
@@ -46,7 +48,7 @@ impl TryFrom<u32> for HubPortMode {
             ffi::PhidgetHub_PortMode_PORT_MODE_DIGITAL_OUTPUT => Ok(DigitalOutput), // 2
             ffi::PhidgetHub_PortMode_PORT_MODE_VOLTAGE_INPUT => Ok(VoltageInput), // 3
             ffi::PhidgetHub_PortMode_PORT_MODE_VOLTAGE_RATIO_INPUT => Ok(VoltageRatioInput), // 4
-            _ => Err(ReturnCode::InvalidArg),
+            _ => Err(Error::InvalidArg),
         }
     }
 }
@@ -55,64 +57,65 @@ impl TryFrom<u32> for HubPortMode {
 pub struct Hub {
     // Handle to the hub in the phidget22 library
     chan: HubHandle,
-    // Double-boxed attach callback, if registered
-    attach_cb: Option<*mut c_void>,
-    // Double-boxed detach callback, if registered
-    detach_cb: Option<*mut c_void>,
 }
 
 impl Hub {
     /// Create a new hub.
+    ///
+    /// This panics if the library can't create the channel. Use
+    /// `try_new()` to get the error instead.
     pub fn new() -> Self {
+        Self::try_new().expect("failed to create the channel")
+    }
+
+    /// Create a new hub, returning an error if the library can't
+    /// create the channel.
+    pub fn try_new() -> Result<Self> {
         let mut chan: HubHandle = ptr::null_mut();
-        unsafe {
-            ffi::PhidgetHub_create(&mut chan);
-        }
-        Self::from(chan)
+        ReturnCode::result(unsafe { ffi::PhidgetHub_create(&mut chan) })?;
+        crate::phidget::install_handlers(chan as PhidgetHandle);
+        Ok(Self::from(chan))
     }
 
     /// Get the mode of the specified hub port
     pub fn port_mode(&self, port: i32) -> Result<HubPortMode> {
         let port = port as c_int;
         let mut mode: c_uint = 0;
-        ReturnCode::result(unsafe { ffi::PhidgetHub_getPortMode(self.chan, port, &mut mode) })?;
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetHub_getPortMode(self.chan, port, &mut mode))
+        })?;
         HubPortMode::try_from(mode)
     }
 
     /// Set the mode of the specified hub port
     pub fn set_port_mode(&mut self, port: i32, mode: HubPortMode) -> Result<()> {
         let port = port as c_int;
-        ReturnCode::result(unsafe { ffi::PhidgetHub_setPortMode(self.chan, port, mode as c_uint) })
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetHub_setPortMode(self.chan, port, mode as c_uint))
+        })
     }
+}
 
-    /// Sets a handler to receive attach callbacks
-    pub fn set_on_attach_handler<F>(&mut self, cb: F) -> Result<()>
-    where
-        F: Fn(&GenericPhidget) + Send + 'static,
-    {
-        let ctx = crate::phidget::set_on_attach_handler(self, cb)?;
-        self.attach_cb = Some(ctx);
-        Ok(())
+impl Phidget for Hub {
+    fn as_handle(&mut self) -> PhidgetHandle {
+        self.chan as PhidgetHandle
     }
+}
 
-    /// Sets a handler to receive detach callbacks
-    pub fn set_on_detach_handler<F>(&mut self, cb: F) -> Result<()>
-    where
-        F: Fn(&GenericPhidget) + Send + 'static,
-    {
-        let ctx = crate::phidget::set_on_detach_handler(self, cb)?;
-        self.detach_cb = Some(ctx);
-        Ok(())
+impl fmt::Debug for Hub {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        crate::phidget::fmt_debug(f, "Hub", self.chan as PhidgetHandle)
     }
 }
 
-impl Phidget for Hub {
-    fn as_handle(&mut self) -> PhidgetHandle {
-        self.chan as PhidgetHandle
+impl fmt::Display for Hub {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        crate::phidget::fmt_display(f, "Hub", self.chan as PhidgetHandle)
     }
 }
 
 unsafe impl Send for Hub {}
+unsafe impl Sync for Hub {}
 
 impl Default for Hub {
     fn default() -> Self {
@@ -122,11 +125,18 @@ impl Default for Hub {
 
 impl From<HubHandle> for Hub {
     fn from(chan: HubHandle) -> Self {
-        Self {
-            chan,
-            attach_cb: None,
-            detach_cb: None,
-        }
+        Self { chan }
+    }
+}
+
+impl TryFrom<GenericPhidget> for Hub {
+    type Error = Error;
+
+    /// Converts a generic phidget into a typed channel, if it's of the
+    /// right class. If the channel is open, it's closed when dropped.
+    fn try_from(ph: GenericPhidget) -> Result<Self> {
+        let chan = ph.into_channel(ChannelClass::Hub)?;
+        Ok(Self::from(chan as HubHandle))
     }
 }
 
@@ -135,10 +145,10 @@ impl Drop for Hub {
         if let Ok(true) = self.is_open() {
             let _ = self.close();
         }
+        let phid = self.chan as PhidgetHandle;
         unsafe {
             ffi::PhidgetHub_delete(&mut self.chan);
-            crate::drop_cb::<AttachCallback>(self.attach_cb.take());
-            crate::drop_cb::<DetachCallback>(self.detach_cb.take());
         }
+        crate::notify::reset(phid);
     }
 }
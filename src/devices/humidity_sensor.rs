@@ -12,50 +12,62 @@
 //! Phidget Humidity sensor
 //!
 
-use crate::{AttachCallback, DetachCallback, GenericPhidget, Phidget, Result, ReturnCode};
+use crate::{
+    handler::Context, ChannelClass, Error, EventReceiver, GenericPhidget, HandlerGuard,
+    HandlerResult, HandlerScope, Phidget, Result, ReturnCode, Sensor,
+};
 use phidget_sys::{
     self as ffi, PhidgetHandle, PhidgetHumiditySensorHandle as HumiditySensorHandle,
 };
-use std::{mem, os::raw::c_void, ptr};
+use std::{fmt, mem, os::raw::c_void, ptr, sync::mpsc};
+
+#[cfg(feature = "async")]
+use crate::stream::EventStream;
+
+#[cfg(feature = "units")]
+use uom::si::{f64::Ratio, ratio::percent};
 
 /// The function signature for the safe Rust humidity change callback.
-pub type HumidityCallback = dyn Fn(&HumiditySensor, f64) + Send + 'static;
+pub type HumidityCallback = dyn FnMut(&HumiditySensor, f64) + Send + 'static;
 
 /// Phidget humidity sensor
 pub struct HumiditySensor {
     // Handle to the sensor for the phidget22 library
     chan: HumiditySensorHandle,
-    // Double-boxed HumidityCallback, if registered
-    cb: Option<*mut c_void>,
-    // Double-boxed attach callback, if registered
-    attach_cb: Option<*mut c_void>,
-    // Double-boxed detach callback, if registered
-    detach_cb: Option<*mut c_void>,
 }
 
 impl HumiditySensor {
     /// Create a new humidity sensor.
+    ///
+    /// This panics if the library can't create the channel. Use
+    /// `try_new()` to get the error instead.
     pub fn new() -> Self {
+        Self::try_new().expect("failed to create the channel")
+    }
+
+    /// Create a new humidity sensor, returning an error if the library can't
+    /// create the channel.
+    pub fn try_new() -> Result<Self> {
         let mut chan: HumiditySensorHandle = ptr::null_mut();
-        unsafe {
-            ffi::PhidgetHumiditySensor_create(&mut chan);
-        }
-        Self::from(chan)
+        ReturnCode::result(unsafe { ffi::PhidgetHumiditySensor_create(&mut chan) })?;
+        crate::phidget::install_handlers(chan as PhidgetHandle);
+        Ok(Self::from(chan))
     }
 
     // Low-level, unsafe, callback for humidity change events.
-    // The context is a double-boxed pointer the the safe Rust callback.
-    unsafe extern "C" fn on_humidity_change(
+    // The context holds a view of the channel and the safe Rust callback.
+    unsafe extern "C" fn on_humidity_change<F, R>(
         chan: HumiditySensorHandle,
         ctx: *mut c_void,
         humidity: f64,
-    ) {
-        if !ctx.is_null() {
-            let cb: &mut Box<HumidityCallback> = &mut *(ctx as *mut _);
-            let sensor = Self::from(chan);
-            cb(&sensor, humidity);
-            mem::forget(sensor);
-        }
+    ) where
+        F: FnMut(&HumiditySensor, f64) -> R + Send + 'static,
+        R: HandlerResult,
+    {
+        crate::handler::deliver(chan as PhidgetHandle, ctx, move |phid, ctx| {
+            let ctx: &mut Context<Self, F> = &mut *(ctx as *mut _);
+            crate::handler::report(phid, (ctx.cb)(&ctx.chan, humidity));
+        });
     }
 
     /// Get a reference to the underlying sensor handle
@@ -67,49 +79,140 @@ impl HumiditySensor {
     pub fn humidity(&self) -> Result<f64> {
         let mut humidity = 0.0;
         ReturnCode::result(unsafe {
-            ffi::PhidgetHumiditySensor_getHumidity(self.chan, &mut humidity)
+            traced!(ffi::PhidgetHumiditySensor_getHumidity(
+                self.chan,
+                &mut humidity
+            ))
         })?;
         Ok(humidity)
     }
 
-    /// Sets a handler to receive humitity change callbacks.
-    pub fn set_on_humidity_change_handler<F>(&mut self, cb: F) -> Result<()>
-    where
-        F: Fn(&HumiditySensor, f64) + Send + 'static,
-    {
-        // 1st box is fat ptr, 2nd is regular pointer.
-        let cb: Box<Box<HumidityCallback>> = Box::new(Box::new(cb));
-        let ctx = Box::into_raw(cb) as *mut c_void;
-        self.cb = Some(ctx);
+    /// Read the current relative humidity as a typed quantity.
+    #[cfg(feature = "units")]
+    pub fn humidity_quantity(&self) -> Result<Ratio> {
+        Ok(Ratio::new::<percent>(self.humidity()?))
+    }
 
+    /// Gets the minimum change in humidity, in percent relative humidity, that triggers a change
+    /// event.
+    pub fn humidity_change_trigger(&self) -> Result<f64> {
+        let mut trigger = 0.0;
         ReturnCode::result(unsafe {
-            ffi::PhidgetHumiditySensor_setOnHumidityChangeHandler(
+            traced!(ffi::PhidgetHumiditySensor_getHumidityChangeTrigger(
                 self.chan,
-                Some(Self::on_humidity_change),
-                ctx,
-            )
+                &mut trigger
+            ))
+        })?;
+        Ok(trigger)
+    }
+
+    /// Sets the minimum change in humidity, in percent relative humidity, that triggers a change
+    /// event. Zero reports every reading.
+    pub fn set_humidity_change_trigger(&mut self, trigger: f64) -> Result<()> {
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetHumiditySensor_setHumidityChangeTrigger(
+                self.chan, trigger
+            ))
         })
     }
 
-    /// Sets a handler to receive attach callbacks
-    pub fn set_on_attach_handler<F>(&mut self, cb: F) -> Result<()>
+    /// Set a handler to receive humidity change callbacks with the value as a
+    /// typed quantity.
+    /// This replaces any humidity change handler that was previously set.
+    /// The handler is removed when the returned guard is dropped.
+    #[cfg(feature = "units")]
+    pub fn set_on_humidity_change_quantity_handler<F, R>(&self, mut cb: F) -> Result<HandlerGuard>
     where
-        F: Fn(&GenericPhidget) + Send + 'static,
+        F: FnMut(&HumiditySensor, Ratio) -> R + Send + 'static,
+        R: HandlerResult,
     {
-        let ctx = crate::phidget::set_on_attach_handler(self, cb)?;
-        self.attach_cb = Some(ctx);
-        Ok(())
+        self.set_on_humidity_change_handler(move |ch, val| cb(ch, Ratio::new::<percent>(val)))
     }
 
-    /// Sets a handler to receive detach callbacks
-    pub fn set_on_detach_handler<F>(&mut self, cb: F) -> Result<()>
+    /// Sets a handler to receive humitity change callbacks.
+    /// The handler is removed when the returned guard is dropped.
+    pub fn set_on_humidity_change_handler<F, R>(&self, cb: F) -> Result<HandlerGuard>
     where
-        F: Fn(&GenericPhidget) + Send + 'static,
+        F: FnMut(&HumiditySensor, f64) -> R + Send + 'static,
+        R: HandlerResult,
     {
-        let ctx = crate::phidget::set_on_detach_handler(self, cb)?;
-        self.detach_cb = Some(ctx);
+        let ctx = Context::into_raw(Self::from(self.chan), cb);
+
+        crate::handler::register::<Context<Self, F>, _>(
+            self.chan as PhidgetHandle,
+            "humidity_change",
+            ctx,
+            || unsafe {
+                traced!(ffi::PhidgetHumiditySensor_setOnHumidityChangeHandler(
+                    self.chan,
+                    Some(Self::on_humidity_change::<F, R>),
+                    ctx,
+                ))
+            },
+            |phid| unsafe {
+                traced!(ffi::PhidgetHumiditySensor_setOnHumidityChangeHandler(
+                    phid as _,
+                    None,
+                    ptr::null_mut(),
+                ))
+            },
+        )
+    }
+
+    /// Set a handler to receive humidity change callbacks, which can borrow
+    /// from the environment of the scope.
+    /// The handler is removed at the end of the scope.
+    pub fn set_on_humidity_change_handler_scoped<'scope, F, R>(
+        &self,
+        scope: &'scope HandlerScope<'scope, '_>,
+        cb: F,
+    ) -> Result<()>
+    where
+        F: FnMut(&HumiditySensor, f64) -> R + Send + 'scope,
+        R: HandlerResult + 'static,
+    {
+        // SAFETY: The guard is held by the scope, which can't be forgotten,
+        // and which waits for any call to the handler. See `HandlerScope`.
+        let cb = unsafe {
+            mem::transmute::<
+                Box<dyn FnMut(&HumiditySensor, f64) -> R + Send + 'scope>,
+                Box<dyn FnMut(&HumiditySensor, f64) -> R + Send>,
+            >(Box::new(cb))
+        };
+        scope.hold(self.set_on_humidity_change_handler(cb)?);
         Ok(())
     }
+
+    /// Removes the humidity change handler, if one is set, and releases
+    /// its closure.
+    pub fn remove_on_humidity_change_handler(&self) -> Result<()> {
+        crate::handler::unregister(self.chan as PhidgetHandle, "humidity_change")
+    }
+
+    /// Gets a channel that receives the humidity readings, fed by the humidity change events.
+    ///
+    /// This registers the channel's humidity change handler, replacing any handler
+    /// that was previously set.
+    /// The handler is removed when the receiver is dropped.
+    pub fn humidity_channel(&self) -> Result<EventReceiver<f64>> {
+        let (tx, rx) = mpsc::channel();
+        let guard = self.set_on_humidity_change_handler(move |_, h| {
+            let _ = tx.send(h);
+        })?;
+        Ok(EventReceiver::new(rx, guard))
+    }
+
+    /// Gets a stream of the humidity readings, fed by the humidity change events.
+    ///
+    /// This registers the channel's humidity change handler, replacing any handler
+    /// that was previously set.
+    /// The handler is removed when the stream is dropped.
+    #[cfg(feature = "async")]
+    pub fn humidity_stream(&self) -> Result<EventStream<f64>> {
+        let (mut tx, rx) = crate::stream::event_channel();
+        let guard = self.set_on_humidity_change_handler(move |_, h| tx.send(h))?;
+        Ok(EventStream::new(rx, guard))
+    }
 }
 
 impl Phidget for HumiditySensor {
@@ -118,7 +221,35 @@ impl Phidget for HumiditySensor {
     }
 }
 
+impl Sensor for HumiditySensor {
+    type Value = f64;
+
+    fn value(&self) -> Result<f64> {
+        self.humidity()
+    }
+
+    fn on_change<F>(&self, cb: F) -> Result<HandlerGuard>
+    where
+        F: FnMut(&Self, f64) + Send + 'static,
+    {
+        self.set_on_humidity_change_handler(cb)
+    }
+}
+
+impl fmt::Debug for HumiditySensor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        crate::phidget::fmt_debug(f, "HumiditySensor", self.chan as PhidgetHandle)
+    }
+}
+
+impl fmt::Display for HumiditySensor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        crate::phidget::fmt_display(f, "HumiditySensor", self.chan as PhidgetHandle)
+    }
+}
+
 unsafe impl Send for HumiditySensor {}
+unsafe impl Sync for HumiditySensor {}
 
 impl Default for HumiditySensor {
     fn default() -> Self {
@@ -128,12 +259,18 @@ impl Default for HumiditySensor {
 
 impl From<HumiditySensorHandle> for HumiditySensor {
     fn from(chan: HumiditySensorHandle) -> Self {
-        Self {
-            chan,
-            cb: None,
-            attach_cb: None,
-            detach_cb: None,
-        }
+        Self { chan }
+    }
+}
+
+impl TryFrom<GenericPhidget> for HumiditySensor {
+    type Error = Error;
+
+    /// Converts a generic phidget into a typed channel, if it's of the
+    /// right class. If the channel is open, it's closed when dropped.
+    fn try_from(ph: GenericPhidget) -> Result<Self> {
+        let chan = ph.into_channel(ChannelClass::HumiditySensor)?;
+        Ok(Self::from(chan as HumiditySensorHandle))
     }
 }
 
@@ -142,11 +279,10 @@ impl Drop for HumiditySensor {
         if let Ok(true) = self.is_open() {
             let _ = self.close();
         }
+        let phid = self.chan as PhidgetHandle;
         unsafe {
             ffi::PhidgetHumiditySensor_delete(&mut self.chan);
-            crate::drop_cb::<HumidityCallback>(self.cb.take());
-            crate::drop_cb::<AttachCallback>(self.attach_cb.take());
-            crate::drop_cb::<DetachCallback>(self.detach_cb.take());
         }
+        crate::notify::reset(phid);
     }
 }
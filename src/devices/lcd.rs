@@ -0,0 +1,426 @@
+// phidget-rs/src/devices/lcd.rs
+//
+// This file is part of the 'phidget-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+use crate::{ChannelClass, Error, GenericPhidget, Phidget, Result, ReturnCode};
+use phidget_sys::{self as ffi, PhidgetHandle, PhidgetLCDHandle};
+use std::{ffi::CString, fmt, os::raw::c_int, ptr};
+
+/// The fonts for writing text to an LCD
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(u32)]
+pub enum Font {
+    /// The first user-defined font
+    User1 = ffi::PhidgetLCD_Font_FONT_User1,
+    /// The second user-defined font
+    User2 = ffi::PhidgetLCD_Font_FONT_User2,
+    /// The built-in 6x10 pixel font
+    Font6x10 = ffi::PhidgetLCD_Font_FONT_6x10,
+    /// The built-in 5x8 pixel font
+    Font5x8 = ffi::PhidgetLCD_Font_FONT_5x8,
+    /// The built-in 6x12 pixel font
+    Font6x12 = ffi::PhidgetLCD_Font_FONT_6x12,
+}
+
+/// The state to draw a pixel in
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(u32)]
+pub enum PixelState {
+    /// The pixel is turned off
+    Off = ffi::PhidgetLCD_PixelState_PIXEL_STATE_OFF,
+    /// The pixel is turned on
+    On = ffi::PhidgetLCD_PixelState_PIXEL_STATE_ON,
+    /// The pixel is switched from its current state
+    Invert = ffi::PhidgetLCD_PixelState_PIXEL_STATE_INVERT,
+}
+
+/// A rectangular region of the screen, in pixels.
+///
+/// The corners are inclusive, so a single pixel has the same start and
+/// end.
+#[derive(Copy, Clone, Debug, Default, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Region {
+    /// The left column
+    pub x1: i32,
+    /// The top row
+    pub y1: i32,
+    /// The right column
+    pub x2: i32,
+    /// The bottom row
+    pub y2: i32,
+}
+
+impl Region {
+    /// Creates a region from two opposite corners, in any order.
+    pub fn new(x1: i32, y1: i32, x2: i32, y2: i32) -> Self {
+        Self {
+            x1: x1.min(x2),
+            y1: y1.min(y2),
+            x2: x1.max(x2),
+            y2: y1.max(y2),
+        }
+    }
+
+    /// Gets the smallest region that covers this one and the other.
+    pub fn union(&self, other: &Region) -> Self {
+        Self {
+            x1: self.x1.min(other.x1),
+            y1: self.y1.min(other.y1),
+            x2: self.x2.max(other.x2),
+            y2: self.y2.max(other.y2),
+        }
+    }
+
+    /// Gets the width of the region, in pixels.
+    pub fn width(&self) -> i32 {
+        self.x2 - self.x1 + 1
+    }
+
+    /// Gets the height of the region, in pixels.
+    pub fn height(&self) -> i32 {
+        self.y2 - self.y1 + 1
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+/// Phidget graphic LCD, like the LCD1100
+///
+/// Drawing is done in the frame buffer of the library, and is sent to the
+/// screen by `flush()`. The channel keeps track of the region that was
+/// drawn since the last flush, so a flush with nothing drawn doesn't make
+/// a transfer to the device. Sending the frame buffer is up to the
+/// phidget22 library, so the region can't limit what a flush sends, but
+/// it lets an application see, or batch, what changed.
+pub struct Lcd {
+    // Handle to the LCD in the phidget22 library
+    chan: PhidgetLCDHandle,
+    // The region drawn since the last flush
+    dirty: Option<Region>,
+}
+
+impl Lcd {
+    /// Create a new LCD channel.
+    ///
+    /// This panics if the library can't create the channel. Use
+    /// `try_new()` to get the error instead.
+    pub fn new() -> Self {
+        Self::try_new().expect("failed to create the channel")
+    }
+
+    /// Create a new LCD channel, returning an error if the library can't
+    /// create the channel.
+    pub fn try_new() -> Result<Self> {
+        let mut chan: PhidgetLCDHandle = ptr::null_mut();
+        ReturnCode::result(unsafe { ffi::PhidgetLCD_create(&mut chan) })?;
+        crate::phidget::install_handlers(chan as PhidgetHandle);
+        Ok(Self::from(chan))
+    }
+
+    /// Get a reference to the underlying LCD handle
+    pub fn as_channel(&self) -> &PhidgetLCDHandle {
+        &self.chan
+    }
+
+    /// Gets the width of the screen, in pixels.
+    pub fn width(&self) -> Result<i32> {
+        crate::cache::property(self.chan as PhidgetHandle, "Width", || {
+            let mut w: c_int = 0;
+            ReturnCode::result(unsafe { traced!(ffi::PhidgetLCD_getWidth(self.chan, &mut w)) })?;
+            Ok(w as i32)
+        })
+    }
+
+    /// Gets the height of the screen, in pixels.
+    pub fn height(&self) -> Result<i32> {
+        crate::cache::property(self.chan as PhidgetHandle, "Height", || {
+            let mut h: c_int = 0;
+            ReturnCode::result(unsafe { traced!(ffi::PhidgetLCD_getHeight(self.chan, &mut h)) })?;
+            Ok(h as i32)
+        })
+    }
+
+    /// Gets the brightness of the backlight, from 0.0 to 1.0.
+    pub fn backlight(&self) -> Result<f64> {
+        let mut value = 0.0;
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetLCD_getBacklight(self.chan, &mut value))
+        })?;
+        Ok(value)
+    }
+
+    /// Sets the brightness of the backlight, from 0.0 to 1.0.
+    pub fn set_backlight(&mut self, backlight: f64) -> Result<()> {
+        ReturnCode::result(unsafe { traced!(ffi::PhidgetLCD_setBacklight(self.chan, backlight)) })
+    }
+
+    /// Gets the contrast of the screen, from 0.0 to 1.0.
+    pub fn contrast(&self) -> Result<f64> {
+        let mut value = 0.0;
+        ReturnCode::result(unsafe { traced!(ffi::PhidgetLCD_getContrast(self.chan, &mut value)) })?;
+        Ok(value)
+    }
+
+    /// Sets the contrast of the screen, from 0.0 to 1.0.
+    pub fn set_contrast(&mut self, contrast: f64) -> Result<()> {
+        ReturnCode::result(unsafe { traced!(ffi::PhidgetLCD_setContrast(self.chan, contrast)) })
+    }
+
+    /// Gets the size of the characters of a font, in pixels, as
+    /// (width, height).
+    pub fn font_size(&self, font: Font) -> Result<(i32, i32)> {
+        let (mut w, mut h): (c_int, c_int) = (0, 0);
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetLCD_getFontSize(
+                self.chan,
+                font as ffi::PhidgetLCD_Font,
+                &mut w,
+                &mut h
+            ))
+        })?;
+        Ok((w as i32, h as i32))
+    }
+
+    /// Gets the region of the screen drawn since the last flush, if any.
+    pub fn dirty_region(&self) -> Option<Region> {
+        self.dirty
+    }
+
+    // Adds a region to the one drawn since the last flush.
+    fn mark(&mut self, region: Region) {
+        self.dirty = Some(match self.dirty {
+            Some(dirty) => dirty.union(&region),
+            None => region,
+        });
+    }
+
+    // Gets the region covering the whole screen.
+    fn screen(&self) -> Result<Region> {
+        Ok(Region::new(0, 0, self.width()? - 1, self.height()? - 1))
+    }
+
+    /// Clears the screen.
+    pub fn clear(&mut self) -> Result<()> {
+        ReturnCode::result(unsafe { traced!(ffi::PhidgetLCD_clear(self.chan)) })?;
+        let screen = self.screen()?;
+        self.mark(screen);
+        Ok(())
+    }
+
+    /// Draws a single pixel.
+    pub fn draw_pixel(&mut self, x: i32, y: i32, state: PixelState) -> Result<()> {
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetLCD_drawPixel(
+                self.chan,
+                x as c_int,
+                y as c_int,
+                state as ffi::PhidgetLCD_PixelState
+            ))
+        })?;
+        self.mark(Region::new(x, y, x, y));
+        Ok(())
+    }
+
+    /// Draws a line between two points.
+    pub fn draw_line(&mut self, x1: i32, y1: i32, x2: i32, y2: i32) -> Result<()> {
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetLCD_drawLine(
+                self.chan,
+                x1 as c_int,
+                y1 as c_int,
+                x2 as c_int,
+                y2 as c_int
+            ))
+        })?;
+        self.mark(Region::new(x1, y1, x2, y2));
+        Ok(())
+    }
+
+    /// Draws a rectangle, optionally filled, with pixels that are on, or
+    /// off if it's inverted.
+    pub fn draw_rect(&mut self, region: Region, filled: bool, inverted: bool) -> Result<()> {
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetLCD_drawRect(
+                self.chan,
+                region.x1 as c_int,
+                region.y1 as c_int,
+                region.x2 as c_int,
+                region.y2 as c_int,
+                filled as c_int,
+                inverted as c_int
+            ))
+        })?;
+        self.mark(region);
+        Ok(())
+    }
+
+    /// Writes text with its top left corner at the position.
+    ///
+    /// Lines of the text are separated by newlines.
+    pub fn write_text(&mut self, font: Font, x: i32, y: i32, text: &str) -> Result<()> {
+        let s = CString::new(text).map_err(|_| Error::InvalidArg)?;
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetLCD_writeText(
+                self.chan,
+                font as ffi::PhidgetLCD_Font,
+                x as c_int,
+                y as c_int,
+                s.as_ptr()
+            ))
+        })?;
+
+        // If the font's size isn't known, the whole screen is marked
+        let region = match self.font_size(font) {
+            Ok((w, h)) => {
+                let cols = text.lines().map(|l| l.chars().count()).max().unwrap_or(0);
+                let rows = text.lines().count().max(1);
+                Region::new(x, y, x + w * cols as i32 - 1, y + h * rows as i32 - 1)
+            }
+            Err(_) => self.screen()?,
+        };
+        self.mark(region);
+        Ok(())
+    }
+
+    /// Writes a bitmap with its top left corner at the position.
+    ///
+    /// The bitmap has one byte per pixel, row by row, with zero for a pixel
+    /// that's off, and any other value for one that's on.
+    pub fn write_bitmap(
+        &mut self,
+        x: i32,
+        y: i32,
+        width: i32,
+        height: i32,
+        bitmap: &[u8],
+    ) -> Result<()> {
+        if width <= 0 || height <= 0 || bitmap.len() < (width * height) as usize {
+            return Err(Error::InvalidArg);
+        }
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetLCD_writeBitmap(
+                self.chan,
+                x as c_int,
+                y as c_int,
+                width as c_int,
+                height as c_int,
+                bitmap.as_ptr()
+            ))
+        })?;
+        self.mark(Region::new(x, y, x + width - 1, y + height - 1));
+        Ok(())
+    }
+
+    /// Sends the drawing to the screen.
+    ///
+    /// This does nothing if nothing was drawn since the last flush.
+    pub fn flush(&mut self) -> Result<()> {
+        if self.dirty.is_none() {
+            return Ok(());
+        }
+        ReturnCode::result(unsafe { traced!(ffi::PhidgetLCD_flush(self.chan)) })?;
+        self.dirty = None;
+        Ok(())
+    }
+}
+
+impl Phidget for Lcd {
+    fn as_handle(&mut self) -> PhidgetHandle {
+        self.chan as PhidgetHandle
+    }
+}
+
+impl fmt::Debug for Lcd {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        crate::phidget::fmt_debug(f, "Lcd", self.chan as PhidgetHandle)
+    }
+}
+
+impl fmt::Display for Lcd {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        crate::phidget::fmt_display(f, "Lcd", self.chan as PhidgetHandle)
+    }
+}
+
+unsafe impl Send for Lcd {}
+unsafe impl Sync for Lcd {}
+
+impl Default for Lcd {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl From<PhidgetLCDHandle> for Lcd {
+    fn from(chan: PhidgetLCDHandle) -> Self {
+        Self { chan, dirty: None }
+    }
+}
+
+impl TryFrom<GenericPhidget> for Lcd {
+    type Error = Error;
+
+    /// Converts a generic phidget into a typed channel, if it's of the
+    /// right class. If the channel is open, it's closed when dropped.
+    fn try_from(ph: GenericPhidget) -> Result<Self> {
+        let chan = ph.into_channel(ChannelClass::Lcd)?;
+        Ok(Self::from(chan as PhidgetLCDHandle))
+    }
+}
+
+impl Drop for Lcd {
+    fn drop(&mut self) {
+        if let Ok(true) = self.is_open() {
+            let _ = self.close();
+        }
+        let phid = self.chan as PhidgetHandle;
+        unsafe {
+            ffi::PhidgetLCD_delete(&mut self.chan);
+        }
+        crate::notify::reset(phid);
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::*;
+    use crate::mock::{attach, detach, set_property};
+
+    #[test]
+    fn test_mock_lcd_dirty_region() {
+        let mut lcd = Lcd::new();
+        lcd.open().unwrap();
+        set_property(&mut lcd, "Width", 128.0).unwrap();
+        set_property(&mut lcd, "Height", 64.0).unwrap();
+        assert_eq!(lcd.dirty_region(), None);
+
+        lcd.draw_pixel(10, 20, PixelState::On).unwrap();
+        lcd.draw_line(30, 5, 12, 8).unwrap();
+        assert_eq!(lcd.dirty_region(), Some(Region::new(10, 5, 30, 20)));
+
+        lcd.write_text(Font::Font5x8, 0, 40, "ab\nc").unwrap();
+        assert_eq!(lcd.dirty_region(), Some(Region::new(0, 5, 30, 55)));
+
+        lcd.flush().unwrap();
+        assert_eq!(lcd.dirty_region(), None);
+
+        // With nothing drawn, a flush doesn't reach the device
+        detach(&mut lcd);
+        assert_eq!(lcd.flush(), Ok(()));
+
+        attach(&mut lcd);
+        lcd.clear().unwrap();
+        assert_eq!(lcd.dirty_region(), Some(Region::new(0, 0, 127, 63)));
+    }
+}
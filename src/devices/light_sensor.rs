@@ -0,0 +1,365 @@
+// phidget-rs/src/devices/light_sensor.rs
+//
+// This file is part of the 'phidget-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+use crate::{
+    handler::Context, ChannelClass, Error, EventReceiver, GenericPhidget, HandlerGuard,
+    HandlerResult, HandlerScope, Phidget, Result, ReturnCode, Sensor,
+};
+use phidget_sys::{self as ffi, PhidgetHandle, PhidgetLightSensorHandle};
+use std::{fmt, mem, os::raw::c_void, ptr, sync::mpsc};
+
+#[cfg(feature = "async")]
+use crate::stream::EventStream;
+
+/// The function signature for the safe Rust illuminance change callback.
+pub type IlluminanceChangeCallback = dyn FnMut(&LightSensor, f64) + Send + 'static;
+
+/////////////////////////////////////////////////////////////////////////////
+
+/// Phidget light sensor
+pub struct LightSensor {
+    // Handle to the light sensor in the phidget22 library
+    chan: PhidgetLightSensorHandle,
+}
+
+impl LightSensor {
+    /// Create a new light sensor.
+    ///
+    /// This panics if the library can't create the channel. Use
+    /// `try_new()` to get the error instead.
+    pub fn new() -> Self {
+        Self::try_new().expect("failed to create the channel")
+    }
+
+    /// Create a new light sensor, returning an error if the library can't
+    /// create the channel.
+    pub fn try_new() -> Result<Self> {
+        let mut chan: PhidgetLightSensorHandle = ptr::null_mut();
+        ReturnCode::result(unsafe { ffi::PhidgetLightSensor_create(&mut chan) })?;
+        crate::phidget::install_handlers(chan as PhidgetHandle);
+        Ok(Self::from(chan))
+    }
+
+    // Low-level, unsafe, callback for the illuminance change event.
+    // The context holds a view of the channel and the safe Rust callback.
+    unsafe extern "C" fn on_illuminance_change<F, R>(
+        chan: PhidgetLightSensorHandle,
+        ctx: *mut c_void,
+        illuminance: f64,
+    ) where
+        F: FnMut(&LightSensor, f64) -> R + Send + 'static,
+        R: HandlerResult,
+    {
+        crate::handler::deliver(chan as PhidgetHandle, ctx, move |phid, ctx| {
+            let ctx: &mut Context<Self, F> = &mut *(ctx as *mut _);
+            crate::handler::report(phid, (ctx.cb)(&ctx.chan, illuminance));
+        });
+    }
+
+    /// Get a reference to the underlying sensor handle
+    pub fn as_channel(&self) -> &PhidgetLightSensorHandle {
+        &self.chan
+    }
+
+    /// Get the illuminance measured by the sensor, in lux
+    pub fn illuminance(&self) -> Result<f64> {
+        let mut v: f64 = 0.0;
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetLightSensor_getIlluminance(self.chan, &mut v))
+        })?;
+        Ok(v)
+    }
+
+    /// Gets the minimum change in illuminance, in lux, that triggers a change
+    /// event.
+    pub fn illuminance_change_trigger(&self) -> Result<f64> {
+        let mut trigger = 0.0;
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetLightSensor_getIlluminanceChangeTrigger(
+                self.chan,
+                &mut trigger
+            ))
+        })?;
+        Ok(trigger)
+    }
+
+    /// Sets the minimum change in illuminance, in lux, that triggers a change
+    /// event. Zero reports every reading.
+    pub fn set_illuminance_change_trigger(&mut self, trigger: f64) -> Result<()> {
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetLightSensor_setIlluminanceChangeTrigger(
+                self.chan, trigger
+            ))
+        })
+    }
+
+    /// Gets the minimum illuminance that the sensor can measure, in lux.
+    pub fn min_illuminance(&self) -> Result<f64> {
+        crate::cache::property(self.chan as PhidgetHandle, "MinIlluminance", || {
+            let mut value = 0.0;
+            ReturnCode::result(unsafe {
+                traced!(ffi::PhidgetLightSensor_getMinIlluminance(
+                    self.chan, &mut value
+                ))
+            })?;
+            Ok(value)
+        })
+    }
+
+    /// Gets the maximum illuminance that the sensor can measure, in lux.
+    ///
+    /// A reading at this value means that the sensor is saturated.
+    pub fn max_illuminance(&self) -> Result<f64> {
+        crate::cache::property(self.chan as PhidgetHandle, "MaxIlluminance", || {
+            let mut value = 0.0;
+            ReturnCode::result(unsafe {
+                traced!(ffi::PhidgetLightSensor_getMaxIlluminance(
+                    self.chan, &mut value
+                ))
+            })?;
+            Ok(value)
+        })
+    }
+
+    /// Gets the smallest change trigger that can be set.
+    pub fn min_illuminance_change_trigger(&self) -> Result<f64> {
+        crate::cache::property(
+            self.chan as PhidgetHandle,
+            "MinIlluminanceChangeTrigger",
+            || {
+                let mut value = 0.0;
+                ReturnCode::result(unsafe {
+                    traced!(ffi::PhidgetLightSensor_getMinIlluminanceChangeTrigger(
+                        self.chan, &mut value
+                    ))
+                })?;
+                Ok(value)
+            },
+        )
+    }
+
+    /// Gets the largest change trigger that can be set.
+    pub fn max_illuminance_change_trigger(&self) -> Result<f64> {
+        crate::cache::property(
+            self.chan as PhidgetHandle,
+            "MaxIlluminanceChangeTrigger",
+            || {
+                let mut value = 0.0;
+                ReturnCode::result(unsafe {
+                    traced!(ffi::PhidgetLightSensor_getMaxIlluminanceChangeTrigger(
+                        self.chan, &mut value
+                    ))
+                })?;
+                Ok(value)
+            },
+        )
+    }
+
+    /// Sets a handler to receive illuminance change callbacks.
+    /// The handler is removed when the returned guard is dropped.
+    pub fn set_on_illuminance_change_handler<F, R>(&self, cb: F) -> Result<HandlerGuard>
+    where
+        F: FnMut(&LightSensor, f64) -> R + Send + 'static,
+        R: HandlerResult,
+    {
+        let ctx = Context::into_raw(Self::from(self.chan), cb);
+
+        crate::handler::register::<Context<Self, F>, _>(
+            self.chan as PhidgetHandle,
+            "illuminance_change",
+            ctx,
+            || unsafe {
+                traced!(ffi::PhidgetLightSensor_setOnIlluminanceChangeHandler(
+                    self.chan,
+                    Some(Self::on_illuminance_change::<F, R>),
+                    ctx,
+                ))
+            },
+            |phid| unsafe {
+                traced!(ffi::PhidgetLightSensor_setOnIlluminanceChangeHandler(
+                    phid as _,
+                    None,
+                    ptr::null_mut()
+                ))
+            },
+        )
+    }
+
+    /// Set a handler to receive illuminance change callbacks, which can borrow
+    /// from the environment of the scope.
+    /// The handler is removed at the end of the scope.
+    pub fn set_on_illuminance_change_handler_scoped<'scope, F, R>(
+        &self,
+        scope: &'scope HandlerScope<'scope, '_>,
+        cb: F,
+    ) -> Result<()>
+    where
+        F: FnMut(&LightSensor, f64) -> R + Send + 'scope,
+        R: HandlerResult + 'static,
+    {
+        // SAFETY: The guard is held by the scope, which can't be forgotten,
+        // and which waits for any call to the handler. See `HandlerScope`.
+        let cb = unsafe {
+            mem::transmute::<
+                Box<dyn FnMut(&LightSensor, f64) -> R + Send + 'scope>,
+                Box<dyn FnMut(&LightSensor, f64) -> R + Send>,
+            >(Box::new(cb))
+        };
+        scope.hold(self.set_on_illuminance_change_handler(cb)?);
+        Ok(())
+    }
+
+    /// Removes the illuminance change handler, if one is set, and releases
+    /// its closure.
+    pub fn remove_on_illuminance_change_handler(&self) -> Result<()> {
+        crate::handler::unregister(self.chan as PhidgetHandle, "illuminance_change")
+    }
+
+    /// Gets a channel that receives the illuminance readings, fed by the illuminance change events.
+    ///
+    /// This registers the channel's illuminance change handler, replacing any handler
+    /// that was previously set.
+    /// The handler is removed when the receiver is dropped.
+    pub fn illuminance_channel(&self) -> Result<EventReceiver<f64>> {
+        let (tx, rx) = mpsc::channel();
+        let guard = self.set_on_illuminance_change_handler(move |_, v| {
+            let _ = tx.send(v);
+        })?;
+        Ok(EventReceiver::new(rx, guard))
+    }
+
+    /// Gets a stream of the illuminance readings, fed by the illuminance change events.
+    ///
+    /// This registers the channel's illuminance change handler, replacing any handler
+    /// that was previously set.
+    /// The handler is removed when the stream is dropped.
+    #[cfg(feature = "async")]
+    pub fn illuminance_stream(&self) -> Result<EventStream<f64>> {
+        let (mut tx, rx) = crate::stream::event_channel();
+        let guard = self.set_on_illuminance_change_handler(move |_, v| tx.send(v))?;
+        Ok(EventStream::new(rx, guard))
+    }
+}
+
+impl Phidget for LightSensor {
+    fn as_handle(&mut self) -> PhidgetHandle {
+        self.chan as PhidgetHandle
+    }
+}
+
+impl Sensor for LightSensor {
+    type Value = f64;
+
+    fn value(&self) -> Result<f64> {
+        self.illuminance()
+    }
+
+    fn on_change<F>(&self, cb: F) -> Result<HandlerGuard>
+    where
+        F: FnMut(&Self, f64) + Send + 'static,
+    {
+        self.set_on_illuminance_change_handler(cb)
+    }
+}
+
+impl fmt::Debug for LightSensor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        crate::phidget::fmt_debug(f, "LightSensor", self.chan as PhidgetHandle)
+    }
+}
+
+impl fmt::Display for LightSensor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        crate::phidget::fmt_display(f, "LightSensor", self.chan as PhidgetHandle)
+    }
+}
+
+unsafe impl Send for LightSensor {}
+unsafe impl Sync for LightSensor {}
+
+impl Default for LightSensor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl From<PhidgetLightSensorHandle> for LightSensor {
+    fn from(chan: PhidgetLightSensorHandle) -> Self {
+        Self { chan }
+    }
+}
+
+impl TryFrom<GenericPhidget> for LightSensor {
+    type Error = Error;
+
+    /// Converts a generic phidget into a typed channel, if it's of the
+    /// right class. If the channel is open, it's closed when dropped.
+    fn try_from(ph: GenericPhidget) -> Result<Self> {
+        let chan = ph.into_channel(ChannelClass::LightSensor)?;
+        Ok(Self::from(chan as PhidgetLightSensorHandle))
+    }
+}
+
+impl Drop for LightSensor {
+    fn drop(&mut self) {
+        if let Ok(true) = self.is_open() {
+            let _ = self.close();
+        }
+        let phid = self.chan as PhidgetHandle;
+        unsafe {
+            ffi::PhidgetLightSensor_delete(&mut self.chan);
+        }
+        crate::notify::reset(phid);
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::*;
+    use crate::mock::{set_property, set_value};
+
+    #[test]
+    fn test_mock_illuminance_change_trigger() {
+        let mut light = LightSensor::new();
+        light.open().unwrap();
+        set_property(&mut light, "MinIlluminanceChangeTrigger", 0.0).unwrap();
+        set_property(&mut light, "MaxIlluminanceChangeTrigger", 100.0).unwrap();
+        assert_eq!(light.min_illuminance_change_trigger(), Ok(0.0));
+        assert_eq!(light.max_illuminance_change_trigger(), Ok(100.0));
+
+        light.set_illuminance_change_trigger(5.0).unwrap();
+        assert_eq!(light.illuminance_change_trigger(), Ok(5.0));
+
+        // Smaller changes than the trigger aren't logged
+        let rx = light.illuminance_channel().unwrap();
+        for lux in [250.0, 252.0, 254.5, 256.0, 250.0] {
+            set_value(&mut light, lux).unwrap();
+        }
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), [250.0, 256.0, 250.0]);
+    }
+
+    #[test]
+    fn test_mock_illuminance_range() {
+        let mut light = LightSensor::new();
+        assert_eq!(light.max_illuminance(), Err(Error::NotAttached));
+
+        light.open().unwrap();
+        set_property(&mut light, "MinIlluminance", 0.0).unwrap();
+        set_property(&mut light, "MaxIlluminance", 1000.0).unwrap();
+        assert_eq!(light.min_illuminance(), Ok(0.0));
+        assert_eq!(light.max_illuminance(), Ok(1000.0));
+
+        // A reading at the maximum shows that the sensor is saturated
+        set_value(&mut light, 1000.0).unwrap();
+        assert!(light.illuminance().unwrap() >= light.max_illuminance().unwrap());
+    }
+}
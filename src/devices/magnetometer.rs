@@ -0,0 +1,455 @@
+// phidget-rs/src/devices/magnetometer.rs
+//
+// This file is part of the 'phidget-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+use crate::{
+    handler::Context, ChannelClass, Error, EventReceiver, GenericPhidget, HandlerGuard,
+    HandlerResult, HandlerScope, Phidget, Result, ReturnCode,
+};
+use phidget_sys::{self as ffi, PhidgetHandle, PhidgetMagnetometerHandle};
+use std::{
+    fmt, mem,
+    os::raw::{c_int, c_void},
+    ptr, slice,
+    sync::mpsc,
+};
+
+#[cfg(feature = "async")]
+use crate::stream::EventStream;
+
+/// The function signature for the safe Rust magnetic field change callback.
+///
+/// This receives the magnetic field on each axis, in gauss, and the
+/// timestamp of the sample, in milliseconds.
+pub type MagneticFieldChangeCallback = dyn FnMut(&Magnetometer, [f64; 3], f64) + Send + 'static;
+
+/// The parameters that correct the readings of a magnetometer for the
+/// distortions of the magnetic field around it.
+///
+/// These are normally found by a calibration program, with the
+/// magnetometer mounted in its final position.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CorrectionParameters {
+    /// The strength of the ambient magnetic field, in gauss
+    pub magnetic_field: f64,
+    /// The offset of each axis, in gauss
+    pub offset: [f64; 3],
+    /// The gain of each axis
+    pub gain: [f64; 3],
+    /// The transform parameters, T0 to T5, which correct for the
+    /// misalignment of the axes
+    pub transform: [f64; 6],
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+/// Phidget magnetometer
+pub struct Magnetometer {
+    // Handle to the magnetometer in the phidget22 library
+    chan: PhidgetMagnetometerHandle,
+}
+
+impl Magnetometer {
+    /// Create a new magnetometer.
+    ///
+    /// This panics if the library can't create the channel. Use
+    /// `try_new()` to get the error instead.
+    pub fn new() -> Self {
+        Self::try_new().expect("failed to create the channel")
+    }
+
+    /// Create a new magnetometer, returning an error if the library can't
+    /// create the channel.
+    pub fn try_new() -> Result<Self> {
+        let mut chan: PhidgetMagnetometerHandle = ptr::null_mut();
+        ReturnCode::result(unsafe { ffi::PhidgetMagnetometer_create(&mut chan) })?;
+        crate::phidget::install_handlers(chan as PhidgetHandle);
+        Ok(Self::from(chan))
+    }
+
+    // Low-level, unsafe, callback for the magnetic field change event.
+    // The context holds a view of the channel and the safe Rust callback.
+    unsafe extern "C" fn on_magnetic_field_change<F, R>(
+        chan: PhidgetMagnetometerHandle,
+        ctx: *mut c_void,
+        magnetic_field: *const f64,
+        timestamp: f64,
+    ) where
+        F: FnMut(&Magnetometer, [f64; 3], f64) -> R + Send + 'static,
+        R: HandlerResult,
+    {
+        let mut field = [0.0; 3];
+        if !magnetic_field.is_null() {
+            field.copy_from_slice(slice::from_raw_parts(magnetic_field, 3));
+        }
+        crate::handler::deliver(chan as PhidgetHandle, ctx, move |phid, ctx| {
+            let ctx: &mut Context<Self, F> = &mut *(ctx as *mut _);
+            crate::handler::report(phid, (ctx.cb)(&ctx.chan, field, timestamp));
+        });
+    }
+
+    /// Get a reference to the underlying magnetometer handle
+    pub fn as_channel(&self) -> &PhidgetMagnetometerHandle {
+        &self.chan
+    }
+
+    /// Gets the number of axes that the magnetometer measures.
+    pub fn axis_count(&self) -> Result<i32> {
+        crate::cache::property(self.chan as PhidgetHandle, "AxisCount", || {
+            let mut n: c_int = 0;
+            ReturnCode::result(unsafe {
+                traced!(ffi::PhidgetMagnetometer_getAxisCount(self.chan, &mut n))
+            })?;
+            Ok(n as i32)
+        })
+    }
+
+    /// Get the magnetic field on each axis, in gauss
+    pub fn magnetic_field(&self) -> Result<[f64; 3]> {
+        let mut field = [0.0; 3];
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetMagnetometer_getMagneticField(
+                self.chan, &mut field
+            ))
+        })?;
+        Ok(field)
+    }
+
+    /// Gets the minimum magnetic field that can be measured on each axis,
+    /// in gauss.
+    pub fn min_magnetic_field(&self) -> Result<[f64; 3]> {
+        crate::cache::property(self.chan as PhidgetHandle, "MinMagneticField", || {
+            let mut field = [0.0; 3];
+            ReturnCode::result(unsafe {
+                traced!(ffi::PhidgetMagnetometer_getMinMagneticField(
+                    self.chan, &mut field
+                ))
+            })?;
+            Ok(field)
+        })
+    }
+
+    /// Gets the maximum magnetic field that can be measured on each axis,
+    /// in gauss.
+    pub fn max_magnetic_field(&self) -> Result<[f64; 3]> {
+        crate::cache::property(self.chan as PhidgetHandle, "MaxMagneticField", || {
+            let mut field = [0.0; 3];
+            ReturnCode::result(unsafe {
+                traced!(ffi::PhidgetMagnetometer_getMaxMagneticField(
+                    self.chan, &mut field
+                ))
+            })?;
+            Ok(field)
+        })
+    }
+
+    /// Gets the minimum change in magnetic field, in gauss, that triggers a
+    /// change event.
+    pub fn magnetic_field_change_trigger(&self) -> Result<f64> {
+        let mut trigger = 0.0;
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetMagnetometer_getMagneticFieldChangeTrigger(
+                self.chan,
+                &mut trigger
+            ))
+        })?;
+        Ok(trigger)
+    }
+
+    /// Sets the minimum change in magnetic field, in gauss, that triggers a
+    /// change event. Zero reports every sample.
+    pub fn set_magnetic_field_change_trigger(&mut self, trigger: f64) -> Result<()> {
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetMagnetometer_setMagneticFieldChangeTrigger(
+                self.chan, trigger
+            ))
+        })
+    }
+
+    /// Gets the smallest change trigger that can be set.
+    pub fn min_magnetic_field_change_trigger(&self) -> Result<f64> {
+        crate::cache::property(
+            self.chan as PhidgetHandle,
+            "MinMagneticFieldChangeTrigger",
+            || {
+                let mut value = 0.0;
+                ReturnCode::result(unsafe {
+                    traced!(ffi::PhidgetMagnetometer_getMinMagneticFieldChangeTrigger(
+                        self.chan, &mut value
+                    ))
+                })?;
+                Ok(value)
+            },
+        )
+    }
+
+    /// Gets the largest change trigger that can be set.
+    pub fn max_magnetic_field_change_trigger(&self) -> Result<f64> {
+        crate::cache::property(
+            self.chan as PhidgetHandle,
+            "MaxMagneticFieldChangeTrigger",
+            || {
+                let mut value = 0.0;
+                ReturnCode::result(unsafe {
+                    traced!(ffi::PhidgetMagnetometer_getMaxMagneticFieldChangeTrigger(
+                        self.chan, &mut value
+                    ))
+                })?;
+                Ok(value)
+            },
+        )
+    }
+
+    /// Determines if the heating element of the magnetometer is enabled.
+    pub fn heating_enabled(&self) -> Result<bool> {
+        let mut enabled: c_int = 0;
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetMagnetometer_getHeatingEnabled(
+                self.chan,
+                &mut enabled
+            ))
+        })?;
+        Ok(enabled != 0)
+    }
+
+    /// Enables or disables the heating element of the magnetometer.
+    pub fn set_heating_enabled(&mut self, enabled: bool) -> Result<()> {
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetMagnetometer_setHeatingEnabled(
+                self.chan,
+                enabled as c_int
+            ))
+        })
+    }
+
+    /// Sets the parameters that correct the readings for the distortions
+    /// of the magnetic field around the magnetometer.
+    ///
+    /// These take effect right away, but are lost when the device is
+    /// powered off, unless they are saved with
+    /// `save_correction_parameters()`.
+    pub fn set_correction_parameters(&mut self, params: &CorrectionParameters) -> Result<()> {
+        let CorrectionParameters {
+            magnetic_field,
+            offset,
+            gain,
+            transform: t,
+        } = *params;
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetMagnetometer_setCorrectionParameters(
+                self.chan,
+                magnetic_field,
+                offset[0],
+                offset[1],
+                offset[2],
+                gain[0],
+                gain[1],
+                gain[2],
+                t[0],
+                t[1],
+                t[2],
+                t[3],
+                t[4],
+                t[5],
+            ))
+        })
+    }
+
+    /// Saves the correction parameters to the flash memory of the device,
+    /// so that they persist across power cycles.
+    pub fn save_correction_parameters(&mut self) -> Result<()> {
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetMagnetometer_saveCorrectionParameters(self.chan))
+        })
+    }
+
+    /// Resets the correction parameters to their defaults.
+    ///
+    /// This doesn't change the saved parameters unless it's followed by
+    /// `save_correction_parameters()`.
+    pub fn reset_correction_parameters(&mut self) -> Result<()> {
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetMagnetometer_resetCorrectionParameters(
+                self.chan
+            ))
+        })
+    }
+
+    /// Sets a handler to receive magnetic field change callbacks, with the
+    /// magnetic field on each axis and the timestamp of the sample.
+    /// The handler is removed when the returned guard is dropped.
+    pub fn set_on_magnetic_field_change_handler<F, R>(&self, cb: F) -> Result<HandlerGuard>
+    where
+        F: FnMut(&Magnetometer, [f64; 3], f64) -> R + Send + 'static,
+        R: HandlerResult,
+    {
+        let ctx = Context::into_raw(Self::from(self.chan), cb);
+
+        crate::handler::register::<Context<Self, F>, _>(
+            self.chan as PhidgetHandle,
+            "magnetic_field_change",
+            ctx,
+            || unsafe {
+                traced!(ffi::PhidgetMagnetometer_setOnMagneticFieldChangeHandler(
+                    self.chan,
+                    Some(Self::on_magnetic_field_change::<F, R>),
+                    ctx,
+                ))
+            },
+            |phid| unsafe {
+                traced!(ffi::PhidgetMagnetometer_setOnMagneticFieldChangeHandler(
+                    phid as _,
+                    None,
+                    ptr::null_mut()
+                ))
+            },
+        )
+    }
+
+    /// Set a handler to receive magnetic field change callbacks, which can
+    /// borrow from the environment of the scope.
+    /// The handler is removed at the end of the scope.
+    pub fn set_on_magnetic_field_change_handler_scoped<'scope, F, R>(
+        &self,
+        scope: &'scope HandlerScope<'scope, '_>,
+        cb: F,
+    ) -> Result<()>
+    where
+        F: FnMut(&Magnetometer, [f64; 3], f64) -> R + Send + 'scope,
+        R: HandlerResult + 'static,
+    {
+        // SAFETY: The guard is held by the scope, which can't be forgotten,
+        // and which waits for any call to the handler. See `HandlerScope`.
+        let cb = unsafe {
+            mem::transmute::<
+                Box<dyn FnMut(&Magnetometer, [f64; 3], f64) -> R + Send + 'scope>,
+                Box<dyn FnMut(&Magnetometer, [f64; 3], f64) -> R + Send>,
+            >(Box::new(cb))
+        };
+        scope.hold(self.set_on_magnetic_field_change_handler(cb)?);
+        Ok(())
+    }
+
+    /// Removes the magnetic field change handler, if one is set, and
+    /// releases its closure.
+    pub fn remove_on_magnetic_field_change_handler(&self) -> Result<()> {
+        crate::handler::unregister(self.chan as PhidgetHandle, "magnetic_field_change")
+    }
+
+    /// Gets a channel that receives the magnetic field readings, fed by the
+    /// magnetic field change events.
+    ///
+    /// This registers the channel's magnetic field change handler, replacing
+    /// any handler that was previously set.
+    /// The handler is removed when the receiver is dropped.
+    pub fn magnetic_field_channel(&self) -> Result<EventReceiver<[f64; 3]>> {
+        let (tx, rx) = mpsc::channel();
+        let guard = self.set_on_magnetic_field_change_handler(move |_, f, _| {
+            let _ = tx.send(f);
+        })?;
+        Ok(EventReceiver::new(rx, guard))
+    }
+
+    /// Gets a stream of the magnetic field readings, fed by the magnetic field
+    /// change events.
+    ///
+    /// This registers the channel's magnetic field change handler, replacing
+    /// any handler that was previously set.
+    /// The handler is removed when the stream is dropped.
+    #[cfg(feature = "async")]
+    pub fn magnetic_field_stream(&self) -> Result<EventStream<[f64; 3]>> {
+        let (mut tx, rx) = crate::stream::event_channel();
+        let guard = self.set_on_magnetic_field_change_handler(move |_, f, _| tx.send(f))?;
+        Ok(EventStream::new(rx, guard))
+    }
+}
+
+impl Phidget for Magnetometer {
+    fn as_handle(&mut self) -> PhidgetHandle {
+        self.chan as PhidgetHandle
+    }
+}
+
+impl fmt::Debug for Magnetometer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        crate::phidget::fmt_debug(f, "Magnetometer", self.chan as PhidgetHandle)
+    }
+}
+
+impl fmt::Display for Magnetometer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        crate::phidget::fmt_display(f, "Magnetometer", self.chan as PhidgetHandle)
+    }
+}
+
+unsafe impl Send for Magnetometer {}
+unsafe impl Sync for Magnetometer {}
+
+impl Default for Magnetometer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl From<PhidgetMagnetometerHandle> for Magnetometer {
+    fn from(chan: PhidgetMagnetometerHandle) -> Self {
+        Self { chan }
+    }
+}
+
+impl TryFrom<GenericPhidget> for Magnetometer {
+    type Error = Error;
+
+    /// Converts a generic phidget into a typed channel, if it's of the
+    /// right class. If the channel is open, it's closed when dropped.
+    fn try_from(ph: GenericPhidget) -> Result<Self> {
+        let chan = ph.into_channel(ChannelClass::Magnetometer)?;
+        Ok(Self::from(chan as PhidgetMagnetometerHandle))
+    }
+}
+
+impl Drop for Magnetometer {
+    fn drop(&mut self) {
+        if let Ok(true) = self.is_open() {
+            let _ = self.close();
+        }
+        let phid = self.chan as PhidgetHandle;
+        unsafe {
+            ffi::PhidgetMagnetometer_delete(&mut self.chan);
+        }
+        crate::notify::reset(phid);
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::*;
+    use crate::mock::set_property;
+
+    #[test]
+    fn test_mock_magnetometer() {
+        let mut mag = Magnetometer::new();
+        mag.open().unwrap();
+        let params = CorrectionParameters {
+            magnetic_field: 0.52,
+            offset: [0.01, -0.02, 0.03],
+            gain: [1.0, 1.1, 0.9],
+            transform: [0.0; 6],
+        };
+        mag.set_correction_parameters(&params).unwrap();
+        mag.save_correction_parameters().unwrap();
+        mag.reset_correction_parameters().unwrap();
+
+        set_property(&mut mag, "AxisCount", 3.0).unwrap();
+        assert_eq!(mag.axis_count(), Ok(3));
+    }
+}
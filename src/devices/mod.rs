@@ -1,3 +1,27 @@
+/// Phidget accelerometer
+pub mod accelerometer;
+pub use crate::devices::accelerometer::Accelerometer;
+
+/// Phidget capacitive touch sensor
+pub mod capacitive_touch;
+pub use crate::devices::capacitive_touch::CapacitiveTouch;
+
+/// Phidget current input
+pub mod current_input;
+pub use crate::devices::current_input::CurrentInput;
+
+/// Phidget encoder
+pub mod encoder;
+pub use crate::devices::encoder::{Encoder, EncoderIoMode, PositionChange};
+
+/// Phidget frequency counter
+pub mod frequency_counter;
+pub use crate::devices::frequency_counter::FrequencyCounter;
+
+/// Phidget GPS
+pub mod gps;
+pub use crate::devices::gps::Gps;
+
 /// Phidget hub
 pub mod hub;
 pub use crate::devices::hub::{Hub, HubPortMode};
@@ -6,32 +30,117 @@ pub use crate::devices::hub::{Hub, HubPortMode};
 pub mod humidity_sensor;
 pub use crate::devices::humidity_sensor::HumiditySensor;
 
+/// Phidget graphic LCD
+pub mod lcd;
+pub use crate::devices::lcd::Lcd;
+
+/// Phidget light sensor
+pub mod light_sensor;
+pub use crate::devices::light_sensor::LightSensor;
+
+/// Phidget magnetometer
+pub mod magnetometer;
+pub use crate::devices::magnetometer::{CorrectionParameters, Magnetometer};
+
+/// Phidget pH sensor
+pub mod ph_sensor;
+pub use crate::devices::ph_sensor::PhSensor;
+
+/// Phidget pressure sensor
+pub mod pressure_sensor;
+pub use crate::devices::pressure_sensor::PressureSensor;
+
+/// Phidget RC servo controller
+pub mod rc_servo;
+pub use crate::devices::rc_servo::RcServo;
+
+/// Phidget spatial (IMU)
+pub mod spatial;
+pub use crate::devices::spatial::{Spatial, SpatialData};
+
 /// Phidget stepper
 pub mod stepper;
-pub use crate::devices::stepper::Stepper;
+pub use crate::devices::stepper::{ControlMode, Stepper};
 
 /// Phidget temerature sensor
 pub mod temperature_sensor;
-pub use crate::devices::temperature_sensor::TemperatureSensor;
+pub use crate::devices::temperature_sensor::{
+    RtdType, RtdWireSetup, TemperatureSensor, ThermocoupleType,
+};
+
+/// Phidget network dictionary
+pub mod dictionary;
+pub use crate::devices::dictionary::Dictionary;
 
 /// Phidget digital input
 pub mod digital_output;
-pub use crate::devices::digital_input::DigitalInput;
+pub use crate::devices::digital_input::{DigitalInput, InputMode, PowerSupply};
 
 /// Phidget digital ouput
 pub mod digital_input;
-pub use crate::devices::digital_output::DigitalOutput;
+pub use crate::devices::digital_output::{DigitalOutput, LedForwardVoltage};
 
 /// Phidget voltage input
 pub mod voltage_input;
-pub use crate::devices::voltage_input::VoltageInput;
+pub use crate::devices::voltage_input::{VoltageInput, VoltageRange};
 
 /// Phidget voltage ratio input
 pub mod voltage_ratio_input;
-pub use crate::devices::voltage_ratio_input::VoltageRatioInput;
+pub use crate::devices::voltage_ratio_input::{BridgeGain, VoltageRatioInput};
 
 /// Phidget voltage ouput
 pub mod voltage_output;
 // mod voltage_ratio_input;
 
 pub use crate::devices::voltage_output::VoltageOutput;
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::*;
+    use crate::Phidget;
+
+    // Opens the channel, and checks that it's closed when dropped, even
+    // while another reference to it is held.
+    fn assert_closed_on_drop<P: Phidget>(mut dev: P) {
+        dev.open().unwrap();
+        let mut phid = dev.as_handle();
+        unsafe { phidget_sys::Phidget_retain(phid) };
+        drop(dev);
+
+        let mut open = 1;
+        unsafe {
+            phidget_sys::Phidget_getIsOpen(phid, &mut open);
+            phidget_sys::Phidget_release(&mut phid);
+        }
+        assert_eq!(open, 0);
+    }
+
+    #[test]
+    fn test_mock_close_on_drop() {
+        assert_closed_on_drop(Accelerometer::new());
+        assert_closed_on_drop(CapacitiveTouch::new());
+        assert_closed_on_drop(CurrentInput::new());
+        assert_closed_on_drop(Dictionary::new());
+        assert_closed_on_drop(DigitalInput::new());
+        assert_closed_on_drop(DigitalOutput::new());
+        assert_closed_on_drop(Encoder::new());
+        assert_closed_on_drop(FrequencyCounter::new());
+        assert_closed_on_drop(Gps::new());
+        assert_closed_on_drop(Hub::new());
+        assert_closed_on_drop(HumiditySensor::new());
+        assert_closed_on_drop(Lcd::new());
+        assert_closed_on_drop(LightSensor::new());
+        assert_closed_on_drop(Magnetometer::new());
+        assert_closed_on_drop(PhSensor::new());
+        assert_closed_on_drop(PressureSensor::new());
+        assert_closed_on_drop(RcServo::new());
+        assert_closed_on_drop(Spatial::new());
+        assert_closed_on_drop(Stepper::new());
+        assert_closed_on_drop(TemperatureSensor::new());
+        assert_closed_on_drop(VoltageInput::new());
+        assert_closed_on_drop(VoltageOutput::new());
+        assert_closed_on_drop(VoltageRatioInput::new());
+    }
+}
@@ -0,0 +1,328 @@
+// phidget-rs/src/devices/ph_sensor.rs
+//
+// This file is part of the 'phidget-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+use crate::{
+    handler::Context, ChannelClass, Error, EventReceiver, GenericPhidget, HandlerGuard,
+    HandlerResult, HandlerScope, Phidget, Result, ReturnCode, Sensor,
+};
+use phidget_sys::{self as ffi, PhidgetHandle, PhidgetPHSensorHandle};
+use std::{fmt, mem, os::raw::c_void, ptr, sync::mpsc};
+
+#[cfg(feature = "async")]
+use crate::stream::EventStream;
+
+/// The function signature for the safe Rust pH change callback.
+pub type PHChangeCallback = dyn FnMut(&PhSensor, f64) + Send + 'static;
+
+/////////////////////////////////////////////////////////////////////////////
+
+/// Phidget pH sensor
+pub struct PhSensor {
+    // Handle to the pH sensor in the phidget22 library
+    chan: PhidgetPHSensorHandle,
+}
+
+impl PhSensor {
+    /// Create a new pH sensor.
+    ///
+    /// This panics if the library can't create the channel. Use
+    /// `try_new()` to get the error instead.
+    pub fn new() -> Self {
+        Self::try_new().expect("failed to create the channel")
+    }
+
+    /// Create a new pH sensor, returning an error if the library can't
+    /// create the channel.
+    pub fn try_new() -> Result<Self> {
+        let mut chan: PhidgetPHSensorHandle = ptr::null_mut();
+        ReturnCode::result(unsafe { ffi::PhidgetPHSensor_create(&mut chan) })?;
+        crate::phidget::install_handlers(chan as PhidgetHandle);
+        Ok(Self::from(chan))
+    }
+
+    // Low-level, unsafe, callback for the pH change event.
+    // The context holds a view of the channel and the safe Rust callback.
+    unsafe extern "C" fn on_ph_change<F, R>(chan: PhidgetPHSensorHandle, ctx: *mut c_void, ph: f64)
+    where
+        F: FnMut(&PhSensor, f64) -> R + Send + 'static,
+        R: HandlerResult,
+    {
+        crate::handler::deliver(chan as PhidgetHandle, ctx, move |phid, ctx| {
+            let ctx: &mut Context<Self, F> = &mut *(ctx as *mut _);
+            crate::handler::report(phid, (ctx.cb)(&ctx.chan, ph));
+        });
+    }
+
+    /// Get a reference to the underlying sensor handle
+    pub fn as_channel(&self) -> &PhidgetPHSensorHandle {
+        &self.chan
+    }
+
+    /// Get the pH measured by the sensor
+    pub fn ph(&self) -> Result<f64> {
+        let mut v: f64 = 0.0;
+        ReturnCode::result(unsafe { traced!(ffi::PhidgetPHSensor_getPH(self.chan, &mut v)) })?;
+        Ok(v)
+    }
+
+    /// Gets the minimum change in pH that triggers a change event.
+    pub fn ph_change_trigger(&self) -> Result<f64> {
+        let mut trigger = 0.0;
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetPHSensor_getPHChangeTrigger(
+                self.chan,
+                &mut trigger
+            ))
+        })?;
+        Ok(trigger)
+    }
+
+    /// Sets the minimum change in pH that triggers a change event. Zero
+    /// reports every reading.
+    pub fn set_ph_change_trigger(&mut self, trigger: f64) -> Result<()> {
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetPHSensor_setPHChangeTrigger(self.chan, trigger))
+        })
+    }
+
+    /// Gets the minimum pH that the sensor can measure.
+    pub fn min_ph(&self) -> Result<f64> {
+        crate::cache::property(self.chan as PhidgetHandle, "MinPH", || {
+            let mut value = 0.0;
+            ReturnCode::result(unsafe {
+                traced!(ffi::PhidgetPHSensor_getMinPH(self.chan, &mut value))
+            })?;
+            Ok(value)
+        })
+    }
+
+    /// Gets the maximum pH that the sensor can measure.
+    pub fn max_ph(&self) -> Result<f64> {
+        crate::cache::property(self.chan as PhidgetHandle, "MaxPH", || {
+            let mut value = 0.0;
+            ReturnCode::result(unsafe {
+                traced!(ffi::PhidgetPHSensor_getMaxPH(self.chan, &mut value))
+            })?;
+            Ok(value)
+        })
+    }
+
+    /// Gets the smallest change trigger that can be set.
+    pub fn min_ph_change_trigger(&self) -> Result<f64> {
+        crate::cache::property(self.chan as PhidgetHandle, "MinPHChangeTrigger", || {
+            let mut value = 0.0;
+            ReturnCode::result(unsafe {
+                traced!(ffi::PhidgetPHSensor_getMinPHChangeTrigger(
+                    self.chan, &mut value
+                ))
+            })?;
+            Ok(value)
+        })
+    }
+
+    /// Gets the largest change trigger that can be set.
+    pub fn max_ph_change_trigger(&self) -> Result<f64> {
+        crate::cache::property(self.chan as PhidgetHandle, "MaxPHChangeTrigger", || {
+            let mut value = 0.0;
+            ReturnCode::result(unsafe {
+                traced!(ffi::PhidgetPHSensor_getMaxPHChangeTrigger(
+                    self.chan, &mut value
+                ))
+            })?;
+            Ok(value)
+        })
+    }
+
+    /// Sets a handler to receive pH change callbacks.
+    /// The handler is removed when the returned guard is dropped.
+    pub fn set_on_ph_change_handler<F, R>(&self, cb: F) -> Result<HandlerGuard>
+    where
+        F: FnMut(&PhSensor, f64) -> R + Send + 'static,
+        R: HandlerResult,
+    {
+        let ctx = Context::into_raw(Self::from(self.chan), cb);
+
+        crate::handler::register::<Context<Self, F>, _>(
+            self.chan as PhidgetHandle,
+            "ph_change",
+            ctx,
+            || unsafe {
+                traced!(ffi::PhidgetPHSensor_setOnPHChangeHandler(
+                    self.chan,
+                    Some(Self::on_ph_change::<F, R>),
+                    ctx,
+                ))
+            },
+            |phid| unsafe {
+                traced!(ffi::PhidgetPHSensor_setOnPHChangeHandler(
+                    phid as _,
+                    None,
+                    ptr::null_mut()
+                ))
+            },
+        )
+    }
+
+    /// Set a handler to receive pH change callbacks, which can borrow
+    /// from the environment of the scope.
+    /// The handler is removed at the end of the scope.
+    pub fn set_on_ph_change_handler_scoped<'scope, F, R>(
+        &self,
+        scope: &'scope HandlerScope<'scope, '_>,
+        cb: F,
+    ) -> Result<()>
+    where
+        F: FnMut(&PhSensor, f64) -> R + Send + 'scope,
+        R: HandlerResult + 'static,
+    {
+        // SAFETY: The guard is held by the scope, which can't be forgotten,
+        // and which waits for any call to the handler. See `HandlerScope`.
+        let cb = unsafe {
+            mem::transmute::<
+                Box<dyn FnMut(&PhSensor, f64) -> R + Send + 'scope>,
+                Box<dyn FnMut(&PhSensor, f64) -> R + Send>,
+            >(Box::new(cb))
+        };
+        scope.hold(self.set_on_ph_change_handler(cb)?);
+        Ok(())
+    }
+
+    /// Removes the pH change handler, if one is set, and releases
+    /// its closure.
+    pub fn remove_on_ph_change_handler(&self) -> Result<()> {
+        crate::handler::unregister(self.chan as PhidgetHandle, "ph_change")
+    }
+
+    /// Gets a channel that receives the pH readings, fed by the pH change events.
+    ///
+    /// This registers the channel's pH change handler, replacing any handler
+    /// that was previously set.
+    /// The handler is removed when the receiver is dropped.
+    pub fn ph_channel(&self) -> Result<EventReceiver<f64>> {
+        let (tx, rx) = mpsc::channel();
+        let guard = self.set_on_ph_change_handler(move |_, v| {
+            let _ = tx.send(v);
+        })?;
+        Ok(EventReceiver::new(rx, guard))
+    }
+
+    /// Gets a stream of the pH readings, fed by the pH change events.
+    ///
+    /// This registers the channel's pH change handler, replacing any handler
+    /// that was previously set.
+    /// The handler is removed when the stream is dropped.
+    #[cfg(feature = "async")]
+    pub fn ph_stream(&self) -> Result<EventStream<f64>> {
+        let (mut tx, rx) = crate::stream::event_channel();
+        let guard = self.set_on_ph_change_handler(move |_, v| tx.send(v))?;
+        Ok(EventStream::new(rx, guard))
+    }
+}
+
+impl Phidget for PhSensor {
+    fn as_handle(&mut self) -> PhidgetHandle {
+        self.chan as PhidgetHandle
+    }
+}
+
+impl Sensor for PhSensor {
+    type Value = f64;
+
+    fn value(&self) -> Result<f64> {
+        self.ph()
+    }
+
+    fn on_change<F>(&self, cb: F) -> Result<HandlerGuard>
+    where
+        F: FnMut(&Self, f64) + Send + 'static,
+    {
+        self.set_on_ph_change_handler(cb)
+    }
+}
+
+impl fmt::Debug for PhSensor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        crate::phidget::fmt_debug(f, "PhSensor", self.chan as PhidgetHandle)
+    }
+}
+
+impl fmt::Display for PhSensor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        crate::phidget::fmt_display(f, "PhSensor", self.chan as PhidgetHandle)
+    }
+}
+
+unsafe impl Send for PhSensor {}
+unsafe impl Sync for PhSensor {}
+
+impl Default for PhSensor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl From<PhidgetPHSensorHandle> for PhSensor {
+    fn from(chan: PhidgetPHSensorHandle) -> Self {
+        Self { chan }
+    }
+}
+
+impl TryFrom<GenericPhidget> for PhSensor {
+    type Error = Error;
+
+    /// Converts a generic phidget into a typed channel, if it's of the
+    /// right class. If the channel is open, it's closed when dropped.
+    fn try_from(ph: GenericPhidget) -> Result<Self> {
+        let chan = ph.into_channel(ChannelClass::PhSensor)?;
+        Ok(Self::from(chan as PhidgetPHSensorHandle))
+    }
+}
+
+impl Drop for PhSensor {
+    fn drop(&mut self) {
+        if let Ok(true) = self.is_open() {
+            let _ = self.close();
+        }
+        let phid = self.chan as PhidgetHandle;
+        unsafe {
+            ffi::PhidgetPHSensor_delete(&mut self.chan);
+        }
+        crate::notify::reset(phid);
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::*;
+    use crate::mock::{set_property, set_value};
+
+    #[test]
+    fn test_mock_ph_change_trigger() {
+        let mut ph = PhSensor::new();
+        ph.open().unwrap();
+        set_property(&mut ph, "MinPHChangeTrigger", 0.0).unwrap();
+        set_property(&mut ph, "MaxPHChangeTrigger", 14.0).unwrap();
+        assert_eq!(ph.min_ph_change_trigger(), Ok(0.0));
+        assert_eq!(ph.max_ph_change_trigger(), Ok(14.0));
+
+        ph.set_ph_change_trigger(0.1).unwrap();
+        assert_eq!(ph.ph_change_trigger(), Ok(0.1));
+
+        // Sensor noise doesn't reach the controller, but a real drift does
+        let rx = ph.ph_channel().unwrap();
+        for val in [7.00, 7.04, 6.97, 7.02, 6.85] {
+            set_value(&mut ph, val).unwrap();
+        }
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), [7.00, 6.85]);
+        assert_eq!(ph.ph(), Ok(6.85));
+    }
+}
@@ -0,0 +1,381 @@
+// phidget-rs/src/devices/pressure_sensor.rs
+//
+// This file is part of the 'phidget-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+use crate::{
+    handler::Context, ChannelClass, Error, EventReceiver, GenericPhidget, HandlerGuard,
+    HandlerResult, HandlerScope, Phidget, Result, ReturnCode, Sensor,
+};
+use phidget_sys::{self as ffi, PhidgetHandle, PhidgetPressureSensorHandle};
+use std::{fmt, mem, os::raw::c_void, ptr, sync::mpsc};
+
+#[cfg(feature = "async")]
+use crate::stream::EventStream;
+
+#[cfg(feature = "units")]
+use uom::si::{f64::Pressure, pressure::kilopascal};
+
+/// The function signature for the safe Rust pressure change callback.
+pub type PressureChangeCallback = dyn FnMut(&PressureSensor, f64) + Send + 'static;
+
+/////////////////////////////////////////////////////////////////////////////
+
+/// Phidget pressure sensor
+pub struct PressureSensor {
+    // Handle to the pressure sensor in the phidget22 library
+    chan: PhidgetPressureSensorHandle,
+}
+
+impl PressureSensor {
+    /// Create a new pressure sensor.
+    ///
+    /// This panics if the library can't create the channel. Use
+    /// `try_new()` to get the error instead.
+    pub fn new() -> Self {
+        Self::try_new().expect("failed to create the channel")
+    }
+
+    /// Create a new pressure sensor, returning an error if the library can't
+    /// create the channel.
+    pub fn try_new() -> Result<Self> {
+        let mut chan: PhidgetPressureSensorHandle = ptr::null_mut();
+        ReturnCode::result(unsafe { ffi::PhidgetPressureSensor_create(&mut chan) })?;
+        crate::phidget::install_handlers(chan as PhidgetHandle);
+        Ok(Self::from(chan))
+    }
+
+    // Low-level, unsafe, callback for the pressure change event.
+    // The context holds a view of the channel and the safe Rust callback.
+    unsafe extern "C" fn on_pressure_change<F, R>(
+        chan: PhidgetPressureSensorHandle,
+        ctx: *mut c_void,
+        pressure: f64,
+    ) where
+        F: FnMut(&PressureSensor, f64) -> R + Send + 'static,
+        R: HandlerResult,
+    {
+        crate::handler::deliver(chan as PhidgetHandle, ctx, move |phid, ctx| {
+            let ctx: &mut Context<Self, F> = &mut *(ctx as *mut _);
+            crate::handler::report(phid, (ctx.cb)(&ctx.chan, pressure));
+        });
+    }
+
+    /// Get a reference to the underlying sensor handle
+    pub fn as_channel(&self) -> &PhidgetPressureSensorHandle {
+        &self.chan
+    }
+
+    /// Get the pressure measured by the sensor, in kPa
+    pub fn pressure(&self) -> Result<f64> {
+        let mut v: f64 = 0.0;
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetPressureSensor_getPressure(self.chan, &mut v))
+        })?;
+        Ok(v)
+    }
+
+    /// Read the pressure as a typed quantity.
+    #[cfg(feature = "units")]
+    pub fn pressure_quantity(&self) -> Result<Pressure> {
+        Ok(Pressure::new::<kilopascal>(self.pressure()?))
+    }
+
+    /// Gets the minimum change in pressure, in kPa, that triggers a change
+    /// event.
+    pub fn pressure_change_trigger(&self) -> Result<f64> {
+        let mut trigger = 0.0;
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetPressureSensor_getPressureChangeTrigger(
+                self.chan,
+                &mut trigger
+            ))
+        })?;
+        Ok(trigger)
+    }
+
+    /// Sets the minimum change in pressure, in kPa, that triggers a change
+    /// event. Zero reports every reading.
+    pub fn set_pressure_change_trigger(&mut self, trigger: f64) -> Result<()> {
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetPressureSensor_setPressureChangeTrigger(
+                self.chan, trigger
+            ))
+        })
+    }
+
+    /// Gets the minimum pressure that the sensor can measure, in kPa.
+    pub fn min_pressure(&self) -> Result<f64> {
+        crate::cache::property(self.chan as PhidgetHandle, "MinPressure", || {
+            let mut value = 0.0;
+            ReturnCode::result(unsafe {
+                traced!(ffi::PhidgetPressureSensor_getMinPressure(
+                    self.chan, &mut value
+                ))
+            })?;
+            Ok(value)
+        })
+    }
+
+    /// Gets the maximum pressure that the sensor can measure, in kPa.
+    pub fn max_pressure(&self) -> Result<f64> {
+        crate::cache::property(self.chan as PhidgetHandle, "MaxPressure", || {
+            let mut value = 0.0;
+            ReturnCode::result(unsafe {
+                traced!(ffi::PhidgetPressureSensor_getMaxPressure(
+                    self.chan, &mut value
+                ))
+            })?;
+            Ok(value)
+        })
+    }
+
+    /// Gets the smallest change trigger that can be set.
+    pub fn min_pressure_change_trigger(&self) -> Result<f64> {
+        crate::cache::property(
+            self.chan as PhidgetHandle,
+            "MinPressureChangeTrigger",
+            || {
+                let mut value = 0.0;
+                ReturnCode::result(unsafe {
+                    traced!(ffi::PhidgetPressureSensor_getMinPressureChangeTrigger(
+                        self.chan, &mut value
+                    ))
+                })?;
+                Ok(value)
+            },
+        )
+    }
+
+    /// Gets the largest change trigger that can be set.
+    pub fn max_pressure_change_trigger(&self) -> Result<f64> {
+        crate::cache::property(
+            self.chan as PhidgetHandle,
+            "MaxPressureChangeTrigger",
+            || {
+                let mut value = 0.0;
+                ReturnCode::result(unsafe {
+                    traced!(ffi::PhidgetPressureSensor_getMaxPressureChangeTrigger(
+                        self.chan, &mut value
+                    ))
+                })?;
+                Ok(value)
+            },
+        )
+    }
+
+    /// Set a handler to receive pressure change callbacks with the value as a
+    /// typed quantity.
+    /// This replaces any pressure change handler that was previously set.
+    /// The handler is removed when the returned guard is dropped.
+    #[cfg(feature = "units")]
+    pub fn set_on_pressure_change_quantity_handler<F, R>(&self, mut cb: F) -> Result<HandlerGuard>
+    where
+        F: FnMut(&PressureSensor, Pressure) -> R + Send + 'static,
+        R: HandlerResult,
+    {
+        self.set_on_pressure_change_handler(move |ch, val| cb(ch, Pressure::new::<kilopascal>(val)))
+    }
+
+    /// Sets a handler to receive pressure change callbacks.
+    /// The handler is removed when the returned guard is dropped.
+    pub fn set_on_pressure_change_handler<F, R>(&self, cb: F) -> Result<HandlerGuard>
+    where
+        F: FnMut(&PressureSensor, f64) -> R + Send + 'static,
+        R: HandlerResult,
+    {
+        let ctx = Context::into_raw(Self::from(self.chan), cb);
+
+        crate::handler::register::<Context<Self, F>, _>(
+            self.chan as PhidgetHandle,
+            "pressure_change",
+            ctx,
+            || unsafe {
+                traced!(ffi::PhidgetPressureSensor_setOnPressureChangeHandler(
+                    self.chan,
+                    Some(Self::on_pressure_change::<F, R>),
+                    ctx,
+                ))
+            },
+            |phid| unsafe {
+                traced!(ffi::PhidgetPressureSensor_setOnPressureChangeHandler(
+                    phid as _,
+                    None,
+                    ptr::null_mut()
+                ))
+            },
+        )
+    }
+
+    /// Set a handler to receive pressure change callbacks, which can borrow
+    /// from the environment of the scope.
+    /// The handler is removed at the end of the scope.
+    pub fn set_on_pressure_change_handler_scoped<'scope, F, R>(
+        &self,
+        scope: &'scope HandlerScope<'scope, '_>,
+        cb: F,
+    ) -> Result<()>
+    where
+        F: FnMut(&PressureSensor, f64) -> R + Send + 'scope,
+        R: HandlerResult + 'static,
+    {
+        // SAFETY: The guard is held by the scope, which can't be forgotten,
+        // and which waits for any call to the handler. See `HandlerScope`.
+        let cb = unsafe {
+            mem::transmute::<
+                Box<dyn FnMut(&PressureSensor, f64) -> R + Send + 'scope>,
+                Box<dyn FnMut(&PressureSensor, f64) -> R + Send>,
+            >(Box::new(cb))
+        };
+        scope.hold(self.set_on_pressure_change_handler(cb)?);
+        Ok(())
+    }
+
+    /// Removes the pressure change handler, if one is set, and releases
+    /// its closure.
+    pub fn remove_on_pressure_change_handler(&self) -> Result<()> {
+        crate::handler::unregister(self.chan as PhidgetHandle, "pressure_change")
+    }
+
+    /// Gets a channel that receives the pressure readings, fed by the pressure change events.
+    ///
+    /// This registers the channel's pressure change handler, replacing any handler
+    /// that was previously set.
+    /// The handler is removed when the receiver is dropped.
+    pub fn pressure_channel(&self) -> Result<EventReceiver<f64>> {
+        let (tx, rx) = mpsc::channel();
+        let guard = self.set_on_pressure_change_handler(move |_, v| {
+            let _ = tx.send(v);
+        })?;
+        Ok(EventReceiver::new(rx, guard))
+    }
+
+    /// Gets a stream of the pressure readings, fed by the pressure change events.
+    ///
+    /// This registers the channel's pressure change handler, replacing any handler
+    /// that was previously set.
+    /// The handler is removed when the stream is dropped.
+    #[cfg(feature = "async")]
+    pub fn pressure_stream(&self) -> Result<EventStream<f64>> {
+        let (mut tx, rx) = crate::stream::event_channel();
+        let guard = self.set_on_pressure_change_handler(move |_, v| tx.send(v))?;
+        Ok(EventStream::new(rx, guard))
+    }
+}
+
+impl Phidget for PressureSensor {
+    fn as_handle(&mut self) -> PhidgetHandle {
+        self.chan as PhidgetHandle
+    }
+}
+
+impl Sensor for PressureSensor {
+    type Value = f64;
+
+    fn value(&self) -> Result<f64> {
+        self.pressure()
+    }
+
+    fn on_change<F>(&self, cb: F) -> Result<HandlerGuard>
+    where
+        F: FnMut(&Self, f64) + Send + 'static,
+    {
+        self.set_on_pressure_change_handler(cb)
+    }
+}
+
+impl fmt::Debug for PressureSensor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        crate::phidget::fmt_debug(f, "PressureSensor", self.chan as PhidgetHandle)
+    }
+}
+
+impl fmt::Display for PressureSensor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        crate::phidget::fmt_display(f, "PressureSensor", self.chan as PhidgetHandle)
+    }
+}
+
+unsafe impl Send for PressureSensor {}
+unsafe impl Sync for PressureSensor {}
+
+impl Default for PressureSensor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl From<PhidgetPressureSensorHandle> for PressureSensor {
+    fn from(chan: PhidgetPressureSensorHandle) -> Self {
+        Self { chan }
+    }
+}
+
+impl TryFrom<GenericPhidget> for PressureSensor {
+    type Error = Error;
+
+    /// Converts a generic phidget into a typed channel, if it's of the
+    /// right class. If the channel is open, it's closed when dropped.
+    fn try_from(ph: GenericPhidget) -> Result<Self> {
+        let chan = ph.into_channel(ChannelClass::PressureSensor)?;
+        Ok(Self::from(chan as PhidgetPressureSensorHandle))
+    }
+}
+
+impl Drop for PressureSensor {
+    fn drop(&mut self) {
+        if let Ok(true) = self.is_open() {
+            let _ = self.close();
+        }
+        let phid = self.chan as PhidgetHandle;
+        unsafe {
+            ffi::PhidgetPressureSensor_delete(&mut self.chan);
+        }
+        crate::notify::reset(phid);
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::*;
+    use crate::mock::{set_property, set_value};
+
+    #[test]
+    fn test_mock_pressure_change_trigger() {
+        let mut baro = PressureSensor::new();
+        baro.open().unwrap();
+        set_property(&mut baro, "MinPressureChangeTrigger", 0.0).unwrap();
+        set_property(&mut baro, "MaxPressureChangeTrigger", 50.0).unwrap();
+        assert_eq!(baro.min_pressure_change_trigger(), Ok(0.0));
+        assert_eq!(baro.max_pressure_change_trigger(), Ok(50.0));
+
+        baro.set_pressure_change_trigger(0.1).unwrap();
+        assert_eq!(baro.pressure_change_trigger(), Ok(0.1));
+
+        // The trend is logged without the small fluctuations
+        let rx = baro.pressure_channel().unwrap();
+        for kpa in [101.30, 101.34, 101.27, 101.45, 101.50, 101.60] {
+            set_value(&mut baro, kpa).unwrap();
+        }
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), [101.30, 101.45, 101.60]);
+    }
+
+    #[test]
+    fn test_mock_pressure_range() {
+        let mut baro = PressureSensor::new();
+        assert_eq!(baro.min_pressure(), Err(Error::NotAttached));
+
+        baro.open().unwrap();
+        set_property(&mut baro, "MinPressure", 50.0).unwrap();
+        set_property(&mut baro, "MaxPressure", 110.0).unwrap();
+        assert_eq!(baro.min_pressure(), Ok(50.0));
+        assert_eq!(baro.max_pressure(), Ok(110.0));
+    }
+}
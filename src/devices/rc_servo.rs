@@ -0,0 +1,363 @@
+// phidget-rs/src/devices/rc_servo.rs
+//
+// This file is part of the 'phidget-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+use crate::{
+    handler::Context, ChannelClass, Error, GenericPhidget, HandlerGuard, HandlerResult,
+    HandlerScope, Phidget, Result, ReturnCode,
+};
+use phidget_sys::{self as ffi, PhidgetHandle, PhidgetRCServoHandle};
+use std::{
+    fmt, mem,
+    os::raw::{c_int, c_void},
+    ptr,
+};
+
+/// The function signature for the safe Rust position callbacks.
+pub type ServoPositionCallback = dyn FnMut(&RcServo, f64) + Send + 'static;
+
+/////////////////////////////////////////////////////////////////////////////
+
+/// Phidget RC servo controller channel
+pub struct RcServo {
+    // Handle to the RC servo in the phidget22 library
+    chan: PhidgetRCServoHandle,
+}
+
+impl RcServo {
+    /// Create a new RC servo channel.
+    ///
+    /// This panics if the library can't create the channel. Use
+    /// `try_new()` to get the error instead.
+    pub fn new() -> Self {
+        Self::try_new().expect("failed to create the channel")
+    }
+
+    /// Create a new RC servo channel, returning an error if the library
+    /// can't create the channel.
+    pub fn try_new() -> Result<Self> {
+        let mut chan: PhidgetRCServoHandle = ptr::null_mut();
+        ReturnCode::result(unsafe { ffi::PhidgetRCServo_create(&mut chan) })?;
+        crate::phidget::install_handlers(chan as PhidgetHandle);
+        Ok(Self::from(chan))
+    }
+
+    // Low-level, unsafe, callback for the position events.
+    // The context holds a view of the channel and the safe Rust callback.
+    unsafe extern "C" fn on_position<F, R>(chan: PhidgetRCServoHandle, ctx: *mut c_void, pos: f64)
+    where
+        F: FnMut(&RcServo, f64) -> R + Send + 'static,
+        R: HandlerResult,
+    {
+        crate::handler::deliver(chan as PhidgetHandle, ctx, move |phid, ctx| {
+            let ctx: &mut Context<Self, F> = &mut *(ctx as *mut _);
+            crate::handler::report(phid, (ctx.cb)(&ctx.chan, pos));
+        });
+    }
+
+    /// Get a reference to the underlying RC servo handle
+    pub fn as_channel(&self) -> &PhidgetRCServoHandle {
+        &self.chan
+    }
+
+    /// Gets the current position of the servo.
+    pub fn position(&self) -> Result<f64> {
+        let mut value = 0.0;
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetRCServo_getPosition(self.chan, &mut value))
+        })?;
+        Ok(value)
+    }
+
+    /// Gets the minimum position that the servo can be set to.
+    pub fn min_position(&self) -> Result<f64> {
+        let mut value = 0.0;
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetRCServo_getMinPosition(self.chan, &mut value))
+        })?;
+        Ok(value)
+    }
+
+    /// Sets the position that corresponds to the minimum pulse width.
+    pub fn set_min_position(&mut self, pos: f64) -> Result<()> {
+        ReturnCode::result(unsafe { traced!(ffi::PhidgetRCServo_setMinPosition(self.chan, pos)) })
+    }
+
+    /// Gets the maximum position that the servo can be set to.
+    pub fn max_position(&self) -> Result<f64> {
+        let mut value = 0.0;
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetRCServo_getMaxPosition(self.chan, &mut value))
+        })?;
+        Ok(value)
+    }
+
+    /// Sets the position that corresponds to the maximum pulse width.
+    pub fn set_max_position(&mut self, pos: f64) -> Result<()> {
+        ReturnCode::result(unsafe { traced!(ffi::PhidgetRCServo_setMaxPosition(self.chan, pos)) })
+    }
+
+    /// Gets the position that the servo is moving to.
+    pub fn target_position(&self) -> Result<f64> {
+        let mut value = 0.0;
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetRCServo_getTargetPosition(self.chan, &mut value))
+        })?;
+        Ok(value)
+    }
+
+    /// Sets the position for the servo to move to.
+    ///
+    /// The servo only moves while it's engaged.
+    pub fn set_target_position(&mut self, pos: f64) -> Result<()> {
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetRCServo_setTargetPosition(self.chan, pos))
+        })
+    }
+
+    /// Determines if the servo is engaged.
+    pub fn engaged(&self) -> Result<bool> {
+        let mut value: c_int = 0;
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetRCServo_getEngaged(self.chan, &mut value))
+        })?;
+        Ok(value != 0)
+    }
+
+    /// Engages or disengages the servo.
+    ///
+    /// An engaged servo is driven to the target position, and holds it.
+    pub fn set_engaged(&mut self, engaged: bool) -> Result<()> {
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetRCServo_setEngaged(self.chan, engaged as c_int))
+        })
+    }
+
+    /// Determines if the servo is moving toward its target position.
+    pub fn is_moving(&self) -> Result<bool> {
+        let mut value: c_int = 0;
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetRCServo_getIsMoving(self.chan, &mut value))
+        })?;
+        Ok(value != 0)
+    }
+
+    /// Gets the maximum velocity of the servo.
+    pub fn velocity_limit(&self) -> Result<f64> {
+        let mut value = 0.0;
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetRCServo_getVelocityLimit(self.chan, &mut value))
+        })?;
+        Ok(value)
+    }
+
+    /// Sets the maximum velocity of the servo.
+    pub fn set_velocity_limit(&mut self, velocity_limit: f64) -> Result<()> {
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetRCServo_setVelocityLimit(
+                self.chan,
+                velocity_limit
+            ))
+        })
+    }
+
+    /// Gets the acceleration of the servo.
+    pub fn acceleration(&self) -> Result<f64> {
+        let mut value = 0.0;
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetRCServo_getAcceleration(self.chan, &mut value))
+        })?;
+        Ok(value)
+    }
+
+    /// Sets the acceleration of the servo.
+    pub fn set_acceleration(&mut self, acceleration: f64) -> Result<()> {
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetRCServo_setAcceleration(self.chan, acceleration))
+        })
+    }
+
+    /// Sets a handler to receive position change callbacks.
+    /// The handler is removed when the returned guard is dropped.
+    pub fn set_on_position_change_handler<F, R>(&self, cb: F) -> Result<HandlerGuard>
+    where
+        F: FnMut(&RcServo, f64) -> R + Send + 'static,
+        R: HandlerResult,
+    {
+        let ctx = Context::into_raw(Self::from(self.chan), cb);
+
+        crate::handler::register::<Context<Self, F>, _>(
+            self.chan as PhidgetHandle,
+            "position_change",
+            ctx,
+            || unsafe {
+                traced!(ffi::PhidgetRCServo_setOnPositionChangeHandler(
+                    self.chan,
+                    Some(Self::on_position::<F, R>),
+                    ctx,
+                ))
+            },
+            |phid| unsafe {
+                traced!(ffi::PhidgetRCServo_setOnPositionChangeHandler(
+                    phid as _,
+                    None,
+                    ptr::null_mut()
+                ))
+            },
+        )
+    }
+
+    /// Set a handler to receive position change callbacks, which can borrow
+    /// from the environment of the scope.
+    /// The handler is removed at the end of the scope.
+    pub fn set_on_position_change_handler_scoped<'scope, F, R>(
+        &self,
+        scope: &'scope HandlerScope<'scope, '_>,
+        cb: F,
+    ) -> Result<()>
+    where
+        F: FnMut(&RcServo, f64) -> R + Send + 'scope,
+        R: HandlerResult + 'static,
+    {
+        // SAFETY: The guard is held by the scope, which can't be forgotten,
+        // and which waits for any call to the handler. See `HandlerScope`.
+        let cb = unsafe {
+            mem::transmute::<
+                Box<dyn FnMut(&RcServo, f64) -> R + Send + 'scope>,
+                Box<dyn FnMut(&RcServo, f64) -> R + Send>,
+            >(Box::new(cb))
+        };
+        scope.hold(self.set_on_position_change_handler(cb)?);
+        Ok(())
+    }
+
+    /// Removes the position change handler, if one is set, and releases its
+    /// closure.
+    pub fn remove_on_position_change_handler(&self) -> Result<()> {
+        crate::handler::unregister(self.chan as PhidgetHandle, "position_change")
+    }
+
+    /// Sets a handler to receive a callback, with the position, when the
+    /// servo reaches its target position.
+    /// The handler is removed when the returned guard is dropped.
+    pub fn set_on_target_position_reached_handler<F, R>(&self, cb: F) -> Result<HandlerGuard>
+    where
+        F: FnMut(&RcServo, f64) -> R + Send + 'static,
+        R: HandlerResult,
+    {
+        let ctx = Context::into_raw(Self::from(self.chan), cb);
+
+        crate::handler::register::<Context<Self, F>, _>(
+            self.chan as PhidgetHandle,
+            "target_position_reached",
+            ctx,
+            || unsafe {
+                traced!(ffi::PhidgetRCServo_setOnTargetPositionReachedHandler(
+                    self.chan,
+                    Some(Self::on_position::<F, R>),
+                    ctx,
+                ))
+            },
+            |phid| unsafe {
+                traced!(ffi::PhidgetRCServo_setOnTargetPositionReachedHandler(
+                    phid as _,
+                    None,
+                    ptr::null_mut()
+                ))
+            },
+        )
+    }
+
+    /// Set a handler to receive target position reached callbacks, which
+    /// can borrow from the environment of the scope.
+    /// The handler is removed at the end of the scope.
+    pub fn set_on_target_position_reached_handler_scoped<'scope, F, R>(
+        &self,
+        scope: &'scope HandlerScope<'scope, '_>,
+        cb: F,
+    ) -> Result<()>
+    where
+        F: FnMut(&RcServo, f64) -> R + Send + 'scope,
+        R: HandlerResult + 'static,
+    {
+        // SAFETY: The guard is held by the scope, which can't be forgotten,
+        // and which waits for any call to the handler. See `HandlerScope`.
+        let cb = unsafe {
+            mem::transmute::<
+                Box<dyn FnMut(&RcServo, f64) -> R + Send + 'scope>,
+                Box<dyn FnMut(&RcServo, f64) -> R + Send>,
+            >(Box::new(cb))
+        };
+        scope.hold(self.set_on_target_position_reached_handler(cb)?);
+        Ok(())
+    }
+
+    /// Removes the target position reached handler, if one is set, and
+    /// releases its closure.
+    pub fn remove_on_target_position_reached_handler(&self) -> Result<()> {
+        crate::handler::unregister(self.chan as PhidgetHandle, "target_position_reached")
+    }
+}
+
+impl Phidget for RcServo {
+    fn as_handle(&mut self) -> PhidgetHandle {
+        self.chan as PhidgetHandle
+    }
+}
+
+impl fmt::Debug for RcServo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        crate::phidget::fmt_debug(f, "RcServo", self.chan as PhidgetHandle)
+    }
+}
+
+impl fmt::Display for RcServo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        crate::phidget::fmt_display(f, "RcServo", self.chan as PhidgetHandle)
+    }
+}
+
+unsafe impl Send for RcServo {}
+unsafe impl Sync for RcServo {}
+
+impl Default for RcServo {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl From<PhidgetRCServoHandle> for RcServo {
+    fn from(chan: PhidgetRCServoHandle) -> Self {
+        Self { chan }
+    }
+}
+
+impl TryFrom<GenericPhidget> for RcServo {
+    type Error = Error;
+
+    /// Converts a generic phidget into a typed channel, if it's of the
+    /// right class. If the channel is open, it's closed when dropped.
+    fn try_from(ph: GenericPhidget) -> Result<Self> {
+        let chan = ph.into_channel(ChannelClass::RcServo)?;
+        Ok(Self::from(chan as PhidgetRCServoHandle))
+    }
+}
+
+impl Drop for RcServo {
+    fn drop(&mut self) {
+        if let Ok(true) = self.is_open() {
+            let _ = self.close();
+        }
+        let phid = self.chan as PhidgetHandle;
+        unsafe {
+            ffi::PhidgetRCServo_delete(&mut self.chan);
+        }
+        crate::notify::reset(phid);
+    }
+}
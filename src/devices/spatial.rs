@@ -0,0 +1,376 @@
+// phidget-rs/src/devices/spatial.rs
+//
+// This file is part of the 'phidget-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+use crate::{
+    handler::Context, ChannelClass, Error, EventReceiver, GenericPhidget, HandlerGuard,
+    HandlerResult, HandlerScope, Phidget, Result, ReturnCode,
+};
+use phidget_sys::{self as ffi, PhidgetHandle, PhidgetSpatialHandle as SpatialHandle};
+use std::{fmt, mem, os::raw::c_void, ptr, slice, sync::mpsc, sync::Mutex};
+
+#[cfg(feature = "async")]
+use crate::stream::EventStream;
+
+/// The function type for the safe Rust spatial data callback.
+pub type SpatialDataCallback = dyn FnMut(&Spatial, SpatialData) + Send + 'static;
+
+/// A single sample from a spatial (IMU) channel.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SpatialData {
+    /// The acceleration on each axis, in g
+    pub acceleration: [f64; 3],
+    /// The angular rate on each axis, in degrees per second
+    pub angular_rate: [f64; 3],
+    /// The magnetic field on each axis, in gauss
+    pub magnetic_field: [f64; 3],
+    /// The time of the sample, in milliseconds
+    pub timestamp: f64,
+}
+
+impl SpatialData {
+    // Creates a sample from the values passed to the low-level callback.
+    unsafe fn from_raw(
+        acceleration: *const f64,
+        angular_rate: *const f64,
+        magnetic_field: *const f64,
+        timestamp: f64,
+    ) -> Self {
+        let axes = |p: *const f64| {
+            let mut v = [0.0; 3];
+            if !p.is_null() {
+                v.copy_from_slice(slice::from_raw_parts(p, 3));
+            }
+            v
+        };
+        Self {
+            acceleration: axes(acceleration),
+            angular_rate: axes(angular_rate),
+            magnetic_field: axes(magnetic_field),
+            timestamp,
+        }
+    }
+}
+
+// The latest sample from each spatial channel, keyed by channel handle.
+static SAMPLES: Mutex<Vec<(usize, SpatialData)>> = Mutex::new(Vec::new());
+
+// Records the latest sample from the channel.
+fn record(chan: SpatialHandle, data: SpatialData) {
+    let key = chan as usize;
+    let mut samples = SAMPLES.lock().unwrap_or_else(|err| err.into_inner());
+    match samples.iter_mut().find(|(k, _)| *k == key) {
+        Some((_, d)) => *d = data,
+        None => samples.push((key, data)),
+    }
+}
+
+// Removes the recorded sample for the channel.
+fn forget(chan: SpatialHandle) {
+    let key = chan as usize;
+    let mut samples = SAMPLES.lock().unwrap_or_else(|err| err.into_inner());
+    samples.retain(|(k, _)| *k != key);
+}
+
+/// Phidget spatial (IMU) channel
+///
+/// A spatial channel reports its acceleration, angular rate, and magnetic
+/// field together, in a single data event for each sample.
+pub struct Spatial {
+    // Handle to the channel for the phidget22 library
+    chan: SpatialHandle,
+}
+
+impl Spatial {
+    /// Create a new spatial channel.
+    ///
+    /// This panics if the library can't create the channel. Use
+    /// `try_new()` to get the error instead.
+    pub fn new() -> Self {
+        Self::try_new().expect("failed to create the channel")
+    }
+
+    /// Create a new spatial channel, returning an error if the library can't
+    /// create the channel.
+    pub fn try_new() -> Result<Self> {
+        let mut chan: SpatialHandle = ptr::null_mut();
+        ReturnCode::result(unsafe { ffi::PhidgetSpatial_create(&mut chan) })?;
+        crate::phidget::install_handlers(chan as PhidgetHandle);
+        forget(chan);
+        unsafe {
+            traced!(ffi::PhidgetSpatial_setOnSpatialDataHandler(
+                chan,
+                Some(Self::on_record),
+                ptr::null_mut(),
+            ));
+        }
+        Ok(Self::from(chan))
+    }
+
+    // Low-level, unsafe, callback that only records the latest sample.
+    // This is installed whenever no other data handler is set.
+    unsafe extern "C" fn on_record(
+        chan: SpatialHandle,
+        _ctx: *mut c_void,
+        acceleration: *const f64,
+        angular_rate: *const f64,
+        magnetic_field: *const f64,
+        timestamp: f64,
+    ) {
+        let data = SpatialData::from_raw(acceleration, angular_rate, magnetic_field, timestamp);
+        record(chan, data);
+    }
+
+    // Low-level, unsafe, callback for spatial data events.
+    // The context holds a view of the channel and the safe Rust callback.
+    unsafe extern "C" fn on_spatial_data<F, R>(
+        chan: SpatialHandle,
+        ctx: *mut c_void,
+        acceleration: *const f64,
+        angular_rate: *const f64,
+        magnetic_field: *const f64,
+        timestamp: f64,
+    ) where
+        F: FnMut(&Spatial, SpatialData) -> R + Send + 'static,
+        R: HandlerResult,
+    {
+        let data = SpatialData::from_raw(acceleration, angular_rate, magnetic_field, timestamp);
+        record(chan, data);
+        crate::handler::deliver(chan as PhidgetHandle, ctx, move |phid, ctx| {
+            let ctx: &mut Context<Self, F> = &mut *(ctx as *mut _);
+            crate::handler::report(phid, (ctx.cb)(&ctx.chan, data));
+        });
+    }
+
+    /// Get a reference to the underlying channel handle
+    pub fn as_channel(&self) -> &SpatialHandle {
+        &self.chan
+    }
+
+    /// Read the latest sample: the acceleration, angular rate, magnetic
+    /// field, and timestamp, together.
+    ///
+    /// The library only reports spatial data through its data events, so
+    /// this is the last sample delivered to the channel, taken in a single
+    /// lock rather than with a separate call for each value. Returns an
+    /// `UnknownValue` error if no sample has arrived yet.
+    pub fn read_all(&self) -> Result<SpatialData> {
+        let key = self.chan as usize;
+        let samples = SAMPLES.lock().unwrap_or_else(|err| err.into_inner());
+        samples
+            .iter()
+            .find(|(k, _)| *k == key)
+            .map(|(_, d)| *d)
+            .ok_or(Error::UnknownValue(ReturnCode::UnknownVal))
+    }
+
+    /// Set a handler to receive spatial data callbacks.
+    /// The handler is removed when the returned guard is dropped.
+    pub fn set_on_spatial_data_handler<F, R>(&self, cb: F) -> Result<HandlerGuard>
+    where
+        F: FnMut(&Spatial, SpatialData) -> R + Send + 'static,
+        R: HandlerResult,
+    {
+        let ctx = Context::into_raw(Self::from(self.chan), cb);
+
+        crate::handler::register::<Context<Self, F>, _>(
+            self.chan as PhidgetHandle,
+            "spatial_data",
+            ctx,
+            || unsafe {
+                traced!(ffi::PhidgetSpatial_setOnSpatialDataHandler(
+                    self.chan,
+                    Some(Self::on_spatial_data::<F, R>),
+                    ctx,
+                ))
+            },
+            |phid| unsafe {
+                // Keep recording the samples for `read_all()`
+                traced!(ffi::PhidgetSpatial_setOnSpatialDataHandler(
+                    phid as _,
+                    Some(Self::on_record),
+                    ptr::null_mut(),
+                ))
+            },
+        )
+    }
+
+    /// Set a handler to receive spatial data callbacks, which can borrow
+    /// from the environment of the scope.
+    /// The handler is removed at the end of the scope.
+    pub fn set_on_spatial_data_handler_scoped<'scope, F, R>(
+        &self,
+        scope: &'scope HandlerScope<'scope, '_>,
+        cb: F,
+    ) -> Result<()>
+    where
+        F: FnMut(&Spatial, SpatialData) -> R + Send + 'scope,
+        R: HandlerResult + 'static,
+    {
+        // SAFETY: The guard is held by the scope, which can't be forgotten,
+        // and which waits for any call to the handler. See `HandlerScope`.
+        let cb = unsafe {
+            mem::transmute::<
+                Box<dyn FnMut(&Spatial, SpatialData) -> R + Send + 'scope>,
+                Box<dyn FnMut(&Spatial, SpatialData) -> R + Send>,
+            >(Box::new(cb))
+        };
+        scope.hold(self.set_on_spatial_data_handler(cb)?);
+        Ok(())
+    }
+
+    /// Removes the spatial data handler, if one is set, and releases its
+    /// closure.
+    pub fn remove_on_spatial_data_handler(&self) -> Result<()> {
+        crate::handler::unregister(self.chan as PhidgetHandle, "spatial_data")
+    }
+
+    /// Gets a channel that receives the samples, fed by the spatial data events.
+    ///
+    /// This registers the channel's spatial data handler, replacing any
+    /// handler that was previously set.
+    /// The handler is removed when the receiver is dropped.
+    pub fn spatial_data_channel(&self) -> Result<EventReceiver<SpatialData>> {
+        let (tx, rx) = mpsc::channel();
+        let guard = self.set_on_spatial_data_handler(move |_, d| {
+            let _ = tx.send(d);
+        })?;
+        Ok(EventReceiver::new(rx, guard))
+    }
+
+    /// Gets a stream of the samples, fed by the spatial data events.
+    ///
+    /// This registers the channel's spatial data handler, replacing any
+    /// handler that was previously set.
+    /// The handler is removed when the stream is dropped.
+    #[cfg(feature = "async")]
+    pub fn spatial_data_stream(&self) -> Result<EventStream<SpatialData>> {
+        let (mut tx, rx) = crate::stream::event_channel();
+        let guard = self.set_on_spatial_data_handler(move |_, d| tx.send(d))?;
+        Ok(EventStream::new(rx, guard))
+    }
+
+    /// Zeroes the gyroscope.
+    /// The channel should be kept still while this is done.
+    pub fn zero_gyro(&mut self) -> Result<()> {
+        ReturnCode::result(unsafe { traced!(ffi::PhidgetSpatial_zeroGyro(self.chan)) })
+    }
+}
+
+impl Phidget for Spatial {
+    fn as_handle(&mut self) -> PhidgetHandle {
+        self.chan as PhidgetHandle
+    }
+}
+
+impl fmt::Debug for Spatial {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        crate::phidget::fmt_debug(f, "Spatial", self.chan as PhidgetHandle)
+    }
+}
+
+impl fmt::Display for Spatial {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        crate::phidget::fmt_display(f, "Spatial", self.chan as PhidgetHandle)
+    }
+}
+
+unsafe impl Send for Spatial {}
+unsafe impl Sync for Spatial {}
+
+impl Default for Spatial {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl From<SpatialHandle> for Spatial {
+    fn from(chan: SpatialHandle) -> Self {
+        Self { chan }
+    }
+}
+
+impl TryFrom<GenericPhidget> for Spatial {
+    type Error = Error;
+
+    /// Converts a generic phidget into a typed channel, if it's of the
+    /// right class. If the channel is open, it's closed when dropped.
+    fn try_from(ph: GenericPhidget) -> Result<Self> {
+        let chan = ph.into_channel(ChannelClass::Spatial)? as SpatialHandle;
+        if !crate::handler::is_set(chan as PhidgetHandle, "spatial_data") {
+            unsafe {
+                traced!(ffi::PhidgetSpatial_setOnSpatialDataHandler(
+                    chan,
+                    Some(Self::on_record),
+                    ptr::null_mut(),
+                ));
+            }
+        }
+        Ok(Self::from(chan))
+    }
+}
+
+impl Drop for Spatial {
+    fn drop(&mut self) {
+        if let Ok(true) = self.is_open() {
+            let _ = self.close();
+        }
+        forget(self.chan);
+        let phid = self.chan as PhidgetHandle;
+        unsafe {
+            ffi::PhidgetSpatial_delete(&mut self.chan);
+        }
+        crate::notify::reset(phid);
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::*;
+    use crate::mock::set_spatial_data;
+
+    #[test]
+    fn test_mock_spatial() {
+        let mut imu = Spatial::new();
+        imu.open().unwrap();
+        assert_eq!(
+            imu.read_all(),
+            Err(Error::UnknownValue(ReturnCode::UnknownVal))
+        );
+
+        let data = SpatialData {
+            acceleration: [0.0, 0.0, 1.0],
+            angular_rate: [0.5, -0.5, 0.0],
+            magnetic_field: [0.2, 0.1, -0.4],
+            timestamp: 1000.0,
+        };
+        set_spatial_data(&mut imu, data).unwrap();
+        assert_eq!(imu.read_all(), Ok(data));
+
+        // Still recorded while a handler is set, and after it's removed
+        let rx = imu.spatial_data_channel().unwrap();
+        let data = SpatialData {
+            timestamp: 1004.0,
+            ..data
+        };
+        set_spatial_data(&mut imu, data).unwrap();
+        assert_eq!(rx.try_recv(), Ok(data));
+        drop(rx);
+
+        let data = SpatialData {
+            timestamp: 1008.0,
+            ..data
+        };
+        set_spatial_data(&mut imu, data).unwrap();
+        assert_eq!(imu.read_all(), Ok(data));
+    }
+}
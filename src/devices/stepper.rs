@@ -12,41 +12,40 @@
 // to those terms.
 //
 
-use crate::{AttachCallback, DetachCallback, Error, GenericPhidget, Phidget, Result, ReturnCode};
+use crate::{
+    devices::DigitalInput, handler::Context, ChannelClass, Error, GenericPhidget, HandlerGuard,
+    HandlerResult, HandlerScope, Phidget, Result, ReturnCode,
+};
 use phidget_sys::{self as ffi, PhidgetHandle, PhidgetStepperHandle as StepperHandle};
 use std::{
-    mem,
+    fmt, mem,
     os::raw::{c_uint, c_void},
-    ptr,
+    ptr, thread,
+    time::{Duration, Instant},
 };
 
 /// The function type for the safe Rust position change callback.
-pub type PositionChangeCallback = dyn Fn(&Stepper, f64) + Send + 'static;
+pub type PositionChangeCallback = dyn FnMut(&Stepper, f64) + Send + 'static;
 /// The function type for the safe Rust velocity change callback.
-pub type VelocityChangeCallback = dyn Fn(&Stepper, f64) + Send + 'static;
+pub type VelocityChangeCallback = dyn FnMut(&Stepper, f64) + Send + 'static;
 /// The function type for the safe Rust stop callback.
-pub type StoppedCallback = dyn Fn(&Stepper) + Send + 'static;
+pub type StoppedCallback = dyn FnMut(&Stepper) + Send + 'static;
 
 /// Phidget Stepper sensor
 pub struct Stepper {
     // Handle to the sensor for the phidget22 library
     chan: StepperHandle,
-    // Double-boxed StepperCallback, if registered
-    cb: Option<*mut c_void>,
-    // Double-boxed attach callback, if registered
-    attach_cb: Option<*mut c_void>,
-    // Double-boxed detach callback, if registered
-    detach_cb: Option<*mut c_void>,
 }
 
 /// ControlMode for stepper
 #[derive(Copy, Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u32)]
 pub enum ControlMode {
     /// Step: Control the motor by setting a target position.
-    Step = 0,
+    Step = ffi::PhidgetStepper_ControlMode_CONTROL_MODE_STEP,
     /// Run: Control the motor by selecting a target velocity (sign indicates direction). The motor will rotate continuously in the chosen direction.
-    Run,
+    Run = ffi::PhidgetStepper_ControlMode_CONTROL_MODE_RUN,
 }
 
 impl TryFrom<u32> for ControlMode {
@@ -55,21 +54,52 @@ impl TryFrom<u32> for ControlMode {
     fn try_from(value: u32) -> Result<Self> {
         use ControlMode::*;
         match value {
-            0 => Ok(Step),
-            1 => Ok(Run),
-            _ => Err(ReturnCode::UnknownVal),
+            ffi::PhidgetStepper_ControlMode_CONTROL_MODE_STEP => Ok(Step),
+            ffi::PhidgetStepper_ControlMode_CONTROL_MODE_RUN => Ok(Run),
+            _ => Err(Error::InvalidArg),
         }
     }
 }
 
+/// The direction in which to move a stepper.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Direction {
+    /// Toward increasing positions
+    Forward,
+    /// Toward decreasing positions
+    Reverse,
+}
+
+impl Direction {
+    /// Gets the sign of a velocity in this direction, as 1.0 or -1.0.
+    pub fn sign(&self) -> f64 {
+        match self {
+            Direction::Forward => 1.0,
+            Direction::Reverse => -1.0,
+        }
+    }
+}
+
+// How often the limit switch is checked while homing
+const HOME_POLL_INTERVAL: Duration = Duration::from_millis(2);
+
 impl Stepper {
-    /// Create a new Stepper sensor.
+    /// Create a new stepper.
+    ///
+    /// This panics if the library can't create the channel. Use
+    /// `try_new()` to get the error instead.
     pub fn new() -> Self {
+        Self::try_new().expect("failed to create the channel")
+    }
+
+    /// Create a new stepper, returning an error if the library can't
+    /// create the channel.
+    pub fn try_new() -> Result<Self> {
         let mut chan: StepperHandle = ptr::null_mut();
-        unsafe {
-            ffi::PhidgetStepper_create(&mut chan);
-        }
-        Self::from(chan)
+        ReturnCode::result(unsafe { ffi::PhidgetStepper_create(&mut chan) })?;
+        crate::phidget::install_handlers(chan as PhidgetHandle);
+        Ok(Self::from(chan))
     }
 
     /// Get a reference to the underlying sensor handle
@@ -80,7 +110,7 @@ impl Stepper {
     /// Set enable failsafe
     pub fn set_enable_failsafe(&mut self, failsafe_time: u32) -> Result<()> {
         ReturnCode::result(unsafe {
-            ffi::PhidgetStepper_enableFailsafe(self.chan, failsafe_time)
+            traced!(ffi::PhidgetStepper_enableFailsafe(self.chan, failsafe_time))
         })?;
         Ok(())
     }
@@ -88,20 +118,23 @@ impl Stepper {
     /// Add position offset
     pub fn add_position_offset(&mut self, position_offset: f64) -> Result<()> {
         ReturnCode::result(unsafe {
-            ffi::PhidgetStepper_addPositionOffset(self.chan, position_offset)
+            traced!(ffi::PhidgetStepper_addPositionOffset(
+                self.chan,
+                position_offset
+            ))
         })?;
         Ok(())
     }
 
     /// Set reset failsafe
     pub fn set_reset_failsafe(&mut self) -> Result<()> {
-        ReturnCode::result(unsafe { ffi::PhidgetStepper_resetFailsafe(self.chan) })?;
+        ReturnCode::result(unsafe { traced!(ffi::PhidgetStepper_resetFailsafe(self.chan)) })?;
         Ok(())
     }
     /// Set acceleration
     pub fn set_acceleration(&mut self, acceleration: f64) -> Result<()> {
         ReturnCode::result(unsafe {
-            ffi::PhidgetStepper_setAcceleration(self.chan, acceleration)
+            traced!(ffi::PhidgetStepper_setAcceleration(self.chan, acceleration))
         })?;
         Ok(())
     }
@@ -109,7 +142,9 @@ impl Stepper {
     /// Get acceleration
     pub fn acceleration(&self) -> Result<f64> {
         let mut value = 0.0;
-        ReturnCode::result(unsafe { ffi::PhidgetStepper_getAcceleration(self.chan, &mut value) })?;
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetStepper_getAcceleration(self.chan, &mut value))
+        })?;
         Ok(value)
     }
 
@@ -117,7 +152,9 @@ impl Stepper {
     pub fn min_acceleration(&self) -> Result<f64> {
         let mut value = 0.0;
         ReturnCode::result(unsafe {
-            ffi::PhidgetStepper_getMinAcceleration(self.chan, &mut value)
+            traced!(ffi::PhidgetStepper_getMinAcceleration(
+                self.chan, &mut value
+            ))
         })?;
         Ok(value)
     }
@@ -126,7 +163,9 @@ impl Stepper {
     pub fn max_acceleration(&self) -> Result<f64> {
         let mut value = 0.0;
         ReturnCode::result(unsafe {
-            ffi::PhidgetStepper_getMaxAcceleration(self.chan, &mut value)
+            traced!(ffi::PhidgetStepper_getMaxAcceleration(
+                self.chan, &mut value
+            ))
         })?;
         Ok(value)
     }
@@ -134,7 +173,10 @@ impl Stepper {
     /// Set control mode
     pub fn set_control_mode(&mut self, control_mode: ControlMode) -> Result<()> {
         ReturnCode::result(unsafe {
-            ffi::PhidgetStepper_setControlMode(self.chan, control_mode as c_uint)
+            traced!(ffi::PhidgetStepper_setControlMode(
+                self.chan,
+                control_mode as c_uint
+            ))
         })?;
         Ok(())
     }
@@ -142,129 +184,183 @@ impl Stepper {
     /// Get control mode
     pub fn control_mode(&self) -> Result<ControlMode> {
         let mut cm: ffi::PhidgetStepper_ControlMode = 0;
-        ReturnCode::result(unsafe { ffi::PhidgetStepper_getControlMode(self.chan, &mut cm) })?;
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetStepper_getControlMode(self.chan, &mut cm))
+        })?;
         ControlMode::try_from(cm)
     }
 
     /// Set current limit
     pub fn set_current_limit(&mut self, current_limit: f64) -> Result<()> {
         ReturnCode::result(unsafe {
-            ffi::PhidgetStepper_setCurrentLimit(self.chan, current_limit)
+            traced!(ffi::PhidgetStepper_setCurrentLimit(
+                self.chan,
+                current_limit
+            ))
         })?;
         Ok(())
     }
     /// Get current limit
     pub fn current_limit(&self) -> Result<f64> {
         let mut value = 0.0;
-        ReturnCode::result(unsafe { ffi::PhidgetStepper_getCurrentLimit(self.chan, &mut value) })?;
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetStepper_getCurrentLimit(self.chan, &mut value))
+        })?;
         Ok(value)
     }
     /// Get minimum current limit
     pub fn min_current_limit(&self) -> Result<f64> {
-        let mut value = 0.0;
-        ReturnCode::result(unsafe {
-            ffi::PhidgetStepper_getMinCurrentLimit(self.chan, &mut value)
-        })?;
-        Ok(value)
+        crate::cache::property(self.chan as PhidgetHandle, "MinCurrentLimit", || {
+            let mut value = 0.0;
+            ReturnCode::result(unsafe {
+                traced!(ffi::PhidgetStepper_getMinCurrentLimit(
+                    self.chan, &mut value
+                ))
+            })?;
+            Ok(value)
+        })
     }
     /// Get maximum current limit
     pub fn max_current_limit(&self) -> Result<f64> {
-        let mut value = 0.0;
-        ReturnCode::result(unsafe {
-            ffi::PhidgetStepper_getMaxCurrentLimit(self.chan, &mut value)
-        })?;
-        Ok(value)
+        crate::cache::property(self.chan as PhidgetHandle, "MaxCurrentLimit", || {
+            let mut value = 0.0;
+            ReturnCode::result(unsafe {
+                traced!(ffi::PhidgetStepper_getMaxCurrentLimit(
+                    self.chan, &mut value
+                ))
+            })?;
+            Ok(value)
+        })
     }
 
     /// Set data interval
-    pub fn set_data_interval(&mut self, data_interval: u32) -> Result<()> {
-        ReturnCode::result(unsafe {
-            ffi::PhidgetStepper_setDataInterval(self.chan, data_interval)
-        })?;
+    ///
+    /// If the stepper is attached, the interval is checked against its
+    /// range first, failing with `Error::IntervalOutOfRange` if it's
+    /// outside. The interval is truncated to whole milliseconds, and one
+    /// too long to be given in a `u32` of them is an `Error::InvalidArg`.
+    pub fn set_data_interval(&mut self, interval: Duration) -> Result<()> {
+        let range = self
+            .min_data_interval()
+            .and_then(|min| Ok(min..=self.max_data_interval()?));
+        crate::phidget::check_data_interval(interval, range)?;
+        let ms = u32::try_from(interval.as_millis()).map_err(|_| Error::InvalidArg)?;
+        ReturnCode::result(unsafe { traced!(ffi::PhidgetStepper_setDataInterval(self.chan, ms)) })?;
         Ok(())
     }
     /// Get data interval
-    pub fn data_interval(&self) -> Result<u32> {
+    pub fn data_interval(&self) -> Result<Duration> {
         let mut value = 0;
-        ReturnCode::result(unsafe { ffi::PhidgetStepper_getDataInterval(self.chan, &mut value) })?;
-        Ok(value)
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetStepper_getDataInterval(self.chan, &mut value))
+        })?;
+        Ok(Duration::from_millis(value as u64))
     }
 
     /// Get minimum data interval
-    pub fn min_data_interval(&self) -> Result<u32> {
-        let mut value = 0;
-        ReturnCode::result(unsafe {
-            ffi::PhidgetStepper_getMinDataInterval(self.chan, &mut value)
-        })?;
-        Ok(value)
+    pub fn min_data_interval(&self) -> Result<Duration> {
+        crate::cache::property(self.chan as PhidgetHandle, "MinDataInterval", || {
+            let mut value = 0;
+            ReturnCode::result(unsafe {
+                traced!(ffi::PhidgetStepper_getMinDataInterval(
+                    self.chan, &mut value
+                ))
+            })?;
+            Ok(Duration::from_millis(value as u64))
+        })
     }
 
     /// Get maximum data interval
-    pub fn max_data_interval(&self) -> Result<u32> {
-        let mut value = 0;
-        ReturnCode::result(unsafe {
-            ffi::PhidgetStepper_getMaxDataInterval(self.chan, &mut value)
-        })?;
-        Ok(value)
+    pub fn max_data_interval(&self) -> Result<Duration> {
+        crate::cache::property(self.chan as PhidgetHandle, "MaxDataInterval", || {
+            let mut value = 0;
+            ReturnCode::result(unsafe {
+                traced!(ffi::PhidgetStepper_getMaxDataInterval(
+                    self.chan, &mut value
+                ))
+            })?;
+            Ok(Duration::from_millis(value as u64))
+        })
     }
 
     /// Set data rate
     pub fn set_data_rate(&mut self, data_rate: f64) -> Result<()> {
-        ReturnCode::result(unsafe { ffi::PhidgetStepper_setDataRate(self.chan, data_rate) })?;
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetStepper_setDataRate(self.chan, data_rate))
+        })?;
         Ok(())
     }
     /// Get data rate
     pub fn data_rate(&self) -> Result<f64> {
         let mut value = 0.0;
-        ReturnCode::result(unsafe { ffi::PhidgetStepper_getDataRate(self.chan, &mut value) })?;
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetStepper_getDataRate(self.chan, &mut value))
+        })?;
         Ok(value)
     }
 
     /// Get minimum data rate
     pub fn min_data_rate(&self) -> Result<f64> {
-        let mut value = 0.0;
-        ReturnCode::result(unsafe { ffi::PhidgetStepper_getMinDataRate(self.chan, &mut value) })?;
-        Ok(value)
+        crate::cache::property(self.chan as PhidgetHandle, "MinDataRate", || {
+            let mut value = 0.0;
+            ReturnCode::result(unsafe {
+                traced!(ffi::PhidgetStepper_getMinDataRate(self.chan, &mut value))
+            })?;
+            Ok(value)
+        })
     }
 
     /// Get maximum data rate
     pub fn max_data_rate(&self) -> Result<f64> {
-        let mut value = 0.0;
-        ReturnCode::result(unsafe { ffi::PhidgetStepper_getMaxDataRate(self.chan, &mut value) })?;
-        Ok(value)
+        crate::cache::property(self.chan as PhidgetHandle, "MaxDataRate", || {
+            let mut value = 0.0;
+            ReturnCode::result(unsafe {
+                traced!(ffi::PhidgetStepper_getMaxDataRate(self.chan, &mut value))
+            })?;
+            Ok(value)
+        })
     }
 
     /// Set engaged
     pub fn set_engaged(&mut self, engaged: bool) -> Result<()> {
         let value = engaged as i32;
-        ReturnCode::result(unsafe { ffi::PhidgetStepper_setEngaged(self.chan, value) })?;
+        ReturnCode::result(unsafe { traced!(ffi::PhidgetStepper_setEngaged(self.chan, value)) })?;
         Ok(())
     }
 
     /// Get engaged
     pub fn engaged(&self) -> Result<bool> {
         let mut value = 0;
-        ReturnCode::result(unsafe { ffi::PhidgetStepper_getEngaged(self.chan, &mut value) })?;
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetStepper_getEngaged(self.chan, &mut value))
+        })?;
         Ok(value != 0)
     }
     /// Get minimum data rate
     pub fn min_failsafe_time(&self) -> Result<f64> {
         let mut value = 0.0;
-        ReturnCode::result(unsafe { ffi::PhidgetStepper_getMinDataRate(self.chan, &mut value) })?;
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetStepper_getMinDataRate(self.chan, &mut value))
+        })?;
         Ok(value)
     }
 
     /// Get maximum data rate
     pub fn max_failsafe_time(&self) -> Result<f64> {
         let mut value = 0.0;
-        ReturnCode::result(unsafe { ffi::PhidgetStepper_getMaxDataRate(self.chan, &mut value) })?;
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetStepper_getMaxDataRate(self.chan, &mut value))
+        })?;
         Ok(value)
     }
 
     /// Set holding current limit
     pub fn set_holding_current_limit(&mut self, holding_current_limit: f64) -> Result<()> {
         ReturnCode::result(unsafe {
-            ffi::PhidgetStepper_setHoldingCurrentLimit(self.chan, holding_current_limit)
+            traced!(ffi::PhidgetStepper_setHoldingCurrentLimit(
+                self.chan,
+                holding_current_limit
+            ))
         })?;
         Ok(())
     }
@@ -273,7 +369,9 @@ impl Stepper {
     pub fn holding_current_limit(&self) -> Result<f64> {
         let mut value = 0.0;
         ReturnCode::result(unsafe {
-            ffi::PhidgetStepper_getHoldingCurrentLimit(self.chan, &mut value)
+            traced!(ffi::PhidgetStepper_getHoldingCurrentLimit(
+                self.chan, &mut value
+            ))
         })?;
         Ok(value)
     }
@@ -281,35 +379,46 @@ impl Stepper {
     /// Get is moving
     pub fn is_moving(&self) -> Result<bool> {
         let mut value = 0;
-        ReturnCode::result(unsafe { ffi::PhidgetStepper_getIsMoving(self.chan, &mut value) })?;
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetStepper_getIsMoving(self.chan, &mut value))
+        })?;
         Ok(value != 0)
     }
 
     /// Get position
     pub fn position(&self) -> Result<f64> {
         let mut value = 0.0;
-        ReturnCode::result(unsafe { ffi::PhidgetStepper_getPosition(self.chan, &mut value) })?;
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetStepper_getPosition(self.chan, &mut value))
+        })?;
         Ok(value)
     }
 
     /// Get minimum position
     pub fn min_position(&self) -> Result<f64> {
         let mut value = 0.0;
-        ReturnCode::result(unsafe { ffi::PhidgetStepper_getMinPosition(self.chan, &mut value) })?;
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetStepper_getMinPosition(self.chan, &mut value))
+        })?;
         Ok(value)
     }
 
     /// Get maximum position
     pub fn max_position(&self) -> Result<f64> {
         let mut value = 0.0;
-        ReturnCode::result(unsafe { ffi::PhidgetStepper_getMaxPosition(self.chan, &mut value) })?;
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetStepper_getMaxPosition(self.chan, &mut value))
+        })?;
         Ok(value)
     }
 
     /// Set rescale factor
     pub fn set_rescale_factor(&mut self, rescale_factor: f64) -> Result<()> {
         ReturnCode::result(unsafe {
-            ffi::PhidgetStepper_setRescaleFactor(self.chan, rescale_factor)
+            traced!(ffi::PhidgetStepper_setRescaleFactor(
+                self.chan,
+                rescale_factor
+            ))
         })?;
         Ok(())
     }
@@ -317,13 +426,17 @@ impl Stepper {
     /// Get rescale factor
     pub fn rescale_factor(&self) -> Result<f64> {
         let mut value = 0.0;
-        ReturnCode::result(unsafe { ffi::PhidgetStepper_getRescaleFactor(self.chan, &mut value) })?;
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetStepper_getRescaleFactor(self.chan, &mut value))
+        })?;
         Ok(value)
     }
 
     /// Set target position
     pub fn set_target_position(&mut self, stepper: f64) -> Result<()> {
-        ReturnCode::result(unsafe { ffi::PhidgetStepper_setTargetPosition(self.chan, stepper) })?;
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetStepper_setTargetPosition(self.chan, stepper))
+        })?;
         Ok(())
     }
 
@@ -337,7 +450,7 @@ impl Stepper {
     pub fn target_position(&self) -> Result<f64> {
         let mut value = 0.0;
         ReturnCode::result(unsafe {
-            ffi::PhidgetStepper_getTargetPosition(self.chan, &mut value)
+            traced!(ffi::PhidgetStepper_getTargetPosition(self.chan, &mut value))
         })?;
         Ok(value)
     }
@@ -345,7 +458,10 @@ impl Stepper {
     /// Set velocity limit
     pub fn set_velocity_limit(&mut self, velocity_limit: f64) -> Result<()> {
         ReturnCode::result(unsafe {
-            ffi::PhidgetStepper_setVelocityLimit(self.chan, velocity_limit)
+            traced!(ffi::PhidgetStepper_setVelocityLimit(
+                self.chan,
+                velocity_limit
+            ))
         })?;
         Ok(())
     }
@@ -353,7 +469,9 @@ impl Stepper {
     /// Get rescale factor
     pub fn velocity_limit(&self) -> Result<f64> {
         let mut value = 0.0;
-        ReturnCode::result(unsafe { ffi::PhidgetStepper_getVelocityLimit(self.chan, &mut value) })?;
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetStepper_getVelocityLimit(self.chan, &mut value))
+        })?;
         Ok(value)
     }
 
@@ -361,7 +479,9 @@ impl Stepper {
     pub fn min_velocity_limit(&self) -> Result<f64> {
         let mut value = 0.0;
         ReturnCode::result(unsafe {
-            ffi::PhidgetStepper_getMinVelocityLimit(self.chan, &mut value)
+            traced!(ffi::PhidgetStepper_getMinVelocityLimit(
+                self.chan, &mut value
+            ))
         })?;
         Ok(value)
     }
@@ -370,115 +490,321 @@ impl Stepper {
     pub fn max_velocity_limit(&self) -> Result<f64> {
         let mut value = 0.0;
         ReturnCode::result(unsafe {
-            ffi::PhidgetStepper_getMaxVelocityLimit(self.chan, &mut value)
+            traced!(ffi::PhidgetStepper_getMaxVelocityLimit(
+                self.chan, &mut value
+            ))
         })?;
         Ok(value)
     }
 
-    // Low-level, unsafe, callback for position change events.
-    // The context is a double-boxed pointer the safe Rust callback.
-    unsafe extern "C" fn on_position_change(chan: StepperHandle, ctx: *mut c_void, stepper: f64) {
-        if !ctx.is_null() {
-            let cb: &mut Box<PositionChangeCallback> = &mut *(ctx as *mut _);
-            let sensor = Self::from(chan);
-            cb(&sensor, stepper);
-            mem::forget(sensor);
+    /// Moves the stepper to its home position, at a limit switch.
+    ///
+    /// This drives the motor in the direction, at the speed, until the
+    /// limit switch is active, then stops, sets the position there to zero,
+    /// and backs off slowly until the switch releases. The switch is
+    /// expected to read `true` when it's pressed. The motor is engaged and
+    /// left in its previous control mode, holding its final position.
+    ///
+    /// This blocks until homing is done, which might be forever if the
+    /// switch is never reached. See `home_timeout()` to limit the time.
+    pub fn home(&mut self, limit: &DigitalInput, direction: Direction, speed: f64) -> Result<()> {
+        self.home_timeout(limit, direction, speed, Duration::MAX)
+    }
+
+    /// Moves the stepper to its home position, at a limit switch, giving
+    /// up after a timeout.
+    ///
+    /// This is the same as `home()`, but if homing isn't done within the
+    /// timeout, the motor is stopped and this returns `Error::Timeout`.
+    pub fn home_timeout(
+        &mut self,
+        limit: &DigitalInput,
+        direction: Direction,
+        speed: f64,
+        to: Duration,
+    ) -> Result<()> {
+        let deadline = Instant::now().checked_add(to);
+        let mode = self.control_mode()?;
+        let speed = speed.abs();
+
+        self.set_control_mode(ControlMode::Run)?;
+        self.set_engaged(true)?;
+
+        let res = self.home_run(limit, direction.sign() * speed, true, deadline);
+        let stopped = self.stop_wait(deadline);
+        res.and(stopped)?;
+
+        self.add_position_offset(-self.position()?)?;
+
+        // Back off slowly until the switch releases
+        let res = self.home_run(limit, -direction.sign() * speed / 4.0, false, deadline);
+        let stopped = self.stop_wait(deadline);
+        res.and(stopped)?;
+
+        if mode != ControlMode::Run {
+            self.set_target_position(self.position()?)?;
+            self.set_control_mode(mode)?;
+        }
+        Ok(())
+    }
+
+    // Runs the motor at the velocity until the limit switch is in the
+    // state. If it already is, the motor isn't started.
+    fn home_run(
+        &mut self,
+        limit: &DigitalInput,
+        velocity: f64,
+        until: bool,
+        deadline: Option<Instant>,
+    ) -> Result<()> {
+        if limit.state()? == until {
+            return Ok(());
+        }
+        self.set_velocity_limit(velocity)?;
+        while limit.state()? != until {
+            if deadline.is_some_and(|d| Instant::now() >= d) {
+                return Err(Error::Timeout);
+            }
+            thread::sleep(HOME_POLL_INTERVAL);
         }
+        Ok(())
+    }
+
+    // Stops the motor, in run mode, and waits for it to stop moving.
+    fn stop_wait(&mut self, deadline: Option<Instant>) -> Result<()> {
+        self.set_velocity_limit(0.0)?;
+        while self.is_moving()? {
+            if deadline.is_some_and(|d| Instant::now() >= d) {
+                return Err(Error::Timeout);
+            }
+            thread::sleep(HOME_POLL_INTERVAL);
+        }
+        Ok(())
+    }
+
+    // Low-level, unsafe, callback for position change events.
+    // The context holds a view of the channel and the safe Rust callback.
+    unsafe extern "C" fn on_position_change<F, R>(
+        chan: StepperHandle,
+        ctx: *mut c_void,
+        stepper: f64,
+    ) where
+        F: FnMut(&Stepper, f64) -> R + Send + 'static,
+        R: HandlerResult,
+    {
+        crate::handler::deliver(chan as PhidgetHandle, ctx, move |phid, ctx| {
+            let ctx: &mut Context<Self, F> = &mut *(ctx as *mut _);
+            crate::handler::report(phid, (ctx.cb)(&ctx.chan, stepper));
+        });
     }
 
     /// Set a handler to receive position change callbacks.
-    pub fn set_on_position_change_handler<F>(&mut self, cb: F) -> Result<()>
+    /// The handler is removed when the returned guard is dropped.
+    pub fn set_on_position_change_handler<F, R>(&self, cb: F) -> Result<HandlerGuard>
     where
-        F: Fn(&Stepper, f64) + Send + 'static,
+        F: FnMut(&Stepper, f64) -> R + Send + 'static,
+        R: HandlerResult,
     {
-        // 1st box is fat ptr, 2nd is regular pointer.
-        let cb: Box<Box<PositionChangeCallback>> = Box::new(Box::new(cb));
-        let ctx = Box::into_raw(cb) as *mut c_void;
-        self.cb = Some(ctx);
+        let ctx = Context::into_raw(Self::from(self.chan), cb);
+
+        crate::handler::register::<Context<Self, F>, _>(
+            self.chan as PhidgetHandle,
+            "position_change",
+            ctx,
+            || unsafe {
+                traced!(ffi::PhidgetStepper_setOnPositionChangeHandler(
+                    self.chan,
+                    Some(Self::on_position_change::<F, R>),
+                    ctx,
+                ))
+            },
+            |phid| unsafe {
+                traced!(ffi::PhidgetStepper_setOnPositionChangeHandler(
+                    phid as _,
+                    None,
+                    ptr::null_mut()
+                ))
+            },
+        )
+    }
+
+    /// Set a handler to receive position change callbacks, which can borrow
+    /// from the environment of the scope.
+    /// The handler is removed at the end of the scope.
+    pub fn set_on_position_change_handler_scoped<'scope, F, R>(
+        &self,
+        scope: &'scope HandlerScope<'scope, '_>,
+        cb: F,
+    ) -> Result<()>
+    where
+        F: FnMut(&Stepper, f64) -> R + Send + 'scope,
+        R: HandlerResult + 'static,
+    {
+        // SAFETY: The guard is held by the scope, which can't be forgotten,
+        // and which waits for any call to the handler. See `HandlerScope`.
+        let cb = unsafe {
+            mem::transmute::<
+                Box<dyn FnMut(&Stepper, f64) -> R + Send + 'scope>,
+                Box<dyn FnMut(&Stepper, f64) -> R + Send>,
+            >(Box::new(cb))
+        };
+        scope.hold(self.set_on_position_change_handler(cb)?);
+        Ok(())
+    }
 
-        ReturnCode::result(unsafe {
-            ffi::PhidgetStepper_setOnPositionChangeHandler(
-                self.chan,
-                Some(Self::on_position_change),
-                ctx,
-            )
-        })
+    /// Removes the position change handler, if one is set, and releases
+    /// its closure.
+    pub fn remove_on_position_change_handler(&self) -> Result<()> {
+        crate::handler::unregister(self.chan as PhidgetHandle, "position_change")
     }
 
     // Low-level, unsafe, callback for stop events.
-    // The context is a double-boxed pointer the safe Rust callback.
-    unsafe extern "C" fn on_stopped(chan: StepperHandle, ctx: *mut c_void) {
-        if !ctx.is_null() {
-            let cb: &mut Box<StoppedCallback> = &mut *(ctx as *mut _);
-            let sensor = Self::from(chan);
-            cb(&sensor);
-            mem::forget(sensor);
-        }
+    // The context holds a view of the channel and the safe Rust callback.
+    unsafe extern "C" fn on_stopped<F, R>(chan: StepperHandle, ctx: *mut c_void)
+    where
+        F: FnMut(&Stepper) -> R + Send + 'static,
+        R: HandlerResult,
+    {
+        crate::handler::deliver(chan as PhidgetHandle, ctx, move |phid, ctx| {
+            let ctx: &mut Context<Self, F> = &mut *(ctx as *mut _);
+            crate::handler::report(phid, (ctx.cb)(&ctx.chan));
+        });
     }
 
     /// Set a handler to receive stop callbacks.
-    pub fn set_on_stopped_handler<F>(&mut self, cb: F) -> Result<()>
+    /// The handler is removed when the returned guard is dropped.
+    pub fn set_on_stopped_handler<F, R>(&self, cb: F) -> Result<HandlerGuard>
     where
-        F: Fn(&Stepper) + Send + 'static,
+        F: FnMut(&Stepper) -> R + Send + 'static,
+        R: HandlerResult,
     {
-        // 1st box is fat ptr, 2nd is regular pointer.
-        let cb: Box<Box<StoppedCallback>> = Box::new(Box::new(cb));
-        let ctx = Box::into_raw(cb) as *mut c_void;
-        self.cb = Some(ctx);
+        let ctx = Context::into_raw(Self::from(self.chan), cb);
+
+        crate::handler::register::<Context<Self, F>, _>(
+            self.chan as PhidgetHandle,
+            "stopped",
+            ctx,
+            || unsafe {
+                traced!(ffi::PhidgetStepper_setOnStoppedHandler(
+                    self.chan,
+                    Some(Self::on_stopped::<F, R>),
+                    ctx
+                ))
+            },
+            |phid| unsafe {
+                traced!(ffi::PhidgetStepper_setOnStoppedHandler(
+                    phid as _,
+                    None,
+                    ptr::null_mut()
+                ))
+            },
+        )
+    }
+
+    /// Set a handler to receive stopped callbacks, which can borrow
+    /// from the environment of the scope.
+    /// The handler is removed at the end of the scope.
+    pub fn set_on_stopped_handler_scoped<'scope, F, R>(
+        &self,
+        scope: &'scope HandlerScope<'scope, '_>,
+        cb: F,
+    ) -> Result<()>
+    where
+        F: FnMut(&Stepper) -> R + Send + 'scope,
+        R: HandlerResult + 'static,
+    {
+        // SAFETY: The guard is held by the scope, which can't be forgotten,
+        // and which waits for any call to the handler. See `HandlerScope`.
+        let cb = unsafe {
+            mem::transmute::<
+                Box<dyn FnMut(&Stepper) -> R + Send + 'scope>,
+                Box<dyn FnMut(&Stepper) -> R + Send>,
+            >(Box::new(cb))
+        };
+        scope.hold(self.set_on_stopped_handler(cb)?);
+        Ok(())
+    }
 
-        ReturnCode::result(unsafe {
-            ffi::PhidgetStepper_setOnStoppedHandler(self.chan, Some(Self::on_stopped), ctx)
-        })
+    /// Removes the stopped handler, if one is set, and releases
+    /// its closure.
+    pub fn remove_on_stopped_handler(&self) -> Result<()> {
+        crate::handler::unregister(self.chan as PhidgetHandle, "stopped")
     }
 
     // Low-level, unsafe, callback for velocity change events.
-    // The context is a double-boxed pointer the safe Rust callback.
-    unsafe extern "C" fn on_velocity_change(chan: StepperHandle, ctx: *mut c_void, stepper: f64) {
-        if !ctx.is_null() {
-            let cb: &mut Box<VelocityChangeCallback> = &mut *(ctx as *mut _);
-            let sensor = Self::from(chan);
-            cb(&sensor, stepper);
-            mem::forget(sensor);
-        }
+    // The context holds a view of the channel and the safe Rust callback.
+    unsafe extern "C" fn on_velocity_change<F, R>(
+        chan: StepperHandle,
+        ctx: *mut c_void,
+        stepper: f64,
+    ) where
+        F: FnMut(&Stepper, f64) -> R + Send + 'static,
+        R: HandlerResult,
+    {
+        crate::handler::deliver(chan as PhidgetHandle, ctx, move |phid, ctx| {
+            let ctx: &mut Context<Self, F> = &mut *(ctx as *mut _);
+            crate::handler::report(phid, (ctx.cb)(&ctx.chan, stepper));
+        });
     }
 
     /// Set a handler to receive stepper change callbacks.
-    pub fn set_on_velocity_change_handler<F>(&mut self, cb: F) -> Result<()>
+    /// The handler is removed when the returned guard is dropped.
+    pub fn set_on_velocity_change_handler<F, R>(&self, cb: F) -> Result<HandlerGuard>
     where
-        F: Fn(&Stepper, f64) + Send + 'static,
+        F: FnMut(&Stepper, f64) -> R + Send + 'static,
+        R: HandlerResult,
     {
-        // 1st box is fat ptr, 2nd is regular pointer.
-        let cb: Box<Box<VelocityChangeCallback>> = Box::new(Box::new(cb));
-        let ctx = Box::into_raw(cb) as *mut c_void;
-        self.cb = Some(ctx);
-
-        ReturnCode::result(unsafe {
-            ffi::PhidgetStepper_setOnVelocityChangeHandler(
-                self.chan,
-                Some(Self::on_velocity_change),
-                ctx,
-            )
-        })
-    }
-
-    /// Sets a handler to receive attach callbacks
-    pub fn set_on_attach_handler<F>(&mut self, cb: F) -> Result<()>
+        let ctx = Context::into_raw(Self::from(self.chan), cb);
+
+        crate::handler::register::<Context<Self, F>, _>(
+            self.chan as PhidgetHandle,
+            "velocity_change",
+            ctx,
+            || unsafe {
+                traced!(ffi::PhidgetStepper_setOnVelocityChangeHandler(
+                    self.chan,
+                    Some(Self::on_velocity_change::<F, R>),
+                    ctx,
+                ))
+            },
+            |phid| unsafe {
+                traced!(ffi::PhidgetStepper_setOnVelocityChangeHandler(
+                    phid as _,
+                    None,
+                    ptr::null_mut()
+                ))
+            },
+        )
+    }
+
+    /// Set a handler to receive velocity change callbacks, which can borrow
+    /// from the environment of the scope.
+    /// The handler is removed at the end of the scope.
+    pub fn set_on_velocity_change_handler_scoped<'scope, F, R>(
+        &self,
+        scope: &'scope HandlerScope<'scope, '_>,
+        cb: F,
+    ) -> Result<()>
     where
-        F: Fn(&GenericPhidget) + Send + 'static,
+        F: FnMut(&Stepper, f64) -> R + Send + 'scope,
+        R: HandlerResult + 'static,
     {
-        let ctx = crate::phidget::set_on_attach_handler(self, cb)?;
-        self.attach_cb = Some(ctx);
+        // SAFETY: The guard is held by the scope, which can't be forgotten,
+        // and which waits for any call to the handler. See `HandlerScope`.
+        let cb = unsafe {
+            mem::transmute::<
+                Box<dyn FnMut(&Stepper, f64) -> R + Send + 'scope>,
+                Box<dyn FnMut(&Stepper, f64) -> R + Send>,
+            >(Box::new(cb))
+        };
+        scope.hold(self.set_on_velocity_change_handler(cb)?);
         Ok(())
     }
 
-    /// Sets a handler to receive detach callbacks
-    pub fn set_on_detach_handler<F>(&mut self, cb: F) -> Result<()>
-    where
-        F: Fn(&GenericPhidget) + Send + 'static,
-    {
-        let ctx = crate::phidget::set_on_detach_handler(self, cb)?;
-        self.detach_cb = Some(ctx);
-        Ok(())
+    /// Removes the velocity change handler, if one is set, and releases
+    /// its closure.
+    pub fn remove_on_velocity_change_handler(&self) -> Result<()> {
+        crate::handler::unregister(self.chan as PhidgetHandle, "velocity_change")
     }
 }
 
@@ -488,7 +814,20 @@ impl Phidget for Stepper {
     }
 }
 
+impl fmt::Debug for Stepper {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        crate::phidget::fmt_debug(f, "Stepper", self.chan as PhidgetHandle)
+    }
+}
+
+impl fmt::Display for Stepper {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        crate::phidget::fmt_display(f, "Stepper", self.chan as PhidgetHandle)
+    }
+}
+
 unsafe impl Send for Stepper {}
+unsafe impl Sync for Stepper {}
 
 impl Default for Stepper {
     fn default() -> Self {
@@ -498,12 +837,18 @@ impl Default for Stepper {
 
 impl From<StepperHandle> for Stepper {
     fn from(chan: StepperHandle) -> Self {
-        Self {
-            chan,
-            cb: None,
-            attach_cb: None,
-            detach_cb: None,
-        }
+        Self { chan }
+    }
+}
+
+impl TryFrom<GenericPhidget> for Stepper {
+    type Error = Error;
+
+    /// Converts a generic phidget into a typed channel, if it's of the
+    /// right class. If the channel is open, it's closed when dropped.
+    fn try_from(ph: GenericPhidget) -> Result<Self> {
+        let chan = ph.into_channel(ChannelClass::Stepper)?;
+        Ok(Self::from(chan as StepperHandle))
     }
 }
 
@@ -512,13 +857,50 @@ impl Drop for Stepper {
         if let Ok(true) = self.is_open() {
             let _ = self.close();
         }
+        let phid = self.chan as PhidgetHandle;
         unsafe {
             ffi::PhidgetStepper_delete(&mut self.chan);
-            crate::drop_cb::<PositionChangeCallback>(self.cb.take());
-            crate::drop_cb::<VelocityChangeCallback>(self.cb.take());
-            crate::drop_cb::<StoppedCallback>(self.cb.take());
-            crate::drop_cb::<AttachCallback>(self.attach_cb.take());
-            crate::drop_cb::<DetachCallback>(self.detach_cb.take());
         }
+        crate::notify::reset(phid);
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::*;
+    use crate::mock::{set_property, set_value};
+
+    #[test]
+    fn test_mock_stepper_home() {
+        use std::mem::ManuallyDrop;
+
+        let mut stepper = Stepper::new();
+        let mut limit = DigitalInput::new();
+        stepper.open().unwrap();
+        limit.open().unwrap();
+        set_value(&mut limit, 0.0).unwrap();
+        set_property(&mut stepper, "Position", 1500.0).unwrap();
+        set_property(&mut stepper, "IsMoving", 0.0).unwrap();
+        stepper.set_control_mode(ControlMode::Step).unwrap();
+
+        // A view of the switch, to press and release it while homing
+        let mut switch = ManuallyDrop::new(DigitalInput::from(*limit.as_channel()));
+        thread::scope(|s| {
+            s.spawn(move || {
+                thread::sleep(Duration::from_millis(20));
+                set_value(&mut *switch, 1.0).unwrap();
+                thread::sleep(Duration::from_millis(20));
+                set_value(&mut *switch, 0.0).unwrap();
+            });
+            stepper
+                .home_timeout(&limit, Direction::Reverse, 500.0, Duration::from_secs(5))
+                .unwrap();
+        });
+
+        assert_eq!(stepper.position(), Ok(0.0));
+        assert_eq!(stepper.control_mode(), Ok(ControlMode::Step));
+        assert_eq!(stepper.engaged(), Ok(true));
     }
 }
@@ -10,50 +10,170 @@
 // to those terms.
 //
 
-use crate::{AttachCallback, DetachCallback, GenericPhidget, Phidget, Result, ReturnCode};
+use crate::{
+    handler::Context, ChannelClass, Error, EventReceiver, GenericPhidget, HandlerGuard,
+    HandlerResult, HandlerScope, Phidget, Result, ReturnCode, Sensor,
+};
 use phidget_sys::{
     self as ffi, PhidgetHandle, PhidgetTemperatureSensorHandle as TemperatureSensorHandle,
 };
-use std::{mem, os::raw::c_void, ptr};
+use std::{
+    fmt, mem,
+    os::raw::{c_uint, c_void},
+    ptr,
+    sync::mpsc,
+};
+
+#[cfg(feature = "async")]
+use crate::stream::EventStream;
+
+#[cfg(feature = "units")]
+use uom::si::{f64::ThermodynamicTemperature, thermodynamic_temperature::degree_celsius};
 
 /// The function type for the safe Rust temperature change callback.
-pub type TemperatureCallback = dyn Fn(&TemperatureSensor, f64) + Send + 'static;
+pub type TemperatureCallback = dyn FnMut(&TemperatureSensor, f64) + Send + 'static;
+
+/// The type of thermocouple connected to a temperature sensor
+#[derive(Copy, Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(u32)]
+pub enum ThermocoupleType {
+    /// A type J thermocouple
+    J = ffi::PhidgetTemperatureSensor_ThermocoupleType_THERMOCOUPLE_TYPE_J,
+    /// A type K thermocouple
+    K = ffi::PhidgetTemperatureSensor_ThermocoupleType_THERMOCOUPLE_TYPE_K,
+    /// A type E thermocouple
+    E = ffi::PhidgetTemperatureSensor_ThermocoupleType_THERMOCOUPLE_TYPE_E,
+    /// A type T thermocouple
+    T = ffi::PhidgetTemperatureSensor_ThermocoupleType_THERMOCOUPLE_TYPE_T,
+}
+
+impl TryFrom<u32> for ThermocoupleType {
+    type Error = Error;
+
+    fn try_from(value: u32) -> Result<Self> {
+        use ThermocoupleType::*;
+        match value {
+            ffi::PhidgetTemperatureSensor_ThermocoupleType_THERMOCOUPLE_TYPE_J => Ok(J),
+            ffi::PhidgetTemperatureSensor_ThermocoupleType_THERMOCOUPLE_TYPE_K => Ok(K),
+            ffi::PhidgetTemperatureSensor_ThermocoupleType_THERMOCOUPLE_TYPE_E => Ok(E),
+            ffi::PhidgetTemperatureSensor_ThermocoupleType_THERMOCOUPLE_TYPE_T => Ok(T),
+            _ => Err(Error::InvalidArg),
+        }
+    }
+}
+
+/// The type of RTD connected to a temperature sensor
+#[derive(Copy, Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(u32)]
+pub enum RtdType {
+    /// A PT100 RTD, with an alpha of 0.00385
+    Pt100_3850 = ffi::PhidgetTemperatureSensor_RTDType_RTD_TYPE_PT100_3850,
+    /// A PT1000 RTD, with an alpha of 0.00385
+    Pt1000_3850 = ffi::PhidgetTemperatureSensor_RTDType_RTD_TYPE_PT1000_3850,
+    /// A PT100 RTD, with an alpha of 0.00392
+    Pt100_3920 = ffi::PhidgetTemperatureSensor_RTDType_RTD_TYPE_PT100_3920,
+    /// A PT1000 RTD, with an alpha of 0.00392
+    Pt1000_3920 = ffi::PhidgetTemperatureSensor_RTDType_RTD_TYPE_PT1000_3920,
+}
+
+impl TryFrom<u32> for RtdType {
+    type Error = Error;
+
+    fn try_from(value: u32) -> Result<Self> {
+        use RtdType::*;
+        match value {
+            ffi::PhidgetTemperatureSensor_RTDType_RTD_TYPE_PT100_3850 => Ok(Pt100_3850),
+            ffi::PhidgetTemperatureSensor_RTDType_RTD_TYPE_PT1000_3850 => Ok(Pt1000_3850),
+            ffi::PhidgetTemperatureSensor_RTDType_RTD_TYPE_PT100_3920 => Ok(Pt100_3920),
+            ffi::PhidgetTemperatureSensor_RTDType_RTD_TYPE_PT1000_3920 => Ok(Pt1000_3920),
+            _ => Err(Error::InvalidArg),
+        }
+    }
+}
+
+/// The number of wires that connect an RTD to a temperature sensor
+#[derive(Copy, Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(u32)]
+pub enum RtdWireSetup {
+    /// A 2-wire RTD
+    TwoWire = ffi::Phidget_RTDWireSetup_RTD_WIRE_SETUP_2WIRE,
+    /// A 3-wire RTD
+    ThreeWire = ffi::Phidget_RTDWireSetup_RTD_WIRE_SETUP_3WIRE,
+    /// A 4-wire RTD
+    FourWire = ffi::Phidget_RTDWireSetup_RTD_WIRE_SETUP_4WIRE,
+}
+
+impl TryFrom<u32> for RtdWireSetup {
+    type Error = Error;
+
+    fn try_from(value: u32) -> Result<Self> {
+        use RtdWireSetup::*;
+        match value {
+            ffi::Phidget_RTDWireSetup_RTD_WIRE_SETUP_2WIRE => Ok(TwoWire),
+            ffi::Phidget_RTDWireSetup_RTD_WIRE_SETUP_3WIRE => Ok(ThreeWire),
+            ffi::Phidget_RTDWireSetup_RTD_WIRE_SETUP_4WIRE => Ok(FourWire),
+            _ => Err(Error::InvalidArg),
+        }
+    }
+}
 
 /// Phidget temperature sensor
 pub struct TemperatureSensor {
     // Handle to the sensor for the phidget22 library
     chan: TemperatureSensorHandle,
-    // Double-boxed TemperatureCallback, if registered
-    cb: Option<*mut c_void>,
-    // Double-boxed attach callback, if registered
-    attach_cb: Option<*mut c_void>,
-    // Double-boxed detach callback, if registered
-    detach_cb: Option<*mut c_void>,
 }
 
 impl TemperatureSensor {
     /// Create a new temperature sensor.
+    ///
+    /// This panics if the library can't create the channel. Use
+    /// `try_new()` to get the error instead.
     pub fn new() -> Self {
+        Self::try_new().expect("failed to create the channel")
+    }
+
+    /// Create a new temperature sensor, returning an error if the library can't
+    /// create the channel.
+    pub fn try_new() -> Result<Self> {
         let mut chan: TemperatureSensorHandle = ptr::null_mut();
-        unsafe {
-            ffi::PhidgetTemperatureSensor_create(&mut chan);
-        }
-        Self::from(chan)
+        ReturnCode::result(unsafe { ffi::PhidgetTemperatureSensor_create(&mut chan) })?;
+        crate::phidget::install_handlers(chan as PhidgetHandle);
+        Ok(Self::from(chan))
+    }
+
+    /// Create a new temperature sensor for a VINT device plugged into a
+    /// port of a hub.
+    ///
+    /// This selects the hub by its serial number and the port by its
+    /// number. Unlike the simple input and output channels, a temperature
+    /// sensor can't use the hub port directly, so this selects the device
+    /// on the port, rather than hub port mode. The channel still needs to
+    /// be opened.
+    pub fn on_hub_port(serial: i32, port: i32) -> Result<Self> {
+        let mut ch = Self::try_new()?;
+        ch.set_is_hub_port_device(false)?;
+        ch.set_hub_port(port)?;
+        ch.set_serial_number(serial)?;
+        Ok(ch)
     }
 
     // Low-level, unsafe, callback for temperature change events.
-    // The context is a double-boxed pointer the the safe Rust callback.
-    unsafe extern "C" fn on_temperature_change(
+    // The context holds a view of the channel and the safe Rust callback.
+    unsafe extern "C" fn on_temperature_change<F, R>(
         chan: TemperatureSensorHandle,
         ctx: *mut c_void,
         temperature: f64,
-    ) {
-        if !ctx.is_null() {
-            let cb: &mut Box<TemperatureCallback> = &mut *(ctx as *mut _);
-            let sensor = Self::from(chan);
-            cb(&sensor, temperature);
-            mem::forget(sensor);
-        }
+    ) where
+        F: FnMut(&TemperatureSensor, f64) -> R + Send + 'static,
+        R: HandlerResult,
+    {
+        crate::handler::deliver(chan as PhidgetHandle, ctx, move |phid, ctx| {
+            let ctx: &mut Context<Self, F> = &mut *(ctx as *mut _);
+            crate::handler::report(phid, (ctx.cb)(&ctx.chan, temperature));
+        });
     }
 
     /// Get a reference to the underlying sensor handle
@@ -65,49 +185,210 @@ impl TemperatureSensor {
     pub fn temperature(&self) -> Result<f64> {
         let mut temperature = 0.0;
         ReturnCode::result(unsafe {
-            ffi::PhidgetTemperatureSensor_getTemperature(self.chan, &mut temperature)
+            traced!(ffi::PhidgetTemperatureSensor_getTemperature(
+                self.chan,
+                &mut temperature
+            ))
         })?;
         Ok(temperature)
     }
 
-    /// Set a handler to receive temperature change callbacks.
-    pub fn set_on_temperature_change_handler<F>(&mut self, cb: F) -> Result<()>
-    where
-        F: Fn(&TemperatureSensor, f64) + Send + 'static,
-    {
-        // 1st box is fat ptr, 2nd is regular pointer.
-        let cb: Box<Box<TemperatureCallback>> = Box::new(Box::new(cb));
-        let ctx = Box::into_raw(cb) as *mut c_void;
-        self.cb = Some(ctx);
+    /// Read the current temperature as a typed quantity.
+    #[cfg(feature = "units")]
+    pub fn temperature_quantity(&self) -> Result<ThermodynamicTemperature> {
+        Ok(ThermodynamicTemperature::new::<degree_celsius>(
+            self.temperature()?,
+        ))
+    }
+
+    /// Gets the minimum change in temperature, in degrees Celsius, that
+    /// triggers a change event.
+    pub fn temperature_change_trigger(&self) -> Result<f64> {
+        let mut trigger = 0.0;
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetTemperatureSensor_getTemperatureChangeTrigger(
+                self.chan,
+                &mut trigger
+            ))
+        })?;
+        Ok(trigger)
+    }
+
+    /// Sets the minimum change in temperature, in degrees Celsius, that
+    /// triggers a change event. Zero reports every reading.
+    pub fn set_temperature_change_trigger(&mut self, trigger: f64) -> Result<()> {
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetTemperatureSensor_setTemperatureChangeTrigger(
+                self.chan, trigger
+            ))
+        })
+    }
+
+    /// Gets the type of thermocouple connected to the channel.
+    pub fn thermocouple_type(&self) -> Result<ThermocoupleType> {
+        let mut tc: ffi::PhidgetTemperatureSensor_ThermocoupleType = 0;
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetTemperatureSensor_getThermocoupleType(
+                self.chan, &mut tc
+            ))
+        })?;
+        ThermocoupleType::try_from(tc)
+    }
 
+    /// Sets the type of thermocouple connected to the channel.
+    pub fn set_thermocouple_type(&mut self, tc: ThermocoupleType) -> Result<()> {
         ReturnCode::result(unsafe {
-            ffi::PhidgetTemperatureSensor_setOnTemperatureChangeHandler(
+            traced!(ffi::PhidgetTemperatureSensor_setThermocoupleType(
                 self.chan,
-                Some(Self::on_temperature_change),
-                ctx,
-            )
+                tc as c_uint
+            ))
         })
     }
 
-    /// Sets a handler to receive attach callbacks
-    pub fn set_on_attach_handler<F>(&mut self, cb: F) -> Result<()>
+    /// Gets the type of RTD connected to the channel.
+    pub fn rtd_type(&self) -> Result<RtdType> {
+        let mut rtd: ffi::PhidgetTemperatureSensor_RTDType = 0;
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetTemperatureSensor_getRTDType(
+                self.chan, &mut rtd
+            ))
+        })?;
+        RtdType::try_from(rtd)
+    }
+
+    /// Sets the type of RTD connected to the channel.
+    pub fn set_rtd_type(&mut self, rtd: RtdType) -> Result<()> {
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetTemperatureSensor_setRTDType(
+                self.chan,
+                rtd as c_uint
+            ))
+        })
+    }
+
+    /// Gets the number of wires that connect the RTD to the channel.
+    pub fn rtd_wire_setup(&self) -> Result<RtdWireSetup> {
+        let mut setup: ffi::Phidget_RTDWireSetup = 0;
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetTemperatureSensor_getRTDWireSetup(
+                self.chan, &mut setup
+            ))
+        })?;
+        RtdWireSetup::try_from(setup)
+    }
+
+    /// Sets the number of wires that connect the RTD to the channel.
+    pub fn set_rtd_wire_setup(&mut self, setup: RtdWireSetup) -> Result<()> {
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetTemperatureSensor_setRTDWireSetup(
+                self.chan,
+                setup as c_uint
+            ))
+        })
+    }
+
+    /// Set a handler to receive temperature change callbacks with the value as a
+    /// typed quantity.
+    /// This replaces any temperature change handler that was previously set.
+    /// The handler is removed when the returned guard is dropped.
+    #[cfg(feature = "units")]
+    pub fn set_on_temperature_change_quantity_handler<F, R>(
+        &self,
+        mut cb: F,
+    ) -> Result<HandlerGuard>
     where
-        F: Fn(&GenericPhidget) + Send + 'static,
+        F: FnMut(&TemperatureSensor, ThermodynamicTemperature) -> R + Send + 'static,
+        R: HandlerResult,
     {
-        let ctx = crate::phidget::set_on_attach_handler(self, cb)?;
-        self.attach_cb = Some(ctx);
-        Ok(())
+        self.set_on_temperature_change_handler(move |ch, val| {
+            cb(ch, ThermodynamicTemperature::new::<degree_celsius>(val))
+        })
+    }
+
+    /// Set a handler to receive temperature change callbacks.
+    /// The handler is removed when the returned guard is dropped.
+    pub fn set_on_temperature_change_handler<F, R>(&self, cb: F) -> Result<HandlerGuard>
+    where
+        F: FnMut(&TemperatureSensor, f64) -> R + Send + 'static,
+        R: HandlerResult,
+    {
+        let ctx = Context::into_raw(Self::from(self.chan), cb);
+
+        crate::handler::register::<Context<Self, F>, _>(
+            self.chan as PhidgetHandle,
+            "temperature_change",
+            ctx,
+            || unsafe {
+                traced!(ffi::PhidgetTemperatureSensor_setOnTemperatureChangeHandler(
+                    self.chan,
+                    Some(Self::on_temperature_change::<F, R>),
+                    ctx,
+                ))
+            },
+            |phid| unsafe {
+                traced!(ffi::PhidgetTemperatureSensor_setOnTemperatureChangeHandler(
+                    phid as _,
+                    None,
+                    ptr::null_mut(),
+                ))
+            },
+        )
     }
 
-    /// Sets a handler to receive detach callbacks
-    pub fn set_on_detach_handler<F>(&mut self, cb: F) -> Result<()>
+    /// Set a handler to receive temperature change callbacks, which can borrow
+    /// from the environment of the scope.
+    /// The handler is removed at the end of the scope.
+    pub fn set_on_temperature_change_handler_scoped<'scope, F, R>(
+        &self,
+        scope: &'scope HandlerScope<'scope, '_>,
+        cb: F,
+    ) -> Result<()>
     where
-        F: Fn(&GenericPhidget) + Send + 'static,
+        F: FnMut(&TemperatureSensor, f64) -> R + Send + 'scope,
+        R: HandlerResult + 'static,
     {
-        let ctx = crate::phidget::set_on_detach_handler(self, cb)?;
-        self.detach_cb = Some(ctx);
+        // SAFETY: The guard is held by the scope, which can't be forgotten,
+        // and which waits for any call to the handler. See `HandlerScope`.
+        let cb = unsafe {
+            mem::transmute::<
+                Box<dyn FnMut(&TemperatureSensor, f64) -> R + Send + 'scope>,
+                Box<dyn FnMut(&TemperatureSensor, f64) -> R + Send>,
+            >(Box::new(cb))
+        };
+        scope.hold(self.set_on_temperature_change_handler(cb)?);
         Ok(())
     }
+
+    /// Removes the temperature change handler, if one is set, and releases
+    /// its closure.
+    pub fn remove_on_temperature_change_handler(&self) -> Result<()> {
+        crate::handler::unregister(self.chan as PhidgetHandle, "temperature_change")
+    }
+
+    /// Gets a channel that receives the temperature readings, fed by the temperature change events.
+    ///
+    /// This registers the channel's temperature change handler, replacing any handler
+    /// that was previously set.
+    /// The handler is removed when the receiver is dropped.
+    pub fn temperature_channel(&self) -> Result<EventReceiver<f64>> {
+        let (tx, rx) = mpsc::channel();
+        let guard = self.set_on_temperature_change_handler(move |_, t| {
+            let _ = tx.send(t);
+        })?;
+        Ok(EventReceiver::new(rx, guard))
+    }
+
+    /// Gets a stream of the temperature readings, fed by the temperature change events.
+    ///
+    /// This registers the channel's temperature change handler, replacing any handler
+    /// that was previously set.
+    /// The handler is removed when the stream is dropped.
+    #[cfg(feature = "async")]
+    pub fn temperature_stream(&self) -> Result<EventStream<f64>> {
+        let (mut tx, rx) = crate::stream::event_channel();
+        let guard = self.set_on_temperature_change_handler(move |_, t| tx.send(t))?;
+        Ok(EventStream::new(rx, guard))
+    }
 }
 
 impl Phidget for TemperatureSensor {
@@ -116,7 +397,35 @@ impl Phidget for TemperatureSensor {
     }
 }
 
+impl Sensor for TemperatureSensor {
+    type Value = f64;
+
+    fn value(&self) -> Result<f64> {
+        self.temperature()
+    }
+
+    fn on_change<F>(&self, cb: F) -> Result<HandlerGuard>
+    where
+        F: FnMut(&Self, f64) + Send + 'static,
+    {
+        self.set_on_temperature_change_handler(cb)
+    }
+}
+
+impl fmt::Debug for TemperatureSensor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        crate::phidget::fmt_debug(f, "TemperatureSensor", self.chan as PhidgetHandle)
+    }
+}
+
+impl fmt::Display for TemperatureSensor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        crate::phidget::fmt_display(f, "TemperatureSensor", self.chan as PhidgetHandle)
+    }
+}
+
 unsafe impl Send for TemperatureSensor {}
+unsafe impl Sync for TemperatureSensor {}
 
 impl Default for TemperatureSensor {
     fn default() -> Self {
@@ -126,12 +435,18 @@ impl Default for TemperatureSensor {
 
 impl From<TemperatureSensorHandle> for TemperatureSensor {
     fn from(chan: TemperatureSensorHandle) -> Self {
-        Self {
-            chan,
-            cb: None,
-            attach_cb: None,
-            detach_cb: None,
-        }
+        Self { chan }
+    }
+}
+
+impl TryFrom<GenericPhidget> for TemperatureSensor {
+    type Error = Error;
+
+    /// Converts a generic phidget into a typed channel, if it's of the
+    /// right class. If the channel is open, it's closed when dropped.
+    fn try_from(ph: GenericPhidget) -> Result<Self> {
+        let chan = ph.into_channel(ChannelClass::TemperatureSensor)?;
+        Ok(Self::from(chan as TemperatureSensorHandle))
     }
 }
 
@@ -140,11 +455,42 @@ impl Drop for TemperatureSensor {
         if let Ok(true) = self.is_open() {
             let _ = self.close();
         }
+        let phid = self.chan as PhidgetHandle;
         unsafe {
             ffi::PhidgetTemperatureSensor_delete(&mut self.chan);
-            crate::drop_cb::<TemperatureCallback>(self.cb.take());
-            crate::drop_cb::<AttachCallback>(self.attach_cb.take());
-            crate::drop_cb::<DetachCallback>(self.detach_cb.take());
         }
+        crate::notify::reset(phid);
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::*;
+    use crate::mock::set_property;
+
+    #[test]
+    fn test_mock_rtd_settings() {
+        let mut sensor = TemperatureSensor::new();
+        sensor.set_serial_number(683_001).unwrap();
+        sensor.open().unwrap();
+        sensor.set_rtd_type(RtdType::Pt1000_3920).unwrap();
+        sensor.set_rtd_wire_setup(RtdWireSetup::FourWire).unwrap();
+        assert_eq!(sensor.rtd_type(), Ok(RtdType::Pt1000_3920));
+        assert_eq!(sensor.rtd_wire_setup(), Ok(RtdWireSetup::FourWire));
+
+        // A value that isn't known is an error, rather than a guess
+        set_property(&mut sensor, "RTDWireSetup", 9.0).unwrap();
+        assert_eq!(sensor.rtd_wire_setup(), Err(Error::InvalidArg));
+        assert!(!sensor.rtd_wire_setup().unwrap_err().is_not_yet_known());
+    }
+
+    #[test]
+    fn test_mock_on_hub_port() {
+        // A VINT device on the port, rather than the port itself
+        let mut sensor = TemperatureSensor::on_hub_port(644_001, 2).unwrap();
+        assert!(!sensor.is_hub_port_device().unwrap());
+        assert_eq!(sensor.hub_port().unwrap(), 2);
     }
 }
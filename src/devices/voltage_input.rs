@@ -10,50 +10,131 @@
 // to those terms.
 //
 
-use crate::{AttachCallback, DetachCallback, GenericPhidget, Phidget, Result, ReturnCode};
+use crate::{
+    devices::digital_input::PowerSupply, handler::Context, ChannelClass, Error, EventReceiver,
+    GenericPhidget, HandlerGuard, HandlerResult, HandlerScope, Phidget, Result, ReturnCode, Sensor,
+};
 use phidget_sys::{self as ffi, PhidgetHandle, PhidgetVoltageInputHandle};
-use std::{mem, os::raw::c_void, ptr};
+use std::{
+    fmt, mem,
+    os::raw::{c_uint, c_void},
+    ptr,
+    sync::mpsc,
+};
+
+#[cfg(feature = "async")]
+use crate::stream::EventStream;
+
+#[cfg(feature = "units")]
+use uom::si::{electric_potential::volt, f64::ElectricPotential};
 
 /// The function signature for the safe Rust voltage change callback.
-pub type VoltageChangeCallback = dyn Fn(&VoltageInput, f64) + Send + 'static;
+pub type VoltageChangeCallback = dyn FnMut(&VoltageInput, f64) + Send + 'static;
 
 /////////////////////////////////////////////////////////////////////////////
 
+/// The range of voltages that a voltage input measures
+#[derive(Copy, Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(u32)]
+pub enum VoltageRange {
+    /// ±10mV
+    Range10mV = ffi::PhidgetVoltageInput_VoltageRange_VOLTAGE_RANGE_10mV,
+    /// ±40mV
+    Range40mV = ffi::PhidgetVoltageInput_VoltageRange_VOLTAGE_RANGE_40mV,
+    /// ±200mV
+    Range200mV = ffi::PhidgetVoltageInput_VoltageRange_VOLTAGE_RANGE_200mV,
+    /// ±312.5mV
+    Range312_5mV = ffi::PhidgetVoltageInput_VoltageRange_VOLTAGE_RANGE_312_5mV,
+    /// ±400mV
+    Range400mV = ffi::PhidgetVoltageInput_VoltageRange_VOLTAGE_RANGE_400mV,
+    /// ±1000mV
+    Range1000mV = ffi::PhidgetVoltageInput_VoltageRange_VOLTAGE_RANGE_1000mV,
+    /// ±2V
+    Range2V = ffi::PhidgetVoltageInput_VoltageRange_VOLTAGE_RANGE_2V,
+    /// ±5V
+    Range5V = ffi::PhidgetVoltageInput_VoltageRange_VOLTAGE_RANGE_5V,
+    /// ±15V
+    Range15V = ffi::PhidgetVoltageInput_VoltageRange_VOLTAGE_RANGE_15V,
+    /// ±40V
+    Range40V = ffi::PhidgetVoltageInput_VoltageRange_VOLTAGE_RANGE_40V,
+    /// Selects the range automatically, from the voltage being measured
+    Auto = ffi::PhidgetVoltageInput_VoltageRange_VOLTAGE_RANGE_AUTO,
+}
+
+impl TryFrom<u32> for VoltageRange {
+    type Error = Error;
+
+    fn try_from(value: u32) -> Result<Self> {
+        use VoltageRange::*;
+        match value {
+            ffi::PhidgetVoltageInput_VoltageRange_VOLTAGE_RANGE_10mV => Ok(Range10mV),
+            ffi::PhidgetVoltageInput_VoltageRange_VOLTAGE_RANGE_40mV => Ok(Range40mV),
+            ffi::PhidgetVoltageInput_VoltageRange_VOLTAGE_RANGE_200mV => Ok(Range200mV),
+            ffi::PhidgetVoltageInput_VoltageRange_VOLTAGE_RANGE_312_5mV => Ok(Range312_5mV),
+            ffi::PhidgetVoltageInput_VoltageRange_VOLTAGE_RANGE_400mV => Ok(Range400mV),
+            ffi::PhidgetVoltageInput_VoltageRange_VOLTAGE_RANGE_1000mV => Ok(Range1000mV),
+            ffi::PhidgetVoltageInput_VoltageRange_VOLTAGE_RANGE_2V => Ok(Range2V),
+            ffi::PhidgetVoltageInput_VoltageRange_VOLTAGE_RANGE_5V => Ok(Range5V),
+            ffi::PhidgetVoltageInput_VoltageRange_VOLTAGE_RANGE_15V => Ok(Range15V),
+            ffi::PhidgetVoltageInput_VoltageRange_VOLTAGE_RANGE_40V => Ok(Range40V),
+            ffi::PhidgetVoltageInput_VoltageRange_VOLTAGE_RANGE_AUTO => Ok(Auto),
+            _ => Err(Error::InvalidArg),
+        }
+    }
+}
+
 /// Phidget voltage input
 pub struct VoltageInput {
     // Handle to the voltage input in the phidget22 library
     chan: PhidgetVoltageInputHandle,
-    // Double-boxed VoltageChangeCallback, if registered
-    cb: Option<*mut c_void>,
-    // Double-boxed attach callback, if registered
-    attach_cb: Option<*mut c_void>,
-    // Double-boxed detach callback, if registered
-    detach_cb: Option<*mut c_void>,
 }
 
 impl VoltageInput {
     /// Create a new voltage input.
+    ///
+    /// This panics if the library can't create the channel. Use
+    /// `try_new()` to get the error instead.
     pub fn new() -> Self {
+        Self::try_new().expect("failed to create the channel")
+    }
+
+    /// Create a new voltage input, returning an error if the library can't
+    /// create the channel.
+    pub fn try_new() -> Result<Self> {
         let mut chan: PhidgetVoltageInputHandle = ptr::null_mut();
-        unsafe {
-            ffi::PhidgetVoltageInput_create(&mut chan);
-        }
-        Self::from(chan)
+        ReturnCode::result(unsafe { ffi::PhidgetVoltageInput_create(&mut chan) })?;
+        crate::phidget::install_handlers(chan as PhidgetHandle);
+        Ok(Self::from(chan))
+    }
+
+    /// Create a new voltage input that uses a port of a VINT hub directly
+    /// as its input, in hub port mode.
+    ///
+    /// This selects the hub by its serial number and the port by its
+    /// number. The channel still needs to be opened.
+    pub fn on_hub_port(serial: i32, port: i32) -> Result<Self> {
+        let mut ch = Self::try_new()?;
+        ch.set_is_hub_port_device(true)?;
+        ch.set_hub_port(port)?;
+        ch.set_serial_number(serial)?;
+        Ok(ch)
     }
 
     // Low-level, unsafe, callback for the voltage change event.
-    // The context is a double-boxed pointer to the safe Rust callback.
-    unsafe extern "C" fn on_voltage_change(
+    // The context holds a view of the channel and the safe Rust callback.
+    unsafe extern "C" fn on_voltage_change<F, R>(
         chan: PhidgetVoltageInputHandle,
         ctx: *mut c_void,
         voltage: f64,
-    ) {
-        if !ctx.is_null() {
-            let cb: &mut Box<VoltageChangeCallback> = &mut *(ctx as *mut _);
-            let sensor = Self::from(chan);
-            cb(&sensor, voltage);
-            mem::forget(sensor);
-        }
+    ) where
+        F: FnMut(&VoltageInput, f64) -> R + Send + 'static,
+        R: HandlerResult,
+    {
+        crate::handler::deliver(chan as PhidgetHandle, ctx, move |phid, ctx| {
+            let ctx: &mut Context<Self, F> = &mut *(ctx as *mut _);
+            crate::handler::report(phid, (ctx.cb)(&ctx.chan, voltage));
+        });
     }
 
     /// Get a reference to the underlying sensor handle
@@ -64,48 +145,184 @@ impl VoltageInput {
     /// Get the voltage on the input channel
     pub fn voltage(&self) -> Result<f64> {
         let mut v: f64 = 0.0;
-        ReturnCode::result(unsafe { ffi::PhidgetVoltageInput_getVoltage(self.chan, &mut v) })?;
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetVoltageInput_getVoltage(self.chan, &mut v))
+        })?;
         Ok(v)
     }
 
-    /// Sets a handler to receive voltage change callbacks.
-    pub fn set_on_voltage_change_handler<F>(&mut self, cb: F) -> Result<()>
-    where
-        F: Fn(&VoltageInput, f64) + Send + 'static,
-    {
-        // 1st box is fat ptr, 2nd is regular pointer.
-        let cb: Box<Box<VoltageChangeCallback>> = Box::new(Box::new(cb));
-        let ctx = Box::into_raw(cb) as *mut c_void;
-        self.cb = Some(ctx);
+    /// Read the current voltage as a typed quantity.
+    #[cfg(feature = "units")]
+    pub fn voltage_quantity(&self) -> Result<ElectricPotential> {
+        Ok(ElectricPotential::new::<volt>(self.voltage()?))
+    }
 
+    /// Gets the minimum change in voltage, in volts, that triggers a change
+    /// event.
+    pub fn voltage_change_trigger(&self) -> Result<f64> {
+        let mut trigger = 0.0;
         ReturnCode::result(unsafe {
-            ffi::PhidgetVoltageInput_setOnVoltageChangeHandler(
+            traced!(ffi::PhidgetVoltageInput_getVoltageChangeTrigger(
                 self.chan,
-                Some(Self::on_voltage_change),
-                ctx,
-            )
+                &mut trigger
+            ))
+        })?;
+        Ok(trigger)
+    }
+
+    /// Sets the minimum change in voltage, in volts, that triggers a change
+    /// event. Zero reports every reading.
+    pub fn set_voltage_change_trigger(&mut self, trigger: f64) -> Result<()> {
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetVoltageInput_setVoltageChangeTrigger(
+                self.chan, trigger
+            ))
+        })
+    }
+
+    /// Gets the range of voltages that the channel measures.
+    pub fn voltage_range(&self) -> Result<VoltageRange> {
+        let mut range: ffi::PhidgetVoltageInput_VoltageRange = 0;
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetVoltageInput_getVoltageRange(
+                self.chan, &mut range
+            ))
+        })?;
+        VoltageRange::try_from(range)
+    }
+
+    /// Sets the range of voltages that the channel measures.
+    /// A smaller range gives a better resolution.
+    pub fn set_voltage_range(&mut self, range: VoltageRange) -> Result<()> {
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetVoltageInput_setVoltageRange(
+                self.chan,
+                range as c_uint
+            ))
         })
     }
 
-    /// Sets a handler to receive attach callbacks
-    pub fn set_on_attach_handler<F>(&mut self, cb: F) -> Result<()>
+    /// Gets the power supply voltage for the sensor on the channel.
+    pub fn power_supply(&self) -> Result<PowerSupply> {
+        let mut power_supply: ffi::Phidget_PowerSupply = 0;
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetVoltageInput_getPowerSupply(
+                self.chan,
+                &mut power_supply
+            ))
+        })?;
+        PowerSupply::try_from(power_supply)
+    }
+
+    /// Sets the power supply voltage for the sensor on the channel.
+    pub fn set_power_supply(&mut self, power_supply: PowerSupply) -> Result<()> {
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetVoltageInput_setPowerSupply(
+                self.chan,
+                power_supply as c_uint
+            ))
+        })
+    }
+
+    /// Set a handler to receive voltage change callbacks with the value as a
+    /// typed quantity.
+    /// This replaces any voltage change handler that was previously set.
+    /// The handler is removed when the returned guard is dropped.
+    #[cfg(feature = "units")]
+    pub fn set_on_voltage_change_quantity_handler<F, R>(&self, mut cb: F) -> Result<HandlerGuard>
     where
-        F: Fn(&GenericPhidget) + Send + 'static,
+        F: FnMut(&VoltageInput, ElectricPotential) -> R + Send + 'static,
+        R: HandlerResult,
     {
-        let ctx = crate::phidget::set_on_attach_handler(self, cb)?;
-        self.attach_cb = Some(ctx);
-        Ok(())
+        self.set_on_voltage_change_handler(move |ch, val| {
+            cb(ch, ElectricPotential::new::<volt>(val))
+        })
     }
 
-    /// Sets a handler to receive detach callbacks
-    pub fn set_on_detach_handler<F>(&mut self, cb: F) -> Result<()>
+    /// Sets a handler to receive voltage change callbacks.
+    /// The handler is removed when the returned guard is dropped.
+    pub fn set_on_voltage_change_handler<F, R>(&self, cb: F) -> Result<HandlerGuard>
+    where
+        F: FnMut(&VoltageInput, f64) -> R + Send + 'static,
+        R: HandlerResult,
+    {
+        let ctx = Context::into_raw(Self::from(self.chan), cb);
+
+        crate::handler::register::<Context<Self, F>, _>(
+            self.chan as PhidgetHandle,
+            "voltage_change",
+            ctx,
+            || unsafe {
+                traced!(ffi::PhidgetVoltageInput_setOnVoltageChangeHandler(
+                    self.chan,
+                    Some(Self::on_voltage_change::<F, R>),
+                    ctx,
+                ))
+            },
+            |phid| unsafe {
+                traced!(ffi::PhidgetVoltageInput_setOnVoltageChangeHandler(
+                    phid as _,
+                    None,
+                    ptr::null_mut()
+                ))
+            },
+        )
+    }
+
+    /// Set a handler to receive voltage change callbacks, which can borrow
+    /// from the environment of the scope.
+    /// The handler is removed at the end of the scope.
+    pub fn set_on_voltage_change_handler_scoped<'scope, F, R>(
+        &self,
+        scope: &'scope HandlerScope<'scope, '_>,
+        cb: F,
+    ) -> Result<()>
     where
-        F: Fn(&GenericPhidget) + Send + 'static,
+        F: FnMut(&VoltageInput, f64) -> R + Send + 'scope,
+        R: HandlerResult + 'static,
     {
-        let ctx = crate::phidget::set_on_detach_handler(self, cb)?;
-        self.detach_cb = Some(ctx);
+        // SAFETY: The guard is held by the scope, which can't be forgotten,
+        // and which waits for any call to the handler. See `HandlerScope`.
+        let cb = unsafe {
+            mem::transmute::<
+                Box<dyn FnMut(&VoltageInput, f64) -> R + Send + 'scope>,
+                Box<dyn FnMut(&VoltageInput, f64) -> R + Send>,
+            >(Box::new(cb))
+        };
+        scope.hold(self.set_on_voltage_change_handler(cb)?);
         Ok(())
     }
+
+    /// Removes the voltage change handler, if one is set, and releases
+    /// its closure.
+    pub fn remove_on_voltage_change_handler(&self) -> Result<()> {
+        crate::handler::unregister(self.chan as PhidgetHandle, "voltage_change")
+    }
+
+    /// Gets a channel that receives the voltage readings, fed by the voltage change events.
+    ///
+    /// This registers the channel's voltage change handler, replacing any handler
+    /// that was previously set.
+    /// The handler is removed when the receiver is dropped.
+    pub fn voltage_channel(&self) -> Result<EventReceiver<f64>> {
+        let (tx, rx) = mpsc::channel();
+        let guard = self.set_on_voltage_change_handler(move |_, v| {
+            let _ = tx.send(v);
+        })?;
+        Ok(EventReceiver::new(rx, guard))
+    }
+
+    /// Gets a stream of the voltage readings, fed by the voltage change events.
+    ///
+    /// This registers the channel's voltage change handler, replacing any handler
+    /// that was previously set.
+    /// The handler is removed when the stream is dropped.
+    #[cfg(feature = "async")]
+    pub fn voltage_stream(&self) -> Result<EventStream<f64>> {
+        let (mut tx, rx) = crate::stream::event_channel();
+        let guard = self.set_on_voltage_change_handler(move |_, v| tx.send(v))?;
+        Ok(EventStream::new(rx, guard))
+    }
 }
 
 impl Phidget for VoltageInput {
@@ -114,7 +331,35 @@ impl Phidget for VoltageInput {
     }
 }
 
+impl Sensor for VoltageInput {
+    type Value = f64;
+
+    fn value(&self) -> Result<f64> {
+        self.voltage()
+    }
+
+    fn on_change<F>(&self, cb: F) -> Result<HandlerGuard>
+    where
+        F: FnMut(&Self, f64) + Send + 'static,
+    {
+        self.set_on_voltage_change_handler(cb)
+    }
+}
+
+impl fmt::Debug for VoltageInput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        crate::phidget::fmt_debug(f, "VoltageInput", self.chan as PhidgetHandle)
+    }
+}
+
+impl fmt::Display for VoltageInput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        crate::phidget::fmt_display(f, "VoltageInput", self.chan as PhidgetHandle)
+    }
+}
+
 unsafe impl Send for VoltageInput {}
+unsafe impl Sync for VoltageInput {}
 
 impl Default for VoltageInput {
     fn default() -> Self {
@@ -124,12 +369,34 @@ impl Default for VoltageInput {
 
 impl From<PhidgetVoltageInputHandle> for VoltageInput {
     fn from(chan: PhidgetVoltageInputHandle) -> Self {
-        Self {
-            chan,
-            cb: None,
-            attach_cb: None,
-            detach_cb: None,
-        }
+        Self { chan }
+    }
+}
+
+impl TryFrom<GenericPhidget> for VoltageInput {
+    type Error = Error;
+
+    /// Converts a generic phidget into a typed channel, if it's of the
+    /// right class. If the channel is open, it's closed when dropped.
+    fn try_from(ph: GenericPhidget) -> Result<Self> {
+        let chan = ph.into_channel(ChannelClass::VoltageInput)?;
+        Ok(Self::from(chan as PhidgetVoltageInputHandle))
+    }
+}
+
+#[cfg(feature = "embedded-hal")]
+impl embedded_hal_02::adc::Channel<crate::hal::Adc> for VoltageInput {
+    type ID = ();
+
+    fn channel() -> Self::ID {}
+}
+
+#[cfg(feature = "embedded-hal")]
+impl embedded_hal_02::adc::OneShot<crate::hal::Adc, f64, VoltageInput> for crate::hal::Adc {
+    type Error = Error;
+
+    fn read(&mut self, pin: &mut VoltageInput) -> nb::Result<f64, Error> {
+        pin.voltage().map_err(nb::Error::Other)
     }
 }
 
@@ -138,11 +405,26 @@ impl Drop for VoltageInput {
         if let Ok(true) = self.is_open() {
             let _ = self.close();
         }
+        let phid = self.chan as PhidgetHandle;
         unsafe {
             ffi::PhidgetVoltageInput_delete(&mut self.chan);
-            crate::drop_cb::<VoltageChangeCallback>(self.cb.take());
-            crate::drop_cb::<AttachCallback>(self.attach_cb.take());
-            crate::drop_cb::<DetachCallback>(self.detach_cb.take());
         }
+        crate::notify::reset(phid);
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mock_on_hub_port() {
+        // The port itself, in voltage input mode
+        let mut vin = VoltageInput::on_hub_port(644_001, 3).unwrap();
+        assert!(vin.is_hub_port_device().unwrap());
+        assert_eq!(vin.hub_port().unwrap(), 3);
+        assert_eq!(vin.serial_number().unwrap(), 644_001);
     }
 }
@@ -10,70 +10,69 @@
 // to those terms.
 //
 
-use crate::{AttachCallback, DetachCallback, GenericPhidget, Phidget, Result, ReturnCode};
+use crate::{ChannelClass, Error, GenericPhidget, Phidget, Result, ReturnCode};
 use phidget_sys::{self as ffi, PhidgetHandle, PhidgetVoltageOutputHandle};
-use std::{os::raw::c_void, ptr};
+use std::{fmt, ptr};
 
 /// Phidget voltage output
 pub struct VoltageOutput {
     // Handle to the voltage output in the phidget22 library
     chan: PhidgetVoltageOutputHandle,
-    // Double-boxed attach callback, if registered
-    attach_cb: Option<*mut c_void>,
-    // Double-boxed detach callback, if registered
-    detach_cb: Option<*mut c_void>,
 }
 
 impl VoltageOutput {
-    /// Create a new voltage input.
+    /// Create a new voltage output.
+    ///
+    /// This panics if the library can't create the channel. Use
+    /// `try_new()` to get the error instead.
     pub fn new() -> Self {
+        Self::try_new().expect("failed to create the channel")
+    }
+
+    /// Create a new voltage output, returning an error if the library can't
+    /// create the channel.
+    pub fn try_new() -> Result<Self> {
         let mut chan: PhidgetVoltageOutputHandle = ptr::null_mut();
-        unsafe {
-            ffi::PhidgetVoltageOutput_create(&mut chan);
-        }
-        Self::from(chan)
+        ReturnCode::result(unsafe { ffi::PhidgetVoltageOutput_create(&mut chan) })?;
+        crate::phidget::install_handlers(chan as PhidgetHandle);
+        Ok(Self::from(chan))
     }
 
     /// Get the voltage value that the channel will output
     pub fn voltage(&self) -> Result<f64> {
         let mut v: f64 = 0.0;
-        ReturnCode::result(unsafe { ffi::PhidgetVoltageOutput_getVoltage(self.chan, &mut v) })?;
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetVoltageOutput_getVoltage(self.chan, &mut v))
+        })?;
         Ok(v)
     }
 
     /// Set the voltage value that the channel will output.
     pub fn set_voltage(&mut self, v: f64) -> Result<()> {
-        ReturnCode::result(unsafe { ffi::PhidgetVoltageOutput_setVoltage(self.chan, v) })
+        ReturnCode::result(unsafe { traced!(ffi::PhidgetVoltageOutput_setVoltage(self.chan, v)) })
     }
+}
 
-    /// Sets a handler to receive attach callbacks
-    pub fn set_on_attach_handler<F>(&mut self, cb: F) -> Result<()>
-    where
-        F: Fn(&GenericPhidget) + Send + 'static,
-    {
-        let ctx = crate::phidget::set_on_attach_handler(self, cb)?;
-        self.attach_cb = Some(ctx);
-        Ok(())
+impl Phidget for VoltageOutput {
+    fn as_handle(&mut self) -> PhidgetHandle {
+        self.chan as PhidgetHandle
     }
+}
 
-    /// Sets a handler to receive detach callbacks
-    pub fn set_on_detach_handler<F>(&mut self, cb: F) -> Result<()>
-    where
-        F: Fn(&GenericPhidget) + Send + 'static,
-    {
-        let ctx = crate::phidget::set_on_detach_handler(self, cb)?;
-        self.detach_cb = Some(ctx);
-        Ok(())
+impl fmt::Debug for VoltageOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        crate::phidget::fmt_debug(f, "VoltageOutput", self.chan as PhidgetHandle)
     }
 }
 
-impl Phidget for VoltageOutput {
-    fn as_handle(&mut self) -> PhidgetHandle {
-        self.chan as PhidgetHandle
+impl fmt::Display for VoltageOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        crate::phidget::fmt_display(f, "VoltageOutput", self.chan as PhidgetHandle)
     }
 }
 
 unsafe impl Send for VoltageOutput {}
+unsafe impl Sync for VoltageOutput {}
 
 impl Default for VoltageOutput {
     fn default() -> Self {
@@ -83,11 +82,18 @@ impl Default for VoltageOutput {
 
 impl From<PhidgetVoltageOutputHandle> for VoltageOutput {
     fn from(chan: PhidgetVoltageOutputHandle) -> Self {
-        Self {
-            chan,
-            attach_cb: None,
-            detach_cb: None,
-        }
+        Self { chan }
+    }
+}
+
+impl TryFrom<GenericPhidget> for VoltageOutput {
+    type Error = Error;
+
+    /// Converts a generic phidget into a typed channel, if it's of the
+    /// right class. If the channel is open, it's closed when dropped.
+    fn try_from(ph: GenericPhidget) -> Result<Self> {
+        let chan = ph.into_channel(ChannelClass::VoltageOutput)?;
+        Ok(Self::from(chan as PhidgetVoltageOutputHandle))
     }
 }
 
@@ -96,10 +102,10 @@ impl Drop for VoltageOutput {
         if let Ok(true) = self.is_open() {
             let _ = self.close();
         }
+        let phid = self.chan as PhidgetHandle;
         unsafe {
             ffi::PhidgetVoltageOutput_delete(&mut self.chan);
-            crate::drop_cb::<AttachCallback>(self.attach_cb.take());
-            crate::drop_cb::<DetachCallback>(self.detach_cb.take());
         }
+        crate::notify::reset(phid);
     }
 }
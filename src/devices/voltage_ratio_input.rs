@@ -10,48 +10,120 @@
 // This file may not be copied, modified, or distributed except according
 // to those terms.
 //
-use crate::{AttachCallback, DetachCallback, GenericPhidget, Phidget, Result, ReturnCode};
+use crate::{
+    handler::Context, ChannelClass, Error, EventReceiver, GenericPhidget, HandlerGuard,
+    HandlerResult, HandlerScope, Phidget, Result, ReturnCode, Sensor,
+};
 use phidget_sys::{self as ffi, PhidgetHandle, PhidgetVoltageRatioInputHandle};
-use std::{mem, os::raw::c_void, ptr};
+use std::{
+    fmt, mem,
+    os::raw::{c_int, c_uint, c_void},
+    ptr,
+    sync::mpsc,
+};
+
+#[cfg(feature = "async")]
+use crate::stream::EventStream;
+
+#[cfg(feature = "units")]
+use uom::si::{f64::Ratio, ratio::ratio};
 
 /// The function type for the safe Rust position change callback.
-pub type VoltageRatioChangeCallback = dyn Fn(&VoltageRatioInput, f64) + Send + 'static;
+pub type VoltageRatioChangeCallback = dyn FnMut(&VoltageRatioInput, f64) + Send + 'static;
+
+/// The gain applied to a bridge input, like a load cell
+#[derive(Copy, Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(u32)]
+pub enum BridgeGain {
+    /// Gain of 1x
+    Gain1 = ffi::PhidgetVoltageRatioInput_BridgeGain_BRIDGE_GAIN_1,
+    /// Gain of 2x
+    Gain2 = ffi::PhidgetVoltageRatioInput_BridgeGain_BRIDGE_GAIN_2,
+    /// Gain of 4x
+    Gain4 = ffi::PhidgetVoltageRatioInput_BridgeGain_BRIDGE_GAIN_4,
+    /// Gain of 8x
+    Gain8 = ffi::PhidgetVoltageRatioInput_BridgeGain_BRIDGE_GAIN_8,
+    /// Gain of 16x
+    Gain16 = ffi::PhidgetVoltageRatioInput_BridgeGain_BRIDGE_GAIN_16,
+    /// Gain of 32x
+    Gain32 = ffi::PhidgetVoltageRatioInput_BridgeGain_BRIDGE_GAIN_32,
+    /// Gain of 64x
+    Gain64 = ffi::PhidgetVoltageRatioInput_BridgeGain_BRIDGE_GAIN_64,
+    /// Gain of 128x
+    Gain128 = ffi::PhidgetVoltageRatioInput_BridgeGain_BRIDGE_GAIN_128,
+}
+
+impl TryFrom<u32> for BridgeGain {
+    type Error = Error;
+
+    fn try_from(value: u32) -> Result<Self> {
+        use BridgeGain::*;
+        match value {
+            ffi::PhidgetVoltageRatioInput_BridgeGain_BRIDGE_GAIN_1 => Ok(Gain1),
+            ffi::PhidgetVoltageRatioInput_BridgeGain_BRIDGE_GAIN_2 => Ok(Gain2),
+            ffi::PhidgetVoltageRatioInput_BridgeGain_BRIDGE_GAIN_4 => Ok(Gain4),
+            ffi::PhidgetVoltageRatioInput_BridgeGain_BRIDGE_GAIN_8 => Ok(Gain8),
+            ffi::PhidgetVoltageRatioInput_BridgeGain_BRIDGE_GAIN_16 => Ok(Gain16),
+            ffi::PhidgetVoltageRatioInput_BridgeGain_BRIDGE_GAIN_32 => Ok(Gain32),
+            ffi::PhidgetVoltageRatioInput_BridgeGain_BRIDGE_GAIN_64 => Ok(Gain64),
+            ffi::PhidgetVoltageRatioInput_BridgeGain_BRIDGE_GAIN_128 => Ok(Gain128),
+            _ => Err(Error::InvalidArg),
+        }
+    }
+}
 
 /// Phidget voltage ratio input.
 pub struct VoltageRatioInput {
     // Handle to the voltage ratio input in the phidget22 libary
     chan: PhidgetVoltageRatioInputHandle,
-    // Double-boxed VoltageRatioChangeCallback, if registered
-    cb: Option<*mut c_void>,
-    // Double-boxed attach callback, if registered
-    attach_cb: Option<*mut c_void>,
-    // Double-boxed detach callback, if registered
-    detach_cb: Option<*mut c_void>,
 }
 
 impl VoltageRatioInput {
     /// Create a new voltage ratio input.
+    ///
+    /// This panics if the library can't create the channel. Use
+    /// `try_new()` to get the error instead.
     pub fn new() -> Self {
+        Self::try_new().expect("failed to create the channel")
+    }
+
+    /// Create a new voltage ratio input, returning an error if the library can't
+    /// create the channel.
+    pub fn try_new() -> Result<Self> {
         let mut chan: PhidgetVoltageRatioInputHandle = ptr::null_mut();
-        unsafe {
-            ffi::PhidgetVoltageRatioInput_create(&mut chan);
-        }
-        Self::from(chan)
+        ReturnCode::result(unsafe { ffi::PhidgetVoltageRatioInput_create(&mut chan) })?;
+        crate::phidget::install_handlers(chan as PhidgetHandle);
+        Ok(Self::from(chan))
+    }
+
+    /// Create a new voltage ratio input that uses a port of a VINT hub directly
+    /// as its input, in hub port mode.
+    ///
+    /// This selects the hub by its serial number and the port by its
+    /// number. The channel still needs to be opened.
+    pub fn on_hub_port(serial: i32, port: i32) -> Result<Self> {
+        let mut ch = Self::try_new()?;
+        ch.set_is_hub_port_device(true)?;
+        ch.set_hub_port(port)?;
+        ch.set_serial_number(serial)?;
+        Ok(ch)
     }
 
     // Low-level, unsafe, callback for the voltage ratio change event.
-    // The context is a double-boxed pointer to the safe Rust callback.
-    unsafe extern "C" fn on_voltage_ratio_change(
+    // The context holds a view of the channel and the safe Rust callback.
+    unsafe extern "C" fn on_voltage_ratio_change<F, R>(
         chan: PhidgetVoltageRatioInputHandle,
         ctx: *mut c_void,
         voltage: f64,
-    ) {
-        if !ctx.is_null() {
-            let cb: &mut Box<VoltageRatioChangeCallback> = &mut *(ctx as *mut _);
-            let sensor = Self::from(chan);
-            cb(&sensor, voltage);
-            mem::forget(sensor);
-        }
+    ) where
+        F: FnMut(&VoltageRatioInput, f64) -> R + Send + 'static,
+        R: HandlerResult,
+    {
+        crate::handler::deliver(chan as PhidgetHandle, ctx, move |phid, ctx| {
+            let ctx: &mut Context<Self, F> = &mut *(ctx as *mut _);
+            crate::handler::report(phid, (ctx.cb)(&ctx.chan, voltage));
+        });
     }
 
     /// Get a reference to the underlying sensor handle
@@ -63,49 +135,229 @@ impl VoltageRatioInput {
     pub fn voltage_ratio(&self) -> Result<f64> {
         let mut voltage_ratio: f64 = 0.0;
         ReturnCode::result(unsafe {
-            ffi::PhidgetVoltageRatioInput_getVoltageRatio(self.chan, &mut voltage_ratio)
+            traced!(ffi::PhidgetVoltageRatioInput_getVoltageRatio(
+                self.chan,
+                &mut voltage_ratio
+            ))
         })?;
         Ok(voltage_ratio)
     }
 
-    /// Sets a handler to receive voltage change callbacks.
-    pub fn set_on_voltage_ratio_change_handler<F>(&mut self, cb: F) -> Result<()>
-    where
-        F: Fn(&VoltageRatioInput, f64) + Send + 'static,
-    {
-        // 1st box is fat ptr, 2nd is regular pointer.
-        let cb: Box<Box<VoltageRatioChangeCallback>> = Box::new(Box::new(cb));
-        let ctx = Box::into_raw(cb) as *mut c_void;
-        self.cb = Some(ctx);
+    /// Read the current voltage ratio as a typed quantity.
+    #[cfg(feature = "units")]
+    pub fn voltage_ratio_quantity(&self) -> Result<Ratio> {
+        Ok(Ratio::new::<ratio>(self.voltage_ratio()?))
+    }
+
+    /// Gets the minimum change in voltage ratio that triggers a change
+    /// event.
+    pub fn voltage_ratio_change_trigger(&self) -> Result<f64> {
+        let mut trigger = 0.0;
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetVoltageRatioInput_getVoltageRatioChangeTrigger(
+                self.chan,
+                &mut trigger
+            ))
+        })?;
+        Ok(trigger)
+    }
 
+    /// Sets the minimum change in voltage ratio that triggers a change
+    /// event. Zero reports every reading.
+    pub fn set_voltage_ratio_change_trigger(&mut self, trigger: f64) -> Result<()> {
         ReturnCode::result(unsafe {
-            ffi::PhidgetVoltageRatioInput_setOnVoltageRatioChangeHandler(
+            traced!(ffi::PhidgetVoltageRatioInput_setVoltageRatioChangeTrigger(
+                self.chan, trigger
+            ))
+        })
+    }
+
+    /// Gets the smallest change trigger that can be set.
+    pub fn min_voltage_ratio_change_trigger(&self) -> Result<f64> {
+        crate::cache::property(
+            self.chan as PhidgetHandle,
+            "MinVoltageRatioChangeTrigger",
+            || {
+                let mut value = 0.0;
+                ReturnCode::result(unsafe {
+                    traced!(
+                        ffi::PhidgetVoltageRatioInput_getMinVoltageRatioChangeTrigger(
+                            self.chan, &mut value
+                        )
+                    )
+                })?;
+                Ok(value)
+            },
+        )
+    }
+
+    /// Gets the largest change trigger that can be set.
+    pub fn max_voltage_ratio_change_trigger(&self) -> Result<f64> {
+        crate::cache::property(
+            self.chan as PhidgetHandle,
+            "MaxVoltageRatioChangeTrigger",
+            || {
+                let mut value = 0.0;
+                ReturnCode::result(unsafe {
+                    traced!(
+                        ffi::PhidgetVoltageRatioInput_getMaxVoltageRatioChangeTrigger(
+                            self.chan, &mut value
+                        )
+                    )
+                })?;
+                Ok(value)
+            },
+        )
+    }
+
+    /// Determines if the bridge input is enabled.
+    pub fn bridge_enabled(&self) -> Result<bool> {
+        let mut en: c_int = 0;
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetVoltageRatioInput_getBridgeEnabled(
+                self.chan, &mut en
+            ))
+        })?;
+        Ok(en != 0)
+    }
+
+    /// Enables or disables the bridge input, which powers a bridge sensor
+    /// like a load cell.
+    pub fn set_bridge_enabled(&mut self, enabled: bool) -> Result<()> {
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetVoltageRatioInput_setBridgeEnabled(
+                self.chan,
+                c_int::from(enabled)
+            ))
+        })
+    }
+
+    /// Gets the gain applied to the bridge input.
+    pub fn bridge_gain(&self) -> Result<BridgeGain> {
+        let mut gain: ffi::PhidgetVoltageRatioInput_BridgeGain = 0;
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetVoltageRatioInput_getBridgeGain(
+                self.chan, &mut gain
+            ))
+        })?;
+        BridgeGain::try_from(gain)
+    }
+
+    /// Sets the gain applied to the bridge input.
+    pub fn set_bridge_gain(&mut self, gain: BridgeGain) -> Result<()> {
+        ReturnCode::result(unsafe {
+            traced!(ffi::PhidgetVoltageRatioInput_setBridgeGain(
                 self.chan,
-                Some(Self::on_voltage_ratio_change),
-                ctx,
-            )
+                gain as c_uint
+            ))
         })
     }
 
-    /// Sets a handler to receive attach callbacks
-    pub fn set_on_attach_handler<F>(&mut self, cb: F) -> Result<()>
+    /// Set a handler to receive voltage ratio change callbacks with the value as a
+    /// typed quantity.
+    /// This replaces any voltage ratio change handler that was previously set.
+    /// The handler is removed when the returned guard is dropped.
+    #[cfg(feature = "units")]
+    pub fn set_on_voltage_ratio_change_quantity_handler<F, R>(
+        &self,
+        mut cb: F,
+    ) -> Result<HandlerGuard>
     where
-        F: Fn(&GenericPhidget) + Send + 'static,
+        F: FnMut(&VoltageRatioInput, Ratio) -> R + Send + 'static,
+        R: HandlerResult,
     {
-        let ctx = crate::phidget::set_on_attach_handler(self, cb)?;
-        self.attach_cb = Some(ctx);
-        Ok(())
+        self.set_on_voltage_ratio_change_handler(move |ch, val| cb(ch, Ratio::new::<ratio>(val)))
     }
 
-    /// Sets a handler to receive detach callbacks
-    pub fn set_on_detach_handler<F>(&mut self, cb: F) -> Result<()>
+    /// Sets a handler to receive voltage change callbacks.
+    /// The handler is removed when the returned guard is dropped.
+    pub fn set_on_voltage_ratio_change_handler<F, R>(&self, cb: F) -> Result<HandlerGuard>
     where
-        F: Fn(&GenericPhidget) + Send + 'static,
+        F: FnMut(&VoltageRatioInput, f64) -> R + Send + 'static,
+        R: HandlerResult,
     {
-        let ctx = crate::phidget::set_on_detach_handler(self, cb)?;
-        self.detach_cb = Some(ctx);
+        let ctx = Context::into_raw(Self::from(self.chan), cb);
+
+        crate::handler::register::<Context<Self, F>, _>(
+            self.chan as PhidgetHandle,
+            "voltage_ratio_change",
+            ctx,
+            || unsafe {
+                traced!(
+                    ffi::PhidgetVoltageRatioInput_setOnVoltageRatioChangeHandler(
+                        self.chan,
+                        Some(Self::on_voltage_ratio_change::<F, R>),
+                        ctx,
+                    )
+                )
+            },
+            |phid| unsafe {
+                traced!(
+                    ffi::PhidgetVoltageRatioInput_setOnVoltageRatioChangeHandler(
+                        phid as _,
+                        None,
+                        ptr::null_mut(),
+                    )
+                )
+            },
+        )
+    }
+
+    /// Set a handler to receive voltage ratio change callbacks, which can borrow
+    /// from the environment of the scope.
+    /// The handler is removed at the end of the scope.
+    pub fn set_on_voltage_ratio_change_handler_scoped<'scope, F, R>(
+        &self,
+        scope: &'scope HandlerScope<'scope, '_>,
+        cb: F,
+    ) -> Result<()>
+    where
+        F: FnMut(&VoltageRatioInput, f64) -> R + Send + 'scope,
+        R: HandlerResult + 'static,
+    {
+        // SAFETY: The guard is held by the scope, which can't be forgotten,
+        // and which waits for any call to the handler. See `HandlerScope`.
+        let cb = unsafe {
+            mem::transmute::<
+                Box<dyn FnMut(&VoltageRatioInput, f64) -> R + Send + 'scope>,
+                Box<dyn FnMut(&VoltageRatioInput, f64) -> R + Send>,
+            >(Box::new(cb))
+        };
+        scope.hold(self.set_on_voltage_ratio_change_handler(cb)?);
         Ok(())
     }
+
+    /// Removes the voltage ratio change handler, if one is set, and releases
+    /// its closure.
+    pub fn remove_on_voltage_ratio_change_handler(&self) -> Result<()> {
+        crate::handler::unregister(self.chan as PhidgetHandle, "voltage_ratio_change")
+    }
+
+    /// Gets a channel that receives the voltage ratio readings, fed by the voltage ratio change
+    /// events.
+    ///
+    /// This registers the channel's voltage ratio change handler, replacing any handler
+    /// that was previously set.
+    /// The handler is removed when the receiver is dropped.
+    pub fn voltage_ratio_channel(&self) -> Result<EventReceiver<f64>> {
+        let (tx, rx) = mpsc::channel();
+        let guard = self.set_on_voltage_ratio_change_handler(move |_, v| {
+            let _ = tx.send(v);
+        })?;
+        Ok(EventReceiver::new(rx, guard))
+    }
+
+    /// Gets a stream of the voltage ratio readings, fed by the voltage ratio change events.
+    ///
+    /// This registers the channel's voltage ratio change handler, replacing any handler
+    /// that was previously set.
+    /// The handler is removed when the stream is dropped.
+    #[cfg(feature = "async")]
+    pub fn voltage_ratio_stream(&self) -> Result<EventStream<f64>> {
+        let (mut tx, rx) = crate::stream::event_channel();
+        let guard = self.set_on_voltage_ratio_change_handler(move |_, v| tx.send(v))?;
+        Ok(EventStream::new(rx, guard))
+    }
 }
 
 impl Phidget for VoltageRatioInput {
@@ -114,7 +366,35 @@ impl Phidget for VoltageRatioInput {
     }
 }
 
+impl Sensor for VoltageRatioInput {
+    type Value = f64;
+
+    fn value(&self) -> Result<f64> {
+        self.voltage_ratio()
+    }
+
+    fn on_change<F>(&self, cb: F) -> Result<HandlerGuard>
+    where
+        F: FnMut(&Self, f64) + Send + 'static,
+    {
+        self.set_on_voltage_ratio_change_handler(cb)
+    }
+}
+
+impl fmt::Debug for VoltageRatioInput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        crate::phidget::fmt_debug(f, "VoltageRatioInput", self.chan as PhidgetHandle)
+    }
+}
+
+impl fmt::Display for VoltageRatioInput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        crate::phidget::fmt_display(f, "VoltageRatioInput", self.chan as PhidgetHandle)
+    }
+}
+
 unsafe impl Send for VoltageRatioInput {}
+unsafe impl Sync for VoltageRatioInput {}
 
 impl Default for VoltageRatioInput {
     fn default() -> Self {
@@ -124,12 +404,34 @@ impl Default for VoltageRatioInput {
 
 impl From<PhidgetVoltageRatioInputHandle> for VoltageRatioInput {
     fn from(chan: PhidgetVoltageRatioInputHandle) -> Self {
-        Self {
-            chan,
-            cb: None,
-            attach_cb: None,
-            detach_cb: None,
-        }
+        Self { chan }
+    }
+}
+
+impl TryFrom<GenericPhidget> for VoltageRatioInput {
+    type Error = Error;
+
+    /// Converts a generic phidget into a typed channel, if it's of the
+    /// right class. If the channel is open, it's closed when dropped.
+    fn try_from(ph: GenericPhidget) -> Result<Self> {
+        let chan = ph.into_channel(ChannelClass::VoltageRatioInput)?;
+        Ok(Self::from(chan as PhidgetVoltageRatioInputHandle))
+    }
+}
+
+#[cfg(feature = "embedded-hal")]
+impl embedded_hal_02::adc::Channel<crate::hal::Adc> for VoltageRatioInput {
+    type ID = ();
+
+    fn channel() -> Self::ID {}
+}
+
+#[cfg(feature = "embedded-hal")]
+impl embedded_hal_02::adc::OneShot<crate::hal::Adc, f64, VoltageRatioInput> for crate::hal::Adc {
+    type Error = Error;
+
+    fn read(&mut self, pin: &mut VoltageRatioInput) -> nb::Result<f64, Error> {
+        pin.voltage_ratio().map_err(nb::Error::Other)
     }
 }
 
@@ -138,11 +440,54 @@ impl Drop for VoltageRatioInput {
         if let Ok(true) = self.is_open() {
             let _ = self.close();
         }
+        let phid = self.chan as PhidgetHandle;
         unsafe {
             ffi::PhidgetVoltageRatioInput_delete(&mut self.chan);
-            crate::drop_cb::<VoltageRatioChangeCallback>(self.cb.take());
-            crate::drop_cb::<AttachCallback>(self.attach_cb.take());
-            crate::drop_cb::<DetachCallback>(self.detach_cb.take());
         }
+        crate::notify::reset(phid);
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::*;
+    use crate::mock::{set_property, set_value};
+    use std::time::Duration;
+
+    #[test]
+    fn test_mock_voltage_ratio_change_trigger() {
+        let mut vin = VoltageRatioInput::new();
+        vin.open().unwrap();
+        set_property(&mut vin, "MinVoltageRatioChangeTrigger", 0.0).unwrap();
+        set_property(&mut vin, "MaxVoltageRatioChangeTrigger", 0.5).unwrap();
+        assert_eq!(vin.min_voltage_ratio_change_trigger(), Ok(0.0));
+        assert_eq!(vin.max_voltage_ratio_change_trigger(), Ok(0.5));
+
+        vin.set_voltage_ratio_change_trigger(0.001).unwrap();
+        assert_eq!(vin.voltage_ratio_change_trigger(), Ok(0.001));
+
+        // Load cell noise within the trigger doesn't raise any events
+        let rx = vin.voltage_ratio_channel().unwrap();
+        for val in [0.0100, 0.0105, 0.0095, 0.0112] {
+            set_value(&mut vin, val).unwrap();
+        }
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), [0.0100, 0.0112]);
+    }
+
+    #[test]
+    fn test_mock_voltage_ratio_data_interval() {
+        let mut vin = VoltageRatioInput::new();
+        vin.open().unwrap();
+
+        // A slow host can lower the event rate, within the range of the device
+        vin.set_data_interval(Duration::from_secs(1)).unwrap();
+        assert_eq!(vin.data_interval(), Ok(Duration::from_secs(1)));
+        assert!(matches!(
+            vin.set_data_interval(Duration::from_secs(120)),
+            Err(Error::IntervalOutOfRange { .. })
+        ));
+        assert_eq!(vin.data_interval(), Ok(Duration::from_secs(1)));
     }
 }
@@ -11,14 +11,18 @@
 //
 //! The error return type for the library.
 //!
-//! This uses the integer ReturnCode from the phidget22 library as the Error
-//! type for most most operations. In the underlying library, a value of zero
-//! indicates success and all non-zero values are errors. When returned as
-//! an error, it will always have a non-zero value. As these are internally
-//! represented by a u32, the integer error value is always >0.
+//! The phidget22 library reports failures with an integer `ReturnCode`. In
+//! the underlying library, a value of zero indicates success and all
+//! non-zero values are errors. As these are internally represented by a
+//! u32, the integer error value is always >0.
 //!
-//! The Rust `ReturnCode` is an enumeration that fully implements
-//! std::error::Error.
+//! The crate's `Error` type groups those return codes into the broad
+//! categories that applications typically want to handle, such as a
+//! timeout, a channel that is not attached, or a network failure. The
+//! original `ReturnCode` is always available, both through
+//! `Error::return_code()` and as the error's `source()`.
+//!
+//! Both `Error` and `ReturnCode` fully implement std::error::Error.
 //!
 
 use phidget_sys as ffi;
@@ -27,6 +31,7 @@ use std::{
     fmt,
     os::raw::{c_char, c_uint},
     ptr,
+    time::Duration,
 };
 
 /////////////////////////////////////////////////////////////////////////////
@@ -37,6 +42,7 @@ use std::{
 /// value indicates failure. These are unsigned, so all errors are >0.
 /// This type is a Rust std::error::Error.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u32)]
 #[allow(missing_docs)]
 pub enum ReturnCode {
@@ -95,9 +101,24 @@ impl ReturnCode {
     pub fn result(rc: c_uint) -> Result<()> {
         match rc {
             0 => Ok(()),
-            _ => Err(ReturnCode::from(rc)),
+            _ => Err(Error::from(rc)),
         }
     }
+
+    /// Determines if the code represents a network or server failure.
+    pub fn is_network(&self) -> bool {
+        use ReturnCode::*;
+        matches!(
+            self,
+            Pipe | ConnRef
+                | BadPassword
+                | Resolv
+                | NetUnavail
+                | ConnReset
+                | HostUnreach
+                | KeepAlive
+        )
+    }
 }
 
 impl std::error::Error for ReturnCode {}
@@ -182,8 +203,343 @@ impl From<c_uint> for ReturnCode {
     }
 }
 
-/// The error type for the crate is a phidget22 return code.
-pub type Error = ReturnCode;
+/////////////////////////////////////////////////////////////////////////////
+
+/// Error event codes from the phidget22 library.
+/// These are the codes sent to a channel's error handler to report
+/// asynchronous problems with the device, like a lost packet or a sensor
+/// reading that is out of range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(u32)]
+#[allow(missing_docs)]
+pub enum ErrorEventCode {
+    BadVersion = ffi::Phidget_ErrorEventCode_EEPHIDGET_BADVERSION, // 1
+    Busy = ffi::Phidget_ErrorEventCode_EEPHIDGET_BUSY,             // 2
+    Network = ffi::Phidget_ErrorEventCode_EEPHIDGET_NETWORK,       // 3
+    Dispatch = ffi::Phidget_ErrorEventCode_EEPHIDGET_DISPATCH,     // 4
+    Failure = ffi::Phidget_ErrorEventCode_EEPHIDGET_FAILURE,       // 5
+    Ok = ffi::Phidget_ErrorEventCode_EEPHIDGET_OK,                 // 4096
+    Overrun = ffi::Phidget_ErrorEventCode_EEPHIDGET_OVERRUN,       // 4098
+    PacketLost = ffi::Phidget_ErrorEventCode_EEPHIDGET_PACKETLOST, // 4099
+    Wrap = ffi::Phidget_ErrorEventCode_EEPHIDGET_WRAP,             // 4100
+    OverTemp = ffi::Phidget_ErrorEventCode_EEPHIDGET_OVERTEMP,     // 4101
+    OverCurrent = ffi::Phidget_ErrorEventCode_EEPHIDGET_OVERCURRENT, // 4102
+    OutOfRange = ffi::Phidget_ErrorEventCode_EEPHIDGET_OUTOFRANGE, // 4103
+    BadPower = ffi::Phidget_ErrorEventCode_EEPHIDGET_BADPOWER,     // 4104
+    Saturation = ffi::Phidget_ErrorEventCode_EEPHIDGET_SATURATION, // 4105
+    OverVoltage = ffi::Phidget_ErrorEventCode_EEPHIDGET_OVERVOLTAGE, // 4107
+    Failsafe = ffi::Phidget_ErrorEventCode_EEPHIDGET_FAILSAFE,     // 4108
+    VoltageError = ffi::Phidget_ErrorEventCode_EEPHIDGET_VOLTAGEERROR, // 4109
+    EnergyDump = ffi::Phidget_ErrorEventCode_EEPHIDGET_ENERGYDUMP, // 4110
+    MotorStall = ffi::Phidget_ErrorEventCode_EEPHIDGET_MOTORSTALL, // 4111
+    InvalidState = ffi::Phidget_ErrorEventCode_EEPHIDGET_INVALIDSTATE, // 4112
+    BadConnection = ffi::Phidget_ErrorEventCode_EEPHIDGET_BADCONNECTION, // 4113
+    OutOfRangeHigh = ffi::Phidget_ErrorEventCode_EEPHIDGET_OUTOFRANGEHIGH, // 4114
+    OutOfRangeLow = ffi::Phidget_ErrorEventCode_EEPHIDGET_OUTOFRANGELOW, // 4115
+    Fault = ffi::Phidget_ErrorEventCode_EEPHIDGET_FAULT,           // 4116
+    EStop = ffi::Phidget_ErrorEventCode_EEPHIDGET_ESTOP,           // 4117
+}
+
+impl From<c_uint> for ErrorEventCode {
+    /// Converts an unsigned integer into an `ErrorEventCode`.
+    /// Any unknown integer value is returned as an
+    /// `ErrorEventCode::Failure`.
+    fn from(val: c_uint) -> Self {
+        use ErrorEventCode::*;
+        match val {
+            ffi::Phidget_ErrorEventCode_EEPHIDGET_BADVERSION => BadVersion,
+            ffi::Phidget_ErrorEventCode_EEPHIDGET_BUSY => Busy,
+            ffi::Phidget_ErrorEventCode_EEPHIDGET_NETWORK => Network,
+            ffi::Phidget_ErrorEventCode_EEPHIDGET_DISPATCH => Dispatch,
+            ffi::Phidget_ErrorEventCode_EEPHIDGET_OK => Ok,
+            ffi::Phidget_ErrorEventCode_EEPHIDGET_OVERRUN => Overrun,
+            ffi::Phidget_ErrorEventCode_EEPHIDGET_PACKETLOST => PacketLost,
+            ffi::Phidget_ErrorEventCode_EEPHIDGET_WRAP => Wrap,
+            ffi::Phidget_ErrorEventCode_EEPHIDGET_OVERTEMP => OverTemp,
+            ffi::Phidget_ErrorEventCode_EEPHIDGET_OVERCURRENT => OverCurrent,
+            ffi::Phidget_ErrorEventCode_EEPHIDGET_OUTOFRANGE => OutOfRange,
+            ffi::Phidget_ErrorEventCode_EEPHIDGET_BADPOWER => BadPower,
+            ffi::Phidget_ErrorEventCode_EEPHIDGET_SATURATION => Saturation,
+            ffi::Phidget_ErrorEventCode_EEPHIDGET_OVERVOLTAGE => OverVoltage,
+            ffi::Phidget_ErrorEventCode_EEPHIDGET_FAILSAFE => Failsafe,
+            ffi::Phidget_ErrorEventCode_EEPHIDGET_VOLTAGEERROR => VoltageError,
+            ffi::Phidget_ErrorEventCode_EEPHIDGET_ENERGYDUMP => EnergyDump,
+            ffi::Phidget_ErrorEventCode_EEPHIDGET_MOTORSTALL => MotorStall,
+            ffi::Phidget_ErrorEventCode_EEPHIDGET_INVALIDSTATE => InvalidState,
+            ffi::Phidget_ErrorEventCode_EEPHIDGET_BADCONNECTION => BadConnection,
+            ffi::Phidget_ErrorEventCode_EEPHIDGET_OUTOFRANGEHIGH => OutOfRangeHigh,
+            ffi::Phidget_ErrorEventCode_EEPHIDGET_OUTOFRANGELOW => OutOfRangeLow,
+            ffi::Phidget_ErrorEventCode_EEPHIDGET_FAULT => Fault,
+            ffi::Phidget_ErrorEventCode_EEPHIDGET_ESTOP => EStop,
+            _ => Failure,
+        }
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+/// The error type for the crate.
+///
+/// This sorts the phidget22 return codes into categories that can be
+/// matched on directly. Codes without a category of their own are
+/// reported as `Error::Phidget`. The underlying return code is always
+/// available as the `source()` of the error.
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Error {
+    /// The operation timed out.
+    Timeout,
+    /// The channel is not attached to a device.
+    NotAttached,
+    /// The operation is not supported by the channel or device.
+    Unsupported,
+    /// The device or a required resource is busy.
+    Busy,
+    /// An invalid argument was passed to the library.
+    InvalidArg,
+    /// The value is not (yet) known, or is out of the measurable range.
+    UnknownValue(ReturnCode),
+    /// A network or server error.
+    Network(ReturnCode),
+    /// Any other error returned by the phidget22 library.
+    Phidget(ReturnCode),
+    /// The phidget22 library is older than the version the crate was
+    /// built against.
+    Version {
+        /// The version of the library that was loaded
+        library: String,
+        /// The version the bindings were generated from
+        bindings: String,
+    },
+    /// The phidget22 library couldn't be loaded at runtime, with the
+    /// reason why.
+    LibraryNotFound(String),
+    /// The data interval is outside the range supported by the device.
+    IntervalOutOfRange {
+        /// The interval that was requested
+        interval: Duration,
+        /// The minimum interval for the device
+        min: Duration,
+        /// The maximum interval for the device
+        max: Duration,
+    },
+}
+
+impl Error {
+    /// Gets the phidget22 return code that produced the error.
+    pub fn return_code(&self) -> ReturnCode {
+        use Error::*;
+        match self {
+            Timeout => ReturnCode::Timeout,
+            NotAttached => ReturnCode::NotAttached,
+            Unsupported => ReturnCode::Unsupported,
+            Busy => ReturnCode::Busy,
+            InvalidArg | IntervalOutOfRange { .. } => ReturnCode::InvalidArg,
+            UnknownValue(rc) | Network(rc) | Phidget(rc) => *rc,
+            Version { .. } => ReturnCode::BadVersion,
+            LibraryNotFound(_) => ReturnCode::NoEnt,
+        }
+    }
+
+    /// Determines if the error is for a value that isn't known yet, like
+    /// the reading of a sensor before its first sample has arrived.
+    ///
+    /// This is not true for a value that's out of the measurable range.
+    pub fn is_not_yet_known(&self) -> bool {
+        matches!(self, Error::UnknownValue(ReturnCode::UnknownVal))
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use Error::*;
+        match self {
+            Timeout => Some(&ReturnCode::Timeout),
+            NotAttached => Some(&ReturnCode::NotAttached),
+            Unsupported => Some(&ReturnCode::Unsupported),
+            Busy => Some(&ReturnCode::Busy),
+            InvalidArg | IntervalOutOfRange { .. } => Some(&ReturnCode::InvalidArg),
+            UnknownValue(rc) | Network(rc) | Phidget(rc) => Some(rc),
+            Version { .. } => Some(&ReturnCode::BadVersion),
+            LibraryNotFound(_) => Some(&ReturnCode::NoEnt),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Error::*;
+        match self {
+            Timeout => write!(f, "operation timed out"),
+            NotAttached => write!(f, "channel not attached"),
+            Unsupported => write!(f, "operation not supported"),
+            Busy => write!(f, "device busy"),
+            InvalidArg => write!(f, "invalid argument"),
+            UnknownValue(rc) => write!(f, "value unknown ({})", *rc as u32),
+            Network(rc) => write!(f, "network error ({})", *rc as u32),
+            Phidget(rc) => write!(f, "phidget22 error ({})", *rc as u32),
+            Version { library, bindings } => write!(
+                f,
+                "phidget22 library version {} is older than the bindings ({})",
+                library, bindings
+            ),
+            LibraryNotFound(reason) => write!(f, "phidget22 library not found: {}", reason),
+            IntervalOutOfRange { interval, min, max } => write!(
+                f,
+                "data interval of {:?} is out of the range of the device ({:?} to {:?})",
+                interval, min, max
+            ),
+        }
+    }
+}
+
+impl From<ReturnCode> for Error {
+    /// Sorts a phidget22 return code into its error category.
+    fn from(rc: ReturnCode) -> Self {
+        use ReturnCode::*;
+        match rc {
+            Timeout => Error::Timeout,
+            NotAttached => Error::NotAttached,
+            Unsupported => Error::Unsupported,
+            Busy => Error::Busy,
+            InvalidArg => Error::InvalidArg,
+            UnknownVal | UnknownValHigh | UnknownValLow => Error::UnknownValue(rc),
+            _ if rc.is_network() => Error::Network(rc),
+            _ => Error::Phidget(rc),
+        }
+    }
+}
+
+impl From<c_uint> for Error {
+    /// Converts a raw, non-zero, phidget22 return code into an error.
+    fn from(rc: c_uint) -> Self {
+        #[cfg(feature = "dlopen")]
+        if rc == ffi::dlopen::PhidgetReturnCode_EPHIDGET_LIBRARYNOTFOUND {
+            return Error::LibraryNotFound(ffi::dlopen::load_error().unwrap_or_default());
+        }
+        Self::from(ReturnCode::from(rc))
+    }
+}
+
+#[cfg(feature = "embedded-hal")]
+impl embedded_hal::digital::Error for Error {
+    fn kind(&self) -> embedded_hal::digital::ErrorKind {
+        embedded_hal::digital::ErrorKind::Other
+    }
+}
+
+#[cfg(feature = "embedded-hal")]
+impl embedded_hal::pwm::Error for Error {
+    fn kind(&self) -> embedded_hal::pwm::ErrorKind {
+        embedded_hal::pwm::ErrorKind::Other
+    }
+}
 
 /// The default result type for the phidget-rs library
 pub type Result<T> = std::result::Result<T, Error>;
+
+/// Converts the result of a getter into an optional value.
+///
+/// Right after a channel attaches, its readings are not known until the
+/// first sample arrives, and the getters fail with an `UnknownValue`
+/// error. This turns that case into `Ok(None)`, so the startup race can
+/// be handled without matching on the error, as with
+/// `sensor.temperature().known()?`:
+///
+/// ```rust
+/// use phidget::{Error, KnownValue, Result, ReturnCode};
+///
+/// // A reading from a channel that hasn't sent its first sample yet
+/// let temp: Result<f64> = Err(Error::UnknownValue(ReturnCode::UnknownVal));
+/// assert_eq!(temp.known()?, None);
+///
+/// let temp: Result<f64> = Ok(21.5);
+/// if let Some(temp) = temp.known()? {
+///     println!("Temperature: {}", temp);
+/// }
+/// # Ok::<(), Error>(())
+/// ```
+pub trait KnownValue<T> {
+    /// Gets the value, or `None` if it isn't known yet.
+    fn known(self) -> Result<Option<T>>;
+}
+
+impl<T> KnownValue<T> for Result<T> {
+    fn known(self) -> Result<Option<T>> {
+        match self {
+            Ok(val) => Ok(Some(val)),
+            Err(err) if err.is_not_yet_known() => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::error::Error as StdError;
+
+    #[test]
+    fn test_error_categories() {
+        assert_eq!(Error::from(ReturnCode::Timeout), Error::Timeout);
+        assert_eq!(Error::from(52), Error::NotAttached);
+        assert_eq!(
+            Error::from(ReturnCode::HostUnreach),
+            Error::Network(ReturnCode::HostUnreach)
+        );
+        assert_eq!(
+            Error::from(ReturnCode::UnknownValHigh),
+            Error::UnknownValue(ReturnCode::UnknownValHigh)
+        );
+        assert_eq!(Error::from(ReturnCode::Io), Error::Phidget(ReturnCode::Io));
+    }
+
+    #[test]
+    fn test_error_display() {
+        let high = Error::UnknownValue(ReturnCode::UnknownValHigh);
+        let low = Error::UnknownValue(ReturnCode::UnknownValLow);
+        assert_ne!(high.to_string(), low.to_string());
+        assert_eq!(
+            high.to_string(),
+            format!("value unknown ({})", ReturnCode::UnknownValHigh as u32)
+        );
+
+        let err = Error::Network(ReturnCode::HostUnreach);
+        assert_eq!(
+            err.to_string(),
+            format!("network error ({})", ReturnCode::HostUnreach as u32)
+        );
+    }
+
+    #[test]
+    fn test_error_source() {
+        let err = Error::Busy;
+        assert_eq!(err.return_code(), ReturnCode::Busy);
+        let src = err.source().unwrap().downcast_ref::<ReturnCode>();
+        assert_eq!(src, Some(&ReturnCode::Busy));
+    }
+
+    #[test]
+    fn test_result() {
+        assert!(ReturnCode::result(0).is_ok());
+        assert_eq!(ReturnCode::result(3), Err(Error::Timeout));
+    }
+
+    #[test]
+    fn test_known() {
+        assert_eq!(Ok(1.5).known(), Ok(Some(1.5)));
+        let unknown: Result<f64> = Err(ReturnCode::UnknownVal.into());
+        assert_eq!(unknown.known(), Ok(None));
+        // Out of range is still an error
+        let high: Result<f64> = Err(ReturnCode::UnknownValHigh.into());
+        assert_eq!(
+            high.known(),
+            Err(Error::UnknownValue(ReturnCode::UnknownValHigh))
+        );
+        assert_eq!(Result::<f64>::Err(Error::Busy).known(), Err(Error::Busy));
+    }
+}
@@ -0,0 +1,217 @@
+// phidget-rs/src/events.rs
+//
+// This file is part of the 'phidget-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+//! A crate-wide bus of device events.
+//!
+//! Every channel created by the crate reports its attach, detach, and
+//! error events to a single bus. A supervisor can subscribe to the bus to
+//! watch all of the hardware from one place, rather than registering
+//! handlers on each channel:
+//!
+//! ```text
+//! let events = phidget::events::subscribe();
+//! for ev in events.iter() {
+//!     match ev {
+//!         DeviceEvent::Attached(src) => println!("Attached: {:?}", src),
+//!         DeviceEvent::Detached(src) => println!("Detached: {:?}", src),
+//!         DeviceEvent::Error(src, code, descr) => println!("Error: {:?} {:?} {}", src, code, descr),
+//!     }
+//! }
+//! ```
+//!
+//! The bus is independent of the event handlers that are set on the
+//! channels; those continue to be called as usual.
+//!
+
+use crate::{ChannelClass, ErrorEventCode};
+use phidget_sys::{self as ffi, PhidgetHandle};
+use std::{
+    os::raw::c_uint,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc::{self, Receiver, Sender},
+        Mutex,
+    },
+};
+
+/// The channel that reported an event.
+///
+/// This identifies the physical channel by its class and address, since
+/// the channel object itself might be gone by the time the event is read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EventSource {
+    /// The class of the channel
+    pub channel_class: ChannelClass,
+    /// The serial number of the device, or the VINT Hub
+    pub serial_number: i32,
+    /// The port on the VINT Hub
+    pub hub_port: i32,
+    /// The index of the channel on the device
+    pub channel: i32,
+}
+
+impl EventSource {
+    /// Reads the source information from the channel.
+    /// Any values that can't be read are left as the "any" values, -1.
+    pub(crate) fn from_handle(phid: PhidgetHandle) -> Self {
+        let mut cls: c_uint = 0;
+        let channel_class = match unsafe { ffi::Phidget_getChannelClass(phid, &mut cls) } {
+            0 => ChannelClass::try_from(cls).unwrap_or(ChannelClass::Nothing),
+            _ => ChannelClass::Nothing,
+        };
+
+        let addr = crate::cache::address(phid);
+        Self {
+            channel_class,
+            serial_number: addr.map(|a| a.serial_number).unwrap_or(-1),
+            hub_port: addr.map(|a| a.hub_port).unwrap_or(-1),
+            channel: addr.map(|a| a.channel).unwrap_or(-1),
+        }
+    }
+}
+
+/// An event from one of the channels.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DeviceEvent {
+    /// The channel attached to a device
+    Attached(EventSource),
+    /// The channel detached from its device
+    Detached(EventSource),
+    /// The channel reported an error, with a description of it
+    Error(EventSource, ErrorEventCode, String),
+}
+
+// The subscribers to the bus
+static SUBSCRIBERS: Mutex<Vec<Sender<DeviceEvent>>> = Mutex::new(Vec::new());
+
+// A listener inside the crate, called on the thread that reports the event,
+// with the handle of the channel that it's from.
+type TapFn = dyn Fn(PhidgetHandle, &DeviceEvent) + Send + 'static;
+
+// The listeners inside the crate, with their IDs
+static TAPS: Mutex<Vec<(u64, Box<TapFn>)>> = Mutex::new(Vec::new());
+
+// The ID for the next listener
+static NEXT_TAP_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Subscribes to the event bus.
+///
+/// The receiver gets every event reported after the call, from all the
+/// channels. Dropping the receiver unsubscribes it.
+pub fn subscribe() -> Receiver<DeviceEvent> {
+    let (tx, rx) = mpsc::channel();
+    SUBSCRIBERS
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .push(tx);
+    rx
+}
+
+/// Adds a listener from inside the crate.
+///
+/// Unlike a subscriber, the listener is called on the thread that reports
+/// the event, as it happens, so it sees the events in order with any
+/// other callbacks on the channels. It's also given the handle of the
+/// channel, to tell apart channels that have the same address. Returns an
+/// ID to remove it.
+pub(crate) fn add_tap<F>(f: F) -> u64
+where
+    F: Fn(PhidgetHandle, &DeviceEvent) + Send + 'static,
+{
+    let id = NEXT_TAP_ID.fetch_add(1, Ordering::Relaxed);
+    TAPS.lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .push((id, Box::new(f)));
+    id
+}
+
+/// Removes a listener that was added with `add_tap()`.
+pub(crate) fn remove_tap(id: u64) {
+    TAPS.lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .retain(|(tap_id, _)| *tap_id != id);
+}
+
+/// Publishes an event from the channel to all of the subscribers and
+/// listeners.
+///
+/// The event is only created if there are any subscribers or listeners,
+/// so that the channel information isn't read from the library
+/// needlessly.
+pub(crate) fn publish<F>(phid: PhidgetHandle, f: F)
+where
+    F: FnOnce() -> DeviceEvent,
+{
+    let Ok(mut subs) = SUBSCRIBERS.lock()
+    else {
+        return;
+    };
+    let taps = TAPS.lock().unwrap_or_else(|err| err.into_inner());
+
+    if subs.is_empty() && taps.is_empty() {
+        return;
+    }
+    let ev = f();
+    for (_, tap) in taps.iter() {
+        tap(phid, &ev);
+    }
+    subs.retain(|tx| tx.send(ev.clone()).is_ok());
+}
+
+/// Publishes an attach event for the channel.
+pub(crate) fn attached(phid: PhidgetHandle) {
+    publish(phid, || {
+        DeviceEvent::Attached(EventSource::from_handle(phid))
+    });
+}
+
+/// Publishes a detach event for the channel.
+pub(crate) fn detached(phid: PhidgetHandle) {
+    publish(phid, || {
+        DeviceEvent::Detached(EventSource::from_handle(phid))
+    });
+}
+
+/// Publishes an error event for the channel.
+pub(crate) fn error(phid: PhidgetHandle, code: ErrorEventCode, descr: &str) {
+    publish(phid, || {
+        DeviceEvent::Error(EventSource::from_handle(phid), code, descr.into())
+    });
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ptr;
+
+    #[test]
+    fn test_publish() {
+        let src = EventSource {
+            channel_class: ChannelClass::TemperatureSensor,
+            serial_number: 12345,
+            hub_port: 2,
+            channel: 0,
+        };
+
+        let rx1 = subscribe();
+        let rx2 = subscribe();
+        publish(ptr::null_mut(), || DeviceEvent::Attached(src));
+        assert_eq!(rx1.try_recv(), Ok(DeviceEvent::Attached(src)));
+        assert_eq!(rx2.try_recv(), Ok(DeviceEvent::Attached(src)));
+
+        // A dropped receiver is unsubscribed on the next event
+        drop(rx2);
+        publish(ptr::null_mut(), || DeviceEvent::Detached(src));
+        assert_eq!(rx1.try_recv(), Ok(DeviceEvent::Detached(src)));
+    }
+}
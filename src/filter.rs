@@ -0,0 +1,406 @@
+// phidget-rs/src/filter.rs
+//
+// This file is part of the 'phidget-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+//! Smoothing filters for sensor values.
+//!
+//! A control loop usually wants a steadier value than the raw readings of
+//! a sensor. The filters here each take a sequence of readings and
+//! produce smoothed values:
+//!
+//! - `MovingAverage` - the mean of the last N readings
+//! - `Median` - the median of the last N readings, which rejects spikes
+//! - `LowPass` - a single-pole IIR filter, which smooths with very little
+//!   state
+//!
+//! A `Filtered` sensor wraps any `Sensor` and applies a filter to the
+//! values delivered to its change handler, while the raw values are still
+//! available:
+//!
+//! ```text
+//! let sensor = Filtered::new(VoltageInput::new(), MovingAverage::new(8));
+//! let _handler = sensor.on_change_with_raw(|_, raw, val| {
+//!     println!("Voltage: {} (raw {})", val, raw);
+//! })?;
+//! ```
+//!
+//! With the `async` feature, a filter can also be applied to an event
+//! stream of readings with `FilteredStream`.
+//!
+
+use crate::{HandlerGuard, Phidget, Result, Sensor};
+use phidget_sys::PhidgetHandle;
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+#[cfg(feature = "async")]
+use futures_core::Stream;
+#[cfg(feature = "async")]
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+/// A filter that smooths a sequence of readings.
+pub trait Filter: Send + 'static {
+    /// Adds a reading to the filter, and returns the filtered value.
+    fn update(&mut self, x: f64) -> f64;
+
+    /// Clears the filter, so that it starts over with the next reading.
+    fn reset(&mut self);
+}
+
+/// The mean of the last N readings.
+///
+/// Until N readings have been added, this is the mean of the ones so far.
+#[derive(Debug, Clone)]
+pub struct MovingAverage {
+    // The readings in the window
+    window: VecDeque<f64>,
+    // The size of the window
+    len: usize,
+    // The sum of the readings in the window
+    sum: f64,
+}
+
+impl MovingAverage {
+    /// Creates a moving average over the specified number of readings.
+    ///
+    /// A window of zero is treated as one, which passes the readings
+    /// through.
+    pub fn new(len: usize) -> Self {
+        let len = len.max(1);
+        Self {
+            window: VecDeque::with_capacity(len),
+            len,
+            sum: 0.0,
+        }
+    }
+}
+
+impl Filter for MovingAverage {
+    fn update(&mut self, x: f64) -> f64 {
+        if self.window.len() == self.len {
+            if let Some(old) = self.window.pop_front() {
+                self.sum -= old;
+            }
+        }
+        self.window.push_back(x);
+        self.sum += x;
+        self.sum / self.window.len() as f64
+    }
+
+    fn reset(&mut self) {
+        self.window.clear();
+        self.sum = 0.0;
+    }
+}
+
+/// The median of the last N readings.
+///
+/// This rejects short spikes entirely, as long as they fill less than
+/// half of the window. With an even number of readings, it's the mean of
+/// the middle two.
+#[derive(Debug, Clone)]
+pub struct Median {
+    // The readings in the window
+    window: VecDeque<f64>,
+    // The size of the window
+    len: usize,
+}
+
+impl Median {
+    /// Creates a median filter over the specified number of readings.
+    ///
+    /// A window of zero is treated as one, which passes the readings
+    /// through.
+    pub fn new(len: usize) -> Self {
+        let len = len.max(1);
+        Self {
+            window: VecDeque::with_capacity(len),
+            len,
+        }
+    }
+}
+
+impl Filter for Median {
+    fn update(&mut self, x: f64) -> f64 {
+        if self.window.len() == self.len {
+            self.window.pop_front();
+        }
+        self.window.push_back(x);
+
+        let mut sorted: Vec<f64> = self.window.iter().copied().collect();
+        sorted.sort_by(f64::total_cmp);
+        let n = sorted.len();
+        if n % 2 == 1 {
+            sorted[n / 2]
+        }
+        else {
+            (sorted[n / 2 - 1] + sorted[n / 2]) / 2.0
+        }
+    }
+
+    fn reset(&mut self) {
+        self.window.clear();
+    }
+}
+
+/// A single-pole, low-pass IIR filter.
+///
+/// Each output moves a fraction, alpha, of the way from the previous
+/// output toward the new reading: `y = y + alpha * (x - y)`. The first
+/// reading is passed through, to start the filter.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LowPass {
+    // The smoothing factor, in the range (0, 1]
+    alpha: f64,
+    // The last output, if any
+    last: Option<f64>,
+}
+
+impl LowPass {
+    /// Creates a filter with the smoothing factor, alpha.
+    ///
+    /// This is clamped to the range (0, 1]. Smaller values smooth more,
+    /// and a value of 1 passes the readings through.
+    pub fn new(alpha: f64) -> Self {
+        let alpha = if alpha > 0.0 {
+            alpha.min(1.0)
+        }
+        else {
+            f64::EPSILON
+        };
+        Self { alpha, last: None }
+    }
+
+    /// Creates a filter with the specified time constant, for readings
+    /// that arrive at the specified interval, like the data interval of
+    /// the channel.
+    pub fn with_time_constant(tau: Duration, interval: Duration) -> Self {
+        let dt = interval.as_secs_f64();
+        Self::new(dt / (tau.as_secs_f64() + dt))
+    }
+
+    /// Gets the smoothing factor.
+    pub fn alpha(&self) -> f64 {
+        self.alpha
+    }
+}
+
+impl Filter for LowPass {
+    fn update(&mut self, x: f64) -> f64 {
+        let y = match self.last {
+            Some(y) => y + self.alpha * (x - y),
+            None => x,
+        };
+        self.last = Some(y);
+        y
+    }
+
+    fn reset(&mut self) {
+        self.last = None;
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+/// A sensor with a filter applied to its values.
+///
+/// The filter is shared with any change handler that was set through the
+/// wrapper, and is fed by the change events. Reading the value with
+/// `value()` also feeds the filter, so the two shouldn't be mixed.
+pub struct Filtered<S, F> {
+    // The sensor
+    sensor: S,
+    // The filter, shared with the change handler
+    filter: Arc<Mutex<F>>,
+}
+
+impl<S, F> Filtered<S, F>
+where
+    S: Sensor,
+    S::Value: Into<f64>,
+    F: Filter,
+{
+    /// Wraps a sensor with a filter.
+    pub fn new(sensor: S, filter: F) -> Self {
+        Self {
+            sensor,
+            filter: Arc::new(Mutex::new(filter)),
+        }
+    }
+
+    /// Gets a reference to the underlying sensor.
+    pub fn sensor(&self) -> &S {
+        &self.sensor
+    }
+
+    /// Gets a mutable reference to the underlying sensor.
+    pub fn sensor_mut(&mut self) -> &mut S {
+        &mut self.sensor
+    }
+
+    /// Unwraps the sensor, discarding the filter.
+    pub fn into_inner(self) -> S {
+        self.sensor
+    }
+
+    /// Clears the filter, so that it starts over with the next reading.
+    pub fn reset(&self) {
+        self.filter
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .reset();
+    }
+
+    /// Reads the current value of the sensor, and adds it to the filter,
+    /// returning the filtered value.
+    ///
+    /// This is meant for polling the sensor at a regular interval, such
+    /// as from a control loop.
+    pub fn value(&self) -> Result<f64> {
+        let x = self.sensor.value()?.into();
+        Ok(self
+            .filter
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .update(x))
+    }
+
+    /// Reads the current value of the sensor, without the filter.
+    pub fn raw_value(&self) -> Result<f64> {
+        self.sensor.value().map(Into::into)
+    }
+
+    /// Sets a handler to receive the sensor's filtered value change
+    /// events.
+    ///
+    /// This replaces any change handler that was previously set on the
+    /// sensor. The handler is removed when the returned guard is dropped.
+    pub fn on_change<G>(&self, mut cb: G) -> Result<HandlerGuard>
+    where
+        G: FnMut(&S, f64) + Send + 'static,
+    {
+        self.on_change_with_raw(move |s, _, val| cb(s, val))
+    }
+
+    /// Sets a handler to receive the sensor's value change events, with
+    /// both the raw and the filtered values, in that order.
+    ///
+    /// This replaces any change handler that was previously set on the
+    /// sensor. The handler is removed when the returned guard is dropped.
+    pub fn on_change_with_raw<G>(&self, mut cb: G) -> Result<HandlerGuard>
+    where
+        G: FnMut(&S, f64, f64) + Send + 'static,
+    {
+        let filter = Arc::clone(&self.filter);
+        self.sensor.on_change(move |s, x| {
+            let x = x.into();
+            let val = filter
+                .lock()
+                .unwrap_or_else(|err| err.into_inner())
+                .update(x);
+            cb(s, x, val);
+        })
+    }
+}
+
+impl<S: Phidget, F: Filter> Phidget for Filtered<S, F> {
+    fn as_handle(&mut self) -> PhidgetHandle {
+        self.sensor.as_handle()
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+/// A stream of readings with a filter applied.
+///
+/// This wraps any stream of readings, like the `EventStream` of a sensor,
+/// and yields the filtered values.
+#[cfg(feature = "async")]
+pub struct FilteredStream<St, F> {
+    stream: St,
+    filter: F,
+}
+
+#[cfg(feature = "async")]
+impl<St, F> FilteredStream<St, F>
+where
+    St: Stream + Unpin,
+    St::Item: Into<f64>,
+    F: Filter + Unpin,
+{
+    /// Wraps a stream of readings with a filter.
+    pub fn new(stream: St, filter: F) -> Self {
+        Self { stream, filter }
+    }
+
+    /// Unwraps the stream, discarding the filter.
+    pub fn into_inner(self) -> St {
+        self.stream
+    }
+}
+
+#[cfg(feature = "async")]
+impl<St, F> Stream for FilteredStream<St, F>
+where
+    St: Stream + Unpin,
+    St::Item: Into<f64>,
+    F: Filter + Unpin,
+{
+    type Item = f64;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<f64>> {
+        let this = &mut *self;
+        match Pin::new(&mut this.stream).poll_next(cx) {
+            Poll::Ready(Some(x)) => Poll::Ready(Some(this.filter.update(x.into()))),
+            Poll::Ready(None) => Poll::Ready(None),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run<F: Filter>(mut f: F, xs: &[f64]) -> Vec<f64> {
+        xs.iter().map(|x| f.update(*x)).collect()
+    }
+
+    #[test]
+    fn test_moving_average() {
+        let out = run(MovingAverage::new(3), &[3.0, 6.0, 9.0, 12.0]);
+        assert_eq!(out, vec![3.0, 4.5, 6.0, 9.0]);
+    }
+
+    #[test]
+    fn test_median() {
+        let out = run(Median::new(3), &[1.0, 100.0, 2.0, 3.0, 4.0]);
+        assert_eq!(out, vec![1.0, 50.5, 2.0, 3.0, 3.0]);
+    }
+
+    #[test]
+    fn test_low_pass() {
+        let mut f = LowPass::new(0.5);
+        assert_eq!(run(f, &[10.0, 20.0, 20.0]), vec![10.0, 15.0, 17.5]);
+
+        f.update(10.0);
+        f.reset();
+        assert_eq!(f.update(4.0), 4.0);
+
+        let f = LowPass::with_time_constant(Duration::from_millis(750), Duration::from_millis(250));
+        assert_eq!(f.alpha(), 0.25);
+    }
+}
@@ -0,0 +1,81 @@
+// phidget-rs/src/hal.rs
+//
+// This file is part of the 'phidget-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+//! Support for the embedded-hal traits, so that driver crates written
+//! against them can run on Phidget hardware.
+//!
+//! The digital input and output channels implement the embedded-hal 1.0
+//! digital pin traits directly. The digital outputs also implement its
+//! `pwm::SetDutyCycle` trait, setting their duty cycle as a fraction of
+//! `u16::MAX`.
+//!
+//! embedded-hal 1.0 has no analog traits, so the voltage and voltage
+//! ratio inputs implement the `adc::Channel` trait from embedded-hal 0.2,
+//! as pins of the Phidget converter, `Adc`. They are read through its
+//! `adc::OneShot` implementation:
+//!
+//! ```text
+//! use embedded_hal_02::adc::OneShot;
+//!
+//! let mut adc = phidget::hal::Adc;
+//! let volts: f64 = nb::block!(adc.read(&mut voltage_input))?;
+//! ```
+//!
+
+/// The Phidget analog-to-digital converter, for the embedded-hal 0.2
+/// `adc::OneShot` trait.
+///
+/// The voltage inputs and voltage ratio inputs are its pins. A read gives
+/// the latest value of the channel, in volts or as a ratio, and never
+/// blocks.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Adc;
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::*;
+    use crate::{mock::set_value, DigitalInput, DigitalOutput, Phidget, VoltageInput};
+
+    #[test]
+    fn test_mock_embedded_hal() {
+        use embedded_hal::{
+            digital::{InputPin, OutputPin, StatefulOutputPin},
+            pwm::SetDutyCycle,
+        };
+
+        let mut out = DigitalOutput::new();
+        out.open().unwrap();
+        out.set_high().unwrap();
+        assert_eq!(out.is_set_high(), Ok(true));
+        out.toggle().unwrap();
+        assert_eq!(out.is_set_low(), Ok(true));
+        out.set_duty_cycle_percent(25).unwrap();
+        assert!((out.duty_cycle().unwrap() - 0.25).abs() < 1e-4);
+
+        let mut input = DigitalInput::new();
+        input.open().unwrap();
+        set_value(&mut input, 1.0).unwrap();
+        assert_eq!(input.is_high(), Ok(true));
+        assert_eq!(input.is_low(), Ok(false));
+    }
+
+    #[test]
+    fn test_mock_embedded_hal_adc() {
+        use embedded_hal_02::adc::OneShot;
+
+        let mut vin = VoltageInput::new();
+        vin.open().unwrap();
+        set_value(&mut vin, 3.3).unwrap();
+
+        let volts: f64 = nb::block!(Adc.read(&mut vin)).unwrap();
+        assert_eq!(volts, 3.3);
+    }
+}
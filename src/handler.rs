@@ -0,0 +1,1098 @@
+// phidget-rs/src/handler.rs
+//
+// This file is part of the 'phidget-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+//! Ownership of the event handlers registered on Phidget channels.
+//!
+//! Setting an event handler on a channel returns a `HandlerGuard`. The
+//! handler stays registered for as long as the guard is alive. When the
+//! guard is dropped, the handler is unregistered from the phidget22
+//! library, and the closure, along with anything that it captured, is
+//! released.
+//!
+//! ```text
+//! let _handler = sensor.set_on_temperature_change_handler(|_, t| {
+//!     println!("Temperature: {}", t);
+//! })?;
+//! ```
+//!
+//! The closures are held in a registry, keyed by the channel and the
+//! event. A channel has at most one handler for each event, so setting a
+//! new handler replaces the previous one in the library and releases its
+//! closure right away. The guard for the replaced handler then becomes
+//! inert, and dropping it does not affect the new handler.
+//!
+//! The library might be in the middle of calling a handler, on one of its
+//! own threads, when the handler is removed or replaced. So the calls are
+//! tracked, and a closure is only released once no call to it is in
+//! progress. Removing a handler from outside of any handler waits for its
+//! calls to return. Removing it from within a handler, such as when a
+//! callback drops its own guard, or the guard of another channel's
+//! handler, doesn't wait, since that could deadlock. The closure is then
+//! released when the last call to it returns.
+//!
+//! The handlers for the channel events can return either nothing, or a
+//! `Result<()>`. An error returned by a handler is reported like an error
+//! event from the device, with the code `ErrorEventCode::Failure`, to the
+//! channel's error handler and the event bus:
+//!
+//! ```text
+//! let _handler = sensor.set_on_temperature_change_handler(move |_, t| {
+//!     fan.set_state(t > 30.0)
+//! })?;
+//! ```
+//!
+//! A panic in a handler is caught before it can unwind into the library,
+//! and is reported the same way.
+//!
+
+use crate::{Error, ErrorEventCode, Result, ReturnCode};
+use phidget_sys::{self as ffi, PhidgetHandle};
+use std::{
+    cell::{Cell, RefCell},
+    marker::PhantomData,
+    mem::{self, ManuallyDrop},
+    ops::Deref,
+    os::raw::{c_uint, c_void},
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc::{self, Receiver, Sender},
+        Condvar, Mutex, MutexGuard,
+    },
+    thread::{self, JoinHandle},
+};
+
+// A handler registered for an event on a channel.
+struct Registration {
+    // The channel handle
+    phid: usize,
+    // The name of the event
+    event: &'static str,
+    // A unique ID for the registration
+    id: u64,
+    // Boxed callback context
+    ctx: usize,
+    // Removes the low-level handler from the channel
+    unset: fn(PhidgetHandle) -> c_uint,
+    // Releases the boxed callback context
+    drop_ctx: fn(*mut c_void),
+    // The number of calls to the callback in progress
+    calls: u32,
+    // Set when the handler was removed while a call was in progress
+    removed: bool,
+    // Set when a thread is waiting for the calls in progress to return
+    waiting: bool,
+}
+
+impl Registration {
+    // Releases the callback.
+    // This must not be called while holding the registry lock, since the
+    // closure might own a guard of its own.
+    fn release(self) {
+        (self.drop_ctx)(self.ctx as *mut c_void);
+    }
+}
+
+// The handlers that are currently registered, along with any that were
+// removed and are waiting for calls in progress to return.
+static HANDLERS: Mutex<Vec<Registration>> = Mutex::new(Vec::new());
+
+// Signaled when the last call to a removed handler returns.
+static IDLE: Condvar = Condvar::new();
+
+thread_local! {
+    // The contexts of the callbacks that this thread is calling
+    static DISPATCHING: RefCell<Vec<usize>> = const { RefCell::new(Vec::new()) };
+}
+
+fn handlers() -> MutexGuard<'static, Vec<Registration>> {
+    HANDLERS.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+// The ID for the next registration
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Registers a boxed callback context of type `T` as the handler for an
+/// event on a channel.
+///
+/// The `set` function installs the low-level handler with the context in
+/// the library, and `unset` removes it. Any handler previously registered
+/// for the same event on the channel is replaced, and its callback is
+/// released. If the handler can't be installed, the new callback is
+/// released and the error returned.
+pub(crate) fn register<T, F>(
+    phid: PhidgetHandle,
+    event: &'static str,
+    ctx: *mut c_void,
+    set: F,
+    unset: fn(PhidgetHandle) -> c_uint,
+) -> Result<HandlerGuard>
+where
+    F: FnOnce() -> c_uint,
+{
+    let key = phid as usize;
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+
+    let reg = Registration {
+        phid: key,
+        event,
+        id,
+        ctx: ctx as usize,
+        unset,
+        drop_ctx: drop_ctx::<T>,
+        calls: 0,
+        removed: false,
+        waiting: false,
+    };
+
+    // The library is called without holding the registry lock, since it
+    // might call a handler, on this thread or another, that needs it.
+    // The registration is added first, so that an event delivered as soon
+    // as the handler is set finds it.
+    handlers().push(reg);
+
+    if let Err(err) = ReturnCode::result(set()) {
+        let mut handlers = handlers();
+        if let Some(i) = handlers.iter().position(|r| r.id == id) {
+            let reg = handlers.swap_remove(i);
+            drop(handlers);
+            reg.release();
+        }
+        return Err(err);
+    }
+
+    // The library replaced any previous handler for the event
+    let handlers = handlers();
+    let prev = handlers
+        .iter()
+        .position(|r| !r.removed && r.phid == key && r.event == event && r.id < id)
+        .map(|i| handlers[i].id);
+
+    if let Some(prev) = prev {
+        retire(handlers, prev, false);
+    }
+    Ok(HandlerGuard::new(phid, id))
+}
+
+/// Gets the context of the handler for an event on a channel, or null if
+/// there isn't one.
+pub(crate) fn context(phid: PhidgetHandle, event: &'static str) -> *mut c_void {
+    let key = phid as usize;
+    handlers()
+        .iter()
+        .find(|r| !r.removed && r.phid == key && r.event == event)
+        .map_or(std::ptr::null_mut(), |r| r.ctx as *mut c_void)
+}
+
+/// Unregisters the handler for an event on a channel, if there is one,
+/// and releases its callback.
+pub(crate) fn unregister(phid: PhidgetHandle, event: &'static str) -> Result<()> {
+    let key = phid as usize;
+    remove(|r| r.phid == key && r.event == event)
+}
+
+/// Determines if a handler is registered for an event on a channel.
+pub(crate) fn is_set(phid: PhidgetHandle, event: &'static str) -> bool {
+    let key = phid as usize;
+    handlers()
+        .iter()
+        .any(|r| !r.removed && r.phid == key && r.event == event)
+}
+
+// Unregisters the first handler that matches the predicate, if any,
+// and releases its callback.
+fn remove<P>(pred: P) -> Result<()>
+where
+    P: Fn(&Registration) -> bool,
+{
+    let (id, phid, unset) = {
+        let mut handlers = handlers();
+        let Some(reg) = handlers.iter_mut().find(|r| !r.removed && pred(r))
+        else {
+            return Ok(());
+        };
+
+        // Marked as removed, so that no new calls are started, then
+        // removed from the library without holding the lock.
+        reg.removed = true;
+        (reg.id, reg.phid, reg.unset)
+    };
+
+    let rc = unset(phid as PhidgetHandle);
+    retire(handlers(), id, false);
+    ReturnCode::result(rc)
+}
+
+// Takes the handler with the ID out of the registry, once it has been
+// removed from the library, and releases its callback when no call to it
+// is in progress.
+//
+// If the calling thread is in a call to any handler, the callback is
+// released when the last call to it returns. Waiting there could
+// deadlock, as with two handlers that each remove the other. Otherwise,
+// or if `wait` is set, this waits for the calls on other threads to
+// return.
+fn retire(mut handlers: MutexGuard<'static, Vec<Registration>>, id: u64, wait: bool) {
+    let Some(i) = handlers.iter().position(|r| r.id == id)
+    else {
+        return;
+    };
+
+    if handlers[i].calls != 0 {
+        handlers[i].removed = true;
+        if !wait && DISPATCHING.with(|d| !d.borrow().is_empty()) {
+            return;
+        }
+        handlers[i].waiting = true;
+        handlers = IDLE
+            .wait_while(handlers, |h| h.iter().any(|r| r.id == id && r.calls != 0))
+            .unwrap_or_else(|err| err.into_inner());
+    }
+
+    let Some(i) = handlers.iter().position(|r| r.id == id)
+    else {
+        return;
+    };
+    let reg = handlers.swap_remove(i);
+    drop(handlers);
+    reg.release();
+}
+
+// Finds the ID of the registration for the context on the channel, if
+// it's registered, and not yet removed.
+fn find(key: usize, ctx: usize) -> Option<u64> {
+    handlers()
+        .iter()
+        .find(|r| !r.removed && r.phid == key && r.ctx == ctx)
+        .map(|r| r.id)
+}
+
+/// Delivers an event to a registered callback, from the low-level handler
+/// for the event on a channel.
+///
+/// If a dispatch pool was set up with `set_dispatch_threads()`, the call
+/// is queued to one of its threads. Otherwise it's made right away, on
+/// the calling thread, as with `dispatch()`. The function `f` is given the
+/// channel handle and the context, and must own everything else that it
+/// uses from the event, since it might be called on another thread, after
+/// the low-level handler returns.
+pub(crate) fn deliver<F>(phid: PhidgetHandle, ctx: *mut c_void, f: F)
+where
+    F: FnOnce(PhidgetHandle, *mut c_void) + Send + 'static,
+{
+    let (key, ctx) = (phid as usize, ctx as usize);
+    if ctx == 0 {
+        return;
+    }
+
+    let pool = POOL.lock().unwrap_or_else(|err| err.into_inner());
+    let Some(pool) = pool.as_ref()
+    else {
+        drop(pool);
+        return dispatch(phid, ctx as *mut c_void, || f(phid, ctx as *mut c_void));
+    };
+
+    // The registration is found now, so that the event can't go to a
+    // later handler that happens to get the same context address.
+    if let Some(id) = find(key, ctx) {
+        let job = move || call(key, id, || f(key as PhidgetHandle, ctx as *mut c_void));
+        pool.queue(key, Job(Box::new(job)));
+    }
+}
+
+/// Calls a registered callback, from the low-level handler for an event
+/// on a channel.
+///
+/// The function `f` is only called if the context is registered for the
+/// channel, and not yet removed. The call is tracked so that the context
+/// isn't released while it is in progress. This does nothing for a null
+/// context.
+pub(crate) fn dispatch<F>(phid: PhidgetHandle, ctx: *mut c_void, f: F)
+where
+    F: FnOnce(),
+{
+    let (key, ctx) = (phid as usize, ctx as usize);
+    if ctx == 0 {
+        return;
+    }
+    if let Some(id) = find(key, ctx) {
+        call(key, id, f);
+    }
+}
+
+// Calls the callback for the registration with the ID, if it hasn't been
+// removed, tracking the call.
+fn call<F>(_key: usize, id: u64, f: F)
+where
+    F: FnOnce(),
+{
+    let (ctx, _event) = {
+        let mut handlers = handlers();
+        let Some(reg) = handlers.iter_mut().find(|r| !r.removed && r.id == id)
+        else {
+            return;
+        };
+        reg.calls += 1;
+        (reg.ctx, reg.event)
+    };
+
+    // Marks the end of the call, even if the callback panics
+    struct Call(u64);
+
+    impl Drop for Call {
+        fn drop(&mut self) {
+            DISPATCHING.with(|d| d.borrow_mut().pop());
+
+            let mut handlers = handlers();
+            let Some(i) = handlers.iter().position(|r| r.id == self.0)
+            else {
+                return;
+            };
+            handlers[i].calls -= 1;
+            if handlers[i].calls == 0 && handlers[i].removed {
+                if handlers[i].waiting {
+                    IDLE.notify_all();
+                }
+                else {
+                    let reg = handlers.swap_remove(i);
+                    drop(handlers);
+                    reg.release();
+                }
+            }
+        }
+    }
+
+    DISPATCHING.with(|d| d.borrow_mut().push(ctx));
+    let _call = Call(id);
+
+    #[cfg(feature = "tracing")]
+    let _span = crate::trace::callback_span(_event, _key as PhidgetHandle).entered();
+    #[cfg(feature = "metrics")]
+    let start = std::time::Instant::now();
+    catch(_key as PhidgetHandle, _event, f);
+    #[cfg(feature = "metrics")]
+    crate::metrics::callback(_event, start.elapsed());
+}
+
+/// Calls a handler for an event on a channel, catching any panic, since
+/// unwinding out of a callback from the library is undefined behavior.
+///
+/// A panic is reported as an error event on the channel, with the code
+/// `Failure`. A panic in the error handler itself is only published to
+/// the event bus, so that it isn't fed back into the same handler.
+pub(crate) fn catch<F>(phid: PhidgetHandle, event: &str, f: F)
+where
+    F: FnOnce(),
+{
+    let Err(payload) = panic::catch_unwind(AssertUnwindSafe(f))
+    else {
+        return;
+    };
+
+    let msg = payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown cause");
+    let descr = format!("Event handler panicked: {}", msg);
+
+    match event {
+        "error" => crate::events::error(phid, ErrorEventCode::Failure, &descr),
+        _ => crate::phidget::raise_error(phid, ErrorEventCode::Failure, descr),
+    }
+}
+
+/// The result of an event handler.
+///
+/// Handlers can return nothing, or a `Result<()>`, in which case an error
+/// is reported to the channel's error handler.
+pub trait HandlerResult {
+    /// Converts the return value of the handler into a result.
+    fn into_result(self) -> Result<()>;
+}
+
+impl HandlerResult for () {
+    fn into_result(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl HandlerResult for Result<()> {
+    fn into_result(self) -> Result<()> {
+        self
+    }
+}
+
+/// Reports the error, if any, that a handler returned for an event on a
+/// channel.
+///
+/// The error goes to the channel's error handler, if one is set, and to
+/// the event bus, as an error event with the code `Failure`.
+pub(crate) fn report<R: HandlerResult>(phid: PhidgetHandle, res: R) {
+    if let Err(err) = res.into_result() {
+        crate::phidget::raise_error(phid, ErrorEventCode::Failure, handler_error(&err));
+    }
+}
+
+// Gets the description of an error returned by a handler.
+fn handler_error(err: &Error) -> String {
+    format!("Event handler failed: {}", err)
+}
+
+/////////////////////////////////////////////////////////////////////////////
+// Dispatching the callbacks on a pool of threads
+
+// A call to a callback, queued for a pool thread
+struct Job(Box<dyn FnOnce() + Send>);
+
+// A pool of threads that call the callbacks
+struct Pool {
+    // The queue for each thread
+    queues: Vec<Sender<Job>>,
+    // The threads, to wait for them to finish
+    workers: Vec<JoinHandle<()>>,
+}
+
+thread_local! {
+    // Whether the current thread is one of the pool threads
+    static IN_POOL: Cell<bool> = const { Cell::new(false) };
+}
+
+impl Pool {
+    // Starts a pool with the number of threads.
+    fn new(n: usize) -> Self {
+        let (queues, workers) = (0..n)
+            .map(|i| {
+                let (tx, rx) = mpsc::channel::<Job>();
+                let worker = thread::Builder::new()
+                    .name(format!("phidget-dispatch-{}", i))
+                    .spawn(move || {
+                        IN_POOL.with(|p| p.set(true));
+                        for job in rx {
+                            // A panic in one callback doesn't stop the thread
+                            let _ = panic::catch_unwind(AssertUnwindSafe(job.0));
+                        }
+                    })
+                    .expect("failed to start a dispatch thread");
+                (tx, worker)
+            })
+            .unzip();
+        Self { queues, workers }
+    }
+
+    // Stops the pool, once the threads have made all the queued calls.
+    fn stop(self) {
+        drop(self.queues);
+        for worker in self.workers {
+            let _ = worker.join();
+        }
+    }
+
+    // Queues a call for the channel.
+    // The calls for a channel always go to the same thread, so that they
+    // are made in order, and never at the same time. The low bits of the
+    // handles are skipped, since they're aligned pointers.
+    fn queue(&self, key: usize, job: Job) {
+        let i = (key >> 4) % self.queues.len();
+        let _ = self.queues[i].send(job);
+    }
+}
+
+// The dispatch pool, if the callbacks aren't called directly
+static POOL: Mutex<Option<Pool>> = Mutex::new(None);
+
+/// Sets the number of threads that call the event handlers.
+///
+/// By default, or when this is set to zero, the handlers are called
+/// directly on the phidget22 library's event threads. A handler that
+/// takes a long time then delays the events of other channels.
+///
+/// With one or more threads, the events are queued to a pool of threads
+/// owned by the crate, and the library's threads return right away. The
+/// events for each channel are always handled by the same thread, in
+/// order. Any events still queued for a handler when it's removed are
+/// discarded.
+///
+/// Changing the number of threads waits for the previous pool to make
+/// all of its queued calls, and new events wait to be delivered until it
+/// has, so that a handler is never called on two threads at once, and
+/// the events for each channel stay in order. This fails with `Busy` if
+/// it's called from a handler running on the pool, since it would wait
+/// for itself.
+pub fn set_dispatch_threads(n: usize) -> Result<()> {
+    if IN_POOL.with(|p| p.get()) {
+        return Err(Error::Busy);
+    }
+    let mut pool = POOL.lock().unwrap_or_else(|err| err.into_inner());
+    let prev = mem::replace(&mut *pool, (n > 0).then(|| Pool::new(n)));
+    // The lock is held, to hold back new events, until the queue is empty
+    if let Some(prev) = prev {
+        prev.stop();
+    }
+    Ok(())
+}
+
+/// Gets the number of threads that call the event handlers, or zero if
+/// they are called directly on the library's event threads.
+pub fn dispatch_threads() -> usize {
+    POOL.lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .as_ref()
+        .map_or(0, |pool| pool.queues.len())
+}
+
+// Release the memory held in a boxed callback context.
+fn drop_ctx<T>(ctx: *mut c_void) {
+    let _: Box<T> = unsafe { Box::from_raw(ctx as *mut _) };
+}
+
+/// The context for a channel's event handler.
+///
+/// This holds a non-owning view of the channel along with the closure in
+/// a single heap allocation. The low-level callback, which is generic
+/// over the closure type, can then call the closure directly, without
+/// re-creating the channel wrapper or going through a second box for
+/// every event.
+pub(crate) struct Context<P, F> {
+    /// A view of the channel, which is never dropped
+    pub chan: ManuallyDrop<P>,
+    /// The safe Rust callback
+    pub cb: F,
+}
+
+impl<P, F> Context<P, F> {
+    /// Moves a new context to the heap, returning the raw pointer to it,
+    /// to be passed to the library as the handler's context.
+    pub fn into_raw(chan: P, cb: F) -> *mut c_void {
+        let ctx = Box::new(Self {
+            chan: ManuallyDrop::new(chan),
+            cb,
+        });
+        Box::into_raw(ctx) as *mut c_void
+    }
+}
+
+/// A guard that keeps an event handler registered on a channel.
+///
+/// The handler is unregistered and its closure released when the guard is
+/// dropped. If the handler was already removed or replaced by another
+/// one, dropping the guard has no effect. The guard holds its own
+/// reference to the channel, so it is safe to keep it after the device
+/// object itself is dropped.
+#[must_use = "the handler is removed as soon as the guard is dropped"]
+pub struct HandlerGuard {
+    // A retained handle to the channel
+    phid: PhidgetHandle,
+    // The ID of the registration
+    id: u64,
+}
+
+impl HandlerGuard {
+    // Creates a guard for a registration.
+    fn new(phid: PhidgetHandle, id: u64) -> Self {
+        unsafe {
+            ffi::Phidget_retain(phid);
+        }
+        Self { phid, id }
+    }
+
+    /// Determines if the handler is still registered with the channel.
+    ///
+    /// This is false once the handler has been removed or replaced.
+    pub fn is_registered(&self) -> bool {
+        HANDLERS
+            .lock()
+            .map(|handlers| handlers.iter().any(|r| r.id == self.id && !r.removed))
+            .unwrap_or(false)
+    }
+}
+
+unsafe impl Send for HandlerGuard {}
+
+impl Drop for HandlerGuard {
+    fn drop(&mut self) {
+        let id = self.id;
+        let _ = remove(|r| r.id == id);
+        unsafe {
+            ffi::Phidget_release(&mut self.phid);
+        }
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+/// A scope for event handlers that can borrow from the environment.
+///
+/// Handlers set through the scope don't need to be `'static`, since they
+/// are all removed before `scope()` returns. See `scope()`.
+///
+/// The `_scoped` setters extend the lifetime of their closures to
+/// `'static`, and rely on the scope to remove them in time. That's sound
+/// because the scope, and the guards that it holds, are owned by
+/// `scope()`, and the closure given to it only gets a reference. So the
+/// guards can't be forgotten or leaked, and are always dropped before
+/// `scope()` returns or unwinds. Dropping the scope waits for any call to
+/// its handlers that's in progress, even from within another handler, and
+/// even if the handler was already removed or replaced.
+pub struct HandlerScope<'scope, 'env: 'scope> {
+    // The guards for the handlers set in the scope
+    guards: Mutex<Vec<ScopedGuard>>,
+    // Invariance over the lifetimes, as in `std::thread::Scope`
+    scope: PhantomData<&'scope mut &'scope ()>,
+    env: PhantomData<&'env mut &'env ()>,
+}
+
+impl<'scope, 'env> HandlerScope<'scope, 'env> {
+    /// Keeps the handler for the guard registered until the end of the
+    /// scope.
+    pub(crate) fn hold(&self, guard: HandlerGuard) {
+        self.guards
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .push(ScopedGuard(guard));
+    }
+}
+
+// A guard for a handler set in a scope
+struct ScopedGuard(HandlerGuard);
+
+impl Drop for ScopedGuard {
+    fn drop(&mut self) {
+        let id = self.0.id;
+        let _ = remove(|r| r.id == id);
+        // The handler might have been removed without waiting for its
+        // calls, but the borrows end here.
+        retire(handlers(), id, true);
+    }
+}
+
+/// Creates a scope for event handlers that can borrow local data.
+///
+/// This is similar to `std::thread::scope()`. The closure receives a
+/// `HandlerScope`, and handlers can be set with the `_scoped` variant of
+/// any of the handler setters. Those handlers are allowed to borrow
+/// non-`'static` data, such as local variables, since all of them are
+/// removed before this function returns, even if the closure panics.
+///
+/// ```text
+/// let mut n = 0;
+/// phidget::handler::scope(|s| {
+///     sensor.set_on_temperature_change_handler_scoped(s, |_, _| n += 1)?;
+///     thread::sleep(Duration::from_secs(10));
+///     Ok(())
+/// })?;
+/// println!("There were {} temperature changes", n);
+/// ```
+pub fn scope<'env, F, T>(f: F) -> T
+where
+    F: for<'scope> FnOnce(&'scope HandlerScope<'scope, 'env>) -> T,
+{
+    let scope = HandlerScope {
+        guards: Mutex::new(Vec::new()),
+        scope: PhantomData,
+        env: PhantomData,
+    };
+    f(&scope)
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+/// A receiver for events from a Phidget channel.
+///
+/// This is a `std::sync::mpsc::Receiver` that is fed by one of the
+/// channel's event handlers, and dereferences to it for reading the
+/// events. The handler is removed when the receiver is dropped, and the
+/// receiver is disconnected if that handler is replaced or removed.
+pub struct EventReceiver<T> {
+    rx: Receiver<T>,
+    _guard: HandlerGuard,
+}
+
+impl<T> EventReceiver<T> {
+    /// Creates a receiver from the handler that feeds it.
+    pub(crate) fn new(rx: Receiver<T>, guard: HandlerGuard) -> Self {
+        Self { rx, _guard: guard }
+    }
+}
+
+impl<T> Deref for EventReceiver<T> {
+    type Target = Receiver<T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.rx
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    type TestCallback = dyn FnMut() + Send + 'static;
+
+    // Creates a double-boxed test callback that holds a reference
+    // to the counter.
+    fn test_ctx(count: &Arc<()>) -> *mut c_void {
+        let count = Arc::clone(count);
+        let cb: Box<Box<TestCallback>> = Box::new(Box::new(move || {
+            let _ = &count;
+        }));
+        Box::into_raw(cb) as *mut c_void
+    }
+
+    #[test]
+    fn test_replace_and_drop() {
+        let phid = 0x1000 as PhidgetHandle;
+        let count = Arc::new(());
+
+        let ctx = test_ctx(&count);
+        let guard1 = register::<Box<TestCallback>, _>(phid, "test", ctx, || 0, |_| 0).unwrap();
+        assert!(guard1.is_registered());
+        assert_eq!(Arc::strong_count(&count), 2);
+
+        // Replacing the handler releases the first callback
+        let ctx = test_ctx(&count);
+        let guard2 = register::<Box<TestCallback>, _>(phid, "test", ctx, || 0, |_| 0).unwrap();
+        assert!(!guard1.is_registered());
+        assert!(guard2.is_registered());
+        assert_eq!(Arc::strong_count(&count), 2);
+
+        // Dropping the stale guard doesn't affect the new handler
+        drop(guard1);
+        assert!(guard2.is_registered());
+        assert_eq!(Arc::strong_count(&count), 2);
+
+        drop(guard2);
+        assert_eq!(Arc::strong_count(&count), 1);
+    }
+
+    #[test]
+    fn test_unregister() {
+        let phid = 0x2000 as PhidgetHandle;
+        let count = Arc::new(());
+
+        let ctx = test_ctx(&count);
+        let guard = register::<Box<TestCallback>, _>(phid, "test", ctx, || 0, |_| 0).unwrap();
+        unregister(phid, "test").unwrap();
+        assert!(!guard.is_registered());
+        assert_eq!(Arc::strong_count(&count), 1);
+
+        // A failure to set the handler releases the callback
+        let ctx = test_ctx(&count);
+        let res = register::<Box<TestCallback>, _>(phid, "test", ctx, || 20, |_| 0);
+        assert!(res.is_err());
+        assert_eq!(Arc::strong_count(&count), 1);
+    }
+
+    #[test]
+    fn test_scope() {
+        let phid = 0x3000 as PhidgetHandle;
+        let count = Arc::new(());
+
+        let id = scope(|s| {
+            let ctx = test_ctx(&count);
+            let guard = register::<Box<TestCallback>, _>(phid, "test", ctx, || 0, |_| 0).unwrap();
+            let id = guard.id;
+            s.hold(guard);
+            assert_eq!(Arc::strong_count(&count), 2);
+            id
+        });
+
+        // The handler was removed at the end of the scope
+        let handlers = HANDLERS.lock().unwrap();
+        assert!(!handlers.iter().any(|r| r.id == id));
+        assert_eq!(Arc::strong_count(&count), 1);
+    }
+
+    #[test]
+    fn test_scope_forget() {
+        let phid = 0x3100 as PhidgetHandle;
+        let count = Arc::new(());
+
+        // The closure only has a reference to the scope, so there's no
+        // way to forget or leak it, and keep the handler registered.
+        let id = scope(|s| {
+            let ctx = test_ctx(&count);
+            let guard = register::<Box<TestCallback>, _>(phid, "test", ctx, || 0, |_| 0).unwrap();
+            let id = guard.id;
+            s.hold(guard);
+            #[allow(forgetting_references)]
+            mem::forget(s);
+            id
+        });
+
+        let handlers = HANDLERS.lock().unwrap();
+        assert!(!handlers.iter().any(|r| r.id == id));
+        assert_eq!(Arc::strong_count(&count), 1);
+    }
+
+    #[test]
+    fn test_scope_waits_for_call() {
+        use std::{
+            sync::{atomic::AtomicBool, mpsc},
+            time::Duration,
+        };
+
+        let (phid1, phid2) = (0x3200 as PhidgetHandle, 0x3300 as PhidgetHandle);
+        let count = Arc::new(());
+
+        let ctx1 = test_ctx(&count);
+        let _guard = register::<Box<TestCallback>, _>(phid1, "test", ctx1, || 0, |_| 0).unwrap();
+
+        // A scope ending within a handler still waits for the calls to its
+        // handlers on other threads, even one that was already removed.
+        let done = Arc::new(AtomicBool::new(false));
+        dispatch(phid1, ctx1, || {
+            scope(|s| {
+                let ctx2 = test_ctx(&count);
+                let guard =
+                    register::<Box<TestCallback>, _>(phid2, "test", ctx2, || 0, |_| 0).unwrap();
+                s.hold(guard);
+
+                let (tx, rx) = mpsc::channel();
+                let thr_done = Arc::clone(&done);
+                let (key, ctx) = (phid2 as usize, ctx2 as usize);
+                thread::spawn(move || {
+                    dispatch(key as PhidgetHandle, ctx as *mut c_void, || {
+                        tx.send(()).unwrap();
+                        thread::sleep(Duration::from_millis(50));
+                        thr_done.store(true, Ordering::SeqCst);
+                    });
+                });
+                rx.recv().unwrap();
+                unregister(phid2, "test").unwrap();
+            });
+            assert!(done.load(Ordering::SeqCst));
+        });
+        assert_eq!(Arc::strong_count(&count), 2);
+    }
+
+    #[test]
+    fn test_remove_during_call() {
+        let phid = 0x4000 as PhidgetHandle;
+        let count = Arc::new(());
+
+        // Removed from within its own call, it's released when the call returns
+        let ctx = test_ctx(&count);
+        let guard = register::<Box<TestCallback>, _>(phid, "test", ctx, || 0, |_| 0).unwrap();
+        dispatch(phid, ctx, || {
+            unregister(phid, "test").unwrap();
+            assert!(!guard.is_registered());
+            assert_eq!(Arc::strong_count(&count), 2);
+        });
+        assert_eq!(Arc::strong_count(&count), 1);
+
+        // A removed handler isn't called
+        let mut called = false;
+        dispatch(phid, ctx, || called = true);
+        assert!(!called);
+    }
+
+    #[test]
+    fn test_remove_waits_for_call() {
+        use std::{
+            sync::{atomic::AtomicBool, mpsc},
+            thread,
+            time::Duration,
+        };
+
+        let phid = 0x5000 as PhidgetHandle;
+        let count = Arc::new(());
+
+        let ctx = test_ctx(&count);
+        let guard = register::<Box<TestCallback>, _>(phid, "test", ctx, || 0, |_| 0).unwrap();
+
+        let (tx, rx) = mpsc::channel();
+        let done = Arc::new(AtomicBool::new(false));
+        let thr_done = Arc::clone(&done);
+        let (key, ctx) = (phid as usize, ctx as usize);
+        let thr = thread::spawn(move || {
+            dispatch(key as PhidgetHandle, ctx as *mut c_void, || {
+                tx.send(()).unwrap();
+                thread::sleep(Duration::from_millis(50));
+                thr_done.store(true, Ordering::SeqCst);
+            });
+        });
+
+        // Dropping the guard waits for the call to return
+        rx.recv().unwrap();
+        drop(guard);
+        assert!(done.load(Ordering::SeqCst));
+        assert_eq!(Arc::strong_count(&count), 1);
+        thr.join().unwrap();
+    }
+
+    #[test]
+    fn test_remove_each_other() {
+        use std::sync::Barrier;
+
+        let (phid1, phid2) = (0x7000 as PhidgetHandle, 0x7100 as PhidgetHandle);
+        let count = Arc::new(());
+
+        // The library isn't called with the registry locked
+        let ctx1 = test_ctx(&count);
+        let _guard1 = register::<Box<TestCallback>, _>(
+            phid1,
+            "test",
+            ctx1,
+            || {
+                let _ = context(phid1, "test");
+                0
+            },
+            |_| 0,
+        )
+        .unwrap();
+        let ctx2 = test_ctx(&count);
+        let _guard2 = register::<Box<TestCallback>, _>(
+            phid2,
+            "test",
+            ctx2,
+            || 0,
+            |p| {
+                let _ = is_set(p, "test");
+                0
+            },
+        )
+        .unwrap();
+        assert_eq!(Arc::strong_count(&count), 3);
+
+        // Two handlers, called at the same time, each remove the other
+        let barrier = Arc::new(Barrier::new(2));
+        let thrs: Vec<_> = [(phid1, ctx1, phid2), (phid2, ctx2, phid1)]
+            .into_iter()
+            .map(|(phid, ctx, other)| {
+                let barrier = Arc::clone(&barrier);
+                let (phid, ctx, other) = (phid as usize, ctx as usize, other as usize);
+                thread::spawn(move || {
+                    dispatch(phid as PhidgetHandle, ctx as *mut c_void, || {
+                        barrier.wait();
+                        unregister(other as PhidgetHandle, "test").unwrap();
+                        barrier.wait();
+                    });
+                })
+            })
+            .collect();
+
+        for thr in thrs {
+            thr.join().unwrap();
+        }
+        assert_eq!(Arc::strong_count(&count), 1);
+    }
+
+    #[test]
+    fn test_pool() {
+        use std::{sync::mpsc, time::Duration};
+
+        let key = 0x6000;
+        let phid = key as PhidgetHandle;
+        let count = Arc::new(());
+
+        let ctx = test_ctx(&count);
+        let guard = register::<Box<TestCallback>, _>(phid, "test", ctx, || 0, |_| 0).unwrap();
+        let id = find(phid as usize, ctx as usize).unwrap();
+
+        // The calls for a channel run in order on one of the pool threads
+        let pool = Pool::new(2);
+        let (tx, rx) = mpsc::channel();
+        for i in 0..3 {
+            let tx = tx.clone();
+            pool.queue(
+                key,
+                Job(Box::new(move || {
+                    call(key, id, || {
+                        let name = thread::current().name().map(String::from);
+                        tx.send((i, name)).unwrap();
+                    })
+                })),
+            );
+        }
+
+        for i in 0..3 {
+            let (n, name) = rx.recv_timeout(Duration::from_secs(1)).unwrap();
+            assert_eq!(n, i);
+            assert!(name.unwrap().starts_with("phidget-dispatch-"));
+        }
+
+        // Calls queued after the handler is removed are discarded
+        drop(guard);
+        pool.queue(
+            key,
+            Job(Box::new(move || {
+                call(key, id, move || tx.send((3, None)).unwrap())
+            })),
+        );
+        assert!(rx.recv_timeout(Duration::from_millis(100)).is_err());
+        assert_eq!(Arc::strong_count(&count), 1);
+    }
+
+    #[test]
+    fn test_pool_stop() {
+        use std::{sync::atomic::AtomicUsize, time::Duration};
+
+        // Stopping the pool waits for the queued calls to be made
+        let pool = Pool::new(2);
+        let done = Arc::new(AtomicUsize::new(0));
+        for key in [0x10, 0x20, 0x30] {
+            let done = Arc::clone(&done);
+            pool.queue(
+                key,
+                Job(Box::new(move || {
+                    thread::sleep(Duration::from_millis(20));
+                    done.fetch_add(1, Ordering::SeqCst);
+                })),
+            );
+        }
+        pool.stop();
+        assert_eq!(done.load(Ordering::SeqCst), 3);
+    }
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod mock_tests {
+    use super::*;
+    use crate::{mock::set_value, Phidget, TemperatureSensor};
+
+    #[test]
+    fn test_mock_handler_error() {
+        let mut sensor = TemperatureSensor::new();
+        sensor.open().unwrap();
+
+        let (tx, rx) = mpsc::channel();
+        let _err = sensor
+            .set_on_error_handler(move |_, code, descr| {
+                let _ = tx.send((code, descr.to_string()));
+            })
+            .unwrap();
+        let _change = sensor
+            .set_on_temperature_change_handler(|_, t| match t > 100.0 {
+                true => Err(Error::InvalidArg),
+                false => Ok(()),
+            })
+            .unwrap();
+
+        set_value(&mut sensor, 25.0).unwrap();
+        assert!(rx.try_recv().is_err());
+
+        set_value(&mut sensor, 150.0).unwrap();
+        let (code, descr) = rx.try_recv().unwrap();
+        assert_eq!(code, ErrorEventCode::Failure);
+        assert!(descr.starts_with("Event handler failed"));
+
+        // A panic doesn't unwind into the library
+        let _change = sensor
+            .set_on_temperature_change_handler(|_, _| -> Result<()> { panic!("sensor on fire") })
+            .unwrap();
+        set_value(&mut sensor, 25.0).unwrap();
+        let (code, descr) = rx.try_recv().unwrap();
+        assert_eq!(code, ErrorEventCode::Failure);
+        assert_eq!(descr, "Event handler panicked: sensor on fire");
+    }
+}
@@ -0,0 +1,401 @@
+// phidget-rs/src/health.rs
+//
+// This file is part of the 'phidget-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+//! A health monitor for channels.
+//!
+//! A sensor that dies quietly, like one whose cable was cut while its hub
+//! stays up, often doesn't detach or report an error; its readings just
+//! stop. The `HealthMonitor` keeps statistics for a set of channels, from
+//! their attach, detach, and error events and from their readings, and
+//! can report the channels that have gone stale:
+//!
+//! ```text
+//! let mut health = HealthMonitor::new();
+//! health.watch_sensor("boiler", &mut boiler_temp)?;
+//!
+//! // Or, to keep an application handler on the sensor
+//! health.watch("flow", &mut flow)?;
+//! flow.set_on_voltage_change_handler(health.tracked("flow", |_, v| {
+//!     println!("Flow: {}", v);
+//! }))?;
+//!
+//! health.on_stale(Duration::from_secs(30), |name, age| {
+//!     eprintln!("No readings from {} for {:?}", name, age);
+//! });
+//!
+//! for ch in health.report() {
+//!     println!("{:?}", ch);
+//! }
+//! ```
+//!
+//! The device events are matched to the channels by their address, as it
+//! was when they were watched, so the channels should be attached by
+//! then.
+//!
+
+use crate::{
+    events::{DeviceEvent, EventSource},
+    ErrorEventCode, HandlerGuard, Phidget, Result, Sensor,
+};
+use std::{
+    collections::VecDeque,
+    sync::{
+        mpsc::{self, RecvTimeoutError, Sender},
+        Arc, Mutex, MutexGuard,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+/// The period over which the event rate of a channel is measured.
+pub const RATE_WINDOW: Duration = Duration::from_secs(10);
+
+/// The health of a channel, from a `HealthMonitor`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChannelHealth {
+    /// The name of the channel
+    pub name: String,
+    /// The address of the channel
+    pub source: EventSource,
+    /// Whether the channel is attached
+    pub attached: bool,
+    /// The number of times the channel attached since it was watched
+    pub attach_count: u64,
+    /// The number of times the channel detached since it was watched
+    pub detach_count: u64,
+    /// The last error event from the channel, with its description
+    pub last_error: Option<(ErrorEventCode, String)>,
+    /// The number of readings since the channel was watched
+    pub reading_count: u64,
+    /// The readings per second, over the last `RATE_WINDOW`
+    pub event_rate: f64,
+    /// The time since the last reading, or `None` if there hasn't been
+    /// one
+    pub since_last_reading: Option<Duration>,
+}
+
+// The statistics for a channel
+struct Entry {
+    name: String,
+    source: EventSource,
+    attached: bool,
+    attach_count: u64,
+    detach_count: u64,
+    last_error: Option<(ErrorEventCode, String)>,
+    reading_count: u64,
+    // The times of the readings within the rate window
+    recent: VecDeque<Instant>,
+    // The time the channel was watched
+    watched: Instant,
+    last_reading: Option<Instant>,
+    // Whether the staleness callback was made for the current silence
+    stale: bool,
+}
+
+impl Entry {
+    // Determines if a device event is for this channel.
+    // Any part of the address that wasn't known matches any device.
+    fn matches(&self, src: &EventSource) -> bool {
+        let s = &self.source;
+        s.channel_class == src.channel_class
+            && (s.serial_number < 0 || s.serial_number == src.serial_number)
+            && (s.hub_port < 0 || s.hub_port == src.hub_port)
+            && (s.channel < 0 || s.channel == src.channel)
+    }
+
+    // Drops the reading times that are out of the rate window.
+    fn prune(&mut self, now: Instant) {
+        while let Some(&t) = self.recent.front() {
+            if now.saturating_duration_since(t) <= RATE_WINDOW {
+                break;
+            }
+            self.recent.pop_front();
+        }
+    }
+
+    // Records a reading.
+    fn reading(&mut self, now: Instant) {
+        self.reading_count += 1;
+        self.last_reading = Some(now);
+        self.stale = false;
+        self.recent.push_back(now);
+        self.prune(now);
+    }
+
+    // Gets the time since the last reading, or since the channel was
+    // watched if there hasn't been one.
+    fn silence(&self, now: Instant) -> Duration {
+        now.saturating_duration_since(self.last_reading.unwrap_or(self.watched))
+    }
+
+    fn health(&mut self, now: Instant) -> ChannelHealth {
+        self.prune(now);
+        ChannelHealth {
+            name: self.name.clone(),
+            source: self.source,
+            attached: self.attached,
+            attach_count: self.attach_count,
+            detach_count: self.detach_count,
+            last_error: self.last_error.clone(),
+            reading_count: self.reading_count,
+            event_rate: self.recent.len() as f64 / RATE_WINDOW.as_secs_f64(),
+            since_last_reading: self.last_reading.map(|t| now.saturating_duration_since(t)),
+        }
+    }
+}
+
+type Entries = Arc<Mutex<Vec<Entry>>>;
+
+// Locks the entries.
+fn lock(entries: &Entries) -> MutexGuard<'_, Vec<Entry>> {
+    entries.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+/// A monitor of the health of a set of channels.
+///
+/// The statistics are kept until the monitor is dropped, which also
+/// removes any handlers that it set on the sensors, and stops the
+/// staleness checks.
+pub struct HealthMonitor {
+    // The statistics for the channels
+    entries: Entries,
+    // The ID of the device event listener
+    tap: u64,
+    // Stops the staleness thread, and the thread
+    stale: Option<(Sender<()>, JoinHandle<()>)>,
+    // The change handlers of the sensors watched with `watch_sensor()`
+    guards: Vec<HandlerGuard>,
+}
+
+impl HealthMonitor {
+    /// Creates a monitor, with no channels.
+    pub fn new() -> Self {
+        let entries: Entries = Arc::default();
+        let tap = crate::events::add_tap({
+            let entries = Arc::clone(&entries);
+            move |_, ev| {
+                let mut entries = lock(&entries);
+                match ev {
+                    DeviceEvent::Attached(src) => {
+                        for e in entries.iter_mut().filter(|e| e.matches(src)) {
+                            e.attached = true;
+                            e.attach_count += 1;
+                        }
+                    }
+                    DeviceEvent::Detached(src) => {
+                        for e in entries.iter_mut().filter(|e| e.matches(src)) {
+                            e.attached = false;
+                            e.detach_count += 1;
+                        }
+                    }
+                    DeviceEvent::Error(src, code, descr) => {
+                        for e in entries.iter_mut().filter(|e| e.matches(src)) {
+                            e.last_error = Some((*code, descr.clone()));
+                        }
+                    }
+                }
+            }
+        });
+
+        Self {
+            entries,
+            tap,
+            stale: None,
+            guards: Vec::new(),
+        }
+    }
+
+    /// Watches the device events of a channel.
+    ///
+    /// The readings of the channel aren't seen by the monitor unless its
+    /// handler is wrapped with `tracked()`.
+    pub fn watch<P>(&mut self, name: &str, dev: &mut P) -> Result<()>
+    where
+        P: Phidget + ?Sized,
+    {
+        let attached = dev.is_attached()?;
+        let source = EventSource::from_handle(dev.as_handle());
+        lock(&self.entries).push(Entry {
+            name: name.into(),
+            source,
+            attached,
+            attach_count: 0,
+            detach_count: 0,
+            last_error: None,
+            reading_count: 0,
+            recent: VecDeque::new(),
+            watched: Instant::now(),
+            last_reading: None,
+            stale: false,
+        });
+        Ok(())
+    }
+
+    /// Watches the device events and the readings of a sensor.
+    ///
+    /// This sets the sensor's change handler, replacing any handler that
+    /// was previously set.
+    pub fn watch_sensor<S: Sensor + 'static>(&mut self, name: &str, sensor: &mut S) -> Result<()> {
+        self.watch(name, sensor)?;
+        let guard = sensor.on_change(self.tracked(name, |_, _| ()))?;
+        self.guards.push(guard);
+        Ok(())
+    }
+
+    /// Wraps an event handler so that each event is counted as a reading
+    /// of the channel with the name, before it's passed on.
+    ///
+    /// This can be passed to any of the `set_on_*_handler()` functions.
+    pub fn tracked<C, T, F>(&self, name: &str, mut f: F) -> impl FnMut(&C, T) + Send + 'static
+    where
+        C: ?Sized + 'static,
+        T: 'static,
+        F: FnMut(&C, T) + Send + 'static,
+    {
+        let entries = Arc::clone(&self.entries);
+        let name = name.to_string();
+        move |ch, val| {
+            let now = Instant::now();
+            if let Some(e) = lock(&entries).iter_mut().find(|e| e.name == name) {
+                e.reading(now);
+            }
+            f(ch, val);
+        }
+    }
+
+    /// Gets the health of all the channels, in the order they were
+    /// watched.
+    pub fn report(&self) -> Vec<ChannelHealth> {
+        let now = Instant::now();
+        lock(&self.entries)
+            .iter_mut()
+            .map(|e| e.health(now))
+            .collect()
+    }
+
+    /// Gets the health of the channel with the name, if it's watched.
+    pub fn health(&self, name: &str) -> Option<ChannelHealth> {
+        let now = Instant::now();
+        lock(&self.entries)
+            .iter_mut()
+            .find(|e| e.name == name)
+            .map(|e| e.health(now))
+    }
+
+    /// Sets a callback for channels that go stale, with no readings for
+    /// the timeout.
+    ///
+    /// The callback gets the name of the channel, and the time since its
+    /// last reading, or since it was watched if it never had one. It's
+    /// called once each time a channel goes stale, from a background
+    /// thread, and not again until the channel has a reading. This
+    /// replaces any callback that was previously set.
+    pub fn on_stale<F>(&mut self, timeout: Duration, mut cb: F)
+    where
+        F: FnMut(&str, Duration) + Send + 'static,
+    {
+        self.stop_stale();
+
+        let period = (timeout / 4).max(Duration::from_millis(10));
+        let entries = Arc::clone(&self.entries);
+        let (tx, rx) = mpsc::channel();
+
+        // Any message, or the monitor going away, stops the checks
+        let thr = thread::spawn(move || {
+            while let Err(RecvTimeoutError::Timeout) = rx.recv_timeout(period) {
+                let now = Instant::now();
+                let stale: Vec<_> = lock(&entries)
+                    .iter_mut()
+                    .filter(|e| !e.stale && e.silence(now) >= timeout)
+                    .map(|e| {
+                        e.stale = true;
+                        (e.name.clone(), e.silence(now))
+                    })
+                    .collect();
+                for (name, age) in stale {
+                    cb(&name, age);
+                }
+            }
+        });
+        self.stale = Some((tx, thr));
+    }
+
+    // Stops the staleness checks, if they're running.
+    fn stop_stale(&mut self) {
+        if let Some((tx, thr)) = self.stale.take() {
+            let _ = tx.send(());
+            let _ = thr.join();
+        }
+    }
+}
+
+impl Default for HealthMonitor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for HealthMonitor {
+    fn drop(&mut self) {
+        self.guards.clear();
+        crate::events::remove_tap(self.tap);
+        self.stop_stale();
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::*;
+    use crate::{
+        mock::{attach, detach, raise_error, set_value},
+        TemperatureSensor,
+    };
+
+    #[test]
+    fn test_mock_health_monitor() {
+        let mut sensor = TemperatureSensor::new();
+        sensor.open().unwrap();
+
+        let mut health = HealthMonitor::new();
+        health.watch_sensor("boiler", &mut sensor).unwrap();
+
+        let (tx, rx) = mpsc::channel();
+        health.on_stale(Duration::from_millis(40), move |name, _| {
+            let _ = tx.send(name.to_string());
+        });
+
+        for t in [20.0, 21.0, 22.0] {
+            set_value(&mut sensor, t).unwrap();
+        }
+        raise_error(&mut sensor, ErrorEventCode::OutOfRange, "too hot");
+        detach(&mut sensor);
+        attach(&mut sensor);
+
+        let report = health.health("boiler").unwrap();
+        assert!(report.attached);
+        assert_eq!((report.attach_count, report.detach_count), (1, 1));
+        assert_eq!(report.reading_count, 3);
+        assert_eq!(
+            report.last_error,
+            Some((ErrorEventCode::OutOfRange, "too hot".into()))
+        );
+        assert!(report.event_rate > 0.0);
+        assert!(report.since_last_reading.is_some());
+
+        // The readings stop, so the channel goes stale, once
+        assert_eq!(rx.recv_timeout(Duration::from_secs(1)), Ok("boiler".into()));
+        assert!(rx.recv_timeout(Duration::from_millis(100)).is_err());
+
+        set_value(&mut sensor, 23.0).unwrap();
+        assert_eq!(rx.recv_timeout(Duration::from_secs(1)), Ok("boiler".into()));
+    }
+}
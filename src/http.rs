@@ -0,0 +1,481 @@
+// phidget-rs/src/http.rs
+//
+// This file is part of the 'phidget-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+//! A REST API for channels, over HTTP.
+//!
+//! With the `http` feature, an `HttpApi` can expose the values of any
+//! number of sensors, and take commands for any number of outputs, over a
+//! small JSON API, so that other services can query and drive a Phidget
+//! rig without any code of their own:
+//!
+//! ```text
+//! let mut api = HttpApi::new();
+//! api.add_sensor("boiler", &boiler_temp)?;
+//! api.add_output("fan", fan_output)?;
+//!
+//! api.set_token("a-long-random-secret");
+//!
+//! let _server = api.serve("0.0.0.0:8080")?;
+//! ```
+//!
+//! The API is:
+//!
+//! ```text
+//! GET  /channels          [{"name":"boiler","kind":"sensor","value":64.5},
+//!                          {"name":"fan","kind":"output"}]
+//! GET  /channels/boiler   {"name":"boiler","kind":"sensor","value":64.5}
+//! POST /channels/fan      (body "on", "off", or "0.5")
+//! ```
+//!
+//! A sensor's value is the latest from its change events, or `null`
+//! until it has one. The body of a POST is parsed by the output, as
+//! described in the `command` module. A command that's applied gets a
+//! `204 No Content`, and one that can't be gets a `400 Bad Request` with
+//! the error, like `{"error":"Invalid argument"}`.
+//!
+//! **The outputs are driven by anyone who can reach the server.** Unless
+//! a token is set with `set_token()`, an API with outputs can only be
+//! served on a loopback address, like `127.0.0.1:8080`. With a token,
+//! every request has to carry it as `Authorization: Bearer <token>`, or
+//! it gets a `401 Unauthorized`. The token is sent in the clear, so it
+//! only keeps out the clients on a network that can't see the traffic.
+//!
+//! The server is deliberately simple. It handles each connection on its
+//! own thread, up to a limit, and closes it after responding. The size of
+//! a request, and the time taken to send it, are limited too.
+//!
+
+use crate::{httpd, Commandable, Error, HandlerGuard, Result, Sensor};
+use std::{
+    fmt::Write as _,
+    io::{self, BufReader},
+    net::{SocketAddr, TcpStream, ToSocketAddrs},
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+/// The largest request body that the server accepts.
+pub const MAX_BODY_LEN: usize = 4096;
+
+/// The path of the channel list, under which each channel is found by
+/// its name.
+pub const CHANNELS_PATH: &str = "/channels";
+
+// What a channel is, with its state
+enum Kind {
+    // The latest value of a sensor, if it has one
+    Sensor(Option<f64>),
+    // An output, which takes commands
+    Output(Box<dyn Commandable>),
+}
+
+// A channel exposed by the API
+struct Channel {
+    name: String,
+    kind: Kind,
+}
+
+impl Channel {
+    // Renders the channel as a JSON object.
+    fn to_json(&self) -> String {
+        match &self.kind {
+            Kind::Sensor(val) => format!(
+                "{{\"name\":{},\"kind\":\"sensor\",\"value\":{}}}",
+                json_str(&self.name),
+                val.filter(|v| v.is_finite())
+                    .map_or_else(|| "null".to_string(), |v| v.to_string())
+            ),
+            Kind::Output(_) => format!("{{\"name\":{},\"kind\":\"output\"}}", json_str(&self.name)),
+        }
+    }
+}
+
+type Channels = Arc<Mutex<Vec<Channel>>>;
+
+// Locks the channels.
+fn lock(channels: &Channels) -> MutexGuard<'_, Vec<Channel>> {
+    channels.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+/// A set of channels exposed over a REST API.
+///
+/// The sensors' change handlers are removed when the API is dropped. A
+/// server that's still running keeps serving the last values of the
+/// sensors, and the outputs are only dropped once the server is stopped
+/// as well.
+#[derive(Default)]
+pub struct HttpApi {
+    // The channels, shared with the handlers and the server
+    channels: Channels,
+    // The handlers feeding the sensor values
+    guards: Vec<HandlerGuard>,
+    // The token that the requests have to carry, if any
+    token: Option<Arc<str>>,
+}
+
+impl HttpApi {
+    /// Creates a new API with no channels.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Adds a channel, failing if the name isn't valid or is already used.
+    fn add(&mut self, name: &str, kind: Kind) -> Result<()> {
+        if !is_valid_name(name) {
+            return Err(Error::InvalidArg);
+        }
+        let mut channels = lock(&self.channels);
+        if channels.iter().any(|ch| ch.name == name) {
+            return Err(Error::InvalidArg);
+        }
+        channels.push(Channel {
+            name: name.into(),
+            kind,
+        });
+        Ok(())
+    }
+
+    /// Exposes the value of the sensor under the name.
+    ///
+    /// The name is used in the URL of the channel, so it can only have
+    /// ASCII letters, digits, '-', '_', and '.', and it has to be unique,
+    /// otherwise this returns `InvalidArg`. This sets the sensor's change
+    /// handler, replacing any handler that was previously set. Boolean
+    /// values are given as 0 or 1.
+    pub fn add_sensor<S>(&mut self, name: &str, sensor: &S) -> Result<()>
+    where
+        S: Sensor,
+        S::Value: Into<f64>,
+    {
+        let val = sensor.value().ok().map(Into::into);
+        self.add(name, Kind::Sensor(val))?;
+
+        let channels = Arc::clone(&self.channels);
+        let chan_name = name.to_string();
+        let res = sensor.on_change(move |_, val| {
+            let mut channels = lock(&channels);
+            if let Some(ch) = channels.iter_mut().find(|ch| ch.name == chan_name) {
+                ch.kind = Kind::Sensor(Some(val.into()));
+            }
+        });
+
+        match res {
+            Ok(guard) => {
+                self.guards.push(guard);
+                Ok(())
+            }
+            Err(err) => {
+                lock(&self.channels).retain(|ch| ch.name != name);
+                Err(err)
+            }
+        }
+    }
+
+    /// Drives the output channel with the commands posted to the name.
+    ///
+    /// The name has the same rules as for a sensor. The API takes
+    /// ownership of the channel, which should already be open.
+    pub fn add_output<O>(&mut self, name: &str, out: O) -> Result<()>
+    where
+        O: Commandable + 'static,
+    {
+        self.add(name, Kind::Output(Box::new(out)))
+    }
+
+    /// Gets the names of the channels, in the order they were added.
+    pub fn names(&self) -> Vec<String> {
+        lock(&self.channels)
+            .iter()
+            .map(|ch| ch.name.clone())
+            .collect()
+    }
+
+    /// Sets the token that every request has to carry, as an
+    /// `Authorization: Bearer <token>` header.
+    ///
+    /// This applies to the servers started after it's set. An empty
+    /// token is not allowed, and returns `InvalidArg`.
+    pub fn set_token(&mut self, token: &str) -> Result<()> {
+        if token.is_empty() {
+            return Err(Error::InvalidArg);
+        }
+        self.token = Some(token.into());
+        Ok(())
+    }
+
+    /// Starts serving the API over HTTP on the specified address.
+    ///
+    /// The requests are handled by background threads, which are stopped
+    /// when the returned server is dropped.
+    ///
+    /// If the API has outputs, but no token, this fails with
+    /// `PermissionDenied` unless the address is a loopback address, so
+    /// that the outputs can't be driven from the network.
+    pub fn serve<A: ToSocketAddrs>(&self, addr: A) -> io::Result<HttpServer> {
+        let addrs: Vec<_> = addr.to_socket_addrs()?.collect();
+        let has_outputs = lock(&self.channels)
+            .iter()
+            .any(|ch| matches!(ch.kind, Kind::Output(_)));
+
+        if self.token.is_none() && has_outputs && addrs.iter().any(|a| !a.ip().is_loopback()) {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "an API with outputs needs a token to be served on a non-loopback address",
+            ));
+        }
+
+        let channels = Arc::clone(&self.channels);
+        let token = self.token.clone();
+        let server = httpd::Server::start(&addrs[..], httpd::MAX_CONNECTIONS, move |stream| {
+            handle_request(stream, &channels, token.as_deref())
+        })?;
+        Ok(HttpServer { server })
+    }
+}
+
+/// A running HTTP server for an `HttpApi`.
+///
+/// The server is stopped when this is dropped.
+pub struct HttpServer {
+    server: httpd::Server,
+}
+
+impl HttpServer {
+    /// Gets the address that the server is listening on.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.server.local_addr()
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+// Determines if the name can be used as a channel name.
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+}
+
+// Quotes a string for JSON.
+fn json_str(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+// Renders an error as a JSON object.
+fn json_error(err: &str) -> String {
+    format!("{{\"error\":{}}}", json_str(err))
+}
+
+// Handles a request, returning the status and the body of the response.
+fn route(channels: &Channels, method: &str, path: &str, body: &str) -> (&'static str, String) {
+    let path = path.split('?').next().unwrap_or_default();
+    let path = path.strip_suffix('/').unwrap_or(path);
+    let not_found = || ("404 Not Found", json_error("Not Found"));
+    let not_allowed = || ("405 Method Not Allowed", json_error("Method Not Allowed"));
+
+    if path == CHANNELS_PATH {
+        if method != "GET" {
+            return not_allowed();
+        }
+        let list: Vec<_> = lock(channels).iter().map(Channel::to_json).collect();
+        return ("200 OK", format!("[{}]", list.join(",")));
+    }
+
+    let Some(name) = path
+        .strip_prefix(CHANNELS_PATH)
+        .and_then(|p| p.strip_prefix('/'))
+    else {
+        return not_found();
+    };
+
+    let mut channels = lock(channels);
+    let Some(ch) = channels.iter_mut().find(|ch| ch.name == name)
+    else {
+        return not_found();
+    };
+
+    match (method, &mut ch.kind) {
+        ("GET", _) => ("200 OK", ch.to_json()),
+        ("POST", Kind::Output(out)) => match out.command(body) {
+            Ok(()) => ("204 No Content", String::new()),
+            Err(err) => ("400 Bad Request", json_error(&err.to_string())),
+        },
+        _ => not_allowed(),
+    }
+}
+
+// Determines if the request carries the token, comparing it in a time
+// that doesn't depend on where it differs.
+fn is_authorized(req: &httpd::Request, token: &str) -> bool {
+    let given = req
+        .header("authorization")
+        .and_then(|auth| auth.strip_prefix("Bearer "))
+        .unwrap_or_default()
+        .trim();
+    given.len() == token.len()
+        && given
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |acc, (a, b)| acc | (a ^ b))
+            == 0
+}
+
+// Reads an HTTP request from the stream and sends back the response.
+fn handle_request(stream: TcpStream, channels: &Channels, token: Option<&str>) -> io::Result<()> {
+    let mut rdr = BufReader::new(&stream);
+    let req = httpd::read_request(&mut rdr).and_then(|req| Ok((req.content_length()?, req)));
+
+    let (status, body) = match req {
+        Err(_) => ("400 Bad Request", json_error("Bad Request")),
+        Ok((_, req)) if !token.map_or(true, |token| is_authorized(&req, token)) => {
+            ("401 Unauthorized", json_error("Unauthorized"))
+        }
+        Ok((len, _)) if len > MAX_BODY_LEN => {
+            ("413 Payload Too Large", json_error("Payload Too Large"))
+        }
+        Ok((len, req)) => {
+            let body = httpd::read_body(&mut rdr, len)?;
+            route(
+                channels,
+                &req.method,
+                &req.target,
+                &String::from_utf8_lossy(&body),
+            )
+        }
+    };
+    httpd::write_response(&stream, status, "application/json", &body)
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+
+    // An output that records its commands
+    struct Recorder(Arc<Mutex<Vec<f64>>>);
+
+    impl Commandable for Recorder {
+        fn command(&mut self, payload: &str) -> Result<()> {
+            let v = payload.trim().parse().map_err(|_| Error::InvalidArg)?;
+            self.0.lock().unwrap().push(v);
+            Ok(())
+        }
+    }
+
+    // Sends a request to the server, returning the whole response.
+    fn request(server: &HttpServer, req: &str) -> String {
+        let mut stream = TcpStream::connect(server.local_addr()).unwrap();
+        stream.write_all(req.as_bytes()).unwrap();
+        let mut resp = String::new();
+        stream.read_to_string(&mut resp).unwrap();
+        resp
+    }
+
+    #[test]
+    fn test_route() {
+        assert!(is_valid_name("boiler-2.temp"));
+        assert!(!is_valid_name("boiler/temp"));
+        assert_eq!(json_str("a\"b\n"), "\"a\\\"b\\n\"");
+
+        let mut api = HttpApi::new();
+        api.add("boiler", Kind::Sensor(Some(64.5))).unwrap();
+        api.add("tank", Kind::Sensor(None)).unwrap();
+        assert_eq!(api.add("tank", Kind::Sensor(None)), Err(Error::InvalidArg));
+
+        let (status, body) = route(&api.channels, "GET", "/channels", "");
+        assert_eq!(status, "200 OK");
+        assert_eq!(
+            body,
+            "[{\"name\":\"boiler\",\"kind\":\"sensor\",\"value\":64.5},\
+             {\"name\":\"tank\",\"kind\":\"sensor\",\"value\":null}]"
+        );
+        assert_eq!(
+            route(&api.channels, "GET", "/channels/boiler/", "").1,
+            "{\"name\":\"boiler\",\"kind\":\"sensor\",\"value\":64.5}"
+        );
+        assert_eq!(
+            route(&api.channels, "GET", "/nothing", "").0,
+            "404 Not Found"
+        );
+        assert_eq!(
+            route(&api.channels, "POST", "/channels/boiler", "1").0,
+            "405 Method Not Allowed"
+        );
+    }
+
+    #[test]
+    fn test_serve() {
+        let vals = Arc::new(Mutex::new(Vec::new()));
+        let mut api = HttpApi::new();
+        api.add_output("fan", Recorder(Arc::clone(&vals))).unwrap();
+
+        let server = api.serve("127.0.0.1:0").unwrap();
+
+        let resp = request(
+            &server,
+            "POST /channels/fan HTTP/1.1\r\nHost: localhost\r\nContent-Length: 3\r\n\r\n0.5",
+        );
+        assert!(resp.starts_with("HTTP/1.1 204 No Content\r\n"));
+
+        let resp = request(
+            &server,
+            "POST /channels/fan HTTP/1.1\r\nContent-Length: 4\r\n\r\nfast",
+        );
+        assert!(resp.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+
+        let resp = request(&server, "GET /channels/fan HTTP/1.1\r\n\r\n");
+        assert!(resp.ends_with("{\"name\":\"fan\",\"kind\":\"output\"}"));
+        assert_eq!(*vals.lock().unwrap(), vec![0.5]);
+    }
+
+    #[test]
+    fn test_token() {
+        let vals = Arc::new(Mutex::new(Vec::new()));
+        let mut api = HttpApi::new();
+        api.add_output("fan", Recorder(Arc::clone(&vals))).unwrap();
+
+        // The outputs can't be served to the network without a token
+        let err = api.serve("0.0.0.0:0").err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+
+        api.set_token("s3cret").unwrap();
+        let server = api.serve("127.0.0.1:0").unwrap();
+
+        let resp = request(
+            &server,
+            "POST /channels/fan HTTP/1.1\r\nContent-Length: 1\r\n\r\n1",
+        );
+        assert!(resp.starts_with("HTTP/1.1 401 Unauthorized\r\n"));
+
+        let resp = request(
+            &server,
+            "POST /channels/fan HTTP/1.1\r\nAuthorization: Bearer s3cret\r\n\
+             Content-Length: 1\r\n\r\n1",
+        );
+        assert!(resp.starts_with("HTTP/1.1 204 No Content\r\n"));
+        assert_eq!(*vals.lock().unwrap(), vec![1.0]);
+    }
+}
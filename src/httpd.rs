@@ -0,0 +1,366 @@
+// phidget-rs/src/httpd.rs
+//
+// This file is part of the 'phidget-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+//! A minimal HTTP server, shared by the exporters and APIs.
+//!
+//! This is just enough of HTTP/1.1 to serve a few simple requests, but
+//! with limits on everything a client controls: the length of the request
+//! line and each header line, the number of headers, the time to send
+//! them, and the number of connections handled at once. Each connection
+//! is handled on its own thread, so a slow client doesn't hold up the
+//! others.
+//!
+
+// Each feature that uses the server needs only some of it.
+#![cfg_attr(
+    not(all(
+        feature = "exporter-prometheus",
+        feature = "http",
+        feature = "websocket"
+    )),
+    allow(dead_code)
+)]
+
+use std::{
+    io::{self, BufRead, Write},
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+/// The longest request line or header line that's accepted.
+pub(crate) const MAX_LINE_LEN: usize = 8192;
+
+/// The most headers that are accepted in a request.
+pub(crate) const MAX_HEADERS: usize = 64;
+
+/// The default number of connections that are handled at once.
+pub(crate) const MAX_CONNECTIONS: usize = 32;
+
+/// The timeout for each read or write on a connection.
+pub(crate) const IO_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The time that a client has to send the whole head of its request.
+pub(crate) const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The head of an HTTP request.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct Request {
+    /// The method, like "GET"
+    pub method: String,
+    /// The target, with the path and any query
+    pub target: String,
+    /// The headers, as name and value
+    pub headers: Vec<(String, String)>,
+}
+
+impl Request {
+    /// Gets the path of the target, without the query.
+    pub fn path(&self) -> &str {
+        self.target.split('?').next().unwrap_or_default()
+    }
+
+    /// Gets the query of the target, if it has one.
+    pub fn query(&self) -> Option<&str> {
+        self.target.split_once('?').map(|(_, q)| q)
+    }
+
+    /// Gets the value of the first header with the name, ignoring case.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Gets the length of the body, from the Content-Length header.
+    pub fn content_length(&self) -> io::Result<usize> {
+        self.header("content-length")
+            .map_or(Ok(0), |len| len.parse().map_err(|_| invalid_data()))
+    }
+}
+
+fn invalid_data() -> io::Error {
+    io::ErrorKind::InvalidData.into()
+}
+
+// Reads a line, up to the limit, failing if the deadline passes first.
+fn read_line<R: BufRead>(rdr: &mut R, deadline: Instant) -> io::Result<String> {
+    let mut line = Vec::new();
+    loop {
+        if Instant::now() > deadline {
+            return Err(io::ErrorKind::TimedOut.into());
+        }
+        let buf = rdr.fill_buf()?;
+        if buf.is_empty() {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        let (n, done) = match buf.iter().position(|&b| b == b'\n') {
+            Some(i) => (i + 1, true),
+            None => (buf.len(), false),
+        };
+        if line.len() + n > MAX_LINE_LEN {
+            return Err(invalid_data());
+        }
+        line.extend_from_slice(&buf[..n]);
+        rdr.consume(n);
+        if done {
+            break;
+        }
+    }
+    let line = String::from_utf8(line).map_err(|_| invalid_data())?;
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+/// Reads the head of a request: the request line and the headers.
+///
+/// This fails with `InvalidData` if a line is too long, there are too
+/// many headers, or the request is malformed, and with `TimedOut` if
+/// the whole head doesn't arrive within `REQUEST_TIMEOUT`.
+pub(crate) fn read_request<R: BufRead>(rdr: &mut R) -> io::Result<Request> {
+    let deadline = Instant::now() + REQUEST_TIMEOUT;
+
+    let line = read_line(rdr, deadline)?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next())
+    else {
+        return Err(invalid_data());
+    };
+    let mut req = Request {
+        method: method.into(),
+        target: target.into(),
+        headers: Vec::new(),
+    };
+
+    loop {
+        let line = read_line(rdr, deadline)?;
+        if line.is_empty() {
+            break;
+        }
+        if req.headers.len() == MAX_HEADERS {
+            return Err(invalid_data());
+        }
+        let (name, val) = line.split_once(':').ok_or_else(invalid_data)?;
+        req.headers.push((name.trim().into(), val.trim().into()));
+    }
+    Ok(req)
+}
+
+/// Reads a body of the length, failing with `TimedOut` if it doesn't
+/// arrive within `REQUEST_TIMEOUT`.
+pub(crate) fn read_body<R: BufRead>(rdr: &mut R, len: usize) -> io::Result<Vec<u8>> {
+    let deadline = Instant::now() + REQUEST_TIMEOUT;
+    let mut body = Vec::with_capacity(len);
+    while body.len() < len {
+        if Instant::now() > deadline {
+            return Err(io::ErrorKind::TimedOut.into());
+        }
+        let buf = rdr.fill_buf()?;
+        if buf.is_empty() {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        let n = buf.len().min(len - body.len());
+        body.extend_from_slice(&buf[..n]);
+        rdr.consume(n);
+    }
+    Ok(body)
+}
+
+/// Writes a complete response, and closes the connection.
+pub(crate) fn write_response<W: Write>(
+    mut stream: W,
+    status: &str,
+    content_type: &str,
+    body: &str,
+) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\n\
+         Content-Type: {}\r\n\
+         Content-Length: {}\r\n\
+         Connection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+/// A running server, which is stopped when this is dropped.
+///
+/// Connections that are being handled when the server is stopped are
+/// left to finish on their own threads.
+pub(crate) struct Server {
+    local_addr: SocketAddr,
+    stop: Arc<AtomicBool>,
+    thr: Option<JoinHandle<()>>,
+}
+
+impl Server {
+    /// Starts a server on the address, which handles each connection
+    /// with the function, on a thread of its own.
+    ///
+    /// The connection is given read and write timeouts of `IO_TIMEOUT`.
+    /// Up to `max_conn` connections are handled at once. Any more are
+    /// sent a `503 Service Unavailable`.
+    pub fn start<A, F>(addr: A, max_conn: usize, handler: F) -> io::Result<Self>
+    where
+        A: ToSocketAddrs,
+        F: Fn(TcpStream) -> io::Result<()> + Send + Sync + 'static,
+    {
+        let listener = TcpListener::bind(addr)?;
+        let local_addr = listener.local_addr()?;
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let handler = Arc::new(handler);
+        let active = Arc::new(AtomicUsize::new(0));
+        let thr_stop = Arc::clone(&stop);
+
+        let thr = thread::spawn(move || {
+            for stream in listener.incoming() {
+                if thr_stop.load(Ordering::SeqCst) {
+                    break;
+                }
+                let Ok(stream) = stream
+                else {
+                    continue;
+                };
+                if stream.set_read_timeout(Some(IO_TIMEOUT)).is_err()
+                    || stream.set_write_timeout(Some(IO_TIMEOUT)).is_err()
+                {
+                    continue;
+                }
+                if active.fetch_add(1, Ordering::SeqCst) >= max_conn {
+                    active.fetch_sub(1, Ordering::SeqCst);
+                    let _ = write_response(
+                        stream,
+                        "503 Service Unavailable",
+                        "text/plain",
+                        "Service Unavailable\n",
+                    );
+                    continue;
+                }
+                let handler = Arc::clone(&handler);
+                let active = Arc::clone(&active);
+                thread::spawn(move || {
+                    let _ = handler(stream);
+                    active.fetch_sub(1, Ordering::SeqCst);
+                });
+            }
+        });
+
+        Ok(Self {
+            local_addr,
+            stop,
+            thr: Some(thr),
+        })
+    }
+
+    /// Gets the address that the server is listening on.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+
+        // Wake the thread from the blocking accept. A server bound to the
+        // unspecified address is reached through the loopback address of
+        // the same family, since the unspecified address can't be
+        // connected to on every platform.
+        let mut addr = self.local_addr;
+        if addr.ip().is_unspecified() {
+            addr.set_ip(match addr {
+                SocketAddr::V4(_) => Ipv4Addr::LOCALHOST.into(),
+                SocketAddr::V6(_) => Ipv6Addr::LOCALHOST.into(),
+            });
+        }
+        let woken = TcpStream::connect_timeout(&addr, IO_TIMEOUT).is_ok();
+
+        // If the thread couldn't be woken, it's left to stop on its own
+        // at the next connection, rather than blocking here.
+        if let Some(thr) = self.thr.take() {
+            if woken {
+                let _ = thr.join();
+            }
+        }
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufReader, Read};
+
+    #[test]
+    fn test_read_request() {
+        let mut rdr: &[u8] =
+            b"GET /metrics?x=1 HTTP/1.1\r\nHost: localhost\r\nContent-Length: 3\r\n\r\nabcd";
+        let req = read_request(&mut rdr).unwrap();
+        assert_eq!(req.method, "GET");
+        assert_eq!(req.path(), "/metrics");
+        assert_eq!(req.query(), Some("x=1"));
+        assert_eq!(req.header("HOST"), Some("localhost"));
+        assert_eq!(req.content_length().unwrap(), 3);
+        assert_eq!(read_body(&mut rdr, 3).unwrap(), b"abc");
+        assert_eq!(rdr, b"d");
+
+        // A line that's too long
+        let long = format!("GET /{} HTTP/1.1\r\n\r\n", "a".repeat(MAX_LINE_LEN));
+        let err = read_request(&mut long.as_bytes()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        // Too many headers
+        let many = format!(
+            "GET / HTTP/1.1\r\n{}\r\n",
+            "X-A: b\r\n".repeat(MAX_HEADERS + 1)
+        );
+        let err = read_request(&mut many.as_bytes()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        // A head that never ends
+        let err = read_request(&mut &b"GET / HTTP/1.1\r\nHost: x\r\n"[..]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn test_concurrent() {
+        let server = Server::start("127.0.0.1:0", 2, |stream| {
+            let req = read_request(&mut BufReader::new(&stream))?;
+            write_response(&stream, "200 OK", "text/plain", req.path())
+        })
+        .unwrap();
+
+        // An idle client doesn't hold up the others
+        let _idle = TcpStream::connect(server.local_addr()).unwrap();
+        let mut stream = TcpStream::connect(server.local_addr()).unwrap();
+        stream.write_all(b"GET /ok HTTP/1.1\r\n\r\n").unwrap();
+        let mut resp = String::new();
+        stream.read_to_string(&mut resp).unwrap();
+        assert!(resp.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(resp.ends_with("/ok"));
+    }
+
+    #[test]
+    fn test_drop_unspecified() {
+        // A server on all interfaces is woken through the loopback address
+        let server = Server::start("0.0.0.0:0", 1, |_| Ok(())).unwrap();
+        assert!(server.local_addr().ip().is_unspecified());
+        drop(server);
+    }
+}
@@ -47,10 +47,12 @@
 //! for instance by turning on an LED whenever another sensor detects something,
 //! you need to set a callback listening for sensor value changes, and keep a valid handle to the output device to set its state.
 //! 
-//! The problem is, Phidget callbacks do run in a different thread. A Phidget handle can already be sent
-//! to a different thread, as it implements [Send], but it doesn't implement [Sync].
-//! Hence, if you desire to access the same handle from different callbacks, it has to be wrapped in a
-//! Sync container, such as a [Mutex](std::sync::Mutex) or a [RwLock](std::sync::RwLock).
+//! The problem is, Phidget callbacks do run in a different thread. The phidget22 library is thread-safe,
+//! so the device objects implement both [Send] and [Sync], and can be shared between threads with an
+//! [Arc](std::sync::Arc). The getters and the event handler setters only need a shared reference, but
+//! most of the setters require a mutable one. Hence, if you desire to change the same device from
+//! different callbacks, it has to be wrapped in a container such as a [Mutex](std::sync::Mutex) or a
+//! [RwLock](std::sync::RwLock).
 //! 
 //! ```rust,no_run
 //! # use phidget::{Phidget, DigitalOutput, DigitalInput};
@@ -72,8 +74,9 @@
 //!             tmp
 //!         }
 //!     );
-//!     // // Make the button alternate the LED state
-//!     button.set_on_state_change_handler(move |_, s: i32|
+//!     // // Make the button alternate the LED state.
+//!     // The handler stays registered as long as the guard is kept.
+//!     let _handler = button.set_on_state_change_handler(move |_, s: i32|
 //!         {
 //!             match s
 //!             {
@@ -100,7 +103,8 @@
 
 use std::{
     ffi::CStr,
-    os::raw::{c_char, c_uint, c_void},
+    fmt,
+    os::raw::{c_char, c_uint},
     ptr,
     time::Duration,
 };
@@ -110,17 +114,207 @@ pub use phidget_sys::{
     PHIDGET_SERIALNUMBER_ANY, PHIDGET_TIMEOUT_DEFAULT, PHIDGET_TIMEOUT_INFINITE,
 };
 
+// Calls a phidget22 library function on a channel, returning its raw
+// return code. With the `tracing` feature, the call is made in a span
+// with the name of the function, the address of the channel, and the
+// return code.
+macro_rules! traced {
+    ($ffi:ident :: $func:ident($phid:expr $(, $arg:expr)* $(,)?)) => {{
+        let phid = $phid;
+        #[cfg(feature = "tracing")]
+        let span = crate::trace::ffi_span(stringify!($func), phid);
+        #[cfg(feature = "tracing")]
+        let _enter = span.enter();
+        let rc = $ffi::$func(phid $(, $arg)*);
+        #[cfg(feature = "tracing")]
+        crate::trace::ffi_result(&span, rc);
+        rc
+    }};
+}
+
 /// The error types for the crate
 pub mod errors;
 pub use crate::errors::*;
 
 /// The main Phidget trait
 pub mod phidget;
-pub use crate::phidget::{AttachCallback, DetachCallback, GenericPhidget, Phidget};
+pub use crate::phidget::{AttachCallback, DetachCallback, ErrorCallback, GenericPhidget, Phidget};
 
 /// Network API
 pub mod net;
-pub use crate::net::ServerType;
+pub use crate::net::{ServerEvent, ServerStatus, ServerType};
+
+/// Ownership of the event handlers
+pub mod handler;
+pub use crate::handler::{EventReceiver, HandlerGuard, HandlerResult, HandlerScope};
+
+/// Host-side timestamps for channel events
+pub mod timestamp;
+pub use crate::timestamp::{timestamped, Timestamped};
+
+/// Debouncing and rate-limiting of event handlers
+pub mod limit;
+pub use crate::limit::{debounced, throttled};
+
+/// A crate-wide bus of device events
+pub mod events;
+pub use crate::events::{DeviceEvent, EventSource};
+
+// Internal notification of channel events
+mod notify;
+
+// Tracing of the library calls and callbacks
+#[cfg(feature = "tracing")]
+mod trace;
+
+// Internal cache of channel properties
+mod cache;
+
+/// Asynchronous streams of channel events
+#[cfg(feature = "async")]
+pub mod stream;
+#[cfg(feature = "async")]
+pub use crate::stream::EventStream;
+
+/// A common interface for the single-value sensors
+pub mod sensor;
+pub use crate::sensor::Sensor;
+
+/// A polling scheduler for sensors
+pub mod sampler;
+pub use crate::sampler::{Sample, Sampler};
+
+/// Synchronized sampling of a group of sensors
+pub mod sync_group;
+pub use crate::sync_group::{SyncGroup, SyncRow};
+
+/// Coordinated control of a group of RC servos
+pub mod servo_group;
+pub use crate::servo_group::ServoGroup;
+
+/// Threshold alarms on sensor values
+pub mod alarms;
+pub use crate::alarms::{Alarm, AlarmEvent, Alarms};
+
+/// A health monitor for channels
+pub mod health;
+pub use crate::health::{ChannelHealth, HealthMonitor};
+
+/// A scale, from a load cell on a voltage ratio input
+pub mod scale;
+pub use crate::scale::{Scale, ScaleCalibration, StableWeight, WeightUnit};
+
+/// On/off temperature control
+pub mod thermostat;
+pub use crate::thermostat::{Thermostat, ThermostatEvent};
+
+/// PID control
+pub mod pid;
+pub use crate::pid::{Actuator, Pid, PidLoop};
+
+/// Decimation and resampling of timestamped readings
+pub mod decimate;
+#[cfg(feature = "async")]
+pub use crate::decimate::DecimatedStream;
+pub use crate::decimate::{Aggregate, Decimated, Decimator, Downsample, EveryNth, Resample};
+
+/// Multi-channel temperature boards
+pub mod temperature_array;
+pub use crate::temperature_array::{ArrayReadings, TemperatureArray};
+
+/// A data logger for sensor readings
+pub mod datalog;
+pub use crate::datalog::{DataLogger, LogConfig, LogFormat};
+
+/// Recording and replay of channel events
+pub mod record;
+pub use crate::record::{Record, RecordedEvent, Recorder};
+
+/// Calibration of sensor values
+pub mod calibration;
+pub use crate::calibration::{Calibrated, Calibration, CalibrationTable};
+
+/// Smoothing filters for sensor values
+pub mod filter;
+#[cfg(feature = "async")]
+pub use crate::filter::FilteredStream;
+pub use crate::filter::{Filter, Filtered, LowPass, Median, MovingAverage};
+
+// A minimal HTTP server for the exporters and APIs
+#[cfg(any(
+    feature = "exporter-prometheus",
+    feature = "http",
+    feature = "websocket"
+))]
+mod httpd;
+
+/// A Prometheus exporter for sensor values
+#[cfg(feature = "exporter-prometheus")]
+pub mod prometheus;
+#[cfg(feature = "exporter-prometheus")]
+pub use crate::prometheus::{MetricsServer, PrometheusExporter};
+
+/// Text commands for output channels
+pub mod command;
+pub use crate::command::Commandable;
+
+/// A bridge to an MQTT broker
+#[cfg(feature = "mqtt")]
+pub mod mqtt;
+#[cfg(feature = "mqtt")]
+pub use crate::mqtt::{Discovery, MqttBridge};
+
+/// A REST API for channels, over HTTP
+#[cfg(feature = "http")]
+pub mod http;
+#[cfg(feature = "http")]
+pub use crate::http::{HttpApi, HttpServer};
+
+/// Live sensor events over WebSockets
+#[cfg(feature = "websocket")]
+pub mod websocket;
+#[cfg(feature = "websocket")]
+pub use crate::websocket::{WebSocketHub, WebSocketServer};
+
+/// Sinks that send sensor readings to other systems
+#[cfg(feature = "sink-influx")]
+pub mod sink;
+
+/// Health metrics for the channels
+#[cfg(feature = "metrics")]
+pub mod metrics;
+
+/// Support for the embedded-hal traits
+#[cfg(feature = "embedded-hal")]
+pub mod hal;
+
+/// Saving and applying channel settings
+pub mod config;
+pub use crate::config::{ChannelConfig, ConfigProfile, Configurable};
+
+/// A supervisor to keep channels connected
+pub mod reconnect;
+pub use crate::reconnect::Reconnector;
+
+/// The Phidget Manager, to discover channels
+pub mod manager;
+pub use crate::manager::{ChannelInfo, Manager};
+
+/// Shutting down all the hardware at once
+pub mod shutdown;
+pub use crate::shutdown::{shutdown, ShutdownGuard};
+
+/// A builder to select and open channels
+pub mod builder;
+pub use crate::builder::{builder, ChannelBuilder};
+
+/// Opening channels of any type by their address
+pub mod any;
+pub use crate::any::{open_any, AnyChannel, ChannelAddress};
+
+/// Control of simulated devices for testing without hardware
+#[cfg(feature = "mock")]
+pub mod mock;
 
 /// Module containing all implemented devices
 pub mod devices;
@@ -151,22 +345,16 @@ where
         let mut ver: *const c_char = ptr::null_mut();
         ReturnCode::result(f(&mut ver))?;
         if ver.is_null() {
-            return Err(ReturnCode::NoMemory);
+            return Err(ReturnCode::NoMemory.into());
         }
         let s = CStr::from_ptr(ver);
         Ok(s.to_string_lossy().into())
     }
 }
 
-/// Release the memory held in a double-boxed callback function/lambda.
-pub(crate) fn drop_cb<P: ?Sized>(cb: Option<*mut c_void>) {
-    if let Some(ctx) = cb {
-        let _: Box<Box<P>> = unsafe { Box::from_raw(ctx as *mut _) };
-    }
-}
-
 /// Phidget channel class
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u32)]
 #[allow(missing_docs)]
 pub enum ChannelClass {
@@ -262,13 +450,137 @@ impl TryFrom<u32> for ChannelClass {
             ffi::Phidget_ChannelClass_PHIDCHCLASS_VOLTAGEINPUT => Ok(VoltageInput), // 29
             ffi::Phidget_ChannelClass_PHIDCHCLASS_VOLTAGEOUTPUT => Ok(VoltageOutput), // 30
             ffi::Phidget_ChannelClass_PHIDCHCLASS_VOLTAGERATIOINPUT => Ok(VoltageRatioInput), // 31
-            _ => Err(ReturnCode::InvalidArg),
+            _ => Err(Error::InvalidArg),
         }
     }
 }
 
-/// Phidget device class
+impl ChannelClass {
+    /// Gets a human-readable name for the channel class.
+    pub fn name(&self) -> &'static str {
+        use ChannelClass::*;
+        match self {
+            Nothing => "Nothing",
+            Accelerometer => "Accelerometer",
+            BldcMotor => "BLDC Motor",
+            CaptiveTouch => "Capacitive Touch",
+            CurrentInput => "Current Input",
+            CurrentOutput => "Current Output",
+            DataAdapter => "Data Adapter",
+            DcMotor => "DC Motor",
+            Dictionary => "Dictionary",
+            DigitalInput => "Digital Input",
+            DigitalOutput => "Digital Output",
+            DistanceSensor => "Distance Sensor",
+            Encoder => "Encoder",
+            FirmwareUpgrade => "Firmware Upgrade",
+            FrequencyCounter => "Frequency Counter",
+            Generic => "Generic",
+            Gps => "GPS",
+            Gyroscope => "Gyroscope",
+            Hub => "Hub",
+            HumiditySensor => "Humidity Sensor",
+            Ir => "IR",
+            Lcd => "LCD",
+            LightSensor => "Light Sensor",
+            Magnetometer => "Magnetometer",
+            MeshDongle => "Mesh Dongle",
+            MotorPositionController => "Motor Position Controller",
+            MotorVelocityController => "Motor Velocity Controller",
+            PhSensor => "pH Sensor",
+            PowerGuard => "Power Guard",
+            PressureSensor => "Pressure Sensor",
+            RcServo => "RC Servo",
+            ResistanceInput => "Resistance Input",
+            Rfid => "RFID",
+            SoundSensor => "Sound Sensor",
+            Spatial => "Spatial",
+            Stepper => "Stepper",
+            TemperatureSensor => "Temperature Sensor",
+            VoltageInput => "Voltage Input",
+            VoltageOutput => "Voltage Output",
+            VoltageRatioInput => "Voltage Ratio Input",
+        }
+    }
+}
+
+impl fmt::Display for ChannelClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Phidget channel subclass
+///
+/// This further describes the type of some channels, such as whether an
+/// LCD channel is graphic or text-only.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(u32)]
+#[allow(missing_docs)]
+pub enum ChannelSubclass {
+    None = ffi::Phidget_ChannelSubclass_PHIDCHSUBCLASS_NONE, // 1
+    DigitalOutputDutyCycle = ffi::Phidget_ChannelSubclass_PHIDCHSUBCLASS_DIGITALOUTPUT_DUTY_CYCLE, // 16
+    DigitalOutputLedDriver = ffi::Phidget_ChannelSubclass_PHIDCHSUBCLASS_DIGITALOUTPUT_LED_DRIVER, // 17
+    DigitalOutputFrequency = ffi::Phidget_ChannelSubclass_PHIDCHSUBCLASS_DIGITALOUTPUT_FREQUENCY, // 18
+    TemperatureSensorRtd = ffi::Phidget_ChannelSubclass_PHIDCHSUBCLASS_TEMPERATURESENSOR_RTD, // 32
+    TemperatureSensorThermocouple =
+        ffi::Phidget_ChannelSubclass_PHIDCHSUBCLASS_TEMPERATURESENSOR_THERMOCOUPLE, // 33
+    VoltageInputSensorPort = ffi::Phidget_ChannelSubclass_PHIDCHSUBCLASS_VOLTAGEINPUT_SENSOR_PORT, // 48
+    VoltageRatioInputSensorPort =
+        ffi::Phidget_ChannelSubclass_PHIDCHSUBCLASS_VOLTAGERATIOINPUT_SENSOR_PORT, // 64
+    VoltageRatioInputBridge = ffi::Phidget_ChannelSubclass_PHIDCHSUBCLASS_VOLTAGERATIOINPUT_BRIDGE, // 65
+    LcdGraphic = ffi::Phidget_ChannelSubclass_PHIDCHSUBCLASS_LCD_GRAPHIC, // 80
+    LcdText = ffi::Phidget_ChannelSubclass_PHIDCHSUBCLASS_LCD_TEXT,       // 81
+    EncoderModeSettable = ffi::Phidget_ChannelSubclass_PHIDCHSUBCLASS_ENCODER_MODE_SETTABLE, // 96
+    SpatialAhrs = ffi::Phidget_ChannelSubclass_PHIDCHSUBCLASS_SPATIAL_AHRS, // 112
+}
+
+impl TryFrom<u32> for ChannelSubclass {
+    type Error = Error;
+
+    fn try_from(val: u32) -> Result<Self> {
+        use ChannelSubclass::*;
+        match val {
+            ffi::Phidget_ChannelSubclass_PHIDCHSUBCLASS_NONE => Ok(None),
+            ffi::Phidget_ChannelSubclass_PHIDCHSUBCLASS_DIGITALOUTPUT_DUTY_CYCLE => {
+                Ok(DigitalOutputDutyCycle)
+            }
+            ffi::Phidget_ChannelSubclass_PHIDCHSUBCLASS_DIGITALOUTPUT_LED_DRIVER => {
+                Ok(DigitalOutputLedDriver)
+            }
+            ffi::Phidget_ChannelSubclass_PHIDCHSUBCLASS_DIGITALOUTPUT_FREQUENCY => {
+                Ok(DigitalOutputFrequency)
+            }
+            ffi::Phidget_ChannelSubclass_PHIDCHSUBCLASS_TEMPERATURESENSOR_RTD => {
+                Ok(TemperatureSensorRtd)
+            }
+            ffi::Phidget_ChannelSubclass_PHIDCHSUBCLASS_TEMPERATURESENSOR_THERMOCOUPLE => {
+                Ok(TemperatureSensorThermocouple)
+            }
+            ffi::Phidget_ChannelSubclass_PHIDCHSUBCLASS_VOLTAGEINPUT_SENSOR_PORT => {
+                Ok(VoltageInputSensorPort)
+            }
+            ffi::Phidget_ChannelSubclass_PHIDCHSUBCLASS_VOLTAGERATIOINPUT_SENSOR_PORT => {
+                Ok(VoltageRatioInputSensorPort)
+            }
+            ffi::Phidget_ChannelSubclass_PHIDCHSUBCLASS_VOLTAGERATIOINPUT_BRIDGE => {
+                Ok(VoltageRatioInputBridge)
+            }
+            ffi::Phidget_ChannelSubclass_PHIDCHSUBCLASS_LCD_GRAPHIC => Ok(LcdGraphic),
+            ffi::Phidget_ChannelSubclass_PHIDCHSUBCLASS_LCD_TEXT => Ok(LcdText),
+            ffi::Phidget_ChannelSubclass_PHIDCHSUBCLASS_ENCODER_MODE_SETTABLE => {
+                Ok(EncoderModeSettable)
+            }
+            ffi::Phidget_ChannelSubclass_PHIDCHSUBCLASS_SPATIAL_AHRS => Ok(SpatialAhrs),
+            _ => Err(Error::InvalidArg),
+        }
+    }
+}
+
+/// Phidget device class
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u32)]
 #[allow(missing_docs)]
 pub enum DeviceClass {
@@ -294,8 +606,8 @@ pub enum DeviceClass {
     Rfid = ffi::Phidget_DeviceClass_PHIDCLASS_RFID,       // 15
     Servo = ffi::Phidget_DeviceClass_PHIDCLASS_SERVO,     // 16
     Spatial = ffi::Phidget_DeviceClass_PHIDCLASS_SPATIAL, // 17
-    Steper = ffi::Phidget_DeviceClass_PHIDCLASS_STEPPER,  // 18
-    TemperatreSensor = ffi::Phidget_DeviceClass_PHIDCLASS_TEMPERATURESENSOR, // 19
+    Stepper = ffi::Phidget_DeviceClass_PHIDCLASS_STEPPER, // 18
+    TemperatureSensor = ffi::Phidget_DeviceClass_PHIDCLASS_TEMPERATURESENSOR, // 19
     TextLcd = ffi::Phidget_DeviceClass_PHIDCLASS_TEXTLCD, // 20
     Vint = ffi::Phidget_DeviceClass_PHIDCLASS_VINT,       // 21
 }
@@ -328,14 +640,484 @@ impl TryFrom<u32> for DeviceClass {
             ffi::Phidget_DeviceClass_PHIDCLASS_RFID => Ok(Rfid),       // 15
             ffi::Phidget_DeviceClass_PHIDCLASS_SERVO => Ok(Servo),     // 16
             ffi::Phidget_DeviceClass_PHIDCLASS_SPATIAL => Ok(Spatial), // 17
-            ffi::Phidget_DeviceClass_PHIDCLASS_STEPPER => Ok(Steper),  // 18
-            ffi::Phidget_DeviceClass_PHIDCLASS_TEMPERATURESENSOR => Ok(TemperatreSensor), // 19
+            ffi::Phidget_DeviceClass_PHIDCLASS_STEPPER => Ok(Stepper), // 18
+            ffi::Phidget_DeviceClass_PHIDCLASS_TEMPERATURESENSOR => Ok(TemperatureSensor), // 19
             ffi::Phidget_DeviceClass_PHIDCLASS_TEXTLCD => Ok(TextLcd), // 20
             ffi::Phidget_DeviceClass_PHIDCLASS_VINT => Ok(Vint),       // 21
-            _ => Err(ReturnCode::InvalidArg),
+            _ => Err(Error::InvalidArg),
+        }
+    }
+}
+
+impl DeviceClass {
+    /// Gets a human-readable name for the device class.
+    pub fn name(&self) -> &'static str {
+        use DeviceClass::*;
+        match self {
+            Nothing => "Nothing",
+            Accelerometer => "Accelerometer",
+            AdvancedServo => "Advanced Servo",
+            Analog => "Analog",
+            Bridge => "Bridge",
+            DataAdapter => "Data Adapter",
+            Dictionary => "Dictionary",
+            Encoder => "Encoder",
+            FirmwareUpgrade => "Firmware Upgrade",
+            FrequencyCounter => "Frequency Counter",
+            Generic => "Generic",
+            Gps => "GPS",
+            Hub => "Hub",
+            InterfaceKit => "InterfaceKit",
+            Ir => "IR",
+            Led => "LED",
+            MeshDongle => "Mesh Dongle",
+            MotorControl => "Motor Control",
+            PhSensor => "pH Sensor",
+            Rfid => "RFID",
+            Servo => "Servo",
+            Spatial => "Spatial",
+            Stepper => "Stepper",
+            TemperatureSensor => "Temperature Sensor",
+            TextLcd => "TextLCD",
+            Vint => "VINT",
+        }
+    }
+}
+
+impl fmt::Display for DeviceClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[allow(non_upper_case_globals)]
+impl DeviceClass {
+    /// The stepper device class.
+    #[deprecated(note = "Use `DeviceClass::Stepper`")]
+    pub const Steper: Self = Self::Stepper;
+
+    /// The temperature sensor device class.
+    #[deprecated(note = "Use `DeviceClass::TemperatureSensor`")]
+    pub const TemperatreSensor: Self = Self::TemperatureSensor;
+}
+
+/// Phidget device ID, identifying the specific model of a device.
+///
+/// The VINT devices are named for their SKU, like `Tmp1101`, and the
+/// older USB devices for their number, like `Id1018`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(u32)]
+#[allow(missing_docs)]
+pub enum DeviceId {
+    Nothing = ffi::Phidget_DeviceID_PHIDID_NOTHING, // 0
+    Unknown = ffi::Phidget_DeviceID_PHIDID_UNKNOWN, // 125
+    DigitalInputPort = ffi::Phidget_DeviceID_PHIDID_DIGITALINPUT_PORT, // 95
+    DigitalOutputPort = ffi::Phidget_DeviceID_PHIDID_DIGITALOUTPUT_PORT, // 96
+    VoltageInputPort = ffi::Phidget_DeviceID_PHIDID_VOLTAGEINPUT_PORT, // 97
+    VoltageRatioInputPort = ffi::Phidget_DeviceID_PHIDID_VOLTAGERATIOINPUT_PORT, // 98
+    Dictionary = ffi::Phidget_DeviceID_PHIDID_DICTIONARY, // 111
+    Id1000 = ffi::Phidget_DeviceID_PHIDID_1000,     // 2
+    Id1001 = ffi::Phidget_DeviceID_PHIDID_1001,     // 3
+    Id1002 = ffi::Phidget_DeviceID_PHIDID_1002,     // 4
+    Id1008 = ffi::Phidget_DeviceID_PHIDID_1008,     // 5
+    Id1010_1013_1018_1019 = ffi::Phidget_DeviceID_PHIDID_1010_1013_1018_1019, // 6
+    Id1011 = ffi::Phidget_DeviceID_PHIDID_1011,     // 7
+    Id1012 = ffi::Phidget_DeviceID_PHIDID_1012,     // 8
+    Id1014 = ffi::Phidget_DeviceID_PHIDID_1014,     // 9
+    Id1015 = ffi::Phidget_DeviceID_PHIDID_1015,     // 10
+    Id1016 = ffi::Phidget_DeviceID_PHIDID_1016,     // 11
+    Id1017 = ffi::Phidget_DeviceID_PHIDID_1017,     // 12
+    Id1023 = ffi::Phidget_DeviceID_PHIDID_1023,     // 13
+    Id1024 = ffi::Phidget_DeviceID_PHIDID_1024,     // 14
+    Id1030 = ffi::Phidget_DeviceID_PHIDID_1030,     // 15
+    Id1031 = ffi::Phidget_DeviceID_PHIDID_1031,     // 16
+    Id1032 = ffi::Phidget_DeviceID_PHIDID_1032,     // 17
+    Id1040 = ffi::Phidget_DeviceID_PHIDID_1040,     // 18
+    Id1041 = ffi::Phidget_DeviceID_PHIDID_1041,     // 19
+    Id1042 = ffi::Phidget_DeviceID_PHIDID_1042,     // 20
+    Id1043 = ffi::Phidget_DeviceID_PHIDID_1043,     // 21
+    Id1044 = ffi::Phidget_DeviceID_PHIDID_1044,     // 22
+    Id1045 = ffi::Phidget_DeviceID_PHIDID_1045,     // 23
+    Id1046 = ffi::Phidget_DeviceID_PHIDID_1046,     // 24
+    Id1047 = ffi::Phidget_DeviceID_PHIDID_1047,     // 25
+    Id1048 = ffi::Phidget_DeviceID_PHIDID_1048,     // 26
+    Id1049 = ffi::Phidget_DeviceID_PHIDID_1049,     // 27
+    Id1051 = ffi::Phidget_DeviceID_PHIDID_1051,     // 28
+    Id1052 = ffi::Phidget_DeviceID_PHIDID_1052,     // 29
+    Id1053 = ffi::Phidget_DeviceID_PHIDID_1053,     // 30
+    Id1054 = ffi::Phidget_DeviceID_PHIDID_1054,     // 31
+    Id1055 = ffi::Phidget_DeviceID_PHIDID_1055,     // 32
+    Id1056 = ffi::Phidget_DeviceID_PHIDID_1056,     // 33
+    Id1057 = ffi::Phidget_DeviceID_PHIDID_1057,     // 34
+    Id1058 = ffi::Phidget_DeviceID_PHIDID_1058,     // 35
+    Id1059 = ffi::Phidget_DeviceID_PHIDID_1059,     // 36
+    Id1060 = ffi::Phidget_DeviceID_PHIDID_1060,     // 37
+    Id1061 = ffi::Phidget_DeviceID_PHIDID_1061,     // 38
+    Id1062 = ffi::Phidget_DeviceID_PHIDID_1062,     // 39
+    Id1063 = ffi::Phidget_DeviceID_PHIDID_1063,     // 40
+    Id1064 = ffi::Phidget_DeviceID_PHIDID_1064,     // 41
+    Id1065 = ffi::Phidget_DeviceID_PHIDID_1065,     // 42
+    Id1066 = ffi::Phidget_DeviceID_PHIDID_1066,     // 43
+    Id1067 = ffi::Phidget_DeviceID_PHIDID_1067,     // 44
+    Id1202_1203 = ffi::Phidget_DeviceID_PHIDID_1202_1203, // 45
+    Id1204 = ffi::Phidget_DeviceID_PHIDID_1204,     // 46
+    Id1215_1218 = ffi::Phidget_DeviceID_PHIDID_1215__1218, // 47
+    Id1219_1222 = ffi::Phidget_DeviceID_PHIDID_1219__1222, // 48
+    Adp1000 = ffi::Phidget_DeviceID_PHIDID_ADP1000, // 49
+    Daq1000 = ffi::Phidget_DeviceID_PHIDID_DAQ1000, // 51
+    Daq1200 = ffi::Phidget_DeviceID_PHIDID_DAQ1200, // 52
+    Daq1300 = ffi::Phidget_DeviceID_PHIDID_DAQ1300, // 53
+    Daq1301 = ffi::Phidget_DeviceID_PHIDID_DAQ1301, // 54
+    Daq1400 = ffi::Phidget_DeviceID_PHIDID_DAQ1400, // 55
+    Daq1500 = ffi::Phidget_DeviceID_PHIDID_DAQ1500, // 56
+    Dcc1000 = ffi::Phidget_DeviceID_PHIDID_DCC1000, // 57
+    Dcc1001 = ffi::Phidget_DeviceID_PHIDID_DCC1001, // 110
+    Dcc1002 = ffi::Phidget_DeviceID_PHIDID_DCC1002, // 117
+    Dcc1003 = ffi::Phidget_DeviceID_PHIDID_DCC1003, // 120
+    Dcc1100 = ffi::Phidget_DeviceID_PHIDID_DCC1100, // 108
+    Dst1000 = ffi::Phidget_DeviceID_PHIDID_DST1000, // 58
+    Dst1001 = ffi::Phidget_DeviceID_PHIDID_DST1001, // 121
+    Dst1002 = ffi::Phidget_DeviceID_PHIDID_DST1002, // 126
+    Dst1200 = ffi::Phidget_DeviceID_PHIDID_DST1200, // 59
+    Enc1000 = ffi::Phidget_DeviceID_PHIDID_ENC1000, // 60
+    Enc1001 = ffi::Phidget_DeviceID_PHIDID_ENC1001, // 155
+    FirmwareUpgradeSpi = ffi::Phidget_DeviceID_PHIDID_FIRMWARE_UPGRADE_SPI, // 104
+    FirmwareUpgradeStm32f0 = ffi::Phidget_DeviceID_PHIDID_FIRMWARE_UPGRADE_STM32F0, // 102
+    FirmwareUpgradeStm32f3 = ffi::Phidget_DeviceID_PHIDID_FIRMWARE_UPGRADE_STM32F3, // 145
+    FirmwareUpgradeStm32g0 = ffi::Phidget_DeviceID_PHIDID_FIRMWARE_UPGRADE_STM32G0, // 143
+    FirmwareUpgradeStm8s = ffi::Phidget_DeviceID_PHIDID_FIRMWARE_UPGRADE_STM8S, // 103
+    FirmwareUpgradeUsb = ffi::Phidget_DeviceID_PHIDID_FIRMWARE_UPGRADE_USB, // 101
+    Hin1000 = ffi::Phidget_DeviceID_PHIDID_HIN1000, // 61
+    Hin1001 = ffi::Phidget_DeviceID_PHIDID_HIN1001, // 62
+    Hin1100 = ffi::Phidget_DeviceID_PHIDID_HIN1100, // 63
+    Hin1101 = ffi::Phidget_DeviceID_PHIDID_HIN1101, // 109
+    Hub0000 = ffi::Phidget_DeviceID_PHIDID_HUB0000, // 64
+    Hub0001 = ffi::Phidget_DeviceID_PHIDID_HUB0001, // 142
+    Hub0002 = ffi::Phidget_DeviceID_PHIDID_HUB0002, // 147
+    Hub0004 = ffi::Phidget_DeviceID_PHIDID_HUB0004, // 67
+    Hub0007 = ffi::Phidget_DeviceID_PHIDID_HUB0007, // 148
+    Hub5000 = ffi::Phidget_DeviceID_PHIDID_HUB5000, // 123
+    Hum1000 = ffi::Phidget_DeviceID_PHIDID_HUM1000, // 69
+    Hum1001 = ffi::Phidget_DeviceID_PHIDID_HUM1001, // 127
+    Hum1100 = ffi::Phidget_DeviceID_PHIDID_HUM1100, // 136
+    InterfaceKit4_8_8 = ffi::Phidget_DeviceID_PHIDID_INTERFACEKIT_4_8_8, // 1
+    Lcd1100 = ffi::Phidget_DeviceID_PHIDID_LCD1100, // 70
+    Led1000 = ffi::Phidget_DeviceID_PHIDID_LED1000, // 71
+    Lux1000 = ffi::Phidget_DeviceID_PHIDID_LUX1000, // 72
+    Mot0100 = ffi::Phidget_DeviceID_PHIDID_MOT0100, // 146
+    Mot0109 = ffi::Phidget_DeviceID_PHIDID_MOT0109, // 140
+    Mot0110 = ffi::Phidget_DeviceID_PHIDID_MOT0110, // 141
+    Mot1100 = ffi::Phidget_DeviceID_PHIDID_MOT1100, // 73
+    Mot1101 = ffi::Phidget_DeviceID_PHIDID_MOT1101, // 74
+    Mot1102 = ffi::Phidget_DeviceID_PHIDID_MOT1102, // 137
+    Out1000 = ffi::Phidget_DeviceID_PHIDID_OUT1000, // 75
+    Out1001 = ffi::Phidget_DeviceID_PHIDID_OUT1001, // 76
+    Out1002 = ffi::Phidget_DeviceID_PHIDID_OUT1002, // 77
+    Out1100 = ffi::Phidget_DeviceID_PHIDID_OUT1100, // 78
+    Pre1000 = ffi::Phidget_DeviceID_PHIDID_PRE1000, // 79
+    Rcc0004 = ffi::Phidget_DeviceID_PHIDID_RCC0004, // 124
+    Rcc1000 = ffi::Phidget_DeviceID_PHIDID_RCC1000, // 80
+    Rel1000 = ffi::Phidget_DeviceID_PHIDID_REL1000, // 81
+    Rel1100 = ffi::Phidget_DeviceID_PHIDID_REL1100, // 82
+    Rel1101 = ffi::Phidget_DeviceID_PHIDID_REL1101, // 83
+    Saf1000 = ffi::Phidget_DeviceID_PHIDID_SAF1000, // 84
+    Snd1000 = ffi::Phidget_DeviceID_PHIDID_SND1000, // 85
+    Stc1000 = ffi::Phidget_DeviceID_PHIDID_STC1000, // 86
+    Stc1001 = ffi::Phidget_DeviceID_PHIDID_STC1001, // 115
+    Stc1002 = ffi::Phidget_DeviceID_PHIDID_STC1002, // 118
+    Stc1003 = ffi::Phidget_DeviceID_PHIDID_STC1003, // 119
+    Stc1005 = ffi::Phidget_DeviceID_PHIDID_STC1005, // 149
+    Tmp1000 = ffi::Phidget_DeviceID_PHIDID_TMP1000, // 87
+    Tmp1100 = ffi::Phidget_DeviceID_PHIDID_TMP1100, // 88
+    Tmp1101 = ffi::Phidget_DeviceID_PHIDID_TMP1101, // 89
+    Tmp1200 = ffi::Phidget_DeviceID_PHIDID_TMP1200, // 90
+    Vcp1000 = ffi::Phidget_DeviceID_PHIDID_VCP1000, // 92
+    Vcp1001 = ffi::Phidget_DeviceID_PHIDID_VCP1001, // 93
+    Vcp1002 = ffi::Phidget_DeviceID_PHIDID_VCP1002, // 94
+    Vcp1100 = ffi::Phidget_DeviceID_PHIDID_VCP1100, // 105
+}
+
+impl TryFrom<u32> for DeviceId {
+    type Error = Error;
+
+    fn try_from(val: u32) -> Result<Self> {
+        use DeviceId::*;
+        match val {
+            ffi::Phidget_DeviceID_PHIDID_NOTHING => Ok(Nothing), // 0
+            ffi::Phidget_DeviceID_PHIDID_UNKNOWN => Ok(Unknown), // 125
+            ffi::Phidget_DeviceID_PHIDID_DIGITALINPUT_PORT => Ok(DigitalInputPort), // 95
+            ffi::Phidget_DeviceID_PHIDID_DIGITALOUTPUT_PORT => Ok(DigitalOutputPort), // 96
+            ffi::Phidget_DeviceID_PHIDID_VOLTAGEINPUT_PORT => Ok(VoltageInputPort), // 97
+            ffi::Phidget_DeviceID_PHIDID_VOLTAGERATIOINPUT_PORT => Ok(VoltageRatioInputPort), // 98
+            ffi::Phidget_DeviceID_PHIDID_DICTIONARY => Ok(Dictionary), // 111
+            ffi::Phidget_DeviceID_PHIDID_1000 => Ok(Id1000),     // 2
+            ffi::Phidget_DeviceID_PHIDID_1001 => Ok(Id1001),     // 3
+            ffi::Phidget_DeviceID_PHIDID_1002 => Ok(Id1002),     // 4
+            ffi::Phidget_DeviceID_PHIDID_1008 => Ok(Id1008),     // 5
+            ffi::Phidget_DeviceID_PHIDID_1010_1013_1018_1019 => Ok(Id1010_1013_1018_1019), // 6
+            ffi::Phidget_DeviceID_PHIDID_1011 => Ok(Id1011),     // 7
+            ffi::Phidget_DeviceID_PHIDID_1012 => Ok(Id1012),     // 8
+            ffi::Phidget_DeviceID_PHIDID_1014 => Ok(Id1014),     // 9
+            ffi::Phidget_DeviceID_PHIDID_1015 => Ok(Id1015),     // 10
+            ffi::Phidget_DeviceID_PHIDID_1016 => Ok(Id1016),     // 11
+            ffi::Phidget_DeviceID_PHIDID_1017 => Ok(Id1017),     // 12
+            ffi::Phidget_DeviceID_PHIDID_1023 => Ok(Id1023),     // 13
+            ffi::Phidget_DeviceID_PHIDID_1024 => Ok(Id1024),     // 14
+            ffi::Phidget_DeviceID_PHIDID_1030 => Ok(Id1030),     // 15
+            ffi::Phidget_DeviceID_PHIDID_1031 => Ok(Id1031),     // 16
+            ffi::Phidget_DeviceID_PHIDID_1032 => Ok(Id1032),     // 17
+            ffi::Phidget_DeviceID_PHIDID_1040 => Ok(Id1040),     // 18
+            ffi::Phidget_DeviceID_PHIDID_1041 => Ok(Id1041),     // 19
+            ffi::Phidget_DeviceID_PHIDID_1042 => Ok(Id1042),     // 20
+            ffi::Phidget_DeviceID_PHIDID_1043 => Ok(Id1043),     // 21
+            ffi::Phidget_DeviceID_PHIDID_1044 => Ok(Id1044),     // 22
+            ffi::Phidget_DeviceID_PHIDID_1045 => Ok(Id1045),     // 23
+            ffi::Phidget_DeviceID_PHIDID_1046 => Ok(Id1046),     // 24
+            ffi::Phidget_DeviceID_PHIDID_1047 => Ok(Id1047),     // 25
+            ffi::Phidget_DeviceID_PHIDID_1048 => Ok(Id1048),     // 26
+            ffi::Phidget_DeviceID_PHIDID_1049 => Ok(Id1049),     // 27
+            ffi::Phidget_DeviceID_PHIDID_1051 => Ok(Id1051),     // 28
+            ffi::Phidget_DeviceID_PHIDID_1052 => Ok(Id1052),     // 29
+            ffi::Phidget_DeviceID_PHIDID_1053 => Ok(Id1053),     // 30
+            ffi::Phidget_DeviceID_PHIDID_1054 => Ok(Id1054),     // 31
+            ffi::Phidget_DeviceID_PHIDID_1055 => Ok(Id1055),     // 32
+            ffi::Phidget_DeviceID_PHIDID_1056 => Ok(Id1056),     // 33
+            ffi::Phidget_DeviceID_PHIDID_1057 => Ok(Id1057),     // 34
+            ffi::Phidget_DeviceID_PHIDID_1058 => Ok(Id1058),     // 35
+            ffi::Phidget_DeviceID_PHIDID_1059 => Ok(Id1059),     // 36
+            ffi::Phidget_DeviceID_PHIDID_1060 => Ok(Id1060),     // 37
+            ffi::Phidget_DeviceID_PHIDID_1061 => Ok(Id1061),     // 38
+            ffi::Phidget_DeviceID_PHIDID_1062 => Ok(Id1062),     // 39
+            ffi::Phidget_DeviceID_PHIDID_1063 => Ok(Id1063),     // 40
+            ffi::Phidget_DeviceID_PHIDID_1064 => Ok(Id1064),     // 41
+            ffi::Phidget_DeviceID_PHIDID_1065 => Ok(Id1065),     // 42
+            ffi::Phidget_DeviceID_PHIDID_1066 => Ok(Id1066),     // 43
+            ffi::Phidget_DeviceID_PHIDID_1067 => Ok(Id1067),     // 44
+            ffi::Phidget_DeviceID_PHIDID_1202_1203 => Ok(Id1202_1203), // 45
+            ffi::Phidget_DeviceID_PHIDID_1204 => Ok(Id1204),     // 46
+            ffi::Phidget_DeviceID_PHIDID_1215__1218 => Ok(Id1215_1218), // 47
+            ffi::Phidget_DeviceID_PHIDID_1219__1222 => Ok(Id1219_1222), // 48
+            ffi::Phidget_DeviceID_PHIDID_ADP1000 => Ok(Adp1000), // 49
+            ffi::Phidget_DeviceID_PHIDID_DAQ1000 => Ok(Daq1000), // 51
+            ffi::Phidget_DeviceID_PHIDID_DAQ1200 => Ok(Daq1200), // 52
+            ffi::Phidget_DeviceID_PHIDID_DAQ1300 => Ok(Daq1300), // 53
+            ffi::Phidget_DeviceID_PHIDID_DAQ1301 => Ok(Daq1301), // 54
+            ffi::Phidget_DeviceID_PHIDID_DAQ1400 => Ok(Daq1400), // 55
+            ffi::Phidget_DeviceID_PHIDID_DAQ1500 => Ok(Daq1500), // 56
+            ffi::Phidget_DeviceID_PHIDID_DCC1000 => Ok(Dcc1000), // 57
+            ffi::Phidget_DeviceID_PHIDID_DCC1001 => Ok(Dcc1001), // 110
+            ffi::Phidget_DeviceID_PHIDID_DCC1002 => Ok(Dcc1002), // 117
+            ffi::Phidget_DeviceID_PHIDID_DCC1003 => Ok(Dcc1003), // 120
+            ffi::Phidget_DeviceID_PHIDID_DCC1100 => Ok(Dcc1100), // 108
+            ffi::Phidget_DeviceID_PHIDID_DST1000 => Ok(Dst1000), // 58
+            ffi::Phidget_DeviceID_PHIDID_DST1001 => Ok(Dst1001), // 121
+            ffi::Phidget_DeviceID_PHIDID_DST1002 => Ok(Dst1002), // 126
+            ffi::Phidget_DeviceID_PHIDID_DST1200 => Ok(Dst1200), // 59
+            ffi::Phidget_DeviceID_PHIDID_ENC1000 => Ok(Enc1000), // 60
+            ffi::Phidget_DeviceID_PHIDID_ENC1001 => Ok(Enc1001), // 155
+            ffi::Phidget_DeviceID_PHIDID_FIRMWARE_UPGRADE_SPI => Ok(FirmwareUpgradeSpi), // 104
+            ffi::Phidget_DeviceID_PHIDID_FIRMWARE_UPGRADE_STM32F0 => Ok(FirmwareUpgradeStm32f0), // 102
+            ffi::Phidget_DeviceID_PHIDID_FIRMWARE_UPGRADE_STM32F3 => Ok(FirmwareUpgradeStm32f3), // 145
+            ffi::Phidget_DeviceID_PHIDID_FIRMWARE_UPGRADE_STM32G0 => Ok(FirmwareUpgradeStm32g0), // 143
+            ffi::Phidget_DeviceID_PHIDID_FIRMWARE_UPGRADE_STM8S => Ok(FirmwareUpgradeStm8s), // 103
+            ffi::Phidget_DeviceID_PHIDID_FIRMWARE_UPGRADE_USB => Ok(FirmwareUpgradeUsb),     // 101
+            ffi::Phidget_DeviceID_PHIDID_HIN1000 => Ok(Hin1000),                             // 61
+            ffi::Phidget_DeviceID_PHIDID_HIN1001 => Ok(Hin1001),                             // 62
+            ffi::Phidget_DeviceID_PHIDID_HIN1100 => Ok(Hin1100),                             // 63
+            ffi::Phidget_DeviceID_PHIDID_HIN1101 => Ok(Hin1101),                             // 109
+            ffi::Phidget_DeviceID_PHIDID_HUB0000 => Ok(Hub0000),                             // 64
+            ffi::Phidget_DeviceID_PHIDID_HUB0001 => Ok(Hub0001),                             // 142
+            ffi::Phidget_DeviceID_PHIDID_HUB0002 => Ok(Hub0002),                             // 147
+            ffi::Phidget_DeviceID_PHIDID_HUB0004 => Ok(Hub0004),                             // 67
+            ffi::Phidget_DeviceID_PHIDID_HUB0007 => Ok(Hub0007),                             // 148
+            ffi::Phidget_DeviceID_PHIDID_HUB5000 => Ok(Hub5000),                             // 123
+            ffi::Phidget_DeviceID_PHIDID_HUM1000 => Ok(Hum1000),                             // 69
+            ffi::Phidget_DeviceID_PHIDID_HUM1001 => Ok(Hum1001),                             // 127
+            ffi::Phidget_DeviceID_PHIDID_HUM1100 => Ok(Hum1100),                             // 136
+            ffi::Phidget_DeviceID_PHIDID_INTERFACEKIT_4_8_8 => Ok(InterfaceKit4_8_8),        // 1
+            ffi::Phidget_DeviceID_PHIDID_LCD1100 => Ok(Lcd1100),                             // 70
+            ffi::Phidget_DeviceID_PHIDID_LED1000 => Ok(Led1000),                             // 71
+            ffi::Phidget_DeviceID_PHIDID_LUX1000 => Ok(Lux1000),                             // 72
+            ffi::Phidget_DeviceID_PHIDID_MOT0100 => Ok(Mot0100),                             // 146
+            ffi::Phidget_DeviceID_PHIDID_MOT0109 => Ok(Mot0109),                             // 140
+            ffi::Phidget_DeviceID_PHIDID_MOT0110 => Ok(Mot0110),                             // 141
+            ffi::Phidget_DeviceID_PHIDID_MOT1100 => Ok(Mot1100),                             // 73
+            ffi::Phidget_DeviceID_PHIDID_MOT1101 => Ok(Mot1101),                             // 74
+            ffi::Phidget_DeviceID_PHIDID_MOT1102 => Ok(Mot1102),                             // 137
+            ffi::Phidget_DeviceID_PHIDID_OUT1000 => Ok(Out1000),                             // 75
+            ffi::Phidget_DeviceID_PHIDID_OUT1001 => Ok(Out1001),                             // 76
+            ffi::Phidget_DeviceID_PHIDID_OUT1002 => Ok(Out1002),                             // 77
+            ffi::Phidget_DeviceID_PHIDID_OUT1100 => Ok(Out1100),                             // 78
+            ffi::Phidget_DeviceID_PHIDID_PRE1000 => Ok(Pre1000),                             // 79
+            ffi::Phidget_DeviceID_PHIDID_RCC0004 => Ok(Rcc0004),                             // 124
+            ffi::Phidget_DeviceID_PHIDID_RCC1000 => Ok(Rcc1000),                             // 80
+            ffi::Phidget_DeviceID_PHIDID_REL1000 => Ok(Rel1000),                             // 81
+            ffi::Phidget_DeviceID_PHIDID_REL1100 => Ok(Rel1100),                             // 82
+            ffi::Phidget_DeviceID_PHIDID_REL1101 => Ok(Rel1101),                             // 83
+            ffi::Phidget_DeviceID_PHIDID_SAF1000 => Ok(Saf1000),                             // 84
+            ffi::Phidget_DeviceID_PHIDID_SND1000 => Ok(Snd1000),                             // 85
+            ffi::Phidget_DeviceID_PHIDID_STC1000 => Ok(Stc1000),                             // 86
+            ffi::Phidget_DeviceID_PHIDID_STC1001 => Ok(Stc1001),                             // 115
+            ffi::Phidget_DeviceID_PHIDID_STC1002 => Ok(Stc1002),                             // 118
+            ffi::Phidget_DeviceID_PHIDID_STC1003 => Ok(Stc1003),                             // 119
+            ffi::Phidget_DeviceID_PHIDID_STC1005 => Ok(Stc1005),                             // 149
+            ffi::Phidget_DeviceID_PHIDID_TMP1000 => Ok(Tmp1000),                             // 87
+            ffi::Phidget_DeviceID_PHIDID_TMP1100 => Ok(Tmp1100),                             // 88
+            ffi::Phidget_DeviceID_PHIDID_TMP1101 => Ok(Tmp1101),                             // 89
+            ffi::Phidget_DeviceID_PHIDID_TMP1200 => Ok(Tmp1200),                             // 90
+            ffi::Phidget_DeviceID_PHIDID_VCP1000 => Ok(Vcp1000),                             // 92
+            ffi::Phidget_DeviceID_PHIDID_VCP1001 => Ok(Vcp1001),                             // 93
+            ffi::Phidget_DeviceID_PHIDID_VCP1002 => Ok(Vcp1002),                             // 94
+            ffi::Phidget_DeviceID_PHIDID_VCP1100 => Ok(Vcp1100),                             // 105
+            _ => Err(Error::InvalidArg),
+        }
+    }
+}
+
+impl DeviceId {
+    /// Gets the SKU of the device, like "TMP1101".
+    ///
+    /// Some IDs cover several older devices, which are listed together,
+    /// like "1202/1203". IDs that aren't products get a descriptive name.
+    pub fn sku(&self) -> &'static str {
+        use DeviceId::*;
+        match self {
+            Nothing => "Nothing",
+            Unknown => "Unknown",
+            DigitalInputPort => "Digital Input Port",
+            DigitalOutputPort => "Digital Output Port",
+            VoltageInputPort => "Voltage Input Port",
+            VoltageRatioInputPort => "Voltage Ratio Input Port",
+            Dictionary => "Dictionary",
+            Id1000 => "1000",
+            Id1001 => "1001",
+            Id1002 => "1002",
+            Id1008 => "1008",
+            Id1010_1013_1018_1019 => "1010/1013/1018/1019",
+            Id1011 => "1011",
+            Id1012 => "1012",
+            Id1014 => "1014",
+            Id1015 => "1015",
+            Id1016 => "1016",
+            Id1017 => "1017",
+            Id1023 => "1023",
+            Id1024 => "1024",
+            Id1030 => "1030",
+            Id1031 => "1031",
+            Id1032 => "1032",
+            Id1040 => "1040",
+            Id1041 => "1041",
+            Id1042 => "1042",
+            Id1043 => "1043",
+            Id1044 => "1044",
+            Id1045 => "1045",
+            Id1046 => "1046",
+            Id1047 => "1047",
+            Id1048 => "1048",
+            Id1049 => "1049",
+            Id1051 => "1051",
+            Id1052 => "1052",
+            Id1053 => "1053",
+            Id1054 => "1054",
+            Id1055 => "1055",
+            Id1056 => "1056",
+            Id1057 => "1057",
+            Id1058 => "1058",
+            Id1059 => "1059",
+            Id1060 => "1060",
+            Id1061 => "1061",
+            Id1062 => "1062",
+            Id1063 => "1063",
+            Id1064 => "1064",
+            Id1065 => "1065",
+            Id1066 => "1066",
+            Id1067 => "1067",
+            Id1202_1203 => "1202/1203",
+            Id1204 => "1204",
+            Id1215_1218 => "1215-1218",
+            Id1219_1222 => "1219-1222",
+            Adp1000 => "ADP1000",
+            Daq1000 => "DAQ1000",
+            Daq1200 => "DAQ1200",
+            Daq1300 => "DAQ1300",
+            Daq1301 => "DAQ1301",
+            Daq1400 => "DAQ1400",
+            Daq1500 => "DAQ1500",
+            Dcc1000 => "DCC1000",
+            Dcc1001 => "DCC1001",
+            Dcc1002 => "DCC1002",
+            Dcc1003 => "DCC1003",
+            Dcc1100 => "DCC1100",
+            Dst1000 => "DST1000",
+            Dst1001 => "DST1001",
+            Dst1002 => "DST1002",
+            Dst1200 => "DST1200",
+            Enc1000 => "ENC1000",
+            Enc1001 => "ENC1001",
+            FirmwareUpgradeSpi => "Firmware Upgrade (SPI)",
+            FirmwareUpgradeStm32f0 => "Firmware Upgrade (STM32F0)",
+            FirmwareUpgradeStm32f3 => "Firmware Upgrade (STM32F3)",
+            FirmwareUpgradeStm32g0 => "Firmware Upgrade (STM32G0)",
+            FirmwareUpgradeStm8s => "Firmware Upgrade (STM8S)",
+            FirmwareUpgradeUsb => "Firmware Upgrade (USB)",
+            Hin1000 => "HIN1000",
+            Hin1001 => "HIN1001",
+            Hin1100 => "HIN1100",
+            Hin1101 => "HIN1101",
+            Hub0000 => "HUB0000",
+            Hub0001 => "HUB0001",
+            Hub0002 => "HUB0002",
+            Hub0004 => "HUB0004",
+            Hub0007 => "HUB0007",
+            Hub5000 => "HUB5000",
+            Hum1000 => "HUM1000",
+            Hum1001 => "HUM1001",
+            Hum1100 => "HUM1100",
+            InterfaceKit4_8_8 => "InterfaceKit 4/8/8",
+            Lcd1100 => "LCD1100",
+            Led1000 => "LED1000",
+            Lux1000 => "LUX1000",
+            Mot0100 => "MOT0100",
+            Mot0109 => "MOT0109",
+            Mot0110 => "MOT0110",
+            Mot1100 => "MOT1100",
+            Mot1101 => "MOT1101",
+            Mot1102 => "MOT1102",
+            Out1000 => "OUT1000",
+            Out1001 => "OUT1001",
+            Out1002 => "OUT1002",
+            Out1100 => "OUT1100",
+            Pre1000 => "PRE1000",
+            Rcc0004 => "RCC0004",
+            Rcc1000 => "RCC1000",
+            Rel1000 => "REL1000",
+            Rel1100 => "REL1100",
+            Rel1101 => "REL1101",
+            Saf1000 => "SAF1000",
+            Snd1000 => "SND1000",
+            Stc1000 => "STC1000",
+            Stc1001 => "STC1001",
+            Stc1002 => "STC1002",
+            Stc1003 => "STC1003",
+            Stc1005 => "STC1005",
+            Tmp1000 => "TMP1000",
+            Tmp1100 => "TMP1100",
+            Tmp1101 => "TMP1101",
+            Tmp1200 => "TMP1200",
+            Vcp1000 => "VCP1000",
+            Vcp1001 => "VCP1001",
+            Vcp1002 => "VCP1002",
+            Vcp1100 => "VCP1100",
         }
     }
 }
+
+impl fmt::Display for DeviceId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.sku())
+    }
+}
 /////////////////////////////////////////////////////////////////////////////
 
 /// The the full version of the phidget22 library as a string.
@@ -350,12 +1132,166 @@ pub fn library_version_number() -> Result<String> {
     get_ffi_string(|s| unsafe { ffi::Phidget_getLibraryVersionNumber(s) })
 }
 
+/// Checks that the phidget22 library that was loaded at runtime is at
+/// least the version that the bindings were generated from.
+///
+/// An older library is missing some of the functions in the bindings,
+/// which then fail with `Unsupported` errors. Calling this at startup
+/// reports the problem up front, as an `Error::Version`.
+pub fn version_check() -> Result<()> {
+    let library = library_version_number()?;
+    let (major, minor) = ffi::PHIDGET22_BINDINGS_VERSION;
+
+    let mut nums = library.split('.').map(|s| {
+        s.trim_end_matches(|c: char| !c.is_ascii_digit())
+            .parse::<u32>()
+    });
+
+    if let (Some(Ok(lib_major)), Some(Ok(lib_minor))) = (nums.next(), nums.next()) {
+        if lib_major == major && lib_minor >= minor {
+            return Ok(());
+        }
+    }
+    Err(Error::Version {
+        library,
+        bindings: format!("{}.{}", major, minor),
+    })
+}
+
+/// Loads the phidget22 library at runtime, if it isn't already loaded.
+///
+/// With the `dlopen` feature, the library is loaded by the first call
+/// into it. Calling this at startup reports whether it's available, as an
+/// `Error::LibraryNotFound` if not, so that an application can carry on
+/// without Phidget support.
+#[cfg(feature = "dlopen")]
+pub fn load_library() -> Result<()> {
+    ffi::dlopen::load().map_err(Error::LibraryNotFound)
+}
+
+/// Resets the phidget22 library to its newly loaded state.
+///
+/// This shuts down the hardware the crate opened, with `shutdown()`, and
+/// then has the library close everything else and stop its threads. The
+/// library can be used again afterward, so a long-running process, or a
+/// test harness, can start over without restarting.
+///
+/// # Safety
+///
+/// The library frees all of its channels and managers, so every channel,
+/// manager, and handler guard must be dropped before this is called.
+/// Using one of them afterward is undefined behavior.
+pub unsafe fn reset_library() -> Result<()> {
+    let res = shutdown();
+    ReturnCode::result(ffi::Phidget_resetLibrary())?;
+    res
+}
+
+/// Finalizes the phidget22 library, releasing all of its resources.
+///
+/// This is like `reset_library()`, but the library can't be used again,
+/// so it should only be called as the process exits.
+///
+/// # Safety
+///
+/// Every channel, manager, and handler guard must be dropped before this
+/// is called, and no other library function may be called afterward.
+pub unsafe fn finalize() -> Result<()> {
+    let res = shutdown();
+    ReturnCode::result(ffi::Phidget_finalize(0))?;
+    res
+}
+
 /////////////////////////////////////////////////////////////////////////////
 
 #[cfg(test)]
 mod tests {
-    //use super::*;
+    use super::*;
 
     #[test]
     fn it_works() {}
+
+    #[test]
+    fn test_display_names() {
+        assert_eq!(
+            ChannelClass::TemperatureSensor.to_string(),
+            "Temperature Sensor"
+        );
+        assert_eq!(ChannelClass::PhSensor.to_string(), "pH Sensor");
+        assert_eq!(DeviceClass::Vint.to_string(), "VINT");
+        assert_eq!(DeviceId::Tmp1101.to_string(), "TMP1101");
+        assert_eq!(DeviceId::Id1202_1203.sku(), "1202/1203");
+    }
+
+    #[test]
+    fn test_channel_subclasses() {
+        use ChannelSubclass::*;
+        let all = [
+            (None, ffi::Phidget_ChannelSubclass_PHIDCHSUBCLASS_NONE),
+            (
+                DigitalOutputDutyCycle,
+                ffi::Phidget_ChannelSubclass_PHIDCHSUBCLASS_DIGITALOUTPUT_DUTY_CYCLE,
+            ),
+            (
+                DigitalOutputLedDriver,
+                ffi::Phidget_ChannelSubclass_PHIDCHSUBCLASS_DIGITALOUTPUT_LED_DRIVER,
+            ),
+            (
+                DigitalOutputFrequency,
+                ffi::Phidget_ChannelSubclass_PHIDCHSUBCLASS_DIGITALOUTPUT_FREQUENCY,
+            ),
+            (
+                TemperatureSensorRtd,
+                ffi::Phidget_ChannelSubclass_PHIDCHSUBCLASS_TEMPERATURESENSOR_RTD,
+            ),
+            (
+                TemperatureSensorThermocouple,
+                ffi::Phidget_ChannelSubclass_PHIDCHSUBCLASS_TEMPERATURESENSOR_THERMOCOUPLE,
+            ),
+            (
+                VoltageInputSensorPort,
+                ffi::Phidget_ChannelSubclass_PHIDCHSUBCLASS_VOLTAGEINPUT_SENSOR_PORT,
+            ),
+            (
+                VoltageRatioInputSensorPort,
+                ffi::Phidget_ChannelSubclass_PHIDCHSUBCLASS_VOLTAGERATIOINPUT_SENSOR_PORT,
+            ),
+            (
+                VoltageRatioInputBridge,
+                ffi::Phidget_ChannelSubclass_PHIDCHSUBCLASS_VOLTAGERATIOINPUT_BRIDGE,
+            ),
+            (
+                LcdGraphic,
+                ffi::Phidget_ChannelSubclass_PHIDCHSUBCLASS_LCD_GRAPHIC,
+            ),
+            (
+                LcdText,
+                ffi::Phidget_ChannelSubclass_PHIDCHSUBCLASS_LCD_TEXT,
+            ),
+            (
+                EncoderModeSettable,
+                ffi::Phidget_ChannelSubclass_PHIDCHSUBCLASS_ENCODER_MODE_SETTABLE,
+            ),
+            (
+                SpatialAhrs,
+                ffi::Phidget_ChannelSubclass_PHIDCHSUBCLASS_SPATIAL_AHRS,
+            ),
+        ];
+        for (subclass, val) in all {
+            assert_eq!(subclass as u32, val);
+            assert_eq!(ChannelSubclass::try_from(val), Ok(subclass));
+        }
+        assert_eq!(ChannelSubclass::try_from(0), Err(Error::InvalidArg));
+    }
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod mock_tests {
+    use super::*;
+
+    #[test]
+    fn test_mock_version_check() {
+        assert_eq!(library_version_number().as_deref(), Ok("1.14-mock"));
+        assert_eq!(version_check(), Ok(()));
+    }
 }
@@ -0,0 +1,152 @@
+// phidget-rs/src/limit.rs
+//
+// This file is part of the 'phidget-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+//! Debouncing and rate-limiting of event handlers.
+//!
+//! These wrap an event handler in another one that filters the events by
+//! time, and can be passed to any of the `set_on_*_handler()` functions.
+//! A mechanical switch on a digital input chatters for a few milliseconds
+//! when it opens or closes, which can be tamed with `debounced()`:
+//!
+//! ```text
+//! let _handler = switch.set_on_state_change_handler(debounced(
+//!     Duration::from_millis(20),
+//!     |_, state| println!("Switch: {}", state),
+//! ))?;
+//! ```
+//!
+//! A noisy analog channel can report far more changes than the
+//! application needs, which can be limited with `throttled()`:
+//!
+//! ```text
+//! let _handler = sensor.set_on_voltage_change_handler(throttled(
+//!     Duration::from_secs(1),
+//!     |_, v| println!("Voltage: {}", v),
+//! ))?;
+//! ```
+//!
+//! The events are filtered on the library's callback thread, as they
+//! arrive, so nothing is ever delivered late. But this means that an
+//! event that is dropped isn't delivered later, either.
+//!
+
+use std::time::{Duration, Instant};
+
+/// Wraps an event handler so that a burst of events only delivers the
+/// first one.
+///
+/// An event is delivered only if the channel has been quiet for at least
+/// the specified time before it. Every event, whether or not it's
+/// delivered, restarts the quiet time, so the rest of the burst is
+/// dropped until the events stop for that long.
+pub fn debounced<C, T, F>(quiet: Duration, mut f: F) -> impl FnMut(&C, T) + Send + 'static
+where
+    C: ?Sized + 'static,
+    T: 'static,
+    F: FnMut(&C, T) + Send + 'static,
+{
+    let mut last: Option<Instant> = None;
+    move |ch, val| {
+        let now = Instant::now();
+        let is_quiet = last.map_or(true, |t| now.duration_since(t) >= quiet);
+        last = Some(now);
+        if is_quiet {
+            f(ch, val);
+        }
+    }
+}
+
+/// Wraps an event handler so that at most one event is delivered in any
+/// period of the specified time.
+///
+/// An event is delivered if at least the specified time has passed since
+/// the last one that was delivered. Any events in between are dropped.
+pub fn throttled<C, T, F>(period: Duration, mut f: F) -> impl FnMut(&C, T) + Send + 'static
+where
+    C: ?Sized + 'static,
+    T: 'static,
+    F: FnMut(&C, T) + Send + 'static,
+{
+    let mut last: Option<Instant> = None;
+    move |ch, val| {
+        let now = Instant::now();
+        if last.map_or(true, |t| now.duration_since(t) >= period) {
+            last = Some(now);
+            f(ch, val);
+        }
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{sync::mpsc, thread};
+
+    #[test]
+    fn test_debounced() {
+        let (tx, rx) = mpsc::channel();
+        let mut f = debounced(Duration::from_millis(50), move |_: &(), v: i32| {
+            tx.send(v).unwrap()
+        });
+
+        // Only the first event of the burst gets through
+        for v in 1..=3 {
+            f(&(), v);
+        }
+        thread::sleep(Duration::from_millis(60));
+        f(&(), 4);
+
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec![1, 4]);
+    }
+
+    #[test]
+    fn test_throttled() {
+        let (tx, rx) = mpsc::channel();
+        let mut f = throttled(Duration::from_millis(50), move |_: &(), v: i32| {
+            tx.send(v).unwrap()
+        });
+
+        for v in 1..=3 {
+            f(&(), v);
+        }
+        thread::sleep(Duration::from_millis(60));
+        f(&(), 4);
+        f(&(), 5);
+
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec![1, 4]);
+    }
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod mock_tests {
+    use super::*;
+    use crate::{mock::set_value, DigitalInput, Phidget};
+    use std::sync::mpsc;
+
+    #[test]
+    fn test_mock_debounced() {
+        let mut di = DigitalInput::new();
+        di.set_serial_number(639_001).unwrap();
+        di.open().unwrap();
+
+        let (tx, rx) = mpsc::channel();
+        let _handler = di
+            .set_on_state_change_handler(debounced(Duration::from_secs(60), move |_, state| {
+                tx.send(state).unwrap()
+            }))
+            .unwrap();
+
+        for state in [1.0, 0.0, 1.0] {
+            set_value(&mut di, state).unwrap();
+        }
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec![1]);
+    }
+}
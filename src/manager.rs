@@ -0,0 +1,232 @@
+// phidget-rs/src/manager.rs
+//
+// This file is part of the 'phidget-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+//! The Phidget Manager, to discover the channels that are available.
+//!
+//! The manager reports every channel that becomes available to the
+//! system, and every one that goes away, whether or not the application
+//! has opened it. When it's opened, the manager reports an attach event
+//! for each channel that is already available.
+//!
+//! ```text
+//! let mut mgr = Manager::new();
+//! mgr.set_on_attach_handler(|ph| println!("Attached: {}", ph))?;
+//! mgr.open()?;
+//! ```
+//!
+//! To simply get a snapshot of the available channels, use `list()`.
+//!
+
+use crate::{ChannelClass, DeviceClass, GenericPhidget, Phidget, Result, ReturnCode};
+use phidget_sys::{self as ffi, PhidgetHandle, PhidgetManagerHandle};
+use std::{
+    os::raw::c_void,
+    ptr,
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+/// The signature for manager attach and detach callbacks
+pub type ManagerCallback = dyn FnMut(&mut GenericPhidget) + Send + 'static;
+
+// Low-level, unsafe callback for manager attach and detach events
+unsafe extern "C" fn on_event(_mgr: PhidgetManagerHandle, ctx: *mut c_void, phid: PhidgetHandle) {
+    if !ctx.is_null() {
+        let cb: &mut Box<ManagerCallback> = &mut *(ctx as *mut _);
+        let mut ph = GenericPhidget::from(phid);
+        crate::handler::catch(phid, "manager", || cb(&mut ph));
+    }
+}
+
+// Release the memory held in a double-boxed callback.
+fn drop_cb(ctx: *mut c_void) {
+    if !ctx.is_null() {
+        let _: Box<Box<ManagerCallback>> = unsafe { Box::from_raw(ctx as *mut _) };
+    }
+}
+
+/// Information about a channel that is available to the system.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChannelInfo {
+    /// The class of the channel
+    pub channel_class: ChannelClass,
+    /// The name of the channel
+    pub channel_name: String,
+    /// The class of the device
+    pub device_class: DeviceClass,
+    /// The serial number of the device, or the VINT Hub
+    pub serial_number: i32,
+    /// The port on the VINT Hub
+    pub hub_port: i32,
+    /// The index of the channel on the device
+    pub channel: i32,
+    /// Whether the channel is a VINT Hub port used directly as a device
+    pub is_hub_port_device: bool,
+}
+
+impl ChannelInfo {
+    /// Reads the information about the channel from the library.
+    pub fn from_phidget<P: Phidget>(ph: &mut P) -> Result<Self> {
+        Ok(Self {
+            channel_class: ph.channel_class()?,
+            channel_name: ph.channel_name()?,
+            device_class: ph.device_class()?,
+            serial_number: ph.serial_number()?,
+            hub_port: ph.hub_port()?,
+            channel: ph.channel()?,
+            is_hub_port_device: ph.is_hub_port_device()?,
+        })
+    }
+}
+
+/// The Phidget Manager.
+///
+/// The handlers must be set before the manager is opened. They are
+/// removed when the manager is dropped.
+pub struct Manager {
+    // Handle to the manager for the phidget22 library
+    mgr: PhidgetManagerHandle,
+    // Double-boxed attach callback, if registered
+    attach_cb: *mut c_void,
+    // Double-boxed detach callback, if registered
+    detach_cb: *mut c_void,
+}
+
+impl Manager {
+    /// Creates a new manager.
+    ///
+    /// This panics if the library can't create the manager. Use
+    /// `try_new()` to get the error instead.
+    pub fn new() -> Self {
+        Self::try_new().expect("failed to create the manager")
+    }
+
+    /// Creates a new manager, returning an error if the library can't
+    /// create it.
+    pub fn try_new() -> Result<Self> {
+        let mut mgr: PhidgetManagerHandle = ptr::null_mut();
+        ReturnCode::result(unsafe { ffi::PhidgetManager_create(&mut mgr) })?;
+        Ok(Self {
+            mgr,
+            attach_cb: ptr::null_mut(),
+            detach_cb: ptr::null_mut(),
+        })
+    }
+
+    /// Sets a handler to receive the channel attach events.
+    ///
+    /// The phidget passed to the handler can be queried for information
+    /// about the channel. It can be cloned to keep it beyond the call, or
+    /// converted into a typed channel.
+    /// This replaces any attach handler previously set.
+    pub fn set_on_attach_handler<F>(&mut self, cb: F) -> Result<()>
+    where
+        F: FnMut(&mut GenericPhidget) + Send + 'static,
+    {
+        // 1st box is fat ptr, 2nd is regular pointer.
+        let cb: Box<Box<ManagerCallback>> = Box::new(Box::new(cb));
+        let ctx = Box::into_raw(cb) as *mut c_void;
+
+        let rc = unsafe { ffi::PhidgetManager_setOnAttachHandler(self.mgr, Some(on_event), ctx) };
+        if let Err(err) = ReturnCode::result(rc) {
+            drop_cb(ctx);
+            return Err(err);
+        }
+        drop_cb(self.attach_cb);
+        self.attach_cb = ctx;
+        Ok(())
+    }
+
+    /// Sets a handler to receive the channel detach events.
+    ///
+    /// The phidget passed to the handler can be queried for information
+    /// about the channel. It can be cloned to keep it beyond the call, or
+    /// converted into a typed channel.
+    /// This replaces any detach handler previously set.
+    pub fn set_on_detach_handler<F>(&mut self, cb: F) -> Result<()>
+    where
+        F: FnMut(&mut GenericPhidget) + Send + 'static,
+    {
+        // 1st box is fat ptr, 2nd is regular pointer.
+        let cb: Box<Box<ManagerCallback>> = Box::new(Box::new(cb));
+        let ctx = Box::into_raw(cb) as *mut c_void;
+
+        let rc = unsafe { ffi::PhidgetManager_setOnDetachHandler(self.mgr, Some(on_event), ctx) };
+        if let Err(err) = ReturnCode::result(rc) {
+            drop_cb(ctx);
+            return Err(err);
+        }
+        drop_cb(self.detach_cb);
+        self.detach_cb = ctx;
+        Ok(())
+    }
+
+    /// Opens the manager, to start receiving events.
+    pub fn open(&mut self) -> Result<()> {
+        ReturnCode::result(unsafe { ffi::PhidgetManager_open(self.mgr) })?;
+        crate::shutdown::manager_opened(self.mgr);
+        Ok(())
+    }
+
+    /// Closes the manager.
+    pub fn close(&mut self) -> Result<()> {
+        crate::shutdown::manager_closed(self.mgr);
+        ReturnCode::result(unsafe { ffi::PhidgetManager_close(self.mgr) })
+    }
+}
+
+impl Default for Manager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+unsafe impl Send for Manager {}
+
+impl Drop for Manager {
+    fn drop(&mut self) {
+        crate::shutdown::manager_closed(self.mgr);
+        unsafe {
+            ffi::PhidgetManager_close(self.mgr);
+            ffi::PhidgetManager_delete(&mut self.mgr);
+        }
+        drop_cb(self.attach_cb);
+        drop_cb(self.detach_cb);
+    }
+}
+
+/// Gets the information about all the channels that are available.
+///
+/// This opens a manager, and collects the channels that it reports over
+/// the specified time. The library discovers the devices in the
+/// background, so a time of a second or so is usually needed to find
+/// them all, particularly any on the network.
+pub fn list(wait: Duration) -> Result<Vec<ChannelInfo>> {
+    let chans = Arc::new(Mutex::new(Vec::new()));
+
+    let mut mgr = Manager::try_new()?;
+    let attach_chans = Arc::clone(&chans);
+    mgr.set_on_attach_handler(move |ph| {
+        if let Ok(info) = ChannelInfo::from_phidget(ph) {
+            attach_chans
+                .lock()
+                .unwrap_or_else(|err| err.into_inner())
+                .push(info);
+        }
+    })?;
+    mgr.open()?;
+    thread::sleep(wait);
+    drop(mgr);
+
+    let mut chans = chans.lock().unwrap_or_else(|err| err.into_inner()).clone();
+    chans.sort_by_key(|c| (c.serial_number, c.hub_port, c.channel, c.channel_class));
+    Ok(chans)
+}
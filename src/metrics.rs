@@ -0,0 +1,167 @@
+// phidget-rs/src/metrics.rs
+//
+// This file is part of the 'phidget-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+//! Health metrics for the channels, through the `metrics` crate.
+//!
+//! With the `metrics` feature, every channel created by the crate records
+//! its device events, open and close operations, and the time spent in
+//! its callbacks, to whichever `metrics` recorder the application has
+//! installed. Nothing is recorded if there is no recorder.
+//!
+//! | Name                                | Type      | Labels            |
+//! |-------------------------------------|-----------|-------------------|
+//! | `phidget_attach_total`              | counter   | `class`           |
+//! | `phidget_detach_total`              | counter   | `class`           |
+//! | `phidget_attached`                  | gauge     | `class`           |
+//! | `phidget_error_events_total`        | counter   | `class`, `code`   |
+//! | `phidget_open_total`                | counter   | `class`, `result` |
+//! | `phidget_close_total`               | counter   | `class`           |
+//! | `phidget_callback_duration_seconds` | histogram | `event`           |
+//!
+//! The `class` label is the name of the channel class, like
+//! "TemperatureSensor", and `result` is "ok" or "error".
+//!
+//! ```text
+//! metrics_exporter_prometheus::PrometheusBuilder::new().install()?;
+//! phidget::metrics::describe();
+//! ```
+//!
+
+use crate::{ChannelClass, ErrorEventCode, Result};
+use phidget_sys::{self as ffi, PhidgetHandle};
+use std::{os::raw::c_uint, time::Duration};
+
+/// The number of times that channels attached
+pub const ATTACH_TOTAL: &str = "phidget_attach_total";
+/// The number of times that channels detached
+pub const DETACH_TOTAL: &str = "phidget_detach_total";
+/// The number of channels currently attached
+pub const ATTACHED: &str = "phidget_attached";
+/// The number of error events reported by the channels
+pub const ERROR_EVENTS_TOTAL: &str = "phidget_error_events_total";
+/// The number of attempts to open a channel
+pub const OPEN_TOTAL: &str = "phidget_open_total";
+/// The number of times that channels were closed
+pub const CLOSE_TOTAL: &str = "phidget_close_total";
+/// The time spent in the application's event callbacks
+pub const CALLBACK_DURATION_SECONDS: &str = "phidget_callback_duration_seconds";
+
+/// Registers the descriptions of the crate's metrics with the installed
+/// recorder.
+///
+/// This is optional, but lets exporters that support it show help text
+/// and units for each metric.
+pub fn describe() {
+    use ::metrics::{describe_counter, describe_gauge, describe_histogram, Unit};
+
+    describe_counter!(ATTACH_TOTAL, "The number of times that channels attached");
+    describe_counter!(DETACH_TOTAL, "The number of times that channels detached");
+    describe_gauge!(ATTACHED, "The number of channels currently attached");
+    describe_counter!(
+        ERROR_EVENTS_TOTAL,
+        "The number of error events reported by the channels"
+    );
+    describe_counter!(OPEN_TOTAL, "The number of attempts to open a channel");
+    describe_counter!(CLOSE_TOTAL, "The number of times that channels were closed");
+    describe_histogram!(
+        CALLBACK_DURATION_SECONDS,
+        Unit::Seconds,
+        "The time spent in the application's event callbacks"
+    );
+}
+
+// Gets the name of the channel's class, for the `class` label.
+fn class(phid: PhidgetHandle) -> &'static str {
+    let mut cls: c_uint = 0;
+    match unsafe { ffi::Phidget_getChannelClass(phid, &mut cls) } {
+        0 => ChannelClass::try_from(cls)
+            .unwrap_or(ChannelClass::Nothing)
+            .name(),
+        _ => ChannelClass::Nothing.name(),
+    }
+}
+
+/// Records an attach event for the channel.
+pub(crate) fn attached(phid: PhidgetHandle) {
+    let class = class(phid);
+    ::metrics::counter!(ATTACH_TOTAL, "class" => class).increment(1);
+    ::metrics::gauge!(ATTACHED, "class" => class).increment(1.0);
+}
+
+/// Records a detach event for the channel.
+pub(crate) fn detached(phid: PhidgetHandle) {
+    let class = class(phid);
+    ::metrics::counter!(DETACH_TOTAL, "class" => class).increment(1);
+    ::metrics::gauge!(ATTACHED, "class" => class).decrement(1.0);
+}
+
+/// Records an error event for the channel.
+pub(crate) fn error(phid: PhidgetHandle, code: ErrorEventCode) {
+    ::metrics::counter!(
+        ERROR_EVENTS_TOTAL,
+        "class" => class(phid),
+        "code" => format!("{:?}", code)
+    )
+    .increment(1);
+}
+
+/// Records an attempt to open the channel, and whether it succeeded.
+pub(crate) fn opened(phid: PhidgetHandle, res: &Result<()>) {
+    let result = if res.is_ok() { "ok" } else { "error" };
+    ::metrics::counter!(OPEN_TOTAL, "class" => class(phid), "result" => result).increment(1);
+}
+
+/// Records that the channel was closed.
+pub(crate) fn closed(phid: PhidgetHandle) {
+    ::metrics::counter!(CLOSE_TOTAL, "class" => class(phid)).increment(1);
+}
+
+/// Records the time spent in a callback for the named event.
+pub(crate) fn callback(event: &'static str, elapsed: Duration) {
+    ::metrics::histogram!(CALLBACK_DURATION_SECONDS, "event" => event).record(elapsed);
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::*;
+    use crate::{mock::raise_error, Phidget, TemperatureSensor};
+
+    #[test]
+    fn test_mock_metrics() {
+        use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+
+        ::metrics::with_local_recorder(&recorder, || {
+            let mut sensor = TemperatureSensor::new();
+            let _handler = sensor.set_on_attach_handler(|_| {}).unwrap();
+            sensor.open().unwrap();
+            raise_error(&mut sensor, ErrorEventCode::Overrun, "overrun");
+            sensor.close().unwrap();
+        });
+
+        let snap = snapshotter.snapshot().into_vec();
+        let value = |name| {
+            snap.iter()
+                .find(|(key, ..)| key.key().name() == name)
+                .map(|(.., val)| val)
+        };
+        assert_eq!(value(OPEN_TOTAL), Some(&DebugValue::Counter(1)));
+        assert_eq!(value(ATTACH_TOTAL), Some(&DebugValue::Counter(1)));
+        assert_eq!(value(ERROR_EVENTS_TOTAL), Some(&DebugValue::Counter(1)));
+        assert_eq!(value(CLOSE_TOTAL), Some(&DebugValue::Counter(1)));
+        assert!(matches!(
+            value(CALLBACK_DURATION_SECONDS),
+            Some(DebugValue::Histogram(h)) if h.len() == 1
+        ));
+    }
+}
@@ -0,0 +1,139 @@
+// phidget-rs/src/mock.rs
+//
+// This file is part of the 'phidget-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+//! Control of simulated devices, for testing without hardware.
+//!
+//! When the crate is built with the `mock` feature, it doesn't link to
+//! the phidget22 library. The library is simulated in memory instead,
+//! and channels are created and used through the normal wrapper types.
+//! A channel attaches to a simulated device as soon as it is opened.
+//! The functions in this module then drive the simulation: detaching and
+//! re-attaching the device, feeding it new values, and raising errors.
+//! The events are delivered to the channel's handlers, and to the event
+//! bus, on the calling thread.
+//!
+//! ```text
+//! let mut sensor = TemperatureSensor::new();
+//! let _handler = sensor.set_on_temperature_change_handler(|_, t| {
+//!     println!("Temperature: {}", t);
+//! })?;
+//! sensor.open()?;
+//!
+//! phidget::mock::set_value(&mut sensor, 21.5)?;
+//! assert_eq!(sensor.temperature()?, 21.5);
+//! ```
+//!
+//! Properties that are not fed by `set_value()`, like the minimum and
+//! maximum values of a sensor, can be set with `set_property()`, using
+//! the name from the phidget22 API. Reading a property that was never set
+//! returns `Error::UnknownValue`.
+//!
+
+use crate::{
+    devices::{Accelerometer, Encoder, Gps, Spatial, SpatialData},
+    ErrorEventCode, Phidget, ReturnCode,
+};
+use phidget_sys::mock as ffi;
+use std::ffi::CString;
+
+pub use phidget_sys::mock::MOCK_SERIAL_NUMBER;
+
+/// Attaches an open channel to its simulated device, firing the attach
+/// event.
+///
+/// This has no effect if the channel is not open or is already attached.
+/// Any part of the address that was not set, like the serial number, is
+/// given a value when the channel attaches.
+pub fn attach<P: Phidget + ?Sized>(dev: &mut P) {
+    ffi::attach(dev.as_handle());
+}
+
+/// Detaches a channel from its simulated device, firing the detach event.
+///
+/// The channel stays open, and can be attached again with `attach()`.
+pub fn detach<P: Phidget + ?Sized>(dev: &mut P) {
+    ffi::detach(dev.as_handle());
+}
+
+/// Sets the value reported by an attached channel, firing its change
+/// event.
+///
+/// This is the main reading of the channel, like the temperature of a
+/// temperature sensor, or the position of a stepper. Boolean states are
+/// set as 0.0 or 1.0. As with a device, a value that differs from the last
+/// one by less than the change trigger of the channel is dropped.
+pub fn set_value<P: Phidget + ?Sized>(dev: &mut P, val: f64) -> crate::Result<()> {
+    ReturnCode::result(ffi::set_value(dev.as_handle(), val))
+}
+
+/// Sets a property of a channel, without firing any events.
+///
+/// The name is the one used by the phidget22 API, such as "MinVoltage"
+/// for the minimum voltage of a voltage input.
+pub fn set_property<P: Phidget + ?Sized>(
+    dev: &mut P,
+    name: &'static str,
+    val: f64,
+) -> crate::Result<()> {
+    ReturnCode::result(ffi::set_property(dev.as_handle(), name, val))
+}
+
+/// Sets the acceleration of an attached accelerometer, firing its change
+/// event.
+pub fn set_acceleration(
+    dev: &mut Accelerometer,
+    accel: [f64; 3],
+    timestamp: f64,
+) -> crate::Result<()> {
+    ReturnCode::result(ffi::acceleration(dev.as_handle(), accel, timestamp))
+}
+
+/// Moves an attached encoder by a number of counts, firing its position
+/// change event.
+pub fn move_encoder(
+    dev: &mut Encoder,
+    change: i32,
+    time_change: f64,
+    index_triggered: bool,
+) -> crate::Result<()> {
+    ReturnCode::result(ffi::position_change(
+        dev.as_handle(),
+        change,
+        time_change,
+        index_triggered,
+    ))
+}
+
+/// Sets the heading and velocity of an attached GPS, firing its heading
+/// change event.
+pub fn set_heading(dev: &mut Gps, heading: f64, velocity: f64) -> crate::Result<()> {
+    ReturnCode::result(ffi::heading(dev.as_handle(), heading, velocity))
+}
+
+/// Feeds a sample to an attached spatial channel, firing its data event.
+pub fn set_spatial_data(dev: &mut Spatial, data: SpatialData) -> crate::Result<()> {
+    ReturnCode::result(ffi::spatial_data(
+        dev.as_handle(),
+        data.acceleration,
+        data.angular_rate,
+        data.magnetic_field,
+        data.timestamp,
+    ))
+}
+
+/// Raises an error event on a channel.
+pub fn raise_error<P: Phidget + ?Sized>(dev: &mut P, code: ErrorEventCode, descr: &str) {
+    let descr = CString::new(descr).unwrap_or_default();
+    ffi::raise_error(dev.as_handle(), code as u32, &descr);
+}
+
+/// Gets the number of simulated channels that currently exist.
+pub fn channel_count() -> usize {
+    ffi::channel_count()
+}
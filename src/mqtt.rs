@@ -0,0 +1,597 @@
+// phidget-rs/src/mqtt.rs
+//
+// This file is part of the 'phidget-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+//! A bridge between Phidget channels and an MQTT broker.
+//!
+//! With the `mqtt` feature, an `MqttBridge` can publish the value changes
+//! of any number of sensors to MQTT topics, and subscribe to command
+//! topics that drive output channels. This lets Phidgets join an existing
+//! MQTT-based automation system:
+//!
+//! ```text
+//! let opts = MqttOptions::new("greenhouse", "broker.local", 1883);
+//! let mut bridge = MqttBridge::new(opts);
+//!
+//! bridge.publish("greenhouse/temperature", &temp_sensor)?;
+//! bridge.control("greenhouse/fan/set", fan_output);
+//! ```
+//!
+//! Sensor values are published as plain text numbers. Boolean values are
+//! published as 0 or 1.
+//!
+//! The payload of a command message is parsed by the output channel. A
+//! digital output takes "on", "off", "true", "false", or a duty cycle
+//! from 0.0 to 1.0. A voltage output takes a voltage. Commands that can't
+//! be parsed or applied are ignored, but the last such error is kept, and
+//! can be retrieved with `take_error()`.
+//!
+//! The connection to the broker is made, and remade if it drops, by a
+//! background thread.
+//!
+//! The channels can also be announced to Home Assistant, using its MQTT
+//! discovery convention, so that they show up there as entities without
+//! any configuration:
+//!
+//! ```text
+//! bridge.set_discovery(Discovery::new("greenhouse", "Greenhouse"));
+//! bridge.publish_entity("Temperature", "greenhouse/temperature", &mut temp_sensor)?;
+//! bridge.control_entity("Fan", "greenhouse/fan/set", fan_output)?;
+//! ```
+//!
+//! A sensor is announced as a `sensor` entity, with the device class and
+//! unit of its channel class where Home Assistant has one, and a digital
+//! input as a `binary_sensor`. An output is announced as a `switch`. The
+//! announcements are retained by the broker, and published again
+//! whenever the bridge reconnects, or Home Assistant comes back online.
+//!
+
+use crate::{ChannelClass, Error, HandlerGuard, Result, Sensor};
+use rumqttc::{Client, Event, Packet};
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+pub use crate::command::Commandable;
+pub use rumqttc::{MqttOptions, QoS};
+
+/// The capacity of the queue of outgoing messages.
+pub const QUEUE_CAPACITY: usize = 64;
+
+/// The time to wait before reconnecting to the broker after an error.
+pub const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+/// The settings for announcing the channels to Home Assistant.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Discovery {
+    /// The prefix of the discovery topics, which is "homeassistant" unless
+    /// it was changed in Home Assistant
+    pub prefix: String,
+    /// The ID of the node, which groups the entities of this bridge. It
+    /// can only have ASCII letters, digits, '_', and '-'
+    pub node_id: String,
+    /// The name of the device that the entities belong to
+    pub device_name: String,
+}
+
+impl Discovery {
+    /// Creates discovery settings with the default prefix.
+    pub fn new(node_id: &str, device_name: &str) -> Self {
+        Self {
+            prefix: "homeassistant".into(),
+            node_id: node_id.into(),
+            device_name: device_name.into(),
+        }
+    }
+
+    // Gets the topic of Home Assistant's status messages.
+    fn status_topic(&self) -> String {
+        format!("{}/status", self.prefix)
+    }
+
+    // Gets the discovery topic for an entity.
+    fn config_topic(&self, component: &str, object_id: &str) -> String {
+        format!(
+            "{}/{}/{}/{}/config",
+            self.prefix, component, self.node_id, object_id
+        )
+    }
+
+    // Makes the discovery payload for an entity, from its own fields,
+    // which are already JSON.
+    fn config(&self, name: &str, object_id: &str, fields: &[(&str, String)]) -> String {
+        let mut out = format!(
+            "{{\"name\":{},\"unique_id\":{}",
+            json_str(name),
+            json_str(&format!("{}_{}", self.node_id, object_id))
+        );
+        for (key, val) in fields {
+            out.push_str(&format!(",\"{}\":{}", key, val));
+        }
+        out.push_str(&format!(
+            ",\"device\":{{\"identifiers\":[{}],\"name\":{},\"manufacturer\":\"Phidgets\"}}}}",
+            json_str(&self.node_id),
+            json_str(&self.device_name)
+        ));
+        out
+    }
+}
+
+impl Default for Discovery {
+    fn default() -> Self {
+        Self::new("phidget", "Phidgets")
+    }
+}
+
+// Gets the Home Assistant component, device class, and unit for the
+// value of a channel class.
+fn entity_kind(cls: ChannelClass) -> (&'static str, Option<&'static str>, Option<&'static str>) {
+    use ChannelClass::*;
+    match cls {
+        DigitalInput => ("binary_sensor", None, None),
+        TemperatureSensor => ("sensor", Some("temperature"), Some("°C")),
+        HumiditySensor => ("sensor", Some("humidity"), Some("%")),
+        VoltageInput => ("sensor", Some("voltage"), Some("V")),
+        CurrentInput => ("sensor", Some("current"), Some("A")),
+        PressureSensor => ("sensor", Some("pressure"), Some("kPa")),
+        LightSensor => ("sensor", Some("illuminance"), Some("lx")),
+        SoundSensor => ("sensor", Some("sound_pressure"), Some("dB")),
+        PhSensor => ("sensor", Some("ph"), None),
+        FrequencyCounter => ("sensor", Some("frequency"), Some("Hz")),
+        _ => ("sensor", None, None),
+    }
+}
+
+// Makes an object ID for an entity from its name.
+fn object_id(name: &str) -> String {
+    let id: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            }
+            else {
+                '_'
+            }
+        })
+        .collect();
+    id.trim_matches('_').to_string()
+}
+
+// Quotes a string for JSON.
+fn json_str(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+// An output channel and the topic that drives it
+struct Output {
+    topic: String,
+    out: Box<dyn Commandable>,
+}
+
+// The state shared with the connection thread
+#[derive(Default)]
+struct Shared {
+    // The output channels
+    outputs: Mutex<Vec<Output>>,
+    // The last error applying a command
+    last_error: Mutex<Option<Error>>,
+    // Whether the client is connected to the broker
+    connected: AtomicBool,
+    // Set when the bridge is dropped, to stop the thread
+    stop: AtomicBool,
+    // The settings for Home Assistant discovery
+    discovery: Mutex<Discovery>,
+    // The discovery topics and payloads of the entities
+    entities: Mutex<Vec<(String, String)>>,
+}
+
+impl Shared {
+    // Applies a command message to the outputs for the topic.
+    fn dispatch(&self, topic: &str, payload: &[u8]) {
+        let payload = String::from_utf8_lossy(payload);
+        let mut outputs = self.outputs.lock().unwrap_or_else(|err| err.into_inner());
+        for output in outputs.iter_mut().filter(|o| o.topic == topic) {
+            if let Err(err) = output.out.command(&payload) {
+                *self
+                    .last_error
+                    .lock()
+                    .unwrap_or_else(|err| err.into_inner()) = Some(err);
+            }
+        }
+    }
+
+    // Gets the topics of all the outputs, and the Home Assistant status
+    // if there are any entities.
+    fn topics(&self) -> Vec<String> {
+        let mut topics: Vec<_> = self
+            .outputs
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .iter()
+            .map(|o| o.topic.clone())
+            .collect();
+        if !self.entities().is_empty() {
+            topics.push(self.status_topic());
+        }
+        topics
+    }
+
+    // Gets the discovery topics and payloads of the entities.
+    fn entities(&self) -> Vec<(String, String)> {
+        self.entities
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .clone()
+    }
+
+    // Gets the topic of Home Assistant's status messages.
+    fn status_topic(&self) -> String {
+        self.discovery
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .status_topic()
+    }
+
+    // Publishes the discovery messages for all the entities.
+    fn announce(&self, client: &Client) {
+        for (topic, payload) in self.entities() {
+            let _ = client.try_publish(topic, QoS::AtLeastOnce, true, payload);
+        }
+    }
+}
+
+/// A bridge that publishes sensor values to, and takes output commands
+/// from, an MQTT broker.
+///
+/// The sensor handlers are removed, the output channels are dropped, and
+/// the connection is closed when the bridge is dropped.
+pub struct MqttBridge {
+    // The client for sending requests to the broker
+    client: Client,
+    // The state shared with the connection thread
+    shared: Arc<Shared>,
+    // The quality of service for published values
+    qos: QoS,
+    // Whether published values are retained by the broker
+    retain: bool,
+    // The handlers feeding the published values
+    guards: Vec<HandlerGuard>,
+}
+
+impl MqttBridge {
+    /// Creates a new bridge, and starts connecting to the broker.
+    pub fn new(opts: MqttOptions) -> Self {
+        let (client, mut conn) = Client::new(opts, QUEUE_CAPACITY);
+        let shared = Arc::new(Shared::default());
+
+        let thr_client = client.clone();
+        let thr_shared = Arc::clone(&shared);
+        thread::spawn(move || {
+            for ev in conn.iter() {
+                if thr_shared.stop.load(Ordering::SeqCst) {
+                    break;
+                }
+                match ev {
+                    Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                        thr_shared.connected.store(true, Ordering::SeqCst);
+                        // Subscriptions don't survive a reconnect
+                        for topic in thr_shared.topics() {
+                            let _ = thr_client.try_subscribe(topic, QoS::AtLeastOnce);
+                        }
+                        thr_shared.announce(&thr_client);
+                    }
+                    Ok(Event::Incoming(Packet::Publish(msg))) => {
+                        // Home Assistant forgets the entities when it restarts
+                        if msg.topic == thr_shared.status_topic() {
+                            if &msg.payload[..] == b"online" {
+                                thr_shared.announce(&thr_client);
+                            }
+                        }
+                        else {
+                            thr_shared.dispatch(&msg.topic, &msg.payload);
+                        }
+                    }
+                    Ok(_) => (),
+                    Err(_) => {
+                        thr_shared.connected.store(false, Ordering::SeqCst);
+                        thread::sleep(RECONNECT_DELAY);
+                    }
+                }
+            }
+        });
+
+        Self {
+            client,
+            shared,
+            qos: QoS::AtMostOnce,
+            retain: false,
+            guards: Vec::new(),
+        }
+    }
+
+    /// Sets the quality of service for published sensor values.
+    /// The default is "at most once".
+    pub fn set_qos(&mut self, qos: QoS) -> &mut Self {
+        self.qos = qos;
+        self
+    }
+
+    /// Sets whether the broker should retain the last published value of
+    /// each sensor, for clients that subscribe later.
+    /// The default is not to retain them.
+    pub fn set_retain(&mut self, retain: bool) -> &mut Self {
+        self.retain = retain;
+        self
+    }
+
+    /// Determines if the bridge is currently connected to the broker.
+    pub fn is_connected(&self) -> bool {
+        self.shared.connected.load(Ordering::SeqCst)
+    }
+
+    /// Publishes all the value changes from the sensor to the topic.
+    ///
+    /// This sets the sensor's change handler, replacing any handler that
+    /// was previously set. Values that change while the bridge is not
+    /// connected are dropped once the outgoing queue is full.
+    pub fn publish<S>(&mut self, topic: &str, sensor: &S) -> Result<()>
+    where
+        S: Sensor,
+        S::Value: Into<f64>,
+    {
+        let client = self.client.clone();
+        let topic = topic.to_string();
+        let (qos, retain) = (self.qos, self.retain);
+
+        let guard = sensor.on_change(move |_, val| {
+            let payload = val.into().to_string();
+            let _ = client.try_publish(topic.as_str(), qos, retain, payload);
+        })?;
+        self.guards.push(guard);
+        Ok(())
+    }
+
+    /// Drives the output channel with the messages sent to the topic.
+    ///
+    /// The bridge takes ownership of the channel, which should already be
+    /// open. Several outputs can share the same topic.
+    pub fn control<O>(&mut self, topic: &str, out: O)
+    where
+        O: Commandable + 'static,
+    {
+        self.shared
+            .outputs
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .push(Output {
+                topic: topic.to_string(),
+                out: Box::new(out),
+            });
+
+        // If not yet connected, it's subscribed upon connection
+        if self.is_connected() {
+            let _ = self.client.try_subscribe(topic, QoS::AtLeastOnce);
+        }
+    }
+
+    /// Sets the settings for announcing entities to Home Assistant.
+    ///
+    /// This should be set before any entities are added, since they're
+    /// announced with the settings at the time.
+    pub fn set_discovery(&mut self, discovery: Discovery) -> &mut Self {
+        *self
+            .shared
+            .discovery
+            .lock()
+            .unwrap_or_else(|err| err.into_inner()) = discovery;
+        self
+    }
+
+    // Adds an entity, and announces it if the bridge is connected.
+    fn add_entity(&mut self, topic: String, payload: String) {
+        let first = {
+            let mut entities = self
+                .shared
+                .entities
+                .lock()
+                .unwrap_or_else(|err| err.into_inner());
+            entities.push((topic.clone(), payload.clone()));
+            entities.len() == 1
+        };
+
+        if self.is_connected() {
+            if first {
+                let _ = self
+                    .client
+                    .try_subscribe(self.shared.status_topic(), QoS::AtLeastOnce);
+            }
+            let _ = self
+                .client
+                .try_publish(topic, QoS::AtLeastOnce, true, payload);
+        }
+    }
+
+    /// Publishes all the value changes from the sensor to the topic, like
+    /// `publish()`, and announces the sensor to Home Assistant as an
+    /// entity with the name.
+    ///
+    /// The name must have at least one ASCII letter or digit, since the
+    /// entity's ID is made from it.
+    pub fn publish_entity<S>(&mut self, name: &str, topic: &str, sensor: &mut S) -> Result<()>
+    where
+        S: Sensor,
+        S::Value: Into<f64>,
+    {
+        let id = object_id(name);
+        if id.is_empty() {
+            return Err(Error::InvalidArg);
+        }
+        let (component, device_class, unit) = entity_kind(sensor.channel_class()?);
+        self.publish(topic, sensor)?;
+
+        let disc = self
+            .shared
+            .discovery
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .clone();
+        let mut fields = vec![("state_topic", json_str(topic))];
+        if component == "binary_sensor" {
+            fields.push(("payload_on", json_str("1")));
+            fields.push(("payload_off", json_str("0")));
+        }
+        else {
+            fields.push(("state_class", json_str("measurement")));
+        }
+        if let Some(dc) = device_class {
+            fields.push(("device_class", json_str(dc)));
+        }
+        if let Some(unit) = unit {
+            fields.push(("unit_of_measurement", json_str(unit)));
+        }
+
+        let payload = disc.config(name, &id, &fields);
+        self.add_entity(disc.config_topic(component, &id), payload);
+        Ok(())
+    }
+
+    /// Drives the output channel with the messages sent to the topic, like
+    /// `control()`, and announces it to Home Assistant as a switch with
+    /// the name.
+    ///
+    /// The switch sends "on" and "off". Home Assistant assumes that the
+    /// commands are applied, since the output's state isn't published.
+    pub fn control_entity<O>(&mut self, name: &str, topic: &str, out: O) -> Result<()>
+    where
+        O: Commandable + 'static,
+    {
+        let disc = self
+            .shared
+            .discovery
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .clone();
+        let id = object_id(name);
+        if id.is_empty() {
+            return Err(Error::InvalidArg);
+        }
+        self.control(topic, out);
+
+        let fields = [
+            ("command_topic", json_str(topic)),
+            ("payload_on", json_str("on")),
+            ("payload_off", json_str("off")),
+            ("optimistic", "true".to_string()),
+        ];
+        let payload = disc.config(name, &id, &fields);
+        self.add_entity(disc.config_topic("switch", &id), payload);
+        Ok(())
+    }
+
+    /// Takes the last error that occurred applying a command to one of
+    /// the output channels.
+    pub fn take_error(&self) -> Option<Error> {
+        self.shared
+            .last_error
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .take()
+    }
+}
+
+impl Drop for MqttBridge {
+    fn drop(&mut self) {
+        self.guards.clear();
+        self.shared.stop.store(true, Ordering::SeqCst);
+        let _ = self.client.try_disconnect();
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // An output that records its commands
+    struct Recorder(Arc<Mutex<Vec<f64>>>);
+
+    impl Commandable for Recorder {
+        fn command(&mut self, payload: &str) -> Result<()> {
+            let v = payload.parse().map_err(|_| Error::InvalidArg)?;
+            self.0.lock().unwrap().push(v);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_dispatch() {
+        let shared = Shared::default();
+        let vals = Arc::new(Mutex::new(Vec::new()));
+        shared.outputs.lock().unwrap().push(Output {
+            topic: "fan/set".into(),
+            out: Box::new(Recorder(Arc::clone(&vals))),
+        });
+
+        shared.dispatch("fan/set", b"0.5");
+        shared.dispatch("pump/set", b"1.0");
+        assert_eq!(*vals.lock().unwrap(), vec![0.5]);
+        assert!(shared.last_error.lock().unwrap().is_none());
+
+        shared.dispatch("fan/set", b"fast");
+        assert_eq!(
+            shared.last_error.lock().unwrap().take(),
+            Some(Error::InvalidArg)
+        );
+        assert_eq!(shared.topics(), vec!["fan/set".to_string()]);
+    }
+
+    #[test]
+    fn test_discovery() {
+        let disc = Discovery::new("greenhouse", "Greenhouse");
+        let id = object_id("North Temperature!");
+        assert_eq!(id, "north_temperature");
+        assert_eq!(
+            disc.config_topic("sensor", &id),
+            "homeassistant/sensor/greenhouse/north_temperature/config"
+        );
+
+        let (component, dc, unit) = entity_kind(ChannelClass::TemperatureSensor);
+        assert_eq!(component, "sensor");
+        let fields = [
+            ("state_topic", json_str("greenhouse/temperature")),
+            ("device_class", json_str(dc.unwrap())),
+            ("unit_of_measurement", json_str(unit.unwrap())),
+        ];
+        assert_eq!(
+            disc.config("North Temperature", &id, &fields),
+            "{\"name\":\"North Temperature\",\"unique_id\":\"greenhouse_north_temperature\",\
+             \"state_topic\":\"greenhouse/temperature\",\"device_class\":\"temperature\",\
+             \"unit_of_measurement\":\"°C\",\"device\":{\"identifiers\":[\"greenhouse\"],\
+             \"name\":\"Greenhouse\",\"manufacturer\":\"Phidgets\"}}"
+        );
+        assert_eq!(entity_kind(ChannelClass::DigitalInput).0, "binary_sensor");
+    }
+}
@@ -12,12 +12,21 @@
 //! Phidget network API
 //!
 
-use crate::{Error, Result, ReturnCode};
-use phidget_sys as ffi;
-use std::{ffi::CString, os::raw::c_int};
+use crate::{Error, ErrorEventCode, Result, ReturnCode};
+use phidget_sys::{self as ffi, PhidgetHandle};
+use std::{
+    collections::BTreeMap,
+    ffi::CString,
+    os::raw::c_int,
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Mutex, MutexGuard,
+    },
+};
 
 /// Phidget server types
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u32)]
 #[allow(missing_docs)]
 pub enum ServerType {
@@ -45,7 +54,7 @@ impl TryFrom<u32> for ServerType {
             ffi::PhidgetServerType_PHIDGETSERVER_WWW => Ok(Www),   // 5
             ffi::PhidgetServerType_PHIDGETSERVER_WWWREMOTE => Ok(WwwRemote), // 6
             ffi::PhidgetServerType_PHIDGETSERVER_SBC => Ok(Sbc),   // 7
-            _ => Err(ReturnCode::InvalidArg),
+            _ => Err(Error::InvalidArg),
         }
     }
 }
@@ -74,6 +83,20 @@ pub fn add_server(
     })
 }
 
+/// Registers the Phidget server at a host and port, so that it can be
+/// reached without discovery, returning the name given to the server.
+///
+/// The server is named for its address, as `host:port`, and is added
+/// without a password. Registering the same address again is not an
+/// error.
+pub fn add_server_host(host: &str, port: i32) -> Result<String> {
+    let name = format!("{}:{}", host, port);
+    match add_server(&name, host, port, "", 0) {
+        Err(err) if err.return_code() != ReturnCode::Duplicate => Err(err),
+        _ => Ok(name),
+    }
+}
+
 /// Removes the registration for a server.
 pub fn remove_server(server_name: &str) -> Result<()> {
     let server_name = CString::new(server_name).unwrap();
@@ -125,6 +148,150 @@ pub fn disable_server_discovery(server_type: ServerType) -> Result<()> {
     ReturnCode::result(unsafe { ffi::PhidgetNet_disableServerDiscovery(server_type as u32) })
 }
 
+/////////////////////////////////////////////////////////////////////////////
+// Server connection status
+
+/// The state of the connection to a remote server.
+///
+/// The library doesn't report the connections themselves, so this is
+/// tracked from the channels opened through the server. The server is
+/// considered connected while any of those channels is attached.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ServerStatus {
+    /// Whether any channels are attached through the server
+    pub connected: bool,
+    /// The number of channels attached through the server
+    pub channels: usize,
+    /// The last error reported by a channel on the server, with its
+    /// description
+    pub last_error: Option<(ErrorEventCode, String)>,
+}
+
+/// A change in the connection to a remote server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ServerEvent {
+    /// The first channel attached through the named server
+    Connected(String),
+    /// The last channel attached through the named server detached
+    Disconnected(String),
+    /// A channel on the named server reported an error, with a
+    /// description of it
+    Error(String, ErrorEventCode, String),
+}
+
+impl ServerEvent {
+    /// Gets the name of the server for the event.
+    pub fn server(&self) -> &str {
+        use ServerEvent::*;
+        match self {
+            Connected(name) | Disconnected(name) | Error(name, ..) => name,
+        }
+    }
+}
+
+// The status of the servers that have had channels, by name
+static STATUS: Mutex<BTreeMap<String, ServerStatus>> = Mutex::new(BTreeMap::new());
+
+// The subscribers to the server events
+static SUBSCRIBERS: Mutex<Vec<Sender<ServerEvent>>> = Mutex::new(Vec::new());
+
+fn status() -> MutexGuard<'static, BTreeMap<String, ServerStatus>> {
+    STATUS.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+/// Gets the connection status of the named server, if any channels have
+/// been attached through it.
+pub fn server_status(server_name: &str) -> Option<ServerStatus> {
+    status().get(server_name).cloned()
+}
+
+/// Gets the connection status of all the servers that have had channels
+/// attached through them, by name.
+pub fn server_statuses() -> Vec<(String, ServerStatus)> {
+    status()
+        .iter()
+        .map(|(name, st)| (name.clone(), st.clone()))
+        .collect()
+}
+
+/// Subscribes to the changes in the server connections.
+///
+/// The receiver gets every change after the call, for all of the servers.
+/// Dropping the receiver unsubscribes it.
+pub fn subscribe_server_events() -> Receiver<ServerEvent> {
+    let (tx, rx) = mpsc::channel();
+    SUBSCRIBERS
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .push(tx);
+    rx
+}
+
+// Sends an event to the subscribers.
+fn publish(ev: ServerEvent) {
+    let mut subs = SUBSCRIBERS.lock().unwrap_or_else(|err| err.into_inner());
+    subs.retain(|tx| tx.send(ev.clone()).is_ok());
+}
+
+// Gets the name of the server for a channel, if it's remote.
+fn remote_server(phid: PhidgetHandle) -> Option<String> {
+    let mut rem: c_int = 0;
+    let rc = unsafe { ffi::Phidget_getIsRemote(phid, &mut rem) };
+    if rc != 0 || rem == 0 {
+        return None;
+    }
+    crate::get_ffi_string(|s| unsafe { ffi::Phidget_getServerName(phid, s) })
+        .ok()
+        .filter(|name| !name.is_empty())
+}
+
+/// Updates the server status for a channel that attached.
+pub(crate) fn attached(phid: PhidgetHandle) {
+    let Some(name) = remote_server(phid)
+    else {
+        return;
+    };
+    let mut status = status();
+    let st = status.entry(name.clone()).or_default();
+    st.channels += 1;
+    if !st.connected {
+        st.connected = true;
+        drop(status);
+        publish(ServerEvent::Connected(name));
+    }
+}
+
+/// Updates the server status for a channel that detached.
+pub(crate) fn detached(phid: PhidgetHandle) {
+    let Some(name) = remote_server(phid)
+    else {
+        return;
+    };
+    let mut status = status();
+    let Some(st) = status.get_mut(&name)
+    else {
+        return;
+    };
+    st.channels = st.channels.saturating_sub(1);
+    if st.channels == 0 && st.connected {
+        st.connected = false;
+        drop(status);
+        publish(ServerEvent::Disconnected(name));
+    }
+}
+
+/// Records an error reported by a channel on a server.
+pub(crate) fn error(phid: PhidgetHandle, code: ErrorEventCode, descr: &str) {
+    let Some(name) = remote_server(phid)
+    else {
+        return;
+    };
+    status().entry(name.clone()).or_default().last_error = Some((code, descr.into()));
+    publish(ServerEvent::Error(name, code, descr.into()));
+}
+
 /*
 pub type PhidgetNet_OnServerAddedCallback = ::std::option::Option<
     unsafe extern "C" fn(
@@ -177,3 +344,59 @@ extern "C" {
     pub fn PhidgetNet_stopServer(server: *mut PhidgetServerHandle) -> PhidgetReturnCode;
 }
 */
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::*;
+    use crate::{
+        mock::{detach, raise_error},
+        Phidget, TemperatureSensor,
+    };
+
+    #[test]
+    fn test_mock_server_hostname() {
+        let mut sensor = crate::builder::<TemperatureSensor>()
+            .server("10.1.2.3", 5661)
+            .build()
+            .unwrap();
+        assert_eq!(sensor.server_name().unwrap(), "10.1.2.3:5661");
+        assert!(sensor.is_remote().unwrap());
+
+        // The server is only registered once
+        let mut sensor2 = TemperatureSensor::new();
+        sensor2.set_server_hostname("10.1.2.3", 5661).unwrap();
+        let res = add_server("10.1.2.3:5661", "10.1.2.3", 5661, "", 0);
+        assert_eq!(res.unwrap_err().return_code(), ReturnCode::Duplicate);
+        remove_server("10.1.2.3:5661").unwrap();
+    }
+
+    #[test]
+    fn test_mock_server_status() {
+        let events = subscribe_server_events();
+        let mut sensor = TemperatureSensor::new();
+        sensor.set_server_hostname("10.4.9.1", 5661).unwrap();
+        sensor.open().unwrap();
+
+        let name = "10.4.9.1:5661".to_string();
+        let st = server_status(&name).unwrap();
+        assert!(st.connected);
+        assert_eq!(st.channels, 1);
+
+        detach(&mut sensor);
+        raise_error(&mut sensor, ErrorEventCode::Network, "link down");
+        assert!(!server_status(&name).unwrap().connected);
+
+        let evs: Vec<_> = events.try_iter().filter(|ev| ev.server() == name).collect();
+        assert_eq!(
+            evs,
+            vec![
+                ServerEvent::Connected(name.clone()),
+                ServerEvent::Disconnected(name.clone()),
+                ServerEvent::Error(name.clone(), ErrorEventCode::Network, "link down".into()),
+            ]
+        );
+        remove_server(&name).unwrap();
+    }
+}
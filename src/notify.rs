@@ -0,0 +1,283 @@
+// phidget-rs/src/notify.rs
+//
+// This file is part of the 'phidget-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+//! Internal notification of channel attach and detach events.
+//!
+//! The crate installs its own attach and detach handlers on every channel
+//! that it creates. Along with calling any user handlers, they record the
+//! attach state of the channel, and wake up any threads or tasks that are
+//! waiting for that channel to attach or detach.
+//!
+//! Threads block on a condition variable. Async tasks are woken directly
+//! from the callback, which only relies on wakers, so it works with any
+//! async executor (Tokio, async-std, smol, etc).
+//!
+
+use phidget_sys::PhidgetHandle;
+use std::{
+    sync::{Condvar, Mutex},
+    time::{Duration, Instant},
+};
+
+#[cfg(feature = "async")]
+use futures_channel::oneshot;
+#[cfg(feature = "async")]
+use futures_timer::Delay;
+#[cfg(feature = "async")]
+use std::{
+    future::{self, Future},
+    pin::Pin,
+    task::Poll,
+};
+
+// The last known attach state of the channels, keyed by channel handle.
+static ATTACH_STATE: Mutex<Vec<(usize, bool)>> = Mutex::new(Vec::new());
+
+// Signaled whenever any channel attaches or detaches.
+static ATTACH_CHANGED: Condvar = Condvar::new();
+
+// The tasks waiting for a channel to reach an attach state, keyed by the
+// channel handle, along with the state they're waiting for.
+#[cfg(feature = "async")]
+static ATTACH_WAITERS: Mutex<Vec<(usize, bool, oneshot::Sender<()>)>> = Mutex::new(Vec::new());
+
+/// Clears any recorded state for a channel.
+///
+/// This is called when a channel is created, since the handle might be
+/// re-using the memory of a channel that was deleted, and when a channel
+/// is deleted, so that the state doesn't grow without bound.
+pub(crate) fn reset(phid: PhidgetHandle) {
+    crate::cache::clear(phid);
+    let key = phid as usize;
+    if let Ok(mut states) = ATTACH_STATE.lock() {
+        states.retain(|(k, _)| *k != key);
+    }
+}
+
+/// Records that the channel attached, and wakes anyone waiting for it.
+pub(crate) fn attached(phid: PhidgetHandle) {
+    set_attach_state(phid, true);
+}
+
+/// Records that the channel detached, and wakes anyone waiting for it.
+pub(crate) fn detached(phid: PhidgetHandle) {
+    crate::cache::clear(phid);
+    set_attach_state(phid, false);
+}
+
+/// Determines if an attach state is recorded for the channel.
+#[cfg(all(test, feature = "mock"))]
+pub(crate) fn has_attach_state(phid: PhidgetHandle) -> bool {
+    let key = phid as usize;
+    ATTACH_STATE.lock().unwrap().iter().any(|(k, _)| *k == key)
+}
+
+// Updates the attach state of a channel and notifies the waiters.
+fn set_attach_state(phid: PhidgetHandle, on: bool) {
+    let key = phid as usize;
+    if let Ok(mut states) = ATTACH_STATE.lock() {
+        match states.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, state)) => *state = on,
+            None => states.push((key, on)),
+        }
+        ATTACH_CHANGED.notify_all();
+    }
+
+    #[cfg(feature = "async")]
+    if let Ok(mut waiters) = ATTACH_WAITERS.lock() {
+        let (ready, waiting) = waiters
+            .drain(..)
+            .partition(|(k, state, _)| *k == key && *state == on);
+        *waiters = waiting;
+        for (_, _, tx) in ready {
+            let _ = tx.send(());
+        }
+    }
+}
+
+// Gets the attach state from the events that the channel has seen,
+// defaulting to the current state read from the library.
+fn attach_state(states: &[(usize, bool)], key: usize, current: bool) -> bool {
+    states
+        .iter()
+        .find(|(k, _)| *k == key)
+        .map(|(_, state)| *state)
+        .unwrap_or(current)
+}
+
+/// Blocks the calling thread up to the specified time, waiting for the
+/// channel to reach the requested attach state.
+///
+/// The `current` state is what the library reported just prior to the
+/// call, and is used if no events have been seen yet for the channel.
+/// Returns true if the channel reached the state, or false on a timeout.
+pub(crate) fn wait_attach_state(
+    phid: PhidgetHandle,
+    on: bool,
+    current: bool,
+    to: Duration,
+) -> bool {
+    let key = phid as usize;
+    let deadline = Instant::now() + to;
+
+    let Ok(mut states) = ATTACH_STATE.lock()
+    else {
+        return current == on;
+    };
+
+    loop {
+        if attach_state(&states, key, current) == on {
+            return true;
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            return false;
+        }
+        states = match ATTACH_CHANGED.wait_timeout(states, deadline - now) {
+            Ok((states, _)) => states,
+            Err(_) => return false,
+        };
+    }
+}
+
+/// A registration for a task to be notified when a channel reaches an
+/// attach state.
+///
+/// The waiting task is woken directly from the channel's attach or detach
+/// callback, and timeouts are driven by a runtime-agnostic timer.
+#[cfg(feature = "async")]
+pub(crate) struct AttachWaiter {
+    rx: oneshot::Receiver<()>,
+    reached: bool,
+}
+
+#[cfg(feature = "async")]
+impl AttachWaiter {
+    /// Registers a waiter for the channel with the specified handle to
+    /// reach the attach state `on`.
+    ///
+    /// The `current` state is what the library reported just prior to the
+    /// call, and is used if no events have been seen yet for the channel.
+    /// To wait for a channel to attach after opening it, the waiter should
+    /// be created before the channel is opened so that the attach event
+    /// can't be missed.
+    pub fn new(phid: PhidgetHandle, on: bool, current: bool) -> Self {
+        let key = phid as usize;
+        let (tx, rx) = oneshot::channel();
+
+        // Hold the state lock while registering so an event can't slip in
+        // between reading the state and adding the waiter.
+        let states = ATTACH_STATE.lock();
+        let reached = match &states {
+            Ok(states) => attach_state(states, key, current) == on,
+            Err(_) => current == on,
+        };
+        if let Ok(mut waiters) = ATTACH_WAITERS.lock() {
+            // Clear out any waiters that were dropped without a notification
+            waiters.retain(|(_, _, tx)| !tx.is_canceled());
+            if !reached {
+                waiters.push((key, on, tx));
+            }
+        }
+        Self { rx, reached }
+    }
+
+    /// Waits up to the specified time for the channel to reach the attach
+    /// state. Returns true if it did, or false on a timeout.
+    pub async fn wait_within(mut self, to: Duration) -> bool {
+        if self.reached {
+            return true;
+        }
+        let mut delay = Delay::new(to);
+        future::poll_fn(|cx| {
+            if Pin::new(&mut self.rx).poll(cx).is_ready() {
+                Poll::Ready(true)
+            }
+            else if Pin::new(&mut delay).poll(cx).is_ready() {
+                Poll::Ready(false)
+            }
+            else {
+                Poll::Pending
+            }
+        })
+        .await
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn test_wait_attach_state() {
+        let phid = 0x1000 as PhidgetHandle;
+        reset(phid);
+        assert!(!wait_attach_state(
+            phid,
+            true,
+            false,
+            Duration::from_millis(10)
+        ));
+
+        let thr = thread::spawn(move || {
+            let phid = 0x1000 as PhidgetHandle;
+            wait_attach_state(phid, true, false, Duration::from_secs(5))
+        });
+        thread::sleep(Duration::from_millis(10));
+        attached(phid);
+        assert!(thr.join().unwrap());
+
+        detached(phid);
+        assert!(wait_attach_state(phid, false, true, Duration::ZERO));
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn test_attach_waiter() {
+        use futures::executor::block_on;
+
+        let phid = 0x2000 as PhidgetHandle;
+        reset(phid);
+        let waiter = AttachWaiter::new(phid, true, false);
+        attached(phid);
+        assert!(block_on(waiter.wait_within(Duration::from_secs(5))));
+
+        let waiter = AttachWaiter::new(phid, false, false);
+        detached(0x3000 as PhidgetHandle);
+        assert!(!block_on(waiter.wait_within(Duration::from_millis(10))));
+    }
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod mock_tests {
+    use super::*;
+    use crate::{
+        mock::{attach, MOCK_SERIAL_NUMBER},
+        Phidget, TemperatureSensor,
+    };
+
+    #[test]
+    fn test_mock_state_released_on_drop() {
+        let mut sensor = TemperatureSensor::new();
+        sensor.open().unwrap();
+        attach(&mut sensor);
+        assert_eq!(sensor.serial_number(), Ok(MOCK_SERIAL_NUMBER));
+
+        let phid = sensor.as_handle();
+        assert!(crate::cache::is_cached(phid));
+        assert!(has_attach_state(phid));
+
+        drop(sensor);
+        assert!(!crate::cache::is_cached(phid));
+        assert!(!has_attach_state(phid));
+    }
+}
@@ -6,71 +6,342 @@
 // to those terms.
 //
 
-use crate::{ChannelClass, DeviceClass, Result, ReturnCode};
+use crate::{
+    ChannelClass, ChannelSubclass, DeviceClass, DeviceId, Error, ErrorEventCode, HandlerGuard,
+    HandlerScope, Result, ReturnCode,
+};
 use phidget_sys::{self as ffi, PhidgetHandle};
 use std::{
-    os::raw::{c_int, c_void},
+    ffi::{CStr, CString},
+    fmt, mem,
+    ops::RangeInclusive,
+    os::raw::{c_char, c_int, c_uint, c_void},
+    ptr,
     time::Duration,
 };
 
+#[cfg(feature = "async")]
+use std::{future::Future, pin::Pin};
+
 /// The signature for device attach callbacks
-pub type AttachCallback = dyn Fn(&GenericPhidget) + Send + 'static;
+pub type AttachCallback = dyn FnMut(&GenericPhidget) + Send + 'static;
 
 /// The signature for device detach callbacks
-pub type DetachCallback = dyn Fn(&GenericPhidget) + Send + 'static;
+pub type DetachCallback = dyn FnMut(&GenericPhidget) + Send + 'static;
+
+/// The signature for device error callbacks.
+/// These receive the error code and a description of the error.
+pub type ErrorCallback = dyn FnMut(&GenericPhidget, ErrorEventCode, &str) + Send + 'static;
 
 // Low-level, unsafe callback for device attach events
 unsafe extern "C" fn on_attach(phid: PhidgetHandle, ctx: *mut c_void) {
-    if !ctx.is_null() {
+    crate::notify::attached(phid);
+    crate::events::attached(phid);
+    crate::net::attached(phid);
+    #[cfg(feature = "metrics")]
+    crate::metrics::attached(phid);
+
+    crate::handler::deliver(phid, ctx, move |phid, ctx| {
         let cb: &mut Box<AttachCallback> = &mut *(ctx as *mut _);
         let ph = GenericPhidget::from(phid);
         cb(&ph);
-    }
+    });
 }
 
 // Low-level, unsafe callback for device detach events
 unsafe extern "C" fn on_detach(phid: PhidgetHandle, ctx: *mut c_void) {
-    if !ctx.is_null() {
+    crate::events::detached(phid);
+    crate::notify::detached(phid);
+    crate::net::detached(phid);
+    #[cfg(feature = "metrics")]
+    crate::metrics::detached(phid);
+
+    crate::handler::deliver(phid, ctx, move |phid, ctx| {
         let cb: &mut Box<DetachCallback> = &mut *(ctx as *mut _);
         let ph = GenericPhidget::from(phid);
         cb(&ph);
-    }
+    });
+}
+
+// Low-level, unsafe callback for device error events
+unsafe extern "C" fn on_error(
+    phid: PhidgetHandle,
+    ctx: *mut c_void,
+    code: c_uint,
+    descr: *const c_char,
+) {
+    let code = ErrorEventCode::from(code);
+    let descr = match descr.is_null() {
+        true => String::new(),
+        false => CStr::from_ptr(descr).to_string_lossy().into_owned(),
+    };
+    error_event(phid, ctx, code, descr);
+}
+
+/// Reports an error on the channel from inside the crate, as if the
+/// device had sent an error event.
+///
+/// This goes to the channel's error handler, if one is set, and to
+/// everything else in the crate that watches the error events.
+pub(crate) fn raise_error(phid: PhidgetHandle, code: ErrorEventCode, descr: String) {
+    let ctx = crate::handler::context(phid, "error");
+    unsafe { error_event(phid, ctx, code, descr) }
+}
+
+// Handles an error event for the channel, with the context of the error
+// handler.
+unsafe fn error_event(phid: PhidgetHandle, ctx: *mut c_void, code: ErrorEventCode, descr: String) {
+    crate::events::error(phid, code, &descr);
+    crate::net::error(phid, code, &descr);
+    #[cfg(feature = "metrics")]
+    crate::metrics::error(phid, code);
+
+    crate::handler::deliver(phid, ctx, move |phid, ctx| {
+        let cb: &mut Box<ErrorCallback> = &mut *(ctx as *mut _);
+        let ph = GenericPhidget::from(phid);
+        cb(&ph, code, &descr);
+    });
 }
 
 // ----- Callbacks -----
 
+/// Installs the crate's internal event handlers on a newly created channel.
+///
+/// This is called by the device constructors. User handlers that are set
+/// later go through the same low-level callbacks, so the crate continues
+/// to see the events after they are registered.
+pub(crate) fn install_handlers(phid: PhidgetHandle) {
+    crate::notify::reset(phid);
+    unsafe {
+        traced!(ffi::Phidget_setOnAttachHandler(
+            phid,
+            Some(on_attach),
+            ptr::null_mut()
+        ));
+        traced!(ffi::Phidget_setOnDetachHandler(
+            phid,
+            Some(on_detach),
+            ptr::null_mut()
+        ));
+        traced!(ffi::Phidget_setOnErrorHandler(
+            phid,
+            Some(on_error),
+            ptr::null_mut()
+        ));
+    }
+}
+
+/// Installs the crate's internal event handlers on a channel that it didn't
+/// create, such as one reported by the manager, when it's converted to a
+/// typed channel.
+///
+/// Any handlers already registered on the channel are left in place.
+pub(crate) fn adopt_handlers(phid: PhidgetHandle) {
+    crate::notify::reset(phid);
+    unsafe {
+        if !crate::handler::is_set(phid, "attach") {
+            traced!(ffi::Phidget_setOnAttachHandler(
+                phid,
+                Some(on_attach),
+                ptr::null_mut()
+            ));
+        }
+        if !crate::handler::is_set(phid, "detach") {
+            traced!(ffi::Phidget_setOnDetachHandler(
+                phid,
+                Some(on_detach),
+                ptr::null_mut()
+            ));
+        }
+        if !crate::handler::is_set(phid, "error") {
+            traced!(ffi::Phidget_setOnErrorHandler(
+                phid,
+                Some(on_error),
+                ptr::null_mut()
+            ));
+        }
+    }
+}
+
+/// Writes the `Debug` representation of a channel, showing its attach
+/// state and address.
+pub(crate) fn fmt_debug(
+    f: &mut fmt::Formatter<'_>,
+    name: &str,
+    phid: PhidgetHandle,
+) -> fmt::Result {
+    let mut dbg = f.debug_struct(name);
+    dbg.field("attached", &attached(phid));
+    match crate::cache::address(phid) {
+        Some(addr) => dbg
+            .field("serial_number", &addr.serial_number)
+            .field("hub_port", &addr.hub_port)
+            .field("channel", &addr.channel)
+            .finish(),
+        None => dbg.finish_non_exhaustive(),
+    }
+}
+
+/// Writes the `Display` representation of a channel, like:
+/// "TemperatureSensor [SN 12345, hub port 2, channel 0] (attached)"
+pub(crate) fn fmt_display(
+    f: &mut fmt::Formatter<'_>,
+    name: &str,
+    phid: PhidgetHandle,
+) -> fmt::Result {
+    // The address values are -1 for "any" when not attached
+    fn val(v: i32) -> String {
+        match v {
+            -1 => "any".into(),
+            v => v.to_string(),
+        }
+    }
+
+    write!(f, "{}", name)?;
+    if let Some(addr) = crate::cache::address(phid) {
+        write!(
+            f,
+            " [SN {}, hub port {}, channel {}]",
+            val(addr.serial_number),
+            val(addr.hub_port),
+            val(addr.channel)
+        )?;
+    }
+    match attached(phid) {
+        true => write!(f, " (attached)"),
+        false => write!(f, " (detached)"),
+    }
+}
+
+// Determines if the channel is attached, treating an error as detached.
+fn attached(phid: PhidgetHandle) -> bool {
+    let mut attached: c_int = 0;
+    let rc = unsafe { traced!(ffi::Phidget_getAttached(phid, &mut attached)) };
+    rc == 0 && attached != 0
+}
+
 /// Assigns a handler that will be called when the Attach event occurs for
 /// a matching phidget.
-pub fn set_on_attach_handler<P, F>(ph: &mut P, cb: F) -> Result<*mut c_void>
+///
+/// The handler is removed when the returned guard is dropped, and the
+/// crate's internal attach handler is restored.
+pub fn set_on_attach_handler<P, F>(ph: &mut P, cb: F) -> Result<HandlerGuard>
 where
     P: Phidget,
-    F: Fn(&GenericPhidget) + Send + 'static,
+    F: FnMut(&GenericPhidget) + Send + 'static,
 {
     // 1st box is fat ptr, 2nd is regular pointer.
     let cb: Box<Box<AttachCallback>> = Box::new(Box::new(cb));
     let ctx = Box::into_raw(cb) as *mut c_void;
 
-    ReturnCode::result(unsafe {
-        ffi::Phidget_setOnAttachHandler(ph.as_handle(), Some(on_attach), ctx)
-    })?;
-    Ok(ctx)
+    crate::handler::register::<Box<AttachCallback>, _>(
+        ph.as_handle(),
+        "attach",
+        ctx,
+        || unsafe {
+            traced!(ffi::Phidget_setOnAttachHandler(
+                ph.as_handle(),
+                Some(on_attach),
+                ctx
+            ))
+        },
+        |phid| unsafe {
+            traced!(ffi::Phidget_setOnAttachHandler(
+                phid,
+                Some(on_attach),
+                ptr::null_mut()
+            ))
+        },
+    )
 }
 
 /// Assigns a handler that will be called when the Detach event occurs for
 /// a matching Phidget.
-pub fn set_on_detach_handler<P, F>(ph: &mut P, cb: F) -> Result<*mut c_void>
+///
+/// The handler is removed when the returned guard is dropped, and the
+/// crate's internal detach handler is restored.
+pub fn set_on_detach_handler<P, F>(ph: &mut P, cb: F) -> Result<HandlerGuard>
 where
     P: Phidget,
-    F: Fn(&GenericPhidget) + Send + 'static,
+    F: FnMut(&GenericPhidget) + Send + 'static,
 {
     // 1st box is fat ptr, 2nd is regular pointer.
     let cb: Box<Box<DetachCallback>> = Box::new(Box::new(cb));
     let ctx = Box::into_raw(cb) as *mut c_void;
 
-    ReturnCode::result(unsafe {
-        ffi::Phidget_setOnDetachHandler(ph.as_handle(), Some(on_detach), ctx)
-    })?;
-    Ok(ctx)
+    crate::handler::register::<Box<DetachCallback>, _>(
+        ph.as_handle(),
+        "detach",
+        ctx,
+        || unsafe {
+            traced!(ffi::Phidget_setOnDetachHandler(
+                ph.as_handle(),
+                Some(on_detach),
+                ctx
+            ))
+        },
+        |phid| unsafe {
+            traced!(ffi::Phidget_setOnDetachHandler(
+                phid,
+                Some(on_detach),
+                ptr::null_mut()
+            ))
+        },
+    )
+}
+
+/// Assigns a handler that will be called when an Error event occurs for
+/// a matching Phidget.
+///
+/// The handler is removed when the returned guard is dropped, and the
+/// crate's internal error handler is restored.
+pub fn set_on_error_handler<P, F>(ph: &mut P, cb: F) -> Result<HandlerGuard>
+where
+    P: Phidget,
+    F: FnMut(&GenericPhidget, ErrorEventCode, &str) + Send + 'static,
+{
+    // 1st box is fat ptr, 2nd is regular pointer.
+    let cb: Box<Box<ErrorCallback>> = Box::new(Box::new(cb));
+    let ctx = Box::into_raw(cb) as *mut c_void;
+
+    crate::handler::register::<Box<ErrorCallback>, _>(
+        ph.as_handle(),
+        "error",
+        ctx,
+        || unsafe {
+            traced!(ffi::Phidget_setOnErrorHandler(
+                ph.as_handle(),
+                Some(on_error),
+                ctx
+            ))
+        },
+        |phid| unsafe {
+            traced!(ffi::Phidget_setOnErrorHandler(
+                phid,
+                Some(on_error),
+                ptr::null_mut()
+            ))
+        },
+    )
+}
+
+/// Checks a data interval against the range of intervals supported by a
+/// channel.
+///
+/// If the range couldn't be read, such as when the channel isn't
+/// attached, the interval is left for the library to check.
+pub(crate) fn check_data_interval(
+    interval: Duration,
+    range: Result<RangeInclusive<Duration>>,
+) -> Result<()> {
+    match range {
+        Ok(range) if !range.contains(&interval) => Err(Error::IntervalOutOfRange {
+            interval,
+            min: *range.start(),
+            max: *range.end(),
+        }),
+        _ => Ok(()),
+    }
 }
 
 /////////////////////////////////////////////////////////////////////////////
@@ -81,15 +352,40 @@ pub trait Phidget: Send {
     fn as_handle(&mut self) -> PhidgetHandle;
 
     /// Attempt to open the channel.
+    ///
+    /// This doesn't block. The library keeps looking for a matching device
+    /// in the background, and the channel attaches whenever one is found.
+    /// To open a number of channels at once, set an attach handler on each
+    /// with `set_on_attach_handler()`, and then open them all, to be
+    /// notified as each one attaches. Or use `wait_attached()` to block on
+    /// a particular channel later.
     fn open(&mut self) -> Result<()> {
-        ReturnCode::result(unsafe { ffi::Phidget_open(self.as_handle()) })
+        let res = ReturnCode::result(unsafe { traced!(ffi::Phidget_open(self.as_handle())) });
+        #[cfg(feature = "metrics")]
+        crate::metrics::opened(self.as_handle(), &res);
+        if res.is_ok() {
+            crate::shutdown::opened(self.as_handle());
+        }
+        res
     }
 
     /// Attempt to open the channel, waiting a limited time
     /// for it to connect.
+    ///
+    /// The library takes the timeout in whole milliseconds, so any fraction
+    /// of a millisecond is dropped. A timeout too long to be given in a
+    /// `u32` of milliseconds is an `Error::InvalidArg`.
     fn open_wait(&mut self, to: Duration) -> Result<()> {
-        let ms = to.as_millis() as u32;
-        ReturnCode::result(unsafe { ffi::Phidget_openWaitForAttachment(self.as_handle(), ms) })
+        let ms = u32::try_from(to.as_millis()).map_err(|_| Error::InvalidArg)?;
+        let res = ReturnCode::result(unsafe {
+            traced!(ffi::Phidget_openWaitForAttachment(self.as_handle(), ms))
+        });
+        #[cfg(feature = "metrics")]
+        crate::metrics::opened(self.as_handle(), &res);
+        if res.is_ok() {
+            crate::shutdown::opened(self.as_handle());
+        }
+        res
     }
 
     /// Attempt to open the channel, waiting the default time
@@ -98,102 +394,467 @@ pub trait Phidget: Send {
         self.open_wait(crate::TIMEOUT_DEFAULT)
     }
 
+    /// Attempt to open the channel, returning a future that completes
+    /// when the channel attaches.
+    ///
+    /// This does not block a thread while waiting. The future is woken by
+    /// the channel's attach event. If the channel does not attach within
+    /// the timeout, it is closed and the future fails with
+    /// `Error::Timeout`.
+    ///
+    /// The future does not depend on any particular async runtime.
+    #[cfg(feature = "async")]
+    fn open_wait_async(
+        &mut self,
+        to: Duration,
+    ) -> Pin<Box<dyn Future<Output = Result<()>> + Send + '_>> {
+        Box::pin(async move {
+            let waiter = crate::notify::AttachWaiter::new(self.as_handle(), true, false);
+            self.open()?;
+            if !self.is_attached()? && !waiter.wait_within(to).await {
+                let _ = self.close();
+                return Err(Error::Timeout);
+            }
+            Ok(())
+        })
+    }
+
+    /// Waits up to the specified time for an open channel to attach.
+    ///
+    /// This returns immediately if the channel is already attached, and
+    /// otherwise blocks until the attach event arrives, or fails with
+    /// `Error::Timeout`. It can be used after a non-blocking `open()`, or
+    /// to wait for a device to be plugged back in after it detached.
+    fn wait_attached(&mut self, to: Duration) -> Result<()> {
+        let cur = self.is_attached()?;
+        match crate::notify::wait_attach_state(self.as_handle(), true, cur, to) {
+            true => Ok(()),
+            false => Err(Error::Timeout),
+        }
+    }
+
+    /// Waits up to the specified time for the channel to detach.
+    ///
+    /// This returns immediately if the channel is not attached, and
+    /// otherwise blocks until the detach event arrives, or fails with
+    /// `Error::Timeout`.
+    fn wait_detached(&mut self, to: Duration) -> Result<()> {
+        let cur = self.is_attached()?;
+        match crate::notify::wait_attach_state(self.as_handle(), false, cur, to) {
+            true => Ok(()),
+            false => Err(Error::Timeout),
+        }
+    }
+
+    /// Returns a future that completes when an open channel attaches.
+    ///
+    /// This is the async version of `wait_attached()`, and fails with
+    /// `Error::Timeout` if the channel does not attach within the time.
+    #[cfg(feature = "async")]
+    fn wait_attached_async(
+        &mut self,
+        to: Duration,
+    ) -> Pin<Box<dyn Future<Output = Result<()>> + Send + '_>> {
+        Box::pin(async move {
+            let cur = self.is_attached()?;
+            let waiter = crate::notify::AttachWaiter::new(self.as_handle(), true, cur);
+            match waiter.wait_within(to).await {
+                true => Ok(()),
+                false => Err(Error::Timeout),
+            }
+        })
+    }
+
+    /// Returns a future that completes when the channel detaches.
+    ///
+    /// This is the async version of `wait_detached()`, and fails with
+    /// `Error::Timeout` if the channel does not detach within the time.
+    #[cfg(feature = "async")]
+    fn wait_detached_async(
+        &mut self,
+        to: Duration,
+    ) -> Pin<Box<dyn Future<Output = Result<()>> + Send + '_>> {
+        Box::pin(async move {
+            let cur = self.is_attached()?;
+            let waiter = crate::notify::AttachWaiter::new(self.as_handle(), false, cur);
+            match waiter.wait_within(to).await {
+                true => Ok(()),
+                false => Err(Error::Timeout),
+            }
+        })
+    }
+
+    /// Sets a handler to receive attach callbacks.
+    /// The handler is removed when the returned guard is dropped.
+    fn set_on_attach_handler<F>(&mut self, cb: F) -> Result<HandlerGuard>
+    where
+        Self: Sized,
+        F: FnMut(&GenericPhidget) + Send + 'static,
+    {
+        set_on_attach_handler(self, cb)
+    }
+
+    /// Sets a handler to receive detach callbacks.
+    /// The handler is removed when the returned guard is dropped.
+    fn set_on_detach_handler<F>(&mut self, cb: F) -> Result<HandlerGuard>
+    where
+        Self: Sized,
+        F: FnMut(&GenericPhidget) + Send + 'static,
+    {
+        set_on_detach_handler(self, cb)
+    }
+
+    /// Sets a handler to receive error callbacks.
+    /// The handler is removed when the returned guard is dropped.
+    fn set_on_error_handler<F>(&mut self, cb: F) -> Result<HandlerGuard>
+    where
+        Self: Sized,
+        F: FnMut(&GenericPhidget, ErrorEventCode, &str) + Send + 'static,
+    {
+        set_on_error_handler(self, cb)
+    }
+
+    /// Sets a handler to receive attach callbacks, which can borrow from
+    /// the environment of the scope.
+    /// The handler is removed at the end of the scope.
+    fn set_on_attach_handler_scoped<'scope, F>(
+        &mut self,
+        scope: &'scope HandlerScope<'scope, '_>,
+        cb: F,
+    ) -> Result<()>
+    where
+        Self: Sized,
+        F: FnMut(&GenericPhidget) + Send + 'scope,
+    {
+        // SAFETY: The guard is held by the scope, which can't be forgotten,
+        // and which waits for any call to the handler. See `HandlerScope`.
+        let cb = unsafe {
+            mem::transmute::<Box<dyn FnMut(&GenericPhidget) + Send + 'scope>, Box<AttachCallback>>(
+                Box::new(cb),
+            )
+        };
+        scope.hold(set_on_attach_handler(self, cb)?);
+        Ok(())
+    }
+
+    /// Sets a handler to receive detach callbacks, which can borrow from
+    /// the environment of the scope.
+    /// The handler is removed at the end of the scope.
+    fn set_on_detach_handler_scoped<'scope, F>(
+        &mut self,
+        scope: &'scope HandlerScope<'scope, '_>,
+        cb: F,
+    ) -> Result<()>
+    where
+        Self: Sized,
+        F: FnMut(&GenericPhidget) + Send + 'scope,
+    {
+        // SAFETY: The guard is held by the scope, which can't be forgotten,
+        // and which waits for any call to the handler. See `HandlerScope`.
+        let cb = unsafe {
+            mem::transmute::<Box<dyn FnMut(&GenericPhidget) + Send + 'scope>, Box<DetachCallback>>(
+                Box::new(cb),
+            )
+        };
+        scope.hold(set_on_detach_handler(self, cb)?);
+        Ok(())
+    }
+
+    /// Sets a handler to receive error callbacks, which can borrow from
+    /// the environment of the scope.
+    /// The handler is removed at the end of the scope.
+    fn set_on_error_handler_scoped<'scope, F>(
+        &mut self,
+        scope: &'scope HandlerScope<'scope, '_>,
+        cb: F,
+    ) -> Result<()>
+    where
+        Self: Sized,
+        F: FnMut(&GenericPhidget, ErrorEventCode, &str) + Send + 'scope,
+    {
+        // SAFETY: The guard is held by the scope, which can't be forgotten,
+        // and which waits for any call to the handler. See `HandlerScope`.
+        let cb = unsafe {
+            mem::transmute::<
+                Box<dyn FnMut(&GenericPhidget, ErrorEventCode, &str) + Send + 'scope>,
+                Box<ErrorCallback>,
+            >(Box::new(cb))
+        };
+        scope.hold(set_on_error_handler(self, cb)?);
+        Ok(())
+    }
+
+    /// Removes the attach handler, if one is set, and releases its closure.
+    fn remove_on_attach_handler(&mut self) -> Result<()> {
+        crate::handler::unregister(self.as_handle(), "attach")
+    }
+
+    /// Removes the detach handler, if one is set, and releases its closure.
+    fn remove_on_detach_handler(&mut self) -> Result<()> {
+        crate::handler::unregister(self.as_handle(), "detach")
+    }
+
+    /// Removes the error handler, if one is set, and releases its closure.
+    fn remove_on_error_handler(&mut self) -> Result<()> {
+        crate::handler::unregister(self.as_handle(), "error")
+    }
+
     /// Closes the channel
     fn close(&mut self) -> Result<()> {
-        ReturnCode::result(unsafe { ffi::Phidget_close(self.as_handle()) })
+        ReturnCode::result(unsafe { traced!(ffi::Phidget_close(self.as_handle())) })?;
+        #[cfg(feature = "metrics")]
+        crate::metrics::closed(self.as_handle());
+        crate::shutdown::closed(self.as_handle());
+        Ok(())
+    }
+
+    /// Reboots the device that the channel is attached to.
+    ///
+    /// This power-cycles the device, which can recover one that has
+    /// stopped responding. For a VINT device, only the device on the hub
+    /// port is rebooted. The channel detaches, and attaches again once
+    /// the device comes back.
+    fn reboot(&mut self) -> Result<()> {
+        ReturnCode::result(unsafe { traced!(ffi::Phidget_reboot(self.as_handle())) })
+    }
+
+    /// Reboots the device into its firmware upgrade mode.
+    ///
+    /// The device waits in the bootloader for up to the specified time for
+    /// new firmware to be written, then resumes normal operation. This is
+    /// only supported by some devices; others return `Unsupported`.
+    ///
+    /// Writing the firmware is done through the phidget22 firmware upgrade
+    /// channel, which isn't in the bindings that this crate is built
+    /// from, so the upgrade itself has to be done with the Phidget Control
+    /// Panel or the `phidget22admin` tool while the device waits.
+    ///
+    /// As with `open_wait()`, the timeout is truncated to milliseconds, and
+    /// must fit in a `u32` of them.
+    fn reboot_firmware_upgrade(&mut self, timeout: Duration) -> Result<()> {
+        let ms = u32::try_from(timeout.as_millis()).map_err(|_| Error::InvalidArg)?;
+        ReturnCode::result(unsafe {
+            traced!(ffi::Phidget_rebootFirmwareUpgrade(self.as_handle(), ms))
+        })
     }
 
     /// Determines if the channel is open
     fn is_open(&mut self) -> Result<bool> {
         let mut open: c_int = 0;
-        ReturnCode::result(unsafe { ffi::Phidget_getIsOpen(self.as_handle(), &mut open) })?;
+        ReturnCode::result(unsafe {
+            traced!(ffi::Phidget_getIsOpen(self.as_handle(), &mut open))
+        })?;
         Ok(open != 0)
     }
 
     /// Determines if the channel is open and attached to a device.
     fn is_attached(&mut self) -> Result<bool> {
         let mut attached: c_int = 0;
-        ReturnCode::result(unsafe { ffi::Phidget_getAttached(self.as_handle(), &mut attached) })?;
+        ReturnCode::result(unsafe {
+            traced!(ffi::Phidget_getAttached(self.as_handle(), &mut attached))
+        })?;
         Ok(attached != 0)
     }
 
     /// Determines if the channel is open locally (not over a network).
     fn is_local(&mut self) -> Result<bool> {
-        let mut local: c_int = 0;
-        ReturnCode::result(unsafe { ffi::Phidget_getIsLocal(self.as_handle(), &mut local) })?;
-        Ok(local != 0)
+        let phid = self.as_handle();
+        crate::cache::property(phid, "IsLocal", || {
+            let mut local: c_int = 0;
+            ReturnCode::result(unsafe { traced!(ffi::Phidget_getIsLocal(phid, &mut local)) })?;
+            Ok(local != 0)
+        })
+    }
+
+    /// Set true to restrict the channel to open locally (not over a
+    /// network).
+    /// This must be set before the channel is opened.
+    fn set_is_local(&mut self, local: bool) -> Result<()> {
+        let local = c_int::from(local);
+        ReturnCode::result(unsafe { traced!(ffi::Phidget_setIsLocal(self.as_handle(), local)) })
     }
 
     /// Set true to open the channel locally (not over a network).
+    #[deprecated(note = "Use `set_is_local()`")]
     fn set_local(&mut self, local: bool) -> Result<()> {
-        let local = c_int::from(local);
-        ReturnCode::result(unsafe { ffi::Phidget_setIsLocal(self.as_handle(), local) })
+        self.set_is_local(local)
     }
 
     /// Determines if the channel is open remotely (over a network).
     fn is_remote(&mut self) -> Result<bool> {
-        let mut rem: c_int = 0;
-        ReturnCode::result(unsafe { ffi::Phidget_getIsRemote(self.as_handle(), &mut rem) })?;
-        Ok(rem != 0)
+        let phid = self.as_handle();
+        crate::cache::property(phid, "IsRemote", || {
+            let mut rem: c_int = 0;
+            ReturnCode::result(unsafe { traced!(ffi::Phidget_getIsRemote(phid, &mut rem)) })?;
+            Ok(rem != 0)
+        })
     }
 
-    /// Set true to open the channel locally,  (not over a network).
-    fn set_remote(&mut self, rem: bool) -> Result<()> {
+    /// Set true to restrict the channel to open remotely (over a
+    /// network).
+    /// This must be set before the channel is opened.
+    fn set_is_remote(&mut self, rem: bool) -> Result<()> {
         let rem = c_int::from(rem);
-        ReturnCode::result(unsafe { ffi::Phidget_setIsRemote(self.as_handle(), rem) })
+        ReturnCode::result(unsafe { traced!(ffi::Phidget_setIsRemote(self.as_handle(), rem)) })
+    }
+
+    /// Set true to open the channel remotely (over a network).
+    #[deprecated(note = "Use `set_is_remote()`")]
+    fn set_remote(&mut self, rem: bool) -> Result<()> {
+        self.set_is_remote(rem)
+    }
+
+    /// Gets the name of the server that the channel is restricted to, or,
+    /// once attached remotely, the one it was opened through.
+    fn server_name(&mut self) -> Result<String> {
+        crate::get_ffi_string(|s| unsafe {
+            traced!(ffi::Phidget_getServerName(self.as_handle(), s))
+        })
+    }
+
+    /// Restricts the channel to be opened through the named server.
+    /// This must be set before the channel is opened.
+    fn set_server_name(&mut self, name: &str) -> Result<()> {
+        let name = CString::new(name).map_err(|_| Error::InvalidArg)?;
+        ReturnCode::result(unsafe {
+            traced!(ffi::Phidget_setServerName(self.as_handle(), name.as_ptr()))
+        })
+    }
+
+    /// Restricts the channel to be opened through the Phidget server at
+    /// the host and port.
+    ///
+    /// This registers the server with `net::add_server_host()`, so it
+    /// doesn't need to be found by discovery, and sets the channel to
+    /// open remotely through it. This must be set before the channel is
+    /// opened.
+    fn set_server_hostname(&mut self, host: &str, port: i32) -> Result<()> {
+        let name = crate::net::add_server_host(host, port)?;
+        self.set_server_name(&name)?;
+        self.set_is_remote(true)
     }
 
     /// Gets the data interval for the device, if supported.
     fn data_interval(&mut self) -> Result<Duration> {
         let mut ms: u32 = 0;
-        ReturnCode::result(unsafe { ffi::Phidget_getDataInterval(self.as_handle(), &mut ms) })?;
+        ReturnCode::result(unsafe {
+            traced!(ffi::Phidget_getDataInterval(self.as_handle(), &mut ms))
+        })?;
         Ok(Duration::from_millis(ms as u64))
     }
 
     /// Sets the data interval for the device, if supported.
+    ///
+    /// If the device is attached, the interval is checked against its
+    /// range first, failing with `Error::IntervalOutOfRange` if it's
+    /// outside. The library only works in whole milliseconds, so any
+    /// fraction of a millisecond is dropped, and an interval too long to
+    /// be given in a `u32` of milliseconds is an `Error::InvalidArg`.
     fn set_data_interval(&mut self, interval: Duration) -> Result<()> {
-        let ms = interval.as_millis() as u32;
-        ReturnCode::result(unsafe { ffi::Phidget_setDataInterval(self.as_handle(), ms) })
+        check_data_interval(interval, self.data_interval_range())?;
+        let ms = u32::try_from(interval.as_millis()).map_err(|_| Error::InvalidArg)?;
+        ReturnCode::result(unsafe { traced!(ffi::Phidget_setDataInterval(self.as_handle(), ms)) })
     }
 
     /// Gets the minimum data interval for the device, if supported.
     fn min_data_interval(&mut self) -> Result<Duration> {
-        let mut ms: u32 = 0;
-        ReturnCode::result(unsafe { ffi::Phidget_getMinDataInterval(self.as_handle(), &mut ms) })?;
-        Ok(Duration::from_millis(ms as u64))
+        let phid = self.as_handle();
+        crate::cache::property(phid, "MinDataInterval", || {
+            let mut ms: u32 = 0;
+            ReturnCode::result(unsafe { traced!(ffi::Phidget_getMinDataInterval(phid, &mut ms)) })?;
+            Ok(Duration::from_millis(ms as u64))
+        })
     }
 
     /// Gets the maximum data interval for the device, if supported.
     fn max_data_interval(&mut self) -> Result<Duration> {
-        let mut ms: u32 = 0;
-        ReturnCode::result(unsafe { ffi::Phidget_getMaxDataInterval(self.as_handle(), &mut ms) })?;
-        Ok(Duration::from_millis(ms as u64))
+        let phid = self.as_handle();
+        crate::cache::property(phid, "MaxDataInterval", || {
+            let mut ms: u32 = 0;
+            ReturnCode::result(unsafe { traced!(ffi::Phidget_getMaxDataInterval(phid, &mut ms)) })?;
+            Ok(Duration::from_millis(ms as u64))
+        })
     }
 
     /// Gets the data update rate for the device, if supported.
     fn data_rate(&mut self) -> Result<f64> {
         let mut freq: f64 = 0.0;
-        ReturnCode::result(unsafe { ffi::Phidget_getDataRate(self.as_handle(), &mut freq) })?;
+        ReturnCode::result(unsafe {
+            traced!(ffi::Phidget_getDataRate(self.as_handle(), &mut freq))
+        })?;
         Ok(freq)
     }
 
     /// Sets the data update rate for the device, if supported.
     fn set_data_rate(&mut self, freq: f64) -> Result<()> {
-        ReturnCode::result(unsafe { ffi::Phidget_setDataRate(self.as_handle(), freq) })
+        ReturnCode::result(unsafe { traced!(ffi::Phidget_setDataRate(self.as_handle(), freq)) })
     }
 
-    /// Gets the minimum data interval for the device, if supported.
+    /// Gets the minimum data update rate for the device, if supported.
     fn min_data_rate(&mut self) -> Result<f64> {
-        let mut freq: f64 = 0.0;
-        ReturnCode::result(unsafe { ffi::Phidget_getMinDataRate(self.as_handle(), &mut freq) })?;
-        Ok(freq)
+        let phid = self.as_handle();
+        crate::cache::property(phid, "MinDataRate", || {
+            let mut freq: f64 = 0.0;
+            ReturnCode::result(unsafe { traced!(ffi::Phidget_getMinDataRate(phid, &mut freq)) })?;
+            Ok(freq)
+        })
     }
 
-    /// Gets the maximum data interval for the device, if supported.
+    /// Gets the maximum data update rate for the device, if supported.
     fn max_data_rate(&mut self) -> Result<f64> {
-        let mut freq: f64 = 0.0;
-        ReturnCode::result(unsafe { ffi::Phidget_getMaxDataRate(self.as_handle(), &mut freq) })?;
-        Ok(freq)
+        let phid = self.as_handle();
+        crate::cache::property(phid, "MaxDataRate", || {
+            let mut freq: f64 = 0.0;
+            ReturnCode::result(unsafe { traced!(ffi::Phidget_getMaxDataRate(phid, &mut freq)) })?;
+            Ok(freq)
+        })
+    }
+
+    /// Gets the range of data intervals supported by the device.
+    ///
+    /// This can be used to check or clamp an interval before setting it,
+    /// like:
+    ///
+    /// ```text
+    /// let range = sensor.data_interval_range()?;
+    /// let interval = want.clamp(*range.start(), *range.end());
+    /// sensor.set_data_interval(interval)?;
+    /// ```
+    fn data_interval_range(&mut self) -> Result<RangeInclusive<Duration>> {
+        Ok(self.min_data_interval()?..=self.max_data_interval()?)
+    }
+
+    /// Gets the range of data update rates supported by the device, in Hz.
+    fn data_rate_range(&mut self) -> Result<RangeInclusive<f64>> {
+        Ok(self.min_data_rate()?..=self.max_data_rate()?)
+    }
+
+    /// Gets the parent of the channel or device in the device tree.
+    ///
+    /// The parent of a channel is the device that it's on, and the parent
+    /// of a VINT device is the VINT Hub that it's plugged into. So to get
+    /// the hub of a VINT sensor, go up two levels:
+    ///
+    /// ```text
+    /// let hub = sensor.parent()?.parent()?;
+    /// let mut hub_chan = Hub::new();
+    /// hub_chan.set_serial_number(hub.serial_number()?)?;
+    /// ```
+    ///
+    /// The parent holds a reference to the library's handle, so it can be
+    /// kept after the channel detaches, but it can only be queried while
+    /// the device is attached.
+    fn parent(&mut self) -> Result<GenericPhidget> {
+        let mut parent: PhidgetHandle = ptr::null_mut();
+        ReturnCode::result(unsafe {
+            traced!(ffi::Phidget_getParent(self.as_handle(), &mut parent))
+        })?;
+        if parent.is_null() {
+            return Err(Error::UnknownValue(ReturnCode::UnknownVal));
+        }
+        // The library returns a new reference to the parent, which the
+        // caller owns and must release, so it's not retained again.
+        Ok(GenericPhidget { phid: parent })
     }
 
     /// Get the number of channels of the specified class on the device.
@@ -201,38 +862,110 @@ pub trait Phidget: Send {
         let mut n: u32 = 0;
         let cls = cls as ffi::Phidget_ChannelClass;
         ReturnCode::result(unsafe {
-            ffi::Phidget_getDeviceChannelCount(self.as_handle(), cls, &mut n)
+            traced!(ffi::Phidget_getDeviceChannelCount(
+                self.as_handle(),
+                cls,
+                &mut n
+            ))
         })?;
         Ok(n)
     }
 
     /// Gets class of the channel
     fn channel_class(&mut self) -> Result<ChannelClass> {
-        let mut cls = ffi::Phidget_ChannelClass_PHIDCHCLASS_NOTHING;
-        ReturnCode::result(unsafe { ffi::Phidget_getChannelClass(self.as_handle(), &mut cls) })?;
-        ChannelClass::try_from(cls)
+        let phid = self.as_handle();
+        crate::cache::property(phid, "ChannelClass", || {
+            let mut cls = ffi::Phidget_ChannelClass_PHIDCHCLASS_NOTHING;
+            ReturnCode::result(unsafe { traced!(ffi::Phidget_getChannelClass(phid, &mut cls)) })?;
+            ChannelClass::try_from(cls)
+        })
     }
 
     /// Get the name of the channel class
     fn channel_class_name(&mut self) -> Result<String> {
-        crate::get_ffi_string(|s| unsafe { ffi::Phidget_getChannelClassName(self.as_handle(), s) })
+        let phid = self.as_handle();
+        crate::cache::property(phid, "ChannelClassName", || {
+            crate::get_ffi_string(|s| unsafe { traced!(ffi::Phidget_getChannelClassName(phid, s)) })
+        })
+    }
+
+    /// Gets the subclass of the channel, which further describes the type
+    /// of some channels.
+    fn channel_subclass(&mut self) -> Result<ChannelSubclass> {
+        let phid = self.as_handle();
+        crate::cache::property(phid, "ChannelSubclass", || {
+            let mut cls = ffi::Phidget_ChannelSubclass_PHIDCHSUBCLASS_NONE;
+            ReturnCode::result(unsafe {
+                traced!(ffi::Phidget_getChannelSubclass(phid, &mut cls))
+            })?;
+            ChannelSubclass::try_from(cls)
+        })
     }
 
     /// Get the channel's name.
     fn channel_name(&mut self) -> Result<String> {
-        crate::get_ffi_string(|s| unsafe { ffi::Phidget_getChannelName(self.as_handle(), s) })
+        let phid = self.as_handle();
+        crate::cache::property(phid, "ChannelName", || {
+            crate::get_ffi_string(|s| unsafe { traced!(ffi::Phidget_getChannelName(phid, s)) })
+        })
     }
 
     /// Gets class of the device
     fn device_class(&mut self) -> Result<DeviceClass> {
-        let mut cls = ffi::Phidget_DeviceClass_PHIDCLASS_NOTHING;
-        ReturnCode::result(unsafe { ffi::Phidget_getDeviceClass(self.as_handle(), &mut cls) })?;
-        DeviceClass::try_from(cls)
+        let phid = self.as_handle();
+        crate::cache::property(phid, "DeviceClass", || {
+            let mut cls = ffi::Phidget_DeviceClass_PHIDCLASS_NOTHING;
+            ReturnCode::result(unsafe { traced!(ffi::Phidget_getDeviceClass(phid, &mut cls)) })?;
+            DeviceClass::try_from(cls)
+        })
     }
 
     /// Get the name of the device class
     fn device_class_name(&mut self) -> Result<String> {
-        crate::get_ffi_string(|s| unsafe { ffi::Phidget_getDeviceClassName(self.as_handle(), s) })
+        let phid = self.as_handle();
+        crate::cache::property(phid, "DeviceClassName", || {
+            crate::get_ffi_string(|s| unsafe { traced!(ffi::Phidget_getDeviceClassName(phid, s)) })
+        })
+    }
+
+    /// Gets the ID of the device, identifying its exact model.
+    fn device_id(&mut self) -> Result<DeviceId> {
+        let phid = self.as_handle();
+        crate::cache::property(phid, "DeviceID", || {
+            let mut id = ffi::Phidget_DeviceID_PHIDID_NOTHING;
+            ReturnCode::result(unsafe { traced!(ffi::Phidget_getDeviceID(phid, &mut id)) })?;
+            DeviceId::try_from(id)
+        })
+    }
+
+    /// Gets the name of the device, like "4x Thermocouple Phidget".
+    fn device_name(&mut self) -> Result<String> {
+        let phid = self.as_handle();
+        crate::cache::property(phid, "DeviceName", || {
+            crate::get_ffi_string(|s| unsafe { traced!(ffi::Phidget_getDeviceName(phid, s)) })
+        })
+    }
+
+    /// Gets the SKU (part number) of the device, like "TMP1101_0".
+    fn device_sku(&mut self) -> Result<String> {
+        let phid = self.as_handle();
+        crate::cache::property(phid, "DeviceSKU", || {
+            crate::get_ffi_string(|s| unsafe { traced!(ffi::Phidget_getDeviceSKU(phid, s)) })
+        })
+    }
+
+    /// Gets the version of the device's firmware.
+    ///
+    /// This is the major version times 100, plus the minor version, like
+    /// 100 for v1.00, or 121 for v1.21. The library has no other firmware
+    /// version property.
+    fn device_version(&mut self) -> Result<i32> {
+        let phid = self.as_handle();
+        crate::cache::property(phid, "DeviceVersion", || {
+            let mut ver: c_int = 0;
+            ReturnCode::result(unsafe { traced!(ffi::Phidget_getDeviceVersion(phid, &mut ver)) })?;
+            Ok(ver as i32)
+        })
     }
 
     // ----- Filters -----
@@ -240,9 +973,12 @@ pub trait Phidget: Send {
     /// Determines whether this channel is a VINT Hub port channel, or part
     /// of a VINT device attached to a hub port.
     fn is_hub_port_device(&mut self) -> Result<bool> {
-        let mut on: c_int = 0;
-        ReturnCode::result(unsafe { ffi::Phidget_getIsHubPortDevice(self.as_handle(), &mut on) })?;
-        Ok(on != 0)
+        let phid = self.as_handle();
+        crate::cache::property(phid, "IsHubPortDevice", || {
+            let mut on: c_int = 0;
+            ReturnCode::result(unsafe { traced!(ffi::Phidget_getIsHubPortDevice(phid, &mut on)) })?;
+            Ok(on != 0)
+        })
     }
 
     /// Specify whether this channel should be opened on a VINT Hub port
@@ -250,28 +986,140 @@ pub trait Phidget: Send {
     /// This must be set before the channel is opened.
     fn set_is_hub_port_device(&mut self, on: bool) -> Result<()> {
         let on = c_int::from(on);
-        ReturnCode::result(unsafe { ffi::Phidget_setIsHubPortDevice(self.as_handle(), on) })
+        ReturnCode::result(unsafe {
+            traced!(ffi::Phidget_setIsHubPortDevice(self.as_handle(), on))
+        })
     }
 
     /// Gets the index of the port on the VINT Hub to which the channel is attached.
     fn hub_port(&mut self) -> Result<i32> {
-        let mut port: c_int = 0;
-        ReturnCode::result(unsafe { ffi::Phidget_getHubPort(self.as_handle(), &mut port) })?;
-        Ok(port as i32)
+        let phid = self.as_handle();
+        crate::cache::property(phid, "HubPort", || {
+            let mut port: c_int = 0;
+            ReturnCode::result(unsafe { traced!(ffi::Phidget_getHubPort(phid, &mut port)) })?;
+            Ok(port as i32)
+        })
     }
 
     /// Gets the index of the port on the VINT Hub to which the channel is attached.
     /// Set to PHIDGET_HUBPORT_ANY to open the channel on any port of the hub.
     /// This must be set before the channel is opened.
     fn set_hub_port(&mut self, port: i32) -> Result<()> {
-        ReturnCode::result(unsafe { ffi::Phidget_setHubPort(self.as_handle(), port as c_int) })
+        ReturnCode::result(unsafe {
+            traced!(ffi::Phidget_setHubPort(self.as_handle(), port as c_int))
+        })
+    }
+
+    /// Gets the communication speed of the VINT Hub port, in bits per
+    /// second.
+    fn hub_port_speed(&mut self) -> Result<u32> {
+        let mut speed: u32 = 0;
+        ReturnCode::result(unsafe {
+            traced!(ffi::Phidget_getHubPortSpeed(self.as_handle(), &mut speed))
+        })?;
+        Ok(speed)
+    }
+
+    /// Sets the communication speed of the VINT Hub port, in bits per
+    /// second.
+    ///
+    /// The speed can only be raised above the default when both the hub
+    /// port and the VINT device support it. It can't be more than the
+    /// lower of `max_hub_port_speed()` and `max_vint_device_speed()`.
+    fn set_hub_port_speed(&mut self, speed: u32) -> Result<()> {
+        ReturnCode::result(unsafe {
+            traced!(ffi::Phidget_setHubPortSpeed(self.as_handle(), speed))
+        })
+    }
+
+    /// Gets the maximum communication speed of the VINT Hub port, in bits
+    /// per second.
+    fn max_hub_port_speed(&mut self) -> Result<u32> {
+        let phid = self.as_handle();
+        crate::cache::property(phid, "MaxHubPortSpeed", || {
+            let mut speed: u32 = 0;
+            ReturnCode::result(unsafe {
+                traced!(ffi::Phidget_getMaxHubPortSpeed(phid, &mut speed))
+            })?;
+            Ok(speed)
+        })
+    }
+
+    /// Determines whether the VINT Hub port supports setting its
+    /// communication speed.
+    fn hub_port_supports_set_speed(&mut self) -> Result<bool> {
+        let phid = self.as_handle();
+        crate::cache::property(phid, "HubPortSupportsSetSpeed", || {
+            let mut on: c_int = 0;
+            ReturnCode::result(unsafe {
+                traced!(ffi::Phidget_getHubPortSupportsSetSpeed(phid, &mut on))
+            })?;
+            Ok(on != 0)
+        })
+    }
+
+    /// Determines whether the VINT Hub port can set its communication
+    /// speed automatically.
+    fn hub_port_supports_auto_set_speed(&mut self) -> Result<bool> {
+        let phid = self.as_handle();
+        crate::cache::property(phid, "HubPortSupportsAutoSetSpeed", || {
+            let mut on: c_int = 0;
+            ReturnCode::result(unsafe {
+                traced!(ffi::Phidget_getHubPortSupportsAutoSetSpeed(phid, &mut on))
+            })?;
+            Ok(on != 0)
+        })
+    }
+
+    /// Determines whether the VINT device supports setting its
+    /// communication speed.
+    fn vint_device_supports_set_speed(&mut self) -> Result<bool> {
+        let phid = self.as_handle();
+        crate::cache::property(phid, "VINTDeviceSupportsSetSpeed", || {
+            let mut on: c_int = 0;
+            ReturnCode::result(unsafe {
+                traced!(ffi::Phidget_getVINTDeviceSupportsSetSpeed(phid, &mut on))
+            })?;
+            Ok(on != 0)
+        })
+    }
+
+    /// Determines whether the VINT device can have its communication speed
+    /// set automatically by the hub.
+    fn vint_device_supports_auto_set_speed(&mut self) -> Result<bool> {
+        let phid = self.as_handle();
+        crate::cache::property(phid, "VINTDeviceSupportsAutoSetSpeed", || {
+            let mut on: c_int = 0;
+            ReturnCode::result(unsafe {
+                traced!(ffi::Phidget_getVINTDeviceSupportsAutoSetSpeed(
+                    phid, &mut on
+                ))
+            })?;
+            Ok(on != 0)
+        })
+    }
+
+    /// Gets the maximum communication speed of the VINT device, in bits
+    /// per second.
+    fn max_vint_device_speed(&mut self) -> Result<u32> {
+        let phid = self.as_handle();
+        crate::cache::property(phid, "MaxVINTDeviceSpeed", || {
+            let mut speed: u32 = 0;
+            ReturnCode::result(unsafe {
+                traced!(ffi::Phidget_getMaxVINTDeviceSpeed(phid, &mut speed))
+            })?;
+            Ok(speed)
+        })
     }
 
     /// Gets the channel index of the device.
     fn channel(&mut self) -> Result<i32> {
-        let mut ch: c_int = 0;
-        ReturnCode::result(unsafe { ffi::Phidget_getChannel(self.as_handle(), &mut ch) })?;
-        Ok(ch as i32)
+        let phid = self.as_handle();
+        crate::cache::property(phid, "Channel", || {
+            let mut ch: c_int = 0;
+            ReturnCode::result(unsafe { traced!(ffi::Phidget_getChannel(phid, &mut ch)) })?;
+            Ok(ch as i32)
+        })
     }
 
     /// Sets the channel index to be opened.
@@ -279,18 +1127,23 @@ pub trait Phidget: Send {
     /// channel on the specified device. This must be set before the channel
     /// is opened.
     fn set_channel(&mut self, chan: i32) -> Result<()> {
-        ReturnCode::result(unsafe { ffi::Phidget_setChannel(self.as_handle(), chan as c_int) })
+        ReturnCode::result(unsafe {
+            traced!(ffi::Phidget_setChannel(self.as_handle(), chan as c_int))
+        })
     }
 
     /// Gets the serial number of the device.
     /// If the channel is part of a VINT device, this is the serial number
     /// of the VINT Hub to which the device is attached.
     fn serial_number(&mut self) -> Result<i32> {
-        let mut n = 0;
-        ReturnCode::result(unsafe {
-            ffi::Phidget_getDeviceSerialNumber(self.as_handle(), &mut n)
-        })?;
-        Ok(n)
+        let phid = self.as_handle();
+        crate::cache::property(phid, "DeviceSerialNumber", || {
+            let mut n = 0;
+            ReturnCode::result(unsafe {
+                traced!(ffi::Phidget_getDeviceSerialNumber(phid, &mut n))
+            })?;
+            Ok(n)
+        })
     }
 
     /// Sets the device serial number to be opened.
@@ -299,30 +1152,103 @@ pub trait Phidget: Send {
     /// number of the VINT Hub to which the device is attached.
     /// This must be set before the channel is opened.
     fn set_serial_number(&mut self, sn: i32) -> Result<()> {
-        ReturnCode::result(unsafe { ffi::Phidget_setDeviceSerialNumber(self.as_handle(), sn) })
+        ReturnCode::result(unsafe {
+            traced!(ffi::Phidget_setDeviceSerialNumber(self.as_handle(), sn))
+        })
+    }
+
+    /// Gets the label of the device.
+    /// For a channel that is not attached, this is the label that was set
+    /// to match a device.
+    fn device_label(&mut self) -> Result<String> {
+        crate::get_ffi_string(|s| unsafe {
+            traced!(ffi::Phidget_getDeviceLabel(self.as_handle(), s))
+        })
+    }
+
+    /// Sets the label of the device to be opened.
+    /// This can be used instead of the serial number to select a device.
+    /// This must be set before the channel is opened.
+    fn set_device_label(&mut self, label: &str) -> Result<()> {
+        let label = CString::new(label).map_err(|_| Error::InvalidArg)?;
+        ReturnCode::result(unsafe {
+            traced!(ffi::Phidget_setDeviceLabel(
+                self.as_handle(),
+                label.as_ptr()
+            ))
+        })
+    }
+
+    /// Writes a label to the device's flash memory.
+    /// The label is kept when the device is powered off, so it can be used
+    /// to identify the device when it is opened again. The channel must be
+    /// attached. Not all devices support labels.
+    fn write_device_label(&mut self, label: &str) -> Result<()> {
+        let label = CString::new(label).map_err(|_| Error::InvalidArg)?;
+        ReturnCode::result(unsafe {
+            traced!(ffi::Phidget_writeDeviceLabel(
+                self.as_handle(),
+                label.as_ptr()
+            ))
+        })
     }
 }
 
 /////////////////////////////////////////////////////////////////////////////
 
+// Takes a reference to the handle, if it's not null.
+fn retain(phid: PhidgetHandle) {
+    if !phid.is_null() {
+        unsafe {
+            ffi::Phidget_retain(phid);
+        }
+    }
+}
+
 /// A wrapper for a generic phidget.
 ///
 /// This contains a wrapper around a generic PhidgetHandle, which might be
 /// any type of device. It can be queried for additional information and
-/// potentially converted into a specific device object.
+/// converted into a specific channel type with `try_into()`, like:
+///
+/// ```text
+/// mgr.set_on_attach_handler(|ph| {
+///     if let Ok(sensor) = TemperatureSensor::try_from(ph.clone()) {
+///         // ...
+///     }
+/// })?;
+/// ```
+///
+/// The conversion fails with a `WrongDevice` error if the channel is of a
+/// different class.
 ///
-/// This is a non-owning object. It will not release the underlying Phidget
-/// when dropped. It is typically used to wrap a generic handle sent to a
-/// callback from the phidget22 library.
-#[allow(missing_copy_implementations)]
+/// This holds a reference to the underlying Phidget, which is released
+/// when it's dropped, so it can be kept beyond the callback that it was
+/// passed to. It never closes the channel.
 pub struct GenericPhidget {
     phid: PhidgetHandle,
 }
 
 impl GenericPhidget {
-    /// Creates a new, generic phidget for the handle.
+    /// Creates a new, generic phidget for the handle, taking a reference
+    /// to it.
     pub fn new(phid: PhidgetHandle) -> Self {
-        Self { phid }
+        Self::from(phid)
+    }
+
+    /// Gives up the phidget's reference to the handle of a channel of the
+    /// specified class, so that it can be owned by a typed channel.
+    ///
+    /// The crate's internal handlers are installed on the channel.
+    /// Returns a `WrongDevice` error if the channel is of another class.
+    pub(crate) fn into_channel(mut self, cls: ChannelClass) -> Result<PhidgetHandle> {
+        if self.channel_class()? != cls {
+            return Err(Error::from(ReturnCode::WrongDevice));
+        }
+        let phid = self.phid;
+        mem::forget(self);
+        adopt_handlers(phid);
+        Ok(phid)
     }
 }
 
@@ -333,10 +1259,227 @@ impl Phidget for GenericPhidget {
     }
 }
 
+impl Clone for GenericPhidget {
+    fn clone(&self) -> Self {
+        Self::new(self.phid)
+    }
+}
+
+impl fmt::Debug for GenericPhidget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_debug(f, "GenericPhidget", self.phid)
+    }
+}
+
+impl fmt::Display for GenericPhidget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_display(f, "GenericPhidget", self.phid)
+    }
+}
+
 unsafe impl Send for GenericPhidget {}
+unsafe impl Sync for GenericPhidget {}
 
 impl From<PhidgetHandle> for GenericPhidget {
     fn from(phid: PhidgetHandle) -> Self {
-        Self::new(phid)
+        retain(phid);
+        Self { phid }
+    }
+}
+
+impl Drop for GenericPhidget {
+    fn drop(&mut self) {
+        if !self.phid.is_null() {
+            // This might be the last reference, as for a parent or a
+            // channel from the manager, so nothing is left in the cache.
+            crate::cache::clear(self.phid);
+            unsafe {
+                ffi::Phidget_release(&mut self.phid);
+            }
+        }
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::*;
+    use crate::{
+        mock::{attach, detach, set_value, MOCK_SERIAL_NUMBER},
+        DeviceEvent, Sensor, TemperatureSensor,
+    };
+    use phidget_sys::mock::ref_count;
+    use std::sync::mpsc;
+
+    #[test]
+    fn test_mock_device() {
+        let mut sensor = TemperatureSensor::new();
+        assert_eq!(sensor.temperature(), Err(Error::NotAttached));
+
+        let (tx, rx) = mpsc::channel();
+        let _handler = sensor
+            .set_on_temperature_change_handler(move |_, t| {
+                let _ = tx.send(t);
+            })
+            .unwrap();
+
+        sensor.set_hub_port(3).unwrap();
+        sensor.open().unwrap();
+        assert!(sensor.is_attached().unwrap());
+        assert_eq!(sensor.is_local(), Ok(true));
+        assert_eq!(sensor.is_remote(), Ok(false));
+        assert_eq!(sensor.hub_port(), Ok(3));
+        assert_eq!(sensor.serial_number(), Ok(MOCK_SERIAL_NUMBER));
+        assert_eq!(sensor.device_id(), Ok(DeviceId::Unknown));
+        assert_eq!(sensor.channel_subclass(), Ok(ChannelSubclass::None));
+        assert_eq!(sensor.device_sku().as_deref(), Ok("MOCK0000_0"));
+        assert_eq!(sensor.device_version(), Ok(100));
+        assert_eq!(
+            sensor.temperature(),
+            Err(Error::UnknownValue(ReturnCode::UnknownVal))
+        );
+
+        assert_eq!(sensor.known_value(), Ok(None));
+
+        set_value(&mut sensor, 21.5).unwrap();
+        assert_eq!(rx.try_recv(), Ok(21.5));
+        assert_eq!(sensor.temperature(), Ok(21.5));
+        assert_eq!(sensor.known_value(), Ok(Some(21.5)));
+
+        let events = crate::events::subscribe();
+        detach(&mut sensor);
+        assert!(!sensor.is_attached().unwrap());
+        assert!(matches!(
+            events.try_recv(),
+            Ok(DeviceEvent::Detached(src)) if src.hub_port == 3
+        ));
+        assert_eq!(set_value(&mut sensor, 22.0), Err(Error::NotAttached));
+    }
+
+    #[test]
+    fn test_mock_reboot() {
+        let mut sensor = TemperatureSensor::new();
+        assert_eq!(sensor.reboot(), Err(Error::NotAttached));
+
+        let (tx, rx) = mpsc::channel();
+        let _handler = sensor
+            .set_on_attach_handler(move |_| {
+                let _ = tx.send(());
+            })
+            .unwrap();
+
+        sensor.open().unwrap();
+        assert_eq!(rx.try_recv(), Ok(()));
+
+        // The device detaches and attaches again
+        sensor.reboot().unwrap();
+        assert_eq!(rx.try_recv(), Ok(()));
+        assert!(sensor.is_attached().unwrap());
+    }
+
+    #[test]
+    fn test_mock_millisecond_overflow() {
+        let mut sensor = TemperatureSensor::new();
+        let too_long = Duration::from_millis(u64::from(u32::MAX) + 1);
+        assert_eq!(sensor.set_data_interval(too_long), Err(Error::InvalidArg));
+        assert_eq!(sensor.open_wait(too_long), Err(Error::InvalidArg));
+        assert_eq!(sensor.is_open(), Ok(false));
+
+        sensor.open_wait(Duration::from_millis(100)).unwrap();
+        assert_eq!(
+            sensor.reboot_firmware_upgrade(too_long),
+            Err(Error::InvalidArg)
+        );
+    }
+
+    #[test]
+    fn test_mock_hub_port_speed() {
+        let mut sensor = TemperatureSensor::new();
+        assert_eq!(sensor.set_hub_port_speed(400_000), Err(Error::NotAttached));
+
+        sensor.open().unwrap();
+        assert_eq!(sensor.vint_device_supports_set_speed(), Ok(true));
+        assert_eq!(sensor.max_vint_device_speed(), Ok(400_000));
+        assert_eq!(sensor.hub_port_speed(), Ok(100_000));
+
+        sensor.set_hub_port_speed(400_000).unwrap();
+        assert_eq!(sensor.hub_port_speed(), Ok(400_000));
+    }
+
+    #[test]
+    fn test_mock_generic_try_into() {
+        use crate::devices::HumiditySensor;
+
+        let mut sensor = TemperatureSensor::new();
+        let ph = GenericPhidget::from(sensor.as_handle());
+        assert_eq!(
+            HumiditySensor::try_from(ph.clone()).err(),
+            Some(Error::Phidget(ReturnCode::WrongDevice))
+        );
+
+        // The typed channel keeps its own reference to the handle
+        let mut temp = TemperatureSensor::try_from(ph).unwrap();
+        drop(sensor);
+
+        temp.open().unwrap();
+        set_value(&mut temp, 21.5).unwrap();
+        assert_eq!(temp.temperature(), Ok(21.5));
+    }
+
+    #[test]
+    fn test_mock_parent() {
+        let mut sensor = TemperatureSensor::new();
+        assert_eq!(sensor.parent().err(), Some(Error::NotAttached));
+        sensor.open().unwrap();
+        attach(&mut sensor);
+
+        // Each call takes over the reference that the library returns,
+        // and releases it when dropped.
+        let mut parent = sensor.parent().unwrap();
+        let phid = parent.as_handle();
+        assert_eq!(ref_count(phid), 2);
+        let parent2 = sensor.parent().unwrap();
+        assert_eq!(ref_count(phid), 3);
+        drop(parent2);
+        assert_eq!(ref_count(phid), 2);
+        drop(parent);
+        assert_eq!(ref_count(phid), 1);
+    }
+
+    #[test]
+    fn test_mock_state_released_with_generic() {
+        let mut sensor = TemperatureSensor::new();
+        sensor.open().unwrap();
+        attach(&mut sensor);
+
+        let mut parent = sensor.parent().unwrap();
+        let phid = parent.as_handle();
+        assert!(parent.device_id().is_ok());
+        assert!(crate::cache::is_cached(phid));
+
+        drop(parent);
+        assert!(!crate::cache::is_cached(phid));
+    }
+
+    #[test]
+    fn test_mock_device_label() {
+        let mut sensor = crate::builder::<TemperatureSensor>()
+            .label("greenhouse-north")
+            .build()
+            .unwrap();
+        assert_eq!(sensor.device_label().as_deref(), Ok("greenhouse-north"));
+        assert_eq!(
+            sensor.write_device_label("greenhouse-south"),
+            Err(Error::NotAttached)
+        );
+
+        sensor.open().unwrap();
+        sensor.write_device_label("greenhouse-south").unwrap();
+        assert_eq!(sensor.device_label().as_deref(), Ok("greenhouse-south"));
+        assert_eq!(
+            sensor.set_device_label("bad\0label"),
+            Err(Error::InvalidArg)
+        );
     }
 }
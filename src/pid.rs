@@ -0,0 +1,351 @@
+// phidget-rs/src/pid.rs
+//
+// This file is part of the 'phidget-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+//! PID control.
+//!
+//! The `Pid` is a plain proportional-integral-derivative controller, with
+//! output limits, anti-windup, and a minimum sample time. It can be run
+//! by hand, or in a `PidLoop`, which feeds it from the change events of a
+//! sensor and drives an output with the result, like the duty cycle of a
+//! digital output or the voltage of a voltage output:
+//!
+//! ```text
+//! let pid = Pid::new(0.08, 0.01, 0.0)
+//!     .limits(0.0, 1.0)
+//!     .sample_time(Duration::from_millis(250));
+//!
+//! let mut control = PidLoop::new(pid, &oven_temp, heater_pwm, 180.0)?;
+//! thread::sleep(Duration::from_secs(600));
+//! control.set_setpoint(0.0);
+//! ```
+//!
+//! The derivative is taken on the measurement rather than the error, so
+//! that a change of the setpoint doesn't kick the output.
+//!
+
+use crate::{
+    devices::{DigitalOutput, VoltageOutput},
+    Error, HandlerGuard, Result, Sensor,
+};
+use std::{
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+/// An output that can be driven by a control value.
+pub trait Actuator: Send {
+    /// Applies the control value to the output.
+    fn set_output(&mut self, val: f64) -> Result<()>;
+}
+
+impl Actuator for DigitalOutput {
+    /// Sets the duty cycle, from 0.0 to 1.0.
+    fn set_output(&mut self, val: f64) -> Result<()> {
+        self.set_duty_cycle(val)
+    }
+}
+
+impl Actuator for VoltageOutput {
+    /// Sets the voltage.
+    fn set_output(&mut self, val: f64) -> Result<()> {
+        self.set_voltage(val)
+    }
+}
+
+impl<F> Actuator for F
+where
+    F: FnMut(f64) -> Result<()> + Send,
+{
+    fn set_output(&mut self, val: f64) -> Result<()> {
+        self(val)
+    }
+}
+
+/// A PID controller.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pid {
+    kp: f64,
+    ki: f64,
+    kd: f64,
+    min: f64,
+    max: f64,
+    sample_time: Duration,
+    // The integral term, already scaled by the gain, so that a change of
+    // the gain doesn't bump the output
+    integral: f64,
+    // The last input, and the time it was used
+    last: Option<(f64, Instant)>,
+    output: Option<f64>,
+}
+
+impl Pid {
+    /// Creates a controller with the proportional, integral, and
+    /// derivative gains.
+    ///
+    /// The output is unlimited, and every input is used, until they're
+    /// set otherwise.
+    pub fn new(kp: f64, ki: f64, kd: f64) -> Self {
+        Self {
+            kp,
+            ki,
+            kd,
+            min: f64::NEG_INFINITY,
+            max: f64::INFINITY,
+            sample_time: Duration::ZERO,
+            integral: 0.0,
+            last: None,
+            output: None,
+        }
+    }
+
+    /// Sets the limits of the output.
+    ///
+    /// The integral term is held within the limits as well, so that it
+    /// doesn't wind up while the output is saturated.
+    pub fn limits(mut self, min: f64, max: f64) -> Self {
+        self.min = min.min(max);
+        self.max = max.max(min);
+        self
+    }
+
+    /// Sets the minimum time between updates.
+    ///
+    /// Inputs that arrive sooner than this after the last one that was
+    /// used are ignored.
+    pub fn sample_time(mut self, sample_time: Duration) -> Self {
+        self.sample_time = sample_time;
+        self
+    }
+
+    /// Gets the proportional, integral, and derivative gains.
+    pub fn gains(&self) -> (f64, f64, f64) {
+        (self.kp, self.ki, self.kd)
+    }
+
+    /// Sets the proportional, integral, and derivative gains.
+    pub fn set_gains(&mut self, kp: f64, ki: f64, kd: f64) {
+        self.kp = kp;
+        self.ki = ki;
+        self.kd = kd;
+    }
+
+    /// Gets the last output, if there's been one.
+    pub fn output(&self) -> Option<f64> {
+        self.output
+    }
+
+    /// Clears the integral term and the history, as if the controller
+    /// was new.
+    pub fn reset(&mut self) {
+        self.integral = 0.0;
+        self.last = None;
+        self.output = None;
+    }
+
+    /// Updates the controller with an input taken at a time, returning the
+    /// new output.
+    ///
+    /// This returns `None` if the input came sooner than the sample time
+    /// after the last one. The first input only has the proportional term,
+    /// since there's no time over which to integrate or differentiate.
+    pub fn update(&mut self, setpoint: f64, input: f64, now: Instant) -> Option<f64> {
+        let err = setpoint - input;
+        let mut deriv = 0.0;
+
+        if let Some((last_input, last_time)) = self.last {
+            let dt = now.saturating_duration_since(last_time);
+            if dt < self.sample_time || dt.is_zero() {
+                return None;
+            }
+            let dt = dt.as_secs_f64();
+            self.integral = (self.integral + self.ki * err * dt).clamp(self.min, self.max);
+            deriv = -self.kd * (input - last_input) / dt;
+        }
+
+        let out = (self.kp * err + self.integral + deriv).clamp(self.min, self.max);
+        self.last = Some((input, now));
+        self.output = Some(out);
+        Some(out)
+    }
+}
+
+// The state shared with the change handler
+struct State {
+    pid: Pid,
+    setpoint: f64,
+    output: Box<dyn Actuator>,
+    last_error: Option<Error>,
+}
+
+type Shared = Arc<Mutex<State>>;
+
+// Locks the state.
+fn lock(state: &Shared) -> MutexGuard<'_, State> {
+    state.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+/// A PID controller running from the change events of a sensor, and
+/// driving an output.
+///
+/// The sensor's change handler is removed when the loop is dropped. The
+/// output is left as it was last set.
+pub struct PidLoop {
+    // The controller, setpoint, and output
+    state: Shared,
+    // The change handler of the sensor
+    guards: Vec<HandlerGuard>,
+}
+
+impl PidLoop {
+    /// Starts a control loop, from the sensor to the output.
+    ///
+    /// The sensor and the output should be open and attached, and the
+    /// loop owns the output from here on. This sets the sensor's change
+    /// handler, replacing any handler that was previously set.
+    pub fn new<S, A>(pid: Pid, sensor: &S, output: A, setpoint: f64) -> Result<Self>
+    where
+        S: Sensor,
+        S::Value: Into<f64>,
+        A: Actuator + 'static,
+    {
+        let state: Shared = Arc::new(Mutex::new(State {
+            pid,
+            setpoint,
+            output: Box::new(output),
+            last_error: None,
+        }));
+
+        let guard = sensor.on_change({
+            let state = Arc::clone(&state);
+            move |_, val| {
+                let mut st = lock(&state);
+                let setpoint = st.setpoint;
+                if let Some(out) = st.pid.update(setpoint, val.into(), Instant::now()) {
+                    if let Err(err) = st.output.set_output(out) {
+                        st.last_error = Some(err);
+                    }
+                }
+            }
+        })?;
+
+        Ok(Self {
+            state,
+            guards: vec![guard],
+        })
+    }
+
+    /// Gets the setpoint.
+    pub fn setpoint(&self) -> f64 {
+        lock(&self.state).setpoint
+    }
+
+    /// Sets the setpoint.
+    ///
+    /// This takes effect on the next reading from the sensor.
+    pub fn set_setpoint(&mut self, setpoint: f64) {
+        lock(&self.state).setpoint = setpoint;
+    }
+
+    /// Gets the proportional, integral, and derivative gains.
+    pub fn gains(&self) -> (f64, f64, f64) {
+        lock(&self.state).pid.gains()
+    }
+
+    /// Sets the proportional, integral, and derivative gains.
+    pub fn set_gains(&mut self, kp: f64, ki: f64, kd: f64) {
+        lock(&self.state).pid.set_gains(kp, ki, kd);
+    }
+
+    /// Gets the last output, if there's been one.
+    pub fn output(&self) -> Option<f64> {
+        lock(&self.state).pid.output()
+    }
+
+    /// Takes the last error from setting the output, if there was one.
+    pub fn take_error(&mut self) -> Option<Error> {
+        lock(&self.state).last_error.take()
+    }
+}
+
+impl Drop for PidLoop {
+    fn drop(&mut self) {
+        self.guards.clear();
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pid() {
+        let mut pid = Pid::new(2.0, 1.0, 0.0)
+            .limits(-10.0, 10.0)
+            .sample_time(Duration::from_millis(100));
+        let t0 = Instant::now();
+        let ms = Duration::from_millis;
+
+        // The first input is only proportional
+        assert_eq!(pid.update(5.0, 4.0, t0), Some(2.0));
+        assert_eq!(pid.update(5.0, 4.0, t0 + ms(50)), None);
+
+        // One second of an error of 1.0 integrates to 1.0
+        assert_eq!(pid.update(5.0, 4.0, t0 + ms(1000)), Some(3.0));
+
+        // A large error saturates the output, and the integral is held
+        // at the limit rather than winding up
+        assert_eq!(pid.update(100.0, 0.0, t0 + ms(2000)), Some(10.0));
+        assert_eq!(pid.update(100.0, 0.0, t0 + ms(3000)), Some(10.0));
+        assert_eq!(pid.update(5.0, 5.0, t0 + ms(4000)), Some(10.0));
+        assert_eq!(pid.update(5.0, 6.0, t0 + ms(5000)), Some(7.0));
+
+        pid.reset();
+        assert_eq!(pid.output(), None);
+
+        // The derivative is on the measurement
+        let mut pid = Pid::new(0.0, 0.0, 1.0);
+        assert_eq!(pid.update(0.0, 1.0, t0), Some(0.0));
+        assert_eq!(pid.update(10.0, 3.0, t0 + ms(1000)), Some(-2.0));
+    }
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod mock_tests {
+    use super::*;
+    use crate::{mock::set_value, Phidget, TemperatureSensor};
+
+    #[test]
+    fn test_mock_pid_loop() {
+        let mut sensor = TemperatureSensor::new();
+        let mut heater = DigitalOutput::new();
+        sensor.open().unwrap();
+        heater.open().unwrap();
+
+        let handle = heater.as_handle() as phidget_sys::PhidgetDigitalOutputHandle;
+        let view = std::mem::ManuallyDrop::new(DigitalOutput::from(handle));
+
+        let pid = Pid::new(0.1, 0.0, 0.0).limits(0.0, 1.0);
+        let mut control = PidLoop::new(pid, &sensor, heater, 50.0).unwrap();
+
+        set_value(&mut sensor, 45.0).unwrap();
+        assert_eq!(control.output(), Some(0.5));
+        assert_eq!(view.duty_cycle(), Ok(0.5));
+
+        // Too far below the setpoint saturates the output
+        set_value(&mut sensor, 20.0).unwrap();
+        assert_eq!(view.duty_cycle(), Ok(1.0));
+
+        control.set_setpoint(10.0);
+        set_value(&mut sensor, 20.0).unwrap();
+        assert_eq!(view.duty_cycle(), Ok(0.0));
+        assert!(control.take_error().is_none());
+    }
+}
@@ -0,0 +1,279 @@
+// phidget-rs/src/prometheus.rs
+//
+// This file is part of the 'phidget-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+//! A Prometheus exporter for sensor values.
+//!
+//! With the `exporter-prometheus` feature, the values of any number of
+//! sensors can be exported as Prometheus gauges, turning a Phidget rig
+//! into a scrape target. Each sensor is exported under a metric name,
+//! and labeled with the address of its channel:
+//!
+//! ```text
+//! let mut exporter = PrometheusExporter::new();
+//! exporter.add("temperature_celsius", &mut temp_sensor)?;
+//! exporter.add("humidity_percent", &mut hum_sensor)?;
+//!
+//! let _server = exporter.serve("0.0.0.0:9100")?;
+//! ```
+//!
+//! A scrape of `/metrics` then returns something like:
+//!
+//! ```text
+//! # TYPE phidget_temperature_celsius gauge
+//! phidget_temperature_celsius{serial="561234",hub_port="2",channel="0"} 21.5
+//! ```
+//!
+//! A gauge appears once its sensor reports its first value change.
+//!
+
+use crate::{httpd, Error, HandlerGuard, Result, Sensor};
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    io::{self, BufReader},
+    net::{SocketAddr, TcpStream, ToSocketAddrs},
+    sync::{Arc, Mutex},
+};
+
+/// The prefix added to all the metric names.
+pub const METRIC_PREFIX: &str = "phidget_";
+
+// The latest value of a gauge for one channel
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Gauge {
+    // The channel handle, identifying the gauge
+    key: usize,
+    serial_number: i32,
+    hub_port: i32,
+    channel: i32,
+    value: f64,
+}
+
+// The gauges, by metric name
+type Registry = Arc<Mutex<BTreeMap<String, Vec<Gauge>>>>;
+
+/// An exporter of sensor values as Prometheus gauges.
+///
+/// The sensors' change handlers feed the gauges, and are removed when
+/// the exporter is dropped.
+#[derive(Default)]
+pub struct PrometheusExporter {
+    // The gauges, shared with the handlers and the server
+    gauges: Registry,
+    // The handlers feeding the gauges
+    guards: Vec<HandlerGuard>,
+}
+
+impl PrometheusExporter {
+    /// Creates a new exporter with no sensors.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Exports the value of the sensor under the metric name.
+    ///
+    /// The name is given the "phidget_" prefix. It must be a valid
+    /// Prometheus metric name, otherwise this returns `InvalidArg`.
+    /// Several sensors can be exported under the same name; they are told
+    /// apart by their labels. This sets the sensor's change handler,
+    /// replacing any handler that was previously set. Boolean values are
+    /// exported as 0 or 1.
+    pub fn add<S>(&mut self, name: &str, sensor: &mut S) -> Result<()>
+    where
+        S: Sensor,
+        S::Value: Into<f64>,
+    {
+        if !is_valid_name(name) {
+            return Err(Error::InvalidArg);
+        }
+        let name = format!("{}{}", METRIC_PREFIX, name);
+        let key = sensor.as_handle() as usize;
+        let gauges = Arc::clone(&self.gauges);
+
+        let guard = sensor.on_change(move |_, val| {
+            let addr = crate::cache::address(key as _);
+            let gauge = Gauge {
+                key,
+                serial_number: addr.map(|a| a.serial_number).unwrap_or(-1),
+                hub_port: addr.map(|a| a.hub_port).unwrap_or(-1),
+                channel: addr.map(|a| a.channel).unwrap_or(-1),
+                value: val.into(),
+            };
+            let mut gauges = gauges.lock().unwrap_or_else(|err| err.into_inner());
+            let list = gauges.entry(name.clone()).or_default();
+            match list.iter_mut().find(|g| g.key == key) {
+                Some(g) => *g = gauge,
+                None => list.push(gauge),
+            }
+        })?;
+        self.guards.push(guard);
+        Ok(())
+    }
+
+    /// Renders the current values of all the gauges in the Prometheus
+    /// text format.
+    ///
+    /// This can be used to serve the metrics from an application's own
+    /// HTTP server, instead of with `serve()`.
+    pub fn render(&self) -> String {
+        render(&self.gauges)
+    }
+
+    /// Starts serving the metrics over HTTP on the specified address.
+    ///
+    /// The metrics are served at `/metrics` by background threads, which
+    /// are stopped when the returned server is dropped. The server keeps
+    /// serving the last values if the exporter is dropped first.
+    pub fn serve<A: ToSocketAddrs>(&self, addr: A) -> io::Result<MetricsServer> {
+        let gauges = Arc::clone(&self.gauges);
+        let server = httpd::Server::start(addr, httpd::MAX_CONNECTIONS, move |stream| {
+            handle_request(stream, &gauges)
+        })?;
+        Ok(MetricsServer { server })
+    }
+}
+
+/// A running HTTP server for the metrics of a `PrometheusExporter`.
+///
+/// The server is stopped when this is dropped.
+pub struct MetricsServer {
+    server: httpd::Server,
+}
+
+impl MetricsServer {
+    /// Gets the address that the server is listening on.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.server.local_addr()
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+// Determines if the name is a valid Prometheus metric name.
+fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' || c == ':' => (),
+        _ => return false,
+    }
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == ':')
+}
+
+// Renders the gauges in the Prometheus text format.
+fn render(gauges: &Registry) -> String {
+    let gauges = gauges.lock().unwrap_or_else(|err| err.into_inner());
+    let mut out = String::new();
+    for (name, list) in gauges.iter() {
+        let _ = writeln!(out, "# TYPE {} gauge", name);
+        for g in list {
+            let _ = writeln!(
+                out,
+                "{}{{serial=\"{}\",hub_port=\"{}\",channel=\"{}\"}} {}",
+                name,
+                g.serial_number,
+                g.hub_port,
+                g.channel,
+                fmt_value(g.value)
+            );
+        }
+    }
+    out
+}
+
+// Formats a value as Prometheus expects, including the special values.
+fn fmt_value(val: f64) -> String {
+    if val.is_nan() {
+        "NaN".into()
+    }
+    else if val.is_infinite() {
+        if val > 0.0 { "+Inf" } else { "-Inf" }.into()
+    }
+    else {
+        val.to_string()
+    }
+}
+
+// Reads an HTTP request from the stream and sends back the response.
+fn handle_request(stream: TcpStream, gauges: &Registry) -> io::Result<()> {
+    let req = match httpd::read_request(&mut BufReader::new(&stream)) {
+        Ok(req) => req,
+        Err(err) => {
+            let _ =
+                httpd::write_response(&stream, "400 Bad Request", "text/plain", "Bad Request\n");
+            return Err(err);
+        }
+    };
+
+    let (status, body) = match req.method.as_str() {
+        "GET" if req.path() == "/metrics" => ("200 OK", render(gauges)),
+        "GET" => ("404 Not Found", "Not Found\n".to_string()),
+        _ => ("405 Method Not Allowed", "Method Not Allowed\n".to_string()),
+    };
+    httpd::write_response(&stream, status, "text/plain; version=0.0.4", &body)
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+
+    fn gauge(key: usize, value: f64) -> Gauge {
+        Gauge {
+            key,
+            serial_number: 561234,
+            hub_port: key as i32,
+            channel: 0,
+            value,
+        }
+    }
+
+    #[test]
+    fn test_render() {
+        assert!(is_valid_name("temperature_celsius"));
+        assert!(!is_valid_name("2nd_temperature"));
+        assert!(!is_valid_name("temp-c"));
+
+        let exporter = PrometheusExporter::new();
+        exporter.gauges.lock().unwrap().insert(
+            "phidget_temperature".into(),
+            vec![gauge(1, 21.5), gauge(2, f64::NAN)],
+        );
+        assert_eq!(
+            exporter.render(),
+            "# TYPE phidget_temperature gauge\n\
+             phidget_temperature{serial=\"561234\",hub_port=\"1\",channel=\"0\"} 21.5\n\
+             phidget_temperature{serial=\"561234\",hub_port=\"2\",channel=\"0\"} NaN\n"
+        );
+    }
+
+    #[test]
+    fn test_serve() {
+        let exporter = PrometheusExporter::new();
+        exporter
+            .gauges
+            .lock()
+            .unwrap()
+            .insert("phidget_voltage".into(), vec![gauge(1, 2.5)]);
+
+        let server = exporter.serve("127.0.0.1:0").unwrap();
+        let mut stream = TcpStream::connect(server.local_addr()).unwrap();
+        stream
+            .write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .unwrap();
+
+        let mut resp = String::new();
+        stream.read_to_string(&mut resp).unwrap();
+        assert!(resp.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(
+            resp.ends_with("phidget_voltage{serial=\"561234\",hub_port=\"1\",channel=\"0\"} 2.5\n")
+        );
+    }
+}
@@ -0,0 +1,256 @@
+// phidget-rs/src/reconnect.rs
+//
+// This file is part of the 'phidget-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+//! A supervisor that keeps a channel connected.
+//!
+//! When the hardware for an open channel goes away, like when a USB hub
+//! glitches, the phidget22 library detaches the channel and attaches it
+//! again once the device comes back. But the device comes back in its
+//! default state, so any settings like the data interval or the change
+//! trigger are lost.
+//!
+//! The `Reconnector` owns a channel and runs a background thread that
+//! watches it. A setup function is run every time the channel attaches,
+//! to reapply the settings. If the channel is ever found closed, or its
+//! state can't be read, it is re-opened. Event handlers that were set on
+//! the channel stay registered across a detach, and don't need to be set
+//! again.
+//!
+//! ```text
+//! let mut sensor = TemperatureSensor::new();
+//! sensor.set_hub_port(2)?;
+//! let _handler = sensor.set_on_temperature_change_handler(|_, t| {
+//!     println!("Temperature: {}", t);
+//! })?;
+//!
+//! let sensor = Reconnector::new(sensor, |s| {
+//!     s.set_data_interval(Duration::from_millis(500))
+//! })?;
+//! ```
+//!
+
+use crate::{
+    events::{self, DeviceEvent},
+    Error, Phidget, Result,
+};
+use std::{
+    sync::{
+        mpsc::{self, RecvTimeoutError, Sender},
+        Arc, Mutex, MutexGuard,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+/// The default time between checks that the channel is still open.
+pub const RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
+// The messages sent to the supervisor thread
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Msg {
+    Attached,
+    Detached,
+    Stop,
+}
+
+// The state shared with the supervisor thread
+struct Shared<P> {
+    // The channel
+    dev: Mutex<P>,
+    // The last error from the setup function or from re-opening the channel
+    last_error: Mutex<Option<Error>>,
+}
+
+impl<P> Shared<P> {
+    fn dev(&self) -> MutexGuard<'_, P> {
+        self.dev.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    fn set_error(&self, res: Result<()>) {
+        if let Err(err) = res {
+            *self
+                .last_error
+                .lock()
+                .unwrap_or_else(|err| err.into_inner()) = Some(err);
+        }
+    }
+}
+
+/// A supervisor that owns a channel and keeps it connected.
+///
+/// The channel is opened when the supervisor is created, and closed when
+/// it is dropped. The supervisor watches the channel's attach and detach
+/// events without using its handlers, so any that were set on the channel
+/// stay in place.
+pub struct Reconnector<P: Phidget + 'static> {
+    // The channel and the state shared with the thread
+    shared: Arc<Shared<P>>,
+    // Sends messages to the supervisor thread
+    tx: Sender<Msg>,
+    // The supervisor thread
+    thr: Option<JoinHandle<()>>,
+    // The ID of the event listener that feeds the thread
+    tap: u64,
+}
+
+impl<P: Phidget + 'static> Reconnector<P> {
+    /// Takes ownership of the channel, and opens it.
+    ///
+    /// The `setup` function is called every time the channel attaches,
+    /// and should apply any settings that the application needs, like the
+    /// data interval.
+    pub fn new<F>(dev: P, setup: F) -> Result<Self>
+    where
+        F: FnMut(&mut P) -> Result<()> + Send + 'static,
+    {
+        Self::with_retry_interval(dev, RETRY_INTERVAL, setup)
+    }
+
+    /// Takes ownership of the channel, and opens it, checking that it is
+    /// still open at the specified interval.
+    ///
+    /// The `setup` function is called every time the channel attaches,
+    /// and should apply any settings that the application needs, like the
+    /// data interval.
+    pub fn with_retry_interval<F>(mut dev: P, retry: Duration, mut setup: F) -> Result<Self>
+    where
+        F: FnMut(&mut P) -> Result<()> + Send + 'static,
+    {
+        let (tx, rx) = mpsc::channel();
+
+        // Only events from this channel, not others at the same address
+        let phid = dev.as_handle() as usize;
+        let tap = events::add_tap({
+            let tx = tx.clone();
+            move |ev_phid, ev| {
+                if ev_phid as usize == phid {
+                    let msg = match ev {
+                        DeviceEvent::Attached(_) => Msg::Attached,
+                        DeviceEvent::Detached(_) => Msg::Detached,
+                        _ => return,
+                    };
+                    let _ = tx.send(msg);
+                }
+            }
+        });
+
+        let shared = Arc::new(Shared {
+            dev: Mutex::new(dev),
+            last_error: Mutex::new(None),
+        });
+
+        let thr_shared = Arc::clone(&shared);
+        let thr = thread::spawn(move || loop {
+            match rx.recv_timeout(retry) {
+                Ok(Msg::Attached) => {
+                    let res = setup(&mut thr_shared.dev());
+                    thr_shared.set_error(res);
+                }
+                Ok(Msg::Detached) => (),
+                Err(RecvTimeoutError::Timeout) => {
+                    let mut dev = thr_shared.dev();
+                    if !dev.is_open().unwrap_or(false) {
+                        let res = dev.open();
+                        thr_shared.set_error(res);
+                    }
+                }
+                Ok(Msg::Stop) | Err(RecvTimeoutError::Disconnected) => break,
+            }
+        });
+
+        let res = shared.dev().open();
+        let rc = Self {
+            shared,
+            tx,
+            thr: Some(thr),
+            tap,
+        };
+        res.map(|_| rc)
+    }
+
+    /// Locks the channel for access.
+    ///
+    /// The supervisor thread can't run the setup function or re-open the
+    /// channel while it is locked, so the guard should not be held for
+    /// long.
+    pub fn device(&self) -> MutexGuard<'_, P> {
+        self.shared.dev()
+    }
+
+    /// Determines if the channel is currently attached to its device.
+    pub fn is_attached(&self) -> bool {
+        self.device().is_attached().unwrap_or(false)
+    }
+
+    /// Takes the last error that occurred in the background, either from
+    /// the setup function or from re-opening the channel.
+    pub fn take_error(&self) -> Option<Error> {
+        self.shared
+            .last_error
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .take()
+    }
+}
+
+impl<P: Phidget + 'static> Drop for Reconnector<P> {
+    fn drop(&mut self) {
+        events::remove_tap(self.tap);
+        let _ = self.tx.send(Msg::Stop);
+        if let Some(thr) = self.thr.take() {
+            let _ = thr.join();
+        }
+        let _ = self.device().close();
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::*;
+    use crate::{
+        mock::{attach, detach},
+        TemperatureSensor,
+    };
+
+    #[test]
+    fn test_mock_reconnector() {
+        let mut sensor = TemperatureSensor::new();
+        let (user_tx, user_rx) = mpsc::channel();
+        let _handler = sensor
+            .set_on_attach_handler(move |_| {
+                let _ = user_tx.send(());
+            })
+            .unwrap();
+
+        let (tx, rx) = mpsc::channel();
+        let rc = Reconnector::with_retry_interval(sensor, Duration::from_millis(20), move |_| {
+            let _ = tx.send(());
+            Ok(())
+        })
+        .unwrap();
+
+        let timeout = Duration::from_secs(1);
+        assert_eq!(rx.recv_timeout(timeout), Ok(()));
+        assert_eq!(user_rx.recv_timeout(timeout), Ok(()));
+
+        // The setup runs again on a re-attach, and the handler is kept
+        detach(&mut *rc.device());
+        attach(&mut *rc.device());
+        assert_eq!(rx.recv_timeout(timeout), Ok(()));
+        assert_eq!(user_rx.recv_timeout(timeout), Ok(()));
+
+        // A closed channel is re-opened
+        rc.device().close().unwrap();
+        assert_eq!(rx.recv_timeout(timeout), Ok(()));
+        assert!(rc.is_attached());
+        assert!(rc.take_error().is_none());
+    }
+}
@@ -0,0 +1,413 @@
+// phidget-rs/src/record.rs
+//
+// This file is part of the 'phidget-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+//! Recording the events from channels, and replaying them.
+//!
+//! A `Recorder` captures the value changes of any number of sensors,
+//! along with the attach, detach, and error events of their channels, into
+//! a compact file, with the time of each event. This can be left running
+//! in the field to capture the conditions that lead to a problem:
+//!
+//! ```text
+//! let mut rec = Recorder::create("greenhouse.rec")?;
+//! rec.add(&mut temp_sensor)?;
+//! rec.add(&mut hum_sensor)?;
+//! ```
+//!
+//! With the `mock` feature, `replay()` feeds a recording back to simulated
+//! channels, on a developer's machine. The events are delivered through
+//! the channels' normal handlers and streams, with the original timing, so
+//! the application code sees them just as it did in the field:
+//!
+//! ```text
+//! let records = phidget::record::load("greenhouse.rec")?;
+//! phidget::record::replay(&records, &mut [&mut temp_sensor, &mut hum_sensor], 1.0)?;
+//! ```
+//!
+//! The file is text, with a header line, and then one line per event:
+//!
+//! ```text
+//! phidget-record 1
+//! 0.250361 v 28 561234 2 0 21.5
+//! 3.102948 e 28 561234 2 0 4103 Temperature out of range
+//! ```
+//!
+//! The fields are the time in seconds since the start of the recording,
+//! the kind of event (`v`alue, `a`ttach, `d`etach, or `e`rror), the
+//! channel class and address, and then the value or the error code and
+//! description.
+//!
+
+use crate::{ChannelClass, DeviceEvent, ErrorEventCode, EventSource, HandlerGuard, Result, Sensor};
+use phidget_sys::PhidgetHandle;
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::Path,
+    sync::{
+        mpsc::{self, Sender},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+// The first line of a recording, with the version of the format
+const HEADER: &str = "phidget-record 1";
+
+/// An event captured in a recording.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RecordedEvent {
+    /// A change in the value of a sensor
+    Value(f64),
+    /// The channel attached to a device
+    Attached,
+    /// The channel detached from its device
+    Detached,
+    /// The channel reported an error, with a description of it
+    Error(ErrorEventCode, String),
+}
+
+/// A single timestamped event in a recording.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Record {
+    /// The time of the event, since the start of the recording
+    pub time: Duration,
+    /// The channel that reported the event
+    pub source: EventSource,
+    /// The event
+    pub event: RecordedEvent,
+}
+
+impl Record {
+    // Writes the record as a line of the file.
+    fn write_to<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let src = &self.source;
+        write!(
+            out,
+            "{:.6} {} {} {} {} {}",
+            self.time.as_secs_f64(),
+            match self.event {
+                RecordedEvent::Value(_) => 'v',
+                RecordedEvent::Attached => 'a',
+                RecordedEvent::Detached => 'd',
+                RecordedEvent::Error(..) => 'e',
+            },
+            src.channel_class as u32,
+            src.serial_number,
+            src.hub_port,
+            src.channel
+        )?;
+        match &self.event {
+            RecordedEvent::Value(val) => writeln!(out, " {}", val),
+            RecordedEvent::Error(code, descr) => {
+                writeln!(out, " {} {}", *code as u32, descr.replace('\n', " "))
+            }
+            _ => writeln!(out),
+        }
+    }
+
+    // Parses a line of the file.
+    fn parse(line: &str) -> Option<Self> {
+        let mut fields = line.splitn(7, ' ');
+        let time = Duration::from_secs_f64(fields.next()?.parse().ok()?);
+        let kind = fields.next()?;
+        let source = EventSource {
+            channel_class: ChannelClass::try_from(fields.next()?.parse::<u32>().ok()?).ok()?,
+            serial_number: fields.next()?.parse().ok()?,
+            hub_port: fields.next()?.parse().ok()?,
+            channel: fields.next()?.parse().ok()?,
+        };
+        let rest = fields.next().unwrap_or("");
+
+        let event = match kind {
+            "v" => RecordedEvent::Value(rest.parse().ok()?),
+            "a" => RecordedEvent::Attached,
+            "d" => RecordedEvent::Detached,
+            "e" => {
+                let (code, descr) = rest.split_once(' ').unwrap_or((rest, ""));
+                let code = ErrorEventCode::from(code.parse::<u32>().ok()?);
+                RecordedEvent::Error(code, descr.into())
+            }
+            _ => return None,
+        };
+        Some(Self {
+            time,
+            source,
+            event,
+        })
+    }
+}
+
+// Determines if an event source is one of the selected channels.
+// A part of a selected address that is not set (-1) matches any value.
+fn is_selected(sel: &[EventSource], src: &EventSource) -> bool {
+    sel.iter().any(|s| {
+        s.channel_class == src.channel_class
+            && (s.serial_number < 0 || s.serial_number == src.serial_number)
+            && (s.hub_port < 0 || s.hub_port == src.hub_port)
+            && (s.channel < 0 || s.channel == src.channel)
+    })
+}
+
+/// A recorder of the events from a set of channels.
+///
+/// The file is written by a background thread. The sensor handlers are
+/// removed and the file is flushed when the recorder is dropped.
+pub struct Recorder {
+    // The time at which the recording started
+    start: Instant,
+    // Sends the records to the writer thread
+    tx: Option<Sender<Record>>,
+    // The addresses of the channels that were added
+    selected: Arc<Mutex<Vec<EventSource>>>,
+    // The ID of the listener for the device events
+    tap: u64,
+    // The writer thread
+    thr: Option<JoinHandle<io::Result<()>>>,
+    // The handlers feeding the recorder
+    guards: Vec<HandlerGuard>,
+}
+
+impl Recorder {
+    /// Creates the recording file, and starts recording.
+    ///
+    /// This replaces the file if it already exists.
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let mut out = BufWriter::new(File::create(path)?);
+        writeln!(out, "{}", HEADER)?;
+        out.flush()?;
+
+        let start = Instant::now();
+        let (tx, rx) = mpsc::channel::<Record>();
+
+        let thr = thread::spawn(move || {
+            while let Ok(rec) = rx.recv() {
+                rec.write_to(&mut out)?;
+                for rec in rx.try_iter() {
+                    rec.write_to(&mut out)?;
+                }
+                out.flush()?;
+            }
+            Ok(())
+        });
+
+        // Records the device events of the selected channels
+        let selected = Arc::new(Mutex::new(Vec::new()));
+        let tap = crate::events::add_tap({
+            let (tx, selected) = (tx.clone(), Arc::clone(&selected));
+            move |_, ev| {
+                let time = start.elapsed();
+                let (source, event) = match ev {
+                    DeviceEvent::Attached(src) => (*src, RecordedEvent::Attached),
+                    DeviceEvent::Detached(src) => (*src, RecordedEvent::Detached),
+                    DeviceEvent::Error(src, code, descr) => {
+                        (*src, RecordedEvent::Error(*code, descr.clone()))
+                    }
+                };
+                let sel = selected.lock().unwrap_or_else(|err| err.into_inner());
+                if is_selected(&sel, &source) {
+                    let _ = tx.send(Record {
+                        time,
+                        source,
+                        event,
+                    });
+                }
+            }
+        });
+
+        Ok(Self {
+            start,
+            tx: Some(tx),
+            selected,
+            tap,
+            thr: Some(thr),
+            guards: Vec::new(),
+        })
+    }
+
+    /// Records the value changes of the sensor, and the attach, detach,
+    /// and error events of its channel.
+    ///
+    /// The channel is selected by its address, as set when it's added.
+    /// Any part of the address that is not set matches any device.
+    /// This sets the sensor's change handler, replacing any handler that
+    /// was previously set. Boolean values are recorded as 0 or 1.
+    pub fn add<S>(&mut self, sensor: &mut S) -> Result<()>
+    where
+        S: Sensor,
+        S::Value: Into<f64>,
+    {
+        let Some(tx) = self.tx.clone()
+        else {
+            return Ok(());
+        };
+        let phid = sensor.as_handle() as usize;
+        self.selected
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .push(EventSource::from_handle(phid as PhidgetHandle));
+
+        let start = self.start;
+        let guard = sensor.on_change(move |_, val| {
+            let _ = tx.send(Record {
+                time: start.elapsed(),
+                source: EventSource::from_handle(phid as PhidgetHandle),
+                event: RecordedEvent::Value(val.into()),
+            });
+        })?;
+        self.guards.push(guard);
+        Ok(())
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        self.guards.clear();
+        crate::events::remove_tap(self.tap);
+        self.tx = None;
+        if let Some(thr) = self.thr.take() {
+            let _ = thr.join();
+        }
+    }
+}
+
+/// Loads the events from a recording file.
+///
+/// Lines that can't be parsed, like one cut short when the recording
+/// stopped, are skipped.
+pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Vec<Record>> {
+    let mut lines = BufReader::new(File::open(path)?).lines();
+
+    match lines.next() {
+        Some(Ok(line)) if line.trim_end() == HEADER => {}
+        Some(Err(err)) => return Err(err),
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a phidget recording",
+            ))
+        }
+    }
+
+    let mut recs = Vec::new();
+    for line in lines {
+        if let Some(rec) = Record::parse(&line?) {
+            recs.push(rec);
+        }
+    }
+    Ok(recs)
+}
+
+/// Replays a recording to simulated channels.
+///
+/// Each event is fed to the channel of the same class and address, or, if
+/// there is no such channel, to the only channel of the same class. Events
+/// for any other channels are skipped. The channels should be open.
+///
+/// The events are delivered at their recorded times, scaled by the speed:
+/// 1.0 replays in real time, 2.0 twice as fast, and so on. A speed of zero
+/// replays the events without any delay.
+#[cfg(feature = "mock")]
+pub fn replay(recs: &[Record], chans: &mut [&mut dyn crate::Phidget], speed: f64) -> Result<()> {
+    use crate::mock;
+
+    let mut sources: Vec<_> = chans
+        .iter_mut()
+        .map(|ch| EventSource::from_handle(ch.as_handle()))
+        .collect();
+
+    let start = Instant::now();
+    for rec in recs {
+        let src = &rec.source;
+        let i = match sources.iter().position(|s| s == src) {
+            Some(i) => i,
+            None => {
+                let mut same = sources
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, s)| s.channel_class == src.channel_class);
+                match (same.next(), same.next()) {
+                    (Some((i, _)), None) => i,
+                    _ => continue,
+                }
+            }
+        };
+
+        if speed > 0.0 {
+            let at = rec.time.div_f64(speed);
+            if let Some(wait) = at.checked_sub(start.elapsed()) {
+                thread::sleep(wait);
+            }
+        }
+
+        let ch = &mut *chans[i];
+        match &rec.event {
+            RecordedEvent::Value(val) => mock::set_value(ch, *val)?,
+            RecordedEvent::Attached => mock::attach(ch),
+            RecordedEvent::Detached => mock::detach(ch),
+            RecordedEvent::Error(code, descr) => mock::raise_error(ch, *code, descr),
+        }
+        sources[i] = EventSource::from_handle(ch.as_handle());
+    }
+    Ok(())
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::*;
+    use crate::{
+        mock::{raise_error, set_value},
+        Phidget, TemperatureSensor,
+    };
+
+    #[test]
+    fn test_mock_record_replay() {
+        let path = std::env::temp_dir().join(format!("phidget-{}.rec", std::process::id()));
+
+        let mut sensor = TemperatureSensor::new();
+        sensor.set_serial_number(637_001).unwrap();
+        let mut rec = Recorder::create(&path).unwrap();
+        rec.add(&mut sensor).unwrap();
+
+        sensor.open().unwrap();
+        set_value(&mut sensor, 21.5).unwrap();
+        raise_error(&mut sensor, ErrorEventCode::OutOfRange, "too hot");
+        drop(rec);
+
+        let recs = load(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        let events: Vec<_> = recs.iter().map(|r| r.event.clone()).collect();
+        assert_eq!(
+            events,
+            [
+                RecordedEvent::Attached,
+                RecordedEvent::Value(21.5),
+                RecordedEvent::Error(ErrorEventCode::OutOfRange, "too hot".into()),
+            ]
+        );
+        assert!(recs.iter().all(|r| r.source.serial_number == 637_001));
+
+        // Replay to another channel, through its normal handler
+        let mut replayed = TemperatureSensor::new();
+        replayed.set_serial_number(637_002).unwrap();
+        replayed.open().unwrap();
+        let (tx, rx) = mpsc::channel();
+        let _handler = replayed
+            .set_on_temperature_change_handler(move |_, t| tx.send(t).unwrap())
+            .unwrap();
+
+        replay(&recs, &mut [&mut replayed], 0.0).unwrap();
+        assert_eq!(rx.try_recv(), Ok(21.5));
+    }
+}
@@ -0,0 +1,212 @@
+// phidget-rs/src/sampler.rs
+//
+// This file is part of the 'phidget-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+//! A polling scheduler for a set of sensors.
+//!
+//! Some sensors report change events too coarsely for an application, or
+//! an application wants readings at fixed times. A `Sampler` holds any
+//! number of sensors, each with its own sampling interval, and polls all
+//! of them from a single background thread. The readings are sent as
+//! timestamped `Sample` records over a channel:
+//!
+//! ```text
+//! let mut sampler = Sampler::new();
+//! sampler.add("outside", outside_temp, Duration::from_secs(1));
+//! sampler.add("door", door_switch, Duration::from_millis(100));
+//!
+//! let samples = sampler.start();
+//! for sample in samples.iter() {
+//!     println!("{:?}", sample);
+//! }
+//! ```
+//!
+
+use crate::{Result, Sensor};
+use std::{
+    ops::Deref,
+    sync::mpsc::{self, Receiver, RecvTimeoutError, Sender},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant, SystemTime},
+};
+
+/// A timestamped reading from one of the sensors in a `Sampler`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Sample {
+    /// The name of the sensor, as given when it was added
+    pub name: String,
+    /// The time at which the sensor was read
+    pub time: SystemTime,
+    /// The value that was read, or the error if the read failed
+    pub value: Result<f64>,
+}
+
+// A function that reads a value from a sensor
+type ReadFn = dyn FnMut() -> Result<f64> + Send + 'static;
+
+// A sensor in the sampler
+struct Entry {
+    // The name of the sensor
+    name: String,
+    // The time between readings
+    interval: Duration,
+    // Reads the sensor
+    read: Box<ReadFn>,
+}
+
+/// A set of sensors that are polled at individual rates by a single
+/// background thread.
+#[derive(Default)]
+pub struct Sampler {
+    entries: Vec<Entry>,
+}
+
+impl Sampler {
+    /// Creates a new sampler with no sensors.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a sensor to be read at the specified interval.
+    ///
+    /// The sampler takes ownership of the sensor, which should already be
+    /// open. Boolean values are reported as 0.0 or 1.0.
+    pub fn add<S>(&mut self, name: &str, sensor: S, interval: Duration) -> &mut Self
+    where
+        S: Sensor + 'static,
+        S::Value: Into<f64>,
+    {
+        self.add_fn(name, interval, move || sensor.value().map(Into::into))
+    }
+
+    // Adds a function to be called at the specified interval.
+    fn add_fn<F>(&mut self, name: &str, interval: Duration, read: F) -> &mut Self
+    where
+        F: FnMut() -> Result<f64> + Send + 'static,
+    {
+        self.entries.push(Entry {
+            name: name.into(),
+            interval,
+            read: Box::new(read),
+        });
+        self
+    }
+
+    /// Starts polling the sensors in a background thread.
+    ///
+    /// Each sensor is read right away, and then at its interval. The
+    /// returned receiver gets the samples. The thread is stopped, and the
+    /// sensors are dropped, when the receiver is dropped.
+    pub fn start(self) -> SampleReceiver {
+        let (tx, rx) = mpsc::channel();
+        let (stop_tx, stop_rx) = mpsc::channel();
+        let thr = thread::spawn(move || self.run(tx, stop_rx));
+
+        SampleReceiver {
+            rx,
+            stop_tx,
+            thr: Some(thr),
+        }
+    }
+
+    // The polling loop for the background thread.
+    fn run(mut self, tx: Sender<Sample>, stop_rx: Receiver<()>) {
+        let start = Instant::now();
+        let mut due = vec![start; self.entries.len()];
+
+        loop {
+            let now = Instant::now();
+            for (entry, due) in self.entries.iter_mut().zip(due.iter_mut()) {
+                if *due <= now {
+                    let sample = Sample {
+                        name: entry.name.clone(),
+                        time: SystemTime::now(),
+                        value: (entry.read)(),
+                    };
+                    if tx.send(sample).is_err() {
+                        return;
+                    }
+                    // Keep to the schedule, but skip any missed readings
+                    while *due <= now {
+                        *due += entry.interval.max(Duration::from_millis(1));
+                    }
+                }
+            }
+
+            let Some(next) = due.iter().min()
+            else {
+                return;
+            };
+            match stop_rx.recv_timeout(next.saturating_duration_since(Instant::now())) {
+                Err(RecvTimeoutError::Timeout) => (),
+                _ => return,
+            }
+        }
+    }
+}
+
+/// A receiver for the samples from a running `Sampler`.
+///
+/// This dereferences to a `std::sync::mpsc::Receiver` for reading the
+/// samples. The sampler thread is stopped when it is dropped.
+pub struct SampleReceiver {
+    rx: Receiver<Sample>,
+    stop_tx: Sender<()>,
+    thr: Option<JoinHandle<()>>,
+}
+
+impl Deref for SampleReceiver {
+    type Target = Receiver<Sample>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.rx
+    }
+}
+
+impl Drop for SampleReceiver {
+    fn drop(&mut self) {
+        let _ = self.stop_tx.send(());
+        if let Some(thr) = self.thr.take() {
+            let _ = thr.join();
+        }
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sampler() {
+        let mut sampler = Sampler::new();
+        let mut n = 0.0;
+        sampler
+            .add_fn("fast", Duration::from_millis(10), move || {
+                n += 1.0;
+                Ok(n)
+            })
+            .add_fn("slow", Duration::from_secs(60), || Ok(-1.0));
+
+        let rx = sampler.start();
+
+        // Both are read right away
+        let mut names = vec![rx.recv().unwrap().name, rx.recv().unwrap().name];
+        names.sort();
+        assert_eq!(names, vec!["fast", "slow"]);
+
+        // Then only the fast one
+        for i in 2..5 {
+            let sample = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+            assert_eq!(sample.name, "fast");
+            assert_eq!(sample.value, Ok(i as f64));
+        }
+    }
+}
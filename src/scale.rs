@@ -0,0 +1,471 @@
+// phidget-rs/src/scale.rs
+//
+// This file is part of the 'phidget-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+//! A scale, from a load cell on a voltage ratio input.
+//!
+//! A load cell on a bridge input, like the DAQ1500, reports a voltage
+//! ratio that's proportional to the load. The `Scale` turns that into a
+//! weight, with a tare, a two-point calibration from known weights, and a
+//! choice of units. It also reports when the weight settles, with the
+//! readings staying within a tolerance for a time, which is when a
+//! weighing is usually taken:
+//!
+//! ```text
+//! let mut input = VoltageRatioInput::new();
+//! input.open_wait_default()?;
+//! input.set_bridge_gain(BridgeGain::Gain128)?;
+//!
+//! let mut scale = Scale::new(input)?;
+//!
+//! // With the scale empty, then with a 1 kg weight on it
+//! let empty = scale.ratio()?;
+//! let loaded = scale.ratio()?;
+//! scale.calibrate((empty, 0.0), (loaded, 1.0))?;
+//!
+//! scale.set_unit(WeightUnit::Grams);
+//! scale.set_stability(2.0, Duration::from_millis(500));
+//! scale.tare()?;
+//!
+//! for ev in scale.iter() {
+//!     println!("{:.1} {}", ev.weight, ev.unit.symbol());
+//! }
+//! ```
+//!
+//! The calibration can be saved, with `calibration()`, and restored with
+//! `set_calibration()`, so that it only needs to be done once for each
+//! load cell.
+//!
+
+use crate::{devices::VoltageRatioInput, Error, HandlerGuard, Result, Sensor};
+use std::{
+    ops::Deref,
+    sync::{
+        mpsc::{self, Receiver},
+        Arc, Mutex, MutexGuard,
+    },
+    time::{Duration, Instant, SystemTime},
+};
+
+/// The default tolerance for a stable weight, in kilograms.
+pub const DEFAULT_TOLERANCE: f64 = 0.005;
+
+/// The default time that the weight needs to hold within the tolerance
+/// to be stable.
+pub const DEFAULT_SETTLE_TIME: Duration = Duration::from_millis(500);
+
+/// A unit of weight.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum WeightUnit {
+    /// Kilograms (kg)
+    #[default]
+    Kilograms,
+    /// Grams (g)
+    Grams,
+    /// Pounds (lb)
+    Pounds,
+    /// Ounces (oz)
+    Ounces,
+}
+
+impl WeightUnit {
+    /// Gets the number of kilograms in one of the unit.
+    pub fn kilograms(&self) -> f64 {
+        use WeightUnit::*;
+        match *self {
+            Kilograms => 1.0,
+            Grams => 0.001,
+            Pounds => 0.453_592_37,
+            Ounces => 0.028_349_523_125,
+        }
+    }
+
+    /// Converts a weight in kilograms to this unit.
+    pub fn from_kg(&self, kg: f64) -> f64 {
+        kg / self.kilograms()
+    }
+
+    /// Converts a weight in this unit to kilograms.
+    pub fn to_kg(&self, val: f64) -> f64 {
+        val * self.kilograms()
+    }
+
+    /// Gets the symbol for the unit, like "kg".
+    pub fn symbol(&self) -> &'static str {
+        use WeightUnit::*;
+        match *self {
+            Kilograms => "kg",
+            Grams => "g",
+            Pounds => "lb",
+            Ounces => "oz",
+        }
+    }
+}
+
+/// The calibration of a scale.
+///
+/// The weight, in kilograms, is `(ratio - zero) * gain`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ScaleCalibration {
+    /// The voltage ratio with the scale empty, or tared
+    pub zero: f64,
+    /// The kilograms per unit of voltage ratio
+    pub gain: f64,
+}
+
+impl ScaleCalibration {
+    /// Creates a calibration from two points, each a voltage ratio and the
+    /// weight, in kilograms, that gave it.
+    ///
+    /// This fails if the two ratios are the same.
+    pub fn from_points(p1: (f64, f64), p2: (f64, f64)) -> Result<Self> {
+        let ((r1, w1), (r2, w2)) = (p1, p2);
+        let gain = (w2 - w1) / (r2 - r1);
+        if !gain.is_finite() || gain == 0.0 {
+            return Err(Error::InvalidArg);
+        }
+        Ok(Self {
+            zero: r1 - w1 / gain,
+            gain,
+        })
+    }
+
+    /// Converts a voltage ratio to a weight, in kilograms.
+    pub fn apply(&self, ratio: f64) -> f64 {
+        (ratio - self.zero) * self.gain
+    }
+}
+
+impl Default for ScaleCalibration {
+    /// The identity calibration, which reads the ratio as the weight.
+    fn default() -> Self {
+        Self {
+            zero: 0.0,
+            gain: 1.0,
+        }
+    }
+}
+
+/// A stable weight from a scale.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StableWeight {
+    /// The mean of the readings while the weight settled
+    pub weight: f64,
+    /// The unit of the weight
+    pub unit: WeightUnit,
+    /// The time the weight was seen to be stable
+    pub time: SystemTime,
+}
+
+// Tracks whether the readings have settled.
+struct Settle {
+    // The most that the readings can vary, in kilograms
+    tolerance: f64,
+    // The time the readings need to stay within the tolerance
+    window: Duration,
+    // The start of the current run of readings within the tolerance
+    since: Option<Instant>,
+    // The lowest, highest, and sum of the readings in the run
+    lo: f64,
+    hi: f64,
+    sum: f64,
+    n: u32,
+    // Whether the run was already reported as stable
+    stable: bool,
+}
+
+impl Settle {
+    fn new(tolerance: f64, window: Duration) -> Self {
+        Self {
+            tolerance,
+            window,
+            since: None,
+            lo: 0.0,
+            hi: 0.0,
+            sum: 0.0,
+            n: 0,
+            stable: false,
+        }
+    }
+
+    // Forgets the current run.
+    fn reset(&mut self) {
+        self.since = None;
+        self.stable = false;
+    }
+
+    // Updates with a reading, in kilograms, returning the mean of the run
+    // when it first becomes stable.
+    fn update(&mut self, t: Instant, kg: f64) -> Option<f64> {
+        let within = self.since.is_some() && self.hi.max(kg) - self.lo.min(kg) <= self.tolerance;
+
+        if within {
+            self.lo = self.lo.min(kg);
+            self.hi = self.hi.max(kg);
+            self.sum += kg;
+            self.n += 1;
+        }
+        else {
+            // A reading out of the band starts a new run from itself
+            self.since = Some(t);
+            self.lo = kg;
+            self.hi = kg;
+            self.sum = kg;
+            self.n = 1;
+            self.stable = false;
+        }
+
+        let since = self.since?;
+        if !self.stable && t.saturating_duration_since(since) >= self.window {
+            self.stable = true;
+            return Some(self.sum / f64::from(self.n));
+        }
+        None
+    }
+}
+
+// The state shared with the change handler
+struct State {
+    cal: ScaleCalibration,
+    unit: WeightUnit,
+    settle: Settle,
+}
+
+type Shared = Arc<Mutex<State>>;
+
+// Locks the state.
+fn lock(state: &Shared) -> MutexGuard<'_, State> {
+    state.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+/// A scale, from a load cell on a voltage ratio input.
+///
+/// This dereferences to a `std::sync::mpsc::Receiver` for reading the
+/// stable weight events. A weight is reported once each time it settles,
+/// and not again until the readings move out of the tolerance.
+pub struct Scale {
+    // The input, which has the change handler set
+    input: VoltageRatioInput,
+    // The calibration, unit, and settling state
+    state: Shared,
+    // Receives the stable weight events
+    rx: Receiver<StableWeight>,
+    // The change handler feeding the stable weight events
+    _guard: HandlerGuard,
+}
+
+impl Scale {
+    /// Creates a scale on a voltage ratio input.
+    ///
+    /// The input should be open, with the bridge enabled and its gain set
+    /// for the load cell. This sets its change handler, replacing any
+    /// handler that was previously set. The scale starts with the identity
+    /// calibration, in kilograms, so it needs to be calibrated, or have a
+    /// saved calibration set, to read a weight.
+    pub fn new(input: VoltageRatioInput) -> Result<Self> {
+        let state: Shared = Arc::new(Mutex::new(State {
+            cal: ScaleCalibration::default(),
+            unit: WeightUnit::default(),
+            settle: Settle::new(DEFAULT_TOLERANCE, DEFAULT_SETTLE_TIME),
+        }));
+        let (tx, rx) = mpsc::channel();
+
+        let guard = input.on_change({
+            let state = Arc::clone(&state);
+            move |_, ratio| {
+                let mut st = lock(&state);
+                let kg = st.cal.apply(ratio);
+                if let Some(mean) = st.settle.update(Instant::now(), kg) {
+                    let _ = tx.send(StableWeight {
+                        weight: st.unit.from_kg(mean),
+                        unit: st.unit,
+                        time: SystemTime::now(),
+                    });
+                }
+            }
+        })?;
+
+        Ok(Self {
+            input,
+            state,
+            rx,
+            _guard: guard,
+        })
+    }
+
+    /// Gets a reference to the voltage ratio input.
+    pub fn input(&self) -> &VoltageRatioInput {
+        &self.input
+    }
+
+    /// Gets a mutable reference to the voltage ratio input.
+    ///
+    /// Replacing its change handler stops the stable weight events.
+    pub fn input_mut(&mut self) -> &mut VoltageRatioInput {
+        &mut self.input
+    }
+
+    /// Reads the current voltage ratio of the load cell, without the
+    /// calibration.
+    pub fn ratio(&self) -> Result<f64> {
+        self.input.voltage_ratio()
+    }
+
+    /// Reads the current weight, in the selected unit.
+    pub fn weight(&self) -> Result<f64> {
+        let ratio = self.ratio()?;
+        let st = lock(&self.state);
+        Ok(st.unit.from_kg(st.cal.apply(ratio)))
+    }
+
+    /// Sets the current load as zero.
+    ///
+    /// This keeps the gain of the calibration, and only moves its zero.
+    pub fn tare(&mut self) -> Result<()> {
+        let ratio = self.ratio()?;
+        let mut st = lock(&self.state);
+        st.cal.zero = ratio;
+        st.settle.reset();
+        Ok(())
+    }
+
+    /// Calibrates the scale from two points, each a voltage ratio and the
+    /// known weight, in the selected unit, that gave it.
+    ///
+    /// The points are usually the empty scale, with a weight of zero, and
+    /// a known weight near the top of the range. This fails if the two
+    /// ratios are the same.
+    pub fn calibrate(&mut self, p1: (f64, f64), p2: (f64, f64)) -> Result<()> {
+        let mut st = lock(&self.state);
+        let unit = st.unit;
+        st.cal = ScaleCalibration::from_points((p1.0, unit.to_kg(p1.1)), (p2.0, unit.to_kg(p2.1)))?;
+        st.settle.reset();
+        Ok(())
+    }
+
+    /// Gets the current calibration.
+    pub fn calibration(&self) -> ScaleCalibration {
+        lock(&self.state).cal
+    }
+
+    /// Sets the calibration, like one that was saved from an earlier
+    /// `calibrate()`.
+    pub fn set_calibration(&mut self, cal: ScaleCalibration) {
+        let mut st = lock(&self.state);
+        st.cal = cal;
+        st.settle.reset();
+    }
+
+    /// Gets the unit of the weights.
+    pub fn unit(&self) -> WeightUnit {
+        lock(&self.state).unit
+    }
+
+    /// Sets the unit of the weights.
+    pub fn set_unit(&mut self, unit: WeightUnit) {
+        lock(&self.state).unit = unit;
+    }
+
+    /// Sets how the weight is judged to be stable: the readings need to
+    /// stay within the tolerance, in the selected unit, for the time.
+    ///
+    /// The tolerance is kept as a weight, so it doesn't change if the unit
+    /// is changed later.
+    pub fn set_stability(&mut self, tolerance: f64, settle_time: Duration) {
+        let mut st = lock(&self.state);
+        let tolerance = st.unit.to_kg(tolerance).abs();
+        st.settle = Settle::new(tolerance, settle_time);
+    }
+}
+
+impl Deref for Scale {
+    type Target = Receiver<StableWeight>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.rx
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_calibration() {
+        let cal = ScaleCalibration::from_points((0.0001, 0.0), (0.0003, 1.0)).unwrap();
+        assert!((cal.apply(0.0002) - 0.5).abs() < 1e-9);
+        assert!(ScaleCalibration::from_points((0.1, 0.0), (0.1, 1.0)).is_err());
+
+        let lb = WeightUnit::Pounds;
+        assert!((lb.to_kg(lb.from_kg(2.5)) - 2.5).abs() < 1e-12);
+        assert!((WeightUnit::Grams.from_kg(1.5) - 1500.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_settle() {
+        let mut s = Settle::new(0.01, Duration::from_millis(100));
+        let t0 = Instant::now();
+        let ms = Duration::from_millis;
+
+        // Readings that move too much restart the settling time
+        assert_eq!(s.update(t0, 1.0), None);
+        assert_eq!(s.update(t0 + ms(60), 1.5), None);
+        assert_eq!(s.update(t0 + ms(120), 1.505), None);
+        let mean = s.update(t0 + ms(160), 1.5).unwrap();
+        assert!((mean - 1.501_666_7).abs() < 1e-6);
+
+        // Reported once, until the weight moves again
+        assert_eq!(s.update(t0 + ms(200), 1.502), None);
+        assert_eq!(s.update(t0 + ms(250), 2.0), None);
+        assert_eq!(s.update(t0 + ms(350), 2.0), Some(2.0));
+    }
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod mock_tests {
+    use super::*;
+    use crate::{mock::set_value, Phidget};
+
+    #[test]
+    fn test_mock_scale() {
+        use std::thread;
+
+        let mut input = VoltageRatioInput::new();
+        input.open().unwrap();
+        set_value(&mut input, 0.0001).unwrap();
+
+        let mut scale = Scale::new(input).unwrap();
+        scale.set_unit(WeightUnit::Grams);
+        scale.calibrate((0.0001, 0.0), (0.0005, 1000.0)).unwrap();
+        assert!(scale.weight().unwrap().abs() < 1e-6);
+
+        set_value(scale.input_mut(), 0.0003).unwrap();
+        assert!((scale.weight().unwrap() - 500.0).abs() < 1e-6);
+
+        // Tare with a container on the scale
+        scale.tare().unwrap();
+        assert!(scale.weight().unwrap().abs() < 1e-6);
+        assert!((scale.calibration().gain - 2500.0).abs() < 1e-6);
+
+        // The weight settles after holding within the tolerance
+        scale.set_stability(1.0, Duration::from_millis(30));
+        set_value(scale.input_mut(), 0.0004).unwrap();
+        thread::sleep(Duration::from_millis(40));
+        set_value(scale.input_mut(), 0.0004).unwrap();
+
+        let ev = scale.try_recv().unwrap();
+        assert_eq!(ev.unit, WeightUnit::Grams);
+        assert!((ev.weight - 250.0).abs() < 1e-6);
+        assert!(scale.try_recv().is_err());
+    }
+}
@@ -0,0 +1,113 @@
+// phidget-rs/src/sensor.rs
+//
+// This file is part of the 'phidget-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+//! A common interface for the single-value sensors.
+//!
+//! Sensors like temperature, humidity, and voltage inputs each report a
+//! single value, and have a single change event. The `Sensor` trait
+//! exposes them with the same interface so that they can be treated
+//! polymorphically, such as by dashboards and data loggers:
+//!
+//! ```text
+//! fn log_changes<S: Sensor<Value = f64>>(sensor: &S) -> Result<HandlerGuard> {
+//!     sensor.on_change(|_, val| println!("{}", val))
+//! }
+//! ```
+//!
+
+use crate::{EventReceiver, HandlerGuard, KnownValue, Phidget, Result, Timestamped};
+use std::sync::mpsc;
+
+#[cfg(feature = "async")]
+use crate::stream::EventStream;
+
+/// A sensor channel that reports a single value.
+pub trait Sensor: Phidget {
+    /// The type of the value reported by the sensor.
+    type Value: Copy + Send + 'static;
+
+    /// Reads the current value of the sensor.
+    fn value(&self) -> Result<Self::Value>;
+
+    /// Reads the current value of the sensor, or `None` if no sample has
+    /// arrived since the channel attached.
+    fn known_value(&self) -> Result<Option<Self::Value>> {
+        self.value().known()
+    }
+
+    /// Sets a handler to receive the sensor's value change events.
+    ///
+    /// This sets the same handler as the sensor-specific setter, like
+    /// `set_on_temperature_change_handler()`, and replaces any handler
+    /// that was previously set through either one.
+    /// The handler is removed when the returned guard is dropped.
+    fn on_change<F>(&self, cb: F) -> Result<HandlerGuard>
+    where
+        Self: Sized,
+        F: FnMut(&Self, Self::Value) + Send + 'static;
+
+    /// Gets a channel that receives the sensor's readings, each with the
+    /// time at which it arrived, fed by the change events.
+    ///
+    /// This sets the sensor's change handler, replacing any handler that
+    /// was previously set.
+    /// The handler is removed when the receiver is dropped.
+    fn timestamped_channel(&self) -> Result<EventReceiver<Timestamped<Self::Value>>>
+    where
+        Self: Sized,
+    {
+        let (tx, rx) = mpsc::channel();
+        let guard = self.on_change(move |_, val| {
+            let _ = tx.send(Timestamped::now(val));
+        })?;
+        Ok(EventReceiver::new(rx, guard))
+    }
+
+    /// Gets a stream of the sensor's readings, each with the time at
+    /// which it arrived, fed by the change events.
+    ///
+    /// This sets the sensor's change handler, replacing any handler that
+    /// was previously set.
+    /// The handler is removed when the stream is dropped.
+    #[cfg(feature = "async")]
+    fn timestamped_stream(&self) -> Result<EventStream<Timestamped<Self::Value>>>
+    where
+        Self: Sized,
+    {
+        let (mut tx, rx) = crate::stream::event_channel();
+        let guard = self.on_change(move |_, val| tx.send(Timestamped::now(val)))?;
+        Ok(EventStream::new(rx, guard))
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::*;
+    use crate::{mock::set_value, TemperatureSensor};
+
+    #[test]
+    fn test_mock_timestamped() {
+        let mut sensor = TemperatureSensor::new();
+        sensor.set_serial_number(642_001).unwrap();
+        sensor.open().unwrap();
+
+        let before = std::time::Instant::now();
+        let rx = sensor.timestamped_channel().unwrap();
+        set_value(&mut sensor, 21.5).unwrap();
+        set_value(&mut sensor, 22.0).unwrap();
+
+        let readings: Vec<_> = rx.try_iter().collect();
+        assert_eq!(readings.len(), 2);
+        assert_eq!(readings[0].value, 21.5);
+        assert!(readings[0].instant >= before);
+        assert!(readings[1].instant >= readings[0].instant);
+    }
+}
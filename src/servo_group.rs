@@ -0,0 +1,258 @@
+// phidget-rs/src/servo_group.rs
+//
+// This file is part of the 'phidget-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+//! Coordinated control of a group of RC servos.
+//!
+//! The joints of a robot arm, or the legs of a walker, are driven by
+//! several servos that have to move together. A `ServoGroup` owns the
+//! servos, and moves them as a unit: all of the targets of a move are
+//! checked before any servo is commanded, and the group can wait until
+//! every servo reports that it reached its target:
+//!
+//! ```text
+//! let mut arm = ServoGroup::new();
+//! arm.add("base", base)?;
+//! arm.add("shoulder", shoulder)?;
+//! arm.add("elbow", elbow)?;
+//!
+//! arm.engage()?;
+//! arm.set_targets(&[90.0, 45.0, 120.0])?;
+//! arm.wait_reached(Duration::from_secs(5))?;
+//! arm.disengage()?;
+//! ```
+//!
+
+use crate::{devices::RcServo, Error, HandlerGuard, Result};
+use std::{
+    sync::{Arc, Condvar, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+// Whether each servo in a group reached its target, with a condition
+// that's signalled as they do.
+#[derive(Default)]
+struct Reached {
+    flags: Mutex<Vec<bool>>,
+    cond: Condvar,
+}
+
+impl Reached {
+    // Locks the flags.
+    fn flags(&self) -> MutexGuard<'_, Vec<bool>> {
+        self.flags.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    // Marks the servo at the index as having reached its target.
+    fn set(&self, idx: usize) {
+        if let Some(flag) = self.flags().get_mut(idx) {
+            *flag = true;
+        }
+        self.cond.notify_all();
+    }
+}
+
+/// A group of RC servos that are moved together.
+///
+/// The group owns the servos, which should be open and attached before
+/// they're added. Their target position reached handlers are used by the
+/// group, and are removed when it's dropped.
+pub struct ServoGroup {
+    // The target reached handlers, dropped before the servos
+    guards: Vec<HandlerGuard>,
+    // The servos, in the order they were added
+    servos: Vec<RcServo>,
+    // The names of the servos, in order
+    names: Vec<String>,
+    // Whether each servo reached its target
+    reached: Arc<Reached>,
+}
+
+impl ServoGroup {
+    /// Creates an empty group.
+    pub fn new() -> Self {
+        Self {
+            guards: Vec::new(),
+            servos: Vec::new(),
+            names: Vec::new(),
+            reached: Arc::default(),
+        }
+    }
+
+    /// Adds a servo to the group.
+    ///
+    /// This sets the servo's target position reached handler, replacing
+    /// any handler that was previously set.
+    pub fn add(&mut self, name: &str, servo: RcServo) -> Result<()> {
+        let idx = self.servos.len();
+        let reached = Arc::clone(&self.reached);
+        let guard = servo.set_on_target_position_reached_handler(move |_, _| {
+            reached.set(idx);
+        })?;
+
+        self.reached.flags().push(true);
+        self.guards.push(guard);
+        self.servos.push(servo);
+        self.names.push(name.into());
+        Ok(())
+    }
+
+    /// Gets the names of the servos, in the order they were added.
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    /// Gets the servos, in the order they were added.
+    pub fn servos(&self) -> &[RcServo] {
+        &self.servos
+    }
+
+    /// Gets the servo with the specified name, if it's in the group.
+    pub fn servo(&self, name: &str) -> Option<&RcServo> {
+        self.names
+            .iter()
+            .position(|n| n == name)
+            .map(|idx| &self.servos[idx])
+    }
+
+    /// Engages all of the servos.
+    pub fn engage(&mut self) -> Result<()> {
+        self.servos.iter_mut().try_for_each(|s| s.set_engaged(true))
+    }
+
+    /// Disengages all of the servos, so they stop driving their joints.
+    ///
+    /// This tries every servo, even if one fails, and returns the first
+    /// error.
+    pub fn disengage(&mut self) -> Result<()> {
+        let mut res = Ok(());
+        for servo in &mut self.servos {
+            if let Err(err) = servo.set_engaged(false) {
+                res = res.and(Err(err));
+            }
+        }
+        res
+    }
+
+    /// Sets the target positions of all the servos, in the order they were
+    /// added.
+    ///
+    /// The targets are all checked against the position range of their
+    /// servos before any servo is moved, so a bad target doesn't leave the
+    /// group part way through a move. This returns `Error::InvalidArg` if
+    /// the number of targets doesn't match the number of servos, or any
+    /// target is out of range.
+    pub fn set_targets(&mut self, targets: &[f64]) -> Result<()> {
+        if targets.len() != self.servos.len() {
+            return Err(Error::InvalidArg);
+        }
+        for (servo, &pos) in self.servos.iter().zip(targets) {
+            if pos < servo.min_position()? || pos > servo.max_position()? {
+                return Err(Error::InvalidArg);
+            }
+        }
+
+        // A servo that's already holding its target won't report reaching
+        // it again
+        let mut holding = Vec::with_capacity(targets.len());
+        for (servo, &pos) in self.servos.iter().zip(targets) {
+            holding.push(servo.target_position()? == pos && !servo.is_moving()?);
+        }
+        self.reached.flags().copy_from_slice(&holding);
+
+        for (servo, &pos) in self.servos.iter_mut().zip(targets) {
+            servo.set_target_position(pos)?;
+        }
+        Ok(())
+    }
+
+    /// Determines if all the servos reached the targets of the last move.
+    pub fn is_reached(&self) -> bool {
+        self.reached.flags().iter().all(|&r| r)
+    }
+
+    /// Waits until all the servos reach the targets of the last move.
+    ///
+    /// This returns `Error::Timeout` if any servo hasn't reported reaching
+    /// its target within the timeout. The servos need to be engaged to
+    /// move.
+    pub fn wait_reached(&self, timeout: Duration) -> Result<()> {
+        let deadline = Instant::now().checked_add(timeout);
+        let mut flags = self.reached.flags();
+        while !flags.iter().all(|&r| r) {
+            flags = match deadline {
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return Err(Error::Timeout);
+                    }
+                    self.reached
+                        .cond
+                        .wait_timeout(flags, deadline - now)
+                        .unwrap_or_else(|err| err.into_inner())
+                        .0
+                }
+                None => self
+                    .reached
+                    .cond
+                    .wait(flags)
+                    .unwrap_or_else(|err| err.into_inner()),
+            };
+        }
+        Ok(())
+    }
+}
+
+impl Default for ServoGroup {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::*;
+    use crate::Phidget;
+
+    #[test]
+    fn test_mock_servo_group() {
+        let mut arm = ServoGroup::new();
+        for name in ["shoulder", "elbow"] {
+            let mut servo = RcServo::new();
+            servo.open().unwrap();
+            servo.set_min_position(0.0).unwrap();
+            servo.set_max_position(180.0).unwrap();
+            servo.set_target_position(0.0).unwrap();
+            arm.add(name, servo).unwrap();
+        }
+        assert!(arm.is_reached());
+
+        // Bad targets don't move any servo
+        assert_eq!(arm.set_targets(&[90.0]), Err(Error::InvalidArg));
+        assert_eq!(arm.set_targets(&[90.0, 200.0]), Err(Error::InvalidArg));
+        assert_eq!(arm.servo("shoulder").unwrap().target_position(), Ok(0.0));
+
+        // Disengaged servos don't reach their targets
+        arm.set_targets(&[90.0, 45.0]).unwrap();
+        assert!(!arm.is_reached());
+        assert_eq!(
+            arm.wait_reached(Duration::from_millis(10)),
+            Err(Error::Timeout)
+        );
+
+        arm.engage().unwrap();
+        arm.set_targets(&[120.0, 30.0]).unwrap();
+        arm.wait_reached(Duration::from_millis(100)).unwrap();
+        assert_eq!(arm.servo("elbow").unwrap().position(), Ok(30.0));
+
+        arm.disengage().unwrap();
+        assert!(arm.servos().iter().all(|s| s.engaged() == Ok(false)));
+    }
+}
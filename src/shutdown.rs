@@ -0,0 +1,281 @@
+// phidget-rs/src/shutdown.rs
+//
+// This file is part of the 'phidget-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+//! Shutting down all the hardware at once.
+//!
+//! The crate keeps track of the channels and managers that it opened, so
+//! that they can all be closed with a single call, such as from a Ctrl-C
+//! handler:
+//!
+//! ```text
+//! ctrlc::set_handler(|| {
+//!     let _ = phidget::shutdown();
+//!     std::process::exit(0);
+//! })?;
+//! ```
+//!
+//! Or a `ShutdownGuard` can be held in `main()` to do it on the way out:
+//!
+//! ```text
+//! let _shutdown = ShutdownGuard::new();
+//! ```
+//!
+//! The managers are closed first, so no new channels are reported. Then
+//! the outputs are put in a safe state before their channels are closed:
+//! RC servos and steppers are disengaged, digital outputs are turned off,
+//! and voltage outputs are disabled. Last, the connections to network
+//! servers are removed, and any dispatch threads are stopped.
+//!
+//! The channel objects remain valid after a shutdown, and can be opened
+//! again.
+//!
+
+use crate::{net, ChannelClass, Result, ReturnCode, ServerType};
+use phidget_sys::{self as ffi, PhidgetHandle, PhidgetManagerHandle};
+use std::{
+    mem,
+    os::raw::c_uint,
+    sync::{Condvar, Mutex, MutexGuard},
+    thread::{self, ThreadId},
+};
+
+// The channels that the crate opened, each holding a reference to the
+// channel, so that the handle stays valid until it's closed.
+static CHANNELS: Mutex<Vec<usize>> = Mutex::new(Vec::new());
+
+// The managers that the crate opened.
+static MANAGERS: Mutex<Managers> = Mutex::new(Managers {
+    open: Vec::new(),
+    closing: Vec::new(),
+});
+
+// Signaled when a shutdown has finished closing a manager.
+static MANAGER_CLOSED: Condvar = Condvar::new();
+
+struct Managers {
+    // The managers that are open
+    open: Vec<usize>,
+    // The managers being closed by a shutdown, and the thread doing it
+    closing: Vec<(usize, ThreadId)>,
+}
+
+fn channels() -> MutexGuard<'static, Vec<usize>> {
+    CHANNELS.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+fn managers() -> MutexGuard<'static, Managers> {
+    MANAGERS.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+/// Records that the channel was opened.
+pub(crate) fn opened(phid: PhidgetHandle) {
+    let mut chans = channels();
+    if !chans.contains(&(phid as usize)) {
+        unsafe {
+            ffi::Phidget_retain(phid);
+        }
+        chans.push(phid as usize);
+    }
+}
+
+/// Records that the channel was closed.
+pub(crate) fn closed(phid: PhidgetHandle) {
+    let mut chans = channels();
+    if let Some(i) = chans.iter().position(|&k| k == phid as usize) {
+        chans.swap_remove(i);
+        drop(chans);
+        let mut phid = phid;
+        unsafe {
+            ffi::Phidget_release(&mut phid);
+        }
+    }
+}
+
+/// Records that the manager was opened.
+pub(crate) fn manager_opened(mgr: PhidgetManagerHandle) {
+    let mut mgrs = managers();
+    if !mgrs.open.contains(&(mgr as usize)) {
+        mgrs.open.push(mgr as usize);
+    }
+}
+
+/// Records that the manager was closed.
+///
+/// This must be called before the manager is deleted. If a shutdown on
+/// another thread is closing the manager, this blocks until it's done,
+/// so that the manager isn't deleted while it's being closed.
+pub(crate) fn manager_closed(mgr: PhidgetManagerHandle) {
+    let key = mgr as usize;
+    let this = thread::current().id();
+    let mut mgrs = managers();
+    mgrs.open.retain(|&k| k != key);
+    let _mgrs = MANAGER_CLOSED
+        .wait_while(mgrs, |m| {
+            m.closing.iter().any(|&(k, thr)| k == key && thr != this)
+        })
+        .unwrap_or_else(|err| err.into_inner());
+}
+
+/// Closes all the channels and managers that the crate opened, and
+/// stops the network and dispatch subsystems.
+///
+/// Every step is attempted, even if an earlier one fails. This returns
+/// the first error from closing a channel or removing the servers.
+pub fn shutdown() -> Result<()> {
+    // The managers are deleted when dropped, rather than reference
+    // counted, so they're marked as closing, and dropping one waits for
+    // this to finish with it. The lock isn't held while closing them,
+    // since the library might call a handler that opens or closes one.
+    let this = thread::current().id();
+    let mgrs = {
+        let mut mgrs = managers();
+        let open = mem::take(&mut mgrs.open);
+        mgrs.closing.extend(open.iter().map(|&k| (k, this)));
+        open
+    };
+    for mgr in mgrs {
+        unsafe {
+            ffi::PhidgetManager_close(mgr as PhidgetManagerHandle);
+        }
+        managers()
+            .closing
+            .retain(|&(k, thr)| k != mgr || thr != this);
+        MANAGER_CLOSED.notify_all();
+    }
+
+    let handles: Vec<_> = channels()
+        .iter()
+        .map(|&k| {
+            unsafe {
+                ffi::Phidget_retain(k as PhidgetHandle);
+            }
+            k as PhidgetHandle
+        })
+        .collect();
+
+    let mut res = close_channels(&handles);
+    for mut phid in handles {
+        unsafe {
+            ffi::Phidget_release(&mut phid);
+        }
+    }
+
+    let net_res = net::remove_all_servers();
+    for server_type in [ServerType::DeviceRemote, ServerType::WwwRemote] {
+        let _ = net::disable_server_discovery(server_type);
+    }
+    let _ = crate::handler::set_dispatch_threads(0);
+
+    if res.is_ok() {
+        res = net_res;
+    }
+    res
+}
+
+/// Puts the channels in a safe state, and closes them.
+///
+/// This returns the first error from closing a channel.
+pub(crate) fn close_channels(handles: &[PhidgetHandle]) -> Result<()> {
+    let mut res = Ok(());
+    for &phid in handles {
+        safe_state(phid);
+        let rc = unsafe { traced!(ffi::Phidget_close(phid)) };
+        #[cfg(feature = "metrics")]
+        crate::metrics::closed(phid);
+        closed(phid);
+        if res.is_ok() {
+            res = ReturnCode::result(rc);
+        }
+    }
+    res
+}
+
+// Puts an output channel in a safe state before it's closed.
+// Errors are ignored, as for a channel that isn't attached.
+fn safe_state(phid: PhidgetHandle) {
+    let mut cls: c_uint = 0;
+    if unsafe { ffi::Phidget_getChannelClass(phid, &mut cls) } != 0 {
+        return;
+    }
+    unsafe {
+        match ChannelClass::try_from(cls) {
+            Ok(ChannelClass::RcServo) => {
+                ffi::PhidgetRCServo_setEngaged(phid as _, 0);
+            }
+            Ok(ChannelClass::Stepper) => {
+                ffi::PhidgetStepper_setEngaged(phid as _, 0);
+            }
+            Ok(ChannelClass::DigitalOutput) => {
+                ffi::PhidgetDigitalOutput_setState(phid as _, 0);
+            }
+            Ok(ChannelClass::VoltageOutput) => {
+                ffi::PhidgetVoltageOutput_setEnabled(phid as _, 0);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// A guard that shuts down all the hardware when it's dropped.
+///
+/// This calls `shutdown()` from its `Drop`, so holding one in `main()`
+/// cleans up however the function returns, including by unwinding from a
+/// panic.
+#[must_use = "the hardware is shut down as soon as the guard is dropped"]
+#[derive(Debug, Default)]
+pub struct ShutdownGuard {
+    _priv: (),
+}
+
+impl ShutdownGuard {
+    /// Creates a guard to shut down the hardware when it's dropped.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Drop for ShutdownGuard {
+    fn drop(&mut self) {
+        let _ = shutdown();
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::*;
+    use crate::{
+        devices::{DigitalOutput, RcServo},
+        Phidget,
+    };
+
+    #[test]
+    fn test_mock_shutdown() {
+        let mut servo = RcServo::new();
+        let mut out = DigitalOutput::new();
+        servo.open().unwrap();
+        out.open().unwrap();
+        servo.set_engaged(true).unwrap();
+        out.set_state(true).unwrap();
+
+        // Only these channels, since the other tests share the library
+        let handles = [servo.as_handle(), out.as_handle()];
+        close_channels(&handles).unwrap();
+
+        assert_eq!(servo.is_open(), Ok(false));
+        assert_eq!(out.is_open(), Ok(false));
+
+        // The outputs were made safe before closing
+        servo.open().unwrap();
+        out.open().unwrap();
+        assert_eq!(servo.engaged(), Ok(false));
+        assert_eq!(out.state(), Ok(false));
+    }
+}
@@ -0,0 +1,423 @@
+// phidget-rs/src/sink/influx.rs
+//
+// This file is part of the 'phidget-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+//! An InfluxDB sink, using the line protocol over HTTP.
+//!
+//! With the `sink-influx` feature, an `InfluxSink` subscribes to the
+//! change events of any number of sensors, and writes the readings to an
+//! InfluxDB bucket in batches. Each reading is a point in a measurement
+//! named for the channel class, tagged with the name it was added under
+//! and the address of its channel:
+//!
+//! ```text
+//! let cfg = InfluxConfig {
+//!     bucket: "lab".into(),
+//!     org: "physics".into(),
+//!     token: Some(token),
+//!     ..InfluxConfig::default()
+//! };
+//! let mut sink = InfluxSink::new(cfg);
+//! sink.add("bath", &mut bath_temp)?;
+//! sink.add("cell", &mut load_cell)?;
+//! ```
+//!
+//! which writes lines like:
+//!
+//! ```text
+//! temperature_sensor,name=bath,serial=561234,hub_port=2,channel=0 value=21.5 1760512345125000000
+//! ```
+//!
+//! The points are written to the InfluxDB 2 API, `/api/v2/write`, which
+//! InfluxDB 1.8 and later also accept. The connection is plain HTTP, so
+//! a server that needs TLS has to be reached through a proxy. A batch
+//! that can't be written is dropped, and the error kept for
+//! `take_error()`, so that an outage doesn't grow the queue without
+//! limit.
+//!
+
+use crate::{HandlerGuard, Result, Sensor};
+use std::{
+    fmt::Write as _,
+    io::{self, BufRead, BufReader, Write},
+    net::TcpStream,
+    sync::{
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+/// The configuration of an InfluxDB sink.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InfluxConfig {
+    /// The host and port of the server, like "localhost:8086"
+    pub addr: String,
+    /// The organization that owns the bucket
+    pub org: String,
+    /// The bucket for the points.
+    /// For InfluxDB 1.x this is "database/retention-policy"
+    pub bucket: String,
+    /// The API token, if the server needs one
+    pub token: Option<String>,
+    /// The number of points at which a batch is written
+    pub batch_size: usize,
+    /// The longest time that a point waits to be written
+    pub flush_interval: Duration,
+}
+
+impl Default for InfluxConfig {
+    fn default() -> Self {
+        Self {
+            addr: "localhost:8086".into(),
+            org: String::new(),
+            bucket: String::new(),
+            token: None,
+            batch_size: 500,
+            flush_interval: Duration::from_secs(1),
+        }
+    }
+}
+
+impl InfluxConfig {
+    // Gets the path and query of the write endpoint.
+    fn write_path(&self) -> String {
+        format!(
+            "/api/v2/write?org={}&bucket={}&precision=ns",
+            url_encode(&self.org),
+            url_encode(&self.bucket)
+        )
+    }
+}
+
+/// A point in InfluxDB, with a single "value" field.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Point {
+    /// The name of the measurement
+    pub measurement: String,
+    /// The tags, as key/value pairs
+    pub tags: Vec<(String, String)>,
+    /// The value of the field
+    pub value: f64,
+    /// The time of the reading
+    pub time: SystemTime,
+}
+
+impl Point {
+    /// Formats the point as a line of the line protocol, without the
+    /// newline.
+    ///
+    /// A value that isn't finite can't be written, so it gives `None`.
+    pub fn to_line(&self) -> Option<String> {
+        if !self.value.is_finite() {
+            return None;
+        }
+        let mut line = escape(&self.measurement, &[',', ' ']);
+        for (key, val) in &self.tags {
+            if !key.is_empty() && !val.is_empty() {
+                let _ = write!(
+                    line,
+                    ",{}={}",
+                    escape(key, &[',', '=', ' ']),
+                    escape(val, &[',', '=', ' '])
+                );
+            }
+        }
+        let ns = self
+            .time
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or(0);
+        let _ = write!(line, " value={} {}", self.value, ns);
+        Some(line)
+    }
+}
+
+/// A sink that writes sensor readings to InfluxDB.
+///
+/// The points are written by a background thread. The sensor handlers
+/// are removed, and the points still queued are written, when the sink
+/// is dropped.
+pub struct InfluxSink {
+    // Sends the points to the writer thread
+    tx: Option<Sender<Point>>,
+    // The writer thread
+    thr: Option<JoinHandle<()>>,
+    // The last error writing a batch
+    last_error: Arc<Mutex<Option<io::Error>>>,
+    // The handlers feeding the sink
+    guards: Vec<HandlerGuard>,
+}
+
+impl InfluxSink {
+    /// Creates a new sink, and starts the thread that writes the points.
+    pub fn new(cfg: InfluxConfig) -> Self {
+        let (tx, rx) = mpsc::channel();
+        let last_error = Arc::default();
+        let thr = thread::spawn({
+            let last_error = Arc::clone(&last_error);
+            move || run(cfg, rx, last_error)
+        });
+
+        Self {
+            tx: Some(tx),
+            thr: Some(thr),
+            last_error,
+            guards: Vec::new(),
+        }
+    }
+
+    /// Writes all the value changes from the sensor, tagged with the
+    /// name.
+    ///
+    /// The measurement is named for the sensor's channel class, like
+    /// "temperature_sensor". This sets the sensor's change handler,
+    /// replacing any handler that was previously set. Boolean values are
+    /// written as 0 or 1.
+    pub fn add<S>(&mut self, name: &str, sensor: &mut S) -> Result<()>
+    where
+        S: Sensor,
+        S::Value: Into<f64>,
+    {
+        let Some(tx) = self.tx.clone()
+        else {
+            return Ok(());
+        };
+        let measurement = sensor
+            .channel_class()?
+            .name()
+            .to_ascii_lowercase()
+            .replace(' ', "_");
+        let key = sensor.as_handle() as usize;
+        let name = name.to_string();
+
+        let guard = sensor.on_change(move |_, val| {
+            let mut tags = vec![("name".to_string(), name.clone())];
+            if let Some(addr) = crate::cache::address(key as _) {
+                tags.push(("serial".into(), addr.serial_number.to_string()));
+                tags.push(("hub_port".into(), addr.hub_port.to_string()));
+                tags.push(("channel".into(), addr.channel.to_string()));
+            }
+            let _ = tx.send(Point {
+                measurement: measurement.clone(),
+                tags,
+                value: val.into(),
+                time: SystemTime::now(),
+            });
+        })?;
+        self.guards.push(guard);
+        Ok(())
+    }
+
+    /// Writes a single point, such as one made from a `Sample`.
+    pub fn write(&self, point: Point) {
+        if let Some(tx) = &self.tx {
+            let _ = tx.send(point);
+        }
+    }
+
+    /// Takes the last error that occurred writing a batch of points.
+    pub fn take_error(&self) -> Option<io::Error> {
+        self.last_error
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .take()
+    }
+}
+
+impl Drop for InfluxSink {
+    fn drop(&mut self) {
+        self.guards.clear();
+        self.tx = None;
+        if let Some(thr) = self.thr.take() {
+            let _ = thr.join();
+        }
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+// Batches the points and writes them, until the sender is dropped.
+fn run(cfg: InfluxConfig, rx: Receiver<Point>, last_error: Arc<Mutex<Option<io::Error>>>) {
+    let mut batch = String::new();
+    let mut count = 0;
+    let mut deadline = None;
+
+    loop {
+        let timeout = deadline.map_or(cfg.flush_interval, |t: Instant| {
+            t.saturating_duration_since(Instant::now())
+        });
+        let done = match rx.recv_timeout(timeout) {
+            Ok(point) => {
+                if let Some(line) = point.to_line() {
+                    batch.push_str(&line);
+                    batch.push('\n');
+                    count += 1;
+                    deadline.get_or_insert_with(|| Instant::now() + cfg.flush_interval);
+                }
+                false
+            }
+            Err(RecvTimeoutError::Timeout) => false,
+            Err(RecvTimeoutError::Disconnected) => true,
+        };
+
+        let due = deadline.is_some_and(|t| Instant::now() >= t);
+        if count > 0 && (done || due || count >= cfg.batch_size) {
+            if let Err(err) = post(&cfg, &batch) {
+                *last_error.lock().unwrap_or_else(|err| err.into_inner()) = Some(err);
+            }
+            batch.clear();
+            count = 0;
+            deadline = None;
+        }
+        if done {
+            break;
+        }
+    }
+}
+
+// Posts a batch of lines to the write endpoint.
+fn post(cfg: &InfluxConfig, body: &str) -> io::Result<()> {
+    let mut stream = TcpStream::connect(&cfg.addr)?;
+    stream.set_read_timeout(Some(Duration::from_secs(10)))?;
+    stream.set_write_timeout(Some(Duration::from_secs(10)))?;
+
+    let mut req = format!(
+        "POST {} HTTP/1.1\r\n\
+         Host: {}\r\n\
+         Content-Type: text/plain; charset=utf-8\r\n\
+         Content-Length: {}\r\n\
+         Connection: close\r\n",
+        cfg.write_path(),
+        cfg.addr,
+        body.len()
+    );
+    if let Some(token) = &cfg.token {
+        let _ = write!(req, "Authorization: Token {}\r\n", token);
+    }
+    req.push_str("\r\n");
+    stream.write_all(req.as_bytes())?;
+    stream.write_all(body.as_bytes())?;
+    stream.flush()?;
+
+    let mut status = String::new();
+    BufReader::new(stream).read_line(&mut status)?;
+    match status.split_whitespace().nth(1) {
+        Some(code) if code.starts_with('2') => Ok(()),
+        _ => Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!("InfluxDB write failed: {}", status.trim()),
+        )),
+    }
+}
+
+// Escapes the special characters in a name, key, or tag value.
+fn escape(s: &str, special: &[char]) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        if c == '\\' || special.contains(&c) {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+// Percent-encodes a query parameter.
+fn url_encode(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for b in s.bytes() {
+        if b.is_ascii_alphanumeric() || b"-_.~".contains(&b) {
+            out.push(b as char);
+        }
+        else {
+            let _ = write!(out, "%{:02X}", b);
+        }
+    }
+    out
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{io::Read, net::TcpListener};
+
+    fn point(name: &str, value: f64) -> Point {
+        Point {
+            measurement: "temperature_sensor".into(),
+            tags: vec![
+                ("name".into(), name.into()),
+                ("serial".into(), "561234".into()),
+            ],
+            value,
+            time: UNIX_EPOCH + Duration::from_millis(1_700_000_000_250),
+        }
+    }
+
+    #[test]
+    fn test_line() {
+        assert_eq!(
+            point("bath", 21.5).to_line().unwrap(),
+            "temperature_sensor,name=bath,serial=561234 value=21.5 1700000000250000000"
+        );
+        assert_eq!(
+            point("north bath,2", 1.0).to_line().unwrap(),
+            "temperature_sensor,name=north\\ bath\\,2,serial=561234 value=1 1700000000250000000"
+        );
+        assert_eq!(point("bath", f64::NAN).to_line(), None);
+        assert_eq!(url_encode("lab/autogen"), "lab%2Fautogen");
+    }
+
+    #[test]
+    fn test_write() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let cfg = InfluxConfig {
+            addr: listener.local_addr().unwrap().to_string(),
+            org: "physics".into(),
+            bucket: "lab".into(),
+            token: Some("secret".into()),
+            batch_size: 2,
+            ..InfluxConfig::default()
+        };
+
+        let sink = InfluxSink::new(cfg);
+        sink.write(point("bath", 21.5));
+        sink.write(point("bath", 22.0));
+
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut req = Vec::new();
+        let mut buf = [0u8; 1024];
+        loop {
+            let n = stream.read(&mut buf).unwrap();
+            req.extend_from_slice(&buf[..n]);
+            let s = String::from_utf8_lossy(&req);
+            if s.matches("value=").count() == 2 && s.ends_with('\n') {
+                break;
+            }
+        }
+        stream
+            .write_all(b"HTTP/1.1 204 No Content\r\n\r\n")
+            .unwrap();
+        drop(stream);
+        drop(sink);
+
+        let req = String::from_utf8(req).unwrap();
+        assert!(
+            req.starts_with("POST /api/v2/write?org=physics&bucket=lab&precision=ns HTTP/1.1\r\n")
+        );
+        assert!(req.contains("Authorization: Token secret\r\n"));
+        assert!(req.ends_with(
+            "\r\n\r\ntemperature_sensor,name=bath,serial=561234 value=21.5 1700000000250000000\n\
+             temperature_sensor,name=bath,serial=561234 value=22 1700000000250000000\n"
+        ));
+    }
+}
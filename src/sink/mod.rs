@@ -0,0 +1,19 @@
+// phidget-rs/src/sink/mod.rs
+//
+// This file is part of the 'phidget-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+//! Sinks that send sensor readings on to other systems for storage.
+//!
+//! Each sink is in its own module, behind its own feature.
+//!
+
+/// An InfluxDB sink, using the line protocol over HTTP
+#[cfg(feature = "sink-influx")]
+pub mod influx;
+#[cfg(feature = "sink-influx")]
+pub use crate::sink::influx::{InfluxConfig, InfluxSink, Point};
@@ -0,0 +1,78 @@
+// phidget-rs/src/stream.rs
+//
+// This file is part of the 'phidget-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+//! Asynchronous streams of channel events.
+//!
+//! Sensors can deliver their change events as a `futures::Stream` of
+//! readings, which is fed from the channel's change callback through a
+//! bounded channel. The streams only rely on wakers, so they can be used
+//! with any async executor. This lets an async application consume them
+//! with:
+//!
+//! ```text
+//! while let Some(t) = stream.next().await { ... }
+//! ```
+//!
+
+use crate::HandlerGuard;
+use futures_channel::mpsc;
+use futures_core::Stream;
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+/// The number of events that a stream buffers if it is not being read
+/// as fast as the events arrive.
+pub const STREAM_BUFFER_SIZE: usize = 32;
+
+/// A stream of events from a Phidget channel.
+///
+/// The stream is fed by one of the channel's event handlers. Up to
+/// `STREAM_BUFFER_SIZE` events are buffered if the stream isn't being read
+/// fast enough, after which new events are discarded until there is room
+/// again. The handler feeding the stream is removed when the stream is
+/// dropped, and the stream ends if that handler is replaced or removed.
+pub struct EventStream<T> {
+    rx: mpsc::Receiver<T>,
+    _guard: HandlerGuard,
+}
+
+impl<T> EventStream<T> {
+    /// Creates a stream from the handler that feeds it.
+    pub(crate) fn new(rx: mpsc::Receiver<T>, guard: HandlerGuard) -> Self {
+        Self { rx, _guard: guard }
+    }
+}
+
+impl<T> Stream for EventStream<T> {
+    type Item = T;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        Pin::new(&mut self.rx).poll_next(cx)
+    }
+}
+
+/// The sending side of an event stream, to be moved into a callback.
+pub(crate) struct EventSender<T> {
+    tx: mpsc::Sender<T>,
+}
+
+impl<T> EventSender<T> {
+    /// Sends an event to the stream, if there's room for it.
+    pub fn send(&mut self, val: T) {
+        let _ = self.tx.try_send(val);
+    }
+}
+
+/// Creates the sender and receiver for a new event stream.
+pub(crate) fn event_channel<T>() -> (EventSender<T>, mpsc::Receiver<T>) {
+    let (tx, rx) = mpsc::channel(STREAM_BUFFER_SIZE);
+    (EventSender { tx }, rx)
+}
@@ -0,0 +1,292 @@
+// phidget-rs/src/sync_group.rs
+//
+// This file is part of the 'phidget-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+//! Synchronized sampling of a group of sensors.
+//!
+//! Each channel reports its change events on its own schedule, so the
+//! readings of several sensors drift against each other. A `SyncGroup`
+//! sets all of its sensors to the same data interval, and then combines
+//! their change events into rows, with one value per sensor, by grouping
+//! the events that arrive within a tolerance of each other:
+//!
+//! ```text
+//! let mut group = SyncGroup::new(Duration::from_millis(100));
+//! group.add("inlet", &mut inlet_temp)?;
+//! group.add("outlet", &mut outlet_temp)?;
+//! group.add("flow", &mut flow_voltage)?;
+//!
+//! for row in group.iter() {
+//!     println!("{:?}", row.values);
+//! }
+//! ```
+//!
+//! A row is sent as soon as it has a value from every sensor. If a sensor
+//! reports a second value before the row is complete, or an event arrives
+//! outside the tolerance of the row, the row is sent with the missing
+//! values as `None`, and a new row is started.
+//!
+
+use crate::{HandlerGuard, Result, Sensor};
+use std::{
+    ops::Deref,
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex, MutexGuard,
+    },
+    time::{Duration, Instant, SystemTime},
+};
+
+/// A row of values from a `SyncGroup`, one for each sensor.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SyncRow {
+    /// The time of the first event in the row
+    pub time: SystemTime,
+    /// The values of the sensors, in the order they were added to the
+    /// group, or `None` for any that didn't report in time
+    pub values: Vec<Option<f64>>,
+}
+
+// Groups the events from the sensors into rows.
+struct Aligner {
+    // The maximum time between the first and last events of a row
+    tolerance: Duration,
+    // The number of sensors
+    n: usize,
+    // The time of the first event of the pending row, if any
+    start: Option<Instant>,
+    // The values of the pending row
+    values: Vec<Option<f64>>,
+    // The system and monotonic times when the group was created, to
+    // convert the event times
+    base: (SystemTime, Instant),
+    // Sends the completed rows
+    tx: Sender<SyncRow>,
+}
+
+impl Aligner {
+    // Adds a sensor to the rows.
+    fn add_sensor(&mut self) -> usize {
+        self.n += 1;
+        self.values.resize(self.n, None);
+        self.n - 1
+    }
+
+    // Adds an event from the sensor at the index, sending any rows that
+    // are done.
+    fn push(&mut self, idx: usize, t: Instant, val: f64) {
+        if let Some(start) = self.start {
+            let late = t.saturating_duration_since(start) > self.tolerance;
+            if late || self.values[idx].is_some() {
+                self.flush();
+            }
+        }
+
+        self.start.get_or_insert(t);
+        self.values[idx] = Some(val);
+
+        if self.values.iter().all(Option::is_some) {
+            self.flush();
+        }
+    }
+
+    // Sends the pending row, if there is one.
+    fn flush(&mut self) {
+        if let Some(start) = self.start.take() {
+            let (sys, inst) = self.base;
+            let row = SyncRow {
+                time: sys + start.saturating_duration_since(inst),
+                values: self.values.clone(),
+            };
+            let _ = self.tx.send(row);
+            self.values.iter_mut().for_each(|v| *v = None);
+        }
+    }
+}
+
+/// A group of sensors whose readings are combined into rows.
+///
+/// This dereferences to a `std::sync::mpsc::Receiver` for reading the
+/// rows. The sensors' change handlers are removed when the group is
+/// dropped.
+pub struct SyncGroup {
+    // The data interval for the sensors
+    interval: Duration,
+    // The names of the sensors, in order
+    names: Vec<String>,
+    // Groups the events into rows
+    aligner: Arc<Mutex<Aligner>>,
+    // Receives the rows
+    rx: Receiver<SyncRow>,
+    // The change handlers feeding the group
+    guards: Vec<HandlerGuard>,
+}
+
+impl SyncGroup {
+    /// Creates a group for sensors with the specified data interval.
+    ///
+    /// The tolerance for grouping the events into a row is half of the
+    /// interval.
+    pub fn new(interval: Duration) -> Self {
+        let (tx, rx) = mpsc::channel();
+        let aligner = Aligner {
+            tolerance: interval / 2,
+            n: 0,
+            start: None,
+            values: Vec::new(),
+            base: (SystemTime::now(), Instant::now()),
+            tx,
+        };
+        Self {
+            interval,
+            names: Vec::new(),
+            aligner: Arc::new(Mutex::new(aligner)),
+            rx,
+            guards: Vec::new(),
+        }
+    }
+
+    /// Sets the maximum time between the first and the last events of a
+    /// row.
+    pub fn set_tolerance(&mut self, tolerance: Duration) {
+        self.aligner().tolerance = tolerance;
+    }
+
+    // Locks the aligner.
+    fn aligner(&self) -> MutexGuard<'_, Aligner> {
+        self.aligner.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Adds a sensor to the group.
+    ///
+    /// The sensor should be open and attached. This sets its data interval
+    /// to the one for the group, and sets its change handler, replacing
+    /// any handler that was previously set. Boolean values are reported
+    /// as 0.0 or 1.0.
+    pub fn add<S>(&mut self, name: &str, sensor: &mut S) -> Result<()>
+    where
+        S: Sensor,
+        S::Value: Into<f64>,
+    {
+        sensor.set_data_interval(self.interval)?;
+
+        let idx = self.aligner().add_sensor();
+        let aligner = Arc::clone(&self.aligner);
+        let guard = sensor.on_change(move |_, val| {
+            let t = Instant::now();
+            aligner
+                .lock()
+                .unwrap_or_else(|err| err.into_inner())
+                .push(idx, t, val.into());
+        });
+
+        match guard {
+            Ok(guard) => {
+                self.names.push(name.into());
+                self.guards.push(guard);
+                Ok(())
+            }
+            Err(err) => {
+                let mut aligner = self.aligner();
+                aligner.n -= 1;
+                aligner.values.pop();
+                Err(err)
+            }
+        }
+    }
+
+    /// Gets the names of the sensors, in the order of the values in the
+    /// rows.
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    /// Sends the pending row, even if it's not complete.
+    pub fn flush(&self) {
+        self.aligner().flush();
+    }
+}
+
+impl Deref for SyncGroup {
+    type Target = Receiver<SyncRow>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.rx
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_aligner() {
+        let grp = SyncGroup::new(Duration::from_millis(100));
+        let mut al = grp.aligner();
+        al.add_sensor();
+        al.add_sensor();
+
+        let t0 = Instant::now();
+        let ms = Duration::from_millis;
+
+        // A complete row is sent right away
+        al.push(0, t0, 1.0);
+        al.push(1, t0 + ms(20), 2.0);
+
+        // A repeated sensor, and then a late event, each end the row
+        al.push(0, t0 + ms(100), 3.0);
+        al.push(0, t0 + ms(200), 4.0);
+        al.push(1, t0 + ms(300), 5.0);
+        drop(al);
+
+        let rows: Vec<_> = grp.try_iter().map(|r| r.values).collect();
+        assert_eq!(
+            rows,
+            vec![
+                vec![Some(1.0), Some(2.0)],
+                vec![Some(3.0), None],
+                vec![Some(4.0), None],
+            ]
+        );
+
+        grp.flush();
+        assert_eq!(grp.try_recv().unwrap().values, vec![None, Some(5.0)]);
+    }
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod mock_tests {
+    use super::*;
+    use crate::{mock::set_value, Phidget, TemperatureSensor, VoltageInput};
+
+    #[test]
+    fn test_mock_sync_group() {
+        let mut temp = TemperatureSensor::new();
+        temp.set_serial_number(641_001).unwrap();
+        temp.open().unwrap();
+        let mut volts = VoltageInput::new();
+        volts.set_serial_number(641_002).unwrap();
+        volts.open().unwrap();
+
+        let mut group = SyncGroup::new(Duration::from_millis(100));
+        group.set_tolerance(Duration::from_secs(60));
+        group.add("temp", &mut temp).unwrap();
+        group.add("volts", &mut volts).unwrap();
+        assert_eq!(volts.data_interval(), Ok(Duration::from_millis(100)));
+        assert_eq!(group.names(), ["temp", "volts"]);
+
+        set_value(&mut temp, 21.5).unwrap();
+        assert!(group.try_recv().is_err());
+        set_value(&mut volts, 3.3).unwrap();
+        let row = group.try_recv().unwrap();
+        assert_eq!(row.values, vec![Some(21.5), Some(3.3)]);
+    }
+}
@@ -0,0 +1,258 @@
+// phidget-rs/src/temperature_array.rs
+//
+// This file is part of the 'phidget-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+//! Multi-channel temperature boards.
+//!
+//! A board like the TMP1101 4x Thermocouple Phidget has a temperature
+//! channel for each of its probes, plus one for the board's own ambient
+//! sensor, which is used for cold-junction compensation. Each of these is
+//! a separate `TemperatureSensor` that has to be addressed, opened, and
+//! configured on its own. A `TemperatureArray` does that for the whole
+//! board:
+//!
+//! ```text
+//! let mut board = TemperatureArray::tmp1101(hub_serial, port)?;
+//! board.open_wait(Duration::from_secs(5))?;
+//! board.set_thermocouple_type(ThermocoupleType::K)?;
+//!
+//! let _handlers = board.on_change(|readings| {
+//!     println!("{:?} (ambient {:?})", readings.probes, readings.ambient);
+//! })?;
+//! ```
+//!
+
+use crate::{devices::ThermocoupleType, Error, HandlerGuard, Phidget, Result, TemperatureSensor};
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+/// The latest readings of all the channels of a `TemperatureArray`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ArrayReadings {
+    /// The index of the probe that reported the change, or `None` if it
+    /// was the ambient sensor
+    pub changed: Option<usize>,
+    /// The latest temperature of each probe, in degrees Celsius, or
+    /// `None` for any that hasn't reported yet
+    pub probes: Vec<Option<f64>>,
+    /// The latest ambient temperature of the board, if it has reported
+    pub ambient: Option<f64>,
+}
+
+/// The temperature channels of a multi-probe board.
+pub struct TemperatureArray {
+    // The channels for the probes, in order
+    probes: Vec<TemperatureSensor>,
+    // The channel for the board's ambient sensor
+    ambient: TemperatureSensor,
+}
+
+impl TemperatureArray {
+    /// Creates the channels for a TMP1101 board plugged into a port of a
+    /// VINT hub.
+    ///
+    /// The board has four thermocouple probes, on channels 0-3, and an
+    /// ambient sensor on channel 4. The channels still need to be opened.
+    pub fn tmp1101(serial: i32, port: i32) -> Result<Self> {
+        Self::on_hub_port(serial, port, 4)
+    }
+
+    /// Creates the channels for a board with the number of probes,
+    /// plugged into a port of a VINT hub.
+    ///
+    /// The probes are expected on the first channels, from zero, and the
+    /// ambient sensor on the channel after the last probe.
+    pub fn on_hub_port(serial: i32, port: i32, n: usize) -> Result<Self> {
+        let chan = |i: usize| -> Result<TemperatureSensor> {
+            let mut ch = TemperatureSensor::on_hub_port(serial, port)?;
+            ch.set_channel(i as i32)?;
+            Ok(ch)
+        };
+        let probes = (0..n).map(chan).collect::<Result<Vec<_>>>()?;
+        let ambient = chan(n)?;
+        Ok(Self { probes, ambient })
+    }
+
+    /// Opens all the channels, and waits for them to attach.
+    ///
+    /// If any of the channels fails to attach, the ones that were opened
+    /// are closed again.
+    pub fn open_wait(&mut self, to: Duration) -> Result<()> {
+        for i in 0..self.probes.len() + 1 {
+            let res = self.channel_mut(i).open_wait(to);
+            if let Err(err) = res {
+                for j in 0..i {
+                    let _ = self.channel_mut(j).close();
+                }
+                return Err(err);
+            }
+        }
+        Ok(())
+    }
+
+    /// Closes all the channels.
+    pub fn close(&mut self) -> Result<()> {
+        let mut res = Ok(());
+        for i in 0..self.probes.len() + 1 {
+            let ch = self.channel_mut(i);
+            if let Ok(true) = ch.is_open() {
+                res = res.and(ch.close());
+            }
+        }
+        res
+    }
+
+    // Gets a channel by index, with the ambient sensor after the probes.
+    fn channel_mut(&mut self, i: usize) -> &mut TemperatureSensor {
+        self.probes.get_mut(i).unwrap_or(&mut self.ambient)
+    }
+
+    /// Gets the number of probes, not counting the ambient sensor.
+    pub fn len(&self) -> usize {
+        self.probes.len()
+    }
+
+    /// Determines if the board has no probes.
+    pub fn is_empty(&self) -> bool {
+        self.probes.is_empty()
+    }
+
+    /// Gets the channel for a probe.
+    pub fn probe(&self, i: usize) -> Option<&TemperatureSensor> {
+        self.probes.get(i)
+    }
+
+    /// Gets a mutable reference to the channel for a probe.
+    pub fn probe_mut(&mut self, i: usize) -> Option<&mut TemperatureSensor> {
+        self.probes.get_mut(i)
+    }
+
+    /// Gets the channel for the board's ambient sensor.
+    pub fn ambient_sensor(&self) -> &TemperatureSensor {
+        &self.ambient
+    }
+
+    /// Gets a mutable reference to the channel for the board's ambient
+    /// sensor.
+    pub fn ambient_sensor_mut(&mut self) -> &mut TemperatureSensor {
+        &mut self.ambient
+    }
+
+    /// Reads the temperature of a probe, in degrees Celsius.
+    pub fn temperature(&self, i: usize) -> Result<f64> {
+        self.probe(i).ok_or(Error::InvalidArg)?.temperature()
+    }
+
+    /// Reads the temperatures of all the probes, in degrees Celsius.
+    pub fn temperatures(&self) -> Result<Vec<f64>> {
+        self.probes.iter().map(|p| p.temperature()).collect()
+    }
+
+    /// Reads the ambient temperature of the board, in degrees Celsius.
+    pub fn ambient(&self) -> Result<f64> {
+        self.ambient.temperature()
+    }
+
+    /// Sets the data interval of all the channels.
+    pub fn set_data_interval(&mut self, interval: Duration) -> Result<()> {
+        for i in 0..self.probes.len() + 1 {
+            self.channel_mut(i).set_data_interval(interval)?;
+        }
+        Ok(())
+    }
+
+    /// Sets the type of thermocouple for all the probes.
+    pub fn set_thermocouple_type(&mut self, tc: ThermocoupleType) -> Result<()> {
+        for probe in self.probes.iter_mut() {
+            probe.set_thermocouple_type(tc)?;
+        }
+        Ok(())
+    }
+
+    /// Sets a handler to receive the temperature changes of all the
+    /// channels as a single event.
+    ///
+    /// Each time any channel reports a change, the handler is called with
+    /// the latest readings of all of them. This replaces any temperature
+    /// change handlers that were previously set on the channels. The
+    /// handler is removed when all the returned guards are dropped.
+    #[must_use = "the handler is removed as soon as the guards are dropped"]
+    pub fn on_change<F>(&self, cb: F) -> Result<Vec<HandlerGuard>>
+    where
+        F: FnMut(&ArrayReadings) + Send + 'static,
+    {
+        let state = Arc::new(Mutex::new((
+            cb,
+            ArrayReadings {
+                changed: None,
+                probes: vec![None; self.probes.len()],
+                ambient: None,
+            },
+        )));
+
+        let handler = |idx: Option<usize>| {
+            let state = Arc::clone(&state);
+            move |_: &TemperatureSensor, t: f64| {
+                let mut state = state.lock().unwrap_or_else(|err| err.into_inner());
+                let (cb, readings) = &mut *state;
+                readings.changed = idx;
+                match idx {
+                    Some(i) => readings.probes[i] = Some(t),
+                    None => readings.ambient = Some(t),
+                }
+                cb(readings);
+            }
+        };
+
+        let mut guards = Vec::with_capacity(self.probes.len() + 1);
+        for (i, probe) in self.probes.iter().enumerate() {
+            guards.push(probe.set_on_temperature_change_handler(handler(Some(i)))?);
+        }
+        guards.push(
+            self.ambient
+                .set_on_temperature_change_handler(handler(None))?,
+        );
+        Ok(guards)
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::*;
+    use crate::mock::set_value;
+    use std::sync::mpsc;
+
+    #[test]
+    fn test_mock_temperature_array() {
+        let mut board = TemperatureArray::tmp1101(645_001, 1).unwrap();
+        assert_eq!(board.len(), 4);
+        board.open_wait(Duration::from_secs(1)).unwrap();
+
+        let (tx, rx) = mpsc::channel();
+        let _handlers = board
+            .on_change(move |r| tx.send(r.clone()).unwrap())
+            .unwrap();
+
+        set_value(board.probe_mut(2).unwrap(), 150.0).unwrap();
+        set_value(board.ambient_sensor_mut(), 24.0).unwrap();
+        assert_eq!(board.temperature(2).unwrap(), 150.0);
+        assert!(matches!(board.temperature(4), Err(Error::InvalidArg)));
+
+        let r = rx.try_iter().last().unwrap();
+        assert_eq!(r.changed, None);
+        assert_eq!(r.probes, vec![None, None, Some(150.0), None]);
+        assert_eq!(r.ambient, Some(24.0));
+
+        board.close().unwrap();
+    }
+}
@@ -0,0 +1,319 @@
+// phidget-rs/src/thermostat.rs
+//
+// This file is part of the 'phidget-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+//! On/off temperature control.
+//!
+//! A `Thermostat` switches a digital output, like a relay for a heater or
+//! a cooler, from the change events of a temperature sensor. It keeps the
+//! temperature in a hysteresis band around the setpoint, and can hold the
+//! output on or off for a minimum time, so that a compressor or a contactor
+//! isn't cycled too quickly:
+//!
+//! ```text
+//! let mut thermostat = Thermostat::new(&tank_temp, heater, Mode::Heating, 55.0)?;
+//! thermostat.set_hysteresis(2.0);
+//! thermostat.set_min_on_time(Duration::from_secs(60));
+//! thermostat.set_min_off_time(Duration::from_secs(120));
+//!
+//! for ev in thermostat.iter() {
+//!     println!("Heater {} at {}", if ev.on { "on" } else { "off" }, ev.temperature);
+//! }
+//! ```
+//!
+//! Like the alarms, the temperature is only checked as the change events
+//! arrive, so a switch that's held back by a minimum time happens on the
+//! first event after the time is up.
+//!
+
+use crate::{devices::DigitalOutput, HandlerGuard, Result, Sensor};
+use std::{
+    ops::Deref,
+    sync::{
+        mpsc::{self, Receiver},
+        Arc, Mutex, MutexGuard,
+    },
+    time::{Duration, Instant, SystemTime},
+};
+
+/// Whether a thermostat drives a heater or a cooler.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Mode {
+    /// The output is turned on when the temperature is below the band
+    Heating,
+    /// The output is turned on when the temperature is above the band
+    Cooling,
+}
+
+/// A change in the state of a thermostat's output.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ThermostatEvent {
+    /// Whether the output was turned on
+    pub on: bool,
+    /// The temperature that switched the output
+    pub temperature: f64,
+    /// The time of the switch
+    pub time: SystemTime,
+}
+
+// The control logic of a thermostat
+struct Control {
+    mode: Mode,
+    setpoint: f64,
+    hysteresis: f64,
+    min_on: Duration,
+    min_off: Duration,
+    on: bool,
+    // The time the output last switched, if it has
+    switched: Option<Instant>,
+}
+
+impl Control {
+    fn new(mode: Mode, setpoint: f64) -> Self {
+        Self {
+            mode,
+            setpoint,
+            hysteresis: 0.0,
+            min_on: Duration::ZERO,
+            min_off: Duration::ZERO,
+            on: false,
+            switched: None,
+        }
+    }
+
+    // Updates with a temperature, returning the new state of the output
+    // if it should switch.
+    fn update(&mut self, t: Instant, temp: f64) -> Option<bool> {
+        let half = self.hysteresis / 2.0;
+        let (lo, hi) = (self.setpoint - half, self.setpoint + half);
+        let want = match self.mode {
+            Mode::Heating if temp < lo => true,
+            Mode::Heating if temp > hi => false,
+            Mode::Cooling if temp > hi => true,
+            Mode::Cooling if temp < lo => false,
+            _ => self.on,
+        };
+        if want == self.on {
+            return None;
+        }
+
+        let min = if self.on { self.min_on } else { self.min_off };
+        if let Some(sw) = self.switched {
+            if t.saturating_duration_since(sw) < min {
+                return None;
+            }
+        }
+        self.on = want;
+        self.switched = Some(t);
+        Some(want)
+    }
+}
+
+// The state shared with the change handler
+struct State {
+    control: Control,
+    output: DigitalOutput,
+}
+
+type Shared = Arc<Mutex<State>>;
+
+// Locks the state.
+fn lock(state: &Shared) -> MutexGuard<'_, State> {
+    state.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+/// An on/off controller of a digital output, from a temperature sensor.
+///
+/// This dereferences to a `std::sync::mpsc::Receiver` for reading the
+/// events when the output is switched. When the thermostat is dropped,
+/// the sensor's change handler is removed and the output is turned off.
+pub struct Thermostat {
+    // The control logic and the output
+    state: Shared,
+    // Receives the switch events
+    rx: Receiver<ThermostatEvent>,
+    // The change handler of the sensor
+    guards: Vec<HandlerGuard>,
+}
+
+impl Thermostat {
+    /// Creates a thermostat that drives the output from the sensor, to
+    /// hold the temperature at the setpoint.
+    ///
+    /// The sensor and the output should be open and attached. The output
+    /// is turned off to start, and the thermostat owns it from here on.
+    /// This sets the sensor's change handler, replacing any handler that
+    /// was previously set. The hysteresis and the minimum times start at
+    /// zero.
+    pub fn new<S>(sensor: &S, mut output: DigitalOutput, mode: Mode, setpoint: f64) -> Result<Self>
+    where
+        S: Sensor,
+        S::Value: Into<f64>,
+    {
+        output.set_state(false)?;
+        let state: Shared = Arc::new(Mutex::new(State {
+            control: Control::new(mode, setpoint),
+            output,
+        }));
+        let (tx, rx) = mpsc::channel();
+
+        let guard = sensor.on_change({
+            let state = Arc::clone(&state);
+            move |_, val| {
+                let temperature = val.into();
+                let mut st = lock(&state);
+                let Some(on) = st.control.update(Instant::now(), temperature)
+                else {
+                    return;
+                };
+                // A failed output is still reported by its own error events
+                let _ = st.output.set_state(on);
+                let _ = tx.send(ThermostatEvent {
+                    on,
+                    temperature,
+                    time: SystemTime::now(),
+                });
+            }
+        })?;
+
+        Ok(Self {
+            state,
+            rx,
+            guards: vec![guard],
+        })
+    }
+
+    /// Gets the mode of the thermostat.
+    pub fn mode(&self) -> Mode {
+        lock(&self.state).control.mode
+    }
+
+    /// Gets the setpoint temperature.
+    pub fn setpoint(&self) -> f64 {
+        lock(&self.state).control.setpoint
+    }
+
+    /// Sets the setpoint temperature.
+    ///
+    /// This takes effect on the next reading from the sensor.
+    pub fn set_setpoint(&mut self, setpoint: f64) {
+        lock(&self.state).control.setpoint = setpoint;
+    }
+
+    /// Gets the width of the hysteresis band.
+    pub fn hysteresis(&self) -> f64 {
+        lock(&self.state).control.hysteresis
+    }
+
+    /// Sets the width of the hysteresis band, which is centred on the
+    /// setpoint.
+    ///
+    /// A heater is turned on below the band and off above it, and a
+    /// cooler the other way around.
+    pub fn set_hysteresis(&mut self, band: f64) {
+        lock(&self.state).control.hysteresis = band.abs();
+    }
+
+    /// Sets the minimum time that the output stays on once it's turned on.
+    pub fn set_min_on_time(&mut self, min: Duration) {
+        lock(&self.state).control.min_on = min;
+    }
+
+    /// Sets the minimum time that the output stays off once it's turned
+    /// off.
+    pub fn set_min_off_time(&mut self, min: Duration) {
+        lock(&self.state).control.min_off = min;
+    }
+
+    /// Determines if the output is currently on.
+    pub fn is_on(&self) -> bool {
+        lock(&self.state).control.on
+    }
+}
+
+impl Deref for Thermostat {
+    type Target = Receiver<ThermostatEvent>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.rx
+    }
+}
+
+impl Drop for Thermostat {
+    fn drop(&mut self) {
+        self.guards.clear();
+        let _ = lock(&self.state).output.set_state(false);
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_control() {
+        let mut c = Control::new(Mode::Heating, 20.0);
+        c.hysteresis = 2.0;
+        c.min_off = Duration::from_millis(100);
+        let t0 = Instant::now();
+        let ms = Duration::from_millis;
+
+        // Nothing happens within the band
+        assert_eq!(c.update(t0, 20.5), None);
+        assert_eq!(c.update(t0, 18.9), Some(true));
+        assert_eq!(c.update(t0 + ms(10), 20.9), None);
+        assert_eq!(c.update(t0 + ms(20), 21.1), Some(false));
+
+        // The output has to stay off for the minimum time
+        assert_eq!(c.update(t0 + ms(50), 18.0), None);
+        assert_eq!(c.update(t0 + ms(120), 18.0), Some(true));
+
+        let mut c = Control::new(Mode::Cooling, 4.0);
+        assert_eq!(c.update(t0, 4.1), Some(true));
+        assert_eq!(c.update(t0, 3.9), Some(false));
+    }
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod mock_tests {
+    use super::*;
+    use crate::{mock::set_value, Phidget, TemperatureSensor};
+
+    #[test]
+    fn test_mock_thermostat() {
+        let mut sensor = TemperatureSensor::new();
+        let mut heater = DigitalOutput::new();
+        sensor.open().unwrap();
+        heater.open().unwrap();
+
+        let handle = heater.as_handle() as phidget_sys::PhidgetDigitalOutputHandle;
+        let view = std::mem::ManuallyDrop::new(DigitalOutput::from(handle));
+
+        let mut thermostat = Thermostat::new(&sensor, heater, Mode::Heating, 55.0).unwrap();
+        thermostat.set_hysteresis(2.0);
+        assert_eq!(view.state(), Ok(false));
+
+        set_value(&mut sensor, 53.0).unwrap();
+        let ev = thermostat.try_recv().unwrap();
+        assert!(ev.on && thermostat.is_on());
+        assert_eq!(ev.temperature, 53.0);
+        assert_eq!(view.state(), Ok(true));
+
+        // Within the band, the heater stays on
+        set_value(&mut sensor, 55.5).unwrap();
+        assert!(thermostat.try_recv().is_err());
+
+        set_value(&mut sensor, 56.5).unwrap();
+        assert!(!thermostat.try_recv().unwrap().on);
+        assert_eq!(view.state(), Ok(false));
+    }
+}
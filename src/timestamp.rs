@@ -0,0 +1,84 @@
+// phidget-rs/src/timestamp.rs
+//
+// This file is part of the 'phidget-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+//! Host-side timestamps for channel events.
+//!
+//! The phidget22 callbacks don't say when an event happened, so the time
+//! has to be taken on the host as the event is delivered. A `Timestamped`
+//! value carries the reading along with the monotonic and the wall-clock
+//! times at which it arrived.
+//!
+//! Any sensor can deliver timestamped readings over a channel or a stream:
+//!
+//! ```text
+//! let rx = sensor.timestamped_channel()?;
+//! for reading in rx.iter() {
+//!     println!("{:?}: {}", reading.time, reading.value);
+//! }
+//! ```
+//!
+//! And any event handler can be wrapped to receive them:
+//!
+//! ```text
+//! let _handler = sensor.set_on_temperature_change_handler(timestamped(|_, t| {
+//!     println!("{:?}: {}", t.instant, t.value);
+//! }))?;
+//! ```
+//!
+
+use std::time::{Instant, SystemTime};
+
+/// A value with the host times at which it was received.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Timestamped<T> {
+    /// The value
+    pub value: T,
+    /// The monotonic time at which the value was received, for measuring
+    /// the time between events
+    pub instant: Instant,
+    /// The wall-clock time at which the value was received
+    pub time: SystemTime,
+}
+
+impl<T> Timestamped<T> {
+    /// Stamps the value with the current time.
+    pub fn now(value: T) -> Self {
+        Self {
+            value,
+            instant: Instant::now(),
+            time: SystemTime::now(),
+        }
+    }
+
+    /// Converts the value, keeping the timestamps.
+    pub fn map<U, F>(self, f: F) -> Timestamped<U>
+    where
+        F: FnOnce(T) -> U,
+    {
+        Timestamped {
+            value: f(self.value),
+            instant: self.instant,
+            time: self.time,
+        }
+    }
+}
+
+/// Wraps an event handler so that it receives each value with the time
+/// at which it arrived.
+///
+/// The time is taken as soon as the library delivers the event, before
+/// the handler is called.
+pub fn timestamped<C, T, F>(mut f: F) -> impl FnMut(&C, T) + Send + 'static
+where
+    C: ?Sized + 'static,
+    T: 'static,
+    F: FnMut(&C, Timestamped<T>) + Send + 'static,
+{
+    move |ch, val| f(ch, Timestamped::now(val))
+}
@@ -0,0 +1,70 @@
+// phidget-rs/src/trace.rs
+//
+// This file is part of the 'phidget-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+//! Tracing of the calls into the phidget22 library, and of the event
+//! callbacks.
+//!
+//! With the `tracing` feature, each library call on a channel is made in
+//! a `phidget_ffi` span, at the trace level, with fields for the name of
+//! the function (`func`), the address of the channel (`serial`,
+//! `hub_port`, `channel`), and the return code (`rc`). A call that fails
+//! also emits a debug event with the error, so that occasional failures,
+//! like timeouts, can be found without tracing every call.
+//!
+//! Each call to an application's event handler is made in a
+//! `phidget_callback` span, at the debug level, with the name of the
+//! event and the address of the channel.
+//!
+
+use crate::ReturnCode;
+use phidget_sys::PhidgetHandle;
+use std::os::raw::c_uint;
+use tracing::{field, Level, Span};
+
+/// Creates the span for a library call on a channel.
+///
+/// The address is only read if the span is enabled.
+pub(crate) fn ffi_span<T>(func: &'static str, phid: *mut T) -> Span {
+    if !tracing::enabled!(Level::TRACE) {
+        return Span::none();
+    }
+    let addr = crate::cache::address(phid.cast());
+    tracing::trace_span!(
+        "phidget_ffi",
+        func,
+        serial = addr.map(|a| a.serial_number),
+        hub_port = addr.map(|a| a.hub_port),
+        channel = addr.map(|a| a.channel),
+        rc = field::Empty,
+    )
+}
+
+/// Records the return code of a library call in its span, and reports
+/// a failure.
+pub(crate) fn ffi_result(span: &Span, rc: c_uint) {
+    span.record("rc", rc);
+    if rc != 0 {
+        tracing::debug!(parent: span, rc, error = ?ReturnCode::from(rc), "phidget22 call failed");
+    }
+}
+
+/// Creates the span for a call to an event handler.
+pub(crate) fn callback_span(event: &'static str, phid: PhidgetHandle) -> Span {
+    if !tracing::enabled!(Level::DEBUG) {
+        return Span::none();
+    }
+    let addr = crate::cache::address(phid);
+    tracing::debug_span!(
+        "phidget_callback",
+        event,
+        serial = addr.map(|a| a.serial_number),
+        hub_port = addr.map(|a| a.hub_port),
+        channel = addr.map(|a| a.channel),
+    )
+}
@@ -0,0 +1,529 @@
+// phidget-rs/src/websocket.rs
+//
+// This file is part of the 'phidget-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+//! Live sensor events over WebSockets.
+//!
+//! With the `websocket` feature, a `WebSocketHub` pushes the value changes
+//! of any number of sensors to the clients connected to its server, as
+//! JSON text messages, so that a browser dashboard can show them live
+//! without a separate bridge service:
+//!
+//! ```text
+//! let mut hub = WebSocketHub::new();
+//! hub.add_sensor("boiler", &boiler_temp)?;
+//! hub.add_sensor("flow", &flow_rate)?;
+//!
+//! let _server = hub.serve("0.0.0.0:8081")?;
+//! ```
+//!
+//! Each message is an event like:
+//!
+//! ```text
+//! {"name":"boiler","value":64.5,"time":1760512345.125}
+//! ```
+//!
+//! with the time in seconds since the Unix epoch. A client gets the
+//! events of all the sensors, or can subscribe to some of them by name in
+//! the query of the URL, like `ws://rig.local:8081/?channels=boiler,flow`.
+//!
+//! Messages from the clients, other than the handshake, are ignored, and
+//! one longer than `MAX_FRAME_LEN` closes the connection. Each client is
+//! written by its own thread, so a slow client doesn't hold up the others
+//! or the event handlers, but one that falls too far behind is dropped.
+//! Up to `MAX_CLIENTS` can be connected at once.
+//!
+
+use crate::{httpd, HandlerGuard, Result, Sensor};
+use std::{
+    io::{self, BufReader, Read, Write},
+    net::{Shutdown, SocketAddr, TcpStream, ToSocketAddrs},
+    sync::{
+        mpsc::{self, Receiver, SyncSender, TrySendError},
+        Arc, Mutex, MutexGuard,
+    },
+    thread,
+    time::SystemTime,
+};
+
+/// The number of messages that can be queued for a client before it's
+/// dropped as too slow.
+pub const CLIENT_QUEUE_LEN: usize = 256;
+
+/// The most clients that can be connected at once.
+pub const MAX_CLIENTS: usize = 64;
+
+/// The longest message that's accepted from a client.
+pub const MAX_FRAME_LEN: usize = 4096;
+
+// The GUID appended to the client's key for the handshake, from RFC 6455
+const HANDSHAKE_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+// A connected client
+struct Client {
+    // The names of the channels it subscribed to, or `None` for all
+    channels: Option<Vec<String>>,
+    // Queues the messages for the client's thread
+    tx: SyncSender<Arc<str>>,
+}
+
+impl Client {
+    fn wants(&self, name: &str) -> bool {
+        self.channels
+            .as_ref()
+            .map_or(true, |chans| chans.iter().any(|c| c == name))
+    }
+}
+
+type Clients = Arc<Mutex<Vec<Client>>>;
+
+// Locks the clients.
+fn lock(clients: &Clients) -> MutexGuard<'_, Vec<Client>> {
+    clients.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+// Sends an event to the clients that want it, dropping any that are gone
+// or too far behind.
+fn publish(clients: &Clients, name: &str, value: f64, time: SystemTime) {
+    let secs = time
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64();
+    let value = if value.is_finite() {
+        value.to_string()
+    }
+    else {
+        "null".into()
+    };
+    let msg: Arc<str> = format!(
+        "{{\"name\":{},\"value\":{},\"time\":{:.3}}}",
+        json_str(name),
+        value,
+        secs
+    )
+    .into();
+
+    lock(clients).retain(|c| {
+        !c.wants(name)
+            || match c.tx.try_send(Arc::clone(&msg)) {
+                Ok(()) => true,
+                Err(TrySendError::Full(_) | TrySendError::Disconnected(_)) => false,
+            }
+    });
+}
+
+/// A set of sensors whose events are pushed to WebSocket clients.
+///
+/// The sensors' change handlers are removed when the hub is dropped.
+#[derive(Default)]
+pub struct WebSocketHub {
+    // The connected clients, shared with the handlers and the server
+    clients: Clients,
+    // The handlers feeding the events
+    guards: Vec<HandlerGuard>,
+}
+
+impl WebSocketHub {
+    /// Creates a new hub with no sensors.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Pushes the value changes from the sensor to the clients, under the
+    /// name.
+    ///
+    /// This sets the sensor's change handler, replacing any handler that
+    /// was previously set. Boolean values are sent as 0 or 1.
+    pub fn add_sensor<S>(&mut self, name: &str, sensor: &S) -> Result<()>
+    where
+        S: Sensor,
+        S::Value: Into<f64>,
+    {
+        let clients = Arc::clone(&self.clients);
+        let name = name.to_string();
+        let guard = sensor.on_change(move |_, val| {
+            publish(&clients, &name, val.into(), SystemTime::now());
+        })?;
+        self.guards.push(guard);
+        Ok(())
+    }
+
+    /// Gets the number of clients currently connected.
+    pub fn client_count(&self) -> usize {
+        lock(&self.clients).len()
+    }
+
+    /// Starts serving WebSocket clients on the specified address.
+    ///
+    /// Each client is handled by background threads, which are stopped,
+    /// and the clients disconnected, when the returned server is dropped.
+    pub fn serve<A: ToSocketAddrs>(&self, addr: A) -> io::Result<WebSocketServer> {
+        let clients = Arc::clone(&self.clients);
+        let server = httpd::Server::start(addr, MAX_CLIENTS, move |stream| {
+            let client = accept(&stream)?;
+            lock(&clients).push(client);
+            // Idle clients are normal, so only the writes time out now
+            stream.set_read_timeout(None)?;
+            let res = read_client(&stream);
+            let _ = stream.shutdown(Shutdown::Both);
+            res
+        })?;
+
+        Ok(WebSocketServer {
+            server,
+            clients: Arc::clone(&self.clients),
+        })
+    }
+}
+
+/// A running WebSocket server for a `WebSocketHub`.
+///
+/// The server is stopped, and the hub's clients disconnected, when this
+/// is dropped.
+pub struct WebSocketServer {
+    server: httpd::Server,
+    clients: Clients,
+}
+
+impl WebSocketServer {
+    /// Gets the address that the server is listening on.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.server.local_addr()
+    }
+}
+
+impl Drop for WebSocketServer {
+    fn drop(&mut self) {
+        // Closing the queues closes the connections
+        lock(&self.clients).clear();
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+// Performs the handshake with a new connection, and starts the thread
+// that writes to it.
+fn accept(stream: &TcpStream) -> io::Result<Client> {
+    let req = httpd::read_request(&mut BufReader::new(stream))?;
+
+    let Some(key) = req.header("sec-websocket-key")
+    else {
+        httpd::write_response(stream, "400 Bad Request", "text/plain", "")?;
+        return Err(io::ErrorKind::InvalidData.into());
+    };
+
+    let channels = req
+        .query()
+        .and_then(|query| query.split('&').find_map(|kv| kv.strip_prefix("channels=")))
+        .map(|list| {
+            list.split(',')
+                .filter(|c| !c.is_empty())
+                .map(String::from)
+                .collect()
+        });
+
+    let mut stream = stream.try_clone()?;
+    write!(
+        stream,
+        "HTTP/1.1 101 Switching Protocols\r\n\
+         Upgrade: websocket\r\n\
+         Connection: Upgrade\r\n\
+         Sec-WebSocket-Accept: {}\r\n\r\n",
+        accept_key(key)
+    )?;
+    stream.flush()?;
+
+    let (tx, rx) = mpsc::sync_channel(CLIENT_QUEUE_LEN);
+    thread::spawn(move || write_client(stream, rx));
+    Ok(Client { channels, tx })
+}
+
+// Reads the frames from a client, discarding them, until it sends a
+// close frame or closes the connection. A frame that's longer than the
+// limit is an error.
+fn read_client<R: Read>(mut rdr: R) -> io::Result<()> {
+    loop {
+        let mut hdr = [0u8; 2];
+        rdr.read_exact(&mut hdr)?;
+        let len = match hdr[1] & 0x7F {
+            126 => {
+                let mut n = [0u8; 2];
+                rdr.read_exact(&mut n)?;
+                u64::from(u16::from_be_bytes(n))
+            }
+            127 => {
+                let mut n = [0u8; 8];
+                rdr.read_exact(&mut n)?;
+                u64::from_be_bytes(n)
+            }
+            n => u64::from(n),
+        };
+        if len > MAX_FRAME_LEN as u64 {
+            return Err(io::ErrorKind::InvalidData.into());
+        }
+        // The masking key, then the payload
+        let len = len + if hdr[1] & 0x80 != 0 { 4 } else { 0 };
+        if io::copy(&mut (&mut rdr).take(len), &mut io::sink())? < len {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        if hdr[0] & 0x0F == 0x8 {
+            return Ok(());
+        }
+    }
+}
+
+// Writes the queued messages to a client, until the queue is closed or
+// the client goes away.
+fn write_client(mut stream: TcpStream, rx: Receiver<Arc<str>>) {
+    for msg in rx.iter() {
+        if stream.write_all(&frame(0x1, msg.as_bytes())).is_err() {
+            return;
+        }
+    }
+    // A close frame, with no status
+    let _ = stream.write_all(&frame(0x8, &[]));
+    let _ = stream.shutdown(Shutdown::Both);
+}
+
+// Makes an unmasked, final frame with the opcode and the payload.
+fn frame(opcode: u8, payload: &[u8]) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(payload.len() + 10);
+    buf.push(0x80 | opcode);
+    match payload.len() {
+        n if n < 126 => buf.push(n as u8),
+        n if n <= 0xFFFF => {
+            buf.push(126);
+            buf.extend_from_slice(&(n as u16).to_be_bytes());
+        }
+        n => {
+            buf.push(127);
+            buf.extend_from_slice(&(n as u64).to_be_bytes());
+        }
+    }
+    buf.extend_from_slice(payload);
+    buf
+}
+
+// Quotes a string for JSON.
+fn json_str(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+// Gets the accept key for the handshake, from the client's key.
+fn accept_key(key: &str) -> String {
+    base64(&sha1(format!("{}{}", key, HANDSHAKE_GUID).as_bytes()))
+}
+
+// The SHA-1 hash of the data, which the handshake requires.
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+
+    let mut msg = data.to_vec();
+    msg.push(0x80);
+    while msg.len() % 64 != 56 {
+        msg.push(0);
+    }
+    msg.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+
+    for block in msg.chunks(64) {
+        let mut w = [0u32; 80];
+        for (i, word) in block.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (i, wi) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A827999),
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+            let t = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(*wi);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = t;
+        }
+        for (hi, x) in h.iter_mut().zip([a, b, c, d, e]) {
+            *hi = hi.wrapping_add(x);
+        }
+    }
+
+    let mut out = [0u8; 20];
+    for (chunk, hi) in out.chunks_mut(4).zip(h) {
+        chunk.copy_from_slice(&hi.to_be_bytes());
+    }
+    out
+}
+
+// Encodes the data in standard, padded base64.
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let b = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = u32::from(b[0]) << 16 | u32::from(b[1]) << 8 | u32::from(b[2]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3F) as usize] as char);
+            }
+            else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{io::BufRead, time::Duration};
+
+    fn hex(data: &[u8]) -> String {
+        data.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    #[test]
+    fn test_handshake_key() {
+        // The example from RFC 6455
+        assert_eq!(
+            accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+        assert_eq!(frame(0x1, &[0; 300])[..4], [0x81, 126, 1, 44]);
+    }
+
+    #[test]
+    fn test_sha1() {
+        // The test vectors from FIPS 180
+        assert_eq!(hex(&sha1(b"")), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+        assert_eq!(
+            hex(&sha1(b"abc")),
+            "a9993e364706816aba3e25717850c26c9cd0d89d"
+        );
+        assert_eq!(
+            hex(&sha1(
+                b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"
+            )),
+            "84983e441c3bd26ebaae4aa1f95129e5e54670f1"
+        );
+        assert_eq!(
+            hex(&sha1(&[b'a'; 1_000_000])),
+            "34aa973cd4c4daa4f61eeb2bdbad27316534016f"
+        );
+    }
+
+    #[test]
+    fn test_base64() {
+        // The test vectors from RFC 4648
+        let vectors = [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ];
+        for (data, encoded) in vectors {
+            assert_eq!(base64(data.as_bytes()), encoded);
+        }
+    }
+
+    #[test]
+    fn test_read_client() {
+        // A masked text frame, then a close frame
+        let mut data = vec![0x81, 0x85, 1, 2, 3, 4];
+        data.extend_from_slice(b"hello");
+        data.extend_from_slice(&[0x88, 0x80, 1, 2, 3, 4]);
+        assert!(read_client(&data[..]).is_ok());
+
+        // A frame that's too long is refused before it's read
+        let mut data = vec![0x82, 0xFF];
+        data.extend_from_slice(&(1u64 << 40).to_be_bytes());
+        let err = read_client(&data[..]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_serve() {
+        let hub = WebSocketHub::new();
+        let server = hub.serve("127.0.0.1:0").unwrap();
+
+        let mut stream = TcpStream::connect(server.local_addr()).unwrap();
+        stream
+            .write_all(
+                b"GET /?channels=boiler HTTP/1.1\r\n\
+                  Host: localhost\r\n\
+                  Upgrade: websocket\r\n\
+                  Connection: Upgrade\r\n\
+                  Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+                  Sec-WebSocket-Version: 13\r\n\r\n",
+            )
+            .unwrap();
+
+        let mut rdr = BufReader::new(stream);
+        let mut resp = String::new();
+        while !resp.ends_with("\r\n\r\n") {
+            rdr.read_line(&mut resp).unwrap();
+        }
+        assert!(resp.starts_with("HTTP/1.1 101 Switching Protocols\r\n"));
+        assert!(resp.contains("Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n"));
+
+        while hub.client_count() == 0 {
+            thread::sleep(Duration::from_millis(5));
+        }
+        let time = SystemTime::UNIX_EPOCH + Duration::from_millis(1500);
+        publish(&hub.clients, "flow", 2.0, time);
+        publish(&hub.clients, "boiler", 64.5, time);
+
+        // Only the subscribed channel comes through
+        let mut hdr = [0u8; 2];
+        rdr.read_exact(&mut hdr).unwrap();
+        let mut msg = vec![0u8; usize::from(hdr[1])];
+        rdr.read_exact(&mut msg).unwrap();
+        assert_eq!(hdr[0], 0x81);
+        assert_eq!(
+            String::from_utf8(msg).unwrap(),
+            "{\"name\":\"boiler\",\"value\":64.5,\"time\":1.500}"
+        );
+
+        // And the server closes the connection when it's dropped
+        drop(server);
+        rdr.read_exact(&mut hdr).unwrap();
+        assert_eq!(hdr, [0x88, 0]);
+    }
+}